@@ -0,0 +1,39 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader FullscreenVertexOutput
+
+@group(0) @binding(0)
+var screen_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var texture_sampler: sampler;
+
+struct VisionBlur {
+    intensity: f32,
+    // WebGL2 structs must be 16 byte aligned
+    _padding: vec3<f32>,
+}
+@group(0) @binding(2)
+var<uniform> settings: VisionBlur;
+
+// Maximum sampling distance in pixels at full intensity
+const MAX_RADIUS: f32 = 6.0;
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(screen_texture));
+    let radius = settings.intensity * MAX_RADIUS;
+
+    // 5x5 box blur, spread out by intensity
+    var color = vec3<f32>(0.0);
+    for (var x = -2; x <= 2; x++) {
+        for (var y = -2; y <= 2; y++) {
+            let offset = vec2<f32>(f32(x), f32(y)) * 0.5 * radius * texel;
+            color += textureSample(screen_texture, texture_sampler, in.uv + offset).rgb;
+        }
+    }
+    color /= 25.0;
+
+    // Darken the edges of the screen
+    let distance = length(in.uv - vec2<f32>(0.5));
+    let vignette = 1.0 - smoothstep(0.3, 0.75, distance) * settings.intensity * 0.7;
+
+    return vec4<f32>(color * vignette, 1.0);
+}
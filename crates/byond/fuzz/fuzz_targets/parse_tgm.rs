@@ -0,0 +1,14 @@
+#![no_main]
+
+use byond::tgm::{conversion::to_map_data, parse_map};
+use libfuzzer_sys::fuzz_target;
+
+// Map files are downloaded from untrusted sources, so nothing here may panic
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    if let Ok(map) = parse_map(text) {
+        to_map_data(&map);
+    }
+});
@@ -1,7 +1,7 @@
 use bevy::{asset::AssetPathId, math::UVec2, utils::HashMap};
 
 use super::{Tile, TileMap, Value};
-use maps::{Direction, TileData, TileMapData, DIRECTIONS};
+use maps::{Direction, LootSpawnPoint, TileData, TileMapData, DIRECTIONS};
 
 pub fn to_map_data(tilemap: &TileMap) -> TileMapData {
     let size = tilemap.size();
@@ -9,6 +9,7 @@ pub fn to_map_data(tilemap: &TileMap) -> TileMapData {
     let mut temporary_tiles = Vec::new();
     temporary_tiles.resize_with(size.x as usize * size.y as usize, Default::default);
     let mut job_spawns = HashMap::<String, Vec<UVec2>>::default();
+    let mut loot_spawns = Vec::new();
 
     // Loop through all positions and convert the tile format
     for (position, &definition_index) in tilemap.tiles.iter() {
@@ -33,6 +34,30 @@ pub fn to_map_data(tilemap: &TileMap) -> TileMapData {
                 .or_default()
                 .push(UVec2::new(position.x, position.z));
         }
+
+        // Find loot spawners on tile
+        for path in definition
+            .components
+            .iter()
+            .filter_map(|c| c.path.strip_prefix("/obj/effect/spawner/lootdrop/"))
+        {
+            // Some spawners have a variant with multiple rolls (ex. "maintenance/three")
+            let (table, rolls) = match path.split_once('/') {
+                Some((table, "two")) => (table, 2),
+                Some((table, "three")) => (table, 3),
+                Some((table, "four")) => (table, 4),
+                Some((table, _)) => (table, 1),
+                None => (path, 1),
+            };
+            if table.is_empty() {
+                continue;
+            }
+            loot_spawns.push(LootSpawnPoint {
+                position: UVec2::new(position.x, position.z),
+                table: table.to_owned(),
+                rolls,
+            });
+        }
     }
 
     for index in 0..temporary_tiles.len() {
@@ -47,13 +72,13 @@ pub fn to_map_data(tilemap: &TileMap) -> TileMapData {
 
             let direction = DIRECTIONS[mount_index];
             let target_index = match direction {
-                Direction::North => index - size.x as usize,
-                Direction::East => index + 1,
-                Direction::South => index + size.x as usize,
-                Direction::West => index - 1,
+                Direction::North => index.checked_sub(size.x as usize),
+                Direction::East => Some(index + 1),
+                Direction::South => Some(index + size.x as usize),
+                Direction::West => index.checked_sub(1),
             };
 
-            let Some(target_tile) = temporary_tiles.get_mut(target_index) else {
+            let Some(target_tile) = target_index.and_then(|i| temporary_tiles.get_mut(i)) else {
                 continue;
             };
 
@@ -71,18 +96,33 @@ pub fn to_map_data(tilemap: &TileMap) -> TileMapData {
             .map(|t| t.unwrap_or_default())
             .collect(),
         job_spawn_positions: job_spawns,
+        loot_spawns,
     }
 }
 
 fn tile_to_data(tile: &Tile) -> TileData {
+    let (furniture, furniture_direction) = get_furniture(tile).unzip();
+    let turf_name = get_turf_name(tile);
+    // Floors are placed on top of plating, so they can be pried up
+    let has_plating = turf_name
+        .map(|name| name == "plating" || name.ends_with("floor"))
+        .unwrap_or(false);
     TileData {
-        turf: get_turf_path(tile),
-        furniture: get_furniture_path(tile),
+        underfloor: has_plating.then(|| turf_path("plating")),
+        turf: turf_name.filter(|&name| name != "plating").map(turf_path),
+        furniture,
+        furniture_direction: furniture_direction.unwrap_or_default(),
         high_mounts: get_high_mounts_path(tile),
+        cable: tile
+            .components
+            .iter()
+            .any(|o| o.path.starts_with("/obj/structure/cable"))
+            .then(|| "tilemap/power/cable.scn.ron".into()),
+        state: Vec::new(),
     }
 }
 
-fn get_turf_path(tile: &Tile) -> Option<AssetPathId> {
+fn get_turf_name(tile: &Tile) -> Option<&'static str> {
     let turf_name = tile
         .components
         .iter()
@@ -117,19 +157,19 @@ fn get_turf_path(tile: &Tile) -> Option<AssetPathId> {
         .max_by_key(|x| x.0)?
         .1;
 
-    Some(
-        format!("tilemap/turfs/{}.scn.ron", turf_name)
-            .as_str()
-            .into(),
-    )
+    Some(turf_name)
+}
+
+fn turf_path(name: &str) -> AssetPathId {
+    format!("tilemap/turfs/{}.scn.ron", name).as_str().into()
 }
 
-fn get_furniture_path(tile: &Tile) -> Option<AssetPathId> {
-    let furniture_name = tile
+fn get_furniture(tile: &Tile) -> Option<(AssetPathId, Direction)> {
+    let (object, furniture_name) = tile
         .components
         .iter()
         .filter_map(|o| {
-            if o.path.contains("door/airlock") {
+            let name = if o.path.contains("door/airlock") {
                 if o.path.contains("maintenance") {
                     Some("airlock maintenance")
                 } else if o.path.contains("command") {
@@ -153,17 +193,32 @@ fn get_furniture_path(tile: &Tile) -> Option<AssetPathId> {
                 Some("table")
             } else if o.path.starts_with("/obj/structure/chair") {
                 Some("chair")
+            } else if o.path.starts_with("/obj/machinery/power/smes") {
+                Some("generator")
+            } else if o
+                .path
+                .starts_with("/obj/structure/reagent_dispensers/fueltank")
+            {
+                Some("fuel_tank")
             } else {
                 None
-            }
+            };
+            name.map(|n| (o, n))
         })
         .next()?;
 
-    Some(
+    let direction = match object.variable("dir") {
+        Some(Value::Number(dir)) => Direction::from_byond(*dir as u8),
+        _ => None,
+    }
+    .unwrap_or_default();
+
+    Some((
         format!("tilemap/furniture/{}.scn.ron", furniture_name)
             .as_str()
             .into(),
-    )
+        direction,
+    ))
 }
 
 fn get_high_mounts_path(tile: &Tile) -> [Option<AssetPathId>; 4] {
@@ -175,6 +230,8 @@ fn get_high_mounts_path(tile: &Tile) -> [Option<AssetPathId>; 4] {
         .filter_map(|o| {
             match o.path.as_str() {
                 "/obj/machinery/light" => Some("light_tube"),
+                p if p.starts_with("/obj/machinery/light/small") => Some("light_bulb"),
+                p if p.starts_with("/obj/machinery/power/apc") => Some("apc"),
                 _ => None,
             }
             .map(|n| (o, n))
@@ -4,6 +4,7 @@ use bevy::{
     reflect::{TypePath, TypeUuid},
     utils::HashMap,
 };
+use nom::error::VerboseErrorKind;
 
 pub mod conversion;
 mod loader;
@@ -20,6 +21,119 @@ impl Plugin for TgmPlugin {
     }
 }
 
+/// Why a map file could not be read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TgmError {
+    /// The header comment line is missing
+    MissingHeader,
+    /// The file ended in the middle of the map
+    UnexpectedEnd,
+    /// Invalid syntax, with what was being parsed from innermost to outermost
+    Syntax { line: usize, expected: Vec<String> },
+    /// Values are nested deeper than the parser allows
+    NestingTooDeep { line: usize },
+    /// A tile is outside of the largest supported map
+    CoordinateOutOfRange { line: usize },
+    /// There is text after the map data
+    TrailingData { line: usize },
+}
+
+impl std::fmt::Display for TgmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TgmError::MissingHeader => write!(f, "Missing header line"),
+            TgmError::UnexpectedEnd => write!(f, "Unexpected end of file"),
+            TgmError::Syntax { line, expected } => {
+                write!(f, "Invalid syntax on line {}", line)?;
+                if !expected.is_empty() {
+                    write!(f, ", expected {}", expected.join(" in "))?;
+                }
+                Ok(())
+            }
+            TgmError::NestingTooDeep { line } => {
+                write!(f, "Values nested too deep on line {}", line)
+            }
+            TgmError::CoordinateOutOfRange { line } => write!(
+                f,
+                "Coordinates on line {} are outside of the supported map size of {}",
+                line,
+                parsing::MAX_COORDINATE
+            ),
+            TgmError::TrailingData { line } => {
+                write!(f, "Unexpected data after the map on line {}", line)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TgmError {}
+
+/// Parses the text of a TGM map file, including the header line
+pub fn parse_map(text: &str) -> Result<TileMap, TgmError> {
+    // Every parsed string is a slice of the text
+    let line_of = |part: &str| {
+        let offset = (part.as_ptr() as usize).saturating_sub(text.as_ptr() as usize);
+        text[..offset.min(text.len())].matches('\n').count() + 1
+    };
+
+    let header_end = text.find('\n').ok_or(TgmError::MissingHeader)?;
+    let (remaining, (definitions, chunks)) =
+        parsing::parse(&text[header_end..]).map_err(|err| match err {
+            nom::Err::Incomplete(_) => TgmError::UnexpectedEnd,
+            nom::Err::Error(e) | nom::Err::Failure(e) => {
+                let Some(&(rest, _)) = e.errors.first() else {
+                    return TgmError::Syntax {
+                        line: 0,
+                        expected: Vec::new(),
+                    };
+                };
+                let line = line_of(rest);
+                let contexts: Vec<_> = e
+                    .errors
+                    .iter()
+                    .filter_map(|(_, kind)| match kind {
+                        VerboseErrorKind::Context(context) => Some(*context),
+                        _ => None,
+                    })
+                    .collect();
+                if contexts.contains(&parsing::NESTING_TOO_DEEP) {
+                    TgmError::NestingTooDeep { line }
+                } else if contexts.contains(&parsing::COORDINATE_OUT_OF_RANGE) {
+                    TgmError::CoordinateOutOfRange { line }
+                } else if rest.is_empty() {
+                    TgmError::UnexpectedEnd
+                } else {
+                    TgmError::Syntax {
+                        line,
+                        expected: contexts.into_iter().map(String::from).collect(),
+                    }
+                }
+            }
+        })?;
+    if !remaining.trim().is_empty() {
+        return Err(TgmError::TrailingData {
+            line: line_of(remaining),
+        });
+    }
+
+    let mut positions = Vec::new();
+    for (start, rows) in chunks.iter().rev() {
+        for (offset, row) in rows.split('\n').rev().enumerate() {
+            let z = u32::try_from(offset)
+                .ok()
+                .and_then(|offset| start.z.checked_add(offset))
+                .filter(|&z| z <= parsing::MAX_COORDINATE)
+                .ok_or(TgmError::CoordinateOutOfRange {
+                    line: line_of(rows),
+                })?;
+            let row = row.strip_suffix('\r').unwrap_or(row);
+            positions.push((UVec3::new(start.x, start.y, z), row));
+        }
+    }
+
+    Ok(TileMap::new(definitions, positions))
+}
+
 #[derive(Clone, TypeUuid, TypePath)]
 #[uuid = "b4bcacfa-c562-432a-807a-43a2974cc2d6"]
 pub struct TileMap {
@@ -17,6 +17,21 @@ type IResult<I, O, E = VerboseError<I>> = nom::IResult<I, O, E>;
 
 type MapParseResult<'a> = IResult<&'a str, (Vec<(&'a str, Tile)>, Vec<(UVec3, &'a str)>)>;
 
+/// How deep values can be nested in lists and objects.
+/// Keeps malicious maps from overflowing the stack.
+const MAX_DEPTH: usize = 32;
+/// Largest allowed tile coordinate on any axis
+pub const MAX_COORDINATE: u32 = 2048;
+
+pub(super) const NESTING_TOO_DEEP: &str = "nesting too deep";
+pub(super) const COORDINATE_OUT_OF_RANGE: &str = "coordinate out of range";
+
+fn failure<'a, O>(input: &'a str, message: &'static str) -> IResult<&'a str, O> {
+    Err(nom::Err::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(message))],
+    }))
+}
+
 pub fn parse(input: &str) -> MapParseResult {
     pair(ws(tile_definitions), ws(chunk_definitions))(input)
 }
@@ -33,15 +48,12 @@ fn chunk_definition(input: &str) -> IResult<&str, (UVec3, &str)> {
     .and_then(|(remaining, (floats, name))| {
         let (x, y, z) = match &floats[..] {
             &[x, y, z] => (x, y, z),
-            _ => {
-                return Err(nom::Err::Failure(VerboseError {
-                    errors: vec![(
-                        input,
-                        VerboseErrorKind::Context("Chunk must have three coordinates"),
-                    )],
-                }))
-            }
+            _ => return failure(input, "Chunk must have three coordinates"),
         };
+        let valid = |c: f64| c.is_finite() && (0.0..=MAX_COORDINATE as f64).contains(&c);
+        if !(valid(x) && valid(y) && valid(z)) {
+            return failure(input, COORDINATE_OUT_OF_RANGE);
+        }
         Ok((remaining, (UVec3::new(x as u32, y as u32, z as u32), name)))
     })
 }
@@ -59,8 +71,11 @@ pub fn tile_definitions(input: &str) -> IResult<&str, Vec<(&str, Tile)>> {
 }
 
 fn tile_definition(input: &str) -> IResult<&str, (&str, Tile)> {
-    context("tile definition", assignment(string, list(object)))(input)
-        .map(|(i, (n, tiles))| (i, (n, tiles.into())))
+    context(
+        "tile definition",
+        assignment(string, list(|i| object(i, 0))),
+    )(input)
+    .map(|(i, (n, tiles))| (i, (n, tiles.into())))
 }
 
 fn path(input: &str) -> IResult<&str, &str> {
@@ -70,19 +85,19 @@ fn path(input: &str) -> IResult<&str, &str> {
     )(input)
 }
 
-fn variable(input: &str) -> IResult<&str, Variable> {
-    context("variable", assignment(identifier, value))(input)
+fn variable(input: &str, depth: usize) -> IResult<&str, Variable> {
+    context("variable", assignment(identifier, |i| value(i, depth)))(input)
         .map(|(i, (ident, val))| (i, Variable::new(ident, val)))
 }
 
-fn object(input: &str) -> IResult<&str, Object> {
+fn object(input: &str, depth: usize) -> IResult<&str, Object> {
     context(
         "object",
         pair(
             path,
             opt(delimited(
                 tag("{"),
-                ws(separated_list0(tag(";"), ws(variable))),
+                ws(separated_list0(tag(";"), ws(|i| variable(i, depth)))),
                 tag("}"),
             )),
         ),
@@ -98,15 +113,19 @@ fn string(input: &str) -> IResult<&str, &str> {
     ))(input)
 }
 
-fn value(input: &str) -> IResult<&str, Value> {
+fn value(input: &str, depth: usize) -> IResult<&str, Value> {
+    if depth >= MAX_DEPTH {
+        return failure(input, NESTING_TOO_DEEP);
+    }
+    let depth = depth + 1;
     context(
         "value",
         alt((
             value_parser(number),
             value_parser(string),
-            value_parser(object),
-            value_parser(list(value)),
-            value_parser(map(alt((string, path)), value)),
+            value_parser(|i| object(i, depth)),
+            value_parser(list(|i| value(i, depth))),
+            value_parser(map(alt((string, path)), |i| value(i, depth))),
             null,
         )),
     )(input)
@@ -0,0 +1,180 @@
+use bevy::math::UVec3;
+use byond::tgm::{conversion::to_map_data, parse_map, parsing::MAX_COORDINATE, TgmError};
+use proptest::prelude::*;
+
+const HEADER: &str =
+    "//MAP CONVERTED BY dmm2tgm.py THIS HEADER COMMENT PREVENTS RECONVERSION, DO NOT REMOVE\n";
+
+/// A generated map with a single column of tiles
+#[derive(Debug, Clone)]
+struct GeneratedMap {
+    /// Object paths of each tile definition
+    definitions: Vec<Vec<String>>,
+    /// Definition index of each row
+    rows: Vec<usize>,
+}
+
+impl GeneratedMap {
+    fn key(index: usize) -> String {
+        let letters = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+        let first = letters[index / letters.len()] as char;
+        let second = letters[index % letters.len()] as char;
+        format!("{}{}", first, second)
+    }
+
+    fn to_tgm(&self) -> String {
+        let mut text = HEADER.to_string();
+        for (index, objects) in self.definitions.iter().enumerate() {
+            let objects: Vec<_> = objects
+                .iter()
+                .map(|path| format!("{}{{\n\tdir = 4;\n\tname = \"thing\"\n\t}}", path))
+                .collect();
+            text.push_str(&format!(
+                "\"{}\" = (\n{})\n",
+                Self::key(index),
+                objects.join(",\n")
+            ));
+        }
+        text.push_str("\n(1,1,1) = {\"\n");
+        for &row in self.rows.iter() {
+            text.push_str(&Self::key(row));
+            text.push('\n');
+        }
+        text.push_str("\"}\n");
+        text
+    }
+}
+
+fn object_path() -> impl Strategy<Value = String> {
+    prop::collection::vec("[a-z_]{1,8}", 1..5).prop_map(|parts| format!("/{}", parts.join("/")))
+}
+
+fn generated_map() -> impl Strategy<Value = GeneratedMap> {
+    prop::collection::vec(prop::collection::vec(object_path(), 1..4), 1..20).prop_flat_map(
+        |definitions| {
+            let count = definitions.len();
+            (Just(definitions), prop::collection::vec(0..count, 1..50))
+                .prop_map(|(definitions, rows)| GeneratedMap { definitions, rows })
+        },
+    )
+}
+
+/// A valid map with a variable nested `depth` lists deep
+fn nested_map(depth: usize) -> String {
+    format!(
+        "{}\"aa\" = (/obj{{x = {}1{}}})\n(1,1,1) = {{\"\naa\n\"}}\n",
+        HEADER,
+        "list(".repeat(depth),
+        ")".repeat(depth)
+    )
+}
+
+proptest! {
+    #[test]
+    fn arbitrary_text_does_not_panic(text in any::<String>()) {
+        let _ = parse_map(&text);
+        let _ = parse_map(&format!("{}{}", HEADER, text));
+    }
+
+    #[test]
+    fn generated_maps_parse(map in generated_map()) {
+        let parsed = parse_map(&map.to_tgm()).unwrap();
+        let row_count = map.rows.len() as u32;
+        for (offset, &definition) in map.rows.iter().rev().enumerate() {
+            // The chunk string starts and ends with an empty row
+            let position = UVec3::new(1, 1, 2 + offset as u32);
+            let tile = parsed.get_tile(position).unwrap();
+            let paths: Vec<_> = tile.components.iter().map(|o| o.path.clone()).collect();
+            prop_assert_eq!(&paths, &map.definitions[definition]);
+        }
+        prop_assert!(parsed.get_tile(UVec3::new(1, 1, 2 + row_count)).is_none());
+        to_map_data(&parsed);
+    }
+
+    #[test]
+    fn truncated_maps_do_not_panic(map in generated_map(), cut in any::<prop::sample::Index>()) {
+        let text = map.to_tgm();
+        let _ = parse_map(&text[..cut.index(text.len())]);
+    }
+
+    #[test]
+    fn corrupted_maps_do_not_panic(
+        map in generated_map(),
+        changes in prop::collection::vec((any::<prop::sample::Index>(), any::<char>()), 1..8),
+    ) {
+        let mut chars: Vec<char> = map.to_tgm().chars().collect();
+        for (index, replacement) in changes {
+            let index = index.index(chars.len());
+            chars[index] = replacement;
+        }
+        let text: String = chars.into_iter().collect();
+        if let Ok(parsed) = parse_map(&text) {
+            to_map_data(&parsed);
+        }
+    }
+
+    #[test]
+    fn deep_nesting_is_rejected(depth in 40usize..5000) {
+        let result = parse_map(&nested_map(depth));
+        prop_assert!(matches!(result, Err(TgmError::NestingTooDeep { .. })), "{:?}", result.err());
+    }
+
+    #[test]
+    fn huge_coordinates_are_rejected(
+        coordinate in (MAX_COORDINATE as u64 + 1)..u64::MAX,
+        axis in 0usize..3,
+    ) {
+        let mut coordinates = ["1".to_string(), "1".to_string(), "1".to_string()];
+        coordinates[axis] = coordinate.to_string();
+        let text = format!(
+            "{}\"aa\" = (/turf)\n({}) = {{\"\naa\n\"}}\n",
+            HEADER,
+            coordinates.join(",")
+        );
+        let result = parse_map(&text);
+        prop_assert!(matches!(result, Err(TgmError::CoordinateOutOfRange { .. })), "{:?}", result.err());
+    }
+}
+
+#[test]
+fn shallow_nesting_is_accepted() {
+    assert!(parse_map(&nested_map(8)).is_ok());
+}
+
+#[test]
+fn special_coordinates_are_rejected() {
+    for coordinate in ["-1", "nan", "inf", "1e30"] {
+        let text = format!(
+            "{}\"aa\" = (/turf)\n({},1,1) = {{\"\naa\n\"}}\n",
+            HEADER, coordinate
+        );
+        assert!(
+            matches!(
+                parse_map(&text),
+                Err(TgmError::CoordinateOutOfRange { .. }) | Err(TgmError::Syntax { .. })
+            ),
+            "{}",
+            coordinate
+        );
+    }
+}
+
+#[test]
+fn missing_header_is_reported() {
+    assert_eq!(
+        parse_map("\"aa\" = (/turf)").err(),
+        Some(TgmError::MissingHeader)
+    );
+}
+
+#[test]
+fn trailing_data_is_reported() {
+    let text = format!(
+        "{}\"aa\" = (/turf)\n(1,1,1) = {{\"\naa\n\"}}\ngarbage",
+        HEADER
+    );
+    assert_eq!(
+        parse_map(&text).err(),
+        Some(TgmError::TrailingData { line: 6 })
+    );
+}
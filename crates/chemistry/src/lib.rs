@@ -0,0 +1,299 @@
+//! Chemistry: containers hold mixtures of reagents, which react with each other
+//! and are metabolized by bodies.
+//!
+//! Chemicals and reactions are defined in `assets/chemistry`.
+
+use std::collections::HashMap as StdHashMap;
+
+use bevy::{
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    utils::HashMap,
+};
+use bevy_common_assets::ron::RonAssetPlugin;
+use networking::{
+    is_server,
+    time::{NetworkTick, TickTime},
+};
+use serde::Deserialize;
+
+pub struct ChemistryPlugin;
+
+impl Plugin for ChemistryPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<ReagentContainer>()
+            .register_type::<Metabolizer>();
+
+        if is_server(app) {
+            app.add_plugins((
+                RonAssetPlugin::<Chemical>::new(&["chem.ron"]),
+                RonAssetPlugin::<Reaction>::new(&["reaction.ron"]),
+            ))
+            .add_event::<Metabolized>()
+            .add_systems(Startup, load_chemistry)
+            .add_systems(NetworkTick, (react_reagents, metabolize).chain());
+        }
+    }
+}
+
+/// Amounts below this are treated as empty, so mixtures don't fill up with rounding errors
+const MIN_AMOUNT: f32 = 0.01;
+/// Reaction passes per container and frame, in case products keep reacting
+const MAX_REACTION_STEPS: usize = 8;
+
+/// A substance that can be part of a mixture.
+#[derive(Deserialize, TypeUuid, TypePath)]
+#[uuid = "2d7c9e4a-81f3-4b6e-a5d0-3f9b1c6e8a42"]
+pub struct Chemical {
+    pub id: String,
+    pub name: String,
+    /// Units metabolized per second
+    #[serde(default = "default_metabolism_rate")]
+    pub metabolism_rate: f32,
+    /// What happens to a body metabolizing the chemical
+    #[serde(default)]
+    pub effects: Vec<ChemicalEffect>,
+}
+
+fn default_metabolism_rate() -> f32 {
+    0.2
+}
+
+/// Effect of a metabolized chemical, with its strength per unit.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ChemicalEffect {
+    /// Restores integrity of body parts
+    Heal(f32),
+    /// Damages body parts
+    Toxin(f32),
+    /// Adds liters of oxygen to the blood
+    Oxygenate(f32),
+    /// Adds seconds during which foreign organs aren't rejected
+    Immunosuppress(f32),
+}
+
+/// Turns reagents into products when all of them are in the same container.
+/// Amounts are ratios, the reaction uses up as much of the reagents as possible.
+#[derive(Deserialize, TypeUuid, TypePath)]
+#[uuid = "9a4e1f7b-3c52-4d08-b6e9-71d2a5c8f034"]
+pub struct Reaction {
+    pub id: String,
+    pub reagents: StdHashMap<String, f32>,
+    pub products: StdHashMap<String, f32>,
+}
+
+impl Reaction {
+    /// How many times the ratio of reagents is present in the container
+    fn scale(&self, container: &ReagentContainer) -> f32 {
+        self.reagents
+            .iter()
+            .map(|(id, ratio)| container.amount(id) / ratio)
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    pub fn reacts_with(&self, container: &ReagentContainer) -> bool {
+        let scale = self.scale(container);
+        scale.is_finite() && scale >= MIN_AMOUNT
+    }
+
+    /// Runs the reaction in the container. Returns false if it didn't react.
+    pub fn apply(&self, container: &mut ReagentContainer) -> bool {
+        if !self.reacts_with(container) {
+            return false;
+        }
+
+        let scale = self.scale(container);
+        for (id, ratio) in self.reagents.iter() {
+            container.remove(id, ratio * scale);
+        }
+        for (id, ratio) in self.products.iter() {
+            container.add(id, ratio * scale);
+        }
+        true
+    }
+}
+
+#[derive(Resource)]
+pub struct ChemistryAssets {
+    chemicals: Vec<Handle<Chemical>>,
+    reactions: Vec<Handle<Reaction>>,
+}
+
+impl ChemistryAssets {
+    pub fn chemical<'a>(&self, assets: &'a Assets<Chemical>, id: &str) -> Option<&'a Chemical> {
+        self.chemicals
+            .iter()
+            .filter_map(|handle| assets.get(handle))
+            .find(|chemical| chemical.id == id)
+    }
+}
+
+fn load_chemistry(mut commands: Commands, server: ResMut<AssetServer>) {
+    let assets = ChemistryAssets {
+        chemicals: server
+            .load_folder("chemistry/chemicals")
+            .expect("assets/chemistry/chemicals is missing")
+            .into_iter()
+            .map(HandleUntyped::typed)
+            .collect(),
+        reactions: server
+            .load_folder("chemistry/reactions")
+            .expect("assets/chemistry/reactions is missing")
+            .into_iter()
+            .map(HandleUntyped::typed)
+            .collect(),
+    };
+    commands.insert_resource(assets);
+}
+
+/// Holds a mixture of reagents, like a beaker or the bloodstream of a body.
+#[derive(Component, Reflect, Default, Clone, Debug)]
+#[reflect(Component)]
+pub struct ReagentContainer {
+    /// Maximum volume in units
+    pub capacity: f32,
+    /// Units of each reagent, by chemical id
+    reagents: HashMap<String, f32>,
+}
+
+impl ReagentContainer {
+    pub fn new(capacity: f32) -> Self {
+        Self {
+            capacity,
+            reagents: Default::default(),
+        }
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.reagents.values().sum()
+    }
+
+    pub fn free_space(&self) -> f32 {
+        (self.capacity - self.volume()).max(0.0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.reagents.is_empty()
+    }
+
+    pub fn amount(&self, id: &str) -> f32 {
+        self.reagents.get(id).copied().unwrap_or(0.0)
+    }
+
+    pub fn reagents(&self) -> impl Iterator<Item = (&str, f32)> {
+        self.reagents
+            .iter()
+            .map(|(id, amount)| (id.as_str(), *amount))
+    }
+
+    /// Adds as much of the reagent as fits. Returns the amount added.
+    pub fn add(&mut self, id: &str, amount: f32) -> f32 {
+        let added = amount.min(self.free_space());
+        if added < MIN_AMOUNT {
+            return 0.0;
+        }
+        *self.reagents.entry(id.to_owned()).or_default() += added;
+        added
+    }
+
+    /// Removes up to `amount` of the reagent. Returns the amount removed.
+    pub fn remove(&mut self, id: &str, amount: f32) -> f32 {
+        let Some(present) = self.reagents.get_mut(id) else {
+            return 0.0;
+        };
+        let removed = amount.clamp(0.0, *present);
+        *present -= removed;
+        if *present < MIN_AMOUNT {
+            self.reagents.remove(id);
+        }
+        removed
+    }
+
+    /// Moves up to `amount` units into another container, keeping the ratio of the mixture.
+    /// Returns the amount moved.
+    pub fn transfer_to(&mut self, other: &mut ReagentContainer, amount: f32) -> f32 {
+        let amount = amount.min(self.volume()).min(other.free_space());
+        if amount < MIN_AMOUNT {
+            return 0.0;
+        }
+
+        let ratio = amount / self.volume();
+        let portions: Vec<_> = self
+            .reagents
+            .iter()
+            .map(|(id, present)| (id.clone(), present * ratio))
+            .collect();
+        for (id, portion) in portions {
+            let removed = self.remove(&id, portion);
+            other.add(&id, removed);
+        }
+        amount
+    }
+}
+
+/// Breaks down the reagents in its [`ReagentContainer`] over time.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Metabolizer;
+
+/// Sent for each effect of a chemical metabolized by an entity.
+#[derive(Event)]
+pub struct Metabolized {
+    pub entity: Entity,
+    pub effect: ChemicalEffect,
+    /// Units of the chemical that were metabolized
+    pub units: f32,
+}
+
+fn react_reagents(
+    mut containers: Query<&mut ReagentContainer, Changed<ReagentContainer>>,
+    chemistry: Res<ChemistryAssets>,
+    reactions: Res<Assets<Reaction>>,
+) {
+    for mut container in containers.iter_mut() {
+        for _ in 0..MAX_REACTION_STEPS {
+            let mut reacted = false;
+            for reaction in chemistry.reactions.iter().filter_map(|h| reactions.get(h)) {
+                if reaction.reacts_with(&container) {
+                    reacted |= reaction.apply(&mut container);
+                }
+            }
+            if !reacted {
+                break;
+            }
+        }
+    }
+}
+
+fn metabolize(
+    mut metabolizers: Query<(Entity, &mut ReagentContainer), With<Metabolizer>>,
+    chemistry: Res<ChemistryAssets>,
+    chemicals: Res<Assets<Chemical>>,
+    tick: Res<TickTime>,
+    mut events: EventWriter<Metabolized>,
+) {
+    let delta = tick.delta_seconds();
+    for (entity, mut container) in metabolizers.iter_mut() {
+        if container.is_empty() {
+            continue;
+        }
+
+        let present: Vec<String> = container.reagents().map(|(id, _)| id.to_owned()).collect();
+        for id in present {
+            let chemical = chemistry.chemical(&chemicals, &id);
+            // Unknown chemicals are flushed out without effect
+            let rate = chemical.map_or_else(default_metabolism_rate, |c| c.metabolism_rate);
+            let units = container.remove(&id, rate * delta);
+            let Some(chemical) = chemical else {
+                continue;
+            };
+            for &effect in chemical.effects.iter() {
+                events.send(Metabolized {
+                    entity,
+                    effect,
+                    units,
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use chemistry::{Reaction, ReagentContainer};
+
+fn container(capacity: f32, reagents: &[(&str, f32)]) -> ReagentContainer {
+    let mut container = ReagentContainer::new(capacity);
+    for &(id, amount) in reagents {
+        container.add(id, amount);
+    }
+    container
+}
+
+fn reaction(reagents: &[(&str, f32)], products: &[(&str, f32)]) -> Reaction {
+    let map = |list: &[(&str, f32)]| list.iter().map(|&(id, a)| (id.to_owned(), a)).collect();
+    Reaction {
+        id: "test".into(),
+        reagents: map(reagents),
+        products: map(products),
+    }
+}
+
+#[test]
+fn add_is_limited_by_capacity() {
+    let mut beaker = container(10.0, &[("water", 8.0)]);
+    assert_eq!(beaker.add("sugar", 5.0), 2.0);
+    assert_eq!(beaker.volume(), 10.0);
+    assert_eq!(beaker.add("sugar", 1.0), 0.0);
+}
+
+#[test]
+fn remove_drops_empty_reagents() {
+    let mut beaker = container(10.0, &[("water", 3.0)]);
+    assert_eq!(beaker.remove("water", 5.0), 3.0);
+    assert!(beaker.is_empty());
+    assert_eq!(beaker.remove("water", 1.0), 0.0);
+}
+
+#[test]
+fn transfer_keeps_mixture_ratio() {
+    let mut beaker = container(30.0, &[("water", 20.0), ("sugar", 10.0)]);
+    let mut syringe = ReagentContainer::new(15.0);
+
+    assert_eq!(beaker.transfer_to(&mut syringe, 6.0), 6.0);
+    assert!((syringe.amount("water") - 4.0).abs() < 1e-4);
+    assert!((syringe.amount("sugar") - 2.0).abs() < 1e-4);
+    assert!((beaker.volume() - 24.0).abs() < 1e-4);
+}
+
+#[test]
+fn transfer_is_limited_by_free_space() {
+    let mut beaker = container(30.0, &[("water", 30.0)]);
+    let mut syringe = container(15.0, &[("water", 12.0)]);
+
+    assert_eq!(beaker.transfer_to(&mut syringe, 10.0), 3.0);
+    assert_eq!(syringe.volume(), 15.0);
+}
+
+#[test]
+fn reaction_uses_limiting_reagent() {
+    let mut beaker = container(50.0, &[("carbon", 10.0), ("water", 4.0)]);
+    let reaction = reaction(&[("carbon", 1.0), ("water", 1.0)], &[("charcoal", 2.0)]);
+
+    assert!(reaction.apply(&mut beaker));
+    assert!((beaker.amount("carbon") - 6.0).abs() < 1e-4);
+    assert_eq!(beaker.amount("water"), 0.0);
+    assert!((beaker.amount("charcoal") - 8.0).abs() < 1e-4);
+}
+
+#[test]
+fn reaction_needs_all_reagents() {
+    let mut beaker = container(50.0, &[("carbon", 10.0)]);
+    let reaction = reaction(&[("carbon", 1.0), ("water", 1.0)], &[("charcoal", 2.0)]);
+
+    assert!(!reaction.reacts_with(&beaker));
+    assert!(!reaction.apply(&mut beaker));
+    assert_eq!(beaker.amount("carbon"), 10.0);
+}
@@ -0,0 +1,74 @@
+use bevy::{ecs::entity::Entity, math::UVec2};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use maps::{bench::AdjacencyWorld, TileMap, TileReference, CHUNK_SIZE};
+
+/// Size of the benchmarked maps in chunks, roughly that of a large station
+const MAP_CHUNKS: u32 = 16;
+
+fn filled_map() -> TileMap {
+    let mut map = TileMap::new(UVec2::splat(MAP_CHUNKS));
+    let tiles = MAP_CHUNKS * CHUNK_SIZE;
+    for x in 0..tiles {
+        for y in 0..tiles {
+            let tile = TileReference {
+                turf: Some(Entity::from_raw(x * tiles + y)),
+                ..Default::default()
+            };
+            map.set_tile(UVec2::new(x, y), tile).unwrap();
+        }
+    }
+    map
+}
+
+fn tilemap(c: &mut Criterion) {
+    let tiles = MAP_CHUNKS * CHUNK_SIZE;
+    let mut group = c.benchmark_group("tilemap");
+
+    group.bench_function("set_tile", |b| {
+        let mut map = TileMap::new(UVec2::splat(MAP_CHUNKS));
+        let tile = TileReference {
+            furniture: Some(Entity::from_raw(1)),
+            ..Default::default()
+        };
+        b.iter(|| {
+            for x in 0..tiles {
+                for y in 0..tiles {
+                    map.set_tile(black_box(UVec2::new(x, y)), tile).unwrap();
+                }
+            }
+        })
+    });
+
+    let map = filled_map();
+    group.bench_function("iter_tiles", |b| {
+        b.iter(|| {
+            black_box(&map)
+                .iter_tiles()
+                .filter(|(_, t)| t.turf.is_some())
+                .count()
+        })
+    });
+    group.bench_function("tile", |b| {
+        b.iter(|| {
+            (0..tiles)
+                .flat_map(|x| (0..tiles).map(move |y| UVec2::new(x, y)))
+                .filter(|&p| map.tile(black_box(p)).is_some())
+                .count()
+        })
+    });
+    group.finish();
+}
+
+fn adjacency(c: &mut Criterion) {
+    let mut group = c.benchmark_group("adjacency");
+    for size in [32, 128] {
+        let mut world = AdjacencyWorld::new(size);
+        group.bench_function(BenchmarkId::new("update_all", size), |b| {
+            b.iter(|| world.update_all())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, tilemap, adjacency);
+criterion_main!(benches);
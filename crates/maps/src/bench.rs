@@ -0,0 +1,71 @@
+//! Entry points for the criterion benchmarks
+
+use bevy::{ecs::schedule::ExecutorKind, prelude::*, utils::HashMap};
+
+use crate::{
+    adjacency::TilemapAdjacency, client_update_adjacencies, TileLayer, TileMapClient, TileReference,
+};
+
+/// A client world filled with walls that mesh together
+pub struct AdjacencyWorld {
+    world: World,
+    schedule: Schedule,
+    tilemap: Entity,
+}
+
+impl AdjacencyWorld {
+    /// Fills a square of `size` tiles with walls, leaving some gaps so every variant is used
+    pub fn new(size: u32) -> Self {
+        let mut world = World::new();
+        let mut tiles = HashMap::default();
+        for x in 0..size {
+            for y in 0..size {
+                if (x * 7 + y * 3) % 5 == 0 {
+                    continue;
+                }
+                let wall = world
+                    .spawn((
+                        TilemapAdjacency {
+                            category: "wall".into(),
+                            meshes: Default::default(),
+                        },
+                        Handle::<Mesh>::default(),
+                        Transform::default(),
+                    ))
+                    .id();
+                tiles.insert(
+                    UVec2::new(x, y),
+                    TileReference {
+                        turf: Some(wall),
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+        let tilemap = world
+            .spawn(TileMapClient {
+                tiles,
+                dirty_tiles: Default::default(),
+            })
+            .id();
+
+        let mut schedule = Schedule::default();
+        schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+        schedule.add_systems(client_update_adjacencies);
+        Self {
+            world,
+            schedule,
+            tilemap,
+        }
+    }
+
+    /// Marks every tile as changed and recomputes all adjacencies
+    pub fn update_all(&mut self) {
+        let mut tilemap = self.world.get_mut::<TileMapClient>(self.tilemap).unwrap();
+        let tilemap = tilemap.as_mut();
+        tilemap
+            .dirty_tiles
+            .extend(tilemap.tiles.keys().map(|&p| (p, TileLayer::Turf)));
+        self.schedule.run(&mut self.world);
+    }
+}
@@ -0,0 +1,453 @@
+//! Native map format, which can be saved from and loaded into the runtime [`TileMap`].
+
+use std::{any::TypeId, collections::BTreeMap, path::Path};
+
+use bevy::{
+    asset::{AssetLoader, AssetPathId, LoadContext, LoadedAsset},
+    ecs::system::Command,
+    math::UVec2,
+    prelude::*,
+    reflect::{
+        serde::{ReflectSerializer, UntypedReflectDeserializer},
+        GetTypeRegistration, TypePath, TypeRegistry, TypeUuid,
+    },
+};
+use networking::scene::NetworkSceneEvent;
+use serde::{de::DeserializeSeed, Deserialize, Serialize};
+
+use crate::{
+    journal::scene_path, Direction, LootSpawnPoint, TileCable, TileData, TileEntity, TileLayer,
+    TileMap, TileMapData, CHUNK_SIZE,
+};
+
+/// File extension of native maps
+pub const MAP_FILE_EXTENSION: &str = "ssntmap";
+const MAP_FILE_VERSION: u32 = 1;
+
+/// A map stored in the native format.
+/// Tiles reference their scenes by asset path.
+#[derive(Serialize, Deserialize, TypeUuid, TypePath)]
+#[uuid = "5f0c8e2a-7b1d-4c39-9a64-d2e8f1b3c570"]
+pub struct MapFile {
+    pub version: u32,
+    /// Size in tiles
+    pub size: UVec2,
+    /// Only tiles that contain something are stored
+    pub tiles: Vec<MapFileTile>,
+    #[serde(default)]
+    pub job_spawn_positions: BTreeMap<String, Vec<UVec2>>,
+    #[serde(default)]
+    pub loot_spawns: Vec<LootSpawnPoint>,
+    #[serde(default)]
+    pub environment: MapEnvironment,
+    /// Regions filled with one of several prefabs, picked when the map is spawned
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub prefab_slots: Vec<PrefabSlot>,
+}
+
+/// A rectangular region of a map that is replaced by a randomly picked prefab each round.
+/// Prefabs are map files themselves, their tiles are placed relative to the slot position.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PrefabSlot {
+    /// Tile position of the corner with the lowest coordinates
+    pub position: UVec2,
+    /// Size in tiles, prefab tiles outside of it are ignored
+    pub size: UVec2,
+    /// Asset paths of the map files that can be placed in this slot
+    pub prefabs: Vec<String>,
+}
+
+impl PrefabSlot {
+    fn contains(&self, position: UVec2) -> bool {
+        position.cmpge(self.position).all() && position.cmplt(self.position + self.size).all()
+    }
+}
+
+/// Prefab slots of a spawned map, kept on the tilemap entity so they are saved with it
+#[derive(Component, Clone, Default)]
+pub struct MapPrefabSlots(pub Vec<PrefabSlot>);
+
+/// How the world around a map looks, so stations, asteroids and planets can look different.
+/// Kept on the tilemap entity, clients apply it when the map loads.
+#[derive(Component, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct MapEnvironment {
+    /// Color of the ambient light, its brightness depends on what the player can see
+    pub ambient_color: Color,
+    /// Fog hiding what is far away, no fog if not set
+    pub fog: Option<MapFog>,
+    /// What is seen behind the map, the client's default color if not set
+    pub background: Option<MapBackground>,
+}
+
+impl Default for MapEnvironment {
+    fn default() -> Self {
+        Self {
+            ambient_color: Color::WHITE,
+            fog: None,
+            background: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct MapFog {
+    pub color: Color,
+    /// Distance from the camera where the fog starts
+    pub start: f32,
+    /// Distance from the camera where the fog hides everything
+    pub end: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum MapBackground {
+    Color(Color),
+    /// Asset path of a cubemap image, with its six faces stacked vertically
+    Skybox(String),
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct MapFileTile {
+    pub position: UVec2,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub underfloor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub turf: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub furniture: Option<String>,
+    #[serde(default)]
+    pub furniture_direction: Direction,
+    #[serde(default)]
+    pub high_mounts: [Option<String>; 4],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cable: Option<String>,
+    /// State of the tile entities that their scenes don't define
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub state: Vec<TileEntityState>,
+}
+
+/// Saved components of one tile entity, like the charge of an APC
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TileEntityState {
+    pub layer: TileLayer,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index_in_layer: Option<u8>,
+    /// Reflected components in RON, applied after the scene is spawned
+    pub components: Vec<String>,
+}
+
+/// Component types of tile entities that are saved with the map
+#[derive(Resource, Default)]
+struct SavedTileComponents(Vec<TypeId>);
+
+pub trait TileStateAppExt {
+    /// Saves a reflected component of tile entities in map files, so it is restored when the map loads.
+    /// Fields with `#[reflect(ignore)]` are not saved.
+    fn save_tile_state<T: Component + GetTypeRegistration>(&mut self) -> &mut Self;
+}
+
+impl TileStateAppExt for App {
+    fn save_tile_state<T: Component + GetTypeRegistration>(&mut self) -> &mut Self {
+        self.register_type::<T>();
+        self.world
+            .get_resource_or_insert_with(SavedTileComponents::default)
+            .0
+            .push(TypeId::of::<T>());
+        self
+    }
+}
+
+fn save_components(
+    world: &World,
+    entity: Entity,
+    saved: &SavedTileComponents,
+    registry: &TypeRegistry,
+) -> Vec<String> {
+    let entity_ref = world.entity(entity);
+    saved
+        .0
+        .iter()
+        .filter_map(|type_id| {
+            let registration = registry.get(*type_id)?;
+            let component = registration
+                .data::<ReflectComponent>()?
+                .reflect(entity_ref)?;
+            match ron::to_string(&ReflectSerializer::new(component, registry)) {
+                Ok(text) => Some(text),
+                Err(err) => {
+                    warn!(entity = ?entity, component = registration.type_name(), error = %err, "Failed to save tile entity state");
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Saved components waiting for the scene of a tile entity to spawn
+#[derive(Component)]
+pub(crate) struct PendingTileState(pub(crate) Vec<String>);
+
+pub(crate) fn apply_tile_state(
+    mut events: EventReader<NetworkSceneEvent>,
+    pending: Query<(), With<PendingTileState>>,
+    mut commands: Commands,
+) {
+    for event in events.iter() {
+        let NetworkSceneEvent::Created(entity) = *event;
+        if pending.contains(entity) {
+            commands.add(ApplyTileState(entity));
+        }
+    }
+}
+
+struct ApplyTileState(Entity);
+
+impl Command for ApplyTileState {
+    fn apply(self, world: &mut World) {
+        let Some(PendingTileState(components)) = world
+            .get_entity_mut(self.0)
+            .and_then(|mut entity| entity.take::<PendingTileState>())
+        else {
+            return;
+        };
+        let registry = world.resource::<AppTypeRegistry>().clone();
+        let registry = registry.read();
+        for text in components {
+            let value = ron::Deserializer::from_str(&text)
+                .map_err(|e| e.to_string())
+                .and_then(|mut deserializer| {
+                    UntypedReflectDeserializer::new(&registry)
+                        .deserialize(&mut deserializer)
+                        .map_err(|e| e.to_string())
+                });
+            let value = match value {
+                Ok(value) => value,
+                Err(err) => {
+                    warn!(entity = ?self.0, error = %err, "Invalid saved tile entity state");
+                    continue;
+                }
+            };
+            let Some(reflect_component) = registry
+                .get_with_name(value.type_name())
+                .and_then(|r| r.data::<ReflectComponent>())
+            else {
+                warn!(entity = ?self.0, component = value.type_name(), "Saved tile entity state is not a component");
+                continue;
+            };
+            reflect_component.apply_or_insert(&mut world.entity_mut(self.0), &*value);
+        }
+    }
+}
+
+impl MapFileTile {
+    fn to_tile_data(&self) -> TileData {
+        let asset = |path: &Option<String>| path.as_deref().map(AssetPathId::from);
+        TileData {
+            underfloor: asset(&self.underfloor),
+            turf: asset(&self.turf),
+            furniture: asset(&self.furniture),
+            furniture_direction: self.furniture_direction,
+            high_mounts: [0, 1, 2, 3].map(|i| asset(&self.high_mounts[i])),
+            cable: asset(&self.cable),
+            state: self.state.clone(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.underfloor.is_none()
+            && self.turf.is_none()
+            && self.furniture.is_none()
+            && self.high_mounts.iter().all(Option::is_none)
+            && self.cable.is_none()
+    }
+}
+
+impl MapFile {
+    /// Captures the current state of a spawned tilemap.
+    pub fn from_world(world: &World, tilemap: Entity) -> Option<Self> {
+        let map = world.get::<TileMap>(tilemap)?;
+        let path = |entity: Option<Entity>| entity.and_then(|e| scene_path(world, e));
+        let registry = world.resource::<AppTypeRegistry>().read();
+        let saved = world.get_resource::<SavedTileComponents>();
+        let state = |entity: Option<Entity>, layer, index_in_layer| {
+            let components = save_components(world, entity?, saved?, &registry);
+            (!components.is_empty()).then_some(TileEntityState {
+                layer,
+                index_in_layer,
+                components,
+            })
+        };
+
+        let mut tiles = BTreeMap::new();
+        for (position, tile) in map.iter_tiles() {
+            let entry = MapFileTile {
+                position,
+                underfloor: path(tile.underfloor),
+                turf: path(tile.turf),
+                furniture: path(tile.furniture),
+                furniture_direction: tile
+                    .furniture
+                    .and_then(|e| world.get::<TileEntity>(e))
+                    .map(|t| t.direction())
+                    .unwrap_or_default(),
+                high_mounts: tile.high_mounts.map(path),
+                cable: None,
+                state: [
+                    state(tile.underfloor, TileLayer::Underfloor, None),
+                    state(tile.turf, TileLayer::Turf, None),
+                    state(tile.furniture, TileLayer::Furniture, None),
+                ]
+                .into_iter()
+                .chain(
+                    tile.high_mounts
+                        .iter()
+                        .enumerate()
+                        .map(|(i, &mount)| state(mount, TileLayer::HighMount, Some(i as u8))),
+                )
+                .flatten()
+                .collect(),
+            };
+            if !entry.is_empty() {
+                tiles.insert((position.y, position.x), entry);
+            }
+        }
+
+        if let Some(children) = world.get::<Children>(tilemap) {
+            for &child in children.iter() {
+                let Some(cable) = world.get::<TileCable>(child) else {
+                    continue;
+                };
+                let position = cable.position;
+                tiles
+                    .entry((position.y, position.x))
+                    .or_insert_with(|| MapFileTile {
+                        position,
+                        ..Default::default()
+                    })
+                    .cable = scene_path(world, child);
+            }
+        }
+
+        Some(Self {
+            version: MAP_FILE_VERSION,
+            size: map.size() * CHUNK_SIZE,
+            tiles: tiles.into_values().collect(),
+            job_spawn_positions: map
+                .job_spawn_positions
+                .iter()
+                .map(|(job, positions)| (job.clone(), positions.clone()))
+                .collect(),
+            loot_spawns: map.loot_spawns.clone(),
+            environment: world
+                .get::<MapEnvironment>(tilemap)
+                .cloned()
+                .unwrap_or_default(),
+            prefab_slots: world
+                .get::<MapPrefabSlots>(tilemap)
+                .map(|slots| slots.0.clone())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Converts to data that can be spawned as a tilemap.
+    pub fn to_map_data(&self) -> TileMapData {
+        let mut tiles = Vec::new();
+        tiles.resize_with((self.size.x * self.size.y) as usize, TileData::default);
+        for tile in self.tiles.iter() {
+            if tile.position.x >= self.size.x || tile.position.y >= self.size.y {
+                warn!(position = ?tile.position, "Map file tile is out of bounds");
+                continue;
+            }
+            let index = (tile.position.y * self.size.x + tile.position.x) as usize;
+            tiles[index] = tile.to_tile_data();
+        }
+
+        TileMapData {
+            size: self.size,
+            tiles,
+            job_spawn_positions: self
+                .job_spawn_positions
+                .iter()
+                .map(|(job, positions)| (job.clone(), positions.clone()))
+                .collect(),
+            loot_spawns: self.loot_spawns.clone(),
+        }
+    }
+
+    /// Places this map as a prefab into a slot of converted map data.
+    /// Everything in the slot is replaced, including job spawns and loot spawns.
+    pub fn place_prefab(&self, data: &mut TileMapData, slot: &PrefabSlot) {
+        for y in slot.position.y..(slot.position.y + slot.size.y).min(data.size.y) {
+            for x in slot.position.x..(slot.position.x + slot.size.x).min(data.size.x) {
+                data.tiles[(y * data.size.x + x) as usize] = TileData::default();
+            }
+        }
+        for tile in self.tiles.iter() {
+            let position = slot.position + tile.position;
+            if !slot.contains(position) || position.x >= data.size.x || position.y >= data.size.y {
+                warn!(position = ?tile.position, "Prefab tile is outside of its slot");
+                continue;
+            }
+            data.tiles[(position.y * data.size.x + position.x) as usize] = tile.to_tile_data();
+        }
+
+        for positions in data.job_spawn_positions.values_mut() {
+            positions.retain(|p| !slot.contains(*p));
+        }
+        for (job, positions) in self.job_spawn_positions.iter() {
+            data.job_spawn_positions
+                .entry(job.clone())
+                .or_default()
+                .extend(
+                    positions
+                        .iter()
+                        .map(|p| slot.position + *p)
+                        .filter(|p| slot.contains(*p)),
+                );
+        }
+        data.loot_spawns
+            .retain(|spawn| !slot.contains(spawn.position));
+        data.loot_spawns.extend(
+            self.loot_spawns
+                .iter()
+                .map(|spawn| LootSpawnPoint {
+                    position: slot.position + spawn.position,
+                    ..spawn.clone()
+                })
+                .filter(|spawn| slot.contains(spawn.position)),
+        );
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let text = ron::ser::to_string_pretty(self, Default::default())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, text)
+    }
+}
+
+#[derive(Default)]
+pub struct MapFileLoader;
+
+impl AssetLoader for MapFileLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> bevy::asset::BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let map: MapFile = ron::de::from_bytes(bytes)?;
+            if map.version > MAP_FILE_VERSION {
+                anyhow::bail!("Map file version {} is not supported", map.version);
+            }
+            load_context.set_default_asset(LoadedAsset::new(map));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &[MAP_FILE_EXTENSION]
+    }
+}
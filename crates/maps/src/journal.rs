@@ -0,0 +1,140 @@
+use bevy::{math::UVec2, prelude::*, utils::HashMap};
+use networking::scene::NetworkScene;
+use serde::{Deserialize, Serialize};
+
+use crate::{Direction, MapCommandsExt, TileEntityPath, TileLayer, TileLayerData, TileMap};
+
+/// Records changes made to tilemaps at runtime, so they can be saved as a patch over the base map.
+///
+/// Changes are only recorded if this resource exists.
+#[derive(Resource, Default)]
+pub struct TileJournal {
+    /// The name of the map the changes are relative to
+    pub base_map: Option<String>,
+    changes: HashMap<(UVec2, TileLayer, Option<u8>), TilePatchEntry>,
+    /// Inclusive corners of areas whose changes are not recorded
+    ignored_areas: Vec<(UVec2, UVec2)>,
+    /// Set when changes were recorded since the last save
+    dirty: bool,
+}
+
+impl TileJournal {
+    /// Discards all changes and starts recording for a new map.
+    pub fn reset(&mut self, base_map: impl Into<String>) {
+        self.base_map = Some(base_map.into());
+        self.changes.clear();
+        self.ignored_areas.clear();
+        self.dirty = false;
+    }
+
+    /// Stops recording changes between two corners (inclusive), until the next reset.
+    /// Used for areas that are rebuilt at runtime and should not end up in patches.
+    pub fn ignore_area(&mut self, min: UVec2, max: UVec2) {
+        self.ignored_areas.push((min, max));
+    }
+
+    /// If changes at a position are not recorded
+    pub fn is_ignored(&self, position: UVec2) -> bool {
+        self.ignored_areas
+            .iter()
+            .any(|(min, max)| position.cmpge(*min).all() && position.cmple(*max).all())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Returns true if there are unsaved changes and marks them as saved.
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
+    }
+
+    /// Creates a patch containing the final state of every changed tile slot.
+    pub fn export(&self) -> TileMapPatch {
+        let mut entries: Vec<_> = self.changes.values().cloned().collect();
+        // Keep output stable so patches can be diffed
+        entries.sort_by_key(|e| (e.position.y, e.position.x, e.layer as u8, e.index_in_layer));
+        TileMapPatch {
+            base_map: self.base_map.clone().unwrap_or_default(),
+            entries,
+        }
+    }
+}
+
+/// A set of tile changes relative to a base map.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct TileMapPatch {
+    pub base_map: String,
+    pub entries: Vec<TilePatchEntry>,
+}
+
+/// The state of one tile slot after modification.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TilePatchEntry {
+    pub position: UVec2,
+    pub layer: TileLayer,
+    pub index_in_layer: Option<u8>,
+    /// Path of the scene in the slot, or `None` if it was emptied
+    pub scene: Option<String>,
+    pub direction: Direction,
+}
+
+impl TileMapPatch {
+    /// Replaces the contents of every slot in the patch.
+    /// Slots are emptied first, so the spawned entities don't collide with existing ones.
+    pub fn apply(&self, tilemap: Entity, map: &TileMap, commands: &mut Commands) {
+        for entry in self.entries.iter() {
+            let existing = map.tile(entry.position).and_then(|tile| {
+                match (tile.get(entry.layer), entry.index_in_layer) {
+                    (TileLayerData::Single(e), _) => e,
+                    (TileLayerData::Directional(slots), Some(i)) => {
+                        slots.get(i as usize).copied().flatten()
+                    }
+                    (TileLayerData::Directional(_), None) => None,
+                }
+            });
+            if let Some(existing) = existing {
+                commands.despawn_tile_entity(existing);
+            }
+
+            if let Some(scene) = &entry.scene {
+                commands.spawn_tile_entity(
+                    tilemap,
+                    entry.position,
+                    entry.layer,
+                    entry.direction,
+                    scene.clone(),
+                );
+            }
+        }
+    }
+}
+
+pub(crate) fn record(world: &mut World, path: TileEntityPath, scene: Option<String>) {
+    let Some(mut journal) = world.get_resource_mut::<TileJournal>() else {
+        return;
+    };
+    if journal.is_ignored(path.position) {
+        return;
+    }
+    journal.changes.insert(
+        (path.position, path.layer, path.index_in_layer),
+        TilePatchEntry {
+            position: path.position,
+            layer: path.layer,
+            index_in_layer: path.index_in_layer,
+            scene,
+            direction: path.direction,
+        },
+    );
+    journal.dirty = true;
+}
+
+/// Returns the asset path of the scene an entity was spawned from.
+pub(crate) fn scene_path(world: &World, entity: Entity) -> Option<String> {
+    let scene = world.get::<NetworkScene>(entity)?;
+    let path = world
+        .resource::<AssetServer>()
+        .get_handle_path(scene.handle())?;
+    Some(path.path().to_string_lossy().replace('\\', "/"))
+}
@@ -13,8 +13,9 @@ use bevy::{
 use networking::{
     component::AppExt,
     identity::{NetworkIdentities, NetworkIdentity},
-    scene::NetworkSceneBundle,
+    scene::{insert_pbr_bundles, AppExt as SceneAppExt, NetworkSceneBundle},
     spawning::{NetworkedEntityEvent, SpawningSet},
+    sync::is_synchronized,
     transform::NetworkTransform,
     variable::{NetworkVar, ServerVar},
     visibility::{GridAabb, GLOBAL_GRID_CELL_SIZE},
@@ -26,6 +27,16 @@ pub use enum_map::enum_map;
 
 mod adjacency;
 pub use adjacency::Surrounded;
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod bench;
+pub mod io;
+pub mod journal;
+pub mod overlay;
+#[cfg(feature = "testing")]
+#[doc(hidden)]
+pub mod testing;
+pub mod wear;
 
 #[derive(Component, Networked)]
 #[networked(client = "TileMapClient", priority = 10)]
@@ -34,6 +45,7 @@ pub struct TileMap {
     size: UVec2,
     chunks: Vec<Option<Box<Chunk>>>,
     pub job_spawn_positions: HashMap<String, Vec<UVec2>>,
+    pub loot_spawns: Vec<LootSpawnPoint>,
 }
 
 impl TileMap {
@@ -44,6 +56,7 @@ impl TileMap {
             size,
             chunks,
             job_spawn_positions: Default::default(),
+            loot_spawns: Default::default(),
         }
     }
 
@@ -76,7 +89,7 @@ impl TileMap {
         self.chunk_mut(index)
     }
 
-    pub fn iter_tiles(&mut self) -> impl Iterator<Item = (UVec2, &TileReference)> {
+    pub fn iter_tiles(&self) -> impl Iterator<Item = (UVec2, &TileReference)> {
         let size = self.size;
         self.iter_chunks()
             .map(move |(p, c)| {
@@ -131,6 +144,97 @@ impl TileMap {
         Ok(())
     }
 
+    /// If any tile was modified since the last [`TileMap::take_changed_tiles`].
+    pub fn has_changed_tiles(&self) -> bool {
+        self.iter_chunks().any(|(_, chunk)| chunk.changed)
+    }
+
+    /// Returns the positions of tiles modified since the last call and clears their flags.
+    pub fn take_changed_tiles(&mut self) -> Vec<UVec2> {
+        let size = self.size;
+        let mut changed = Vec::new();
+        for (index, chunk) in self.chunks.iter_mut().enumerate() {
+            let Some(chunk) = chunk.as_mut().filter(|c| c.changed) else {
+                continue;
+            };
+            let origin =
+                Self::position_from_chunk_index(size, index) * UVec2::new(CHUNK_SIZE, CHUNK_SIZE);
+            for (i, tile_changed) in chunk.changed_tiles.iter_mut().enumerate() {
+                if std::mem::take(tile_changed) {
+                    changed.push(origin + TileReference::position_in_chunk(i));
+                }
+            }
+            chunk.changed = false;
+        }
+        changed
+    }
+
+    /// Positions of tiles modified since the map was spawned from its [`TileMapData`].
+    /// Unlike [`TileMap::take_changed_tiles`] these flags are never cleared.
+    pub fn modified_tiles(&self) -> impl Iterator<Item = UVec2> + '_ {
+        let size = self.size;
+        self.iter_chunks().flat_map(move |(index, chunk)| {
+            let origin =
+                Self::position_from_chunk_index(size, index) * UVec2::new(CHUNK_SIZE, CHUNK_SIZE);
+            chunk
+                .modified_tiles
+                .iter()
+                .enumerate()
+                .filter(|(_, modified)| **modified)
+                .map(move |(i, _)| origin + TileReference::position_in_chunk(i))
+        })
+    }
+
+    /// Checks if a new tile entity can be placed at a position.
+    ///
+    /// `direction` selects the slot on directional layers (like [`TileLayer::HighMount`]).
+    /// Wall mounts are placed on a floor, in the slot facing the wall they hang on.
+    /// `is_mount_surface` decides if a turf entity can hold wall mounts.
+    pub fn validate_placement(
+        &self,
+        position: UVec2,
+        layer: TileLayer,
+        direction: Option<Direction>,
+        is_mount_surface: impl Fn(Entity) -> bool,
+    ) -> Result<(), PlacementError> {
+        if position.cmpge(self.size * CHUNK_SIZE).any() {
+            return Err(PlacementError::OutOfBounds);
+        }
+
+        let tile = self.tile(position).copied().unwrap_or_default();
+        let occupant = match (tile.get(layer), direction) {
+            (TileLayerData::Single(existing), None) => existing,
+            (TileLayerData::Directional(slots), Some(direction)) => slots[direction as usize],
+            _ => return Err(PlacementError::InvalidSlot),
+        };
+        if let Some(occupant) = occupant {
+            return Err(PlacementError::Occupied(occupant));
+        }
+
+        if layer == TileLayer::HighMount {
+            // The face of the wall is covered if the mount's own tile is a wall too
+            if tile.turf.map(&is_mount_surface).unwrap_or(false) {
+                return Err(PlacementError::FaceBlocked);
+            }
+
+            // Mounts hang on the wall next to their tile
+            let wall = position.as_ivec2() + IVec2::from(direction.unwrap());
+            let in_bounds =
+                wall.min_element() >= 0 && wall.cmplt((self.size * CHUNK_SIZE).as_ivec2()).all();
+            let supported = in_bounds
+                && self
+                    .tile(wall.as_uvec2())
+                    .and_then(|t| t.turf)
+                    .map(&is_mount_surface)
+                    .unwrap_or(false);
+            if !supported {
+                return Err(PlacementError::MissingSupport);
+            }
+        }
+
+        Ok(())
+    }
+
     fn position_inside_chunk(&self, position: UVec2) -> UVec2 {
         UVec2::new(position.x % CHUNK_SIZE, position.y % CHUNK_SIZE)
     }
@@ -161,6 +265,8 @@ pub struct Chunk {
     tiles: [TileReference; CHUNK_LENGTH],
     changed_tiles: [bool; CHUNK_LENGTH],
     changed: bool,
+    /// Tiles that differ from what was spawned
+    modified_tiles: [bool; CHUNK_LENGTH],
 }
 
 impl Default for Chunk {
@@ -169,6 +275,7 @@ impl Default for Chunk {
             tiles: [TileReference::default(); CHUNK_LENGTH],
             changed_tiles: [false; CHUNK_LENGTH],
             changed: false,
+            modified_tiles: [false; CHUNK_LENGTH],
         }
     }
 }
@@ -187,6 +294,7 @@ impl Chunk {
 
         self.changed = true;
         self.changed_tiles[index] = true;
+        self.modified_tiles[index] = true;
         &mut self.tiles[index]
     }
 
@@ -207,8 +315,9 @@ pub fn tile_neighbours(position: UVec2) -> impl Iterator<Item = (Direction, UVec
         .map(|(dir, p)| (dir, p.as_uvec2()))
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Direction {
+    #[default]
     North = 0,
     East,
     South,
@@ -219,6 +328,11 @@ impl Direction {
     fn rotate_around(self, axis: Vec3) -> Quat {
         Quat::from_axis_angle(axis, std::f32::consts::FRAC_PI_2 * (self as u8 as f32))
     }
+
+    /// The next direction when turning clockwise.
+    pub fn rotate_clockwise(self) -> Self {
+        DIRECTIONS[(self as usize + 1) % DIRECTIONS.len()]
+    }
 }
 
 pub const DIRECTIONS: [Direction; 4] = [
@@ -283,6 +397,8 @@ impl Neg for Direction {
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[non_exhaustive]
 pub enum TileLayer {
+    /// The base of a tile below the turf (like plating).
+    Underfloor,
     Turf,
     Furniture,
     HighMount,
@@ -292,11 +408,49 @@ impl TileLayer {
     fn default_offset(&self) -> Vec3 {
         match self {
             TileLayer::Furniture | TileLayer::Turf => Vec3::ZERO,
+            // Slightly lowered so it doesn't clip into turfs placed on top
+            TileLayer::Underfloor => Vec3::new(0.0, -0.01, 0.0),
             TileLayer::HighMount => Vec3::new(0.5, 2.0, 0.0),
         }
     }
 }
 
+/// Reasons a tile entity can't be placed somewhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlacementError {
+    /// The position is outside of the tilemap.
+    OutOfBounds,
+    /// A direction was given for a single-slot layer, or was missing for a directional layer.
+    InvalidSlot,
+    /// Another tile entity already occupies the slot.
+    Occupied(Entity),
+    /// There is no wall next to the tile to attach a mount to.
+    MissingSupport,
+    /// The wall face is covered, because the tile of the mount is a wall itself.
+    FaceBlocked,
+}
+
+impl std::fmt::Display for PlacementError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlacementError::OutOfBounds => write!(f, "position is outside of the map"),
+            PlacementError::InvalidSlot => write!(f, "invalid slot for this layer"),
+            PlacementError::Occupied(entity) => {
+                write!(f, "slot is already occupied by {:?}", entity)
+            }
+            PlacementError::MissingSupport => write!(f, "no wall to mount on"),
+            PlacementError::FaceBlocked => write!(f, "wall face is covered by a wall"),
+        }
+    }
+}
+
+impl std::error::Error for PlacementError {}
+
+/// Marks a turf that wall mounts can be attached to.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct MountSurface;
+
 /// Uniquely references a tile entity in a [`TileMap`].
 ///
 /// ## Remarks
@@ -306,6 +460,15 @@ struct TileEntityPath {
     position: UVec2,
     layer: TileLayer,
     index_in_layer: Option<u8>,
+    /// Which way the tile entity is facing.
+    /// For directional layers this always matches the index in the layer.
+    direction: Direction,
+}
+
+/// A cable laid into the turf of a tile, spawned from [`TileData::cable`]
+#[derive(Component)]
+pub struct TileCable {
+    pub position: UVec2,
 }
 
 /// Data which can be used to spawn a [`TileMap`]
@@ -315,6 +478,17 @@ pub struct TileMapData {
     pub size: UVec2,
     pub tiles: Vec<TileData>,
     pub job_spawn_positions: HashMap<String, Vec<UVec2>>,
+    pub loot_spawns: Vec<LootSpawnPoint>,
+}
+
+/// A position in the map where loot is spawned from a loot table.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LootSpawnPoint {
+    pub position: UVec2,
+    /// Identifier of the loot table
+    pub table: String,
+    /// How many times the table is rolled
+    pub rolls: u32,
 }
 
 impl TileMapData {
@@ -346,14 +520,24 @@ impl<T> From<[Option<T>; DIRECTIONS.len()]> for TileLayerData<T> {
 /// The makeup of a tile that can be spawned into the world.
 #[derive(Default)]
 pub struct TileData {
+    pub underfloor: Option<AssetPathId>,
     pub turf: Option<AssetPathId>,
     pub furniture: Option<AssetPathId>,
+    pub furniture_direction: Direction,
     pub high_mounts: [Option<AssetPathId>; 4],
+    /// Cable laid into the turf. It isn't part of a tile layer, so it doesn't take up the turf slot.
+    pub cable: Option<AssetPathId>,
+    /// Saved state of the tile entities, applied once their scenes are spawned
+    pub state: Vec<io::TileEntityState>,
 }
 
 impl TileData {
     fn layers(&self) -> impl Iterator<Item = (TileLayer, TileLayerData<AssetPathId>)> {
         [
+            (
+                TileLayer::Underfloor,
+                TileLayerData::Single(self.underfloor),
+            ),
             (TileLayer::Turf, TileLayerData::Single(self.turf)),
             (TileLayer::Furniture, TileLayerData::Single(self.furniture)),
             (
@@ -368,6 +552,7 @@ impl TileData {
 /// Points to the entities making up a tile at runtime
 #[derive(Default, Clone, Copy)]
 pub struct TileReference {
+    pub underfloor: Option<Entity>,
     pub turf: Option<Entity>,
     pub furniture: Option<Entity>,
     pub high_mounts: [Option<Entity>; 4],
@@ -385,6 +570,7 @@ impl TileReference {
 
     fn get(&self, layer: TileLayer) -> TileLayerData<Entity> {
         match layer {
+            TileLayer::Underfloor => self.underfloor.into(),
             TileLayer::Turf => self.turf.into(),
             TileLayer::Furniture => self.furniture.into(),
             TileLayer::HighMount => self.high_mounts.into(),
@@ -393,6 +579,7 @@ impl TileReference {
 
     fn set(&mut self, layer: TileLayer, data: TileLayerData<Entity>) {
         match (layer, data) {
+            (TileLayer::Underfloor, TileLayerData::Single(v)) => self.underfloor = v,
             (TileLayer::Turf, TileLayerData::Single(v)) => self.turf = v,
             (TileLayer::Furniture, TileLayerData::Single(v)) => self.furniture = v,
             (TileLayer::HighMount, TileLayerData::Directional(v)) => self.high_mounts = v,
@@ -405,7 +592,7 @@ impl TileReference {
 
     fn set_index(&mut self, layer: TileLayer, index: usize, value: Option<Entity>) {
         match layer {
-            TileLayer::Turf | TileLayer::Furniture => panic!(
+            TileLayer::Underfloor | TileLayer::Turf | TileLayer::Furniture => panic!(
                 "Can't set index on tile layer '{:?}' with single slot",
                 layer
             ),
@@ -431,7 +618,7 @@ impl TileReference {
 /// Attached to an entity that is a part of a tile.
 #[derive(Component, Networked)]
 #[networked(client = "TileEntityClient")]
-struct TileEntity {
+pub struct TileEntity {
     #[networked(
         with = "Self::network_tilemap(Res<'static, NetworkIdentities>) -> NetworkIdentity"
     )]
@@ -440,6 +627,24 @@ struct TileEntity {
 }
 
 impl TileEntity {
+    /// The tilemap this entity is a part of.
+    pub fn tilemap(&self) -> Entity {
+        *self.tilemap
+    }
+
+    pub fn position(&self) -> UVec2 {
+        self.path.position
+    }
+
+    pub fn layer(&self) -> TileLayer {
+        self.path.layer
+    }
+
+    /// The direction this tile entity is facing.
+    pub fn direction(&self) -> Direction {
+        self.path.direction
+    }
+
     fn network_tilemap(entity: &Entity, param: Res<NetworkIdentities>) -> NetworkIdentity {
         param
             .get_identity(*entity)
@@ -473,6 +678,30 @@ impl TileEntityClient {
     }
 }
 
+fn tile_entity_bundle(
+    tilemap: Entity,
+    scene: Handle<DynamicScene>,
+    path: TileEntityPath,
+) -> (NetworkSceneBundle, TileEntity) {
+    let rotation = path.direction.rotate_around(Vec3::Y);
+    (
+        NetworkSceneBundle {
+            scene: scene.into(),
+            transform: Transform {
+                translation: Vec3::new(path.position.x as f32, 0.0, path.position.y as f32)
+                    + rotation * path.layer.default_offset(),
+                rotation,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        TileEntity {
+            tilemap: tilemap.into(),
+            path: path.into(),
+        },
+    )
+}
+
 /// Creates a tilemap from data and spawns the tile objects into the world
 fn spawn_from_data(
     query: Query<(Entity, &TileMapData), Without<TileMap>>,
@@ -482,6 +711,7 @@ fn spawn_from_data(
     for (map_entity, data) in query.iter() {
         let mut map = TileMap::new(data.size_in_chunks());
         map.job_spawn_positions = data.job_spawn_positions.clone();
+        map.loot_spawns = data.loot_spawns.clone();
 
         for (data_index, tile_data) in data.tiles.iter().enumerate() {
             let y = data_index as u32 / data.size.x;
@@ -493,38 +723,36 @@ fn spawn_from_data(
             for (layer, layer_data) in tile_data.layers() {
                 let mut spawn_object =
                     |asset_path, index_in_layer, direction: Direction| -> Entity {
-                        let scene = server.get_handle(asset_path);
                         let tile = commands
-                            .spawn((
-                                NetworkSceneBundle {
-                                    scene: scene.into(),
-                                    transform: Transform {
-                                        translation: Vec3::new(x as f32, 0.0, y as f32)
-                                            + direction.rotate_around(Vec3::Y)
-                                                * layer.default_offset(),
-                                        rotation: direction.rotate_around(Vec3::Y),
-                                        ..Default::default()
-                                    },
-                                    ..Default::default()
-                                },
-                                TileEntity {
-                                    tilemap: map_entity.into(),
-                                    path: TileEntityPath {
-                                        position: UVec2::new(x, y),
-                                        layer,
-                                        index_in_layer,
-                                    }
-                                    .into(),
+                            .spawn(tile_entity_bundle(
+                                map_entity,
+                                server.get_handle(asset_path),
+                                TileEntityPath {
+                                    position: UVec2::new(x, y),
+                                    layer,
+                                    index_in_layer,
+                                    direction,
                                 },
                             ))
                             .id();
+                        if let Some(state) = tile_data.state.iter().find(|state| {
+                            state.layer == layer && state.index_in_layer == index_in_layer
+                        }) {
+                            commands
+                                .entity(tile)
+                                .insert(io::PendingTileState(state.components.clone()));
+                        }
                         commands.entity(map_entity).add_child(tile);
                         tile
                     };
 
                 match layer_data {
                     TileLayerData::Single(Some(p)) => {
-                        let entity = spawn_object(p, None, Direction::North);
+                        let direction = match layer {
+                            TileLayer::Furniture => tile_data.furniture_direction,
+                            _ => Direction::North,
+                        };
+                        let entity = spawn_object(p, None, direction);
                         tile_ref.set(layer, TileLayerData::Single(Some(entity)));
                     }
                     TileLayerData::Directional(paths) => {
@@ -543,11 +771,32 @@ fn spawn_from_data(
                 };
             }
 
+            if let Some(cable) = tile_data.cable {
+                let cable = commands
+                    .spawn((
+                        NetworkSceneBundle {
+                            scene: server.get_handle(cable).into(),
+                            transform: Transform::from_xyz(x as f32, 0.0, y as f32),
+                            ..Default::default()
+                        },
+                        TileCable {
+                            position: UVec2::new(x, y),
+                        },
+                    ))
+                    .id();
+                commands.entity(map_entity).add_child(cable);
+            }
+
             map.set_tile((x, y).into(), tile_ref).unwrap();
         }
+        // The spawned tiles are the baseline for modifications
+        for chunk in map.chunks.iter_mut().flatten() {
+            chunk.modified_tiles = [false; CHUNK_LENGTH];
+        }
 
         commands.entity(map_entity).insert((
             map,
+            overlay::TileOverlays::default(),
             GridAabb::default(),
             SpatialBundle::default(),
             NetworkTransform::default(),
@@ -573,6 +822,19 @@ fn update_grid_aabb(mut query: Query<(&TileMap, &mut GridAabb), Changed<TileMap>
 
 pub trait MapCommandsExt {
     fn despawn_tile_entity(&mut self, entity: Entity);
+    /// Turns a tile entity to face a new direction.
+    /// Only works for tile entities on a non-directional layer (like furniture).
+    fn rotate_tile_entity(&mut self, entity: Entity, direction: Direction);
+    /// Spawns a scene as a tile entity into an empty slot of a tilemap.
+    /// Use [`TileMap::validate_placement`] beforehand to check if the slot is free.
+    fn spawn_tile_entity(
+        &mut self,
+        tilemap: Entity,
+        position: UVec2,
+        layer: TileLayer,
+        direction: Direction,
+        scene: impl Into<String>,
+    );
 }
 
 impl<'w, 's> MapCommandsExt for Commands<'w, 's> {
@@ -580,6 +842,119 @@ impl<'w, 's> MapCommandsExt for Commands<'w, 's> {
         self.add(DespawnTileEntityCommand { entity });
         self.entity(entity).despawn_recursive();
     }
+
+    fn rotate_tile_entity(&mut self, entity: Entity, direction: Direction) {
+        self.add(RotateTileEntityCommand { entity, direction });
+    }
+
+    fn spawn_tile_entity(
+        &mut self,
+        tilemap: Entity,
+        position: UVec2,
+        layer: TileLayer,
+        direction: Direction,
+        scene: impl Into<String>,
+    ) {
+        self.add(SpawnTileEntityCommand {
+            tilemap,
+            position,
+            layer,
+            direction,
+            scene: scene.into(),
+        });
+    }
+}
+
+struct SpawnTileEntityCommand {
+    tilemap: Entity,
+    position: UVec2,
+    layer: TileLayer,
+    direction: Direction,
+    scene: String,
+}
+
+impl Command for SpawnTileEntityCommand {
+    fn apply(self, world: &mut World) {
+        let index_in_layer = match self.layer {
+            TileLayer::HighMount => Some(self.direction as u8),
+            _ => None,
+        };
+        let path = TileEntityPath {
+            position: self.position,
+            layer: self.layer,
+            index_in_layer,
+            direction: self.direction,
+        };
+
+        let Some(map) = world.get::<TileMap>(self.tilemap) else {
+            warn!(entity = ?self.tilemap, "Tried to spawn tile entity in missing tilemap");
+            return;
+        };
+        let mut reference = map.tile(self.position).copied().unwrap_or_default();
+        let occupied = match (reference.get(self.layer), index_in_layer) {
+            (TileLayerData::Single(existing), _) => existing.is_some(),
+            (TileLayerData::Directional(slots), Some(i)) => slots[i as usize].is_some(),
+            (TileLayerData::Directional(_), None) => true,
+        };
+        if occupied {
+            warn!(position = ?self.position, layer = ?self.layer, "Tried to spawn tile entity in occupied slot");
+            return;
+        }
+
+        let scene = world.resource::<AssetServer>().load(self.scene.as_str());
+        let entity = world
+            .spawn(tile_entity_bundle(self.tilemap, scene, path))
+            .id();
+        world.entity_mut(self.tilemap).add_child(entity);
+
+        match index_in_layer {
+            Some(i) => reference.set_index(self.layer, i as usize, Some(entity)),
+            None => reference.set(self.layer, TileLayerData::Single(Some(entity))),
+        }
+        let mut map = world.get_mut::<TileMap>(self.tilemap).unwrap();
+        if map.set_tile(self.position, reference).is_err() {
+            warn!(position = ?self.position, "Spawned tile entity outside of tilemap");
+        }
+
+        journal::record(world, path, Some(self.scene));
+    }
+}
+
+struct RotateTileEntityCommand {
+    entity: Entity,
+    direction: Direction,
+}
+
+impl Command for RotateTileEntityCommand {
+    fn apply(self, world: &mut World) {
+        let Some(mut entity) = world.get_entity_mut(self.entity) else {
+            return;
+        };
+        let Some(mut tile) = entity.get_mut::<TileEntity>() else {
+            warn!(entity = ?self.entity, "Tried to rotate entity that is not a tile entity");
+            return;
+        };
+
+        // Directional layers store their direction in the slot index
+        if tile.path.index_in_layer.is_some() {
+            warn!(entity = ?self.entity, layer = ?tile.path.layer, "Can't rotate tile entity on a directional layer");
+            return;
+        }
+
+        if tile.path.direction == self.direction {
+            return;
+        }
+        tile.path.direction = self.direction;
+
+        let path = *tile.path;
+
+        if let Some(mut transform) = entity.get_mut::<Transform>() {
+            transform.rotation = self.direction.rotate_around(Vec3::Y);
+        }
+
+        let scene = journal::scene_path(world, self.entity);
+        journal::record(world, path, scene);
+    }
 }
 
 struct DespawnTileEntityCommand {
@@ -588,53 +963,47 @@ struct DespawnTileEntityCommand {
 
 impl Command for DespawnTileEntityCommand {
     fn apply(self, world: &mut World) {
-        if let Some(tile) = world.entity_mut(self.entity).take::<TileEntity>() {
-            let path = *tile.path;
-            if let Some(mut map) = world.get_mut::<TileMap>(*tile.tilemap) {
-                if let Some(reference) = map.tile_mut(path.position) {
-                    reference.remove_at(path);
-                }
-            }
-        }
+        unlink_tile_entity(world, self.entity);
     }
 }
 
-// TODO: Remove once scenes support composition
-/// Adds some bundles to spawned tile scenes, so we don't need to specify them every time
-fn client_initialize_tile_objects(
-    new: Query<Entity, Added<TileEntityClient>>,
-    children_query: Query<&Children>,
-    existing_meshes: Query<(&Handle<Mesh>, Option<&Transform>)>,
-    tile_entities: Query<&TileEntityClient>,
-    mut tilemaps: Query<&mut TileMapClient>,
-    assets: Res<MapAssets>,
-    mut commands: Commands,
-) {
-    let Some(assets) = assets.client.as_ref() else {
+/// Removes an entity from the tilemap it is part of, without despawning it.
+/// Does nothing if the entity is not a tile entity.
+pub fn unlink_tile_entity(world: &mut World, entity: Entity) {
+    let Some(tile) = world
+        .get_entity_mut(entity)
+        .and_then(|mut e| e.take::<TileEntity>())
+    else {
         return;
     };
-
-    let mut process_entity = |entity| {
-        if let Ok((mesh, transform)) = existing_meshes.get(entity) {
-            commands.entity(entity).insert(PbrBundle {
-                mesh: mesh.clone(),
-                material: assets.default_material.clone(),
-                transform: transform.cloned().unwrap_or_default(),
-                ..Default::default()
-            });
+    let path = *tile.path;
+    if let Some(mut map) = world.get_mut::<TileMap>(*tile.tilemap) {
+        if let Some(reference) = map.tile_mut(path.position) {
+            reference.remove_at(path);
         }
-    };
+    }
+    journal::record(world, path, None);
+}
 
-    for root in new.iter() {
-        process_entity(root);
-        for child in children_query.iter_descendants(root) {
-            process_entity(child);
-        }
+/// Adds rendering components to spawned tile objects on the client
+fn tile_preset(world: &mut World, root: Entity) {
+    let Some(material) = world
+        .resource::<MapAssets>()
+        .client
+        .as_ref()
+        .map(|c| c.default_material.clone())
+    else {
+        return;
+    };
+    insert_pbr_bundles(world, root, material);
+}
 
-        // Add to dirty tiles for adjacency
-        let Ok(tile) = tile_entities.get(root) else {
-            continue;
-        };
+/// Marks new tile entities as dirty for adjacency
+fn client_mark_new_tile_entities(
+    new: Query<&TileEntityClient, Added<TileEntityClient>>,
+    mut tilemaps: Query<&mut TileMapClient>,
+) {
+    for tile in new.iter() {
         let mut map = tilemaps.get_mut(*tile.tilemap).unwrap();
         let path = &*tile.path;
         map.dirty_tiles.insert((path.position, path.layer));
@@ -677,11 +1046,12 @@ fn client_update_tile_entities(
             new_transform.translation.x += tile_path.position.x as f32;
             new_transform.translation.z += tile_path.position.y as f32;
 
-            let direction: Direction = (tile_path.index_in_layer.unwrap_or_default() as usize)
-                .try_into()
-                .unwrap();
+            // Undo the rotation of the previous direction before applying the new one
+            if let Some(old_path) = tile_entity.old_path {
+                new_transform.rotation *= old_path.direction.rotate_around(Vec3::Y).inverse();
+            }
             // TODO: This will break if object gets moved
-            new_transform.rotation *= direction.rotate_around(Vec3::Y);
+            new_transform.rotation *= tile_path.direction.rotate_around(Vec3::Y);
             commands.entity(entity).insert(new_transform);
 
             let mut tilemap = tilemaps
@@ -704,6 +1074,7 @@ fn client_update_tile_entities(
                 }
             }
 
+            // Re-resolve adjacency of the tile and its neighbours, also after rotating in place
             tilemap
                 .dirty_tiles
                 .insert((tile_path.position, tile_path.layer));
@@ -737,7 +1108,12 @@ fn client_mark_deleted_tile_entities(
 
 fn client_update_adjacencies(
     mut tilemaps: Query<&mut TileMapClient>,
-    mut adjacents_mut: Query<(&TilemapAdjacency, &mut Handle<Mesh>, &mut Transform)>,
+    mut adjacents_mut: Query<(
+        &TilemapAdjacency,
+        &mut Handle<Mesh>,
+        &mut Transform,
+        Option<&TileEntityClient>,
+    )>,
     adjacencies: Query<&TilemapAdjacency>,
 ) {
     for mut tilemap in tilemaps.iter_mut() {
@@ -765,7 +1141,7 @@ fn client_update_adjacencies(
                     _ => continue,
                 };
 
-                let (adjacency_settings, mut mesh_handle, mut transform) =
+                let (adjacency_settings, mut mesh_handle, mut transform, tile) =
                     match adjacents_mut.get_mut(tile_entity) {
                         Ok(q) => q,
                         Err(_) => continue,
@@ -793,7 +1169,9 @@ fn client_update_adjacencies(
 
                 let (handle, rotation) = adjacency_settings.meshes.get(adjacency_info);
                 *mesh_handle = handle;
-                transform.rotation = rotation;
+                // Keep the direction the tile entity was rotated to
+                let direction = tile.map(|t| t.path.direction).unwrap_or_default();
+                transform.rotation = direction.rotate_around(Vec3::Y) * rotation;
             }
         }
     }
@@ -841,9 +1219,12 @@ impl Plugin for MapPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
         app.add_systems(Startup, load_tilemap_assets)
             .register_type::<TilemapAdjacency>()
+            .register_type::<MountSurface>()
+            .add_scene_preset("tile", tile_preset)
             .register_type::<adjacency::AdjacencyVariants<Handle<Mesh>>>()
             .add_networked_component::<TileEntity, TileEntityClient>()
-            .add_networked_component::<TileMap, TileMapClient>();
+            .add_networked_component::<TileMap, TileMapClient>()
+            .add_plugins(overlay::OverlayPlugin);
 
         if app
             .world
@@ -858,16 +1239,103 @@ impl Plugin for MapPlugin {
             .add_systems(
                 Update,
                 (
-                    client_initialize_tile_objects,
+                    client_mark_new_tile_entities,
                     client_update_tile_entities,
                     apply_deferred,
-                    client_update_adjacencies,
+                    // Tiles keep arriving while joining, update them all at once afterwards
+                    client_update_adjacencies.run_if(is_synchronized),
                 )
                     .chain(),
             );
         } else {
-            app.add_systems(Update, spawn_from_data)
+            app.add_asset::<io::MapFile>()
+                .init_asset_loader::<io::MapFileLoader>()
+                .init_resource::<journal::TileJournal>()
+                .add_systems(Update, (spawn_from_data, io::apply_tile_state))
                 .add_systems(PostUpdate, update_grid_aabb);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use super::{Direction, PlacementError, TileLayer, TileMap, TileReference};
+
+    const WALL: Entity = Entity::from_raw(1);
+    const FLOOR: Entity = Entity::from_raw(2);
+
+    /// A map with a floor at (1, 1) and a wall to the east of it
+    fn map() -> TileMap {
+        let mut map = TileMap::new(UVec2::ONE);
+        let turf = |turf| TileReference {
+            turf: Some(turf),
+            ..Default::default()
+        };
+        map.set_tile(UVec2::new(1, 1), turf(FLOOR)).unwrap();
+        map.set_tile(UVec2::new(2, 1), turf(WALL)).unwrap();
+        map
+    }
+
+    fn place_mount(
+        map: &TileMap,
+        position: UVec2,
+        direction: Direction,
+    ) -> Result<(), PlacementError> {
+        map.validate_placement(position, TileLayer::HighMount, Some(direction), |e| {
+            e == WALL
+        })
+    }
+
+    #[test]
+    fn mounts_on_floor_next_to_wall() {
+        assert_eq!(
+            place_mount(&map(), UVec2::new(1, 1), Direction::East),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn mounts_need_a_wall_in_their_direction() {
+        let map = map();
+        for direction in [Direction::North, Direction::South, Direction::West] {
+            assert_eq!(
+                place_mount(&map, UVec2::new(1, 1), direction),
+                Err(PlacementError::MissingSupport)
+            );
+        }
+        // Facing the edge of the map
+        assert_eq!(
+            place_mount(&map, UVec2::new(0, 1), Direction::West),
+            Err(PlacementError::MissingSupport)
+        );
+    }
+
+    #[test]
+    fn mounts_are_not_placed_inside_walls() {
+        let mut map = map();
+        let wall = TileReference {
+            turf: Some(WALL),
+            ..Default::default()
+        };
+        map.set_tile(UVec2::new(3, 1), wall).unwrap();
+        assert_eq!(
+            place_mount(&map, UVec2::new(2, 1), Direction::East),
+            Err(PlacementError::FaceBlocked)
+        );
+    }
+
+    #[test]
+    fn mount_slots_can_be_occupied() {
+        let mut map = map();
+        let mut tile = map.tile(UVec2::new(1, 1)).copied().unwrap();
+        let light = Entity::from_raw(3);
+        tile.high_mounts[Direction::East as usize] = Some(light);
+        map.set_tile(UVec2::new(1, 1), tile).unwrap();
+        assert_eq!(
+            place_mount(&map, UVec2::new(1, 1), Direction::East),
+            Err(PlacementError::Occupied(light))
+        );
+    }
+}
@@ -0,0 +1,402 @@
+use bevy::{
+    math::UVec2,
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+    utils::{HashMap, HashSet},
+};
+use networking::{
+    identity::{NetworkIdentities, NetworkIdentity},
+    messaging::{AppExt, MessageEvent, MessageReceivers, MessageSender},
+    visibility::NetworkVisibilities,
+    NetworkManager,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::CHUNK_SIZE;
+
+/// A kind of effect that can cover tiles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum TileOverlay {
+    Radiation,
+    Fire,
+    Fog,
+}
+
+impl TileOverlay {
+    pub const ALL: [TileOverlay; 3] = [TileOverlay::Radiation, TileOverlay::Fire, TileOverlay::Fog];
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+
+    fn color(self) -> Color {
+        match self {
+            TileOverlay::Radiation => Color::rgba(0.3, 1.0, 0.2, 0.25),
+            TileOverlay::Fire => Color::rgba(1.0, 0.45, 0.1, 0.4),
+            TileOverlay::Fog => Color::rgba(0.8, 0.8, 0.85, 0.35),
+        }
+    }
+}
+
+const TILES_PER_CHUNK: usize = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+/// Overlay bits of every tile in a chunk
+#[derive(Clone)]
+struct OverlayChunk([u8; TILES_PER_CHUNK]);
+
+impl Default for OverlayChunk {
+    fn default() -> Self {
+        Self([0; TILES_PER_CHUNK])
+    }
+}
+
+impl OverlayChunk {
+    fn is_empty(&self) -> bool {
+        self.0.iter().all(|&t| t == 0)
+    }
+
+    /// Run-length encodes the chunk as pairs of (count, bits)
+    fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for &bits in self.0.iter() {
+            match data.len() {
+                len if len >= 2 && data[len - 1] == bits && data[len - 2] < u8::MAX => {
+                    data[len - 2] += 1;
+                }
+                _ => data.extend([1, bits]),
+            }
+        }
+        data
+    }
+
+    fn decode(data: &[u8]) -> Option<Self> {
+        let mut chunk = Self::default();
+        let mut index = 0;
+        for pair in data.chunks_exact(2) {
+            let (count, bits) = (pair[0] as usize, pair[1]);
+            chunk.0.get_mut(index..index + count)?.fill(bits);
+            index += count;
+        }
+        (index == TILES_PER_CHUNK).then_some(chunk)
+    }
+}
+
+fn split_position(position: UVec2) -> (UVec2, usize) {
+    let chunk = position / CHUNK_SIZE;
+    let inside = position % CHUNK_SIZE;
+    (chunk, (inside.y * CHUNK_SIZE + inside.x) as usize)
+}
+
+/// Overlays on the tiles of a tilemap.
+///
+/// Lives on the tilemap entity on the server. Changed chunks are sent to clients observing the tilemap.
+#[derive(Component, Default)]
+pub struct TileOverlays {
+    chunks: HashMap<UVec2, OverlayChunk>,
+    dirty: HashSet<UVec2>,
+}
+
+impl TileOverlays {
+    pub fn set(&mut self, position: UVec2, overlay: TileOverlay, active: bool) {
+        let (chunk_position, index) = split_position(position);
+        if !active && !self.chunks.contains_key(&chunk_position) {
+            return;
+        }
+
+        let chunk = self.chunks.entry(chunk_position).or_default();
+        let old = chunk.0[index];
+        let new = match active {
+            true => old | overlay.bit(),
+            false => old & !overlay.bit(),
+        };
+        if old != new {
+            chunk.0[index] = new;
+            self.dirty.insert(chunk_position);
+        }
+    }
+
+    pub fn has(&self, position: UVec2, overlay: TileOverlay) -> bool {
+        let (chunk_position, index) = split_position(position);
+        self.chunks
+            .get(&chunk_position)
+            .map(|c| c.0[index] & overlay.bit() != 0)
+            .unwrap_or(false)
+    }
+
+    /// Removes an overlay from every tile.
+    pub fn clear(&mut self, overlay: TileOverlay) {
+        for (position, chunk) in self.chunks.iter_mut() {
+            let mut changed = false;
+            for bits in chunk.0.iter_mut().filter(|b| **b & overlay.bit() != 0) {
+                *bits &= !overlay.bit();
+                changed = true;
+            }
+            if changed {
+                self.dirty.insert(*position);
+            }
+        }
+    }
+}
+
+/// Server message containing the overlays of a chunk
+#[derive(Serialize, Deserialize)]
+struct OverlayChunkMessage {
+    tilemap: NetworkIdentity,
+    chunk: UVec2,
+    /// Run-length encoded overlay bits
+    data: Vec<u8>,
+}
+
+fn send_overlay_updates(
+    mut maps: Query<(&NetworkIdentity, &mut TileOverlays)>,
+    mut visibilities: ResMut<NetworkVisibilities>,
+    mut sender: MessageSender,
+) {
+    for (&identity, mut overlays) in maps.iter_mut() {
+        let overlays = &mut *overlays;
+        let Some(visibility) = visibilities.get_mut(identity) else {
+            continue;
+        };
+
+        // TODO: Only send chunks that are in range of the observer
+        let new_observers: HashSet<_> = visibility.new_observers().copied().collect();
+        if !new_observers.is_empty() {
+            for (&chunk, data) in overlays.chunks.iter() {
+                sender.send(
+                    &OverlayChunkMessage {
+                        tilemap: identity,
+                        chunk,
+                        data: data.encode(),
+                    },
+                    MessageReceivers::Set(new_observers.clone()),
+                );
+            }
+        }
+
+        if overlays.dirty.is_empty() {
+            continue;
+        }
+        let existing: HashSet<_> = visibility.existing_observers().copied().collect();
+        for chunk in overlays.dirty.drain() {
+            let Some(data) = overlays.chunks.get(&chunk) else {
+                continue;
+            };
+            if !existing.is_empty() {
+                sender.send(
+                    &OverlayChunkMessage {
+                        tilemap: identity,
+                        chunk,
+                        data: data.encode(),
+                    },
+                    MessageReceivers::Set(existing.clone()),
+                );
+            }
+            if data.is_empty() {
+                overlays.chunks.remove(&chunk);
+            }
+        }
+    }
+}
+
+/// Overlay state and rendering entities on the client
+#[derive(Component, Default)]
+struct TileOverlaysClient {
+    chunks: HashMap<UVec2, (OverlayChunk, Option<Entity>)>,
+    dirty: HashSet<UVec2>,
+}
+
+#[derive(Resource)]
+struct OverlayMaterial(Handle<StandardMaterial>);
+
+fn create_overlay_material(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let material = materials.add(StandardMaterial {
+        base_color: Color::WHITE,
+        alpha_mode: AlphaMode::Blend,
+        unlit: true,
+        ..Default::default()
+    });
+    commands.insert_resource(OverlayMaterial(material));
+}
+
+/// Chunks kept for tilemaps that aren't spawned yet, the oldest are dropped past this
+const MAX_PENDING_CHUNKS: usize = 4096;
+
+fn client_receive_overlays(
+    mut messages: EventReader<MessageEvent<OverlayChunkMessage>>,
+    mut pending: Local<Vec<OverlayChunkMessage>>,
+    identities: Res<NetworkIdentities>,
+    mut maps: Query<&mut TileOverlaysClient>,
+    mut commands: Commands,
+) {
+    let received = messages.iter().map(|e| OverlayChunkMessage {
+        tilemap: e.message.tilemap,
+        chunk: e.message.chunk,
+        data: e.message.data.clone(),
+    });
+    let all: Vec<_> = pending.drain(..).chain(received).collect();
+    // Overlays for tilemaps that didn't receive any yet
+    let mut new_maps: HashMap<Entity, TileOverlaysClient> = HashMap::default();
+
+    for message in all {
+        // The tilemap may not be spawned yet
+        let Some(entity) = identities.get_entity(message.tilemap) else {
+            pending.push(message);
+            continue;
+        };
+        let Some(chunk) = OverlayChunk::decode(&message.data) else {
+            warn!(chunk = ?message.chunk, "Received invalid overlay chunk");
+            continue;
+        };
+
+        let overlays = match maps.get_mut(entity) {
+            Ok(overlays) => overlays.into_inner(),
+            Err(_) => new_maps.entry(entity).or_default(),
+        };
+        let entry = overlays
+            .chunks
+            .entry(message.chunk)
+            .or_insert_with(|| (Default::default(), None));
+        entry.0 = chunk;
+        overlays.dirty.insert(message.chunk);
+    }
+
+    for (entity, overlays) in new_maps {
+        commands.entity(entity).insert(overlays);
+    }
+
+    let excess = pending.len().saturating_sub(MAX_PENDING_CHUNKS);
+    if excess > 0 {
+        warn!(
+            dropped = excess,
+            "Too many overlay chunks for unknown tilemaps"
+        );
+        pending.drain(..excess);
+    }
+}
+
+/// Height of overlay quads above the tile origin
+const OVERLAY_HEIGHT: f32 = 0.05;
+
+fn build_overlay_mesh(chunk_position: UVec2, chunk: &OverlayChunk) -> Mesh {
+    let mut positions = Vec::new();
+    let mut colors = Vec::new();
+    let mut indices = Vec::new();
+
+    for (index, &bits) in chunk.0.iter().enumerate() {
+        let active: Vec<_> = TileOverlay::ALL
+            .into_iter()
+            .filter(|o| bits & o.bit() != 0)
+            .collect();
+        if active.is_empty() {
+            continue;
+        }
+
+        // Average the colors, but keep the strongest opacity
+        let mut color = Vec4::ZERO;
+        let mut alpha: f32 = 0.0;
+        for overlay in active.iter() {
+            let c = overlay.color();
+            color += Vec4::from(c.as_rgba_f32());
+            alpha = alpha.max(c.a());
+        }
+        color /= active.len() as f32;
+        color.w = alpha;
+
+        let tile = chunk_position * CHUNK_SIZE
+            + UVec2::new(index as u32 % CHUNK_SIZE, index as u32 / CHUNK_SIZE);
+        let (x, z) = (tile.x as f32, tile.y as f32);
+        let start = positions.len() as u32;
+        positions.extend([
+            [x - 0.5, OVERLAY_HEIGHT, z - 0.5],
+            [x + 0.5, OVERLAY_HEIGHT, z - 0.5],
+            [x + 0.5, OVERLAY_HEIGHT, z + 0.5],
+            [x - 0.5, OVERLAY_HEIGHT, z + 0.5],
+        ]);
+        colors.extend([color.to_array(); 4]);
+        indices.extend([start, start + 2, start + 1, start, start + 3, start + 2]);
+    }
+
+    let normals = vec![[0.0, 1.0, 0.0]; positions.len()];
+    let uvs = vec![[0.0, 0.0]; positions.len()];
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+fn client_update_overlay_meshes(
+    mut maps: Query<(Entity, &mut TileOverlaysClient), Changed<TileOverlaysClient>>,
+    material: Res<OverlayMaterial>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut commands: Commands,
+) {
+    for (map_entity, mut overlays) in maps.iter_mut() {
+        let overlays = &mut *overlays;
+        for position in overlays.dirty.drain() {
+            let Some((chunk, mesh_entity)) = overlays.chunks.get_mut(&position) else {
+                continue;
+            };
+
+            if chunk.is_empty() {
+                if let Some(entity) = mesh_entity.take() {
+                    commands.entity(entity).despawn();
+                }
+                overlays.chunks.remove(&position);
+                continue;
+            }
+
+            let mesh = meshes.add(build_overlay_mesh(position, chunk));
+            match mesh_entity {
+                Some(entity) => {
+                    commands.entity(*entity).insert(mesh);
+                }
+                None => {
+                    let entity = commands
+                        .spawn(PbrBundle {
+                            mesh,
+                            material: material.0.clone(),
+                            ..Default::default()
+                        })
+                        .id();
+                    commands.entity(map_entity).add_child(entity);
+                    *mesh_entity = Some(entity);
+                }
+            }
+        }
+    }
+}
+
+pub(crate) struct OverlayPlugin;
+
+impl Plugin for OverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_network_message::<OverlayChunkMessage>();
+
+        if app
+            .world
+            .get_resource::<NetworkManager>()
+            .unwrap()
+            .is_server()
+        {
+            app.add_systems(PostUpdate, send_overlay_updates);
+        } else {
+            app.add_systems(Startup, create_overlay_material)
+                .add_systems(
+                    Update,
+                    (
+                        client_receive_overlays,
+                        apply_deferred,
+                        client_update_overlay_meshes,
+                    )
+                        .chain(),
+                );
+        }
+    }
+}
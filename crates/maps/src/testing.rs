@@ -0,0 +1,55 @@
+//! Helpers to set up tilemaps without loading any scenes
+
+use bevy::prelude::*;
+
+use crate::{tile_entity_bundle, Direction, TileEntity, TileEntityPath, TileLayer, TileMap};
+
+/// Spawns an empty tilemap of the given size in chunks
+pub fn spawn_tilemap(world: &mut World, size: UVec2) -> Entity {
+    world.spawn(TileMap::new(size)).id()
+}
+
+/// Spawns a bare entity into the furniture slot of a tile
+pub fn spawn_furniture(world: &mut World, tilemap: Entity, position: UVec2) -> Entity {
+    let entity = world
+        .spawn(TileEntity {
+            tilemap: tilemap.into(),
+            path: TileEntityPath {
+                position,
+                layer: TileLayer::Furniture,
+                index_in_layer: None,
+                direction: Direction::North,
+            }
+            .into(),
+        })
+        .id();
+    let mut map = world.get_mut::<TileMap>(tilemap).unwrap();
+    let mut tile = map.tile(position).copied().unwrap_or_default();
+    tile.furniture = Some(entity);
+    map.set_tile(position, tile).unwrap();
+    entity
+}
+
+/// Spawns a bare entity into a wall mount slot of a tile, at the position a mount scene is spawned at
+pub fn spawn_mount(
+    world: &mut World,
+    tilemap: Entity,
+    position: UVec2,
+    direction: Direction,
+) -> Entity {
+    let path = TileEntityPath {
+        position,
+        layer: TileLayer::HighMount,
+        index_in_layer: Some(direction as u8),
+        direction,
+    };
+    let (scene, tile_entity) = tile_entity_bundle(tilemap, Handle::default(), path);
+    let entity = world
+        .spawn((tile_entity, GlobalTransform::from(scene.transform)))
+        .id();
+    let mut map = world.get_mut::<TileMap>(tilemap).unwrap();
+    let mut tile = map.tile(position).copied().unwrap_or_default();
+    tile.high_mounts[direction as usize] = Some(entity);
+    map.set_tile(position, tile).unwrap();
+    entity
+}
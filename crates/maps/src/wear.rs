@@ -0,0 +1,296 @@
+//! Tiles that differ from the map they were spawned from, carried over between rounds.
+//!
+//! Only tiles flagged as modified in the [`TileMap`] are looked at, so saving stays cheap
+//! compared to a full [`MapFile`](crate::io::MapFile) snapshot. Files group tiles per chunk
+//! and store every scene path once.
+
+use std::{collections::BTreeMap, path::Path};
+
+use bevy::{
+    asset::{AssetPathId, HandleId},
+    math::UVec2,
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    journal::{scene_path, TileJournal},
+    Direction, TileEntity, TileMap, TileMapData, CHUNK_SIZE,
+};
+
+/// File extension of wear files
+pub const WEAR_FILE_EXTENSION: &str = "ssntwear";
+const WEAR_FILE_VERSION: u32 = 1;
+
+/// Final state of the tiles that changed on a map.
+/// Cables aren't part of the tile layers and aren't included.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(into = "WearFile", try_from = "WearFile")]
+pub struct MapWear {
+    pub base_map: String,
+    /// Size of the base map in tiles
+    pub size: UVec2,
+    /// Keyed by (y, x) to keep files stable
+    tiles: BTreeMap<(u32, u32), WornTile>,
+}
+
+#[derive(Clone, Default, PartialEq, Debug)]
+struct WornTile {
+    underfloor: Option<String>,
+    turf: Option<String>,
+    furniture: Option<String>,
+    furniture_direction: Direction,
+    high_mounts: [Option<String>; 4],
+}
+
+impl MapWear {
+    pub fn new(base_map: impl Into<String>, size: UVec2) -> Self {
+        Self {
+            base_map: base_map.into(),
+            size,
+            tiles: Default::default(),
+        }
+    }
+
+    /// Number of worn tiles
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    /// Records the current state of every modified tile that differs from the spawned map data.
+    /// Tiles ignored by the [`TileJournal`] are skipped. Returns the number of recorded tiles.
+    pub fn record(&mut self, world: &World, tilemap: Entity) -> usize {
+        let (Some(map), Some(data)) = (
+            world.get::<TileMap>(tilemap),
+            world.get::<TileMapData>(tilemap),
+        ) else {
+            return 0;
+        };
+        let journal = world.get_resource::<TileJournal>();
+        let server = world.resource::<AssetServer>();
+        let path = |entity: Option<Entity>| entity.and_then(|e| scene_path(world, e));
+        let asset_path = |id: Option<AssetPathId>| {
+            id.and_then(|id| server.get_handle_path(HandleId::AssetPathId(id)))
+                .map(|path| path.path().to_string_lossy().replace('\\', "/"))
+        };
+
+        let mut recorded = 0;
+        for position in map.modified_tiles() {
+            if journal.map_or(false, |j| j.is_ignored(position)) {
+                continue;
+            }
+            let Some(tile) = map.tile(position) else {
+                continue;
+            };
+            let current = WornTile {
+                underfloor: path(tile.underfloor),
+                turf: path(tile.turf),
+                furniture: path(tile.furniture),
+                furniture_direction: tile
+                    .furniture
+                    .and_then(|e| world.get::<TileEntity>(e))
+                    .map(|t| t.direction())
+                    .unwrap_or_default(),
+                high_mounts: tile.high_mounts.map(path),
+            };
+            let spawned = data
+                .tile_index(position)
+                .map(|index| &data.tiles[index])
+                .map(|tile| WornTile {
+                    underfloor: asset_path(tile.underfloor),
+                    turf: asset_path(tile.turf),
+                    furniture: asset_path(tile.furniture),
+                    furniture_direction: tile.furniture_direction,
+                    high_mounts: tile.high_mounts.map(asset_path),
+                })
+                .unwrap_or_default();
+
+            // Tiles worn in an earlier round and left alone keep their entry
+            if current != spawned {
+                self.tiles.insert((position.y, position.x), current);
+                recorded += 1;
+            }
+        }
+        recorded
+    }
+
+    /// Replaces the layers of worn tiles in map data before it's spawned.
+    /// Returns the number of replaced tiles.
+    pub fn apply(&self, data: &mut TileMapData) -> usize {
+        let asset = |path: &Option<String>| path.as_deref().map(AssetPathId::from);
+        let mut applied = 0;
+        for (&(y, x), tile) in self.tiles.iter() {
+            let Some(index) = data.tile_index(UVec2::new(x, y)) else {
+                warn!(position = ?UVec2::new(x, y), "Worn tile is out of bounds");
+                continue;
+            };
+            let target = &mut data.tiles[index];
+            target.underfloor = asset(&tile.underfloor);
+            target.turf = asset(&tile.turf);
+            target.furniture = asset(&tile.furniture);
+            target.furniture_direction = tile.furniture_direction;
+            target.high_mounts = [0, 1, 2, 3].map(|i| asset(&tile.high_mounts[i]));
+            applied += 1;
+        }
+        applied
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let text = ron::ser::to_string(self)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, text)
+    }
+}
+
+impl TileMapData {
+    fn tile_index(&self, position: UVec2) -> Option<usize> {
+        (position.x < self.size.x && position.y < self.size.y)
+            .then(|| (position.y * self.size.x + position.x) as usize)
+    }
+}
+
+/// On-disk layout of [`MapWear`]
+#[derive(Serialize, Deserialize)]
+struct WearFile {
+    version: u32,
+    base_map: String,
+    size: UVec2,
+    /// Scene paths referenced by index from the tiles
+    scenes: Vec<String>,
+    chunks: Vec<WornChunk>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct WornChunk {
+    /// Position in chunks
+    position: UVec2,
+    tiles: Vec<WornChunkTile>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct WornChunkTile {
+    /// Index of the tile inside the chunk
+    index: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    underfloor: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    turf: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    furniture: Option<u16>,
+    #[serde(default)]
+    furniture_direction: Direction,
+    #[serde(default)]
+    high_mounts: [Option<u16>; 4],
+}
+
+impl From<MapWear> for WearFile {
+    fn from(wear: MapWear) -> Self {
+        let mut scenes: Vec<String> = Vec::new();
+        let mut scene_index = |path: Option<String>| {
+            let path = path?;
+            let index = match scenes.iter().position(|s| *s == path) {
+                Some(index) => index,
+                None => {
+                    scenes.push(path);
+                    scenes.len() - 1
+                }
+            };
+            Some(index as u16)
+        };
+
+        let mut chunks: BTreeMap<(u32, u32), Vec<WornChunkTile>> = BTreeMap::new();
+        for ((y, x), tile) in wear.tiles {
+            let chunk = (y / CHUNK_SIZE, x / CHUNK_SIZE);
+            let index = ((y % CHUNK_SIZE) * CHUNK_SIZE + x % CHUNK_SIZE) as u8;
+            chunks.entry(chunk).or_default().push(WornChunkTile {
+                index,
+                underfloor: scene_index(tile.underfloor),
+                turf: scene_index(tile.turf),
+                furniture: scene_index(tile.furniture),
+                furniture_direction: tile.furniture_direction,
+                high_mounts: tile.high_mounts.map(&mut scene_index),
+            });
+        }
+
+        Self {
+            version: WEAR_FILE_VERSION,
+            base_map: wear.base_map,
+            size: wear.size,
+            scenes,
+            chunks: chunks
+                .into_iter()
+                .map(|((y, x), tiles)| WornChunk {
+                    position: UVec2::new(x, y),
+                    tiles,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<WearFile> for MapWear {
+    type Error = String;
+
+    fn try_from(file: WearFile) -> Result<Self, Self::Error> {
+        if file.version > WEAR_FILE_VERSION {
+            return Err(format!(
+                "Wear file version {} is not supported",
+                file.version
+            ));
+        }
+        let scene = |index: Option<u16>| -> Result<Option<String>, String> {
+            index
+                .map(|i| {
+                    file.scenes
+                        .get(i as usize)
+                        .cloned()
+                        .ok_or_else(|| format!("Scene index {} is out of range", i))
+                })
+                .transpose()
+        };
+
+        let mut tiles = BTreeMap::new();
+        for chunk in file.chunks.iter() {
+            let origin = chunk.position * CHUNK_SIZE;
+            for tile in chunk.tiles.iter() {
+                let position = origin
+                    + UVec2::new(
+                        tile.index as u32 % CHUNK_SIZE,
+                        tile.index as u32 / CHUNK_SIZE,
+                    );
+                let mut high_mounts: [Option<String>; 4] = Default::default();
+                for (slot, index) in high_mounts.iter_mut().zip(tile.high_mounts) {
+                    *slot = scene(index)?;
+                }
+                tiles.insert(
+                    (position.y, position.x),
+                    WornTile {
+                        underfloor: scene(tile.underfloor)?,
+                        turf: scene(tile.turf)?,
+                        furniture: scene(tile.furniture)?,
+                        furniture_direction: tile.furniture_direction,
+                        high_mounts,
+                    },
+                );
+            }
+        }
+
+        Ok(Self {
+            base_map: file.base_map,
+            size: file.size,
+            tiles,
+        })
+    }
+}
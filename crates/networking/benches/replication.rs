@@ -0,0 +1,45 @@
+use bevy::math::{Quat, Vec3};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use networking::{transform::bench as transform, visibility::bench::VisibilityWorld};
+
+/// A walking entity that turns every few steps, sampled at the server tick rate
+fn walking_path(steps: usize) -> Vec<(Vec3, Quat)> {
+    (0..steps)
+        .map(|i| {
+            let t = i as f32 / 60.0;
+            let position = Vec3::new(t.sin() * 10.0, 0.0, t * 4.0);
+            let rotation = Quat::from_rotation_y((i / 20) as f32 * 0.5);
+            (position, rotation)
+        })
+        .collect()
+}
+
+fn transforms(c: &mut Criterion) {
+    let path = walking_path(1000);
+    let serialized = transform::serialize_path(&path);
+
+    let mut group = c.benchmark_group("transform");
+    group.bench_function("diff", |b| {
+        b.iter(|| transform::diff_path(black_box(&path)))
+    });
+    group.bench_function("serialize", |b| {
+        b.iter(|| transform::serialize_path(black_box(&path)))
+    });
+    group.bench_function("deserialize_apply", |b| {
+        b.iter(|| transform::apply_updates(black_box(&serialized)))
+    });
+    group.finish();
+}
+
+fn visibility(c: &mut Criterion) {
+    let mut group = c.benchmark_group("visibility_grid");
+    for (entities, observers) in [(1_000, 10), (10_000, 50)] {
+        let mut world = VisibilityWorld::new(entities, observers, 250.0);
+        let id = BenchmarkId::new("step", format!("{}x{}", entities, observers));
+        group.bench_function(id, |b| b.iter(|| world.step()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, transforms, visibility);
+criterion_main!(benches);
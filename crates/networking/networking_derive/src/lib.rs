@@ -25,6 +25,9 @@ struct NetworkedInput {
     priority: i16,
     #[darling(default = "default_param")]
     param: Type,
+    /// Field holding the connection that receives `owner_only` fields
+    #[darling(default)]
+    owner: Option<Ident>,
 }
 
 fn default_param() -> Type {
@@ -40,6 +43,9 @@ struct NetworkedFieldInput {
     with: Option<FieldMethod>,
     #[darling(default)]
     updated: Option<Path>,
+    /// Only sent to the owner, everyone else sees the default value
+    #[darling(default)]
+    owner_only: bool,
 }
 
 #[derive(Debug)]
@@ -98,6 +104,7 @@ struct NetworkedField {
     networked_type: Type,
     with: Option<FieldMethod>,
     updated: Option<Path>,
+    owner_only: bool,
 }
 
 #[derive(Clone, Copy)]
@@ -162,6 +169,7 @@ fn parse_networked_field_input(
         networked_type: networked_type.to_owned(),
         with: input.with,
         updated: input.updated,
+        owner_only: input.owner_only,
     }))
 }
 
@@ -207,7 +215,7 @@ pub fn networked_derive(input: TokenStream) -> TokenStream {
         Err(err) => return err.write_errors().into(),
     };
 
-    let networked_fields: Vec<_> = match input
+    let mut networked_fields: Vec<_> = match input
         .data
         .take_struct()
         .expect("Should never be enum")
@@ -220,6 +228,26 @@ pub fn networked_derive(input: TokenStream) -> TokenStream {
         Err(err) => return err.write_errors().into(),
     };
 
+    // The owner variable is only tracked for changes, it is never sent
+    let owner = match &input.owner {
+        Some(owner) => match networked_fields.iter().position(|f| &f.ident == owner) {
+            Some(index) => Some(networked_fields.remove(index)),
+            None => {
+                return darling::Error::custom("'owner' must name a NetworkVar field")
+                    .with_span(owner)
+                    .write_errors()
+                    .into()
+            }
+        },
+        None => None,
+    };
+    let receiver_matters = networked_fields.iter().any(|f| f.owner_only);
+    if receiver_matters && owner.is_none() && matches!(side, NetworkedSide::Server) {
+        return darling::Error::custom("'owner_only' fields require an 'owner' attribute")
+            .write_errors()
+            .into();
+    }
+
     let (param_indices, params): (Vec<_>, Vec<_>) = networked_fields
         .iter()
         .enumerate()
@@ -278,20 +306,55 @@ pub fn networked_derive(input: TokenStream) -> TokenStream {
                             quote!(from(#variable_access))
                         },
                     };
+                    if !networked_field.owner_only {
+                        return quote_spanned! { var_name.span() =>
+                            let #changed_name = since_tick
+                                .map(|t| self.#var_name.has_changed_since(t))
+                                .unwrap_or(true);
+                            serde::Serialize::serialize(
+                                &#changed_name.then(|| {
+                                    networking::variable::ValueUpdate::<#networked_type>::#value_expression
+                                }),
+                                &mut serializer,
+                            )
+                            .unwrap();
+                        };
+                    }
+
+                    // A new owner has not received the value yet, even if it did not change.
+                    // Everyone else gets the default, so a previous owner does not keep stale values.
                     quote_spanned! { var_name.span() =>
                         let #changed_name = since_tick
-                            .map(|t| self.#var_name.has_changed_since(t))
+                            .map(|t| owner_changed || self.#var_name.has_changed_since(t))
                             .unwrap_or(true);
-                        serde::Serialize::serialize(
-                            &#changed_name.then(|| {
+                        let update = match is_owner {
+                            true => #changed_name.then(|| {
                                 networking::variable::ValueUpdate::<#networked_type>::#value_expression
                             }),
-                            &mut serializer,
-                        )
-                        .unwrap();
+                            false => (since_tick.is_some() && owner_changed).then(|| {
+                                networking::variable::ValueUpdate::<#networked_type>::owned(Default::default())
+                            }),
+                        };
+                        serde::Serialize::serialize(&update, &mut serializer).unwrap();
                     }
                 }).collect::<Vec<_>>();
 
+            let (receiver_name, owner_check) = match (&owner, receiver_matters) {
+                (Some(owner), true) => {
+                    let owner_name = &owner.ident;
+                    (
+                        quote!(receiver),
+                        quote! {
+                            let is_owner = receiver.is_some() && receiver == *self.#owner_name;
+                            let owner_changed = since_tick
+                                .map(|t| self.#owner_name.has_changed_since(t))
+                                .unwrap_or(true);
+                        },
+                    )
+                }
+                _ => (quote!(_), proc_macro2::TokenStream::new()),
+            };
+
             let serialize_body =
                 match writes.is_empty() {
                     true => {
@@ -313,6 +376,7 @@ pub fn networked_derive(input: TokenStream) -> TokenStream {
                                 networking::variable::serializer_options(),
                             );
 
+                            #owner_check
                             #(#writes)*
 
                             Some(writer.into_inner().into())
@@ -321,7 +385,7 @@ pub fn networked_derive(input: TokenStream) -> TokenStream {
                 };
 
             // Build trait update method
-            let field_updates = networked_fields.iter().map(|networked_field| {
+            let field_updates = networked_fields.iter().chain(owner.iter()).map(|networked_field| {
                 let var_name = &networked_field.ident;
 
                 quote! {
@@ -339,13 +403,13 @@ pub fn networked_derive(input: TokenStream) -> TokenStream {
                     type Param = #paramset;
 
                     fn receiver_matters() -> bool {
-                        false
+                        #receiver_matters
                     }
 
                     fn serialize<'w, 's>(
                         &self,
                         #method_param,
-                        _: Option<networking::ConnectionId>,
+                        #receiver_name: Option<networking::ConnectionId>,
                         since_tick: Option<u32>,
                     ) -> Option<networking::variable::Bytes> {
                         #serialize_body
@@ -0,0 +1,93 @@
+//! Accounting of the bytes the server sends, grouped by the feature that sent them.
+//!
+//! Messages count towards their type, and component updates towards the component type.
+//! Transforms and timing have their own channels and are counted separately.
+//! Join snapshots are counted as a whole, after compression.
+//! Usage is summed up per minute. The totals of the last full minute are logged and kept in [`BandwidthUsage`].
+
+use bevy::{
+    prelude::*,
+    utils::{get_short_name, HashMap},
+};
+use serde::{Deserialize, Serialize};
+
+/// Seconds usage is summed up over
+pub const BANDWIDTH_WINDOW_SECONDS: f32 = 60.0;
+/// Features with the most usage that are logged every window
+const LOGGED_FEATURES: usize = 8;
+
+pub(crate) const TRANSFORMS_FEATURE: &str = "transforms";
+pub(crate) const TIMING_FEATURE: &str = "timing";
+pub(crate) const SNAPSHOT_FEATURE: &str = "join snapshots";
+
+/// Bytes sent for a feature in one window, counting every receiver
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FeatureUsage {
+    pub feature: String,
+    pub bytes: u64,
+    pub messages: u64,
+}
+
+#[derive(Resource, Default)]
+pub struct BandwidthUsage {
+    current: HashMap<&'static str, (u64, u64)>,
+    window_start: f32,
+    last_window: Vec<FeatureUsage>,
+}
+
+impl BandwidthUsage {
+    /// Counts a message sent to `receivers` connections
+    pub(crate) fn record(&mut self, feature: &'static str, bytes: usize, receivers: usize) {
+        let (total_bytes, messages) = self.current.entry(feature).or_default();
+        *total_bytes += (bytes * receivers) as u64;
+        *messages += receivers as u64;
+    }
+
+    /// Usage in the last full window, most bytes first
+    pub fn last_window(&self) -> &[FeatureUsage] {
+        &self.last_window
+    }
+}
+
+/// Starts a new window once the current one is full, logging the usage of the old one
+fn rotate_window(mut usage: ResMut<BandwidthUsage>, time: Res<Time>) {
+    let now = time.elapsed_seconds();
+    if usage.window_start + BANDWIDTH_WINDOW_SECONDS > now {
+        return;
+    }
+    usage.window_start = now;
+
+    let mut features: Vec<_> = usage
+        .current
+        .drain()
+        .map(|(feature, (bytes, messages))| FeatureUsage {
+            feature: get_short_name(feature),
+            bytes,
+            messages,
+        })
+        .collect();
+    features.sort_by(|a, b| b.bytes.cmp(&a.bytes));
+
+    let total: u64 = features.iter().map(|f| f.bytes).sum();
+    if total > 0 {
+        info!(kib = total / 1024, "Bandwidth used in the last minute");
+        for feature in features.iter().take(LOGGED_FEATURES) {
+            info!(
+                feature = feature.feature,
+                kib = feature.bytes / 1024,
+                messages = feature.messages,
+                "Bandwidth by feature"
+            );
+        }
+    }
+    usage.last_window = features;
+}
+
+pub(crate) struct BandwidthPlugin;
+
+impl Plugin for BandwidthPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BandwidthUsage>()
+            .add_systems(Last, rotate_window);
+    }
+}
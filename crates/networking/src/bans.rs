@@ -0,0 +1,75 @@
+use std::{
+    net::IpAddr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{prelude::Resource, utils::Uuid};
+use serde::{Deserialize, Serialize};
+
+/// Seconds since the unix epoch
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Keeps a user from joining, also matching the address they last connected from
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Ban {
+    pub id: Uuid,
+    /// Name of the user when they were banned, to find the ban again
+    pub username: String,
+    pub address: Option<IpAddr>,
+    pub reason: String,
+    /// Unix timestamp in seconds when the ban ends. Permanent if `None`.
+    pub expires: Option<u64>,
+}
+
+impl Ban {
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires.map_or(false, |expires| expires <= now)
+    }
+}
+
+/// Bans checked when users connect.
+/// The server is responsible for loading and saving them.
+#[derive(Resource, Default)]
+pub struct BannedPlayers {
+    bans: Vec<Ban>,
+}
+
+impl BannedPlayers {
+    /// Adds a ban, replacing any previous ban of the user
+    pub fn ban(&mut self, ban: Ban) {
+        self.bans.retain(|b| b.id != ban.id);
+        self.bans.push(ban);
+    }
+
+    /// Lifts the ban of a user, by id or case-insensitive username
+    pub fn unban(&mut self, user: &str) -> Option<Ban> {
+        let index = self
+            .bans
+            .iter()
+            .position(|b| b.id.to_string() == user || b.username.eq_ignore_ascii_case(user))?;
+        Some(self.bans.remove(index))
+    }
+
+    /// Finds an active ban for a user or the address they connect from
+    pub fn find(&self, id: &Uuid, address: Option<IpAddr>, now: u64) -> Option<&Ban> {
+        self.bans.iter().find(|b| {
+            !b.is_expired(now) && (b.id == *id || (address.is_some() && b.address == address))
+        })
+    }
+
+    pub fn bans(&self) -> &[Ban] {
+        &self.bans
+    }
+
+    /// Returns true if any bans ran out
+    pub fn remove_expired(&mut self, now: u64) -> bool {
+        let count = self.bans.len();
+        self.bans.retain(|b| !b.is_expired(now));
+        self.bans.len() != count
+    }
+}
@@ -6,7 +6,8 @@ use serde::{Deserialize, Serialize};
 use crate::{
     identity::{NetworkIdentities, NetworkIdentity},
     messaging::{AppExt as MessagingAppExt, MessageEvent, MessageReceivers, MessageSender},
-    time::ServerNetworkTime,
+    protocol::ProtocolDescription,
+    time::{ClientNetworkTime, ServerNetworkTime},
     variable::*,
     visibility::NetworkVisibilities,
     ConnectionId, NetworkManager, NetworkSet,
@@ -44,6 +45,37 @@ impl From<u16> for ComponentNetworkId {
 
 type NetworkedComponentRegistry = NetworkRegistry<ComponentNetworkId>;
 
+/// Server tick at which the client last received component data for an entity.
+/// Useful to tell entities that stopped being updated apart from ones that never changed.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct LastComponentUpdate {
+    pub tick: u32,
+}
+
+fn record_component_updates(
+    mut events: EventReader<MessageEvent<NetworkedComponentMessage>>,
+    mut updates: Query<&mut LastComponentUpdate>,
+    identities: Res<NetworkIdentities>,
+    network_time: Res<ClientNetworkTime>,
+    mut commands: Commands,
+) {
+    let Some(tick) = network_time.server_tick() else {
+        events.clear();
+        return;
+    };
+    for event in events.iter() {
+        let Some(entity) = identities.get_entity(event.message.identity) else {
+            continue;
+        };
+        match updates.get_mut(entity) {
+            Ok(mut update) => update.tick = tick,
+            Err(_) => {
+                commands.entity(entity).insert(LastComponentUpdate { tick });
+            }
+        }
+    }
+}
+
 fn send_networked_component_changed<S: NetworkedToClient + Component, C: NetworkedFromServer>(
     mut components: Query<(&NetworkIdentity, &mut S), Changed<S>>,
     visibilities: Res<NetworkVisibilities>,
@@ -91,7 +123,7 @@ fn send_networked_component_changed<S: NetworkedToClient + Component, C: Network
                     None => continue,
                 };
 
-                sender.send_with_priority(
+                sender.send_for_feature(
                     &NetworkedComponentMessage {
                         identity: *identity,
                         component_id,
@@ -99,13 +131,14 @@ fn send_networked_component_changed<S: NetworkedToClient + Component, C: Network
                     },
                     MessageReceivers::Single(*connection),
                     priority,
+                    std::any::type_name::<S>(),
                 );
             }
         } else {
             let Some(data) = component.serialize(&mut param, None, None) else {
                 continue;
             };
-            sender.send_with_priority(
+            sender.send_for_feature(
                 &NetworkedComponentMessage {
                     identity: *identity,
                     component_id,
@@ -113,6 +146,7 @@ fn send_networked_component_changed<S: NetworkedToClient + Component, C: Network
                 },
                 MessageReceivers::Set(observer_cache.clone()),
                 priority,
+                std::any::type_name::<S>(),
             );
         }
     }
@@ -143,7 +177,7 @@ fn send_networked_component_to_new<S: NetworkedToClient + Component, C: Networke
                 };
                 let priority = component.priority();
 
-                sender.send_with_priority(
+                sender.send_for_feature(
                     &NetworkedComponentMessage {
                         identity: *identity,
                         component_id,
@@ -151,6 +185,7 @@ fn send_networked_component_to_new<S: NetworkedToClient + Component, C: Networke
                     },
                     MessageReceivers::Single(*connection),
                     priority,
+                    std::any::type_name::<S>(),
                 );
             }
         } else {
@@ -159,7 +194,7 @@ fn send_networked_component_to_new<S: NetworkedToClient + Component, C: Networke
                 let data = component
                     .serialize(&mut param, None, None)
                     .expect("Serializing without a specific receiver should always return data");
-                sender.send_with_priority(
+                sender.send_for_feature(
                     &NetworkedComponentMessage {
                         identity: *identity,
                         component_id,
@@ -167,6 +202,7 @@ fn send_networked_component_to_new<S: NetworkedToClient + Component, C: Networke
                     },
                     MessageReceivers::Set(new_observers),
                     component.priority(),
+                    std::any::type_name::<S>(),
                 );
             }
         }
@@ -261,13 +297,14 @@ fn send_networked_component_removed<S: NetworkedToClient + Component, C: Network
 
         let observers: HashSet<_> = visibility.observers().copied().collect();
         if !observers.is_empty() {
-            sender.send_with_priority(
+            sender.send_for_feature(
                 &RemoveNetworkedComponentMessage {
                     identity,
                     component_id,
                 },
                 MessageReceivers::Set(observers),
                 -10,
+                std::any::type_name::<S>(),
             );
         }
     }
@@ -318,6 +355,10 @@ impl AppExt for App {
         if !registry.register::<C>() {
             panic!("Client component was already registered");
         }
+        self.init_resource::<ProtocolDescription>()
+            .world
+            .resource_mut::<ProtocolDescription>()
+            .add_component::<S, C>();
         if self.world.resource::<NetworkManager>().is_server() {
             self.add_systems(
                 PostUpdate,
@@ -354,5 +395,12 @@ impl Plugin for ComponentPlugin {
         app.init_resource::<NetworkedComponentRegistry>()
             .add_network_message::<NetworkedComponentMessage>()
             .add_network_message::<RemoveNetworkedComponentMessage>();
+
+        if app.world.resource::<NetworkManager>().is_client() {
+            app.add_systems(
+                PreUpdate,
+                record_component_updates.in_set(NetworkSet::ClientApply),
+            );
+        }
     }
 }
@@ -0,0 +1,139 @@
+//! Validation of what clients send, before any system reads their messages.
+//!
+//! Messages are checked against size limits and rate limits per connection and message type.
+//! Rejected messages add to the violation score of the connection in [`MessageViolations`].
+//! Connections are flagged once their score passes [`GuardConfig::flag_score`],
+//! and disconnected once it passes [`GuardConfig::disconnect_score`].
+
+use bevy::{
+    prelude::*,
+    utils::{get_short_name, HashMap},
+};
+use serde::Deserialize;
+
+use crate::{
+    messaging::{MessageLimits, MessageTypes, MessageViolations},
+    ConnectionId,
+};
+
+/// How the server treats misbehaving clients, usually read from the server config
+#[derive(Resource, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct GuardConfig {
+    /// Largest message in bytes for types without their own limit
+    pub max_message_size: usize,
+    /// Messages per second a connection may send of one type
+    pub messages_per_second: f32,
+    /// Messages of one type a connection may send at once after being idle
+    pub burst: f32,
+    /// Messages per second for specific types, by type name without its path
+    pub type_rates: HashMap<String, f32>,
+    /// Largest message in bytes for specific types, by type name without its path
+    pub type_sizes: HashMap<String, usize>,
+    pub oversized_penalty: f32,
+    pub malformed_penalty: f32,
+    pub rate_limited_penalty: f32,
+    /// Score at which a connection is reported with [`ConnectionFlagged`]
+    pub flag_score: f32,
+    /// Score at which a connection is disconnected
+    pub disconnect_score: f32,
+    pub decay_per_second: f32,
+}
+
+impl Default for GuardConfig {
+    fn default() -> Self {
+        Self {
+            max_message_size: 16 * 1024,
+            messages_per_second: 60.0,
+            burst: 120.0,
+            type_rates: HashMap::default(),
+            type_sizes: HashMap::default(),
+            oversized_penalty: 5.0,
+            malformed_penalty: 2.0,
+            rate_limited_penalty: 0.5,
+            flag_score: 10.0,
+            disconnect_score: 20.0,
+            decay_per_second: 1.0,
+        }
+    }
+}
+
+impl GuardConfig {
+    pub(crate) fn limits(&self) -> MessageLimits {
+        let mut limits = MessageLimits::default();
+        limits.default_size = self.max_message_size;
+        limits.type_sizes = self.type_sizes.clone();
+        limits
+    }
+
+    pub(crate) fn violations(&self) -> MessageViolations {
+        let mut violations = MessageViolations::default();
+        violations.oversized_penalty = self.oversized_penalty;
+        violations.malformed_penalty = self.malformed_penalty;
+        violations.rate_limited_penalty = self.rate_limited_penalty;
+        violations.flag_threshold = self.flag_score;
+        violations.threshold = self.disconnect_score;
+        violations.decay_per_second = self.decay_per_second;
+        violations
+    }
+}
+
+/// Sent when a connection's violation score passes the flag score
+#[derive(Event, Clone, Copy, Debug)]
+pub struct ConnectionFlagged {
+    pub connection: ConnectionId,
+    pub score: f32,
+}
+
+/// Seconds after which a bucket that was not used is forgotten, it is full again by then
+const BUCKET_TIMEOUT_SECONDS: f32 = 10.0;
+
+struct Bucket {
+    tokens: f32,
+    last_update: f32,
+}
+
+/// Token buckets limiting how fast each connection sends each message type
+#[derive(Resource, Default)]
+pub(crate) struct RateLimits {
+    buckets: HashMap<(ConnectionId, u16), Bucket>,
+    /// Messages per second by type id, resolved from the configured type names
+    rates: HashMap<u16, f32>,
+}
+
+impl RateLimits {
+    /// Takes a token for the message, returns false if the connection sends this type too fast
+    pub(crate) fn admit(
+        &mut self,
+        connection: ConnectionId,
+        type_id: u16,
+        types: &MessageTypes,
+        config: &GuardConfig,
+        now: f32,
+    ) -> bool {
+        let rate = *self.rates.entry(type_id).or_insert_with(|| {
+            types
+                .name(type_id)
+                .and_then(|name| config.type_rates.get(&get_short_name(name)))
+                .copied()
+                .unwrap_or(config.messages_per_second)
+        });
+        let capacity = config.burst.max(rate);
+        let bucket = self.buckets.entry((connection, type_id)).or_insert(Bucket {
+            tokens: capacity,
+            last_update: now,
+        });
+        bucket.tokens = (bucket.tokens + (now - bucket.last_update) * rate).min(capacity);
+        bucket.last_update = now;
+        if bucket.tokens < 1.0 {
+            return false;
+        }
+        bucket.tokens -= 1.0;
+        true
+    }
+
+    pub(crate) fn forget_idle(&mut self, now: f32) {
+        self.buckets
+            .retain(|_, bucket| now - bucket.last_update < BUCKET_TIMEOUT_SECONDS);
+    }
+}
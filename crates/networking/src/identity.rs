@@ -17,6 +17,11 @@ impl NetworkIdentity {
     pub(crate) fn next(&self) -> Self {
         Self(self.0 + 1)
     }
+
+    #[cfg(feature = "bench")]
+    pub(crate) const fn from_raw(id: u32) -> Self {
+        Self(id)
+    }
 }
 
 // Mock implementation for component reflection
@@ -56,6 +61,11 @@ impl NetworkIdentities {
     pub fn get_identity(&self, entity: Entity) -> Option<NetworkIdentity> {
         self.entities.get(&entity).copied()
     }
+
+    /// All entities that have a network identity registered
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.entities.keys().copied()
+    }
 }
 
 pub struct NetworkCommand {
@@ -1,26 +1,41 @@
 #![allow(clippy::type_complexity)]
 
+pub mod bandwidth;
+pub mod bans;
 pub mod component;
+pub mod guard;
 pub mod identity;
+pub mod local;
 pub mod messaging;
+pub mod protocol;
 pub mod resource;
 pub mod scene;
+mod snapshot;
 pub mod spawning;
+pub mod stats;
+pub mod sync;
 pub mod time;
 pub mod transform;
 pub mod variable;
 pub mod visibility;
 
+#[cfg(feature = "testing")]
+#[doc(hidden)]
+pub mod testing;
+
 pub use bevy_renet::renet::transport::{ConnectToken, ServerAuthentication};
 pub use networking_derive::Networked;
 
+use bandwidth::BandwidthPlugin;
+use bans::BannedPlayers;
 use bevy_renet::{
     renet::{
         transport::{
-            ClientAuthentication, NetcodeClientTransport, NetcodeError, NetcodeServerTransport,
-            NetcodeTransportError, ServerConfig,
+            ClientAuthentication, NetcodeClientTransport, NetcodeDisconnectReason, NetcodeError,
+            NetcodeServerTransport, NetcodeTransportError, ServerConfig, TokenGenerationError,
+            NETCODE_KEY_BYTES, NETCODE_USER_DATA_BYTES,
         },
-        ConnectionConfig, RenetClient, RenetServer,
+        ConnectionConfig, DisconnectReason, RenetClient, RenetServer,
     },
     transport::{NetcodeClientPlugin, NetcodeServerPlugin},
     RenetClientPlugin, RenetServerPlugin,
@@ -28,10 +43,11 @@ use bevy_renet::{
 use component::ComponentPlugin;
 use resource::ResourcePlugin;
 use scene::ScenePlugin;
+use stats::StatsPlugin;
 use time::{ClientNetworkTime, ServerNetworkTime, TimePlugin};
 
 use std::{
-    collections::hash_map::DefaultHasher,
+    collections::{hash_map::DefaultHasher, VecDeque},
     fmt::Display,
     hash::{Hash, Hasher},
     net::{IpAddr, SocketAddr, UdpSocket},
@@ -45,14 +61,45 @@ use bevy::{
     utils::{HashMap, Uuid},
 };
 use identity::IdentityPlugin;
+use local::{LocalClientTransport, LocalServerAddress, LocalTransportPlugin};
 use messaging::{AppExt, Channel, MessageEvent, MessageReceivers, MessageSender, MessagingPlugin};
 use serde::{Deserialize, Serialize};
 use spawning::SpawningPlugin;
+use sync::{InitialSync, SyncPlugin};
 use transform::TransformPlugin;
 use visibility::VisibilityPlugin;
 
 /// A "unique" id for the protocol used by this application
 const PROTOCOL_ID: u64 = 859058192;
+/// Clients need to run the same version as the server to join
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// How long connect tokens can be used to join
+const TOKEN_EXPIRE_SECONDS: u64 = 300;
+/// Seconds without packets until a connection times out
+const TOKEN_TIMEOUT_SECONDS: i32 = 15;
+/// Version of the user data layout in connect tokens.
+///
+/// Anything issuing tokens for a server has to follow this layout in the
+/// [`NETCODE_USER_DATA_BYTES`] of user data, otherwise the client is rejected
+/// with [`RejectionReason::InvalidToken`]:
+///
+/// | Bytes      | Content                                       |
+/// |------------|-----------------------------------------------|
+/// | 0          | Layout version, currently 1                   |
+/// | 1..17      | Player id, a non-nil UUID in big endian bytes |
+/// | 17         | Length of the username in bytes, at least 1   |
+/// | 18..       | Username as UTF-8, the rest is zeroed         |
+///
+/// See [`generate_connect_token`] for creating tokens in Rust.
+pub const USER_DATA_VERSION: u8 = 1;
+/// Seconds a rejected client has to receive the reason before it is disconnected
+const REJECTION_GRACE_SECONDS: f32 = 1.0;
+/// Seconds the slot of a player who lost connection is kept for them
+const RECONNECT_GRACE_SECONDS: f32 = 60.0;
+/// How often the client tries to get back into a server it lost connection to
+const RECONNECT_ATTEMPTS: u32 = 5;
+/// Seconds between reconnect attempts
+const RECONNECT_DELAY_SECONDS: f32 = 2.0;
 
 #[derive(PartialEq, Eq, Clone, Copy)]
 pub enum NetworkRole {
@@ -87,6 +134,7 @@ pub enum ClientState {
 #[derive(Event, Debug, Clone, Eq, PartialEq)]
 pub enum ClientEvent {
     Join(TargetServer),
+    /// Connected and received the entities around the player
     Joined,
     JoinFailed(String),
     Disconnected(String),
@@ -95,8 +143,13 @@ pub enum ClientEvent {
 /// Specifies the target server to join.
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum TargetServer {
+    /// Joins without a token, claiming the configured username.
+    /// Only servers without a registration accept this, servers with a private key
+    /// drop connection requests that weren't signed with it.
     Raw(SocketAddr),
     Token(Box<ConnectToken>),
+    /// A server in the same process, see [`local`]
+    Local,
 }
 
 impl Display for TargetServer {
@@ -109,6 +162,9 @@ impl Display for TargetServer {
             TargetServer::Token(_) => {
                 write!(f, "(opaque token)")
             }
+            TargetServer::Local => {
+                write!(f, "(local server)")
+            }
         }
     }
 }
@@ -123,26 +179,224 @@ pub enum ClientTask {
 pub enum ServerEvent {
     PlayerConnected(ConnectionId),
     PlayerDisconnected(ConnectionId),
+    /// A spectator took a free player slot
+    SpectatorPromoted(ConnectionId),
 }
 
+#[derive(Event, Debug, Clone, Eq, PartialEq, Hash)]
+pub enum ServerTask {
+    /// Disconnects a player, telling them why
+    Kick {
+        connection: ConnectionId,
+        reason: String,
+    },
+}
+
+/// Username to join servers without authentication with
 #[derive(Resource)]
 pub struct UserData {
     pub username: String,
 }
 
+/// How the client wants to join the next server
+#[derive(Resource, Default)]
+pub struct JoinOptions {
+    /// Only watch the round, without taking a player slot
+    pub spectate: bool,
+}
+
+/// Whether the local client is spectating, received from the server
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientRole {
+    pub spectator: bool,
+    /// Place in the queue for a player slot, starting at 0
+    pub queue_position: Option<usize>,
+}
+
+/// The server the client last joined, to get back into it after losing connection.
+///
+/// Only servers joined without a token are reconnected to. Netcode refuses a token that is
+/// used again from another address or after it expired, and the client has no way to get
+/// a new one from whoever issued it.
+#[derive(Resource)]
+struct Reconnect {
+    target: TargetServer,
+    /// Key of the last session on the server, to resume it
+    session: Option<Uuid>,
+    attempts_left: u32,
+    /// When to try next, if the connection was lost
+    next_attempt: Option<f32>,
+    /// The current join is an attempt to get back in
+    reconnecting: bool,
+}
+
+impl Reconnect {
+    fn new(target: TargetServer) -> Self {
+        Self {
+            target,
+            session: None,
+            attempts_left: RECONNECT_ATTEMPTS,
+            next_attempt: None,
+            reconnecting: false,
+        }
+    }
+
+    /// Schedules another attempt, if there are any left
+    fn schedule(&mut self, now: f32) -> bool {
+        if self.attempts_left == 0 {
+            return false;
+        }
+        self.attempts_left -= 1;
+        self.next_attempt = Some(now + RECONNECT_DELAY_SECONDS);
+        true
+    }
+}
+
+/// Who a connection belongs to, carried in the user data of the connect token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserIdentity {
+    pub id: Uuid,
+    pub username: String,
+}
+
+impl UserIdentity {
+    /// Longest username that fits into the user data
+    pub const MAX_USERNAME_BYTES: usize = NETCODE_USER_DATA_BYTES - 18;
+
+    /// Writes the identity in the layout described at [`USER_DATA_VERSION`]
+    fn to_user_data(&self) -> [u8; NETCODE_USER_DATA_BYTES] {
+        let mut data = [0; NETCODE_USER_DATA_BYTES];
+        data[0] = USER_DATA_VERSION;
+        data[1..17].copy_from_slice(self.id.as_bytes());
+        let mut length = self.username.len().min(Self::MAX_USERNAME_BYTES);
+        while !self.username.is_char_boundary(length) {
+            length -= 1;
+        }
+        data[17] = length as u8;
+        data[18..18 + length].copy_from_slice(&self.username.as_bytes()[..length]);
+        data
+    }
+
+    fn from_user_data(data: &[u8; NETCODE_USER_DATA_BYTES]) -> Option<Self> {
+        if data[0] != USER_DATA_VERSION {
+            return None;
+        }
+        let id = Uuid::from_slice(&data[1..17]).ok()?;
+        let length = data[17] as usize;
+        if id.is_nil() || length == 0 || length > Self::MAX_USERNAME_BYTES {
+            return None;
+        }
+        let username = std::str::from_utf8(&data[18..18 + length]).ok()?;
+        Some(Self {
+            id,
+            username: username.to_owned(),
+        })
+    }
+}
+
+/// Issues a token for a user to join the servers at the given addresses.
+/// Only whoever knows the private key of a server can create tokens for it.
+pub fn generate_connect_token(
+    private_key: &[u8; NETCODE_KEY_BYTES],
+    server_addresses: Vec<SocketAddr>,
+    identity: &UserIdentity,
+) -> Result<ConnectToken, TokenGenerationError> {
+    let current_time = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap();
+    let (high, low) = identity.id.as_u64_pair();
+    ConnectToken::generate(
+        current_time,
+        PROTOCOL_ID,
+        TOKEN_EXPIRE_SECONDS,
+        high ^ low,
+        TOKEN_TIMEOUT_SECONDS,
+        server_addresses,
+        Some(&identity.to_user_data()),
+        private_key,
+    )
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct ClientHello {
-    token: Vec<u8>,
     version: String,
-    // TODO: Put these into the token
-    username: String,
-    id: Uuid,
+    spectate: bool,
+    /// Session key from the last connection, to take it over after losing connection
+    resume: Option<Uuid>,
+}
+
+/// Why the server refused a client
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum RejectionReason {
+    VersionMismatch {
+        server: String,
+        client: String,
+    },
+    InvalidToken,
+    AlreadyConnected,
+    /// Bans without `minutes_left` are permanent
+    Banned {
+        reason: String,
+        minutes_left: Option<u64>,
+    },
+    Kicked {
+        reason: String,
+    },
+}
+
+impl Display for RejectionReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RejectionReason::VersionMismatch { server, client } => write!(
+                f,
+                "Version mismatch: server runs {}, client runs {}",
+                server, client
+            ),
+            RejectionReason::InvalidToken => write!(f, "Invalid connect token"),
+            RejectionReason::AlreadyConnected => {
+                write!(f, "This user is already connected to the server")
+            }
+            RejectionReason::Banned {
+                reason,
+                minutes_left: Some(minutes),
+            } => write!(
+                f,
+                "You are banned from this server for {} more minutes: {}",
+                minutes, reason
+            ),
+            RejectionReason::Banned {
+                reason,
+                minutes_left: None,
+            } => write!(f, "You are permanently banned from this server: {}", reason),
+            RejectionReason::Kicked { reason } => write!(f, "Kicked from the server: {}", reason),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct ConnectionRejected {
+    reason: RejectionReason,
+}
+
+/// Tells a client if it's a spectator and where it waits for a player slot
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct SpectatorStatus {
+    spectator: bool,
+    queue_position: Option<usize>,
+}
+
+/// Sent by spectators to start or stop waiting for a player slot
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RequestPlayerSlot {
+    pub waiting: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct ServerInfo {
     /// How many seconds a server tick takes
     tick_duration_seconds: f32,
+    /// Lets the client take over this connection if it reconnects from another address
+    session: Uuid,
 }
 
 pub fn create_server(
@@ -175,8 +429,23 @@ fn connection_config() -> ConnectionConfig {
     }
 }
 
+/// Identity for servers without authentication.
+/// Anyone can claim any username there, so the id is derived from it.
+fn unverified_identity(username: String) -> UserIdentity {
+    let mut hasher = DefaultHasher::default();
+    username.hash(&mut hasher);
+    let hash = hasher.finish();
+    UserIdentity {
+        id: Uuid::from_u64_pair(hash, hash),
+        username,
+    }
+}
+
 fn handle_joining_server(
     mut events: EventReader<ClientEvent>,
+    data: Option<Res<UserData>>,
+    reconnect: Option<Res<Reconnect>>,
+    local: Option<Res<LocalServerAddress>>,
     state: ResMut<State<ClientState>>,
     mut next_state: ResMut<NextState<ClientState>>,
     mut commands: Commands,
@@ -190,25 +459,67 @@ fn handle_joining_server(
                 _ => {
                     next_state.set(ClientState::Joining);
                     info!("Joining server {}", target);
+                    commands.remove_resource::<InitialSync>();
+                    commands.insert_resource(ClientRole::default());
+                    // Joining another server gives up on getting back into the last one
+                    match target {
+                        TargetServer::Raw(_) => {
+                            if reconnect.as_ref().map_or(true, |r| r.target != *target) {
+                                commands.insert_resource(Reconnect::new(target.clone()));
+                            }
+                        }
+                        // Connect tokens can't be reused, see `Reconnect`
+                        TargetServer::Token(_) | TargetServer::Local => {
+                            commands.remove_resource::<Reconnect>();
+                        }
+                    }
+
+                    let username = || {
+                        data.as_ref()
+                            .map(|d| d.username.clone())
+                            .unwrap_or_else(|| "Beep".to_string())
+                    };
 
-                    let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
                     let current_time = SystemTime::now()
                         .duration_since(SystemTime::UNIX_EPOCH)
                         .unwrap();
                     let auth = match target {
                         TargetServer::Raw(address) => {
                             let client_id = current_time.as_millis() as u64;
+                            let username = username();
                             ClientAuthentication::Unsecure {
                                 protocol_id: PROTOCOL_ID,
                                 client_id,
                                 server_addr: *address,
-                                user_data: None,
+                                user_data: Some(unverified_identity(username).to_user_data()),
                             }
                         }
                         TargetServer::Token(token) => ClientAuthentication::Secure {
                             connect_token: *token.clone(),
                         },
+                        TargetServer::Local => {
+                            let identity = unverified_identity(username());
+                            match local.as_ref().and_then(|address| address.connect(identity)) {
+                                Some(transport) => {
+                                    commands.insert_resource(RenetClient::new(connection_config()));
+                                    commands.insert_resource(transport);
+                                }
+                                None => {
+                                    next_state.set(ClientState::Initial);
+                                    // Sent through commands, as this system reads the join events
+                                    commands.add(|world: &mut World| {
+                                        world.resource_mut::<Events<ClientEvent>>().send(
+                                            ClientEvent::JoinFailed(
+                                                "The local server is not running".to_owned(),
+                                            ),
+                                        );
+                                    });
+                                }
+                            }
+                            continue;
+                        }
                     };
+                    let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
                     let client = RenetClient::new(connection_config());
                     commands.insert_resource(client);
                     let transport =
@@ -221,12 +532,16 @@ fn handle_joining_server(
 }
 
 fn client_send_hello(
-    transport: Res<NetcodeClientTransport>,
-    data: Option<Res<UserData>>,
+    transport: Option<Res<NetcodeClientTransport>>,
+    local: Option<Res<LocalClientTransport>>,
+    options: Option<Res<JoinOptions>>,
+    reconnect: Option<Res<Reconnect>>,
     mut sender: MessageSender,
     mut last_state: Local<bool>,
 ) {
-    match (transport.is_connected(), *last_state) {
+    // Local connections exist only while connected
+    let connected = local.is_some() || transport.map_or(false, |t| t.is_connected());
+    match (connected, *last_state) {
         // Connected
         (true, false) => *last_state = true,
         // Disconnected
@@ -238,72 +553,167 @@ fn client_send_hello(
     }
 
     info!("Connected to server");
-    let username = data
-        .map(|d| d.username.clone())
-        .unwrap_or_else(|| "Beep".to_string());
-
-    // TODO: Replace with actual user id
-    let mut hasher = DefaultHasher::default();
-    username.hash(&mut hasher);
-    let hash = hasher.finish();
-
     sender.send_to_server(&ClientHello {
-        token: Vec::new(),
-        version: "TODO".into(),
-        username,
-        // 128 bits, trust me bro
-        id: Uuid::from_u64_pair(hash, hash),
+        version: VERSION.into(),
+        spectate: options.map_or(false, |o| o.spectate),
+        resume: reconnect.filter(|r| r.reconnecting).and_then(|r| r.session),
     });
 }
 
+/// The client is connected once the server accepts it,
+/// but only counts as joined after receiving its surroundings.
 fn client_joined_server(
     mut server_infos: EventReader<MessageEvent<ServerInfo>>,
-    mut client_events: EventWriter<ClientEvent>,
     mut next_state: ResMut<NextState<ClientState>>,
     mut network_time: ResMut<ClientNetworkTime>,
+    mut reconnect: Option<ResMut<Reconnect>>,
+    time: Res<Time>,
+    mut commands: Commands,
 ) {
     for event in server_infos.iter() {
         next_state.set(ClientState::Connected);
-        client_events.send(ClientEvent::Joined);
+        if let Some(reconnect) = reconnect.as_mut() {
+            if reconnect.reconnecting {
+                info!("Reconnected to server");
+            }
+            reconnect.session = Some(event.message.session);
+            reconnect.attempts_left = RECONNECT_ATTEMPTS;
+            reconnect.reconnecting = false;
+        }
+        commands.insert_resource(InitialSync::new(time.elapsed_seconds()));
         let tick_duration = event.message.tick_duration_seconds;
         network_time.server_tick_seconds = Some(tick_duration);
         info!("Joined server tick={}", tick_duration);
     }
 }
 
+fn client_receive_spectator_status(
+    mut messages: EventReader<MessageEvent<SpectatorStatus>>,
+    mut role: ResMut<ClientRole>,
+) {
+    if let Some(event) = messages.iter().last() {
+        *role = ClientRole {
+            spectator: event.message.spectator,
+            queue_position: event.message.queue_position,
+        };
+    }
+}
+
+fn client_handle_rejection(
+    mut messages: EventReader<MessageEvent<ConnectionRejected>>,
+    mut client_events: EventWriter<ClientEvent>,
+    state: Res<State<ClientState>>,
+    mut next_state: ResMut<NextState<ClientState>>,
+    mut client: ResMut<RenetClient>,
+    mut commands: Commands,
+) {
+    let Some(event) = messages.iter().last() else {
+        return;
+    };
+    let reason = &event.message.reason;
+    warn!(%reason, "Server rejected connection");
+    client.disconnect();
+    commands.remove_resource::<Reconnect>();
+    next_state.set(ClientState::Initial);
+    // Players can be kicked after they joined
+    client_events.send(match state.get() {
+        ClientState::Connected => ClientEvent::Disconnected(reason.to_string()),
+        _ => ClientEvent::JoinFailed(reason.to_string()),
+    });
+    commands.remove_resource::<RenetClient>();
+    commands.remove_resource::<LocalClientTransport>();
+}
+
 fn client_handle_join_error(
     mut events: EventReader<NetcodeTransportError>,
     mut client_events: EventWriter<ClientEvent>,
     mut next_state: ResMut<NextState<ClientState>>,
+    mut reconnect: Option<ResMut<Reconnect>>,
+    time: Res<Time>,
     mut commands: Commands,
 ) {
     let err = events.iter().last().unwrap();
     // For now we return to the menu on any network error while joining
     next_state.set(ClientState::Initial);
-    client_events.send(ClientEvent::JoinFailed(err.to_string()));
     commands.remove_resource::<RenetClient>();
+
+    let retrying = reconnect
+        .as_mut()
+        .filter(|r| r.reconnecting)
+        .map_or(false, |r| r.schedule(time.elapsed_seconds()));
+    client_events.send(ClientEvent::JoinFailed(if retrying {
+        format!("Reconnecting failed, trying again: {}", err)
+    } else {
+        err.to_string()
+    }));
 }
 
 fn client_handle_disconnect(
     mut events: EventReader<NetcodeTransportError>,
     mut client_events: EventWriter<ClientEvent>,
     mut next_state: ResMut<NextState<ClientState>>,
+    mut reconnect: Option<ResMut<Reconnect>>,
+    time: Res<Time>,
     mut commands: Commands,
 ) {
-    let reason = match events.iter().last().unwrap() {
-        NetcodeTransportError::Netcode(NetcodeError::Disconnected(reason)) => reason.to_string(),
-        NetcodeTransportError::IO(err) => err.to_string(),
+    let (reason, lost) = match events.iter().last().unwrap() {
+        NetcodeTransportError::Netcode(NetcodeError::Disconnected(reason)) => (
+            reason.to_string(),
+            matches!(reason, NetcodeDisconnectReason::ConnectionTimedOut),
+        ),
+        NetcodeTransportError::IO(err) => (err.to_string(), true),
         _ => return,
     };
 
     next_state.set(ClientState::Initial);
-    client_events.send(ClientEvent::Disconnected(reason));
     commands.remove_resource::<RenetClient>();
+
+    // Brief network blips, like the router changing our address, shouldn't end the round for us
+    let reconnecting = lost
+        && reconnect
+            .as_mut()
+            .map_or(false, |r| r.schedule(time.elapsed_seconds()));
+    if reconnecting {
+        warn!(%reason, "Lost connection to server, reconnecting");
+        client_events.send(ClientEvent::Disconnected(format!(
+            "Lost connection, reconnecting: {}",
+            reason
+        )));
+    } else {
+        client_events.send(ClientEvent::Disconnected(reason));
+    }
+}
+
+/// Joins the server again once it's time for the next reconnect attempt
+fn client_reconnect(
+    mut reconnect: ResMut<Reconnect>,
+    state: Res<State<ClientState>>,
+    mut client_events: EventWriter<ClientEvent>,
+    time: Res<Time>,
+) {
+    if *state.get() != ClientState::Initial {
+        return;
+    }
+    let Some(at) = reconnect.next_attempt else {
+        return;
+    };
+    if at > time.elapsed_seconds() {
+        return;
+    }
+
+    info!(
+        attempts_left = reconnect.attempts_left,
+        "Trying to reconnect to server"
+    );
+    reconnect.next_attempt = None;
+    reconnect.reconnecting = true;
+    client_events.send(ClientEvent::Join(reconnect.target.clone()));
 }
 
 fn client_handle_tasks(
     mut tasks: EventReader<ClientTask>,
     mut client: Option<ResMut<RenetClient>>,
+    mut commands: Commands,
 ) {
     for task in tasks.iter() {
         match task {
@@ -311,6 +721,7 @@ fn client_handle_tasks(
                 if let Some(client) = client.as_mut() {
                     client.disconnect();
                 }
+                commands.remove_resource::<Reconnect>();
             }
         }
     }
@@ -328,35 +739,89 @@ impl Display for ConnectionId {
 pub struct Player {
     pub id: Uuid,
     pub username: String,
+    /// Address the player connected from
+    pub address: Option<IpAddr>,
+    /// Watches the round without taking a player slot
+    pub spectator: bool,
+    /// Key the client can resume this connection with
+    session: Uuid,
 }
 
 #[derive(Default, Resource)]
 pub struct Players {
     players: HashMap<ConnectionId, Player>,
     user_ids: HashMap<Uuid, ConnectionId>,
+    /// Spectators waiting for a player slot, longest waiting first
+    waiting: VecDeque<ConnectionId>,
 }
 
 impl Players {
-    fn add(&mut self, connection: ConnectionId, message: &ClientHello) {
+    fn add(
+        &mut self,
+        connection: ConnectionId,
+        identity: UserIdentity,
+        address: Option<IpAddr>,
+        spectator: bool,
+    ) {
         self.players.insert(
             connection,
             Player {
-                id: message.id,
-                username: message.username.clone(),
+                id: identity.id,
+                username: identity.username,
+                address,
+                spectator,
+                session: Uuid::new_v4(),
             },
         );
-        self.user_ids.insert(message.id, connection);
+        self.user_ids.insert(identity.id, connection);
     }
 
     fn remove(&mut self, connection: ConnectionId) -> Option<Player> {
         if let Some(player) = self.players.remove(&connection) {
             self.user_ids.remove(&player.id);
+            self.waiting.retain(|&c| c != connection);
             Some(player)
         } else {
             None
         }
     }
 
+    /// Number of connections in a player slot
+    pub fn playing(&self) -> usize {
+        self.players.values().filter(|p| !p.spectator).count()
+    }
+
+    pub fn is_spectator(&self, connection: ConnectionId) -> bool {
+        self.players
+            .get(&connection)
+            .map_or(false, |player| player.spectator)
+    }
+
+    /// Place of a spectator in the queue for a player slot
+    pub fn queue_position(&self, connection: ConnectionId) -> Option<usize> {
+        self.waiting.iter().position(|&c| c == connection)
+    }
+
+    fn set_waiting(&mut self, connection: ConnectionId, waiting: bool) {
+        let position = self.queue_position(connection);
+        match (waiting, position) {
+            (true, None) if self.is_spectator(connection) => self.waiting.push_back(connection),
+            (false, Some(position)) => {
+                self.waiting.remove(position);
+            }
+            _ => {}
+        }
+    }
+
+    /// Moves the longest waiting spectator into a player slot
+    fn promote_next(&mut self) -> Option<ConnectionId> {
+        let connection = self.waiting.pop_front()?;
+        if let Some(player) = self.players.get_mut(&connection) {
+            player.spectator = false;
+        }
+        Some(connection)
+    }
+
     pub fn players(&self) -> &HashMap<ConnectionId, Player> {
         &self.players
     }
@@ -370,44 +835,292 @@ impl Players {
     }
 }
 
+/// Limits how many connections can play at once.
+/// Anyone joining past the limit becomes a spectator waiting for a slot.
+#[derive(Resource, Default)]
+pub struct PlayerSlots {
+    pub max_players: Option<usize>,
+}
+
+impl PlayerSlots {
+    fn is_full(&self, players: &Players, reserved: &ReservedSlots) -> bool {
+        self.max_players
+            .map_or(false, |max| players.playing() + reserved.0.len() >= max)
+    }
+}
+
+/// Player slots kept for players who lost connection, with the time they are given up at
+#[derive(Resource, Default)]
+struct ReservedSlots(HashMap<Uuid, f32>);
+
+/// Connections that were rejected, with the time they get disconnected at
+#[derive(Resource, Default)]
+struct RejectedConnections(Vec<(ConnectionId, f32)>);
+
+/// Identities of clients connected without a netcode transport, by client id.
+/// These are local clients and the clients of the test harness.
+#[derive(Resource, Default)]
+pub(crate) struct InProcessIdentities(pub(crate) HashMap<u64, UserIdentity>);
+
+#[allow(clippy::too_many_arguments)]
 fn server_handle_connect(
     mut hello_messages: EventReader<MessageEvent<ClientHello>>,
     mut players: ResMut<Players>,
+    slots: Res<PlayerSlots>,
+    mut reserved: ResMut<ReservedSlots>,
     mut server_events: EventWriter<ServerEvent>,
     mut sender: MessageSender,
+    mut rejected: ResMut<RejectedConnections>,
+    bans: Res<BannedPlayers>,
+    transport: Option<Res<NetcodeServerTransport>>,
+    in_process: Option<Res<InProcessIdentities>>,
     network_time: Res<ServerNetworkTime>,
+    time: Res<Time>,
 ) {
     for event in hello_messages.iter() {
-        // TODO: Auth
+        // The token was verified by the transport, its user data says who connected
+        let identity = transport
+            .as_ref()
+            .and_then(|transport| transport.user_data(event.connection.0))
+            .and_then(|data| UserIdentity::from_user_data(&data))
+            .or_else(|| {
+                in_process
+                    .as_ref()
+                    .and_then(|identities| identities.0.get(&event.connection.0).cloned())
+            });
+        let address = transport
+            .as_ref()
+            .and_then(|transport| transport.client_addr(event.connection.0))
+            .map(|a| a.ip());
+        // A client that lost connection may come back from another address before the old one timed out
+        let resumed = identity
+            .as_ref()
+            .and_then(|identity| players.get_connection(&identity.id))
+            .filter(|&old| {
+                event.message.resume.is_some()
+                    && players.get(old).map(|p| p.session) == event.message.resume
+            });
+        let result = match identity {
+            None => Err(RejectionReason::InvalidToken),
+            Some(_) if event.message.version != VERSION => Err(RejectionReason::VersionMismatch {
+                server: VERSION.into(),
+                client: event.message.version.clone(),
+            }),
+            Some(identity)
+                if players.get_connection(&identity.id).is_some() && resumed.is_none() =>
+            {
+                Err(RejectionReason::AlreadyConnected)
+            }
+            Some(identity) => {
+                let now = bans::unix_now();
+                match bans.find(&identity.id, address, now) {
+                    Some(ban) => Err(RejectionReason::Banned {
+                        reason: ban.reason.clone(),
+                        minutes_left: ban.expires.map(|e| e.saturating_sub(now).div_ceil(60)),
+                    }),
+                    None => Ok(identity),
+                }
+            }
+        };
+        let identity = match result {
+            Ok(identity) => identity,
+            Err(reason) => {
+                warn!(connection = ?event.connection, %reason, "Rejected client");
+                sender.send(
+                    &ConnectionRejected { reason },
+                    MessageReceivers::Single(event.connection),
+                );
+                rejected.0.push((
+                    event.connection,
+                    time.elapsed_seconds() + REJECTION_GRACE_SECONDS,
+                ));
+                continue;
+            }
+        };
+
+        let uuid = identity.id.to_string();
+        let previous = resumed.and_then(|old| players.remove(old));
+        if let Some(old) = resumed {
+            info!(connection = ?event.connection, old = ?old, id = uuid.as_str(), "Client resumed its connection");
+            rejected.0.push((old, time.elapsed_seconds()));
+            server_events.send(ServerEvent::PlayerDisconnected(old));
+        }
+        // Players coming back keep their slot
+        let had_slot = reserved.0.remove(&identity.id).is_some()
+            || previous.as_ref().map_or(false, |p| !p.spectator);
+        // Clients that only wanted to watch don't take the next free slot
+        let full = !had_slot && slots.is_full(&players, &reserved);
+        let spectator = event.message.spectate || full;
+        players.add(event.connection, identity, address, spectator);
         let server_info = ServerInfo {
             tick_duration_seconds: network_time.tick_in_seconds() as f32,
+            session: players.get(event.connection).unwrap().session,
         };
         sender.send(&server_info, MessageReceivers::Single(event.connection));
-        players.add(event.connection, &event.message);
+        if full && !event.message.spectate {
+            players.set_waiting(event.connection, true);
+        }
+        if spectator {
+            sender.send(
+                &SpectatorStatus {
+                    spectator,
+                    queue_position: players.queue_position(event.connection),
+                },
+                MessageReceivers::Single(event.connection),
+            );
+        }
         server_events.send(ServerEvent::PlayerConnected(event.connection));
 
-        let uuid = event.message.id.to_string();
-        info!(connection = ?event.connection, id = uuid.as_str(), "New client connected");
+        info!(connection = ?event.connection, id = uuid.as_str(), spectator, "New client connected");
     }
 }
 
+/// Disconnects rejected clients once they had time to receive the reason
+fn server_disconnect_rejected(
+    mut rejected: ResMut<RejectedConnections>,
+    mut server: ResMut<RenetServer>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    rejected.0.retain(|&(connection, disconnect_at)| {
+        if disconnect_at > now {
+            return true;
+        }
+        server.disconnect(connection.0);
+        false
+    });
+}
+
+fn server_handle_tasks(
+    mut tasks: EventReader<ServerTask>,
+    players: Res<Players>,
+    mut rejected: ResMut<RejectedConnections>,
+    mut sender: MessageSender,
+    time: Res<Time>,
+) {
+    for task in tasks.iter() {
+        match task {
+            ServerTask::Kick { connection, reason } => {
+                if players.get(*connection).is_none() {
+                    continue;
+                }
+                info!(connection = ?connection, %reason, "Kicking player");
+                sender.send(
+                    &ConnectionRejected {
+                        reason: RejectionReason::Kicked {
+                            reason: reason.clone(),
+                        },
+                    },
+                    MessageReceivers::Single(*connection),
+                );
+                rejected.0.push((
+                    *connection,
+                    time.elapsed_seconds() + REJECTION_GRACE_SECONDS,
+                ));
+            }
+        }
+    }
+}
+
+fn server_handle_slot_requests(
+    mut messages: EventReader<MessageEvent<RequestPlayerSlot>>,
+    mut players: ResMut<Players>,
+) {
+    for event in messages.iter() {
+        players.set_waiting(event.connection, event.message.waiting);
+    }
+}
+
+/// Gives free player slots to waiting spectators and tells the others their place in the queue
+fn server_promote_spectators(
+    mut players: ResMut<Players>,
+    slots: Res<PlayerSlots>,
+    reserved: Res<ReservedSlots>,
+    mut server_events: EventWriter<ServerEvent>,
+    mut sender: MessageSender,
+    mut last_queue: Local<Vec<ConnectionId>>,
+) {
+    while !players.waiting.is_empty() && !slots.is_full(&players, &reserved) {
+        let Some(connection) = players.promote_next() else {
+            break;
+        };
+        info!(connection = ?connection, "Promoted spectator to player");
+        sender.send(
+            &SpectatorStatus {
+                spectator: false,
+                queue_position: None,
+            },
+            MessageReceivers::Single(connection),
+        );
+        server_events.send(ServerEvent::SpectatorPromoted(connection));
+    }
+
+    if players.waiting.iter().eq(last_queue.iter()) {
+        return;
+    }
+    // Tell everyone whose place changed, including spectators that stopped waiting
+    for &connection in last_queue.iter() {
+        if players.is_spectator(connection) && players.queue_position(connection).is_none() {
+            sender.send(
+                &SpectatorStatus {
+                    spectator: true,
+                    queue_position: None,
+                },
+                MessageReceivers::Single(connection),
+            );
+        }
+    }
+    for (index, &connection) in players.waiting.iter().enumerate() {
+        if last_queue.get(index) != Some(&connection) {
+            sender.send(
+                &SpectatorStatus {
+                    spectator: true,
+                    queue_position: Some(index),
+                },
+                MessageReceivers::Single(connection),
+            );
+        }
+    }
+    *last_queue = players.waiting.iter().copied().collect();
+}
+
 fn server_handle_disconnect(
     mut renet_events: EventReader<bevy_renet::renet::ServerEvent>,
     mut players: ResMut<Players>,
+    mut reserved: ResMut<ReservedSlots>,
     mut server_events: EventWriter<ServerEvent>,
+    time: Res<Time>,
 ) {
     for event in renet_events.iter() {
-        if let bevy_renet::renet::ServerEvent::ClientDisconnected { client_id: id, .. } = event {
+        if let bevy_renet::renet::ServerEvent::ClientDisconnected {
+            client_id: id,
+            reason,
+        } = event
+        {
             let connection = ConnectionId(*id);
             if let Some(player) = players.remove(connection) {
                 let uuid = player.id.to_string();
                 info!(connection = ?connection, id = uuid.as_str(), "Player disconnected");
+                // Kicked players don't get their slot back
+                if !player.spectator && !matches!(reason, DisconnectReason::DisconnectedByServer) {
+                    reserved
+                        .0
+                        .insert(player.id, time.elapsed_seconds() + RECONNECT_GRACE_SECONDS);
+                }
                 server_events.send(ServerEvent::PlayerDisconnected(connection));
             }
         }
     }
 }
 
+/// Frees the slots of players that didn't come back in time
+fn server_expire_reserved_slots(mut reserved: ResMut<ReservedSlots>, time: Res<Time>) {
+    let now = time.elapsed_seconds();
+    if reserved.0.values().any(|&until| until <= now) {
+        reserved.0.retain(|_, &mut until| until > now);
+    }
+}
+
 fn report_errors(mut events: EventReader<NetcodeTransportError>) {
     for error in events.iter() {
         error!(?error, "Network error");
@@ -485,6 +1198,9 @@ impl Plugin for NetworkingPlugin {
             .add_plugins(MessagingPlugin)
             .add_network_message::<ClientHello>()
             .add_network_message::<ServerInfo>()
+            .add_network_message::<ConnectionRejected>()
+            .add_network_message::<SpectatorStatus>()
+            .add_network_message::<RequestPlayerSlot>()
             .add_plugins((
                 TimePlugin,
                 IdentityPlugin,
@@ -494,6 +1210,9 @@ impl Plugin for NetworkingPlugin {
                 ResourcePlugin,
                 TransformPlugin,
                 ScenePlugin,
+                SyncPlugin,
+                StatsPlugin,
+                LocalTransportPlugin,
             ))
             .add_systems(
                 Update,
@@ -502,6 +1221,7 @@ impl Plugin for NetworkingPlugin {
 
         if self.role == NetworkRole::Client {
             app.add_state::<ClientState>()
+                .init_resource::<ClientRole>()
                 .add_event::<ClientEvent>()
                 .add_event::<ClientTask>()
                 .configure_sets(
@@ -519,22 +1239,50 @@ impl Plugin for NetworkingPlugin {
                     (
                         handle_joining_server,
                         client_joined_server,
-                        client_send_hello.run_if(resource_exists::<NetcodeClientTransport>()),
+                        client_receive_spectator_status,
+                        client_handle_rejection.run_if(resource_exists::<RenetClient>()),
+                        client_send_hello,
                         (
                             client_handle_join_error.run_if(in_state(ClientState::Joining)),
                             client_handle_disconnect.run_if(in_state(ClientState::Connected)),
                         )
                             .run_if(on_event::<NetcodeTransportError>()),
                         client_handle_tasks.run_if(on_event::<ClientTask>()),
+                        client_reconnect.run_if(resource_exists::<Reconnect>()),
                         client_disconnect_on_exit
                             .run_if(on_event::<AppExit>())
                             .run_if(resource_exists::<NetcodeClientTransport>()),
                     ),
                 );
         } else {
-            app.add_event::<ServerEvent>()
+            app.add_plugins(BandwidthPlugin)
+                .add_event::<ServerEvent>()
+                .add_event::<ServerTask>()
                 .init_resource::<Players>()
-                .add_systems(Update, (server_handle_connect, server_handle_disconnect));
+                .init_resource::<RejectedConnections>()
+                .init_resource::<BannedPlayers>()
+                .init_resource::<PlayerSlots>()
+                .init_resource::<ReservedSlots>()
+                .add_systems(
+                    Update,
+                    (
+                        server_handle_connect,
+                        server_handle_tasks.run_if(on_event::<ServerTask>()),
+                        server_disconnect_rejected,
+                        server_handle_disconnect,
+                        server_handle_slot_requests,
+                        server_expire_reserved_slots,
+                        server_promote_spectators
+                            .after(server_handle_connect)
+                            .after(server_handle_disconnect)
+                            .after(server_handle_slot_requests)
+                            .after(server_expire_reserved_slots)
+                            .run_if(
+                                resource_changed::<Players>()
+                                    .or_else(resource_changed::<ReservedSlots>()),
+                            ),
+                    ),
+                );
         }
     }
 }
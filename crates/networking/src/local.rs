@@ -0,0 +1,197 @@
+//! A transport for a server and clients in the same process, used for singleplayer.
+//!
+//! Packets are passed through channels instead of UDP sockets. Clients connect through a
+//! [`LocalServerAddress`] and are trusted to be who they say they are, like on unauthenticated servers.
+//! Either side notices the other one is gone when its end of the channels is dropped.
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_renet::renet::{RenetClient, RenetServer};
+
+use crate::{
+    connection_config, ClientEvent, ClientState, InProcessIdentities, NetworkManager, NetworkSet,
+    UserIdentity,
+};
+
+struct Connection {
+    sender: flume::Sender<Vec<u8>>,
+    receiver: flume::Receiver<Vec<u8>>,
+}
+
+struct ConnectRequest {
+    identity: UserIdentity,
+    connection: Connection,
+}
+
+/// The server end, accepting clients that connect through its [`LocalServerAddress`]
+#[derive(Resource)]
+pub struct LocalServerTransport {
+    requests: flume::Receiver<ConnectRequest>,
+    connections: HashMap<u64, Connection>,
+    last_client_id: u64,
+}
+
+/// Lets clients connect to a server in the same process
+#[derive(Resource, Clone)]
+pub struct LocalServerAddress(flume::Sender<ConnectRequest>);
+
+impl LocalServerAddress {
+    /// Returns `None` if the server is no longer running
+    pub(crate) fn connect(&self, identity: UserIdentity) -> Option<LocalClientTransport> {
+        let (to_server, from_client) = flume::unbounded();
+        let (to_client, from_server) = flume::unbounded();
+        self.0
+            .send(ConnectRequest {
+                identity,
+                connection: Connection {
+                    sender: to_client,
+                    receiver: from_client,
+                },
+            })
+            .ok()?;
+        Some(LocalClientTransport(Connection {
+            sender: to_server,
+            receiver: from_server,
+        }))
+    }
+}
+
+/// The client end of a connection to a local server
+#[derive(Resource)]
+pub struct LocalClientTransport(Connection);
+
+/// Creates a server that clients in the same process connect to through the returned address
+pub fn create_local_server() -> (RenetServer, LocalServerTransport, LocalServerAddress) {
+    let (sender, receiver) = flume::unbounded();
+    let transport = LocalServerTransport {
+        requests: receiver,
+        connections: HashMap::default(),
+        last_client_id: 0,
+    };
+    (
+        RenetServer::new(connection_config()),
+        transport,
+        LocalServerAddress(sender),
+    )
+}
+
+pub(crate) struct LocalTransportPlugin;
+
+impl Plugin for LocalTransportPlugin {
+    fn build(&self, app: &mut App) {
+        if app
+            .world
+            .get_resource::<NetworkManager>()
+            .unwrap()
+            .is_server()
+        {
+            app.init_resource::<InProcessIdentities>()
+                .add_systems(
+                    PreUpdate,
+                    server_receive_packets
+                        .before(NetworkSet::ReadIncoming)
+                        .run_if(resource_exists::<LocalServerTransport>())
+                        .run_if(resource_exists::<RenetServer>()),
+                )
+                .add_systems(
+                    PostUpdate,
+                    server_send_packets
+                        .after(NetworkSet::ServerSyncPhysics)
+                        .run_if(resource_exists::<LocalServerTransport>())
+                        .run_if(resource_exists::<RenetServer>()),
+                );
+        } else {
+            app.add_systems(
+                PreUpdate,
+                client_receive_packets
+                    .before(NetworkSet::ReadIncoming)
+                    .run_if(resource_exists::<LocalClientTransport>())
+                    .run_if(resource_exists::<RenetClient>()),
+            )
+            .add_systems(
+                PostUpdate,
+                client_send_packets
+                    .after(NetworkSet::ServerSyncPhysics)
+                    .run_if(resource_exists::<LocalClientTransport>())
+                    .run_if(resource_exists::<RenetClient>()),
+            );
+        }
+    }
+}
+
+fn server_receive_packets(
+    mut transport: ResMut<LocalServerTransport>,
+    mut server: ResMut<RenetServer>,
+    mut identities: ResMut<InProcessIdentities>,
+) {
+    let transport = &mut *transport;
+    for request in transport.requests.try_iter() {
+        transport.last_client_id += 1;
+        let client_id = transport.last_client_id;
+        server.add_connection(client_id);
+        identities.0.insert(client_id, request.identity);
+        transport.connections.insert(client_id, request.connection);
+    }
+
+    transport.connections.retain(|&client_id, connection| {
+        for packet in connection.receiver.try_iter() {
+            let _ = server.process_packet_from(&packet, client_id);
+        }
+        // The client left or its app stopped
+        if connection.receiver.is_disconnected() {
+            server.remove_connection(client_id);
+            identities.0.remove(&client_id);
+            return false;
+        }
+        true
+    });
+}
+
+fn server_send_packets(
+    mut transport: ResMut<LocalServerTransport>,
+    mut server: ResMut<RenetServer>,
+    mut identities: ResMut<InProcessIdentities>,
+) {
+    transport.connections.retain(|&client_id, connection| {
+        // Dropping the channel tells the client it was disconnected
+        if !server.is_connected(client_id) {
+            server.remove_connection(client_id);
+            identities.0.remove(&client_id);
+            return false;
+        }
+        for packet in server.get_packets_to_send(client_id).unwrap_or_default() {
+            let _ = connection.sender.send(packet);
+        }
+        true
+    });
+}
+
+fn client_receive_packets(
+    transport: Res<LocalClientTransport>,
+    mut client: ResMut<RenetClient>,
+    mut client_events: EventWriter<ClientEvent>,
+    mut next_state: ResMut<NextState<ClientState>>,
+    mut commands: Commands,
+) {
+    for packet in transport.0.receiver.try_iter() {
+        client.process_packet(&packet);
+    }
+
+    let reason = if client.is_disconnected() {
+        "Left the local server"
+    } else if transport.0.receiver.is_disconnected() {
+        "The local server closed the connection"
+    } else {
+        return;
+    };
+    next_state.set(ClientState::Initial);
+    commands.remove_resource::<RenetClient>();
+    // Dropping the channel tells the server the client is gone
+    commands.remove_resource::<LocalClientTransport>();
+    client_events.send(ClientEvent::Disconnected(reason.to_owned()));
+}
+
+fn client_send_packets(transport: Res<LocalClientTransport>, mut client: ResMut<RenetClient>) {
+    for packet in client.get_packets_to_send() {
+        let _ = transport.0.sender.send(packet);
+    }
+}
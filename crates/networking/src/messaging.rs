@@ -3,14 +3,24 @@ use std::{any::TypeId, time::Duration};
 use bevy::{
     ecs::system::SystemParam,
     prelude::*,
-    utils::{HashMap, HashSet},
+    utils::{get_short_name, HashMap, HashSet},
 };
 use bevy_renet::renet::{ChannelConfig, RenetClient, RenetServer, SendType};
 use bincode::Options;
 use bytes::{BufMut, Bytes};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use crate::{ConnectionId, NetworkManager, NetworkSet, Players};
+use crate::{
+    bandwidth::{BandwidthUsage, SNAPSHOT_FEATURE},
+    guard::{ConnectionFlagged, GuardConfig, RateLimits},
+    protocol::ProtocolDescription,
+    snapshot::{self, JoinSnapshots, BATCH_TYPE_ID},
+    stats::NetworkStats,
+    ConnectionId, NetworkManager, NetworkSet, Players,
+};
+
+/// Bytes the message id and length prefix add to a message's content
+const ENVELOPE_SIZE: usize = 10;
 
 /// Serialize data once and allow it to be shared in multiple places without reallocating.
 pub(crate) fn serialize_once<T: Serialize>(data: &T) -> Bytes {
@@ -29,12 +39,23 @@ where
     options.deserialize(data)
 }
 
+/// Deserializes like [`bincode::deserialize`], but fails instead of reading past `limit` bytes.
+/// Keeps length prefixes sent by clients from allocating huge buffers.
+fn deserialize_limited<T: DeserializeOwned>(data: &[u8], limit: usize) -> bincode::Result<T> {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(limit as u64)
+        .deserialize(data)
+}
+
 /// Assigns packet numbers to types uniquely and allows to lookup the id for a specific type.
 /// Used in packet registration, serialization and deserialization.
 #[derive(Default, Resource)]
 pub struct MessageTypes {
     last_type: u16,
     types: HashMap<TypeId, u16>,
+    names: HashMap<u16, &'static str>,
 }
 
 impl MessageTypes {
@@ -43,10 +64,136 @@ impl MessageTypes {
         self.last_type = type_id;
 
         self.types.insert(TypeId::of::<T>(), type_id);
+        self.names.insert(type_id, std::any::type_name::<T>());
         trace!(type_id = ?TypeId::of::<T>(), message_id = type_id, "Registered message type {}", std::any::type_name::<T>());
 
         type_id
     }
+
+    /// The id a message type is sent with
+    pub fn id<T: 'static>(&self) -> Option<u16> {
+        self.types.get(&TypeId::of::<T>()).copied()
+    }
+
+    /// Full type name of the message type sent with an id
+    pub fn name(&self, id: u16) -> Option<&'static str> {
+        self.names.get(&id).copied()
+    }
+}
+
+/// Size limits for messages received from clients, checked before they are deserialized
+#[derive(Resource)]
+pub struct MessageLimits {
+    /// Limit in bytes for message types without their own
+    pub default_size: usize,
+    sizes: HashMap<TypeId, usize>,
+    /// Limits from the config by type name without its path, they replace limits set in code
+    pub(crate) type_sizes: HashMap<String, usize>,
+}
+
+impl Default for MessageLimits {
+    fn default() -> Self {
+        Self {
+            default_size: 16 * 1024,
+            sizes: HashMap::default(),
+            type_sizes: HashMap::default(),
+        }
+    }
+}
+
+impl MessageLimits {
+    pub fn set_max_size<T: 'static>(&mut self, bytes: usize) {
+        if self.configured_size::<T>().is_none() {
+            self.sizes.insert(TypeId::of::<T>(), bytes);
+        }
+    }
+
+    /// Applies the configured limit of a newly registered message type
+    fn register<T: 'static>(&mut self) {
+        if let Some(bytes) = self.configured_size::<T>() {
+            self.sizes.insert(TypeId::of::<T>(), bytes);
+        }
+    }
+
+    fn configured_size<T: 'static>(&self) -> Option<usize> {
+        self.type_sizes
+            .get(&get_short_name(std::any::type_name::<T>()))
+            .copied()
+    }
+
+    pub fn max_size<T: 'static>(&self) -> usize {
+        self.sizes
+            .get(&TypeId::of::<T>())
+            .copied()
+            .unwrap_or(self.default_size)
+    }
+
+    /// Size of the largest message any type allows, including the envelope
+    fn largest(&self) -> usize {
+        self.sizes
+            .values()
+            .copied()
+            .fold(self.default_size, usize::max)
+            + ENVELOPE_SIZE
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// Larger than the limit of the message type
+    Oversized,
+    /// Could not be deserialized or has an unknown type
+    Malformed,
+    /// Sent faster than the rate limit of the message type
+    RateLimited,
+}
+
+/// Scores clients for sending invalid messages and disconnects them past a threshold.
+/// Scores decay over time, so the odd broken message is forgiven.
+#[derive(Resource)]
+pub struct MessageViolations {
+    pub oversized_penalty: f32,
+    pub malformed_penalty: f32,
+    pub rate_limited_penalty: f32,
+    /// Score at which a client is flagged
+    pub flag_threshold: f32,
+    /// Score at which a client is disconnected
+    pub threshold: f32,
+    pub decay_per_second: f32,
+    scores: HashMap<ConnectionId, f32>,
+    flagged: HashSet<ConnectionId>,
+}
+
+impl Default for MessageViolations {
+    fn default() -> Self {
+        Self {
+            oversized_penalty: 5.0,
+            malformed_penalty: 2.0,
+            rate_limited_penalty: 0.5,
+            flag_threshold: 10.0,
+            threshold: 20.0,
+            decay_per_second: 1.0,
+            scores: HashMap::default(),
+            flagged: HashSet::default(),
+        }
+    }
+}
+
+impl MessageViolations {
+    pub fn score(&self, connection: ConnectionId) -> f32 {
+        self.scores.get(&connection).copied().unwrap_or_default()
+    }
+
+    fn add(&mut self, connection: ConnectionId, kind: ViolationKind) {
+        let penalty = match kind {
+            ViolationKind::Oversized => self.oversized_penalty,
+            ViolationKind::Malformed => self.malformed_penalty,
+            ViolationKind::RateLimited => self.rate_limited_penalty,
+        };
+        let score = self.scores.entry(connection).or_default();
+        *score += penalty;
+        warn!(%connection, ?kind, score = *score, "Rejected message from client");
+    }
 }
 
 enum MessageKind {
@@ -81,15 +228,17 @@ pub struct OutboundMessage {
     receivers: MessageReceivers,
     kind: MessageKind,
     priority: i16,
+    /// What the bandwidth is accounted to, the message type unless set otherwise
+    feature: &'static str,
 }
 
 /// The actual data being serialized over the network
 #[derive(Serialize, Deserialize, Debug, Clone)]
-struct NetworkMessage {
+pub(crate) struct NetworkMessage {
     /// The id registered in [`MessageTypes`]
-    type_id: u16,
+    pub(crate) type_id: u16,
     /// The serialized content of the message
-    content: Bytes,
+    pub(crate) content: Bytes,
 }
 
 impl From<OutboundMessage> for NetworkMessage {
@@ -122,6 +271,8 @@ pub trait AppExt {
     fn add_network_message<T>(&mut self) -> &mut Self
     where
         T: 'static + Serialize + DeserializeOwned + Send + Sync;
+
+    fn set_message_limit<T: 'static>(&mut self, bytes: usize) -> &mut Self;
 }
 
 impl AppExt for App {
@@ -134,24 +285,48 @@ impl AppExt for App {
     {
         let mut types = self.world.get_resource_mut::<MessageTypes>().unwrap();
         let type_id = types.register::<T>();
+        self.world
+            .resource_mut::<ProtocolDescription>()
+            .add_message::<T>(type_id);
+        if let Some(mut limits) = self.world.get_resource_mut::<MessageLimits>() {
+            limits.register::<T>();
+        }
 
+        // Limits only exist on the server, messages from the server are trusted
         let packet_reader =
             move |mut raw_events: EventReader<IncomingMessage>,
-                  mut events: EventWriter<MessageEvent<T>>| {
+                  mut events: EventWriter<MessageEvent<T>>,
+                  limits: Option<Res<MessageLimits>>,
+                  mut violations: Option<ResMut<MessageViolations>>| {
                 for event in raw_events.iter() {
                     // TODO: don't run a system for every kind of message
                     if event.type_id != type_id {
                         continue;
                     }
 
-                    let message: T = match bincode::deserialize(&event.content) {
+                    let result = match &limits {
+                        Some(limits) => {
+                            let limit = limits.max_size::<T>();
+                            if event.content.len() > limit {
+                                if let Some(violations) = violations.as_mut() {
+                                    violations.add(event.connection, ViolationKind::Oversized);
+                                }
+                                continue;
+                            }
+                            deserialize_limited(&event.content, limit)
+                        }
+                        None => bincode::deserialize(&event.content),
+                    };
+                    let message: T = match result {
                         Ok(m) => m,
                         Err(_) => {
-                            // TODO: Disconnect after X invalid packets?
                             warn!(
                                 "Received malformed packet from connection={} message_id={}",
                                 event.connection, event.type_id
                             );
+                            if let Some(violations) = violations.as_mut() {
+                                violations.add(event.connection, ViolationKind::Malformed);
+                            }
                             continue;
                         }
                     };
@@ -165,6 +340,14 @@ impl AppExt for App {
         self.add_event::<MessageEvent<T>>()
             .add_systems(PreUpdate, packet_reader.in_set(ReadMessagesSet::EmitEvents))
     }
+
+    /// Limits the serialized size of a message type sent by clients.
+    fn set_message_limit<T: 'static>(&mut self, bytes: usize) -> &mut Self {
+        if let Some(mut limits) = self.world.get_resource_mut::<MessageLimits>() {
+            limits.set_max_size::<T>(bytes);
+        }
+        self
+    }
 }
 
 #[derive(SystemParam)]
@@ -179,14 +362,39 @@ impl<'w, 's> MessageSender<'w, 's> {
     where
         T: 'static + Serialize + Send + Sync,
     {
-        self.send_internal(message, receivers, MessageKind::Reliable, 0);
+        self.send_internal(
+            message,
+            receivers,
+            MessageKind::Reliable,
+            0,
+            std::any::type_name::<T>(),
+        );
     }
 
     pub fn send_with_priority<T>(&mut self, message: &T, receivers: MessageReceivers, priority: i16)
     where
         T: 'static + Serialize + Send + Sync,
     {
-        self.send_internal(message, receivers, MessageKind::Reliable, priority);
+        self.send_internal(
+            message,
+            receivers,
+            MessageKind::Reliable,
+            priority,
+            std::any::type_name::<T>(),
+        );
+    }
+
+    /// Sends a message whose bandwidth counts towards another feature than its type
+    pub(crate) fn send_for_feature<T>(
+        &mut self,
+        message: &T,
+        receivers: MessageReceivers,
+        priority: i16,
+        feature: &'static str,
+    ) where
+        T: 'static + Serialize + Send + Sync,
+    {
+        self.send_internal(message, receivers, MessageKind::Reliable, priority, feature);
     }
 
     pub fn send_to_server<T>(&mut self, message: &T)
@@ -200,7 +408,13 @@ impl<'w, 's> MessageSender<'w, 's> {
     where
         T: 'static + Serialize + Send + Sync,
     {
-        self.send_internal(message, receivers, MessageKind::Unreliable, 0);
+        self.send_internal(
+            message,
+            receivers,
+            MessageKind::Unreliable,
+            0,
+            std::any::type_name::<T>(),
+        );
     }
 
     fn send_internal<T>(
@@ -209,6 +423,7 @@ impl<'w, 's> MessageSender<'w, 's> {
         receivers: MessageReceivers,
         kind: MessageKind,
         priority: i16,
+        feature: &'static str,
     ) where
         T: 'static + Serialize + Send + Sync,
     {
@@ -225,6 +440,7 @@ impl<'w, 's> MessageSender<'w, 's> {
             receivers,
             kind,
             priority,
+            feature,
         };
         self.get_sender().send(event).unwrap();
     }
@@ -235,6 +451,7 @@ impl<'w, 's> MessageSender<'w, 's> {
     }
 }
 
+#[derive(Clone, Copy)]
 pub(crate) enum Channel {
     Default,
     DefaultUnreliable,
@@ -243,6 +460,22 @@ pub(crate) enum Channel {
 }
 
 impl Channel {
+    pub const ALL: [Channel; 4] = [
+        Self::Default,
+        Self::DefaultUnreliable,
+        Self::Timing,
+        Self::Transforms,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Default => "Default",
+            Self::DefaultUnreliable => "DefaultUnreliable",
+            Self::Timing => "Timing",
+            Self::Transforms => "Transforms",
+        }
+    }
+
     pub fn id(&self) -> u8 {
         match self {
             Self::Default => 0,
@@ -280,31 +513,63 @@ impl Channel {
     }
 }
 
-/// Reads from the network channels and sends message events
-fn read_channel_server(mut events: EventWriter<IncomingMessage>, mut server: ResMut<RenetServer>) {
+/// Priority taken off messages that only go to spectators
+const SPECTATOR_PRIORITY_PENALTY: i16 = 20;
+
+/// Reads from the network channels and sends message events for messages that pass the guard
+#[allow(clippy::too_many_arguments)]
+fn read_channel_server(
+    mut events: EventWriter<IncomingMessage>,
+    mut server: ResMut<RenetServer>,
+    types: Res<MessageTypes>,
+    limits: Res<MessageLimits>,
+    mut violations: ResMut<MessageViolations>,
+    mut stats: ResMut<NetworkStats>,
+    guard: Res<GuardConfig>,
+    mut rate_limits: ResMut<RateLimits>,
+    time: Res<Time>,
+) {
+    let now = time.raw_elapsed_seconds();
+    let largest = limits.largest();
     'clients: for client_id in server.clients_id().into_iter() {
-        for channel_id in [Channel::Default.id(), Channel::DefaultUnreliable.id()] {
-            while let Some(message) = server.receive_message(client_id, channel_id) {
-                let message: NetworkMessage = match bincode::deserialize(&message) {
-                    Ok(m) => m,
-                    Err(_) => {
-                        warn!(client_id, "Invalid message from client");
+        let connection = ConnectionId(client_id);
+        for channel in [Channel::Default, Channel::DefaultUnreliable] {
+            while let Some(message) = server.receive_message(client_id, channel.id()) {
+                stats.record_received(channel, message.len());
+                if message.len() > largest {
+                    violations.add(connection, ViolationKind::Oversized);
+                    continue;
+                }
+                let message: NetworkMessage = match deserialize_limited(&message, largest) {
+                    Ok(m) if (1..=types.last_type).contains(&m.type_id) => m,
+                    _ => {
+                        violations.add(connection, ViolationKind::Malformed);
                         continue 'clients;
                     }
                 };
+                stats.record_message_received(message.type_id, message.content.len());
+                if !rate_limits.admit(connection, message.type_id, &types, &guard, now) {
+                    violations.add(connection, ViolationKind::RateLimited);
+                    continue;
+                }
                 events.send(IncomingMessage {
                     type_id: message.type_id,
                     content: message.content,
-                    connection: ConnectionId(client_id),
+                    connection,
                 });
             }
         }
     }
 }
 
-fn read_channel_client(mut events: EventWriter<IncomingMessage>, mut client: ResMut<RenetClient>) {
-    for channel_id in [Channel::Default.id(), Channel::DefaultUnreliable.id()] {
-        while let Some(message) = client.receive_message(channel_id) {
+fn read_channel_client(
+    mut events: EventWriter<IncomingMessage>,
+    mut client: ResMut<RenetClient>,
+    mut stats: ResMut<NetworkStats>,
+) {
+    for channel in [Channel::Default, Channel::DefaultUnreliable] {
+        while let Some(message) = client.receive_message(channel.id()) {
+            stats.record_received(channel, message.len());
             let message: NetworkMessage = match bincode::deserialize(&message) {
                 Ok(m) => m,
                 Err(_) => {
@@ -312,13 +577,28 @@ fn read_channel_client(mut events: EventWriter<IncomingMessage>, mut client: Res
                     continue;
                 }
             };
-            events.send(IncomingMessage {
-                type_id: message.type_id,
-                content: message.content,
-                // TODO: Client should not have any connection id field for server?
-                // Using 0 as a placeholder here
-                connection: ConnectionId(0),
-            });
+            // Join snapshots contain messages that are handled in the order they were batched
+            let messages = if message.type_id == BATCH_TYPE_ID {
+                match snapshot::decompress(&message.content) {
+                    Some(messages) => messages,
+                    None => {
+                        warn!("Invalid join snapshot from server");
+                        continue;
+                    }
+                }
+            } else {
+                vec![message]
+            };
+            for message in messages {
+                stats.record_message_received(message.type_id, message.content.len());
+                events.send(IncomingMessage {
+                    type_id: message.type_id,
+                    content: message.content,
+                    // TODO: Client should not have any connection id field for server?
+                    // Using 0 as a placeholder here
+                    connection: ConnectionId(0),
+                });
+            }
         }
     }
 }
@@ -327,10 +607,26 @@ fn send_outbound_messages_server(
     receiver: &flume::Receiver<OutboundMessage>,
     mut server: ResMut<RenetServer>,
     players: Res<Players>,
+    mut bandwidth: ResMut<BandwidthUsage>,
+    mut stats: ResMut<NetworkStats>,
+    mut snapshots: ResMut<JoinSnapshots>,
     mut message_buffer: Local<Vec<OutboundMessage>>,
 ) {
     // Read messages from outbound channel
     message_buffer.extend(receiver.try_iter());
+    // Spectators have no body to keep in sync, players go first under congestion
+    for outbound in message_buffer.iter_mut() {
+        let only_spectators = match &outbound.receivers {
+            MessageReceivers::Single(connection) => players.is_spectator(*connection),
+            MessageReceivers::Set(connections) => {
+                !connections.is_empty() && connections.iter().all(|c| players.is_spectator(*c))
+            }
+            _ => false,
+        };
+        if only_spectators {
+            outbound.priority = outbound.priority.saturating_sub(SPECTATOR_PRIORITY_PENALTY);
+        }
+    }
     // Sort current messages by priority
     message_buffer.sort_unstable_by(|a, b| b.priority.cmp(&a.priority));
 
@@ -339,49 +635,90 @@ fn send_outbound_messages_server(
             type_id: outbound.type_id,
             content: outbound.content,
         };
-        match outbound.receivers {
-            MessageReceivers::AllPlayers => {
-                send_message_to(
-                    &mut server,
-                    message,
-                    outbound.kind,
-                    players.players.iter().map(|(id, _)| id).copied(),
-                );
-            }
-            MessageReceivers::Set(connections) => {
-                send_message_to(&mut server, message, outbound.kind, connections.into_iter());
-            }
+        let (bytes, receivers) = match outbound.receivers {
+            MessageReceivers::AllPlayers => send_message_to(
+                &mut server,
+                &mut stats,
+                &mut snapshots,
+                message,
+                outbound.kind,
+                players.players.iter().map(|(id, _)| id).copied(),
+            ),
+            MessageReceivers::Set(connections) => send_message_to(
+                &mut server,
+                &mut stats,
+                &mut snapshots,
+                message,
+                outbound.kind,
+                connections.into_iter(),
+            ),
             MessageReceivers::Server => {
                 panic!("Trying to send to server from server");
             }
-            MessageReceivers::Single(id) => {
-                send_message_to(&mut server, message, outbound.kind, std::iter::once(id));
-            }
-        }
+            MessageReceivers::Single(id) => send_message_to(
+                &mut server,
+                &mut stats,
+                &mut snapshots,
+                message,
+                outbound.kind,
+                std::iter::once(id),
+            ),
+        };
+        bandwidth.record(outbound.feature, bytes, receivers);
     }
 
     message_buffer.clear();
+
+    for (connection, content, messages) in snapshots.finish() {
+        let serialized: Bytes = bincode::serialize(&NetworkMessage {
+            type_id: BATCH_TYPE_ID,
+            content,
+        })
+        .unwrap()
+        .into();
+        debug!(%connection, messages, bytes = serialized.len(), "Sending join snapshot");
+        stats.record_sent(Channel::Default, serialized.len());
+        bandwidth.record(SNAPSHOT_FEATURE, serialized.len(), 1);
+        server.send_message(connection.0, Channel::Default.id(), serialized);
+    }
 }
 
+/// Returns the size of the message and how many connections it was sent to,
+/// not counting connections it was added to a join snapshot for
 fn send_message_to(
     server: &mut RenetServer,
+    stats: &mut NetworkStats,
+    snapshots: &mut JoinSnapshots,
     message: NetworkMessage,
     kind: MessageKind,
     receivers: impl Iterator<Item = ConnectionId>,
-) {
+) -> (usize, usize) {
+    let type_id = message.type_id;
+    let content_size = message.content.len();
     let serialized: Bytes = bincode::serialize(&message).unwrap().into();
     let channel = match kind {
         MessageKind::Reliable => Channel::Default,
         MessageKind::Unreliable => Channel::DefaultUnreliable,
     };
+    let mut count = 0;
     for id in receivers {
+        if matches!(kind, MessageKind::Reliable) && snapshots.is_pending(id) {
+            stats.record_message_sent(type_id, content_size);
+            snapshots.add(id, message.clone());
+            continue;
+        }
         server.send_message(id.0, channel.id(), serialized.clone());
+        stats.record_sent(channel, serialized.len());
+        stats.record_message_sent(type_id, content_size);
+        count += 1;
     }
+    (serialized.len(), count)
 }
 
 fn send_outbound_messages_client(
     receiver: &flume::Receiver<OutboundMessage>,
     mut client: ResMut<RenetClient>,
+    mut stats: ResMut<NetworkStats>,
 ) {
     for outbound in receiver.try_iter() {
         let channel = match outbound.kind {
@@ -390,10 +727,49 @@ fn send_outbound_messages_client(
         };
 
         let message: NetworkMessage = outbound.into();
-        client.send_message(channel.id(), bincode::serialize(&message).unwrap());
+        let serialized = bincode::serialize(&message).unwrap();
+        stats.record_sent(channel, serialized.len());
+        stats.record_message_sent(message.type_id, message.content.len());
+        client.send_message(channel.id(), serialized);
     }
 }
 
+/// Lets violation scores decay, flags clients past the flag threshold
+/// and disconnects clients that went past the threshold
+fn enforce_violations(
+    mut violations: ResMut<MessageViolations>,
+    mut rate_limits: ResMut<RateLimits>,
+    mut server: ResMut<RenetServer>,
+    mut flagged_events: EventWriter<ConnectionFlagged>,
+    time: Res<Time>,
+) {
+    let decay = violations.decay_per_second * time.delta_seconds();
+    let flag_threshold = violations.flag_threshold;
+    let threshold = violations.threshold;
+    let MessageViolations {
+        scores, flagged, ..
+    } = &mut *violations;
+    scores.retain(|connection, score| {
+        if *score >= flag_threshold && flagged.insert(*connection) {
+            warn!(%connection, score = *score, "Flagged client for sending invalid messages");
+            flagged_events.send(ConnectionFlagged {
+                connection: *connection,
+                score: *score,
+            });
+        }
+        if *score >= threshold {
+            warn!(%connection, "Disconnecting client for sending invalid messages");
+            server.disconnect(connection.0);
+            return false;
+        }
+        *score -= decay;
+        *score > 0.0
+    });
+    // Clients that behave again can be flagged again
+    flagged.retain(|connection| scores.contains_key(connection));
+    rate_limits.forget_idle(time.raw_elapsed_seconds());
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, SystemSet)]
 pub(crate) enum ReadMessagesSet {
     /// Read network messages from the underlying transport
@@ -409,6 +785,7 @@ impl Plugin for MessagingPlugin {
         let (tx, rx) = flume::unbounded();
 
         app.init_resource::<MessageTypes>()
+            .init_resource::<ProtocolDescription>()
             .insert_resource(InternalSenderRes { sender: tx })
             .add_event::<IncomingMessage>()
             .configure_sets(
@@ -428,8 +805,8 @@ impl Plugin for MessagingPlugin {
             .unwrap()
             .is_client()
         {
-            let outbound = move |client: ResMut<RenetClient>| {
-                send_outbound_messages_client(&rx, client);
+            let outbound = move |client: ResMut<RenetClient>, stats: ResMut<NetworkStats>| {
+                send_outbound_messages_client(&rx, client, stats);
             };
             app.add_systems(
                 PreUpdate,
@@ -439,14 +816,33 @@ impl Plugin for MessagingPlugin {
         } else {
             let outbound = move |server: ResMut<RenetServer>,
                                  players: Res<Players>,
+                                 bandwidth: ResMut<BandwidthUsage>,
+                                 stats: ResMut<NetworkStats>,
+                                 snapshots: ResMut<JoinSnapshots>,
                                  buffer: Local<Vec<OutboundMessage>>| {
-                send_outbound_messages_server(&rx, server, players, buffer);
+                send_outbound_messages_server(
+                    &rx, server, players, bandwidth, stats, snapshots, buffer,
+                );
             };
-            app.add_systems(
-                PreUpdate,
-                read_channel_server.in_set(ReadMessagesSet::ReadChannel),
-            )
-            .add_systems(PostUpdate, outbound.in_set(NetworkSet::SendOutgoing));
+            let guard = app
+                .world
+                .get_resource::<GuardConfig>()
+                .cloned()
+                .unwrap_or_default();
+            app.insert_resource(guard.limits())
+                .insert_resource(guard.violations())
+                .insert_resource(guard)
+                .init_resource::<RateLimits>()
+                .init_resource::<JoinSnapshots>()
+                .add_event::<ConnectionFlagged>()
+                .add_systems(
+                    PreUpdate,
+                    (
+                        read_channel_server.in_set(ReadMessagesSet::ReadChannel),
+                        enforce_violations.after(ReadMessagesSet::EmitEvents),
+                    ),
+                )
+                .add_systems(PostUpdate, outbound.in_set(NetworkSet::SendOutgoing));
         }
     }
 }
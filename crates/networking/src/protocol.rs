@@ -0,0 +1,167 @@
+use std::fmt::Write;
+
+use bevy::{prelude::*, utils::Uuid};
+use bevy_renet::renet::SendType;
+use serde::Serialize;
+
+use crate::{
+    messaging::Channel,
+    variable::{NetworkedFromServer, NetworkedToClient},
+};
+
+/// Everything that can be sent over the network, collected while types are registered.
+/// Meant for authors of other clients and tools, and for finding protocol mismatches.
+#[derive(Resource, Default, Serialize)]
+pub struct ProtocolDescription {
+    messages: Vec<MessageDescription>,
+    components: Vec<SyncedTypeDescription>,
+    resources: Vec<SyncedTypeDescription>,
+}
+
+#[derive(Serialize)]
+struct MessageDescription {
+    id: u16,
+    name: &'static str,
+}
+
+/// A server type that is synced to a client type
+#[derive(Serialize, Clone)]
+struct SyncedTypeDescription {
+    /// Index in the list sorted by uuid, which is what is sent over the network
+    id: u16,
+    uuid: Uuid,
+    server_type: &'static str,
+    client_type: &'static str,
+    /// Checksum of the networked fields
+    data_signature: u64,
+}
+
+#[derive(Serialize)]
+struct ChannelDescription {
+    id: u8,
+    name: &'static str,
+    send_type: &'static str,
+}
+
+#[derive(Serialize)]
+struct ProtocolDocument<'a> {
+    channels: Vec<ChannelDescription>,
+    #[serde(flatten)]
+    protocol: &'a ProtocolDescription,
+}
+
+impl ProtocolDescription {
+    pub(crate) fn add_message<T: 'static>(&mut self, id: u16) {
+        self.messages.push(MessageDescription {
+            id,
+            name: std::any::type_name::<T>(),
+        });
+    }
+
+    pub(crate) fn add_component<
+        S: NetworkedToClient + 'static,
+        C: NetworkedFromServer + 'static,
+    >(
+        &mut self,
+    ) {
+        add_synced::<S, C>(&mut self.components);
+    }
+
+    pub(crate) fn add_resource<S: NetworkedToClient + 'static, C: NetworkedFromServer + 'static>(
+        &mut self,
+    ) {
+        add_synced::<S, C>(&mut self.resources);
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(&ProtocolDocument {
+            channels: channels(),
+            protocol: self,
+        })
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::from("# Network protocol\n\n");
+
+        out.push_str("## Channels\n\n");
+        out.push_str("Messages are sent on `Default` unless sent unreliably. ");
+        out.push_str("Priority is chosen per message when sending.\n\n");
+        out.push_str("| Id | Name | Send type |\n|---|---|---|\n");
+        for channel in channels() {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} |",
+                channel.id, channel.name, channel.send_type
+            );
+        }
+
+        out.push_str("\n## Messages\n\n| Id | Type |\n|---|---|\n");
+        for message in self.messages.iter() {
+            let _ = writeln!(out, "| {} | `{}` |", message.id, message.name);
+        }
+
+        for (title, types) in [
+            ("Components", &self.components),
+            ("Resources", &self.resources),
+        ] {
+            let _ = write!(
+                out,
+                "\n## {}\n\n| Id | Uuid | Server type | Client type | Signature |\n|---|---|---|---|---|\n",
+                title
+            );
+            for entry in types.iter() {
+                let _ = writeln!(
+                    out,
+                    "| {} | {} | `{}` | `{}` | {:016x} |",
+                    entry.id,
+                    entry.uuid,
+                    entry.server_type,
+                    entry.client_type,
+                    entry.data_signature
+                );
+            }
+        }
+        out
+    }
+}
+
+fn add_synced<S: NetworkedToClient + 'static, C: NetworkedFromServer + 'static>(
+    types: &mut Vec<SyncedTypeDescription>,
+) {
+    types.push(SyncedTypeDescription {
+        id: 0,
+        uuid: C::TYPE_UUID,
+        server_type: std::any::type_name::<S>(),
+        client_type: std::any::type_name::<C>(),
+        data_signature: C::data_signature(),
+    });
+    // Ids depend on the uuids of everything registered so far
+    types.sort_unstable_by_key(|t| t.uuid);
+    for (index, entry) in types.iter_mut().enumerate() {
+        entry.id = index as u16;
+    }
+}
+
+fn channels() -> Vec<ChannelDescription> {
+    let configs = Channel::channels_config();
+    Channel::ALL
+        .into_iter()
+        .map(|channel| {
+            let id = channel.id();
+            let name = channel.name();
+            let send_type = configs
+                .iter()
+                .find(|c| c.channel_id == id)
+                .map_or("Unknown", |c| match c.send_type {
+                    SendType::Unreliable => "Unreliable",
+                    SendType::ReliableOrdered { .. } => "ReliableOrdered",
+                    SendType::ReliableUnordered { .. } => "ReliableUnordered",
+                });
+            ChannelDescription {
+                id,
+                name,
+                send_type,
+            }
+        })
+        .collect()
+}
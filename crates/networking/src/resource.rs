@@ -1,3 +1,5 @@
+use std::marker::PhantomData;
+
 use bevy::{ecs::system::StaticSystemParam, prelude::*, utils::HashSet};
 use bytes::Bytes;
 use serde::{Deserialize, Serialize};
@@ -5,9 +7,10 @@ use serde::{Deserialize, Serialize};
 use crate::{
     is_server,
     messaging::{AppExt as MessageAppExt, MessageEvent, MessageReceivers, MessageSender},
+    protocol::ProtocolDescription,
     time::ServerNetworkTime,
     variable::{self, NetworkRegistry, NetworkedFromServer, NetworkedToClient},
-    NetworkSet, Players, ServerEvent,
+    ClientState, NetworkSet, Players, ServerEvent,
 };
 
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -31,12 +34,106 @@ type NetworkedResourceRegistry = NetworkRegistry<ResourceNetworkId>;
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct NetworkedResourceMessage {
     resource_id: ResourceNetworkId,
+    /// Server tick the data is from, so late unreliable updates don't overwrite newer ones
+    tick: u32,
     data: Bytes,
 }
 
+/// How changes to a networked resource are sent to clients, chosen when it is registered.
+/// Players that connect always get the full resource reliably.
+#[derive(Clone, Copy, Debug)]
+pub struct ReplicationPolicy {
+    /// Unreliable updates may be lost, but don't hold up other messages when they are
+    reliable: bool,
+    /// Only send fields that changed since the last update, instead of the whole resource
+    delta: bool,
+    /// Most updates sent per second, changes in between are sent together with the next update
+    max_updates_per_second: Option<f32>,
+}
+
+impl Default for ReplicationPolicy {
+    fn default() -> Self {
+        Self::reliable()
+    }
+}
+
+impl ReplicationPolicy {
+    pub fn reliable() -> Self {
+        Self {
+            reliable: true,
+            delta: false,
+            max_updates_per_second: None,
+        }
+    }
+
+    /// For frequently changing values where only the latest one matters, like timers
+    pub fn unreliable() -> Self {
+        Self {
+            reliable: false,
+            ..Self::reliable()
+        }
+    }
+
+    /// Send only the fields that changed. Requires reliable updates, as no update may be lost.
+    pub fn delta(mut self) -> Self {
+        assert!(self.reliable, "Delta updates must be sent reliably");
+        self.delta = true;
+        self
+    }
+
+    pub fn max_updates_per_second(mut self, rate: f32) -> Self {
+        self.max_updates_per_second = Some(rate);
+        self
+    }
+}
+
+/// The replication policy of a resource and what was sent of it
+#[derive(Resource)]
+struct ResourceReplication<S> {
+    policy: ReplicationPolicy,
+    /// Changes that were not sent yet because of the rate limit
+    pending: bool,
+    last_sent_tick: Option<u32>,
+    last_sent_seconds: f32,
+    marker: PhantomData<fn() -> S>,
+}
+
+impl<S> ResourceReplication<S> {
+    fn new(policy: ReplicationPolicy) -> Self {
+        Self {
+            policy,
+            pending: false,
+            last_sent_tick: None,
+            last_sent_seconds: f32::NEG_INFINITY,
+            marker: PhantomData,
+        }
+    }
+
+    fn may_send(&self, now: f32) -> bool {
+        self.policy
+            .max_updates_per_second
+            .map_or(true, |rate| now - self.last_sent_seconds >= 1.0 / rate)
+    }
+
+    fn send(
+        &self,
+        sender: &mut MessageSender,
+        message: &NetworkedResourceMessage,
+        receivers: MessageReceivers,
+        priority: i16,
+    ) {
+        if self.policy.reliable {
+            sender.send_with_priority(message, receivers, priority);
+        } else {
+            sender.send_unreliable(message, receivers);
+        }
+    }
+}
+
 fn send_networked_resource_to_new<S: NetworkedToClient + Resource, C: NetworkedFromServer>(
     resource: Res<S>,
     registry: Res<NetworkedResourceRegistry>,
+    server_time: Res<ServerNetworkTime>,
     mut sender: MessageSender,
     mut events: EventReader<ServerEvent>,
     mut param: StaticSystemParam<S::Param>,
@@ -44,6 +141,7 @@ fn send_networked_resource_to_new<S: NetworkedToClient + Resource, C: NetworkedF
     let resource_id = registry
         .get_id(&C::TYPE_UUID)
         .expect("Networked resource incorrectly registered");
+    let tick = server_time.current_tick();
     let new_players = events.iter().filter_map(|e| match e {
         ServerEvent::PlayerConnected(c) => Some(c),
         _ => None,
@@ -57,7 +155,11 @@ fn send_networked_resource_to_new<S: NetworkedToClient + Resource, C: NetworkedF
             };
 
             sender.send(
-                &NetworkedResourceMessage { resource_id, data },
+                &NetworkedResourceMessage {
+                    resource_id,
+                    tick,
+                    data,
+                },
                 MessageReceivers::Single(*connection),
             );
         }
@@ -68,58 +170,86 @@ fn send_networked_resource_to_new<S: NetworkedToClient + Resource, C: NetworkedF
                 .serialize(&mut param, None, None)
                 .expect("Serializing without a specific receiver should always return data");
             sender.send(
-                &NetworkedResourceMessage { resource_id, data },
+                &NetworkedResourceMessage {
+                    resource_id,
+                    tick,
+                    data,
+                },
                 MessageReceivers::Set(new_observers),
             );
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn send_changed_networked_resource<S: NetworkedToClient + Resource, C: NetworkedFromServer>(
     mut resource: ResMut<S>,
+    mut replication: ResMut<ResourceReplication<S>>,
     registry: Res<NetworkedResourceRegistry>,
     players: Res<Players>,
     server_time: Res<ServerNetworkTime>,
+    time: Res<Time>,
     mut sender: MessageSender,
     mut param: StaticSystemParam<S::Param>,
 ) {
-    if !resource.is_changed() {
+    let tick = server_time.current_tick();
+    // Changes are recorded on the tick they happen, even if they are sent later
+    if resource.is_changed() && resource.update_state(tick) {
+        replication.pending = true;
+    }
+    let now = time.elapsed_seconds();
+    if !replication.pending || !replication.may_send(now) {
         return;
     }
 
     let resource_id = registry
         .get_id(&C::TYPE_UUID)
         .expect("Networked resource incorrectly registered");
-
-    if !resource.update_state(server_time.current_tick()) {
-        // Resource didn't change this tick
-        return;
-    }
+    let since_tick = replication
+        .last_sent_tick
+        .filter(|_| replication.policy.delta);
+    let priority = resource.priority();
 
     let players = players.players().keys();
     if S::receiver_matters() {
         // Serialize resource for every receiver
         for connection in players {
-            let data = match resource.serialize(&mut param, Some(*connection), None) {
+            let data = match resource.serialize(&mut param, Some(*connection), since_tick) {
                 Some(d) => d,
                 None => continue,
             };
 
-            sender.send(
-                &NetworkedResourceMessage { resource_id, data },
+            replication.send(
+                &mut sender,
+                &NetworkedResourceMessage {
+                    resource_id,
+                    tick,
+                    data,
+                },
                 MessageReceivers::Single(*connection),
+                priority,
             );
         }
     } else {
         let all_players: HashSet<_> = players.copied().collect();
         let data = resource
-            .serialize(&mut param, None, None)
+            .serialize(&mut param, None, since_tick)
             .expect("Serializing without a specific receiver should always return data");
-        sender.send(
-            &NetworkedResourceMessage { resource_id, data },
+        replication.send(
+            &mut sender,
+            &NetworkedResourceMessage {
+                resource_id,
+                tick,
+                data,
+            },
             MessageReceivers::Set(all_players),
+            priority,
         );
     }
+
+    replication.pending = false;
+    replication.last_sent_tick = Some(tick);
+    replication.last_sent_seconds = now;
 }
 
 fn receive_networked_resource<C: NetworkedFromServer + Resource>(
@@ -127,8 +257,14 @@ fn receive_networked_resource<C: NetworkedFromServer + Resource>(
     mut resource: Option<ResMut<C>>,
     registry: Res<NetworkedResourceRegistry>,
     mut param: bevy::ecs::system::StaticSystemParam<C::Param>,
+    client_state: Res<State<ClientState>>,
+    mut last_tick: Local<Option<u32>>,
     mut commands: Commands,
 ) {
+    // Ticks start over on another server
+    if client_state.is_changed() {
+        *last_tick = None;
+    }
     for event in events.iter() {
         let message = &event.message;
         // Check if the message is for this resource
@@ -138,6 +274,11 @@ fn receive_networked_resource<C: NetworkedFromServer + Resource>(
         if uuid != &C::TYPE_UUID {
             continue;
         }
+        // An unreliable update that arrived after a newer one
+        if last_tick.map_or(false, |last| message.tick < last) {
+            continue;
+        }
+        *last_tick = Some(message.tick);
 
         match resource.as_deref_mut() {
             Some(res) => res.deserialize(&mut param, &message.data),
@@ -162,12 +303,26 @@ pub trait AppExt {
     where
         S: NetworkedToClient + Resource,
         C: NetworkedFromServer + Resource;
+
+    fn add_networked_resource_with_policy<S, C>(&mut self, policy: ReplicationPolicy) -> &mut App
+    where
+        S: NetworkedToClient + Resource,
+        C: NetworkedFromServer + Resource;
 }
 
 impl AppExt for App {
-    /// Registers a networked resource.
+    /// Registers a networked resource that is sent in full and reliably when it changes.
     /// Changes are synced from the server resource (`S`) to the client resource (`C`).
     fn add_networked_resource<S, C>(&mut self) -> &mut App
+    where
+        S: NetworkedToClient + Resource,
+        C: NetworkedFromServer + Resource,
+    {
+        self.add_networked_resource_with_policy::<S, C>(ReplicationPolicy::default())
+    }
+
+    /// Registers a networked resource whose changes are sent as the policy says.
+    fn add_networked_resource_with_policy<S, C>(&mut self, policy: ReplicationPolicy) -> &mut App
     where
         S: NetworkedToClient + Resource,
         C: NetworkedFromServer + Resource,
@@ -178,15 +333,20 @@ impl AppExt for App {
         if !registry.register::<C>() {
             panic!("Client resource was already registered");
         }
+        self.init_resource::<ProtocolDescription>()
+            .world
+            .resource_mut::<ProtocolDescription>()
+            .add_resource::<S, C>();
         if is_server(self) {
-            self.add_systems(
-                PostUpdate,
-                (
-                    send_networked_resource_to_new::<S, C>,
-                    send_changed_networked_resource::<S, C>,
-                )
-                    .in_set(NetworkSet::ServerWrite),
-            );
+            self.insert_resource(ResourceReplication::<S>::new(policy))
+                .add_systems(
+                    PostUpdate,
+                    (
+                        send_networked_resource_to_new::<S, C>,
+                        send_changed_networked_resource::<S, C>,
+                    )
+                        .in_set(NetworkSet::ServerWrite),
+                );
         } else {
             self.add_systems(
                 PreUpdate,
@@ -5,6 +5,7 @@ use bevy::{
         system::Command,
     },
     prelude::*,
+    utils::HashMap,
 };
 use smallvec::SmallVec;
 
@@ -19,9 +20,13 @@ pub(crate) struct ScenePlugin;
 impl Plugin for ScenePlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<NetworkSceneSpawner>()
+            .init_resource::<ScenePresets>()
+            .init_resource::<IncludedScenes>()
             .add_event::<NetworkSceneEvent>()
             .register_type::<NetworkedChild>()
             .register_type::<HasNetworkedChildren>()
+            .register_type::<SceneIncludes>()
+            .register_type::<ApplyPresets>()
             .add_systems(
                 PreUpdate,
                 (
@@ -40,6 +45,12 @@ impl Plugin for ScenePlugin {
 #[derive(Component, Default)]
 pub struct NetworkScene(pub(crate) Handle<DynamicScene>);
 
+impl NetworkScene {
+    pub fn handle(&self) -> &Handle<DynamicScene> {
+        &self.0
+    }
+}
+
 impl From<Handle<DynamicScene>> for NetworkScene {
     fn from(handle: Handle<DynamicScene>) -> Self {
         Self(handle)
@@ -68,6 +79,58 @@ impl MapEntities for HasNetworkedChildren {
     }
 }
 
+/// Includes other scenes into this one.
+/// The root entity of every included scene is merged into the root of this scene.
+/// Components defined in this scene override the included ones.
+///
+/// Must be placed on the root entity (#0) of a scene.
+#[derive(Component, Reflect, Default, Clone)]
+#[reflect(Component)]
+pub struct SceneIncludes {
+    pub scenes: Vec<String>,
+}
+
+/// Applies named presets registered with [`AppExt::add_scene_preset`] after the scene is spawned.
+/// Presets run on both the server and client.
+#[derive(Component, Reflect, Default, Clone)]
+#[reflect(Component)]
+pub struct ApplyPresets {
+    pub presets: Vec<String>,
+}
+
+/// A function that adds components to a freshly spawned scene entity.
+pub type ScenePreset = fn(&mut World, Entity);
+
+#[derive(Resource, Default)]
+struct ScenePresets {
+    presets: HashMap<String, ScenePreset>,
+}
+
+/// Strong handles to scenes that are included by other scenes.
+/// Entries are removed once the including scene is unloaded.
+#[derive(Resource, Default)]
+struct IncludedScenes {
+    handles: HashMap<Handle<DynamicScene>, Vec<Handle<DynamicScene>>>,
+}
+
+pub trait AppExt {
+    fn add_scene_preset(&mut self, name: impl Into<String>, preset: ScenePreset) -> &mut Self;
+}
+
+impl AppExt for App {
+    /// Registers a preset that scenes can apply using [`ApplyPresets`].
+    fn add_scene_preset(&mut self, name: impl Into<String>, preset: ScenePreset) -> &mut Self {
+        let name = name.into();
+        let previous = self
+            .world
+            .get_resource_or_insert_with(ScenePresets::default)
+            .presets
+            .insert(name.clone(), preset);
+        assert!(previous.is_none(), "Scene preset {} registered twice", name);
+        self
+    }
+}
+
 #[derive(Event)]
 pub enum NetworkSceneEvent {
     Created(Entity),
@@ -91,15 +154,35 @@ struct NetworkSceneSpawner {
 fn prepare_loaded_scenes(
     mut scenes: ResMut<Assets<DynamicScene>>,
     mut events: EventReader<AssetEvent<DynamicScene>>,
+    mut included: ResMut<IncludedScenes>,
+    asset_server: Res<AssetServer>,
 ) {
     for event in events.iter() {
-        let AssetEvent::Created { handle } = event else {
-            continue;
+        let handle = match event {
+            AssetEvent::Created { handle } => handle,
+            // Nothing spawns the scene anymore, its includes can be unloaded too
+            AssetEvent::Removed { handle } => {
+                included.handles.remove(handle);
+                continue;
+            }
+            AssetEvent::Modified { .. } => continue,
         };
         let Some(scene) = scenes.get_mut(handle) else {
             continue;
         };
 
+        // Start loading included scenes
+        if let Some(includes) = scene_includes(scene) {
+            included.handles.insert(
+                handle.clone_weak(),
+                includes
+                    .scenes
+                    .iter()
+                    .map(|path| asset_server.load(path.as_str()))
+                    .collect(),
+            );
+        }
+
         // Find all entities with `NetworkedChild` component
         let static_children: SmallVec<_> = scene
             .entities
@@ -137,6 +220,92 @@ fn queue_network_scenes(
     }
 }
 
+/// Adds rendering components to every entity with a mesh in a spawned scene hierarchy.
+/// Useful for presets, as scene files can't contain materials yet.
+pub fn insert_pbr_bundles(world: &mut World, root: Entity, material: Handle<StandardMaterial>) {
+    let mut to_visit = vec![root];
+    while let Some(entity) = to_visit.pop() {
+        let entity_ref = world.entity(entity);
+        if let Some(children) = entity_ref.get::<Children>() {
+            to_visit.extend(children.iter().copied());
+        }
+        if entity_ref.contains::<Handle<StandardMaterial>>() {
+            continue;
+        }
+        let Some(mesh) = entity_ref.get::<Handle<Mesh>>().cloned() else {
+            continue;
+        };
+        let transform = entity_ref.get::<Transform>().copied().unwrap_or_default();
+        world.entity_mut(entity).insert(PbrBundle {
+            mesh,
+            material: material.clone(),
+            transform,
+            ..Default::default()
+        });
+    }
+}
+
+/// How deep scene includes can be nested.
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+fn scene_includes(scene: &DynamicScene) -> Option<SceneIncludes> {
+    let component = scene
+        .entities
+        .first()?
+        .components
+        .iter()
+        .find(|c| c.represents::<SceneIncludes>())?;
+    let mut includes = SceneIncludes::default();
+    includes.apply(component.as_ref());
+    Some(includes)
+}
+
+/// Collects a scene and all its (nested) includes in the order they should be written.
+/// Returns `None` if any of them are not loaded yet.
+fn resolve_includes<'a>(
+    handle: &Handle<DynamicScene>,
+    scene_assets: &'a Assets<DynamicScene>,
+    included: &IncludedScenes,
+    depth: usize,
+    resolved: &mut Vec<&'a DynamicScene>,
+) -> Option<()> {
+    // Guard against include cycles
+    if depth > MAX_INCLUDE_DEPTH {
+        warn!("Scene include depth limit reached, is there a cycle?");
+        return Some(());
+    }
+
+    let scene = scene_assets.get(handle)?;
+    if let Some(includes) = included.handles.get(handle) {
+        for include in includes {
+            resolve_includes(include, scene_assets, included, depth + 1, resolved)?;
+        }
+    }
+    resolved.push(scene);
+    Some(())
+}
+
+/// Writes a scene onto an existing entity, keeping any children it already has.
+fn write_scene_onto(
+    world: &mut World,
+    scene: &DynamicScene,
+    entity: Entity,
+) -> Result<(), bevy::scene::SceneSpawnError> {
+    let existing_children = world.entity_mut(entity).take::<Children>();
+
+    // Make the scene entity #0 add components onto our existing entity
+    let mut entity_map = EntityMap::default();
+    entity_map.insert(Entity::from_raw(0), entity);
+    let result = scene.write_to_world(world, &mut entity_map);
+
+    // Merge any existing children into the new children
+    if let Some(children) = existing_children {
+        world.entity_mut(entity).push_children(&children);
+    }
+
+    result
+}
+
 // Spawns loaded networked scenes into the world
 fn spawn_network_scenes(world: &mut World) {
     world.resource_scope(|world, mut spawner: Mut<NetworkSceneSpawner>| {
@@ -145,8 +314,12 @@ fn spawn_network_scenes(world: &mut World) {
         }
         world.resource_scope(|world, scene_assets: Mut<Assets<DynamicScene>>| {
             let registry = world.resource::<AppTypeRegistry>().clone();
+            let included = world.remove_resource::<IncludedScenes>().unwrap();
             spawner.scenes_to_spawn.retain(|(entity, scene_handle)| {
-                let Some(scene) = scene_assets.get(scene_handle) else {
+                let mut scenes = Vec::new();
+                if resolve_includes(scene_handle, &scene_assets, &included, 0, &mut scenes)
+                    .is_none()
+                {
                     return true;
                 };
 
@@ -154,8 +327,6 @@ fn spawn_network_scenes(world: &mut World) {
 
                 // Preserve transform so it doesn't get overwritten
                 let existing_transform = world.get::<Transform>(*entity).cloned();
-                // Remove existing children so we can merge them with any potentially new children later
-                let existing_children = world.entity_mut(*entity).take::<Children>();
 
                 // HACK: Remove and store components that would be remapped by the scene system
                 let mut temporary_world = World::new();
@@ -186,24 +357,19 @@ fn spawn_network_scenes(world: &mut World) {
                 }
                 drop(read_registry);
 
-                // Make the scene entity #0 add components onto our existing entity
-                let mut entity_map = EntityMap::default();
-                entity_map.insert(Entity::from_raw(0), *entity);
-
-                if let Err(err) = scene.write_to_world(world, &mut entity_map) {
-                    warn!(entity = ?entity, "Error spawning network scene: {}", err);
-                    return false;
+                // Included scenes are written first, so the including scene can override them
+                // TODO: Networked children are only supported in the outermost scene
+                for scene in scenes {
+                    if let Err(err) = write_scene_onto(world, scene, *entity) {
+                        warn!(entity = ?entity, "Error spawning network scene: {}", err);
+                        return false;
+                    }
                 }
 
                 if let Some(transform) = existing_transform {
                     world.entity_mut(*entity).insert(transform);
                 }
 
-                // Merge any existing children into the new children
-                if let Some(children) = existing_children {
-                    world.entity_mut(*entity).push_children(&children);
-                }
-
                 // Add back the problematic components
                 let read_registry = registry.read();
                 for type_id in problematic_components.iter() {
@@ -246,6 +412,19 @@ fn spawn_network_scenes(world: &mut World) {
                     }
                 }
 
+                // Apply presets after everything else is in place
+                if let Some(ApplyPresets { presets }) =
+                    world.entity(*entity).get::<ApplyPresets>().cloned()
+                {
+                    for name in presets.iter() {
+                        let preset = world.resource::<ScenePresets>().presets.get(name).copied();
+                        match preset {
+                            Some(preset) => preset(world, *entity),
+                            None => warn!(entity = ?entity, preset = ?name, "Unknown scene preset"),
+                        }
+                    }
+                }
+
                 // Emit scene event
                 world
                     .resource_mut::<Events<NetworkSceneEvent>>()
@@ -253,6 +432,7 @@ fn spawn_network_scenes(world: &mut World) {
 
                 false
             });
+            world.insert_resource(included);
         });
     });
 }
@@ -0,0 +1,79 @@
+//! Join snapshots, which bundle what a connection receives when it starts observing the world.
+//!
+//! When an observer is first placed, it observes all cells in its range at once. Every reliable
+//! message for its connection in that frame, like spawns and components, is sent as one compressed batch.
+//! The client unpacks the batch in order, so entities exist before the updates that follow.
+//! Transforms keep using their own channel and wait on the client until their entity is spawned.
+
+use std::io::{Read, Write};
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use bytes::Bytes;
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+
+use crate::{messaging::NetworkMessage, ConnectionId};
+
+/// Type id of a batch, registered message types start at 1
+pub(crate) const BATCH_TYPE_ID: u16 = 0;
+/// Most uncompressed bytes in one batch, larger snapshots are split into several
+const MAX_BATCH_BYTES: usize = 256 * 1024;
+
+#[derive(Resource, Default)]
+pub(crate) struct JoinSnapshots {
+    /// Connections whose messages are batched this frame
+    pending: HashSet<ConnectionId>,
+    batches: HashMap<ConnectionId, Vec<NetworkMessage>>,
+}
+
+impl JoinSnapshots {
+    pub(crate) fn request(&mut self, connection: ConnectionId) {
+        self.pending.insert(connection);
+    }
+
+    pub(crate) fn is_pending(&self, connection: ConnectionId) -> bool {
+        self.pending.contains(&connection)
+    }
+
+    pub(crate) fn add(&mut self, connection: ConnectionId, message: NetworkMessage) {
+        self.batches.entry(connection).or_default().push(message);
+    }
+
+    /// Compresses the messages batched this frame, split to stay below the batch size.
+    /// Returns the compressed batches with how many messages are in each.
+    pub(crate) fn finish(&mut self) -> Vec<(ConnectionId, Bytes, usize)> {
+        self.pending.clear();
+        let mut finished = Vec::new();
+        for (connection, messages) in self.batches.drain() {
+            let mut batch = Vec::new();
+            let mut size = 0;
+            for message in messages {
+                size += message.content.len();
+                batch.push(message);
+                if size >= MAX_BATCH_BYTES {
+                    finished.push((connection, compress(&batch), batch.len()));
+                    batch.clear();
+                    size = 0;
+                }
+            }
+            if !batch.is_empty() {
+                finished.push((connection, compress(&batch), batch.len()));
+            }
+        }
+        finished
+    }
+}
+
+fn compress(messages: &[NetworkMessage]) -> Bytes {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+    bincode::serialize_into(&mut encoder, messages).unwrap();
+    encoder.finish().unwrap().into()
+}
+
+pub(crate) fn decompress(data: &[u8]) -> Option<Vec<NetworkMessage>> {
+    let mut decoded = Vec::new();
+    DeflateDecoder::new(data).read_to_end(&mut decoded).ok()?;
+    bincode::deserialize(&decoded).ok()
+}
@@ -1,6 +1,9 @@
 use bevy::{
     asset::AssetPathId,
-    ecs::query::{Has, QuerySingleError},
+    ecs::{
+        query::{Has, QuerySingleError},
+        system::SystemState,
+    },
     prelude::*,
     scene::DynamicScene,
     utils::{HashMap, HashSet, Uuid},
@@ -180,25 +183,78 @@ fn network_deleted_entities(
     mut entity_events: EventWriter<ServerEntityEvent>,
 ) {
     for entity in removed.iter() {
-        let identity = identities.get_identity(entity).unwrap();
-        if let Some(visibility) = visibilities.visibility.get(&identity) {
-            let observers: HashSet<ConnectionId> = visibility.all_observers().copied().collect();
-            if !observers.is_empty() {
-                entity_events.send_batch(
-                    observers
-                        .iter()
-                        .map(|c| ServerEntityEvent::Despawned((entity, *c))),
-                );
-                sender.send_with_priority(
-                    &SpawnMessage::Despawn(identity),
-                    MessageReceivers::Set(observers),
-                    DESPAWN_MESSAGE_PRIORITY,
-                );
-            }
-        }
+        // Already handled when it was despawned through `release_network_identity`
+        let Some(identity) = identities.get_identity(entity) else {
+            continue;
+        };
+        let observers = observers_of(&visibilities, identity);
+        send_despawn(entity, identity, observers, &mut sender, &mut entity_events);
     }
 }
 
+/// Tells observers that a networked entity is gone and frees its identity right away.
+///
+/// Entities despawned any other way are handled at the end of the frame.
+/// Commands that despawn entities call this so the identity never points to a despawned entity.
+pub fn release_network_identity(world: &mut World, entity: Entity) {
+    let Some(identity) = world
+        .get_resource::<NetworkIdentities>()
+        .and_then(|identities| identities.get_identity(entity))
+    else {
+        return;
+    };
+
+    // Only servers keep track of who sees an entity
+    let observers = world
+        .get_resource::<NetworkVisibilities>()
+        .map(|visibilities| observers_of(visibilities, identity))
+        .unwrap_or_default();
+    if !observers.is_empty() {
+        let mut state: SystemState<(MessageSender, EventWriter<ServerEntityEvent>)> =
+            SystemState::new(world);
+        let (mut sender, mut entity_events) = state.get_mut(world);
+        send_despawn(entity, identity, observers, &mut sender, &mut entity_events);
+        state.apply(world);
+    }
+
+    world
+        .resource_mut::<NetworkIdentities>()
+        .remove_entity(entity);
+}
+
+fn observers_of(
+    visibilities: &NetworkVisibilities,
+    identity: NetworkIdentity,
+) -> HashSet<ConnectionId> {
+    visibilities
+        .visibility
+        .get(&identity)
+        .map(|visibility| visibility.all_observers().copied().collect())
+        .unwrap_or_default()
+}
+
+fn send_despawn(
+    entity: Entity,
+    identity: NetworkIdentity,
+    observers: HashSet<ConnectionId>,
+    sender: &mut MessageSender,
+    entity_events: &mut EventWriter<ServerEntityEvent>,
+) {
+    if observers.is_empty() {
+        return;
+    }
+    entity_events.send_batch(
+        observers
+            .iter()
+            .map(|c| ServerEntityEvent::Despawned((entity, *c))),
+    );
+    sender.send_with_priority(
+        &SpawnMessage::Despawn(identity),
+        MessageReceivers::Set(observers),
+        DESPAWN_MESSAGE_PRIORITY,
+    );
+}
+
 fn receive_spawn(
     mut spawn_events: EventReader<MessageEvent<SpawnMessage>>,
     mut entity_events: EventWriter<NetworkedEntityEvent>,
@@ -288,6 +344,13 @@ impl ClientControls {
     pub fn controlling_player(&self, entity: Entity) -> Option<Uuid> {
         self.reverse_mapping.get(&entity).copied()
     }
+
+    /// Takes away control from all players, for example when the world is reset.
+    pub fn release_all(&mut self) {
+        self.changed.extend(self.mapping.keys().copied());
+        self.mapping.clear();
+        self.reverse_mapping.clear();
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -0,0 +1,166 @@
+//! Network statistics for diagnosing bandwidth and connection problems.
+//!
+//! Bytes are counted per channel and messages per type id, in both directions.
+//! Round-trip time and packet loss are sampled from the transport.
+//! Everything is summed up over [`STATS_INTERVAL_SECONDS`] and kept in [`NetworkStats`] on client and server.
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_renet::renet::{RenetClient, RenetServer};
+
+use crate::{messaging::Channel, ConnectionId, NetworkManager};
+
+/// Seconds traffic is summed up over
+pub const STATS_INTERVAL_SECONDS: f32 = 1.0;
+
+/// Traffic in one interval
+#[derive(Clone, Copy, Default, Debug)]
+pub struct TrafficStats {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub messages_sent: u64,
+    pub messages_received: u64,
+}
+
+impl TrafficStats {
+    fn sent(&mut self, bytes: usize) {
+        self.bytes_sent += bytes as u64;
+        self.messages_sent += 1;
+    }
+
+    fn received(&mut self, bytes: usize) {
+        self.bytes_received += bytes as u64;
+        self.messages_received += 1;
+    }
+}
+
+/// Connection quality as measured by the transport
+#[derive(Clone, Copy, Default, Debug)]
+pub struct ConnectionQuality {
+    /// Round-trip time in milliseconds
+    pub rtt: f32,
+    /// Share of packets lost, between 0 and 1
+    pub packet_loss: f32,
+}
+
+#[derive(Resource, Default)]
+pub struct NetworkStats {
+    current_channels: [TrafficStats; Channel::ALL.len()],
+    current_types: HashMap<u16, TrafficStats>,
+    channels: [TrafficStats; Channel::ALL.len()],
+    types: HashMap<u16, TrafficStats>,
+    /// Quality of client connections on the server
+    connections: HashMap<ConnectionId, ConnectionQuality>,
+    /// Quality of the connection to the server on a client
+    server: Option<ConnectionQuality>,
+    interval_start: f32,
+}
+
+impl NetworkStats {
+    pub(crate) fn record_sent(&mut self, channel: Channel, bytes: usize) {
+        self.current_channels[channel.id() as usize].sent(bytes);
+    }
+
+    pub(crate) fn record_received(&mut self, channel: Channel, bytes: usize) {
+        self.current_channels[channel.id() as usize].received(bytes);
+    }
+
+    pub(crate) fn record_message_sent(&mut self, type_id: u16, bytes: usize) {
+        self.current_types.entry(type_id).or_default().sent(bytes);
+    }
+
+    pub(crate) fn record_message_received(&mut self, type_id: u16, bytes: usize) {
+        self.current_types
+            .entry(type_id)
+            .or_default()
+            .received(bytes);
+    }
+
+    /// Traffic per channel name in the last interval
+    pub fn channels(&self) -> impl Iterator<Item = (&'static str, &TrafficStats)> {
+        Channel::ALL
+            .iter()
+            .map(|channel| (channel.name(), &self.channels[channel.id() as usize]))
+    }
+
+    /// Traffic per message type id in the last interval,
+    /// names can be looked up in [`MessageTypes`](crate::messaging::MessageTypes)
+    pub fn message_types(&self) -> impl Iterator<Item = (u16, &TrafficStats)> {
+        self.types.iter().map(|(id, stats)| (*id, stats))
+    }
+
+    pub fn connection(&self, connection: ConnectionId) -> Option<ConnectionQuality> {
+        self.connections.get(&connection).copied()
+    }
+
+    pub fn connections(&self) -> impl Iterator<Item = (ConnectionId, ConnectionQuality)> + '_ {
+        self.connections.iter().map(|(id, quality)| (*id, *quality))
+    }
+
+    /// Quality of the connection to the server, only set on clients
+    pub fn server(&self) -> Option<ConnectionQuality> {
+        self.server
+    }
+
+    /// Moves the current counts to the last interval once it's over
+    fn rotate(&mut self, now: f32) -> bool {
+        if self.interval_start + STATS_INTERVAL_SECONDS > now {
+            return false;
+        }
+        self.interval_start = now;
+        self.channels = std::mem::take(&mut self.current_channels);
+        self.types = std::mem::take(&mut self.current_types);
+        true
+    }
+}
+
+fn quality(info: bevy_renet::renet::NetworkInfo) -> ConnectionQuality {
+    ConnectionQuality {
+        rtt: (info.rtt * 1000.0) as f32,
+        packet_loss: info.packet_loss as f32,
+    }
+}
+
+fn rotate_stats_server(
+    mut stats: ResMut<NetworkStats>,
+    server: Option<Res<RenetServer>>,
+    time: Res<Time>,
+) {
+    if !stats.rotate(time.raw_elapsed_seconds()) {
+        return;
+    }
+    let Some(server) = server else {
+        return;
+    };
+    stats.connections = server
+        .clients_id()
+        .into_iter()
+        .filter_map(|id| {
+            let info = server.network_info(id).ok()?;
+            Some((ConnectionId(id), quality(info)))
+        })
+        .collect();
+}
+
+fn rotate_stats_client(
+    mut stats: ResMut<NetworkStats>,
+    client: Option<Res<RenetClient>>,
+    time: Res<Time>,
+) {
+    if !stats.rotate(time.raw_elapsed_seconds()) {
+        return;
+    }
+    stats.server = client.map(|client| quality(client.network_info()));
+}
+
+pub(crate) struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NetworkStats>();
+        if app.world.resource::<NetworkManager>().is_client() {
+            app.add_systems(Last, rotate_stats_client);
+        } else {
+            app.add_systems(Last, rotate_stats_server);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+//! Tells joining clients how many entities to expect, so they can show progress
+//! and hold off on expensive work until the initial flood of spawns is over.
+
+use bevy::{
+    prelude::*,
+    utils::{HashSet, Uuid},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    identity::NetworkIdentity,
+    messaging::{AppExt, MessageEvent, MessageReceivers, MessageSender},
+    spawning::NetworkedEntityEvent,
+    visibility::{GlobalGrid, InGrid, NetworkObserver, NetworkVisibilities, VisibilitySystem},
+    ClientEvent, NetworkManager, NetworkSet, Players, ServerEvent,
+};
+
+/// Seconds after which a sync counts as done, even if entities are missing.
+/// Entities can be despawned before they reach the client.
+const SYNC_TIMEOUT_SECONDS: f32 = 20.0;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct InitialSyncMessage {
+    /// How many entities the server is about to send
+    entities: u32,
+}
+
+/// Progress of receiving the entities around the player after joining
+/// or after the player starts observing from a new place.
+#[derive(Resource)]
+pub struct InitialSync {
+    expected: Option<u32>,
+    spawned: u32,
+    started: f32,
+    complete: bool,
+    /// If [`ClientEvent::Joined`] was sent for this connection
+    joined: bool,
+}
+
+impl InitialSync {
+    pub(crate) fn new(now: f32) -> Self {
+        Self {
+            expected: None,
+            spawned: 0,
+            started: now,
+            complete: false,
+            joined: false,
+        }
+    }
+
+    /// Fraction of the expected entities that arrived.
+    /// Returns `None` until the server said how many to expect.
+    pub fn progress(&self) -> Option<f32> {
+        if self.complete {
+            return Some(1.0);
+        }
+        let expected = self.expected?;
+        Some((self.spawned as f32 / expected.max(1) as f32).min(1.0))
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+}
+
+/// Run condition that is true while no sync is in progress
+pub fn is_synchronized(sync: Option<Res<InitialSync>>) -> bool {
+    sync.map_or(true, |sync| sync.complete)
+}
+
+/// Counts the entities an observer will receive once all cells in its range are observed
+fn server_report_initial_sync(
+    mut server_events: EventReader<ServerEvent>,
+    mut pending: Local<HashSet<Uuid>>,
+    observers: Query<(&NetworkObserver, &InGrid)>,
+    added_observers: Query<&NetworkObserver, Added<NetworkObserver>>,
+    identities: Query<&NetworkIdentity>,
+    players: Res<Players>,
+    grid: Res<GlobalGrid>,
+    visibilities: Res<NetworkVisibilities>,
+    mut sender: MessageSender,
+) {
+    let mut connected = HashSet::new();
+    for event in server_events.iter() {
+        if let ServerEvent::PlayerConnected(connection) = event {
+            if let Some(player) = players.get(*connection) {
+                pending.insert(player.id);
+                connected.insert(player.id);
+            }
+        }
+    }
+    pending.extend(added_observers.iter().map(|observer| observer.player_id));
+
+    pending.retain(|player_id| {
+        let Some(connection) = players.get_connection(player_id) else {
+            return false;
+        };
+        let observer = observers
+            .iter()
+            .find(|(observer, _)| observer.player_id == *player_id);
+        let entities = match observer {
+            // Wait until the observer is placed in the grid
+            Some((_, in_grid)) if in_grid.position().is_none() => return true,
+            Some((observer, in_grid)) => grid
+                .entities_in_range(in_grid.position().unwrap(), observer.range)
+                .into_iter()
+                .filter_map(|entity| identities.get(entity).ok())
+                .filter(|identity| {
+                    visibilities
+                        .visibility
+                        .get(identity)
+                        .map_or(true, |v| !v.existing_observers().any(|c| *c == connection))
+                })
+                .count() as u32,
+            // Players in the lobby don't observe anything yet
+            None if connected.contains(player_id) => 0,
+            None => return false,
+        };
+
+        sender.send(
+            &InitialSyncMessage { entities },
+            MessageReceivers::Single(connection),
+        );
+        false
+    });
+}
+
+fn client_track_sync(
+    mut messages: EventReader<MessageEvent<InitialSyncMessage>>,
+    mut entity_events: EventReader<NetworkedEntityEvent>,
+    sync: Option<ResMut<InitialSync>>,
+    mut client_events: EventWriter<ClientEvent>,
+    time: Res<Time>,
+) {
+    let Some(mut sync) = sync else {
+        entity_events.clear();
+        return;
+    };
+    let now = time.elapsed_seconds();
+
+    for event in messages.iter() {
+        // Spawns before the first report belong to it, later reports start a new sync
+        if sync.expected.is_some() {
+            sync.spawned = 0;
+            sync.started = now;
+            sync.complete = false;
+        }
+        sync.expected = Some(event.message.entities);
+    }
+
+    let spawned = entity_events
+        .iter()
+        .filter(|event| matches!(event, NetworkedEntityEvent::Spawned(_)))
+        .count();
+    sync.spawned += spawned as u32;
+
+    if sync.complete {
+        return;
+    }
+    let received_all = sync.expected.map_or(false, |e| sync.spawned >= e);
+    if !received_all && now - sync.started < SYNC_TIMEOUT_SECONDS {
+        return;
+    }
+
+    sync.complete = true;
+    if !received_all {
+        warn!(
+            spawned = sync.spawned,
+            expected = ?sync.expected,
+            "Initial sync timed out"
+        );
+    }
+    if !sync.joined {
+        sync.joined = true;
+        client_events.send(ClientEvent::Joined);
+    }
+}
+
+pub(crate) struct SyncPlugin;
+
+impl Plugin for SyncPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_network_message::<InitialSyncMessage>();
+
+        if app.world.resource::<NetworkManager>().is_server() {
+            app.add_systems(
+                PreUpdate,
+                server_report_initial_sync
+                    .after(VisibilitySystem::GridVisibility)
+                    .in_set(NetworkSet::ServerVisibility),
+            );
+        } else {
+            app.add_systems(Update, client_track_sync);
+        }
+    }
+}
@@ -0,0 +1,336 @@
+//! In-process servers and clients for integration tests.
+//! Clients are connected directly, without a transport.
+//!
+//! [`TestServer`] runs only the messaging layer, for testing how the server handles what a client sends.
+//! [`TestHarness`] runs the full networking plugin on a server and any number of clients,
+//! stepping them together so tests can assert on the replicated world.
+
+use std::time::Duration;
+
+use bevy::{
+    app::ScheduleRunnerPlugin,
+    ecs::{event::Events, system::Command},
+    prelude::*,
+    time::TimeUpdateStrategy,
+    utils::Uuid,
+};
+use bevy_renet::renet::{RenetClient, RenetServer};
+use bytes::Bytes;
+
+use crate::{
+    bandwidth::BandwidthUsage,
+    connection_config,
+    guard::GuardConfig,
+    identity::{NetworkCommand, NetworkIdentities, NetworkIdentity},
+    messaging::{
+        Channel, MessageEvent, MessageTypes, MessageViolations, MessagingPlugin, NetworkMessage,
+    },
+    stats::NetworkStats,
+    visibility::{InGrid, NetworkObserver, NetworkObserverBundle, NetworkObserverCells},
+    ClientHello, ClientState, ConnectionId, InProcessIdentities, NetworkManager, NetworkRole,
+    NetworkingPlugin, Players, UserIdentity, VERSION,
+};
+
+const CLIENT_ID: u64 = 1;
+
+pub struct TestServer {
+    pub app: App,
+    client: RenetClient,
+}
+
+impl Default for TestServer {
+    fn default() -> Self {
+        Self::with_guard(GuardConfig::default())
+    }
+}
+
+impl TestServer {
+    /// A server that treats its client according to the given config
+    pub fn with_guard(guard: GuardConfig) -> Self {
+        let mut server = RenetServer::new(connection_config());
+        server.add_connection(CLIENT_ID);
+
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(NetworkManager {
+                role: NetworkRole::Server,
+            })
+            .insert_resource(server)
+            .insert_resource(guard)
+            .init_resource::<Players>()
+            .init_resource::<BandwidthUsage>()
+            .init_resource::<NetworkStats>()
+            .add_plugins(MessagingPlugin);
+
+        Self {
+            app,
+            client: RenetClient::new(connection_config()),
+        }
+    }
+
+    pub fn connection(&self) -> ConnectionId {
+        ConnectionId(CLIENT_ID)
+    }
+
+    pub fn message_id<T: 'static>(&self) -> u16 {
+        self.app
+            .world
+            .resource::<MessageTypes>()
+            .id::<T>()
+            .expect("message type is not registered")
+    }
+
+    /// Sends arbitrary content as a message with the given id
+    pub fn send_content(&mut self, type_id: u16, content: impl Into<Bytes>) {
+        // Serializes the same as the message envelope
+        let envelope = bincode::serialize(&(type_id, content.into())).unwrap();
+        self.send_raw(envelope);
+    }
+
+    /// Sends bytes without wrapping them in a message envelope
+    pub fn send_raw(&mut self, bytes: Vec<u8>) {
+        self.client.send_message(Channel::Default.id(), bytes);
+    }
+
+    /// Delivers what the client sent and runs the server for a frame
+    pub fn update(&mut self) {
+        let packets = self.client.get_packets_to_send();
+        let mut server = self.app.world.resource_mut::<RenetServer>();
+        for packet in packets {
+            let _ = server.process_packet_from(&packet, CLIENT_ID);
+        }
+        self.app.update();
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.app
+            .world
+            .resource::<RenetServer>()
+            .is_connected(CLIENT_ID)
+    }
+
+    pub fn violation_score(&self) -> f32 {
+        self.app
+            .world
+            .resource::<MessageViolations>()
+            .score(self.connection())
+    }
+
+    /// Takes the messages of a type the server received so far
+    pub fn received<T: Send + Sync + 'static>(&mut self) -> Vec<T> {
+        self.app
+            .world
+            .resource_mut::<Events<MessageEvent<T>>>()
+            .drain()
+            .map(|event| event.message)
+            .collect()
+    }
+}
+
+/// Time every app advances by in one step, the default server tick
+pub const STEP: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+pub struct TestClient {
+    pub app: App,
+    pub identity: UserIdentity,
+    client_id: u64,
+}
+
+impl TestClient {
+    pub fn connection(&self) -> ConnectionId {
+        ConnectionId(self.client_id)
+    }
+
+    /// Whether the server accepted the client
+    pub fn is_connected(&self) -> bool {
+        *self.app.world.resource::<State<ClientState>>().get() == ClientState::Connected
+    }
+
+    /// The client's copy of a networked entity
+    pub fn entity(&self, identity: NetworkIdentity) -> Option<Entity> {
+        self.app
+            .world
+            .resource::<NetworkIdentities>()
+            .get_entity(identity)
+    }
+}
+
+/// A server and clients in one process, exchanging packets in memory
+pub struct TestHarness {
+    pub server: App,
+    pub clients: Vec<TestClient>,
+    /// Run on every app, so messages and components are registered in the same order
+    setup: Box<dyn Fn(&mut App)>,
+}
+
+impl TestHarness {
+    /// Starts a server and connects clients to it. `setup` is run on the server and every client.
+    pub fn new(clients: usize, setup: impl Fn(&mut App) + 'static) -> Self {
+        let mut server = headless_app(NetworkRole::Server);
+        setup(&mut server);
+        server.insert_resource(RenetServer::new(connection_config()));
+
+        let mut harness = Self {
+            server,
+            clients: Vec::new(),
+            setup: Box::new(setup),
+        };
+        for _ in 0..clients {
+            harness.connect();
+        }
+        harness
+    }
+
+    /// Connects another client, which joins with the next step. Returns its index.
+    pub fn connect(&mut self) -> usize {
+        let index = self.clients.len();
+        let client_id = index as u64 + 1;
+        let identity = UserIdentity {
+            id: Uuid::from_u128(client_id as u128),
+            username: format!("Client{}", client_id),
+        };
+        self.server
+            .world
+            .resource_mut::<RenetServer>()
+            .add_connection(client_id);
+        self.server
+            .world
+            .resource_mut::<InProcessIdentities>()
+            .0
+            .insert(client_id, identity.clone());
+
+        let mut app = headless_app(NetworkRole::Client);
+        (self.setup)(&mut app);
+        app.insert_resource(RenetClient::new(connection_config()));
+
+        // Sent by the client once its transport connects
+        let hello = ClientHello {
+            version: VERSION.into(),
+            spectate: false,
+            resume: None,
+        };
+        let message = NetworkMessage {
+            type_id: app
+                .world
+                .resource::<MessageTypes>()
+                .id::<ClientHello>()
+                .unwrap(),
+            content: bincode::serialize(&hello).unwrap().into(),
+        };
+        app.world
+            .resource_mut::<RenetClient>()
+            .send_message(Channel::Default.id(), bincode::serialize(&message).unwrap());
+
+        self.clients.push(TestClient {
+            app,
+            identity,
+            client_id,
+        });
+        index
+    }
+
+    /// Runs the server for a frame, delivers its packets, then runs every client and delivers theirs
+    pub fn step(&mut self) {
+        self.server.update();
+        for client in self.clients.iter_mut() {
+            let packets = self
+                .server
+                .world
+                .resource_mut::<RenetServer>()
+                .get_packets_to_send(client.client_id)
+                .unwrap_or_default();
+            // Rejected clients lose their renet client
+            if let Some(mut renet) = client.app.world.get_resource_mut::<RenetClient>() {
+                for packet in packets {
+                    renet.process_packet(&packet);
+                }
+            }
+
+            client.app.update();
+
+            let packets = client
+                .app
+                .world
+                .get_resource_mut::<RenetClient>()
+                .map(|mut renet| renet.get_packets_to_send())
+                .unwrap_or_default();
+            let mut server = self.server.world.resource_mut::<RenetServer>();
+            for packet in packets {
+                let _ = server.process_packet_from(&packet, client.client_id);
+            }
+        }
+    }
+
+    /// Steps until the condition holds, returns false if it didn't within the maximum steps
+    pub fn step_until(
+        &mut self,
+        max_steps: usize,
+        mut condition: impl FnMut(&Self) -> bool,
+    ) -> bool {
+        for _ in 0..max_steps {
+            if condition(self) {
+                return true;
+            }
+            self.step();
+        }
+        condition(self)
+    }
+
+    /// Steps until every client is connected
+    pub fn join_all(&mut self, max_steps: usize) -> bool {
+        self.step_until(max_steps, |harness| {
+            harness.clients.iter().all(TestClient::is_connected)
+        })
+    }
+
+    /// Spawns an observer for a client on the server, so it sees the entities around it
+    pub fn spawn_observer(&mut self, client: usize, position: Vec3, range: u32) -> Entity {
+        let player_id = self.clients[client].identity.id;
+        self.server
+            .world
+            .spawn((
+                NetworkObserverBundle {
+                    observer: NetworkObserver { range, player_id },
+                    cells: NetworkObserverCells::default(),
+                },
+                TransformBundle::from_transform(Transform::from_translation(position)),
+                InGrid::default(),
+            ))
+            .id()
+    }
+
+    /// Spawns a networked entity on the server
+    pub fn spawn_networked(&mut self, bundle: impl Bundle) -> Entity {
+        let entity = self.server.world.spawn(bundle).id();
+        NetworkCommand { entity }.apply(&mut self.server.world);
+        entity
+    }
+
+    /// The network identity of a server entity
+    pub fn identity(&self, entity: Entity) -> Option<NetworkIdentity> {
+        self.server
+            .world
+            .resource::<NetworkIdentities>()
+            .get_identity(entity)
+    }
+
+    /// A client's copy of a server entity
+    pub fn client_entity(&self, client: usize, entity: Entity) -> Option<Entity> {
+        self.clients[client].entity(self.identity(entity)?)
+    }
+}
+
+fn headless_app(role: NetworkRole) -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        // The server reads its tick rate from the runner
+        MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(STEP)),
+        AssetPlugin::default(),
+        bevy::scene::ScenePlugin,
+        bevy::transform::TransformPlugin,
+        bevy::hierarchy::HierarchyPlugin,
+    ))
+    .insert_resource(TimeUpdateStrategy::ManualDuration(STEP))
+    .add_plugins(NetworkingPlugin { role });
+    app
+}
@@ -1,10 +1,23 @@
-use std::collections::VecDeque;
-
-use bevy::{app::ScheduleRunnerPlugin, prelude::*, utils::HashMap};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    app::{AppExit, RunFixedUpdateLoop, ScheduleRunnerPlugin},
+    ecs::{event::ManualEventReader, schedule::ScheduleLabel},
+    prelude::*,
+    utils::HashMap,
+};
 use bevy_renet::renet::{RenetClient, RenetServer};
 use serde::{Deserialize, Serialize};
 
-use crate::{messaging::Channel, ConnectionId, NetworkManager, NetworkSet, Players};
+use crate::{
+    bandwidth::{BandwidthUsage, TIMING_FEATURE},
+    messaging::Channel,
+    stats::NetworkStats,
+    ConnectionId, NetworkManager, NetworkSet, Players,
+};
 
 /// Timing data of the server.
 #[derive(Resource)]
@@ -25,6 +38,67 @@ impl ServerNetworkTime {
     }
 }
 
+/// Schedule for simulation systems that should advance once per network tick instead of every frame.
+/// Runs after [`PreUpdate`], once per update on the server. Clients don't simulate,
+/// so it never runs there. Systems in it take their time step from [`TickTime`].
+#[derive(ScheduleLabel, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NetworkTick;
+
+/// The tick the [`NetworkTick`] schedule is running for
+#[derive(Resource, Default)]
+pub struct TickTime {
+    tick: u32,
+    delta_seconds: f32,
+}
+
+impl TickTime {
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    /// How many seconds the tick lasts
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta_seconds
+    }
+}
+
+/// Limits for adapting the server tick rate under load.
+#[derive(Resource, Clone, Copy)]
+pub struct TickRateBounds {
+    /// The lowest tick rate the server may drop to
+    pub min_tps: f64,
+    /// The tick rate used when the server isn't overloaded
+    pub max_tps: f64,
+}
+
+/// How long recent updates took relative to the tick duration
+#[derive(Resource, Default)]
+struct TickLoad {
+    /// Update duration divided by tick duration
+    frames: VecDeque<f32>,
+    last_change: f32,
+}
+
+/// How many updates are considered when deciding to change the tick rate
+const TICK_LOAD_WINDOW: usize = 120;
+/// Minimum seconds between tick rate changes
+const TICK_RATE_COOLDOWN: f32 = 5.0;
+/// Fraction of updates that must exceed their budget to lower the tick rate
+const OVERLOAD_THRESHOLD: f32 = 0.5;
+/// Updates must use less than this fraction of the faster tick to raise the tick rate
+const UNDERLOAD_THRESHOLD: f32 = 0.6;
+const TICK_RATE_STEP: f64 = 1.25;
+
+impl TickLoad {
+    fn record(&mut self, elapsed: Duration, tick: Duration) {
+        if self.frames.len() >= TICK_LOAD_WINDOW {
+            self.frames.pop_front();
+        }
+        self.frames
+            .push_back(elapsed.as_secs_f32() / tick.as_secs_f32());
+    }
+}
+
 #[derive(Default)]
 struct ServerClientTime {
     last_rtt: Option<u32>,
@@ -87,6 +161,11 @@ impl ClientNetworkTime {
         self.interpolated_tick
     }
 
+    /// The latest tick received from the server
+    pub(crate) fn server_tick(&self) -> Option<u32> {
+        self.server_tick.as_ref().map(|received| received.tick)
+    }
+
     fn push_rtt(&mut self, rtt: u32) {
         if self.rtts.len() >= RTT_AVERAGE_COUNT {
             self.rtts.pop_front();
@@ -144,6 +223,8 @@ pub(crate) struct ServerTick {
     tick: u32,
     /// The round-trip-time of the last server to client to server packet
     rtt: Option<u32>,
+    /// How long a tick lasts, sent every time as the timing channel is unreliable
+    tick_duration_seconds: f32,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -161,6 +242,8 @@ fn update_server_tick(mut network_time: ResMut<ServerNetworkTime>) {
 
 fn send_server_tick(
     mut server: ResMut<RenetServer>,
+    mut bandwidth: ResMut<BandwidthUsage>,
+    mut stats: ResMut<NetworkStats>,
     mut client_times: ResMut<ClientTimes>,
     time: Res<Time>,
     network_time: Res<ServerNetworkTime>,
@@ -178,12 +261,15 @@ fn send_server_tick(
         }
 
         let rtt = timing.and_then(|t| t.last_rtt);
-        let message = TimeMessage::ServerTick(ServerTick { tick, rtt });
-        server.send_message(
-            connection.0,
-            Channel::Timing.id(),
-            bincode::serialize(&message).unwrap(),
-        );
+        let message = bincode::serialize(&TimeMessage::ServerTick(ServerTick {
+            tick,
+            rtt,
+            tick_duration_seconds: network_time.server_tick_seconds as f32,
+        }))
+        .unwrap();
+        bandwidth.record(TIMING_FEATURE, message.len(), 1);
+        stats.record_sent(Channel::Timing, message.len());
+        server.send_message(connection.0, Channel::Timing.id(), message);
         // TODO: Can we send this message immediately?
 
         client_times
@@ -197,9 +283,11 @@ fn send_server_tick(
 fn receive_server_tick(
     mut client: ResMut<RenetClient>,
     mut network_time: ResMut<ClientNetworkTime>,
+    mut stats: ResMut<NetworkStats>,
     time: Res<Time>,
 ) {
     while let Some(message) = client.receive_message(Channel::Timing.id()) {
+        stats.record_received(Channel::Timing, message.len());
         let message = match bincode::deserialize(&message) {
             Ok(m) => m,
             Err(_) => {
@@ -216,14 +304,35 @@ fn receive_server_tick(
                 }
             }
 
+            let tick_duration_seconds = tick.tick_duration_seconds;
+            if tick_duration_seconds.is_nan() || tick_duration_seconds <= 0.0 {
+                warn!("Invalid tick duration from server");
+                continue;
+            }
+            // Before joining `ServerInfo` sets the initial tick duration
+            if let Some(old) = network_time
+                .server_tick_seconds
+                .filter(|old| *old != tick_duration_seconds)
+            {
+                // Round-trip-times are measured in ticks, so they need to be rescaled
+                let scale = old / tick_duration_seconds;
+                for rtt in network_time.rtts.iter_mut() {
+                    *rtt = (*rtt as f32 * scale).round() as u32;
+                }
+                network_time.server_tick_seconds = Some(tick_duration_seconds);
+                if !network_time.rtts.is_empty() {
+                    network_time.target_tick_offset = network_time.calculate_tick_offset();
+                }
+                info!(tick = tick_duration_seconds, "Server changed tick rate");
+            }
+
             // Send response as fast as possible
-            client.send_message(
-                Channel::Timing.id(),
-                bincode::serialize(&TimeMessage::ClientResponse {
-                    server_tick: tick.tick,
-                })
-                .unwrap(),
-            );
+            let response = bincode::serialize(&TimeMessage::ClientResponse {
+                server_tick: tick.tick,
+            })
+            .unwrap();
+            stats.record_sent(Channel::Timing, response.len());
+            client.send_message(Channel::Timing.id(), response);
             // TODO: Can we send this message immediately?
 
             let received_tick = ReceivedServerTick {
@@ -243,9 +352,11 @@ fn server_handle_response(
     mut server: ResMut<RenetServer>,
     network_time: Res<ServerNetworkTime>,
     mut client_times: ResMut<ClientTimes>,
+    mut stats: ResMut<NetworkStats>,
 ) {
     'clients: for client_id in server.clients_id().into_iter() {
         while let Some(message) = server.receive_message(client_id, Channel::Timing.id()) {
+            stats.record_received(Channel::Timing, message.len());
             let message: TimeMessage = match bincode::deserialize(&message) {
                 Ok(m) => m,
                 Err(_) => {
@@ -264,6 +375,84 @@ fn server_handle_response(
     }
 }
 
+/// Lowers the tick rate if the server can't keep up, and raises it again once it can.
+fn adapt_tick_rate(
+    mut load: ResMut<TickLoad>,
+    bounds: Res<TickRateBounds>,
+    mut network_time: ResMut<ServerNetworkTime>,
+    time: Res<Time>,
+) {
+    let now = time.raw_elapsed_seconds();
+    if load.frames.len() < TICK_LOAD_WINDOW || load.last_change + TICK_RATE_COOLDOWN > now {
+        return;
+    }
+
+    let current_tps = 1.0 / network_time.server_tick_seconds;
+    let overloaded = load.frames.iter().filter(|&&f| f > 1.0).count() as f32
+        / load.frames.len() as f32
+        >= OVERLOAD_THRESHOLD;
+    let new_tps = if overloaded {
+        (current_tps / TICK_RATE_STEP).max(bounds.min_tps)
+    } else {
+        let faster = (current_tps * TICK_RATE_STEP).min(bounds.max_tps);
+        // Check if updates would still fit into the shorter tick
+        let ratio = (faster / current_tps) as f32;
+        let peak = load.frames.iter().copied().fold(0.0, f32::max);
+        if peak * ratio < UNDERLOAD_THRESHOLD {
+            faster
+        } else {
+            current_tps
+        }
+    };
+
+    if (new_tps - current_tps).abs() < f64::EPSILON {
+        return;
+    }
+
+    info!(
+        from = current_tps,
+        to = new_tps,
+        "Changing server tick rate"
+    );
+    network_time.server_tick_seconds = 1.0 / new_tps;
+    // Clients pick up the new duration from the next `ServerTick`
+    load.frames.clear();
+    load.last_change = now;
+}
+
+/// Runs the server loop, waiting for the current tick duration between updates.
+/// Unlike the [`ScheduleRunnerPlugin`] loop, the tick duration can change while running.
+fn server_runner(mut app: App) {
+    while !app.ready() {
+        bevy::tasks::tick_global_task_pools_on_main_thread();
+    }
+    app.finish();
+    app.cleanup();
+
+    let mut exit_reader = ManualEventReader::<AppExit>::default();
+    loop {
+        let start = Instant::now();
+        app.update();
+
+        if let Some(events) = app.world.get_resource::<Events<AppExit>>() {
+            if exit_reader.iter(events).last().is_some() {
+                break;
+            }
+        }
+
+        let elapsed = start.elapsed();
+        let tick = Duration::from_secs_f64(
+            app.world
+                .resource::<ServerNetworkTime>()
+                .server_tick_seconds,
+        );
+        app.world.resource_mut::<TickLoad>().record(elapsed, tick);
+        if let Some(remaining) = tick.checked_sub(elapsed) {
+            std::thread::sleep(remaining);
+        }
+    }
+}
+
 fn update_interpolated_tick(mut network_time: ResMut<ClientNetworkTime>, time: Res<Time>) {
     let server_tick = match network_time.estimated_server_tick(time.raw_elapsed_seconds()) {
         Some(t) => t,
@@ -291,6 +480,17 @@ fn update_interpolated_tick(mut network_time: ResMut<ClientNetworkTime>, time: R
     network_time.tick_speed = speed;
 }
 
+fn run_server_network_tick(world: &mut World) {
+    let network_time = world.resource::<ServerNetworkTime>();
+    let time = TickTime {
+        tick: network_time.server_tick,
+        delta_seconds: network_time.server_tick_seconds as f32,
+    };
+    world.insert_resource(time);
+    // Fails if no systems were ever added to the schedule
+    let _ = world.try_run_schedule(NetworkTick);
+}
+
 pub(crate) struct TimePlugin;
 
 impl Plugin for TimePlugin {
@@ -309,14 +509,31 @@ impl Plugin for TimePlugin {
             } else {
                 panic!("Server must run with a fixed tick rate")
             };
+            let tick_seconds = tick.unwrap().as_secs_f64();
+            if !app.world.contains_resource::<TickRateBounds>() {
+                app.insert_resource(TickRateBounds {
+                    min_tps: 0.5 / tick_seconds,
+                    max_tps: 1.0 / tick_seconds,
+                });
+            }
+            // Replace the fixed rate loop so the tick rate can adapt to load
+            app.set_runner(server_runner);
             app.insert_resource(ServerNetworkTime {
                 server_tick: 0,
-                server_tick_seconds: tick.unwrap().as_secs_f64(),
+                server_tick_seconds: tick_seconds,
             })
             .init_resource::<ClientTimes>()
+            .init_resource::<TickLoad>()
+            .init_resource::<TickTime>()
+            .add_systems(RunFixedUpdateLoop, run_server_network_tick)
             .add_systems(
                 PreUpdate,
-                (update_server_tick, send_server_tick, server_handle_response)
+                (
+                    adapt_tick_rate,
+                    update_server_tick,
+                    send_server_tick,
+                    server_handle_response,
+                )
                     .chain()
                     .in_set(NetworkSet::UpdateTick),
             );
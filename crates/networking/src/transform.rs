@@ -15,9 +15,11 @@ use physics::{ColliderGroup, SetPhysicsCommand};
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    bandwidth::{BandwidthUsage, TRANSFORMS_FEATURE},
     identity::{NetworkIdentities, NetworkIdentity},
     messaging::{deserialize, serialize_once, Channel},
     spawning::ClientControlled,
+    stats::NetworkStats,
     time::{ClientNetworkTime, ServerNetworkTime},
     visibility::NetworkVisibilities,
     ConnectionId, NetworkManager, NetworkSet,
@@ -122,7 +124,7 @@ impl TransformSnapshot {
         // Calculate at which time point we are between the updates
         let mut t = SequenceNumber::between(from.sequence_number, to.sequence_number, tick);
 
-        // Do not interpolate if the parent changed
+        // Do not interpolate if the parent changed and the snapshots are in different spaces
         if let Some(new_parent) = to.parent {
             if from.parent != Some(new_parent) {
                 t = if t > 0.5 { 1.0 } else { 0.0 };
@@ -164,6 +166,56 @@ impl TransformSnapshot {
             physics,
         }
     }
+
+    /// Moves the snapshot into the space of another parent, using where the parents are now.
+    /// Returns `None` if either parent's transform isn't known.
+    fn in_parent_space(
+        &self,
+        parent: Option<NetworkIdentity>,
+        parent_transform: &impl Fn(Option<NetworkIdentity>) -> Option<GlobalTransform>,
+    ) -> Option<Self> {
+        if self.parent == parent {
+            return Some(*self);
+        }
+        let global = parent_transform(self.parent)?.mul_transform(Transform {
+            translation: self.position,
+            rotation: self.rotation,
+            ..default()
+        });
+        let local = global.reparented_to(&parent_transform(parent)?);
+        Some(Self {
+            position: local.translation,
+            rotation: local.rotation,
+            parent,
+            ..*self
+        })
+    }
+}
+
+/// Interpolates between snapshots, converting the previous one into the parent space of the next one.
+/// This keeps items from snapping when they're picked up or put down somewhere moving.
+fn interpolate_snapshots(
+    previous: Option<&TransformSnapshot>,
+    next: &TransformSnapshot,
+    tick: f32,
+    identities: &NetworkIdentities,
+    global_transforms: &Query<&GlobalTransform>,
+) -> TransformSnapshot {
+    let Some(previous) = previous else {
+        return *next;
+    };
+    // No parent means world space
+    let parent_transform = |parent: Option<NetworkIdentity>| match parent {
+        Some(identity) => identities
+            .get_entity(identity)
+            .and_then(|entity| global_transforms.get(entity).ok())
+            .copied(),
+        None => Some(GlobalTransform::IDENTITY),
+    };
+    match previous.in_parent_space(next.parent, &parent_transform) {
+        Some(previous) => TransformSnapshot::interpolate(&previous, next, tick),
+        None => TransformSnapshot::interpolate(previous, next, tick),
+    }
 }
 
 /// Interpolates two values
@@ -368,6 +420,7 @@ impl NetworkTransform {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn update_transform(
     mut query: Query<(
         Entity,
@@ -385,6 +438,8 @@ fn update_transform(
     time: Res<Time>,
     visibilities: Res<NetworkVisibilities>,
     mut server: ResMut<RenetServer>,
+    mut bandwidth: ResMut<BandwidthUsage>,
+    mut stats: ResMut<NetworkStats>,
     network_time: Res<ServerNetworkTime>,
     mut commands: Commands,
 ) {
@@ -480,7 +535,9 @@ fn update_transform(
                 data,
             });
             let serialized = serialize_once(&message);
-            server.send_message(connection.0, Channel::Transforms.id(), serialized.clone());
+            bandwidth.record(TRANSFORMS_FEATURE, serialized.len(), 1);
+            stats.record_sent(Channel::Transforms, serialized.len());
+            server.send_message(connection.0, Channel::Transforms.id(), serialized);
         }
     }
 }
@@ -489,12 +546,14 @@ fn update_transform(
 fn handle_acks(
     mut query: Query<&mut NetworkTransform>,
     mut server: ResMut<RenetServer>,
+    mut stats: ResMut<NetworkStats>,
     identities: Res<NetworkIdentities>,
     time: Res<Time>,
 ) {
     let seconds = time.raw_elapsed_seconds();
     'clients: for client_id in server.clients_id().into_iter() {
         while let Some(message) = server.receive_message(client_id, Channel::Transforms.id()) {
+            stats.record_received(Channel::Transforms, message.len());
             let message: TransformMessage = match deserialize(&message) {
                 Ok(m) => m,
                 Err(_) => {
@@ -570,6 +629,13 @@ pub struct NetworkedTransform {
 }
 
 impl NetworkedTransform {
+    /// Server tick of the newest snapshot received
+    pub fn last_snapshot_tick(&self) -> Option<u32> {
+        self.snapshots
+            .back()
+            .map(|snapshot| snapshot.sequence_number.0)
+    }
+
     fn add_snapshot(&mut self, snapshot: TransformSnapshot) {
         if self.snapshots.len() >= CLIENT_SNAPSHOT_BUFFER_SIZE {
             self.snapshots.pop_front();
@@ -659,9 +725,11 @@ pub struct ClientMovementClient;
 fn handle_transform_messages(
     mut client: ResMut<RenetClient>,
     mut buffer: ResMut<BufferedTransformUpdates>,
+    mut stats: ResMut<NetworkStats>,
     mut acknowledgments: Local<Vec<Acknowledgment>>,
 ) {
     while let Some(message) = client.receive_message(Channel::Transforms.id()) {
+        stats.record_received(Channel::Transforms, message.len());
         let message: TransformMessage = match deserialize(&message) {
             Ok(m) => m,
             Err(_) => {
@@ -682,10 +750,9 @@ fn handle_transform_messages(
     }
 
     for ack in acknowledgments.drain(..) {
-        client.send_message(
-            Channel::Transforms.id(),
-            serialize_once(&TransformMessage::Ack(ack)),
-        );
+        let serialized = serialize_once(&TransformMessage::Ack(ack));
+        stats.record_sent(Channel::Transforms, serialized.len());
+        client.send_message(Channel::Transforms.id(), serialized);
     }
 }
 
@@ -775,6 +842,8 @@ fn sync_networked_transform(
         (&mut NetworkedTransform, &mut Transform),
         (Without<RigidBody>, Without<ClientControlled>),
     >,
+    global_transforms: Query<&GlobalTransform>,
+    identities: Res<NetworkIdentities>,
     network_time: Res<ClientNetworkTime>,
 ) {
     let current_tick = network_time.interpolated_tick();
@@ -785,12 +854,13 @@ fn sync_networked_transform(
         };
 
         // Interpolate between snapshots if present
-        let snapshot = match previous_snapshot {
-            Some(previous_snapshot) => {
-                TransformSnapshot::interpolate(previous_snapshot, next_snapshot, current_tick)
-            }
-            None => *next_snapshot,
-        };
+        let snapshot = interpolate_snapshots(
+            previous_snapshot,
+            next_snapshot,
+            current_tick,
+            &identities,
+            &global_transforms,
+        );
 
         transform.translation = snapshot.position;
         transform.rotation = snapshot.rotation;
@@ -809,6 +879,7 @@ fn sync_networked_transform_physics(
         Option<Ref<ClientMovementClient>>,
         Has<ClientControlled>,
     )>,
+    global_transforms: Query<&GlobalTransform>,
     identities: Res<NetworkIdentities>,
     network_time: Res<ClientNetworkTime>,
     mut commands: Commands,
@@ -835,12 +906,13 @@ fn sync_networked_transform_physics(
             };
 
         // Interpolate between snapshots if present
-        let snapshot = match previous_snapshot {
-            Some(previous_snapshot) => {
-                TransformSnapshot::interpolate(previous_snapshot, next_snapshot, current_tick)
-            }
-            None => *next_snapshot,
-        };
+        let snapshot = interpolate_snapshots(
+            previous_snapshot,
+            next_snapshot,
+            current_tick,
+            &identities,
+            &global_transforms,
+        );
 
         let ignore_position =
             controlled && client_movement.map(|m| !m.is_added()).unwrap_or_default();
@@ -968,3 +1040,80 @@ impl Plugin for TransformPlugin {
         }
     }
 }
+
+/// Entry points for the criterion benchmarks
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod bench {
+    use bevy::math::{Quat, Vec3};
+    use bytes::Bytes;
+
+    use super::{
+        PhysicsSnapshot, SequenceNumber, Thresholds, TransformMessage, TransformSnapshot,
+        TransformUpdate, TransformUpdateData,
+    };
+    use crate::{
+        identity::NetworkIdentity,
+        messaging::{deserialize, serialize_once},
+    };
+
+    fn snapshot(tick: usize, (position, rotation): (Vec3, Quat)) -> TransformSnapshot {
+        TransformSnapshot {
+            sequence_number: SequenceNumber::from_tick(tick as u32),
+            position,
+            rotation,
+            parent: None,
+            disabled: false,
+            physics: Some(PhysicsSnapshot::default()),
+        }
+    }
+
+    /// Diffs each step of a path against the last sent one, like the server does for a moving entity.
+    /// Returns the number of updates that would be sent.
+    pub fn diff_path(path: &[(Vec3, Quat)]) -> usize {
+        encode(path, |_| ()).len()
+    }
+
+    /// Diffs and serializes each step of a path
+    pub fn serialize_path(path: &[(Vec3, Quat)]) -> Vec<Bytes> {
+        encode(path, |message| serialize_once(&message))
+    }
+
+    /// Deserializes updates and applies them to a client snapshot, returning the final position
+    pub fn apply_updates(updates: &[Bytes]) -> Vec3 {
+        let mut current: Option<TransformSnapshot> = None;
+        for data in updates {
+            let Ok(TransformMessage::Update(update)) = deserialize::<TransformMessage>(data) else {
+                continue;
+            };
+            match &mut current {
+                Some(snapshot) => snapshot.apply(update.data),
+                None => current = TransformSnapshot::from_full(update.data),
+            }
+        }
+        current.map_or(Vec3::ZERO, |s| s.position)
+    }
+
+    fn encode<T>(path: &[(Vec3, Quat)], mut output: impl FnMut(TransformMessage) -> T) -> Vec<T> {
+        let identity = NetworkIdentity::from_raw(0);
+        let thresholds = Thresholds::default();
+        let mut results = Vec::new();
+        let mut base: Option<TransformSnapshot> = None;
+        for (tick, step) in path.iter().copied().enumerate() {
+            let new = snapshot(tick, step);
+            let data = match base {
+                Some(base) => TransformUpdateData::diff(base, new, thresholds),
+                None => Some(TransformUpdateData::full(new)),
+            };
+            let Some(data) = data else {
+                continue;
+            };
+            base = Some(new);
+            results.push(output(TransformMessage::Update(TransformUpdate {
+                identity,
+                data,
+            })));
+        }
+        results
+    }
+}
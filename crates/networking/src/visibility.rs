@@ -8,8 +8,10 @@ use bevy::{
 use smallvec::SmallVec;
 
 use crate::{
-    identity::NetworkIdentity, spawning::ClientControls, ConnectionId, NetworkManager, NetworkSet,
-    Players,
+    identity::{NetworkIdentities, NetworkIdentity},
+    snapshot::JoinSnapshots,
+    spawning::ClientControls,
+    ConnectionId, NetworkManager, NetworkSet, Players,
 };
 
 /// Allows players to observe networked objects in range
@@ -149,6 +151,17 @@ impl NetworkVisibilities {
     pub fn get_mut(&mut self, identity: NetworkIdentity) -> Option<&mut NetworkVisibility> {
         self.visibility.get_mut(&identity)
     }
+
+    /// Identities currently replicated to a connection
+    pub fn observed_by(
+        &self,
+        connection: ConnectionId,
+    ) -> impl Iterator<Item = NetworkIdentity> + '_ {
+        self.visibility
+            .iter()
+            .filter(move |(_, vis)| vis.has_observer(&connection))
+            .map(|(identity, _)| *identity)
+    }
 }
 
 #[derive(Default, Debug)]
@@ -157,7 +170,7 @@ struct SpatialCell {
 }
 
 #[derive(Debug, Resource)]
-struct SpatialHash {
+pub(crate) struct SpatialHash {
     cells: HashMap<IVec2, SpatialCell>,
     cell_size: u16,
 }
@@ -207,6 +220,14 @@ impl SpatialHash {
         }
     }
 
+    /// Entities in the cells an observer at `position` can see
+    pub(crate) fn entities_in_range(&self, position: IVec2, range: u32) -> HashSet<Entity> {
+        self.relevant_positions(position, UVec2::new(range, range))
+            .flat_map(|position| self.cells.get(&position))
+            .flat_map(|cell| cell.entities.iter().copied())
+            .collect()
+    }
+
     fn relevant_positions(&self, position: IVec2, size: UVec2) -> impl Iterator<Item = IVec2> {
         ((position.x - size.x as i32)..=(position.x + size.x as i32)).flat_map(move |x| {
             ((position.y - size.y as i32)..=(position.y + size.y as i32))
@@ -215,10 +236,15 @@ impl SpatialHash {
     }
 }
 
-type GlobalGrid = SpatialHash;
+pub(crate) type GlobalGrid = SpatialHash;
 /// The size of a side of a quadratic cell in the global grid
 pub const GLOBAL_GRID_CELL_SIZE: u16 = 10;
 
+/// Cell of the global grid a world position falls into
+pub fn global_grid_cell(position: Vec3) -> IVec2 {
+    position.xz().as_ivec2() / IVec2::splat(GLOBAL_GRID_CELL_SIZE.into())
+}
+
 #[derive(Component, Default)]
 pub(crate) struct InGrid {
     /// Where this entity is in the global grid
@@ -226,6 +252,12 @@ pub(crate) struct InGrid {
     aabb: GridAabb,
 }
 
+impl InGrid {
+    pub(crate) fn position(&self) -> Option<IVec2> {
+        self.position
+    }
+}
+
 /// A component that sets the size and center of the object in the visibility grid.
 /// This is only required for objects that are massive (bigger than a chunk).
 #[derive(Component, Default, PartialEq, Eq, Clone, Copy)]
@@ -240,6 +272,10 @@ pub struct GridAabb {
 #[derive(Component, Default)]
 pub struct NetworkObserverCells {
     cells: HashMap<IVec2, NetworkObserverCell>,
+    /// World position of the observer last frame
+    last_position: Option<Vec2>,
+    /// Smoothed movement of the observer in units per second
+    velocity: Vec2,
 }
 
 struct NetworkObserverCell {
@@ -248,6 +284,58 @@ struct NetworkObserverCell {
 
 /// How long a grid cell stays observed after it is out of range.
 const OBSERVER_CELL_TIMEOUT_SECONDS: f32 = 3.0;
+/// Movement speed at which cells ahead of the observer get the full priority bonus
+const FULL_PRIORITY_SPEED: f32 = 4.0;
+/// How much closer cells in the movement direction are treated as
+const DIRECTION_PRIORITY: f32 = 0.5;
+
+/// Limits how quickly observers take in new areas, usually read from the server config.
+///
+/// Every cell an observer starts observing spawns all entities in it on the client at once.
+/// Spreading new cells over several ticks keeps a teleport or a fast move from sending a whole
+/// area in one tick. Observers that see nothing yet observe their whole range at once and get
+/// a join snapshot instead.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct ObserverCellRate {
+    /// Cells an observer can start observing each server tick.
+    /// The visibility systems run once per tick, so this scales with the tick rate.
+    pub new_cells_per_tick: usize,
+}
+
+impl Default for ObserverCellRate {
+    fn default() -> Self {
+        // Fills a typical observer range within a second at 60 ticks per second
+        Self {
+            new_cells_per_tick: 4,
+        }
+    }
+}
+
+impl NetworkObserverCells {
+    /// Grid cells this observer currently receives entities from
+    pub fn observed_cells(&self) -> impl Iterator<Item = IVec2> + '_ {
+        self.cells.keys().copied()
+    }
+
+    fn update_velocity(&mut self, position: Vec2, delta: f32) {
+        if let Some(last) = self.last_position {
+            if delta > 0.0 {
+                let velocity = (position - last) / delta;
+                self.velocity = self.velocity.lerp(velocity, 0.2);
+            }
+        }
+        self.last_position = Some(position);
+    }
+
+    /// Lower values should be observed sooner.
+    /// Cells in the direction the observer is moving are preferred over cells behind it.
+    fn cell_priority(&self, offset: IVec2) -> f32 {
+        let offset = offset.as_vec2();
+        let heading = (self.velocity / FULL_PRIORITY_SPEED).clamp_length_max(1.0);
+        let alignment = offset.normalize_or_zero().dot(heading);
+        offset.length() * (1.0 - DIRECTION_PRIORITY * alignment)
+    }
+}
 
 fn global_grid_update(
     mut grid: ResMut<GlobalGrid>,
@@ -274,8 +362,15 @@ fn grid_visibility(
     mut visibilities: ResMut<NetworkVisibilities>,
     players: Res<Players>,
     grid: Res<GlobalGrid>,
-    mut observers: Query<(&NetworkObserver, &InGrid, &mut NetworkObserverCells)>,
+    mut observers: Query<(
+        &NetworkObserver,
+        &InGrid,
+        &GlobalTransform,
+        &mut NetworkObserverCells,
+    )>,
     identities: Query<&NetworkIdentity>,
+    mut snapshots: ResMut<JoinSnapshots>,
+    rate: Res<ObserverCellRate>,
     time: Res<Time>,
 ) {
     // Act like all observers have stopped observing (nothing visible by default)
@@ -283,7 +378,7 @@ fn grid_visibility(
         vis.assume_removed();
     }
 
-    for (observer, grid_position, mut observer_cells) in observers.iter_mut() {
+    for (observer, grid_position, transform, mut observer_cells) in observers.iter_mut() {
         let position = match grid_position.position {
             Some(p) => p,
             None => continue,
@@ -294,13 +389,38 @@ fn grid_visibility(
             None => continue,
         };
 
+        observer_cells.update_velocity(transform.translation().xz(), time.raw_delta_seconds());
+
         // Update the cells the observer sees
         let current_time = time.raw_elapsed_seconds();
-        for position in
+        let mut new_cells = Vec::new();
+        for cell_position in
             grid.relevant_positions(position, UVec2::new(observer.range, observer.range))
         {
+            match observer_cells.cells.get_mut(&cell_position) {
+                Some(cell) => cell.last_observed = current_time,
+                None => new_cells.push(cell_position),
+            }
+        }
+
+        // Start observing the most relevant new cells first
+        new_cells.sort_by(|a, b| {
+            observer_cells
+                .cell_priority(*a - position)
+                .total_cmp(&observer_cells.cell_priority(*b - position))
+        });
+        let joining = observer_cells.cells.is_empty() && !new_cells.is_empty();
+        if joining {
+            snapshots.request(connection);
+        }
+        let limit = if joining {
+            new_cells.len()
+        } else {
+            rate.new_cells_per_tick.max(1)
+        };
+        for cell_position in new_cells.into_iter().take(limit) {
             observer_cells.cells.insert(
-                position,
+                cell_position,
                 NetworkObserverCell {
                     last_observed: current_time,
                 },
@@ -363,6 +483,46 @@ fn always_visible(
     }
 }
 
+/// Sent when an entity starts or stops being replicated to a connection.
+/// Lets gameplay react to what a player can see without recomputing distances.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntityVisibilityEvent {
+    pub entity: Entity,
+    pub connection: ConnectionId,
+    pub change: VisibilityChange,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VisibilityChange {
+    Entered,
+    Exited,
+}
+
+fn send_visibility_events(
+    visibilities: Res<NetworkVisibilities>,
+    identities: Res<NetworkIdentities>,
+    mut events: EventWriter<EntityVisibilityEvent>,
+) {
+    for (identity, visibility) in visibilities.visibility.iter() {
+        let Some(entity) = identities.get_entity(*identity) else {
+            continue;
+        };
+        let entered = visibility
+            .new_observers()
+            .map(|connection| (*connection, VisibilityChange::Entered));
+        let exited = visibility
+            .removed_observers()
+            .map(|connection| (*connection, VisibilityChange::Exited));
+        events.send_batch(entered.chain(exited).map(|(connection, change)| {
+            EntityVisibilityEvent {
+                entity,
+                connection,
+                change,
+            }
+        }));
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, SystemSet)]
 pub enum VisibilitySystem {
     UpdateGrid,
@@ -380,6 +540,8 @@ impl Plugin for VisibilityPlugin {
             .is_server()
         {
             app.init_resource::<NetworkVisibilities>()
+                .init_resource::<ObserverCellRate>()
+                .add_event::<EntityVisibilityEvent>()
                 .insert_resource(GlobalGrid {
                     cell_size: GLOBAL_GRID_CELL_SIZE,
                     ..Default::default()
@@ -390,6 +552,7 @@ impl Plugin for VisibilityPlugin {
                         update_visibility,
                         grid_visibility.in_set(VisibilitySystem::GridVisibility),
                         always_visible,
+                        send_visibility_events,
                     )
                         .chain()
                         .in_set(NetworkSet::ServerVisibility),
@@ -403,3 +566,115 @@ impl Plugin for VisibilityPlugin {
         }
     }
 }
+
+/// Entry points for the criterion benchmarks
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod bench {
+    use bevy::{ecs::schedule::ExecutorKind, prelude::*, utils::Uuid};
+
+    use super::{
+        global_grid_update, grid_visibility, update_visibility, GlobalGrid, InGrid,
+        NetworkObserver, NetworkObserverCells, NetworkVisibilities, ObserverCellRate,
+        GLOBAL_GRID_CELL_SIZE,
+    };
+    use crate::{identity::NetworkIdentity, ConnectionId, Players, UserIdentity};
+
+    /// A server world with networked entities and player observers spread over a square area
+    pub struct VisibilityWorld {
+        world: World,
+        schedule: Schedule,
+        entities: Vec<Entity>,
+        tick: u32,
+    }
+
+    impl VisibilityWorld {
+        pub fn new(entities: u32, observers: u32, extent: f32) -> Self {
+            let mut world = World::new();
+            world.init_resource::<NetworkVisibilities>();
+            world.init_resource::<JoinSnapshots>();
+            world.init_resource::<ObserverCellRate>();
+            world.init_resource::<Time>();
+            world.insert_resource(GlobalGrid {
+                cell_size: GLOBAL_GRID_CELL_SIZE,
+                ..Default::default()
+            });
+
+            // Spread everything evenly in a deterministic pattern
+            let position = |i: u32, count: u32| {
+                let side = (count as f32).sqrt().ceil().max(1.0) as u32;
+                let step = extent / side as f32;
+                Vec3::new((i % side) as f32 * step, 0.0, (i / side) as f32 * step)
+            };
+
+            let entities = (0..entities)
+                .map(|i| {
+                    world
+                        .spawn((
+                            GlobalTransform::from_translation(position(i, entities)),
+                            InGrid::default(),
+                            NetworkIdentity::from_raw(i),
+                        ))
+                        .id()
+                })
+                .collect();
+
+            let mut players = Players::default();
+            for i in 0..observers {
+                let id = Uuid::from_u128(i as u128 + 1);
+                players.add(
+                    ConnectionId(i as u64),
+                    UserIdentity {
+                        id,
+                        username: format!("Observer{}", i),
+                    },
+                    None,
+                    false,
+                );
+                world.spawn((
+                    GlobalTransform::from_translation(position(i, observers)),
+                    InGrid::default(),
+                    NetworkObserver {
+                        range: 1,
+                        player_id: id,
+                    },
+                    NetworkObserverCells::default(),
+                ));
+            }
+            world.insert_resource(players);
+
+            let mut schedule = Schedule::default();
+            schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+            schedule.add_systems((update_visibility, grid_visibility, global_grid_update).chain());
+
+            let mut bench = Self {
+                world,
+                schedule,
+                entities,
+                tick: 0,
+            };
+            // Let observers settle on their starting cells
+            for _ in 0..10 {
+                bench.schedule.run(&mut bench.world);
+            }
+            bench
+        }
+
+        /// Moves every entity back and forth across a cell border and updates the grid and visibility
+        pub fn step(&mut self) {
+            self.tick += 1;
+            let offset = if self.tick % 2 == 0 {
+                GLOBAL_GRID_CELL_SIZE as f32
+            } else {
+                -(GLOBAL_GRID_CELL_SIZE as f32)
+            };
+            for &entity in self.entities.iter() {
+                let mut transform = self.world.get_mut::<GlobalTransform>(entity).unwrap();
+                *transform = GlobalTransform::from_translation(
+                    transform.translation() + Vec3::new(offset, 0.0, 0.0),
+                );
+            }
+            self.schedule.run(&mut self.world);
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use networking::{guard::GuardConfig, messaging::AppExt, testing::TestServer};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Chat {
+    text: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Upload {
+    data: Vec<u8>,
+}
+
+fn server() -> TestServer {
+    let mut server = TestServer::default();
+    server
+        .app
+        .add_network_message::<Chat>()
+        .add_network_message::<Upload>()
+        .set_message_limit::<Chat>(256);
+    server
+}
+
+fn send<T: Serialize + 'static>(server: &mut TestServer, message: &T) {
+    let id = server.message_id::<T>();
+    server.send_content(id, bincode::serialize(message).unwrap());
+}
+
+#[test]
+fn valid_messages_are_received() {
+    let mut server = server();
+    let chat = Chat {
+        text: "Hello".into(),
+    };
+    send(&mut server, &chat);
+    server.update();
+
+    assert_eq!(server.received::<Chat>(), vec![chat]);
+    assert_eq!(server.violation_score(), 0.0);
+}
+
+#[test]
+fn oversized_messages_are_rejected() {
+    let mut server = server();
+    send(
+        &mut server,
+        &Chat {
+            text: "a".repeat(1000),
+        },
+    );
+    server.update();
+
+    assert!(server.received::<Chat>().is_empty());
+    assert!(server.violation_score() > 0.0);
+    assert!(server.is_connected());
+}
+
+#[test]
+fn limits_are_per_message_type() {
+    let mut server = server();
+    let upload = Upload {
+        data: vec![7; 1000],
+    };
+    send(&mut server, &upload);
+    server.update();
+
+    assert_eq!(server.received::<Upload>(), vec![upload]);
+}
+
+#[test]
+fn configured_limits_replace_limits_in_code() {
+    let mut guard = GuardConfig::default();
+    guard.type_sizes.insert("Chat".into(), 2048);
+    guard.type_sizes.insert("Upload".into(), 64);
+    let mut server = TestServer::with_guard(guard);
+    server
+        .app
+        .add_network_message::<Chat>()
+        .add_network_message::<Upload>()
+        .set_message_limit::<Chat>(256);
+
+    let chat = Chat {
+        text: "a".repeat(1000),
+    };
+    send(&mut server, &chat);
+    send(
+        &mut server,
+        &Upload {
+            data: vec![7; 1000],
+        },
+    );
+    server.update();
+
+    assert_eq!(server.received::<Chat>(), vec![chat]);
+    assert!(server.received::<Upload>().is_empty());
+    assert!(server.violation_score() > 0.0);
+}
+
+#[test]
+fn huge_length_prefixes_do_not_allocate() {
+    let mut server = server();
+    // A vector claiming to hold u64::MAX bytes
+    let id = server.message_id::<Upload>();
+    server.send_content(id, u64::MAX.to_le_bytes().to_vec());
+    // An envelope claiming content of u64::MAX bytes
+    let mut envelope = id.to_le_bytes().to_vec();
+    envelope.extend(u64::MAX.to_le_bytes());
+    server.send_raw(envelope);
+    server.update();
+
+    assert!(server.received::<Upload>().is_empty());
+    assert!(server.violation_score() > 0.0);
+}
+
+#[test]
+fn malformed_messages_are_rejected() {
+    let mut server = server();
+    let id = server.message_id::<Chat>();
+    // Invalid UTF-8 in the string
+    let mut content = 2u64.to_le_bytes().to_vec();
+    content.extend([0xff, 0xfe]);
+    server.send_content(id, content);
+    // A type that was never registered
+    server.send_content(u16::MAX, vec![0; 8]);
+    server.update();
+
+    assert!(server.received::<Chat>().is_empty());
+    assert!(server.violation_score() > 0.0);
+}
+
+#[test]
+fn repeated_violations_disconnect() {
+    let mut server = server();
+    for _ in 0..20 {
+        send(
+            &mut server,
+            &Chat {
+                text: "a".repeat(1000),
+            },
+        );
+        server.update();
+        if !server.is_connected() {
+            return;
+        }
+    }
+    panic!("client was not disconnected");
+}
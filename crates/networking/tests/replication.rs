@@ -0,0 +1,128 @@
+use bevy::{prelude::*, reflect::TypeUuid};
+use networking::{
+    component::AppExt,
+    testing::TestHarness,
+    variable::{NetworkVar, ServerVar},
+    ConnectionId, Networked,
+};
+
+#[derive(Component, Networked)]
+#[networked(client = "SecretClient", owner = "owner")]
+struct Secret {
+    owner: NetworkVar<Option<ConnectionId>>,
+    #[networked(owner_only)]
+    value: NetworkVar<u32>,
+}
+
+#[derive(Component, Default, TypeUuid, Networked)]
+#[uuid = "4d3c1f0e-8a7b-4e2d-9c6f-1b5a0e3d7c82"]
+#[networked(server = "Secret")]
+struct SecretClient {
+    value: ServerVar<u32>,
+}
+
+fn secret_value(harness: &TestHarness, client: usize, entity: Entity) -> Option<u32> {
+    let entity = harness.client_entity(client, entity)?;
+    let secret = harness.clients[client]
+        .app
+        .world
+        .get::<SecretClient>(entity)?;
+    secret.value.get().copied()
+}
+
+#[test]
+fn clients_join() {
+    let mut harness = TestHarness::new(2, |_| {});
+    assert!(harness.join_all(60));
+}
+
+#[test]
+fn entities_in_range_are_replicated() {
+    let mut harness = TestHarness::new(2, |_| {});
+    assert!(harness.join_all(60));
+
+    let entity = harness.spawn_networked(TransformBundle::default());
+    harness.spawn_observer(0, Vec3::ZERO, 1);
+    harness.spawn_observer(1, Vec3::new(10_000.0, 0.0, 0.0), 1);
+
+    assert!(harness.step_until(60, |h| h.client_entity(0, entity).is_some()));
+    // The second observer is far away
+    for _ in 0..30 {
+        harness.step();
+    }
+    assert!(harness.client_entity(1, entity).is_none());
+}
+
+#[test]
+fn items_in_containers_are_replicated_where_the_container_is() {
+    let mut harness = TestHarness::new(2, |_| {});
+    assert!(harness.join_all(60));
+
+    let far_away = Vec3::new(10_000.0, 0.0, 0.0);
+    let container = harness.spawn_networked(TransformBundle::from_transform(
+        Transform::from_translation(far_away),
+    ));
+    // Stored items sit at the origin of their container
+    let item = harness.spawn_networked(TransformBundle::default());
+    harness.server.world.entity_mut(container).add_child(item);
+    harness.spawn_observer(0, Vec3::ZERO, 1);
+    harness.spawn_observer(1, far_away, 1);
+
+    assert!(harness.step_until(60, |h| {
+        h.client_entity(1, container).is_some() && h.client_entity(1, item).is_some()
+    }));
+    // The item's local position is near the first observer, but the container isn't
+    for _ in 0..30 {
+        harness.step();
+    }
+    assert!(harness.client_entity(0, item).is_none());
+}
+
+#[test]
+fn despawns_are_replicated() {
+    let mut harness = TestHarness::new(1, |_| {});
+    assert!(harness.join_all(60));
+
+    let entity = harness.spawn_networked(TransformBundle::default());
+    harness.spawn_observer(0, Vec3::ZERO, 1);
+    assert!(harness.step_until(60, |h| h.client_entity(0, entity).is_some()));
+
+    let identity = harness.identity(entity).unwrap();
+    harness.server.world.despawn(entity);
+    assert!(harness.step_until(60, |h| h.clients[0].entity(identity).is_none()));
+}
+
+#[test]
+fn owner_only_fields_follow_the_owner() {
+    let mut harness = TestHarness::new(2, |app| {
+        app.add_networked_component::<Secret, SecretClient>();
+    });
+    assert!(harness.join_all(60));
+
+    let first = harness.clients[0].connection();
+    let second = harness.clients[1].connection();
+    let entity = harness.spawn_networked((
+        TransformBundle::default(),
+        Secret {
+            owner: Some(first).into(),
+            value: 42.into(),
+        },
+    ));
+    harness.spawn_observer(0, Vec3::ZERO, 1);
+    harness.spawn_observer(1, Vec3::ZERO, 1);
+    assert!(harness.step_until(60, |h| {
+        secret_value(h, 0, entity) == Some(42) && h.client_entity(1, entity).is_some()
+    }));
+    assert_ne!(secret_value(&harness, 1, entity), Some(42));
+
+    // The value itself doesn't change, only who may see it
+    *harness
+        .server
+        .world
+        .get_mut::<Secret>(entity)
+        .unwrap()
+        .owner = Some(second);
+    assert!(harness.step_until(60, |h| {
+        secret_value(h, 1, entity) == Some(42) && secret_value(h, 0, entity) == Some(0)
+    }));
+}
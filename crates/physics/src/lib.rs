@@ -63,10 +63,14 @@ pub enum ColliderGroup {
     Default,
     CharacterColliders,
     AttachedLimbs,
+    Fixtures,
+    Incorporeal,
 }
 
 pub const DEFAULT_GROUP: Group = Group::GROUP_1;
 pub const LIMB_GROUP: Group = Group::GROUP_3;
+pub const FIXTURE_GROUP: Group = Group::GROUP_4;
+pub const INCORPOREAL_GROUP: Group = Group::GROUP_5;
 pub const RAYCASTING_GROUP: Group = Group::GROUP_32;
 
 impl From<ColliderGroup> for CollisionGroups {
@@ -77,6 +81,10 @@ impl From<ColliderGroup> for CollisionGroups {
             ColliderGroup::CharacterColliders => CollisionGroups::new(Group::GROUP_2, Group::ALL),
             // Limbs attached to bodies collide with raycasts
             ColliderGroup::AttachedLimbs => CollisionGroups::new(LIMB_GROUP, RAYCASTING_GROUP),
+            // Fixtures mounted out of the way, only there to be clicked on
+            ColliderGroup::Fixtures => CollisionGroups::new(FIXTURE_GROUP, RAYCASTING_GROUP),
+            // Ghosts and the like, which pass through everything
+            ColliderGroup::Incorporeal => CollisionGroups::new(INCORPOREAL_GROUP, Group::NONE),
         }
     }
 }
@@ -89,6 +97,8 @@ impl TryFrom<CollisionGroups> for ColliderGroup {
             (DEFAULT_GROUP, Group::ALL) => Ok(ColliderGroup::Default),
             (Group::GROUP_2, Group::ALL) => Ok(ColliderGroup::CharacterColliders),
             (LIMB_GROUP, RAYCASTING_GROUP) => Ok(ColliderGroup::AttachedLimbs),
+            (FIXTURE_GROUP, RAYCASTING_GROUP) => Ok(ColliderGroup::Fixtures),
+            (INCORPOREAL_GROUP, Group::NONE) => Ok(ColliderGroup::Incorporeal),
             _ => {
                 bevy::log::info!("Error converting collision groups {:?}", value);
                 Err(())
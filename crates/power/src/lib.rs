@@ -0,0 +1,394 @@
+//! Station power: cables connect generators to area power controllers (APCs),
+//! which store charge and supply the machines around them.
+
+use bevy::{
+    math::{IVec2, Vec3Swizzles},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::{HashMap, HashSet},
+};
+use maps::TileEntity;
+use networking::{
+    component::AppExt,
+    is_server,
+    time::{NetworkTick, TickTime},
+    variable::{NetworkVar, ServerVar},
+    Networked,
+};
+
+pub struct PowerPlugin;
+
+impl Plugin for PowerPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Cable>()
+            .register_type::<Generator>()
+            .register_type::<AreaPowerController>()
+            .register_type::<PowerConsumer>()
+            .register_type::<Disrupted>()
+            .add_networked_component::<Powered, PoweredClient>();
+
+        if is_server(app) {
+            app.init_resource::<CableNetworks>().add_systems(
+                NetworkTick,
+                (
+                    rebuild_cable_networks,
+                    add_power_state,
+                    apply_deferred,
+                    distribute_power,
+                    remove_expired_disruptions,
+                )
+                    .chain()
+                    .in_set(PowerSystem::Distribute),
+            );
+        } else {
+            app.add_systems(Update, client_update_lights);
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, SystemSet)]
+pub enum PowerSystem {
+    Distribute,
+}
+
+/// A piece of cable, laid into the turf of the tile it is on.
+/// Cables on neighbouring tiles are connected.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Cable;
+
+/// Feeds power into the cable network on its tile.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Generator {
+    /// Power output in watts
+    pub output: f32,
+}
+
+/// Stores power from its cable network and supplies consumers in its area.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct AreaPowerController {
+    /// Maximum charge in joules
+    pub capacity: f32,
+    /// Current charge in joules
+    pub charge: f32,
+    /// How many tiles away consumers can be supplied
+    pub range: u32,
+}
+
+impl Default for AreaPowerController {
+    fn default() -> Self {
+        Self {
+            capacity: 50_000.0,
+            charge: 50_000.0,
+            range: 12,
+        }
+    }
+}
+
+/// A machine that draws power from the closest APC.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct PowerConsumer {
+    /// Power draw in watts
+    pub draw: f32,
+}
+
+/// Knocks a consumer out until the given time, regardless of supply.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Disrupted {
+    /// Seconds since startup
+    pub until: f32,
+}
+
+/// If a consumer is currently supplied with power.
+/// Consumers outside of every APC's area are unmanaged and stay powered.
+#[derive(Component, Networked)]
+#[networked(client = "PoweredClient")]
+pub struct Powered {
+    powered: NetworkVar<bool>,
+    disrupted: NetworkVar<bool>,
+    /// The APC supplying this consumer
+    supplier: Option<Entity>,
+}
+
+impl Powered {
+    pub fn is_powered(&self) -> bool {
+        *self.powered
+    }
+
+    /// The APC this consumer draws from, `None` if it is unmanaged or disrupted
+    pub fn supplier(&self) -> Option<Entity> {
+        self.supplier
+    }
+}
+
+#[derive(Component, Default, TypeUuid, Networked)]
+#[uuid = "abcd47c1-562a-43f2-89b3-1401a23d7f54"]
+#[networked(server = "Powered")]
+pub struct PoweredClient {
+    powered: ServerVar<bool>,
+    disrupted: ServerVar<bool>,
+}
+
+impl PoweredClient {
+    pub fn is_powered(&self) -> bool {
+        self.powered.get().copied().unwrap_or(true)
+    }
+
+    pub fn is_disrupted(&self) -> bool {
+        self.disrupted.get().copied().unwrap_or_default()
+    }
+}
+
+/// The tile position of an object in the world.
+/// Tile entities use the tile they belong to, as wall mounts sit on the edge of their tile.
+fn tile_position(tile: Option<&TileEntity>, transform: &GlobalTransform) -> IVec2 {
+    match tile {
+        Some(tile) => tile.position().as_ivec2(),
+        None => transform.translation().xz().round().as_ivec2(),
+    }
+}
+
+/// Which connected cable network each cabled tile belongs to
+#[derive(Resource, Default)]
+pub struct CableNetworks {
+    networks: HashMap<IVec2, u32>,
+}
+
+impl CableNetworks {
+    pub fn network_at(&self, position: IVec2) -> Option<u32> {
+        self.networks.get(&position).copied()
+    }
+}
+
+fn rebuild_cable_networks(
+    mut networks: ResMut<CableNetworks>,
+    cables: Query<(Option<&TileEntity>, &GlobalTransform), With<Cable>>,
+    changed: Query<(), (With<Cable>, Changed<GlobalTransform>)>,
+    mut removed: RemovedComponents<Cable>,
+) {
+    if changed.is_empty() && removed.iter().next().is_none() {
+        return;
+    }
+
+    let positions: HashSet<IVec2> = cables
+        .iter()
+        .map(|(tile, transform)| tile_position(tile, transform))
+        .collect();
+    networks.networks.clear();
+    let mut next_id = 0;
+    for &start in positions.iter() {
+        if networks.networks.contains_key(&start) {
+            continue;
+        }
+
+        // Flood fill all connected cables
+        let mut to_visit = vec![start];
+        while let Some(position) = to_visit.pop() {
+            if networks.networks.contains_key(&position) {
+                continue;
+            }
+            networks.networks.insert(position, next_id);
+            for offset in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+                let neighbour = position + offset;
+                if positions.contains(&neighbour) && !networks.networks.contains_key(&neighbour) {
+                    to_visit.push(neighbour);
+                }
+            }
+        }
+        next_id += 1;
+    }
+}
+
+fn add_power_state(
+    consumers: Query<Entity, (With<PowerConsumer>, Without<Powered>)>,
+    mut commands: Commands,
+) {
+    for entity in consumers.iter() {
+        commands.entity(entity).insert(Powered {
+            powered: true.into(),
+            disrupted: false.into(),
+            supplier: None,
+        });
+    }
+}
+
+fn distribute_power(
+    networks: Res<CableNetworks>,
+    generators: Query<(&Generator, Option<&TileEntity>, &GlobalTransform)>,
+    mut apcs: Query<(
+        Entity,
+        &mut AreaPowerController,
+        Option<&TileEntity>,
+        &GlobalTransform,
+    )>,
+    mut consumers: Query<(
+        &PowerConsumer,
+        &mut Powered,
+        Option<&TileEntity>,
+        &GlobalTransform,
+        Option<&Disrupted>,
+    )>,
+    time: Res<Time>,
+    tick: Res<TickTime>,
+) {
+    let delta = tick.delta_seconds();
+    let now = time.elapsed_seconds();
+
+    // Charge APCs from the generators on their network
+    let mut supply: HashMap<u32, f32> = HashMap::default();
+    for (generator, tile, transform) in generators.iter() {
+        if let Some(network) = networks.network_at(tile_position(tile, transform)) {
+            *supply.entry(network).or_default() += generator.output * delta;
+        }
+    }
+    let mut demand: HashMap<u32, f32> = HashMap::default();
+    for (_, apc, tile, transform) in apcs.iter() {
+        if let Some(network) = networks.network_at(tile_position(tile, transform)) {
+            *demand.entry(network).or_default() += apc.capacity - apc.charge;
+        }
+    }
+    for (_, mut apc, tile, transform) in apcs.iter_mut() {
+        let Some(network) = networks.network_at(tile_position(tile, transform)) else {
+            continue;
+        };
+        let (Some(&supply), Some(&demand)) = (supply.get(&network), demand.get(&network)) else {
+            continue;
+        };
+        let missing = apc.capacity - apc.charge;
+        if demand <= 0.0 || missing <= 0.0 {
+            continue;
+        }
+        // Split the supply by how much each APC is missing
+        apc.charge += (supply * missing / demand).min(missing);
+    }
+
+    // Supply consumers from the closest APC covering them
+    let apc_positions: Vec<(Entity, IVec2, u32)> = apcs
+        .iter()
+        .map(|(entity, apc, tile, transform)| (entity, tile_position(tile, transform), apc.range))
+        .collect();
+    for (consumer, mut powered, tile, transform, disrupted) in consumers.iter_mut() {
+        let is_disrupted = disrupted.map(|d| d.until > now).unwrap_or_default();
+        if *powered.disrupted != is_disrupted {
+            *powered.disrupted = is_disrupted;
+        }
+        if is_disrupted {
+            if *powered.powered {
+                *powered.powered = false;
+            }
+            if powered.supplier.is_some() {
+                powered.supplier = None;
+            }
+            continue;
+        }
+
+        let position = tile_position(tile, transform);
+        let closest = apc_positions
+            .iter()
+            .map(|(entity, apc_position, range)| {
+                let distance = (position - *apc_position).abs().max_element() as u32;
+                (*entity, distance, *range)
+            })
+            .filter(|(_, distance, range)| distance <= range)
+            .min_by_key(|(_, distance, _)| *distance)
+            .map(|(entity, ..)| entity);
+        if powered.supplier != closest {
+            powered.supplier = closest;
+        }
+
+        let is_powered = match closest {
+            Some(apc_entity) => {
+                let (_, mut apc, ..) = apcs.get_mut(apc_entity).unwrap();
+                let needed = consumer.draw * delta;
+                if apc.charge >= needed {
+                    apc.charge -= needed;
+                    true
+                } else {
+                    false
+                }
+            }
+            None => true,
+        };
+
+        if *powered.powered != is_powered {
+            *powered.powered = is_powered;
+        }
+    }
+}
+
+fn remove_expired_disruptions(
+    disrupted: Query<(Entity, &Disrupted)>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    let now = time.elapsed_seconds();
+    for (entity, disrupted) in disrupted.iter() {
+        if disrupted.until <= now {
+            commands.entity(entity).remove::<Disrupted>();
+        }
+    }
+}
+
+/// Turns off lights of unpowered machines and flickers those of disrupted ones
+fn client_update_lights(
+    machines: Query<(Entity, Ref<PoweredClient>)>,
+    children: Query<&Children>,
+    mut lights: Query<&mut Visibility, Or<(With<PointLight>, With<SpotLight>)>>,
+    time: Res<Time>,
+) {
+    for (entity, powered) in machines.iter() {
+        let flickering = powered.is_disrupted();
+        if !flickering && !powered.is_changed() {
+            continue;
+        }
+
+        // Offset by entity so lights don't flicker in unison
+        let flicker_on = (time.elapsed_seconds() * 13.0 + entity.index() as f32 * 1.7).sin() > 0.4;
+        let visibility = if powered.is_powered() || (flickering && flicker_on) {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        for descendant in std::iter::once(entity).chain(children.iter_descendants(entity)) {
+            if let Ok(mut light) = lights.get_mut(descendant) {
+                if *light != visibility {
+                    *light = visibility;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{math::Vec3Swizzles, prelude::*};
+    use maps::{testing, TileEntity, DIRECTIONS};
+
+    use super::tile_position;
+
+    #[test]
+    fn wall_mounts_are_on_their_own_tile() {
+        let mut world = World::new();
+        let tilemap = testing::spawn_tilemap(&mut world, UVec2::ONE);
+        let position = UVec2::new(4, 4);
+        for direction in DIRECTIONS {
+            let mount = testing::spawn_mount(&mut world, tilemap, position, direction);
+            let entity = world.entity(mount);
+            let transform = entity.get::<GlobalTransform>().unwrap();
+            // Mounts hang half a tile away from the centre of their tile
+            let offset = transform.translation().xz() - position.as_vec2();
+            assert!((offset.length() - 0.5).abs() < 1e-4, "{:?}", direction);
+
+            assert_eq!(
+                tile_position(entity.get::<TileEntity>(), transform),
+                position.as_ivec2(),
+                "{:?}",
+                direction
+            );
+        }
+    }
+}
@@ -1,2 +1,3 @@
+pub mod rng;
 pub mod task;
 pub mod text;
@@ -0,0 +1,84 @@
+use std::ops::RangeInclusive;
+
+/// A small, fast and seedable pseudo random number generator (xorshift64*).
+///
+/// Not cryptographically secure. Use it for gameplay randomness that should be reproducible from a seed.
+#[derive(Clone, Debug)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        // Scramble the seed so similar seeds don't produce similar sequences.
+        // The state must never be zero.
+        let state = splitmix64(seed);
+        Self {
+            state: if state == 0 {
+                0x9E37_79B9_7F4A_7C15
+            } else {
+                state
+            },
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Returns a float in the range `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Returns a number in the inclusive range.
+    pub fn range(&mut self, range: RangeInclusive<u32>) -> u32 {
+        let (start, end) = range.into_inner();
+        if end <= start {
+            return start;
+        }
+        let span = (end - start) as u64 + 1;
+        start + (self.next_u64() % span) as u32
+    }
+
+    /// Picks an element with a probability proportional to its weight.
+    /// Returns `None` if there are no elements or all weights are zero.
+    pub fn choose_weighted<'a, T>(
+        &mut self,
+        items: &'a [T],
+        weight: impl Fn(&T) -> f32,
+    ) -> Option<&'a T> {
+        let total: f32 = items.iter().map(|i| weight(i).max(0.0)).sum();
+        if total <= 0.0 {
+            return None;
+        }
+
+        let mut target = self.next_f32() * total;
+        for item in items {
+            let w = weight(item).max(0.0);
+            if target < w {
+                return Some(item);
+            }
+            target -= w;
+        }
+        // Floating point inaccuracy, fall back to last item with weight
+        items.iter().rev().find(|i| weight(*i) > 0.0)
+    }
+
+    /// Creates an independent generator from this one.
+    pub fn fork(&mut self) -> Self {
+        Self::new(self.next_u64())
+    }
+}
+
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
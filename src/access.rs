@@ -0,0 +1,107 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+use networking::is_server;
+
+use crate::{
+    body::Hand,
+    economy::AccountId,
+    items::{clothes::ClothingHolder, Item, StoredItem},
+};
+
+pub struct AccessPlugin;
+
+impl Plugin for AccessPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<IdCard>()
+            .register_type::<AccessRequirement>();
+        if is_server(app) {
+            app.add_systems(Update, assign_job_access);
+        }
+    }
+}
+
+/// An item carrying access flags, used by holding or wearing it.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct IdCard {
+    pub access: Vec<String>,
+    /// Bank account payments with this card are made from
+    #[reflect(ignore)]
+    pub account: Option<AccountId>,
+}
+
+/// Restricts use of a machine or door to creatures with at least one of the listed access flags.
+/// Anyone is allowed if the list is empty.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct AccessRequirement {
+    pub any_of: Vec<String>,
+}
+
+impl AccessRequirement {
+    pub fn allows(&self, access: &[String]) -> bool {
+        self.any_of.is_empty() || self.any_of.iter().any(|a| access.contains(a))
+    }
+}
+
+/// Access to write onto an ID card once its scene has spawned.
+/// Used to hand out cards matching a job when a player spawns.
+#[derive(Component)]
+pub struct AssignAccess(pub Vec<String>);
+
+fn assign_job_access(
+    mut items: Query<(Entity, Option<&mut IdCard>, &AssignAccess), With<Item>>,
+    mut commands: Commands,
+) {
+    for (entity, card, assign) in items.iter_mut() {
+        if let Some(mut card) = card {
+            card.access = assign.0.clone();
+        }
+        commands.entity(entity).remove::<AssignAccess>();
+    }
+}
+
+/// Checks the access of a creature, from ID cards in its hands or worn by it
+#[derive(SystemParam)]
+pub struct AccessCheck<'w, 's> {
+    child_query: Query<'w, 's, &'static Children>,
+    cards: Query<'w, 's, (&'static IdCard, &'static StoredItem)>,
+    hands: Query<'w, 's, (), With<Hand>>,
+    clothing_holders: Query<'w, 's, (), With<ClothingHolder>>,
+    requirements: Query<'w, 's, &'static AccessRequirement>,
+}
+
+impl<'w, 's> AccessCheck<'w, 's> {
+    /// ID cards the creature is holding or wearing
+    pub fn cards(&self, creature: Entity) -> impl Iterator<Item = &IdCard> + '_ {
+        self.cards
+            .iter_many(self.child_query.iter_descendants(creature))
+            .filter(|(_, stored)| {
+                // Cards stuffed into a backpack don't count
+                let container = stored.container();
+                self.hands.contains(container) || self.clothing_holders.contains(container)
+            })
+            .map(|(card, _)| card)
+    }
+
+    /// All access flags the creature currently carries
+    pub fn access(&self, creature: Entity) -> Vec<String> {
+        let mut access = Vec::new();
+        for card in self.cards(creature) {
+            for flag in card.access.iter() {
+                if !access.contains(flag) {
+                    access.push(flag.clone());
+                }
+            }
+        }
+        access
+    }
+
+    /// Whether the creature is allowed to use the target.
+    /// Targets without an [`AccessRequirement`] can be used by anyone.
+    pub fn is_allowed(&self, creature: Entity, target: Entity) -> bool {
+        let Ok(requirement) = self.requirements.get(target) else {
+            return true;
+        };
+        requirement.allows(&self.access(creature))
+    }
+}
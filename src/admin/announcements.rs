@@ -0,0 +1,141 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use networking::{
+    is_server,
+    messaging::{AppExt, MessageEvent, MessageSender},
+    Players,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    communication::announcements::{Announcement, AnnouncementPriority},
+    config::ServerConfig,
+    ui::has_window,
+    GameState,
+};
+
+use super::{ModerationLog, StaffRole};
+
+pub(crate) struct AnnouncementAdminPlugin;
+
+impl Plugin for AnnouncementAdminPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_network_message::<AdminAnnouncementMessage>();
+
+        if is_server(app) {
+            app.init_resource::<ModerationLog>()
+                .add_systems(Update, handle_admin_announcement);
+        } else {
+            app.init_resource::<AnnouncementDraft>().add_systems(
+                Update,
+                client_announcement_ui
+                    .run_if(has_window)
+                    .run_if(in_state(GameState::Game)),
+            );
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct AdminAnnouncementMessage {
+    title: String,
+    text: String,
+    priority: AnnouncementPriority,
+    /// Access required to hear it, if not station-wide
+    department: Option<String>,
+}
+
+const MAX_ANNOUNCEMENT_LENGTH: usize = 1024;
+
+fn handle_admin_announcement(
+    mut messages: EventReader<MessageEvent<AdminAnnouncementMessage>>,
+    mut announcements: EventWriter<Announcement>,
+    players: Res<Players>,
+    config: Res<ServerConfig>,
+    mut log: ResMut<ModerationLog>,
+) {
+    for event in messages.iter() {
+        let Some(player) = players.get(event.connection) else {
+            continue;
+        };
+        if config.staff_role(player.id) < Some(StaffRole::Admin) {
+            warn!(player = ?player.username, "Player without permission tried to make an announcement");
+            continue;
+        }
+
+        let message = &event.message;
+        if message.text.trim().is_empty()
+            || message.title.len() + message.text.len() > MAX_ANNOUNCEMENT_LENGTH
+        {
+            continue;
+        }
+
+        log.record(&format!(
+            "{} announced \"{}\": {}",
+            player.username, message.title, message.text
+        ));
+        let mut announcement = Announcement::new(&message.title, &message.text, message.priority);
+        if let Some(department) = &message.department {
+            announcement = announcement.department(department);
+        }
+        announcements.send(announcement);
+    }
+}
+
+#[derive(Resource)]
+struct AnnouncementDraft {
+    title: String,
+    text: String,
+    priority: AnnouncementPriority,
+    department: String,
+}
+
+impl Default for AnnouncementDraft {
+    fn default() -> Self {
+        Self {
+            title: "Central Command Update".into(),
+            text: String::new(),
+            priority: AnnouncementPriority::Normal,
+            department: String::new(),
+        }
+    }
+}
+
+fn client_announcement_ui(
+    mut contexts: EguiContexts,
+    mut draft: ResMut<AnnouncementDraft>,
+    mut sender: MessageSender,
+) {
+    egui::Window::new("Announcement")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            let draft = &mut *draft;
+            ui.text_edit_singleline(&mut draft.title);
+            ui.text_edit_multiline(&mut draft.text);
+            egui::ComboBox::from_label("Priority")
+                .selected_text(format!("{:?}", draft.priority))
+                .show_ui(ui, |ui| {
+                    for priority in AnnouncementPriority::ALL {
+                        ui.selectable_value(
+                            &mut draft.priority,
+                            priority,
+                            format!("{:?}", priority),
+                        );
+                    }
+                });
+            ui.horizontal(|ui| {
+                ui.label("Department access");
+                ui.text_edit_singleline(&mut draft.department);
+            });
+
+            if ui.button("Announce").clicked() && !draft.text.trim().is_empty() {
+                let department = draft.department.trim();
+                sender.send_to_server(&AdminAnnouncementMessage {
+                    title: draft.title.clone(),
+                    text: std::mem::take(&mut draft.text),
+                    priority: draft.priority,
+                    department: (!department.is_empty()).then(|| department.to_owned()),
+                });
+            }
+        });
+}
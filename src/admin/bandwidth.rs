@@ -0,0 +1,141 @@
+//! Report of the server's bandwidth usage per feature, to trace regressions to a system.
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use networking::{
+    bandwidth::{BandwidthUsage, FeatureUsage, BANDWIDTH_WINDOW_SECONDS},
+    is_server,
+    messaging::{AppExt, MessageEvent, MessageReceivers, MessageSender},
+    Players,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{config::ServerConfig, debug::DebugState, ui::has_window, GameState};
+
+use super::StaffRole;
+
+pub(crate) struct BandwidthReportPlugin;
+
+impl Plugin for BandwidthReportPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_network_message::<RequestBandwidthReportMessage>()
+            .add_network_message::<BandwidthReportMessage>();
+
+        if is_server(app) {
+            app.add_systems(Update, handle_bandwidth_request);
+        } else {
+            app.init_resource::<LastBandwidthReport>().add_systems(
+                Update,
+                (
+                    client_receive_bandwidth_report,
+                    client_bandwidth_ui
+                        .run_if(has_window)
+                        .run_if(in_state(GameState::Game)),
+                )
+                    .chain(),
+            );
+        }
+    }
+}
+
+/// Asks the server for its bandwidth usage in the last full window
+#[derive(Serialize, Deserialize, Clone)]
+struct RequestBandwidthReportMessage;
+
+#[derive(Serialize, Deserialize, Clone)]
+struct BandwidthReportMessage {
+    features: Vec<FeatureUsage>,
+}
+
+fn handle_bandwidth_request(
+    mut messages: EventReader<MessageEvent<RequestBandwidthReportMessage>>,
+    players: Res<Players>,
+    config: Res<ServerConfig>,
+    usage: Res<BandwidthUsage>,
+    mut sender: MessageSender,
+) {
+    for event in messages.iter() {
+        let Some(player) = players.get(event.connection) else {
+            continue;
+        };
+        if config.staff_role(player.id) < Some(StaffRole::Admin) {
+            warn!(player = ?player.username, "Player without permission requested bandwidth usage");
+            continue;
+        }
+
+        sender.send(
+            &BandwidthReportMessage {
+                features: usage.last_window().to_vec(),
+            },
+            MessageReceivers::Single(event.connection),
+        );
+    }
+}
+
+#[derive(Resource, Default)]
+struct LastBandwidthReport(Option<BandwidthReportMessage>);
+
+fn client_receive_bandwidth_report(
+    mut messages: EventReader<MessageEvent<BandwidthReportMessage>>,
+    mut report: ResMut<LastBandwidthReport>,
+) {
+    if let Some(event) = messages.iter().last() {
+        report.0 = Some(event.message.clone());
+    }
+}
+
+fn client_bandwidth_ui(
+    mut contexts: EguiContexts,
+    mut debug: ResMut<DebugState>,
+    report: Res<LastBandwidthReport>,
+    mut sender: MessageSender,
+) {
+    if !debug.bandwidth_report {
+        return;
+    }
+
+    egui::Window::new("Bandwidth")
+        .open(&mut debug.bandwidth_report)
+        .show(contexts.ctx_mut(), |ui| {
+            if ui.button("Refresh").clicked() {
+                sender.send_to_server(&RequestBandwidthReportMessage);
+            }
+            let Some(report) = report.0.as_ref() else {
+                return;
+            };
+            if report.features.is_empty() {
+                ui.label("Nothing was sent in the last window.");
+                return;
+            }
+
+            let total: u64 = report.features.iter().map(|f| f.bytes).sum();
+            ui.label(format!(
+                "{:.1} KiB/s in the last {} seconds",
+                total as f32 / 1024.0 / BANDWIDTH_WINDOW_SECONDS,
+                BANDWIDTH_WINDOW_SECONDS
+            ));
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                egui::Grid::new("bandwidth by feature")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("Feature");
+                        ui.label("KiB");
+                        ui.label("Messages");
+                        ui.label("Share");
+                        ui.end_row();
+
+                        for feature in report.features.iter() {
+                            ui.label(&feature.feature);
+                            ui.label(format!("{:.1}", feature.bytes as f32 / 1024.0));
+                            ui.label(feature.messages.to_string());
+                            ui.label(format!(
+                                "{:.0}%",
+                                feature.bytes as f32 / total as f32 * 100.0
+                            ));
+                            ui.end_row();
+                        }
+                    });
+            });
+        });
+}
@@ -0,0 +1,169 @@
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use networking::{
+    bans::{unix_now, Ban, BannedPlayers},
+    guard::ConnectionFlagged,
+    is_server, Players,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{config::ServerConfig, storage::Storage};
+
+use super::ModerationLog;
+
+pub(crate) struct BanPlugin;
+
+impl Plugin for BanPlugin {
+    fn build(&self, app: &mut App) {
+        if is_server(app) {
+            app.insert_resource(ExpiryCheck(Timer::from_seconds(60.0, TimerMode::Repeating)))
+                .init_resource::<ModerationLog>()
+                .add_systems(Startup, load_bans)
+                .add_systems(Update, (remove_expired_bans, record_flagged_connections));
+        }
+    }
+}
+
+const DEFAULT_BAN_FILE: &str = "data/bans.toml";
+/// Where bans were kept before the path could be configured
+const LEGACY_BAN_FILE: &str = "bans.toml";
+
+/// Where bans are saved to
+pub(crate) fn ban_file(config: Option<&ServerConfig>) -> PathBuf {
+    config
+        .and_then(|config| config.ban_file.clone())
+        .unwrap_or_else(|| DEFAULT_BAN_FILE.into())
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct BanFile {
+    #[serde(default)]
+    bans: Vec<Ban>,
+}
+
+fn load_bans(
+    mut banned: ResMut<BannedPlayers>,
+    storage: Res<Storage>,
+    config: Option<Res<ServerConfig>>,
+) {
+    let mut path = ban_file(config.as_deref());
+    if !path.exists() && Path::new(LEGACY_BAN_FILE).exists() {
+        info!(path = LEGACY_BAN_FILE, "Loading bans from the old location");
+        path = LEGACY_BAN_FILE.into();
+    }
+    let Some(file) = storage.load_checked::<BanFile>(&path) else {
+        return;
+    };
+    let count = file.bans.len();
+    for ban in file.bans {
+        banned.ban(ban);
+    }
+    info!(count, "Loaded bans");
+}
+
+/// Writes all bans to disk, call after changing them
+pub(crate) fn save_bans(banned: &BannedPlayers, config: Option<&ServerConfig>) {
+    let file = BanFile {
+        bans: banned.bans().to_vec(),
+    };
+    let path = ban_file(config);
+    let result = toml::to_string(&file)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+        .and_then(|text| {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&path, text)
+        });
+    if let Err(err) = result {
+        error!(path = ?path, error = %err, "Failed to save bans");
+    }
+}
+
+/// Parses ban lengths like `30m`, `12h` or `7d` into seconds.
+/// `perm` gives a permanent ban, which is `Some(None)`.
+pub(crate) fn parse_ban_duration(text: &str) -> Option<Option<u64>> {
+    if text == "perm" {
+        return Some(None);
+    }
+    let unit = text.chars().last()?;
+    let amount: u64 = text.strip_suffix(['m', 'h', 'd'])?.parse().ok()?;
+    let unit_seconds = match unit {
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        _ => return None,
+    };
+    Some(Some(amount.checked_mul(unit_seconds)?))
+}
+
+/// Unix timestamp a ban of the given length ends at
+pub(crate) fn ban_expiry(seconds: Option<u64>) -> Option<u64> {
+    seconds.map(|s| unix_now().saturating_add(s))
+}
+
+#[derive(Resource)]
+struct ExpiryCheck(Timer);
+
+fn remove_expired_bans(
+    mut check: ResMut<ExpiryCheck>,
+    mut banned: ResMut<BannedPlayers>,
+    config: Option<Res<ServerConfig>>,
+    time: Res<Time>,
+) {
+    if !check.0.tick(time.delta()).just_finished() {
+        return;
+    }
+    if banned.remove_expired(unix_now()) {
+        save_bans(&banned, config.as_deref());
+    }
+}
+
+/// Keeps a record of clients that sent invalid messages, so staff can decide on a ban
+fn record_flagged_connections(
+    mut flagged: EventReader<ConnectionFlagged>,
+    players: Res<Players>,
+    mut log: ResMut<ModerationLog>,
+) {
+    for event in flagged.iter() {
+        let name = players
+            .get(event.connection)
+            .map(|player| format!("{} ({})", player.username, player.id))
+            .unwrap_or_else(|| format!("Connection {}", event.connection));
+        log.record(&format!(
+            "{} was flagged for sending invalid messages (score {:.1})",
+            name, event.score
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_ban_duration;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_ban_duration("30m"), Some(Some(30 * 60)));
+        assert_eq!(parse_ban_duration("12h"), Some(Some(12 * 60 * 60)));
+        assert_eq!(parse_ban_duration("7d"), Some(Some(7 * 24 * 60 * 60)));
+        assert_eq!(parse_ban_duration("perm"), Some(None));
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        assert_eq!(parse_ban_duration(""), None);
+        assert_eq!(parse_ban_duration("d"), None);
+        assert_eq!(parse_ban_duration("30"), None);
+        assert_eq!(parse_ban_duration("30s"), None);
+        assert_eq!(parse_ban_duration(&format!("{}d", u64::MAX)), None);
+    }
+
+    #[test]
+    fn rejects_non_ascii_input() {
+        assert_eq!(parse_ban_duration("café"), None);
+        assert_eq!(parse_ban_duration("3é"), None);
+        assert_eq!(parse_ban_duration("é"), None);
+        assert_eq!(parse_ban_duration("3\u{1F600}"), None);
+    }
+}
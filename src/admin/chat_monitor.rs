@@ -0,0 +1,332 @@
+//! Lets staff read the chat of every channel, filtered by player, channel and keyword.
+//!
+//! Mentors only get OOC, admins get everything.
+//! Starting, changing and stopping a monitor is written to the moderation log.
+
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_egui::{egui, EguiContexts};
+use networking::{
+    is_server,
+    messaging::{AppExt, MessageEvent, MessageReceivers, MessageSender},
+    ConnectionId, Players,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    communication::{radio::channel_name, ChatEvent, ChatKind},
+    config::ServerConfig,
+    debug::DebugState,
+    ui::has_window,
+    GameState,
+};
+
+use super::{ModerationLog, StaffRole};
+
+pub(crate) struct ChatMonitorPlugin;
+
+impl Plugin for ChatMonitorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_network_message::<MonitorChatMessage>()
+            .add_network_message::<MonitoredChatMessage>();
+
+        if is_server(app) {
+            app.init_resource::<ChatMonitors>()
+                .init_resource::<ModerationLog>()
+                .add_systems(Update, (handle_monitor_requests, forward_chat).chain());
+        } else {
+            app.init_resource::<ClientChatMonitor>().add_systems(
+                Update,
+                (
+                    client_request_monitor,
+                    client_receive_chat,
+                    client_chat_monitor_ui.run_if(has_window),
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Game)),
+            );
+        }
+    }
+}
+
+/// Most messages kept in the monitor window
+const MAX_MONITOR_ENTRIES: usize = 500;
+
+/// Which chat messages a monitor receives
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+struct ChatFilter {
+    /// Part of a username or character name, empty matches everyone
+    player: String,
+    local: bool,
+    ooc: bool,
+    radio: bool,
+    /// Part of the text, empty matches everything
+    keyword: String,
+}
+
+impl Default for ChatFilter {
+    fn default() -> Self {
+        Self {
+            player: String::new(),
+            local: true,
+            ooc: true,
+            radio: true,
+            keyword: String::new(),
+        }
+    }
+}
+
+impl ChatFilter {
+    fn matches(&self, chat: &MonitoredChat) -> bool {
+        let contains = |text: &str, part: &str| text.to_lowercase().contains(&part.to_lowercase());
+        let channel = match chat.kind {
+            ChatKind::Local => self.local,
+            ChatKind::Ooc => self.ooc,
+            ChatKind::Radio(_) => self.radio,
+        };
+        let player = self.player.is_empty()
+            || contains(&chat.username, &self.player)
+            || chat
+                .speaker
+                .as_deref()
+                .map_or(false, |name| contains(name, &self.player));
+        channel && player && (self.keyword.is_empty() || contains(&chat.text, &self.keyword))
+    }
+
+    fn describe(&self) -> String {
+        let channels: Vec<_> = [
+            (self.local, "local"),
+            (self.ooc, "ooc"),
+            (self.radio, "radio"),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, name)| enabled.then_some(name))
+        .collect();
+        format!(
+            "channels={} player={:?} keyword={:?}",
+            channels.join(","),
+            self.player,
+            self.keyword
+        )
+    }
+}
+
+/// Starts monitoring chat with a filter, or stops without one
+#[derive(Serialize, Deserialize)]
+struct MonitorChatMessage {
+    filter: Option<ChatFilter>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct MonitoredChat {
+    username: String,
+    speaker: Option<String>,
+    kind: ChatKind,
+    text: String,
+}
+
+/// Chat messages sent this frame that passed the filter
+#[derive(Serialize, Deserialize)]
+struct MonitoredChatMessage {
+    entries: Vec<MonitoredChat>,
+}
+
+/// The lowest role allowed to read a chat channel
+fn required_role(kind: ChatKind) -> StaffRole {
+    match kind {
+        ChatKind::Ooc => StaffRole::Mentor,
+        ChatKind::Local | ChatKind::Radio(_) => StaffRole::Admin,
+    }
+}
+
+/// Staff monitoring chat, with their role when they started and their filter
+#[derive(Resource, Default)]
+struct ChatMonitors(HashMap<ConnectionId, (StaffRole, ChatFilter)>);
+
+fn handle_monitor_requests(
+    mut messages: EventReader<MessageEvent<MonitorChatMessage>>,
+    mut monitors: ResMut<ChatMonitors>,
+    mut log: ResMut<ModerationLog>,
+    players: Res<Players>,
+    config: Res<ServerConfig>,
+) {
+    for event in messages.iter() {
+        let Some(player) = players.get(event.connection) else {
+            continue;
+        };
+        let Some(role) = config.staff_role(player.id) else {
+            warn!(player = ?player.username, "Player without permission requested the chat monitor");
+            continue;
+        };
+
+        match &event.message.filter {
+            Some(filter) => {
+                let previous = monitors.0.insert(event.connection, (role, filter.clone()));
+                let action = match previous {
+                    Some((_, previous)) if previous == *filter => continue,
+                    Some(_) => "changed their chat monitor to",
+                    None => "started monitoring chat with",
+                };
+                log.record(&format!(
+                    "{} ({}) {} {}",
+                    player.username,
+                    player.id,
+                    action,
+                    filter.describe()
+                ));
+            }
+            None => {
+                if monitors.0.remove(&event.connection).is_some() {
+                    log.record(&format!(
+                        "{} ({}) stopped monitoring chat",
+                        player.username, player.id
+                    ));
+                }
+            }
+        }
+    }
+}
+
+fn forward_chat(
+    mut chat_events: EventReader<ChatEvent>,
+    mut monitors: ResMut<ChatMonitors>,
+    players: Res<Players>,
+    mut sender: MessageSender,
+) {
+    // Monitors of staff that left stop with them
+    monitors
+        .0
+        .retain(|connection, _| players.get(*connection).is_some());
+    if monitors.0.is_empty() {
+        chat_events.clear();
+        return;
+    }
+
+    let mut batches: HashMap<ConnectionId, Vec<MonitoredChat>> = HashMap::default();
+    for event in chat_events.iter() {
+        let chat = MonitoredChat {
+            username: event.username.clone(),
+            speaker: event.speaker.clone(),
+            kind: event.kind,
+            text: event.text.clone(),
+        };
+        for (&connection, (role, filter)) in monitors.0.iter() {
+            if *role >= required_role(chat.kind) && filter.matches(&chat) {
+                batches.entry(connection).or_default().push(chat.clone());
+            }
+        }
+    }
+
+    for (connection, entries) in batches {
+        sender.send(
+            &MonitoredChatMessage { entries },
+            MessageReceivers::Single(connection),
+        );
+    }
+}
+
+#[derive(Resource, Default)]
+struct ClientChatMonitor {
+    entries: VecDeque<MonitoredChat>,
+    /// Filter being edited in the window
+    draft: ChatFilter,
+    /// Filter last sent to the server
+    applied: ChatFilter,
+}
+
+/// Tells the server when the monitor is opened, closed or gets a new filter
+fn client_request_monitor(
+    debug: Res<DebugState>,
+    monitor: Res<ClientChatMonitor>,
+    mut sent: Local<Option<Option<ChatFilter>>>,
+    mut sender: MessageSender,
+) {
+    let current = debug.chat_monitor.then(|| monitor.applied.clone());
+    if sent.as_ref() == Some(&current) || (sent.is_none() && current.is_none()) {
+        return;
+    }
+
+    sender.send_to_server(&MonitorChatMessage {
+        filter: current.clone(),
+    });
+    *sent = Some(current);
+}
+
+fn client_receive_chat(
+    mut messages: EventReader<MessageEvent<MonitoredChatMessage>>,
+    mut monitor: ResMut<ClientChatMonitor>,
+) {
+    for event in messages.iter() {
+        monitor
+            .entries
+            .extend(event.message.entries.iter().cloned());
+    }
+    let overflow = monitor.entries.len().saturating_sub(MAX_MONITOR_ENTRIES);
+    monitor.entries.drain(..overflow);
+}
+
+fn client_chat_monitor_ui(
+    mut contexts: EguiContexts,
+    mut debug: ResMut<DebugState>,
+    mut monitor: ResMut<ClientChatMonitor>,
+) {
+    if !debug.chat_monitor {
+        return;
+    }
+
+    let monitor = &mut *monitor;
+    egui::Window::new("Chat monitor")
+        .open(&mut debug.chat_monitor)
+        .default_width(400.0)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Player");
+                ui.text_edit_singleline(&mut monitor.draft.player);
+            });
+            ui.horizontal(|ui| {
+                ui.label("Keyword");
+                ui.text_edit_singleline(&mut monitor.draft.keyword);
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut monitor.draft.local, "Local");
+                ui.checkbox(&mut monitor.draft.ooc, "OOC");
+                ui.checkbox(&mut monitor.draft.radio, "Radio");
+            });
+            ui.horizontal(|ui| {
+                let changed = monitor.draft != monitor.applied;
+                if ui
+                    .add_enabled(changed, egui::Button::new("Apply filter"))
+                    .clicked()
+                {
+                    monitor.applied = monitor.draft.clone();
+                }
+                if ui.button("Clear").clicked() {
+                    monitor.entries.clear();
+                }
+            });
+            ui.small("Only what your staff role allows is shown");
+            ui.separator();
+
+            egui::ScrollArea::vertical()
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for chat in monitor.entries.iter() {
+                        let channel = match chat.kind {
+                            ChatKind::Local => "Local".to_owned(),
+                            ChatKind::Ooc => "OOC".to_owned(),
+                            ChatKind::Radio(channel) => channel_name(channel.0),
+                        };
+                        let author = match &chat.speaker {
+                            Some(speaker) => format!("{} ({})", speaker, chat.username),
+                            None => chat.username.clone(),
+                        };
+                        ui.horizontal_wrapped(|ui| {
+                            ui.label(egui::RichText::new(format!("[{}]", channel)).italics());
+                            ui.label(egui::RichText::new(author).strong());
+                            ui.label(chat.text.as_str());
+                        });
+                    }
+                });
+        });
+}
@@ -0,0 +1,127 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use networking::{
+    is_server,
+    messaging::{AppExt, MessageEvent, MessageReceivers, MessageSender},
+    Players,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::ServerConfig,
+    items::cleanup::{ItemCleanupFinished, ItemCleanupRequest},
+    ui::has_window,
+    GameState,
+};
+
+use super::{ModerationLog, StaffRole};
+
+pub(crate) struct ItemCleanupAdminPlugin;
+
+impl Plugin for ItemCleanupAdminPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_network_message::<RequestItemCleanupMessage>()
+            .add_network_message::<ItemCleanupReportMessage>();
+
+        if is_server(app) {
+            app.init_resource::<ModerationLog>()
+                .add_systems(Update, (handle_cleanup_request, report_cleanup_results));
+        } else {
+            app.init_resource::<LastCleanupReport>().add_systems(
+                Update,
+                (
+                    client_receive_report,
+                    client_cleanup_ui
+                        .run_if(has_window)
+                        .run_if(in_state(GameState::Game)),
+                )
+                    .chain(),
+            );
+        }
+    }
+}
+
+/// Asks the server to clean up loose items now
+#[derive(Serialize, Deserialize, Clone)]
+struct RequestItemCleanupMessage;
+
+#[derive(Serialize, Deserialize, Clone)]
+struct ItemCleanupReportMessage {
+    reclaimed: u32,
+    total_reclaimed: u64,
+    loose_items: u32,
+}
+
+fn handle_cleanup_request(
+    mut messages: EventReader<MessageEvent<RequestItemCleanupMessage>>,
+    mut requests: EventWriter<ItemCleanupRequest>,
+    players: Res<Players>,
+    config: Res<ServerConfig>,
+    mut log: ResMut<ModerationLog>,
+) {
+    for event in messages.iter() {
+        let Some(player) = players.get(event.connection) else {
+            continue;
+        };
+        if config.staff_role(player.id) < Some(StaffRole::Admin) {
+            warn!(player = ?player.username, "Player without permission requested item cleanup");
+            continue;
+        }
+
+        log.record(&format!("{} started an item cleanup", player.username));
+        requests.send(ItemCleanupRequest {
+            requested_by: Some(event.connection),
+        });
+    }
+}
+
+fn report_cleanup_results(
+    mut finished: EventReader<ItemCleanupFinished>,
+    mut sender: MessageSender,
+) {
+    for event in finished.iter() {
+        let Some(connection) = event.requested_by else {
+            continue;
+        };
+        sender.send(
+            &ItemCleanupReportMessage {
+                reclaimed: event.stats.last_reclaimed,
+                total_reclaimed: event.stats.total_reclaimed,
+                loose_items: event.stats.loose_items,
+            },
+            MessageReceivers::Single(connection),
+        );
+    }
+}
+
+#[derive(Resource, Default)]
+struct LastCleanupReport(Option<ItemCleanupReportMessage>);
+
+fn client_receive_report(
+    mut messages: EventReader<MessageEvent<ItemCleanupReportMessage>>,
+    mut report: ResMut<LastCleanupReport>,
+) {
+    if let Some(event) = messages.iter().last() {
+        report.0 = Some(event.message.clone());
+    }
+}
+
+fn client_cleanup_ui(
+    mut contexts: EguiContexts,
+    report: Res<LastCleanupReport>,
+    mut sender: MessageSender,
+) {
+    egui::Window::new("Item cleanup").show(contexts.ctx_mut(), |ui| {
+        if ui.button("Clean up loose items").clicked() {
+            sender.send_to_server(&RequestItemCleanupMessage);
+        }
+        if let Some(report) = report.0.as_ref() {
+            ui.label(format!("Removed {} items", report.reclaimed));
+            ui.label(format!("{} loose items remaining", report.loose_items));
+            ui.label(format!(
+                "{} items removed since server start",
+                report.total_reclaimed
+            ));
+        }
+    });
+}
@@ -0,0 +1,855 @@
+use std::path::Path;
+
+use bevy::{prelude::*, utils::Uuid};
+use bevy_egui::{egui, EguiContexts};
+use networking::{
+    bans::{unix_now, Ban, BannedPlayers},
+    is_server,
+    messaging::{AppExt, MessageEvent, MessageReceivers, MessageSender},
+    scene::NetworkSceneBundle,
+    spawning::ClientControls,
+    ConnectionId, Player, Players, ServerTask,
+};
+use serde::{Deserialize, Serialize};
+use utils::task::Tasks;
+
+use crate::{
+    ai::{NpcAssets, NpcDefinition, SpawnNpc},
+    body::Hands,
+    combat::damage::Invulnerable,
+    config::ServerConfig,
+    items::{
+        containers::{Container, MoveItem},
+        Item, ItemAssets,
+    },
+    movement::{ClientAuthoritativeTransform, ForcePositionMessage},
+    navigation::{tile_at, tile_center, TilemapNav},
+    storage::Storage,
+    ui::has_window,
+    GameState,
+};
+
+use super::{
+    bans::{ban_expiry, parse_ban_duration, save_bans},
+    ModerationLog, StaffRole,
+};
+
+pub(crate) struct AdminCommandPlugin;
+
+impl Plugin for AdminCommandPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_network_message::<AdminCommandMessage>()
+            .add_network_message::<CommandResponseMessage>();
+
+        if is_server(app) {
+            app.add_event::<RunCommand>()
+                .init_resource::<ModerationLog>()
+                .init_resource::<PendingGifts>()
+                .add_systems(
+                    Update,
+                    (
+                        handle_command_message,
+                        (
+                            run_moderation_commands,
+                            run_teleport_command,
+                            run_item_commands,
+                            run_npc_command,
+                            run_godmode_command,
+                            run_backup_commands,
+                        ),
+                    )
+                        .chain(),
+                )
+                .add_systems(Update, deliver_gifts);
+        } else {
+            app.init_resource::<ClientConsole>().add_systems(
+                Update,
+                (
+                    client_receive_responses,
+                    client_console_ui
+                        .run_if(has_window)
+                        .run_if(in_state(GameState::Game)),
+                )
+                    .chain(),
+            );
+        }
+    }
+}
+
+/// A command staff can run from the console
+struct CommandInfo {
+    name: &'static str,
+    usage: &'static str,
+    description: &'static str,
+    /// Role needed to run it, unless the server config says otherwise
+    default_role: StaffRole,
+}
+
+const COMMANDS: &[CommandInfo] = &[
+    CommandInfo {
+        name: "help",
+        usage: "help",
+        description: "Lists the commands you can use",
+        default_role: StaffRole::Mentor,
+    },
+    CommandInfo {
+        name: "kick",
+        usage: "kick <player> [reason]",
+        description: "Disconnects a player",
+        default_role: StaffRole::Admin,
+    },
+    CommandInfo {
+        name: "ban",
+        usage: "ban <player> [30m|12h|7d|perm] [reason]",
+        description: "Disconnects a player and keeps them out, permanently by default",
+        default_role: StaffRole::Admin,
+    },
+    CommandInfo {
+        name: "unban",
+        usage: "unban <username or id>",
+        description: "Lifts a ban",
+        default_role: StaffRole::Admin,
+    },
+    CommandInfo {
+        name: "bans",
+        usage: "bans",
+        description: "Lists active bans",
+        default_role: StaffRole::Admin,
+    },
+    CommandInfo {
+        name: "teleport",
+        usage: "teleport <player> (<x> <z> | <player>)",
+        description: "Moves a player to a position or to another player",
+        default_role: StaffRole::Admin,
+    },
+    CommandInfo {
+        name: "spawn",
+        usage: "spawn <item> [count]",
+        description: "Spawns items at your feet",
+        default_role: StaffRole::Admin,
+    },
+    CommandInfo {
+        name: "npc",
+        usage: "npc <npc> [count]",
+        description: "Spawns NPCs at your feet",
+        default_role: StaffRole::Admin,
+    },
+    CommandInfo {
+        name: "give",
+        usage: "give <player> <item>",
+        description: "Puts an item in the hand of a player",
+        default_role: StaffRole::Admin,
+    },
+    CommandInfo {
+        name: "godmode",
+        usage: "godmode <player>",
+        description: "Makes a player's creature immune to damage, or vulnerable again",
+        default_role: StaffRole::Admin,
+    },
+    CommandInfo {
+        name: "backup",
+        usage: "backup",
+        description: "Backs up bans, characters and map wear now",
+        default_role: StaffRole::Admin,
+    },
+    CommandInfo {
+        name: "backups",
+        usage: "backups",
+        description: "Lists backups, newest first",
+        default_role: StaffRole::Admin,
+    },
+    CommandInfo {
+        name: "exportbackup",
+        usage: "exportbackup [backup]",
+        description: "Copies a backup, the newest by default, where it is kept",
+        default_role: StaffRole::Admin,
+    },
+];
+
+/// Most items a single spawn command creates
+const MAX_SPAWN_COUNT: u32 = 50;
+
+impl ServerConfig {
+    /// If the player's staff role allows running a command
+    pub(crate) fn can_run_command(&self, player: Uuid, command: &str) -> bool {
+        let required = self.staff.command_roles.get(command).copied().or_else(|| {
+            COMMANDS
+                .iter()
+                .find(|c| c.name == command)
+                .map(|c| c.default_role)
+        });
+        match required {
+            Some(role) => self.staff_role(player) >= Some(role),
+            None => false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct AdminCommandMessage {
+    line: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct CommandResponseMessage {
+    /// The command line this responds to
+    line: String,
+    result: Result<String, String>,
+}
+
+#[derive(Clone, Debug)]
+enum Destination {
+    Position(Vec2),
+    Player(String),
+}
+
+#[derive(Clone, Debug)]
+enum AdminCommand {
+    Help,
+    Kick {
+        player: String,
+        reason: String,
+    },
+    Ban {
+        player: String,
+        /// Seconds, permanent if `None`
+        duration: Option<u64>,
+        reason: String,
+    },
+    Unban {
+        user: String,
+    },
+    Bans,
+    Teleport {
+        player: String,
+        to: Destination,
+    },
+    Spawn {
+        item: String,
+        count: u32,
+    },
+    Give {
+        player: String,
+        item: String,
+    },
+    Npc {
+        npc: String,
+        count: u32,
+    },
+    GodMode {
+        player: String,
+    },
+    Backup,
+    Backups,
+    ExportBackup {
+        /// The newest backup if not set
+        backup: Option<String>,
+    },
+}
+
+impl AdminCommand {
+    fn parse(line: &str) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+        let name = words.next().ok_or("Empty command")?;
+        let args: Vec<_> = words.collect();
+        let info = COMMANDS
+            .iter()
+            .find(|c| c.name == name)
+            .ok_or_else(|| format!("Unknown command \"{}\", try \"help\"", name))?;
+        let usage = || format!("Usage: {}", info.usage);
+        let rest = |from: usize| args.get(from..).unwrap_or_default().join(" ");
+
+        Ok(match (name, args.as_slice()) {
+            ("help", []) => Self::Help,
+            ("kick", [player, ..]) => Self::Kick {
+                player: player.to_string(),
+                reason: rest(1),
+            },
+            ("ban", [player, length, ..]) if parse_ban_duration(length).is_some() => Self::Ban {
+                player: player.to_string(),
+                duration: parse_ban_duration(length).flatten(),
+                reason: rest(2),
+            },
+            ("ban", [player, ..]) => Self::Ban {
+                player: player.to_string(),
+                duration: None,
+                reason: rest(1),
+            },
+            ("unban", [user]) => Self::Unban {
+                user: user.to_string(),
+            },
+            ("bans", []) => Self::Bans,
+            ("teleport", [player, target]) => Self::Teleport {
+                player: player.to_string(),
+                to: Destination::Player(target.to_string()),
+            },
+            ("teleport", [player, x, z]) => Self::Teleport {
+                player: player.to_string(),
+                to: Destination::Position(Vec2::new(
+                    x.parse().map_err(|_| usage())?,
+                    z.parse().map_err(|_| usage())?,
+                )),
+            },
+            ("spawn", [item]) => Self::Spawn {
+                item: item.to_string(),
+                count: 1,
+            },
+            ("spawn", [item, count]) => Self::Spawn {
+                item: item.to_string(),
+                count: count
+                    .parse()
+                    .ok()
+                    .filter(|c| (1..=MAX_SPAWN_COUNT).contains(c))
+                    .ok_or_else(|| format!("Count must be between 1 and {}", MAX_SPAWN_COUNT))?,
+            },
+            ("npc", [npc]) => Self::Npc {
+                npc: npc.to_string(),
+                count: 1,
+            },
+            ("npc", [npc, count]) => Self::Npc {
+                npc: npc.to_string(),
+                count: count
+                    .parse()
+                    .ok()
+                    .filter(|c| (1..=MAX_SPAWN_COUNT).contains(c))
+                    .ok_or_else(|| format!("Count must be between 1 and {}", MAX_SPAWN_COUNT))?,
+            },
+            ("give", [player, item]) => Self::Give {
+                player: player.to_string(),
+                item: item.to_string(),
+            },
+            ("godmode", [player]) => Self::GodMode {
+                player: player.to_string(),
+            },
+            ("backup", []) => Self::Backup,
+            ("backups", []) => Self::Backups,
+            ("exportbackup", []) => Self::ExportBackup { backup: None },
+            ("exportbackup", [backup]) => Self::ExportBackup {
+                backup: Some(backup.to_string()),
+            },
+            _ => return Err(usage()),
+        })
+    }
+}
+
+/// A parsed command from staff that is allowed to run it
+#[derive(Event)]
+struct RunCommand {
+    connection: ConnectionId,
+    line: String,
+    command: AdminCommand,
+}
+
+impl RunCommand {
+    fn respond(&self, sender: &mut MessageSender, result: Result<String, String>) {
+        sender.send(
+            &CommandResponseMessage {
+                line: self.line.clone(),
+                result,
+            },
+            MessageReceivers::Single(self.connection),
+        );
+    }
+}
+
+fn find_player<'a>(players: &'a Players, name: &str) -> Result<(ConnectionId, &'a Player), String> {
+    players
+        .players()
+        .iter()
+        .find(|(_, p)| p.username.eq_ignore_ascii_case(name))
+        .map(|(c, p)| (*c, p))
+        .ok_or_else(|| format!("No player named \"{}\" is connected", name))
+}
+
+fn handle_command_message(
+    mut messages: EventReader<MessageEvent<AdminCommandMessage>>,
+    mut runs: EventWriter<RunCommand>,
+    players: Res<Players>,
+    config: Res<ServerConfig>,
+    mut log: ResMut<ModerationLog>,
+    mut sender: MessageSender,
+) {
+    for event in messages.iter() {
+        let Some(player) = players.get(event.connection) else {
+            continue;
+        };
+        if config.staff_role(player.id).is_none() {
+            warn!(player = ?player.username, "Player without permission tried to run a command");
+            continue;
+        }
+
+        let line = event.message.line.trim().to_owned();
+        let name = line.split_whitespace().next().unwrap_or_default();
+        let result = AdminCommand::parse(&line).and_then(|command| {
+            if config.can_run_command(player.id, name) {
+                Ok(command)
+            } else {
+                Err(format!("You are not allowed to use \"{}\"", name))
+            }
+        });
+        match result {
+            Ok(command) => {
+                if !matches!(command, AdminCommand::Help) {
+                    log.record(&format!("{} ran command: {}", player.username, line));
+                }
+                runs.send(RunCommand {
+                    connection: event.connection,
+                    line,
+                    command,
+                });
+            }
+            Err(err) => sender.send(
+                &CommandResponseMessage {
+                    line,
+                    result: Err(err),
+                },
+                MessageReceivers::Single(event.connection),
+            ),
+        }
+    }
+}
+
+fn run_moderation_commands(
+    mut runs: EventReader<RunCommand>,
+    players: Res<Players>,
+    mut banned: ResMut<BannedPlayers>,
+    mut tasks: EventWriter<ServerTask>,
+    config: Res<ServerConfig>,
+    mut sender: MessageSender,
+) {
+    for run in runs.iter() {
+        // Staff can only be removed by someone of at least the same rank
+        let find_target = |name: &str| {
+            let issuer_role = players
+                .get(run.connection)
+                .and_then(|p| config.staff_role(p.id));
+            find_player(&players, name).and_then(|(connection, target)| {
+                if config.staff_role(target.id) > issuer_role {
+                    Err(format!("{} outranks you", target.username))
+                } else {
+                    Ok((connection, target))
+                }
+            })
+        };
+        let result = match &run.command {
+            AdminCommand::Help => {
+                let issuer = players.get(run.connection).map(|p| p.id);
+                let lines: Vec<_> = COMMANDS
+                    .iter()
+                    .filter(|c| issuer.map_or(false, |id| config.can_run_command(id, c.name)))
+                    .map(|c| format!("{} - {}", c.usage, c.description))
+                    .collect();
+                Ok(lines.join("\n"))
+            }
+            AdminCommand::Kick { player, reason } => {
+                find_target(player).map(|(connection, target)| {
+                    tasks.send(ServerTask::Kick {
+                        connection,
+                        reason: reason.clone(),
+                    });
+                    format!("Kicked {}", target.username)
+                })
+            }
+            AdminCommand::Ban {
+                player,
+                duration,
+                reason,
+            } => find_target(player).map(|(connection, target)| {
+                banned.ban(Ban {
+                    id: target.id,
+                    username: target.username.clone(),
+                    address: target.address,
+                    reason: reason.clone(),
+                    expires: ban_expiry(*duration),
+                });
+                save_bans(&banned, Some(&config));
+                tasks.send(ServerTask::Kick {
+                    connection,
+                    reason: reason.clone(),
+                });
+                match duration {
+                    Some(seconds) => {
+                        format!("Banned {} for {} minutes", target.username, seconds / 60)
+                    }
+                    None => format!("Banned {} permanently", target.username),
+                }
+            }),
+            AdminCommand::Unban { user } => match banned.unban(user) {
+                Some(ban) => {
+                    save_bans(&banned, Some(&config));
+                    Ok(format!("Unbanned {}", ban.username))
+                }
+                None => Err(format!("No ban found for \"{}\"", user)),
+            },
+            AdminCommand::Bans => {
+                let now = unix_now();
+                let lines: Vec<_> = banned
+                    .bans()
+                    .iter()
+                    .filter(|ban| !ban.is_expired(now))
+                    .map(|ban| {
+                        let length = match ban.expires {
+                            Some(expires) => format!("{} minutes left", (expires - now) / 60),
+                            None => "permanent".to_owned(),
+                        };
+                        format!("{} ({}) - {}: {}", ban.username, ban.id, length, ban.reason)
+                    })
+                    .collect();
+                Ok(if lines.is_empty() {
+                    "No active bans".to_owned()
+                } else {
+                    lines.join("\n")
+                })
+            }
+            _ => continue,
+        };
+        run.respond(&mut sender, result);
+    }
+}
+
+/// How many tiles around a teleport position are searched for free space
+const TELEPORT_SEARCH_DISTANCE: u32 = 3;
+
+fn run_teleport_command(
+    mut runs: EventReader<RunCommand>,
+    players: Res<Players>,
+    controls: Res<ClientControls>,
+    mut transforms: Query<(&mut Transform, Option<&mut ClientAuthoritativeTransform>)>,
+    nav: Res<TilemapNav>,
+    mut sender: MessageSender,
+) {
+    for run in runs.iter() {
+        let AdminCommand::Teleport { player, to } = &run.command else {
+            continue;
+        };
+
+        let creature_of = |name: &str| {
+            find_player(&players, name).and_then(|(_, target)| {
+                controls
+                    .controlled_entity(target.id)
+                    .ok_or_else(|| format!("{} is not controlling a creature", target.username))
+            })
+        };
+        let result = creature_of(player)
+            .and_then(|creature| {
+                let destination = match to {
+                    Destination::Position(position) => {
+                        let height = transforms
+                            .get(creature)
+                            .map(|(t, _)| t.translation.y)
+                            .unwrap_or_default();
+                        let requested = Vec3::new(position.x, height, position.y);
+                        // Avoid putting them inside a wall
+                        tile_at(requested)
+                            .and_then(|tile| nav.nearest_walkable(tile, TELEPORT_SEARCH_DISTANCE))
+                            .map_or(requested, |tile| tile_center(tile) + Vec3::Y * height)
+                    }
+                    Destination::Player(other) => {
+                        let other = creature_of(other)?;
+                        transforms
+                            .get(other)
+                            .map(|(t, _)| t.translation)
+                            .map_err(|_| "Destination creature has no position".to_owned())?
+                    }
+                };
+                Ok((creature, destination))
+            })
+            .and_then(|(creature, destination)| {
+                let (mut transform, authoritative) = transforms
+                    .get_mut(creature)
+                    .map_err(|_| "Creature has no position".to_owned())?;
+                transform.translation = destination;
+                // The client decides where the creature it controls is, so it has to be told
+                if let Some(mut authoritative) = authoritative {
+                    authoritative.position = destination;
+                }
+                let connection = controls
+                    .controlling_player(creature)
+                    .and_then(|id| players.get_connection(&id));
+                if let Some(connection) = connection {
+                    sender.send_with_priority(
+                        &ForcePositionMessage {
+                            position: destination,
+                            rotation: transform.rotation,
+                        },
+                        MessageReceivers::Single(connection),
+                        10,
+                    );
+                }
+                Ok(format!("Teleported {} to {}", player, destination))
+            });
+        run.respond(&mut sender, result);
+    }
+}
+
+/// Given items that still need to be put into a hand once their scene has spawned
+#[derive(Resource, Default)]
+struct PendingGifts(Vec<(Entity, Entity)>);
+
+#[allow(clippy::too_many_arguments)]
+fn run_item_commands(
+    mut runs: EventReader<RunCommand>,
+    players: Res<Players>,
+    controls: Res<ClientControls>,
+    transforms: Query<&GlobalTransform>,
+    assets: Res<ItemAssets>,
+    server: Res<AssetServer>,
+    mut gifts: ResMut<PendingGifts>,
+    mut commands: Commands,
+    mut sender: MessageSender,
+) {
+    for run in runs.iter() {
+        let (receiver, item, count) = match &run.command {
+            AdminCommand::Spawn { item, count } => (None, item, *count),
+            AdminCommand::Give { player, item } => (Some(player.as_str()), item, 1),
+            _ => continue,
+        };
+
+        let path = format!("items/{}.scn.ron", item);
+        let exists = assets
+            .definitions
+            .iter()
+            .filter_map(|h| server.get_handle_path(h))
+            .any(|p| p.path() == Path::new(&path));
+        let creature = match receiver {
+            Some(name) => find_player(&players, name).map(|(_, p)| p.id),
+            None => players
+                .get(run.connection)
+                .map(|p| p.id)
+                .ok_or_else(|| "You are not connected".to_owned()),
+        }
+        .and_then(|id| {
+            controls
+                .controlled_entity(id)
+                .ok_or_else(|| "Player is not controlling a creature".to_owned())
+        });
+        let result = match (exists, creature) {
+            (false, _) => Err(format!("No item called \"{}\"", item)),
+            (_, Err(err)) => Err(err),
+            (true, Ok(creature)) => {
+                let position = transforms
+                    .get(creature)
+                    .map(|t| t.translation())
+                    .unwrap_or_default();
+                for _ in 0..count {
+                    let entity = commands
+                        .spawn(NetworkSceneBundle {
+                            scene: server.load(path.as_str()).into(),
+                            transform: Transform::from_translation(position + Vec3::Y * 0.5),
+                            ..Default::default()
+                        })
+                        .id();
+                    if receiver.is_some() {
+                        gifts.0.push((entity, creature));
+                    }
+                }
+                Ok(format!("Spawned {} {}", count, item))
+            }
+        };
+        run.respond(&mut sender, result);
+    }
+}
+
+fn run_npc_command(
+    mut runs: EventReader<RunCommand>,
+    players: Res<Players>,
+    controls: Res<ClientControls>,
+    transforms: Query<&GlobalTransform>,
+    npc_assets: Option<Res<NpcAssets>>,
+    definitions: Res<Assets<NpcDefinition>>,
+    mut spawns: EventWriter<SpawnNpc>,
+    mut sender: MessageSender,
+) {
+    for run in runs.iter() {
+        let AdminCommand::Npc { npc, count } = &run.command else {
+            continue;
+        };
+
+        let exists = npc_assets
+            .as_ref()
+            .map_or(false, |assets| assets.get(npc, &definitions).is_some());
+        let creature = players
+            .get(run.connection)
+            .ok_or_else(|| "You are not connected".to_owned())
+            .and_then(|player| {
+                controls
+                    .controlled_entity(player.id)
+                    .ok_or_else(|| "You are not controlling a creature".to_owned())
+            });
+        let result = match (exists, creature) {
+            (false, _) => Err(format!("No NPC called \"{}\"", npc)),
+            (_, Err(err)) => Err(err),
+            (true, Ok(creature)) => {
+                let position = transforms
+                    .get(creature)
+                    .map(|t| t.translation())
+                    .unwrap_or_default();
+                for _ in 0..*count {
+                    spawns.send(SpawnNpc {
+                        npc: npc.clone(),
+                        position: position + Vec3::Y * 0.5,
+                    });
+                }
+                Ok(format!("Spawned {} {}", count, npc))
+            }
+        };
+        run.respond(&mut sender, result);
+    }
+}
+
+fn run_godmode_command(
+    mut runs: EventReader<RunCommand>,
+    players: Res<Players>,
+    controls: Res<ClientControls>,
+    invulnerable: Query<(), With<Invulnerable>>,
+    mut commands: Commands,
+    mut sender: MessageSender,
+) {
+    for run in runs.iter() {
+        let AdminCommand::GodMode { player } = &run.command else {
+            continue;
+        };
+
+        let result = find_player(&players, player).and_then(|(_, target)| {
+            let creature = controls
+                .controlled_entity(target.id)
+                .ok_or_else(|| format!("{} is not controlling a creature", target.username))?;
+            Ok(if invulnerable.contains(creature) {
+                commands.entity(creature).remove::<Invulnerable>();
+                format!("{} can be hurt again", target.username)
+            } else {
+                commands.entity(creature).insert(Invulnerable);
+                format!("{} is now invulnerable", target.username)
+            })
+        });
+        run.respond(&mut sender, result);
+    }
+}
+
+/// Moves given items into the active hand of their receiver, if it's free
+fn deliver_gifts(
+    mut gifts: ResMut<PendingGifts>,
+    items: Query<(), With<Item>>,
+    hands: Query<&Hands>,
+    containers: Query<&Container>,
+    mut item_moves: ResMut<Tasks<MoveItem>>,
+) {
+    gifts.0.retain(|&(item, creature)| {
+        if items.get(item).is_err() {
+            // Wait for the scene to spawn
+            return true;
+        }
+        let hand = hands.get(creature).map(|h| h.active_hand());
+        if let Ok(hand) = hand {
+            if containers.get(hand).map_or(false, |c| c.is_empty()) {
+                item_moves.create(MoveItem {
+                    item,
+                    container: Some(hand),
+                    position: Some(UVec2::ZERO),
+                });
+            }
+        }
+        false
+    });
+}
+
+/// Lines of the console, with whether they were errors
+#[derive(Resource, Default)]
+struct ClientConsole {
+    input: String,
+    history: Vec<(String, bool)>,
+}
+
+fn client_receive_responses(
+    mut messages: EventReader<MessageEvent<CommandResponseMessage>>,
+    mut console: ResMut<ClientConsole>,
+) {
+    for event in messages.iter() {
+        let response = &event.message;
+        console
+            .history
+            .push((format!("> {}", response.line), false));
+        match &response.result {
+            Ok(text) => console.history.push((text.clone(), false)),
+            Err(text) => console.history.push((text.clone(), true)),
+        }
+    }
+}
+
+fn client_console_ui(
+    mut contexts: EguiContexts,
+    mut console: ResMut<ClientConsole>,
+    mut sender: MessageSender,
+) {
+    egui::Window::new("Console")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(200.0)
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for (line, error) in console.history.iter() {
+                        let text = egui::RichText::new(line).monospace();
+                        ui.label(if *error {
+                            text.color(egui::Color32::RED)
+                        } else {
+                            text
+                        });
+                    }
+                });
+
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut console.input)
+                    .hint_text("Command, try \"help\"")
+                    .font(egui::TextStyle::Monospace),
+            );
+            if response.lost_focus()
+                && ui.input(|i| i.key_pressed(egui::Key::Enter))
+                && !console.input.trim().is_empty()
+            {
+                sender.send_to_server(&AdminCommandMessage {
+                    line: std::mem::take(&mut console.input),
+                });
+                response.request_focus();
+            }
+        });
+}
+
+fn run_backup_commands(
+    mut runs: EventReader<RunCommand>,
+    storage: Res<Storage>,
+    mut sender: MessageSender,
+) {
+    for run in runs.iter() {
+        let result = match &run.command {
+            AdminCommand::Backup => storage
+                .backup()
+                .map(|name| format!("Created backup {}", name))
+                .map_err(|err| format!("Backup failed: {}", err)),
+            AdminCommand::Backups => {
+                let now = unix_now();
+                let lines: Vec<_> = storage
+                    .backups()
+                    .into_iter()
+                    .map(|name| {
+                        let age = name
+                            .parse::<u64>()
+                            .map_or(0, |made| now.saturating_sub(made));
+                        format!("{} - {} minutes ago", name, age / 60)
+                    })
+                    .collect();
+                Ok(if lines.is_empty() {
+                    "No backups".to_owned()
+                } else {
+                    lines.join("\n")
+                })
+            }
+            AdminCommand::ExportBackup { backup } => storage
+                .export(backup.as_deref())
+                .map(|path| format!("Exported backup to {}", path.display()))
+                .map_err(|err| format!("Export failed: {}", err)),
+            _ => continue,
+        };
+        run.respond(&mut sender, result);
+    }
+}
@@ -0,0 +1,219 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use maps::TileEntity;
+use networking::{
+    identity::{NetworkIdentities, NetworkIdentity},
+    is_server,
+    messaging::{AppExt, MessageEvent, MessageReceivers, MessageSender},
+    Players,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    body::Body, config::ServerConfig, items::Item, round::RoundState, ui::has_window, GameState,
+};
+
+use super::StaffRole;
+
+pub(crate) struct EntityCensusPlugin;
+
+impl Plugin for EntityCensusPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_network_message::<RequestEntityCensusMessage>()
+            .add_network_message::<EntityCensusReportMessage>();
+
+        if is_server(app) {
+            app.init_resource::<EntityCensus>()
+                .add_systems(Update, (count_entities, handle_census_request).chain());
+        } else {
+            app.init_resource::<LastCensusReport>().add_systems(
+                Update,
+                (
+                    client_receive_census,
+                    client_census_ui
+                        .run_if(has_window)
+                        .run_if(in_state(GameState::Game)),
+                )
+                    .chain(),
+            );
+        }
+    }
+}
+
+/// Seconds between entity counts, walking the whole world every frame is wasteful
+const CENSUS_INTERVAL: f32 = 10.0;
+
+/// Number of entities grouped by what they are
+#[derive(Serialize, Deserialize, Clone, Copy, Default, Debug)]
+struct EntityCounts {
+    total: u32,
+    tile_entities: u32,
+    items: u32,
+    creatures: u32,
+    networked: u32,
+    /// Networked entities whose parent was despawned without them,
+    /// and registered identities of entities that no longer exist.
+    orphaned: u32,
+}
+
+impl EntityCounts {
+    fn rows(&self) -> [(&'static str, u32); 6] {
+        [
+            ("Total", self.total),
+            ("Tile entities", self.tile_entities),
+            ("Items", self.items),
+            ("Creatures", self.creatures),
+            ("Networked", self.networked),
+            ("Orphaned networked", self.orphaned),
+        ]
+    }
+}
+
+/// Periodic entity counts to spot entities that are never cleaned up
+#[derive(Resource, Default)]
+struct EntityCensus {
+    current: EntityCounts,
+    /// Counts when the current round started
+    round_start: Option<EntityCounts>,
+    last_count: Option<f32>,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn count_entities(
+    all: Query<Entity>,
+    tile_entities: Query<(), With<TileEntity>>,
+    items: Query<(), With<Item>>,
+    creatures: Query<(), With<Body>>,
+    networked: Query<Option<&Parent>, With<NetworkIdentity>>,
+    identities: Res<NetworkIdentities>,
+    round_state: Res<State<RoundState>>,
+    mut census: ResMut<EntityCensus>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    let round_started = round_state.is_changed() && *round_state.get() == RoundState::InProgress;
+    let due = census
+        .last_count
+        .map_or(true, |last| last + CENSUS_INTERVAL <= now);
+    if !due && !round_started {
+        return;
+    }
+    census.last_count = Some(now);
+
+    let dangling_children = networked
+        .iter()
+        .flatten()
+        .filter(|parent| !all.contains(parent.get()))
+        .count();
+    let stale_identities = identities
+        .entities()
+        .filter(|&entity| !all.contains(entity))
+        .count();
+    let counts = EntityCounts {
+        total: all.iter().len() as u32,
+        tile_entities: tile_entities.iter().len() as u32,
+        items: items.iter().len() as u32,
+        creatures: creatures.iter().len() as u32,
+        networked: networked.iter().len() as u32,
+        orphaned: (dangling_children + stale_identities) as u32,
+    };
+
+    if counts.orphaned > census.current.orphaned {
+        warn!(
+            orphaned = counts.orphaned,
+            "Number of orphaned networked entities increased"
+        );
+    }
+    census.current = counts;
+    if round_started {
+        census.round_start = Some(counts);
+    }
+}
+
+/// Asks the server for the latest entity counts
+#[derive(Serialize, Deserialize, Clone)]
+struct RequestEntityCensusMessage;
+
+#[derive(Serialize, Deserialize, Clone)]
+struct EntityCensusReportMessage {
+    current: EntityCounts,
+    round_start: Option<EntityCounts>,
+}
+
+fn handle_census_request(
+    mut messages: EventReader<MessageEvent<RequestEntityCensusMessage>>,
+    players: Res<Players>,
+    config: Res<ServerConfig>,
+    census: Res<EntityCensus>,
+    mut sender: MessageSender,
+) {
+    for event in messages.iter() {
+        let Some(player) = players.get(event.connection) else {
+            continue;
+        };
+        if config.staff_role(player.id) < Some(StaffRole::Admin) {
+            warn!(player = ?player.username, "Player without permission requested entity counts");
+            continue;
+        }
+
+        sender.send(
+            &EntityCensusReportMessage {
+                current: census.current,
+                round_start: census.round_start,
+            },
+            MessageReceivers::Single(event.connection),
+        );
+    }
+}
+
+#[derive(Resource, Default)]
+struct LastCensusReport(Option<EntityCensusReportMessage>);
+
+fn client_receive_census(
+    mut messages: EventReader<MessageEvent<EntityCensusReportMessage>>,
+    mut report: ResMut<LastCensusReport>,
+) {
+    if let Some(event) = messages.iter().last() {
+        report.0 = Some(event.message.clone());
+    }
+}
+
+fn client_census_ui(
+    mut contexts: EguiContexts,
+    report: Res<LastCensusReport>,
+    mut sender: MessageSender,
+) {
+    egui::Window::new("Entity counts").show(contexts.ctx_mut(), |ui| {
+        if ui.button("Refresh").clicked() {
+            sender.send_to_server(&RequestEntityCensusMessage);
+        }
+        let Some(report) = report.0.as_ref() else {
+            return;
+        };
+
+        egui::Grid::new("entity counts")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("");
+                ui.label("Now");
+                ui.label("This round");
+                ui.end_row();
+
+                let start_rows = report.round_start.as_ref().map(|start| start.rows());
+                for (index, (name, count)) in report.current.rows().into_iter().enumerate() {
+                    ui.label(name);
+                    ui.label(count.to_string());
+                    match start_rows {
+                        Some(start) => {
+                            let delta = count as i64 - start[index].1 as i64;
+                            ui.label(format!("{:+}", delta));
+                        }
+                        None => {
+                            ui.label("-");
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+    });
+}
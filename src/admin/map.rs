@@ -1,21 +1,60 @@
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use bevy::prelude::*;
+use bevy::{asset::FileAssetIo, prelude::*};
 use bevy_egui::*;
-use maps::TileMap;
+use maps::{
+    io::{MapFile, MAP_FILE_EXTENSION},
+    journal::{TileJournal, TileMapPatch},
+    TileMap,
+};
 use networking::{
     messaging::{AppExt, MessageEvent, MessageSender},
-    NetworkManager,
+    ConnectionId, NetworkManager, Players,
 };
 use serde::{Deserialize, Serialize};
 
-use crate::{ui::has_window, GameState};
+use crate::{config::ServerConfig, ui::has_window, GameState};
+
+use super::StaffRole;
 
 #[derive(Serialize, Deserialize, Clone)]
 struct ChangeMapMessage {
     name: String,
 }
 
+/// Asks the server to save all tile changes as a patch for the current map
+#[derive(Serialize, Deserialize, Clone)]
+struct ExportMapPatchMessage;
+
+/// Asks the server to save the current map in the native map format
+#[derive(Serialize, Deserialize, Clone)]
+struct SaveMapMessage;
+
+/// Folder containing patches that are applied when their map is loaded
+const PATCH_FOLDER: &str = "map-patches";
+const AUTOSAVE_FOLDER: &str = "autosave";
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// How many autosaves are kept per map
+const AUTOSAVE_SLOTS: u32 = 3;
+
+#[derive(Resource)]
+struct MapAutosave {
+    timer: Timer,
+    next_slot: u32,
+}
+
+impl Default for MapAutosave {
+    fn default() -> Self {
+        Self {
+            timer: Timer::new(AUTOSAVE_INTERVAL, TimerMode::Repeating),
+            next_slot: 0,
+        }
+    }
+}
+
 fn client_map_selection_ui(mut contexts: EguiContexts, mut sender: MessageSender) {
     egui::Window::new("Load map").show(contexts.ctx_mut(), |ui| {
         for &map_name in ["DeltaStation2", "BoxStation", "MetaStation"].iter() {
@@ -25,16 +64,70 @@ fn client_map_selection_ui(mut contexts: EguiContexts, mut sender: MessageSender
                 });
             }
         }
+        ui.separator();
+        if ui.button("Save map changes").clicked() {
+            sender.send_to_server(&ExportMapPatchMessage);
+        }
+        if ui.button("Save as native map").clicked() {
+            sender.send_to_server(&SaveMapMessage);
+        }
     });
 }
 
+fn is_admin(players: &Players, config: &ServerConfig, connection: ConnectionId) -> bool {
+    let allowed = players
+        .get(connection)
+        .map_or(false, |p| config.staff_role(p.id) >= Some(StaffRole::Admin));
+    if !allowed {
+        warn!(connection = ?connection, "Player without permission tried to manage the map");
+    }
+    allowed
+}
+
+/// Run condition for map tools that take no arguments
+fn admin_sent<T: Send + Sync + 'static>(
+    mut messages: EventReader<MessageEvent<T>>,
+    players: Res<Players>,
+    config: Res<ServerConfig>,
+) -> bool {
+    let mut sent = false;
+    for event in messages.iter() {
+        sent |= is_admin(&players, &config, event.connection);
+    }
+    sent
+}
+
+/// Map names may only contain ASCII letters, digits, underscores and dashes
+fn is_valid_map_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
 fn map_loader_system(
     mut messages: EventReader<MessageEvent<ChangeMapMessage>>,
     mut commands: Commands,
     server: Res<AssetServer>,
     tilemaps: Query<Entity, With<TileMap>>,
+    mut journal: ResMut<TileJournal>,
+    players: Res<Players>,
+    config: Res<ServerConfig>,
 ) {
-    let message = &messages.iter().last().unwrap().message;
+    let Some(message) = messages
+        .iter()
+        .filter(|e| is_admin(&players, &config, e.connection))
+        .last()
+        .map(|e| &e.message)
+    else {
+        return;
+    };
+
+    // The name ends up in file paths for loading and saving the map
+    if !is_valid_map_name(&message.name) {
+        warn!(name = ?message.name, "Rejected invalid map name");
+        return;
+    }
 
     // Delete existing maps
     for entity in tilemaps.iter() {
@@ -42,19 +135,141 @@ fn map_loader_system(
     }
 
     // Add new map to load
-    // Note: you better not send me any path traversal >:/
-    let handle = server.load(PathBuf::from(format!("maps/{}.dmm", message.name)));
-    commands.insert_resource(crate::Map {
-        handle,
-        spawned: false,
-    });
+    commands.insert_resource(crate::Map::load(&server, &message.name));
+    journal.reset(message.name.as_str());
+}
+
+fn write_patch(path: &Path, patch: &TileMapPatch) -> std::io::Result<()> {
+    let text =
+        toml::to_string(patch).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, text)
+}
+
+fn patch_path(map_name: &str) -> PathBuf {
+    PathBuf::from(format!("{}/{}.patch.toml", PATCH_FOLDER, map_name))
+}
+
+fn export_map_patch(journal: Res<TileJournal>) {
+    let Some(map_name) = journal.base_map.as_deref() else {
+        warn!("Can't export map changes without a loaded map");
+        return;
+    };
+
+    let path = patch_path(map_name);
+    match write_patch(&path, &journal.export()) {
+        Ok(()) => info!(path = ?path, "Exported map changes"),
+        Err(err) => error!(path = ?path, error = %err, "Failed to export map changes"),
+    }
+}
+
+/// Periodically saves tile changes to a rotating set of files
+fn autosave_map_changes(
+    mut autosave: ResMut<MapAutosave>,
+    mut journal: ResMut<TileJournal>,
+    time: Res<Time>,
+) {
+    if !autosave.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let Some(map_name) = journal.base_map.clone() else {
+        return;
+    };
+    if !journal.take_dirty() {
+        return;
+    }
+
+    let slot = autosave.next_slot;
+    autosave.next_slot = (slot + 1) % AUTOSAVE_SLOTS;
+    let path = PathBuf::from(format!(
+        "{}/{}-patch-{}.toml",
+        AUTOSAVE_FOLDER, map_name, slot
+    ));
+    match write_patch(&path, &journal.export()) {
+        Ok(()) => debug!(path = ?path, "Autosaved map changes"),
+        Err(err) => error!(path = ?path, error = %err, "Failed to autosave map changes"),
+    }
+}
+
+/// Writes the current tilemap to the assets folder, where it's preferred over the TGM map
+fn save_native_map(world: &mut World) {
+    let Some(map_name) = world.resource::<TileJournal>().base_map.clone() else {
+        warn!("Can't save map without a loaded map");
+        return;
+    };
+    let Some(tilemap) = world
+        .query_filtered::<Entity, With<TileMap>>()
+        .iter(world)
+        .next()
+    else {
+        return;
+    };
+    let Some(map_file) = MapFile::from_world(world, tilemap) else {
+        return;
+    };
+
+    // Write next to the maps the asset server reads from, not relative to the working directory
+    let Some(root) = world
+        .resource::<AssetServer>()
+        .asset_io()
+        .downcast_ref::<FileAssetIo>()
+        .map(|io| io.root_path().clone())
+    else {
+        warn!("Can't save map when assets aren't loaded from the filesystem");
+        return;
+    };
+    let path = root.join(format!("maps/{}.{}", map_name, MAP_FILE_EXTENSION));
+    match map_file.save(&path) {
+        Ok(()) => {
+            info!(path = ?path, tiles = map_file.tiles.len(), "Saved native map");
+            // The changes are part of the saved map now
+            world.resource_mut::<TileJournal>().reset(map_name);
+        }
+        Err(err) => error!(path = ?path, error = %err, "Failed to save native map"),
+    }
+}
+
+/// Applies a previously exported patch when its map is spawned
+fn apply_saved_patch(
+    maps: Query<(Entity, &TileMap), Added<TileMap>>,
+    journal: Res<TileJournal>,
+    mut commands: Commands,
+) {
+    let Some(map_name) = journal.base_map.as_deref() else {
+        return;
+    };
+
+    for (entity, map) in maps.iter() {
+        let path = patch_path(map_name);
+        let Ok(text) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let patch: TileMapPatch = match toml::from_str(&text) {
+            Ok(p) => p,
+            Err(err) => {
+                error!(path = ?path, error = %err, "Failed to parse map patch");
+                continue;
+            }
+        };
+        if patch.base_map != map_name {
+            warn!(path = ?path, base_map = ?patch.base_map, "Map patch is for a different map");
+            continue;
+        }
+
+        patch.apply(entity, map, &mut commands);
+        info!(path = ?path, changes = patch.entries.len(), "Applied map patch");
+    }
 }
 
 pub struct MapManagementPlugin;
 
 impl Plugin for MapManagementPlugin {
     fn build(&self, app: &mut App) {
-        app.add_network_message::<ChangeMapMessage>();
+        app.add_network_message::<ChangeMapMessage>()
+            .add_network_message::<ExportMapPatchMessage>()
+            .add_network_message::<SaveMapMessage>();
 
         if app
             .world
@@ -62,9 +277,15 @@ impl Plugin for MapManagementPlugin {
             .unwrap()
             .is_server()
         {
-            app.add_systems(
+            app.init_resource::<MapAutosave>().add_systems(
                 Update,
-                map_loader_system.run_if(on_event::<MessageEvent<ChangeMapMessage>>()),
+                (
+                    map_loader_system.run_if(on_event::<MessageEvent<ChangeMapMessage>>()),
+                    export_map_patch.run_if(admin_sent::<ExportMapPatchMessage>),
+                    autosave_map_changes,
+                    apply_saved_patch,
+                    save_native_map.run_if(admin_sent::<SaveMapMessage>),
+                ),
             );
         } else {
             app.add_systems(
@@ -76,3 +297,19 @@ impl Plugin for MapManagementPlugin {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::is_valid_map_name;
+
+    #[test]
+    fn map_names_cannot_leave_the_maps_folder() {
+        assert!(is_valid_map_name("DeltaStation2"));
+        assert!(is_valid_map_name("tram_station-v2"));
+        assert!(!is_valid_map_name(""));
+        assert!(!is_valid_map_name("../config"));
+        assert!(!is_valid_map_name("/etc/passwd"));
+        assert!(!is_valid_map_name("maps\\BoxStation"));
+        assert!(!is_valid_map_name("Box Station"));
+    }
+}
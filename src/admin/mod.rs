@@ -1,12 +1,95 @@
-use bevy::prelude::{App, Plugin};
+use std::{fs::OpenOptions, io::Write};
 
+use bevy::{
+    prelude::{error, info, App, Plugin, Resource},
+    utils::Uuid,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::config::ServerConfig;
+
+mod announcements;
+mod bandwidth;
+mod bans;
+mod chat_monitor;
+mod cleanup;
+mod commands;
+mod entities;
 mod map;
+mod round;
 mod spawning;
+mod tickets;
+mod visibility;
+
+pub(crate) use bans::ban_file;
 
 pub(crate) struct AdminPlugin;
 
 impl Plugin for AdminPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins((spawning::SpawningPlugin, map::MapManagementPlugin));
+        app.add_plugins((
+            spawning::SpawningPlugin,
+            map::MapManagementPlugin,
+            tickets::TicketPlugin,
+            cleanup::ItemCleanupAdminPlugin,
+            round::RoundAdminPlugin,
+            announcements::AnnouncementAdminPlugin,
+            commands::AdminCommandPlugin,
+            bans::BanPlugin,
+            entities::EntityCensusPlugin,
+            visibility::VisibilityDebugPlugin,
+            chat_monitor::ChatMonitorPlugin,
+            bandwidth::BandwidthReportPlugin,
+        ));
+    }
+}
+
+/// Roles of server staff, ordered by how much they are allowed to do
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum StaffRole {
+    Mentor,
+    Admin,
+}
+
+/// Staff role of the local player, as told by the server
+#[derive(Resource, Default)]
+pub(crate) struct ClientStaffRole(pub(crate) Option<StaffRole>);
+
+impl ServerConfig {
+    pub(crate) fn staff_role(&self, player: Uuid) -> Option<StaffRole> {
+        let id = player.to_string();
+        if self.staff.admins.contains(&id) {
+            Some(StaffRole::Admin)
+        } else if self.staff.mentors.contains(&id) {
+            Some(StaffRole::Mentor)
+        } else {
+            None
+        }
+    }
+}
+
+const MODERATION_LOG_FILE: &str = "moderation.log";
+
+/// Persistent record of staff actions and player reports
+#[derive(Resource, Default)]
+pub(crate) struct ModerationLog;
+
+impl ModerationLog {
+    pub(crate) fn record(&mut self, entry: &str) {
+        info!(target: "moderation", "{}", entry);
+
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(MODERATION_LOG_FILE)
+            .and_then(|mut file| writeln!(file, "[{}] {}", timestamp, entry));
+        if let Err(err) = result {
+            error!("Error writing moderation log: {}", err);
+        }
     }
 }
@@ -0,0 +1,93 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use networking::{
+    is_server,
+    messaging::{AppExt, MessageEvent, MessageSender},
+    Players,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{config::ServerConfig, round::RoundState, ui::has_window, GameState};
+
+use super::{ModerationLog, StaffRole};
+
+pub(crate) struct RoundAdminPlugin;
+
+impl Plugin for RoundAdminPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_network_message::<RoundControlMessage>();
+
+        if is_server(app) {
+            app.init_resource::<ModerationLog>()
+                .add_systems(Update, handle_round_control);
+        } else {
+            app.add_systems(
+                Update,
+                client_round_ui
+                    .run_if(has_window)
+                    .run_if(in_state(GameState::Game)),
+            );
+        }
+    }
+}
+
+/// Moves the round along without waiting for players or timers
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+enum RoundControlMessage {
+    /// Starts the round immediately
+    Start,
+    End,
+    Restart,
+}
+
+fn handle_round_control(
+    mut messages: EventReader<MessageEvent<RoundControlMessage>>,
+    players: Res<Players>,
+    config: Res<ServerConfig>,
+    state: Res<State<RoundState>>,
+    mut next_state: ResMut<NextState<RoundState>>,
+    mut log: ResMut<ModerationLog>,
+) {
+    for event in messages.iter() {
+        let Some(player) = players.get(event.connection) else {
+            continue;
+        };
+        if config.staff_role(player.id) < Some(StaffRole::Admin) {
+            warn!(player = ?player.username, "Player without permission tried to control the round");
+            continue;
+        }
+
+        let target = match (event.message, state.get()) {
+            (RoundControlMessage::Start, RoundState::Lobby | RoundState::Starting) => {
+                RoundState::InProgress
+            }
+            (RoundControlMessage::End, RoundState::InProgress) => RoundState::Ending,
+            (RoundControlMessage::Restart, current) if *current != RoundState::Restarting => {
+                RoundState::Restarting
+            }
+            _ => continue,
+        };
+
+        log.record(&format!(
+            "{} changed the round state to {:?}",
+            player.username, target
+        ));
+        next_state.set(target);
+    }
+}
+
+fn client_round_ui(mut contexts: EguiContexts, mut sender: MessageSender) {
+    egui::Window::new("Round control").show(contexts.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            if ui.button("Start now").clicked() {
+                sender.send_to_server(&RoundControlMessage::Start);
+            }
+            if ui.button("End round").clicked() {
+                sender.send_to_server(&RoundControlMessage::End);
+            }
+            if ui.button("Restart").clicked() {
+                sender.send_to_server(&RoundControlMessage::Restart);
+            }
+        });
+    });
+}
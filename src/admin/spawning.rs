@@ -13,13 +13,18 @@ use networking::{
     is_server,
     messaging::{AppExt, MessageEvent, MessageSender},
     scene::NetworkSceneBundle,
+    Players,
 };
 use serde::{Deserialize, Serialize};
 
 use crate::{
     camera::MainCamera,
+    config::ServerConfig,
     interaction::InteractionSystem,
-    items::{Item, ItemAssets},
+    items::{
+        variants::{variant_bundle, ItemVariant, ItemVariantAssets},
+        Item, ItemAssets,
+    },
     ui::has_window,
     GameState,
 };
@@ -47,15 +52,21 @@ fn spawning_ui(mut contexts: EguiContexts, mut state: ResMut<SpawnerUiState>) {
 
 fn prepare_item_ui_data(
     assets: Res<ItemAssets>,
+    variant_assets: Res<ItemVariantAssets>,
     mut events: EventReader<AssetEvent<DynamicScene>>,
+    mut variant_events: EventReader<AssetEvent<ItemVariant>>,
     mut ui_data: ResMut<SpawnerUiState>,
     scenes: Res<Assets<DynamicScene>>,
+    variants: Res<Assets<ItemVariant>>,
 ) {
     let loaded_item = events.iter().any(|e| match e {
         AssetEvent::Created { handle } => assets.definitions.contains(handle),
         _ => false,
     });
-    if !loaded_item {
+    let loaded_variant = variant_events
+        .iter()
+        .any(|e| matches!(e, AssetEvent::Created { .. }));
+    if !loaded_item && !loaded_variant {
         return;
     }
 
@@ -87,6 +98,19 @@ fn prepare_item_ui_data(
             },
         });
     }
+
+    for handle in &variant_assets.variants {
+        let Some(variant) = variants.get(handle) else {
+            continue;
+        };
+        let HandleId::AssetPathId(id) = handle.id() else {
+            continue;
+        };
+        ui_data.all_items.push(ItemData {
+            name: format!("{} (variant)", variant.id),
+            id,
+        });
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -158,9 +182,35 @@ fn handle_spawn_request(
     mut messages: EventReader<MessageEvent<SpawnerMessage>>,
     mut commands: Commands,
     assets: Res<ItemAssets>,
+    variant_assets: Res<ItemVariantAssets>,
+    variants: Res<Assets<ItemVariant>>,
+    server: Res<AssetServer>,
+    players: Res<Players>,
+    config: Res<ServerConfig>,
 ) {
     for event in messages.iter() {
+        let allowed = players
+            .get(event.connection)
+            .map_or(false, |p| config.can_run_command(p.id, "spawn"));
+        if !allowed {
+            warn!(connection=?event.connection, "Player without permission tried to spawn an item");
+            continue;
+        }
+
         let SpawnerMessage::Request((position, id)) = event.message;
+        let transform = Transform::from_translation(position + Vec3::Y * 5.0);
+
+        if variant_assets.contains(id) {
+            let Some((mut scene, variant)) = variant_bundle(id, &variants, &server) else {
+                warn!(connection=?event.connection, "Requested variant is not loaded");
+                continue;
+            };
+            scene.transform = transform;
+            commands.spawn((scene, variant));
+            info!(connection=?event.connection, "Spawned item variant");
+            continue;
+        }
+
         let exists = assets
             .definitions
             .iter()
@@ -172,7 +222,7 @@ fn handle_spawn_request(
         }
         commands.spawn(NetworkSceneBundle {
             scene: Handle::weak(id.into()).into(),
-            transform: Transform::from_translation(position + Vec3::Y * 5.0),
+            transform,
             ..Default::default()
         });
         info!(connection=?event.connection, "Spawned item");
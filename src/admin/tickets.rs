@@ -0,0 +1,596 @@
+use bevy::{
+    prelude::*,
+    utils::{HashSet, Uuid},
+};
+use bevy_egui::{egui, EguiContexts};
+use networking::{
+    is_server,
+    messaging::{AppExt, MessageEvent, MessageReceivers, MessageSender},
+    ConnectionId, Players, ServerEvent,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::ServerConfig,
+    round::RoundState,
+    ui::{
+        has_window,
+        toasts::{PlayerToasts, Toast, ToastLevel, Toasts},
+    },
+    GameState,
+};
+
+use super::{ClientStaffRole, ModerationLog, StaffRole};
+
+pub(crate) struct TicketPlugin;
+
+impl Plugin for TicketPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_network_message::<OpenTicketMessage>()
+            .add_network_message::<TicketActionMessage>()
+            .add_network_message::<TicketUpdateMessage>()
+            .add_network_message::<StaffStatusMessage>();
+
+        if is_server(app) {
+            app.init_resource::<Tickets>()
+                .init_resource::<ModerationLog>()
+                .add_systems(
+                    Update,
+                    (
+                        send_tickets_on_connect,
+                        handle_open_ticket,
+                        handle_ticket_action,
+                    ),
+                )
+                .add_systems(OnEnter(RoundState::Restarting), clear_tickets);
+        } else {
+            app.init_resource::<ClientTickets>()
+                .init_resource::<ClientStaffRole>()
+                .add_systems(
+                    Update,
+                    (
+                        client_receive_tickets,
+                        (client_help_ui, client_staff_ui)
+                            .run_if(has_window)
+                            .run_if(in_state(GameState::Game)),
+                    )
+                        .chain(),
+                );
+        }
+    }
+}
+
+/// Maximum length of a ticket message in bytes
+const MAX_TICKET_TEXT: usize = 1024;
+/// Open tickets a player can have at once, so one player can't flood the staff queue
+const MAX_OPEN_TICKETS: usize = 3;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+enum TicketKind {
+    Adminhelp,
+    Mentorhelp,
+}
+
+impl TicketKind {
+    /// The lowest role that can handle tickets of this kind
+    fn handled_by(self) -> StaffRole {
+        match self {
+            TicketKind::Adminhelp => StaffRole::Admin,
+            TicketKind::Mentorhelp => StaffRole::Mentor,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            TicketKind::Adminhelp => "Adminhelp",
+            TicketKind::Mentorhelp => "Mentorhelp",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct TicketEntry {
+    author: String,
+    text: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Ticket {
+    id: u32,
+    kind: TicketKind,
+    owner_name: String,
+    /// Name of the staff member handling the ticket
+    claimed_by: Option<String>,
+    open: bool,
+    entries: Vec<TicketEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+enum TicketAction {
+    Claim,
+    Reply(String),
+    Close,
+}
+
+/// Client message to open a new ticket
+#[derive(Serialize, Deserialize)]
+struct OpenTicketMessage {
+    kind: TicketKind,
+    text: String,
+}
+
+/// Client message to act on an existing ticket
+#[derive(Serialize, Deserialize)]
+struct TicketActionMessage {
+    ticket: u32,
+    action: TicketAction,
+}
+
+/// Server message with the current state of a ticket
+#[derive(Serialize, Deserialize)]
+struct TicketUpdateMessage {
+    ticket: Ticket,
+    /// If the receiving player opened the ticket
+    own: bool,
+}
+
+/// Tells a client which staff role it has
+#[derive(Serialize, Deserialize)]
+struct StaffStatusMessage {
+    role: Option<StaffRole>,
+}
+
+struct ServerTicket {
+    ticket: Ticket,
+    owner: Uuid,
+}
+
+/// All tickets of the current round
+#[derive(Resource, Default)]
+struct Tickets {
+    tickets: Vec<ServerTicket>,
+    next_id: u32,
+}
+
+impl Tickets {
+    fn open_tickets_of(&self, player: Uuid) -> usize {
+        self.tickets
+            .iter()
+            .filter(|t| t.owner == player && t.ticket.open)
+            .count()
+    }
+
+    fn get_mut(&mut self, id: u32) -> Option<&mut ServerTicket> {
+        self.tickets.iter_mut().find(|t| t.ticket.id == id)
+    }
+}
+
+fn clear_tickets(mut tickets: ResMut<Tickets>) {
+    tickets.tickets.clear();
+}
+
+fn can_handle(config: &ServerConfig, player: Uuid, kind: TicketKind) -> bool {
+    config.staff_role(player) >= Some(kind.handled_by())
+}
+
+/// Sends a ticket to its owner and all staff that can handle it
+fn broadcast_ticket(
+    ticket: &ServerTicket,
+    players: &Players,
+    config: &ServerConfig,
+    sender: &mut MessageSender,
+) {
+    let owner_connection = players.get_connection(&ticket.owner);
+    if let Some(connection) = owner_connection {
+        sender.send(
+            &TicketUpdateMessage {
+                ticket: ticket.ticket.clone(),
+                own: true,
+            },
+            MessageReceivers::Single(connection),
+        );
+    }
+
+    let staff: HashSet<ConnectionId> = players
+        .players()
+        .iter()
+        .filter(|(connection, player)| {
+            Some(**connection) != owner_connection
+                && can_handle(config, player.id, ticket.ticket.kind)
+        })
+        .map(|(connection, _)| *connection)
+        .collect();
+    if !staff.is_empty() {
+        sender.send(
+            &TicketUpdateMessage {
+                ticket: ticket.ticket.clone(),
+                own: false,
+            },
+            MessageReceivers::Set(staff),
+        );
+    }
+}
+
+fn send_tickets_on_connect(
+    mut server_events: EventReader<ServerEvent>,
+    players: Res<Players>,
+    config: Res<ServerConfig>,
+    tickets: Res<Tickets>,
+    mut sender: MessageSender,
+) {
+    for event in server_events.iter() {
+        let ServerEvent::PlayerConnected(connection) = event else {
+            continue;
+        };
+        let Some(player) = players.get(*connection) else {
+            continue;
+        };
+
+        sender.send(
+            &StaffStatusMessage {
+                role: config.staff_role(player.id),
+            },
+            MessageReceivers::Single(*connection),
+        );
+
+        for ticket in tickets.tickets.iter() {
+            let own = ticket.owner == player.id;
+            if own || can_handle(&config, player.id, ticket.ticket.kind) {
+                sender.send(
+                    &TicketUpdateMessage {
+                        ticket: ticket.ticket.clone(),
+                        own,
+                    },
+                    MessageReceivers::Single(*connection),
+                );
+            }
+        }
+    }
+}
+
+fn handle_open_ticket(
+    mut messages: EventReader<MessageEvent<OpenTicketMessage>>,
+    mut tickets: ResMut<Tickets>,
+    mut log: ResMut<ModerationLog>,
+    players: Res<Players>,
+    config: Res<ServerConfig>,
+    mut sender: MessageSender,
+    mut toasts: PlayerToasts,
+) {
+    for event in messages.iter() {
+        let Some(player) = players.get(event.connection) else {
+            continue;
+        };
+        let text = event.message.text.trim();
+        if text.is_empty() || text.len() > MAX_TICKET_TEXT {
+            continue;
+        }
+        if tickets.open_tickets_of(player.id) >= MAX_OPEN_TICKETS {
+            debug!(player = ?player.username, "Too many open tickets");
+            toasts.send_to_connection(
+                event.connection,
+                Toast::new(
+                    ToastLevel::Warning,
+                    "You already have too many open tickets. Wait for staff to answer them.",
+                ),
+            );
+            continue;
+        }
+
+        let id = tickets.next_id;
+        tickets.next_id += 1;
+        let kind = event.message.kind;
+        log.record(&format!(
+            "Ticket #{} ({}) opened by {}: {}",
+            id,
+            kind.label(),
+            player.username,
+            text
+        ));
+
+        let ticket = ServerTicket {
+            ticket: Ticket {
+                id,
+                kind,
+                owner_name: player.username.clone(),
+                claimed_by: None,
+                open: true,
+                entries: vec![TicketEntry {
+                    author: player.username.clone(),
+                    text: text.to_owned(),
+                }],
+            },
+            owner: player.id,
+        };
+        broadcast_ticket(&ticket, &players, &config, &mut sender);
+        tickets.tickets.push(ticket);
+    }
+}
+
+fn handle_ticket_action(
+    mut messages: EventReader<MessageEvent<TicketActionMessage>>,
+    mut tickets: ResMut<Tickets>,
+    mut log: ResMut<ModerationLog>,
+    players: Res<Players>,
+    config: Res<ServerConfig>,
+    mut sender: MessageSender,
+) {
+    for event in messages.iter() {
+        let Some(player) = players.get(event.connection) else {
+            continue;
+        };
+        let Some(ticket) = tickets.get_mut(event.message.ticket) else {
+            continue;
+        };
+        if !ticket.ticket.open {
+            continue;
+        }
+
+        let is_owner = ticket.owner == player.id;
+        let is_staff = can_handle(&config, player.id, ticket.ticket.kind);
+        let id = ticket.ticket.id;
+        match &event.message.action {
+            TicketAction::Claim if is_staff => {
+                log.record(&format!("Ticket #{} claimed by {}", id, player.username));
+                ticket.ticket.claimed_by = Some(player.username.clone());
+            }
+            TicketAction::Reply(text) if is_owner || is_staff => {
+                let text = text.trim();
+                if text.is_empty() || text.len() > MAX_TICKET_TEXT {
+                    continue;
+                }
+                log.record(&format!(
+                    "Ticket #{} reply from {}: {}",
+                    id, player.username, text
+                ));
+                ticket.ticket.entries.push(TicketEntry {
+                    author: player.username.clone(),
+                    text: text.to_owned(),
+                });
+            }
+            TicketAction::Close if is_owner || is_staff => {
+                log.record(&format!("Ticket #{} closed by {}", id, player.username));
+                ticket.ticket.open = false;
+            }
+            _ => {
+                warn!(
+                    player = ?player.username,
+                    ticket = id,
+                    "Player tried to act on ticket without permission"
+                );
+                continue;
+            }
+        }
+
+        broadcast_ticket(ticket, &players, &config, &mut sender);
+    }
+}
+
+struct ClientTicket {
+    ticket: Ticket,
+    own: bool,
+    unread: bool,
+    reply: String,
+}
+
+#[derive(Resource)]
+struct ClientTickets {
+    tickets: Vec<ClientTicket>,
+    new_kind: TicketKind,
+    new_text: String,
+}
+
+impl Default for ClientTickets {
+    fn default() -> Self {
+        Self {
+            tickets: Vec::new(),
+            new_kind: TicketKind::Adminhelp,
+            new_text: String::new(),
+        }
+    }
+}
+
+fn client_receive_tickets(
+    mut status: EventReader<MessageEvent<StaffStatusMessage>>,
+    mut updates: EventReader<MessageEvent<TicketUpdateMessage>>,
+    mut tickets: ResMut<ClientTickets>,
+    mut role: ResMut<ClientStaffRole>,
+    mut toasts: ResMut<Toasts>,
+) {
+    for event in status.iter() {
+        role.0 = event.message.role;
+    }
+
+    for event in updates.iter() {
+        let update = &event.message;
+        let known_entries = tickets
+            .tickets
+            .iter()
+            .find(|t| t.ticket.id == update.ticket.id)
+            .map(|t| t.ticket.entries.len());
+        let last = update.ticket.entries.last();
+        let replied = known_entries.map_or(false, |known| update.ticket.entries.len() > known);
+        if update.own {
+            // Staff answered
+            if let Some(entry) =
+                last.filter(|entry| replied && entry.author != update.ticket.owner_name)
+            {
+                toasts.push(
+                    Toast::new(ToastLevel::Info, entry.text.as_str()).title(format!(
+                        "{} from {}",
+                        update.ticket.kind.label(),
+                        entry.author
+                    )),
+                );
+            }
+        } else if known_entries.is_none() && update.ticket.open {
+            toasts.push(
+                Toast::new(
+                    ToastLevel::Warning,
+                    last.map_or("", |entry| entry.text.as_str()),
+                )
+                .title(format!(
+                    "New {} from {}",
+                    update.ticket.kind.label(),
+                    update.ticket.owner_name
+                )),
+            );
+        }
+
+        match tickets
+            .tickets
+            .iter_mut()
+            .find(|t| t.ticket.id == update.ticket.id)
+        {
+            Some(existing) => {
+                existing.ticket = update.ticket.clone();
+                existing.unread = true;
+            }
+            None => tickets.tickets.push(ClientTicket {
+                ticket: update.ticket.clone(),
+                own: update.own,
+                unread: true,
+                reply: String::new(),
+            }),
+        }
+    }
+}
+
+/// Shows a ticket conversation and returns an action if a button was pressed
+fn ticket_details(
+    ui: &mut egui::Ui,
+    ticket: &mut ClientTicket,
+    staff: bool,
+) -> Option<TicketAction> {
+    let mut action = None;
+    ticket.unread = false;
+
+    for entry in ticket.ticket.entries.iter() {
+        ui.label(format!("{}: {}", entry.author, entry.text));
+    }
+
+    if !ticket.ticket.open {
+        ui.label(egui::RichText::new("Closed").italics());
+        return None;
+    }
+
+    ui.horizontal(|ui| {
+        ui.text_edit_singleline(&mut ticket.reply);
+        if ui.button("Reply").clicked() && !ticket.reply.trim().is_empty() {
+            action = Some(TicketAction::Reply(std::mem::take(&mut ticket.reply)));
+        }
+    });
+    ui.horizontal(|ui| {
+        if staff && ticket.ticket.claimed_by.is_none() && ui.button("Claim").clicked() {
+            action = Some(TicketAction::Claim);
+        }
+        if ui.button("Close").clicked() {
+            action = Some(TicketAction::Close);
+        }
+    });
+
+    action
+}
+
+fn window_title(name: &str, unread: usize) -> String {
+    match unread {
+        0 => name.to_owned(),
+        n => format!("{} ({} new)", name, n),
+    }
+}
+
+fn client_help_ui(
+    mut contexts: EguiContexts,
+    mut tickets: ResMut<ClientTickets>,
+    mut sender: MessageSender,
+) {
+    let tickets = &mut *tickets;
+    let unread = tickets.tickets.iter().filter(|t| t.own && t.unread).count();
+
+    egui::Window::new(window_title("Help", unread))
+        .id(egui::Id::new("help_window"))
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                for kind in [TicketKind::Adminhelp, TicketKind::Mentorhelp] {
+                    ui.radio_value(&mut tickets.new_kind, kind, kind.label());
+                }
+            });
+            ui.text_edit_multiline(&mut tickets.new_text);
+            if ui.button("Send").clicked() && !tickets.new_text.trim().is_empty() {
+                sender.send_to_server(&OpenTicketMessage {
+                    kind: tickets.new_kind,
+                    text: std::mem::take(&mut tickets.new_text),
+                });
+            }
+
+            for ticket in tickets.tickets.iter_mut().filter(|t| t.own) {
+                ui.separator();
+                let id = ticket.ticket.id;
+                ui.label(format!("#{} {}", id, ticket.ticket.kind.label()));
+                if let Some(claimed_by) = &ticket.ticket.claimed_by {
+                    ui.label(format!("Handled by {}", claimed_by));
+                }
+                if let Some(action) = ticket_details(ui, ticket, false) {
+                    sender.send_to_server(&TicketActionMessage { ticket: id, action });
+                }
+            }
+        });
+}
+
+fn client_staff_ui(
+    mut contexts: EguiContexts,
+    mut tickets: ResMut<ClientTickets>,
+    role: Res<ClientStaffRole>,
+    mut selected: Local<Option<u32>>,
+    mut sender: MessageSender,
+) {
+    if role.0.is_none() {
+        return;
+    }
+
+    let unread = tickets
+        .tickets
+        .iter()
+        .filter(|t| !t.own && t.unread)
+        .count();
+
+    egui::Window::new(window_title("Tickets", unread))
+        .id(egui::Id::new("staff_tickets_window"))
+        .show(contexts.ctx_mut(), |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for ticket in tickets.tickets.iter_mut().filter(|t| !t.own) {
+                    let id = ticket.ticket.id;
+                    let mut text = format!(
+                        "#{} {} from {}",
+                        id,
+                        ticket.ticket.kind.label(),
+                        ticket.ticket.owner_name
+                    );
+                    if let Some(claimed_by) = &ticket.ticket.claimed_by {
+                        text += &format!(" (claimed by {})", claimed_by);
+                    }
+                    if !ticket.ticket.open {
+                        text += " [closed]";
+                    }
+                    let label = match ticket.unread {
+                        true => egui::RichText::new(text).strong(),
+                        false => egui::RichText::new(text),
+                    };
+
+                    let is_selected = *selected == Some(id);
+                    if ui.selectable_label(is_selected, label).clicked() {
+                        *selected = (!is_selected).then_some(id);
+                    }
+
+                    if is_selected {
+                        ui.indent(id, |ui| {
+                            if let Some(action) = ticket_details(ui, ticket, true) {
+                                sender.send_to_server(&TicketActionMessage { ticket: id, action });
+                            }
+                        });
+                    }
+                }
+            });
+        });
+}
@@ -0,0 +1,318 @@
+//! Overlay of the network visibility grid for debugging entities that don't show up for players.
+//!
+//! Admins pick a player and get sent the cells that player observes
+//! and where the entities replicated to them are.
+
+use bevy::{math::Vec3Swizzles, prelude::*, utils::HashMap};
+use bevy_egui::{egui, EguiContexts};
+use networking::{
+    identity::NetworkIdentities,
+    is_server,
+    messaging::{AppExt, MessageEvent, MessageReceivers, MessageSender},
+    visibility::{
+        global_grid_cell, NetworkObserver, NetworkObserverCells, NetworkVisibilities,
+        GLOBAL_GRID_CELL_SIZE,
+    },
+    ConnectionId, Players,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{config::ServerConfig, debug::DebugState, ui::has_window, GameState};
+
+use super::StaffRole;
+
+pub(crate) struct VisibilityDebugPlugin;
+
+impl Plugin for VisibilityDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_network_message::<WatchVisibilityMessage>()
+            .add_network_message::<VisibilityReportMessage>();
+
+        if is_server(app) {
+            app.init_resource::<VisibilityWatchers>().add_systems(
+                Update,
+                (handle_watch_requests, send_visibility_reports).chain(),
+            );
+        } else {
+            app.init_resource::<ClientVisibilityDebug>().add_systems(
+                Update,
+                (
+                    client_request_visibility,
+                    client_receive_visibility_report,
+                    client_visibility_ui.run_if(has_window),
+                    client_draw_visibility,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Game)),
+            );
+        }
+    }
+}
+
+/// Seconds between reports sent to watching admins
+const REPORT_INTERVAL: f32 = 0.5;
+/// Most entity positions in a report, to keep the message small in busy areas
+const MAX_REPORTED_ENTITIES: usize = 1024;
+
+/// Starts or stops receiving visibility reports
+#[derive(Serialize, Deserialize, Clone)]
+struct WatchVisibilityMessage {
+    enabled: bool,
+    /// Username of the player to watch, or the requesting admin if empty
+    player: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+struct VisibilityReportMessage {
+    /// Usernames of everyone connected, to pick who to watch
+    players: Vec<String>,
+    watched: String,
+    /// Cell the observer is in and how many cells around it are in range
+    observer: Option<(IVec2, u32)>,
+    observed_cells: Vec<IVec2>,
+    /// Positions of entities replicated to the watched player
+    replicated: Vec<Vec3>,
+    /// Number of replicated entities, including ones without a position
+    replicated_count: usize,
+}
+
+/// Admins receiving visibility reports and who they watch
+#[derive(Resource, Default)]
+struct VisibilityWatchers(HashMap<ConnectionId, Option<String>>);
+
+fn handle_watch_requests(
+    mut messages: EventReader<MessageEvent<WatchVisibilityMessage>>,
+    mut watchers: ResMut<VisibilityWatchers>,
+    players: Res<Players>,
+    config: Res<ServerConfig>,
+) {
+    for event in messages.iter() {
+        let Some(player) = players.get(event.connection) else {
+            continue;
+        };
+        if config.staff_role(player.id) < Some(StaffRole::Admin) {
+            warn!(player = ?player.username, "Player without permission requested visibility debugging");
+            continue;
+        }
+
+        if event.message.enabled {
+            watchers
+                .0
+                .insert(event.connection, event.message.player.clone());
+        } else {
+            watchers.0.remove(&event.connection);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn send_visibility_reports(
+    mut watchers: ResMut<VisibilityWatchers>,
+    players: Res<Players>,
+    observers: Query<(&NetworkObserver, &NetworkObserverCells, &GlobalTransform)>,
+    visibilities: Res<NetworkVisibilities>,
+    identities: Res<NetworkIdentities>,
+    transforms: Query<&GlobalTransform>,
+    time: Res<Time>,
+    mut last_report: Local<f32>,
+    mut sender: MessageSender,
+) {
+    let now = time.elapsed_seconds();
+    if watchers.0.is_empty() || *last_report + REPORT_INTERVAL > now {
+        return;
+    }
+    *last_report = now;
+
+    // Stop reporting to admins that left
+    watchers
+        .0
+        .retain(|connection, _| players.get(*connection).is_some());
+
+    let usernames: Vec<String> = players
+        .players()
+        .values()
+        .map(|player| player.username.clone())
+        .collect();
+
+    for (&admin, watched) in watchers.0.iter() {
+        let watched = match watched {
+            Some(username) => players
+                .players()
+                .iter()
+                .find(|(_, player)| &player.username == username),
+            None => players.get(admin).map(|player| (&admin, player)),
+        };
+        let mut report = VisibilityReportMessage {
+            players: usernames.clone(),
+            ..Default::default()
+        };
+
+        if let Some((&connection, player)) = watched {
+            report.watched = player.username.clone();
+            if let Some((observer, cells, transform)) = observers
+                .iter()
+                .find(|(observer, ..)| observer.player_id == player.id)
+            {
+                let cell = global_grid_cell(transform.translation());
+                report.observer = Some((cell, observer.range));
+                report.observed_cells = cells.observed_cells().collect();
+            }
+
+            let replicated: Vec<_> = visibilities.observed_by(connection).collect();
+            report.replicated_count = replicated.len();
+            report.replicated = replicated
+                .into_iter()
+                .filter_map(|identity| identities.get_entity(identity))
+                .filter_map(|entity| transforms.get(entity).ok())
+                .map(GlobalTransform::translation)
+                .take(MAX_REPORTED_ENTITIES)
+                .collect();
+        }
+
+        sender.send(&report, MessageReceivers::Single(admin));
+    }
+}
+
+#[derive(Resource, Default)]
+struct ClientVisibilityDebug {
+    report: Option<VisibilityReportMessage>,
+    /// Player picked in the window, `None` is yourself
+    watched: Option<String>,
+}
+
+/// Tells the server when the overlay is toggled or another player is picked
+fn client_request_visibility(
+    debug: Res<DebugState>,
+    view: Res<ClientVisibilityDebug>,
+    mut sent: Local<Option<(bool, Option<String>)>>,
+    mut sender: MessageSender,
+) {
+    let current = (debug.network_visibility, view.watched.clone());
+    if sent.as_ref() == Some(&current) || (sent.is_none() && !current.0) {
+        return;
+    }
+
+    sender.send_to_server(&WatchVisibilityMessage {
+        enabled: current.0,
+        player: current.1.clone(),
+    });
+    *sent = Some(current);
+}
+
+fn client_receive_visibility_report(
+    mut messages: EventReader<MessageEvent<VisibilityReportMessage>>,
+    mut view: ResMut<ClientVisibilityDebug>,
+    debug: Res<DebugState>,
+) {
+    if let Some(event) = messages.iter().last() {
+        view.report = Some(event.message.clone());
+    }
+    if !debug.network_visibility {
+        view.report = None;
+    }
+}
+
+fn client_visibility_ui(
+    mut contexts: EguiContexts,
+    mut debug: ResMut<DebugState>,
+    mut view: ResMut<ClientVisibilityDebug>,
+) {
+    if !debug.network_visibility {
+        return;
+    }
+
+    let view = &mut *view;
+    egui::Window::new("Network visibility")
+        .open(&mut debug.network_visibility)
+        .show(contexts.ctx_mut(), |ui| {
+            let Some(report) = view.report.as_ref() else {
+                ui.label("Waiting for the server...");
+                return;
+            };
+
+            egui::ComboBox::from_label("Player")
+                .selected_text(view.watched.as_deref().unwrap_or("Yourself"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut view.watched, None, "Yourself");
+                    for username in report.players.iter() {
+                        ui.selectable_value(
+                            &mut view.watched,
+                            Some(username.clone()),
+                            username.as_str(),
+                        );
+                    }
+                });
+
+            egui::Grid::new("visibility stats").show(ui, |ui| {
+                ui.label("Watching");
+                ui.label(report.watched.as_str());
+                ui.end_row();
+                ui.label("Observer cell");
+                ui.label(match report.observer {
+                    Some((cell, _)) => format!("{}, {}", cell.x, cell.y),
+                    None => "No observer".into(),
+                });
+                ui.end_row();
+                ui.label("Observed cells");
+                ui.label(report.observed_cells.len().to_string());
+                ui.end_row();
+                ui.label("Replicated entities");
+                ui.label(report.replicated_count.to_string());
+                ui.end_row();
+            });
+            ui.label("Yellow: observer cell, green: observed, grey: in range but not yet observed");
+        });
+}
+
+const CELL_IN_RANGE_COLOR: Color = Color::GRAY;
+const CELL_OBSERVED_COLOR: Color = Color::GREEN;
+const CELL_OCCUPIED_COLOR: Color = Color::YELLOW;
+const REPLICATED_ENTITY_COLOR: Color = Color::CYAN;
+
+fn client_draw_visibility(view: Res<ClientVisibilityDebug>, mut gizmos: Gizmos) {
+    let Some(report) = view.report.as_ref() else {
+        return;
+    };
+
+    if let Some((center, range)) = report.observer {
+        let range = range as i32;
+        for x in -range..=range {
+            for y in -range..=range {
+                let cell = center + IVec2::new(x, y);
+                if !report.observed_cells.contains(&cell) {
+                    draw_cell(&mut gizmos, cell, 0.0, CELL_IN_RANGE_COLOR);
+                }
+            }
+        }
+    }
+    // Cells stay observed for a while after going out of range
+    for &cell in report.observed_cells.iter() {
+        draw_cell(&mut gizmos, cell, 0.02, CELL_OBSERVED_COLOR);
+    }
+    if let Some((center, _)) = report.observer {
+        draw_cell(&mut gizmos, center, 0.04, CELL_OCCUPIED_COLOR);
+    }
+
+    for &position in report.replicated.iter() {
+        gizmos.line(position, position + Vec3::Y * 0.5, REPLICATED_ENTITY_COLOR);
+    }
+}
+
+/// Outlines a grid cell on the floor, `height` keeps overlapping outlines apart
+fn draw_cell(gizmos: &mut Gizmos, cell: IVec2, height: f32, color: Color) {
+    let size = GLOBAL_GRID_CELL_SIZE as f32;
+    let min = cell.as_vec2() * size;
+    let corners = [
+        Vec2::new(0.0, 0.0),
+        Vec2::new(size, 0.0),
+        Vec2::new(size, size),
+        Vec2::new(0.0, size),
+        Vec2::new(0.0, 0.0),
+    ];
+    gizmos.linestrip(
+        corners
+            .into_iter()
+            .map(|corner| (min + corner).extend(height).xzy()),
+        color,
+    );
+}
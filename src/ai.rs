@@ -0,0 +1,421 @@
+//! Creatures controlled by the server.
+//!
+//! Each NPC regularly scores what it could do (wander, flee or attack)
+//! and follows a path over the tilemap towards the best option.
+
+use bevy::{
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+};
+use bevy_common_assets::ron::RonAssetPlugin;
+use bevy_rapier3d::prelude::{CollisionGroups, QueryFilter, RapierContext, Velocity};
+use networking::is_server;
+use serde::Deserialize;
+use utils::task::{TaskId, Tasks};
+
+use crate::{
+    body::{
+        appearance::CharacterAppearance,
+        ghost::Ghost,
+        health::{BrainState, BrainStateEvent},
+        Body, SpawnCreature,
+    },
+    combat::{
+        damage::{Damage, DamageEvent, KineticDamage, KineticShape},
+        grab::Grabbed,
+    },
+    navigation::{tile_at, tile_center, TilemapNav},
+    round::RoundRng,
+    Player,
+};
+
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonAssetPlugin::<NpcDefinition>::new(&["npc.ron"]));
+
+        if is_server(app) {
+            app.add_event::<SpawnNpc>()
+                .init_resource::<PendingNpcs>()
+                .add_systems(Startup, load_npc_assets)
+                .add_systems(
+                    Update,
+                    (
+                        (spawn_npcs, finish_npc_spawns, place_spawned_npcs).chain(),
+                        track_npc_consciousness,
+                        (think, move_npcs, npc_attacks).chain(),
+                    ),
+                );
+        }
+    }
+}
+
+#[derive(Deserialize, TypeUuid, TypePath)]
+#[uuid = "0f6d3b8e-5a27-4c91-b4e0-8d2a7c61f953"]
+pub struct NpcDefinition {
+    pub id: String,
+    species: String,
+    #[serde(default)]
+    appearance: Option<CharacterAppearance>,
+    /// Walking speed in m/s
+    speed: f32,
+    /// Distance at which players are noticed
+    sight: f32,
+    /// How far from its spawn it wanders, in tiles
+    wander_radius: u32,
+    /// Runs away from players
+    #[serde(default)]
+    flees: bool,
+    /// Goes after players to hurt them
+    #[serde(default)]
+    attack: Option<NpcAttack>,
+}
+
+#[derive(Deserialize, Clone)]
+struct NpcAttack {
+    /// Mass behind a hit in kg
+    mass: f32,
+    /// Speed of a hit in m/s
+    velocity: f32,
+    shape: KineticShape,
+    /// Seconds between hits
+    cooldown: f32,
+}
+
+#[derive(Resource)]
+pub(crate) struct NpcAssets {
+    definitions: Vec<Handle<NpcDefinition>>,
+}
+
+impl NpcAssets {
+    pub fn get<'a>(
+        &self,
+        id: &str,
+        assets: &'a Assets<NpcDefinition>,
+    ) -> Option<&'a NpcDefinition> {
+        self.definitions
+            .iter()
+            .filter_map(|handle| assets.get(handle))
+            .find(|definition| definition.id == id)
+    }
+}
+
+fn load_npc_assets(server: Res<AssetServer>, mut commands: Commands) {
+    commands.insert_resource(NpcAssets {
+        definitions: server
+            .load_folder("npcs")
+            .expect("assets/npcs is missing")
+            .into_iter()
+            .map(HandleUntyped::typed)
+            .collect(),
+    });
+}
+
+/// Spawns an NPC from its definition id
+#[derive(Event)]
+pub struct SpawnNpc {
+    pub npc: String,
+    pub position: Vec3,
+}
+
+/// How often NPCs reconsider what they are doing
+const THINK_INTERVAL: f32 = 0.5;
+/// Distance from which NPCs can hit their target
+const ATTACK_RANGE: f32 = 1.2;
+/// Height at which NPCs hit their target
+const ATTACK_HEIGHT: f32 = 1.0;
+/// How far NPCs run when fleeing, in tiles
+const FLEE_DISTANCE: f32 = 5.0;
+/// Directions relative to straight away from a threat, in degrees
+const FLEE_ANGLES: [f32; 5] = [0.0, 45.0, -45.0, 90.0, -90.0];
+/// Base score of wandering around, other goals win once they become relevant
+const WANDER_SCORE: f32 = 0.1;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Goal {
+    Idle,
+    Wander,
+    Flee(Entity),
+    Attack(Entity),
+}
+
+/// A creature controlled by the server
+#[derive(Component)]
+pub struct Npc {
+    speed: f32,
+    sight: f32,
+    wander_radius: u32,
+    flees: bool,
+    attack: Option<NpcAttack>,
+    /// Where the NPC was spawned, it won't wander far from here
+    home: Vec3,
+    /// Position to move to once the body has spawned
+    spawn_position: Option<Vec3>,
+    goal: Goal,
+    path: Vec<UVec2>,
+    next_think: f32,
+    next_attack: f32,
+    conscious: bool,
+}
+
+impl Npc {
+    fn new(definition: &NpcDefinition, position: Vec3) -> Self {
+        Self {
+            speed: definition.speed,
+            sight: definition.sight,
+            wander_radius: definition.wander_radius,
+            flees: definition.flees,
+            attack: definition.attack.clone(),
+            home: position,
+            spawn_position: Some(position),
+            goal: Goal::Idle,
+            path: Vec::new(),
+            next_think: 0.0,
+            next_attack: 0.0,
+            conscious: true,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct PendingNpcs(Vec<(TaskId<SpawnCreature>, Npc)>);
+
+fn spawn_npcs(
+    mut events: EventReader<SpawnNpc>,
+    assets: Option<Res<NpcAssets>>,
+    definitions: Res<Assets<NpcDefinition>>,
+    mut spawning: ResMut<Tasks<SpawnCreature>>,
+    mut pending: ResMut<PendingNpcs>,
+) {
+    for event in events.iter() {
+        let Some(definition) = assets
+            .as_ref()
+            .and_then(|assets| assets.get(&event.npc, &definitions))
+        else {
+            warn!(npc = event.npc.as_str(), "Missing NPC definition");
+            continue;
+        };
+
+        let task = spawning.create(SpawnCreature {
+            archetype: definition.species.clone(),
+            appearance: definition.appearance.unwrap_or_default(),
+        });
+        pending.0.push((task, Npc::new(definition, event.position)));
+    }
+}
+
+fn finish_npc_spawns(
+    mut pending: ResMut<PendingNpcs>,
+    mut spawning: ResMut<Tasks<SpawnCreature>>,
+    mut commands: Commands,
+) {
+    let mut index = 0;
+    while index < pending.0.len() {
+        match spawning.result(pending.0[index].0) {
+            Some(result) => {
+                let (_, npc) = pending.0.swap_remove(index);
+                commands.entity(result.root).insert(npc);
+            }
+            None => index += 1,
+        }
+    }
+}
+
+/// Moves NPCs to their spawn position once their scene has loaded
+fn place_spawned_npcs(mut npcs: Query<(&mut Npc, &mut Transform), With<Velocity>>) {
+    for (mut npc, mut transform) in npcs.iter_mut() {
+        if let Some(position) = npc.spawn_position.take() {
+            transform.translation = position;
+        }
+    }
+}
+
+fn track_npc_consciousness(
+    mut events: EventReader<BrainStateEvent>,
+    parents: Query<&Parent>,
+    mut npcs: Query<&mut Npc>,
+) {
+    for event in events.iter() {
+        let Some(npc_entity) = parents
+            .iter_ancestors(event.brain)
+            .find(|&e| npcs.contains(e))
+        else {
+            continue;
+        };
+        let mut npc = npcs.get_mut(npc_entity).unwrap();
+        npc.conscious = event.new_state == BrainState::Conscious;
+        if !npc.conscious {
+            npc.goal = Goal::Idle;
+            npc.path.clear();
+        }
+    }
+}
+
+/// Scores the possible goals of each NPC and plans a path for the best one
+fn think(
+    mut npcs: Query<(&mut Npc, &GlobalTransform)>,
+    players: Query<(Entity, &GlobalTransform), (With<Player>, With<Body>, Without<Ghost>)>,
+    nav: Res<TilemapNav>,
+    mut rng: Option<ResMut<RoundRng>>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    for (mut npc, transform) in npcs.iter_mut() {
+        if !npc.conscious || npc.spawn_position.is_some() || npc.next_think > now {
+            continue;
+        }
+        npc.next_think = now + THINK_INTERVAL;
+
+        let position = transform.translation();
+        let Some(tile) = tile_at(position) else {
+            continue;
+        };
+
+        let nearest = players
+            .iter()
+            .map(|(entity, t)| (entity, t.translation().distance(position)))
+            .filter(|&(_, distance)| distance <= npc.sight)
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        let closeness = nearest.map_or(0.0, |(_, distance)| 1.0 - distance / npc.sight);
+
+        let wander_score = match npc.goal {
+            // Keep walking to the current wander destination
+            Goal::Wander if !npc.path.is_empty() => WANDER_SCORE,
+            _ => WANDER_SCORE * rng.as_mut().map_or(0.0, |rng| rng.next_f32()),
+        };
+        let mut options = vec![
+            (Goal::Idle, WANDER_SCORE / 2.0),
+            (Goal::Wander, wander_score),
+        ];
+        if let Some((threat, _)) = nearest {
+            if npc.flees {
+                options.push((Goal::Flee(threat), closeness));
+            }
+            if npc.attack.is_some() {
+                // Hostile NPCs commit to an attack more than others run away
+                options.push((Goal::Attack(threat), 0.2 + closeness));
+            }
+        }
+        let goal = options
+            .into_iter()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(goal, _)| goal)
+            .unwrap_or(Goal::Idle);
+
+        let destination = match goal {
+            Goal::Idle => None,
+            Goal::Wander if npc.goal == Goal::Wander && !npc.path.is_empty() => continue,
+            Goal::Wander => rng.as_mut().and_then(|rng| {
+                let radius = npc.wander_radius;
+                let offset = IVec2::new(
+                    rng.range(0..=2 * radius) as i32,
+                    rng.range(0..=2 * radius) as i32,
+                ) - IVec2::splat(radius as i32);
+                tile_at(npc.home + Vec3::new(offset.x as f32, 0.0, offset.y as f32))
+            }),
+            Goal::Flee(threat) => players.get(threat).ok().and_then(|(_, threat)| {
+                let away = (position - threat.translation()).normalize_or_zero();
+                // Try running at an angle if straight away is blocked
+                FLEE_ANGLES
+                    .iter()
+                    .map(|&angle| Quat::from_rotation_y(angle.to_radians()) * away)
+                    .filter_map(|direction| tile_at(position + direction * FLEE_DISTANCE))
+                    .find(|&tile| nav.is_walkable(tile))
+            }),
+            Goal::Attack(target) => players
+                .get(target)
+                .ok()
+                .and_then(|(_, target)| tile_at(target.translation())),
+        };
+
+        npc.goal = goal;
+        npc.path = destination
+            .and_then(|destination| nav.find_path(tile, destination))
+            .unwrap_or_default();
+    }
+}
+
+/// Grabbed NPCs are moved by whoever holds them
+fn move_npcs(mut npcs: Query<(&mut Npc, &mut Transform, &mut Velocity), Without<Grabbed>>) {
+    for (mut npc, mut transform, mut velocity) in npcs.iter_mut() {
+        if !npc.conscious {
+            continue;
+        }
+        let Some(&next) = npc.path.first() else {
+            velocity.linvel.x = 0.0;
+            velocity.linvel.z = 0.0;
+            continue;
+        };
+
+        let mut offset = tile_center(next) - transform.translation;
+        offset.y = 0.0;
+        if offset.length() < 0.1 {
+            npc.path.remove(0);
+            continue;
+        }
+
+        let direction = offset.normalize();
+        velocity.linvel.x = direction.x * npc.speed;
+        velocity.linvel.z = direction.z * npc.speed;
+        transform.rotation = Quat::from_rotation_arc(Vec3::Z, direction);
+    }
+}
+
+fn npc_attacks(
+    mut npcs: Query<(Entity, &mut Npc, &GlobalTransform)>,
+    targets: Query<&GlobalTransform>,
+    parents: Query<&Parent>,
+    rapier: Res<RapierContext>,
+    time: Res<Time>,
+    mut damage: EventWriter<DamageEvent>,
+) {
+    let now = time.elapsed_seconds();
+    for (entity, mut npc, transform) in npcs.iter_mut() {
+        let Goal::Attack(target) = npc.goal else {
+            continue;
+        };
+        let Some(attack) = npc.attack.clone() else {
+            continue;
+        };
+        if !npc.conscious || npc.next_attack > now {
+            continue;
+        }
+        let Ok(target_transform) = targets.get(target) else {
+            continue;
+        };
+
+        let origin = transform.translation() + Vec3::Y * ATTACK_HEIGHT;
+        let mut direction = target_transform.translation() + Vec3::Y * ATTACK_HEIGHT - origin;
+        if direction.length() > ATTACK_RANGE {
+            continue;
+        }
+        direction = direction.normalize_or_zero();
+
+        let filter = QueryFilter::new()
+            .groups(CollisionGroups::new(
+                physics::RAYCASTING_GROUP,
+                physics::DEFAULT_GROUP | physics::LIMB_GROUP,
+            ))
+            .predicate(&|hit| hit != entity && !parents.iter_ancestors(hit).any(|e| e == entity));
+        let Some((hit, _)) = rapier.cast_ray(origin, direction, ATTACK_RANGE, false, filter) else {
+            continue;
+        };
+        if !parents.iter_ancestors(hit).any(|e| e == target) {
+            continue;
+        }
+
+        damage.send(
+            DamageEvent::new(
+                hit,
+                Damage::Kinetic(KineticDamage {
+                    mass: attack.mass,
+                    velocity: attack.velocity,
+                    shape: attack.shape,
+                }),
+            )
+            .source(entity, None),
+        );
+        npc.next_attack = now + attack.cooldown;
+    }
+}
@@ -0,0 +1,148 @@
+//! Sound effects played somewhere in the world.
+//!
+//! Server systems send [`PlaySound`] events. Each sound goes to the players whose observers
+//! see the grid cell it plays in and are within its range. Clients play it positioned relative to the listener.
+
+use bevy::{prelude::*, utils::HashSet};
+use networking::{
+    is_server,
+    messaging::{AppExt, MessageReceivers, MessageSender},
+    visibility::{global_grid_cell, NetworkObserver, NetworkObserverCells},
+    Players,
+};
+use serde::{Deserialize, Serialize};
+
+pub struct SoundPlugin;
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_network_message::<SoundMessage>();
+
+        if is_server(app) {
+            app.add_event::<PlaySound>()
+                .add_systems(PostUpdate, send_sounds);
+        } else {
+            #[cfg(feature = "client")]
+            app.add_systems(
+                Update,
+                client_play_sounds.run_if(in_state(crate::GameState::Game)),
+            );
+        }
+    }
+}
+
+/// How far sounds can be heard by default, in meters
+pub const DEFAULT_SOUND_RANGE: f32 = 15.0;
+/// Distance between the ears of the listener
+#[cfg(feature = "client")]
+const EAR_GAP: f32 = 0.3;
+
+/// Plays a sound for everyone in range of a position
+#[derive(Event, Clone)]
+pub struct PlaySound {
+    /// Path of the audio asset
+    pub asset: String,
+    pub position: Vec3,
+    /// Volume at the source, 1 is the asset's own volume
+    pub volume: f32,
+    /// Distance at which the sound fades out completely
+    pub range: f32,
+}
+
+impl PlaySound {
+    pub fn new(asset: impl Into<String>, position: Vec3) -> Self {
+        Self {
+            asset: asset.into(),
+            position,
+            volume: 1.0,
+            range: DEFAULT_SOUND_RANGE,
+        }
+    }
+
+    pub fn volume(mut self, volume: f32) -> Self {
+        self.volume = volume;
+        self
+    }
+
+    pub fn range(mut self, range: f32) -> Self {
+        self.range = range;
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SoundMessage {
+    asset: String,
+    position: Vec3,
+    volume: f32,
+    range: f32,
+}
+
+fn send_sounds(
+    mut sounds: EventReader<PlaySound>,
+    observers: Query<(&NetworkObserver, &NetworkObserverCells, &GlobalTransform)>,
+    players: Res<Players>,
+    mut sender: MessageSender,
+) {
+    for sound in sounds.iter() {
+        let cell = global_grid_cell(sound.position);
+        let receivers: HashSet<_> = observers
+            .iter()
+            .filter(|(_, cells, transform)| {
+                cells.observed_cells().any(|observed| observed == cell)
+                    && transform.translation().distance(sound.position) <= sound.range
+            })
+            .filter_map(|(observer, _, _)| players.get_connection(&observer.player_id))
+            .collect();
+        if receivers.is_empty() {
+            continue;
+        }
+
+        sender.send(
+            &SoundMessage {
+                asset: sound.asset.clone(),
+                position: sound.position,
+                volume: sound.volume,
+                range: sound.range,
+            },
+            MessageReceivers::Set(receivers),
+        );
+    }
+}
+
+/// Plays sounds from the server, heard from the controlled creature while facing like the camera
+#[cfg(feature = "client")]
+fn client_play_sounds(
+    mut messages: EventReader<networking::messaging::MessageEvent<SoundMessage>>,
+    camera: Query<&GlobalTransform, With<crate::camera::MainCamera>>,
+    controlled: Query<&GlobalTransform, With<networking::spawning::ClientControlled>>,
+    server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    use bevy::audio::{SpatialAudioBundle, SpatialSettings, Volume};
+
+    let Ok(camera) = camera.get_single() else {
+        messages.clear();
+        return;
+    };
+    let mut listener = camera.compute_transform();
+    if let Ok(creature) = controlled.get_single() {
+        listener.translation = creature.translation();
+    }
+
+    for event in messages.iter() {
+        let sound = &event.message;
+        let distance = listener.translation.distance(sound.position);
+        let falloff = 1.0 - distance / sound.range.max(f32::EPSILON);
+        if falloff <= 0.0 {
+            continue;
+        }
+
+        commands.spawn(SpatialAudioBundle {
+            source: server.load(sound.asset.as_str()),
+            settings: PlaybackSettings::DESPAWN
+                .with_volume(Volume::new_relative(sound.volume * falloff)),
+            spatial: SpatialSettings::new(listener, EAR_GAP, sound.position),
+        });
+    }
+}
@@ -0,0 +1,295 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::{Collider, CollisionGroups, QueryFilter, RapierContext};
+use maps::{Direction, MapCommandsExt, TileEntity, TileLayer, TileMap};
+use networking::{is_server, scene::NetworkSceneBundle};
+
+use crate::{
+    combat::damage::{Damage, DamageQueue, DamageStage},
+    despawn::{ContentsPolicy, DespawnCommandsExt},
+    interaction::{
+        ActiveInteraction, GenerateInteractionList, InteractionListEvents, InteractionOption,
+        InteractionSpecificity, InteractionStatus,
+    },
+    items::Item,
+};
+
+pub struct BarrierPlugin;
+
+impl Plugin for BarrierPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Deployable>()
+            .register_type::<Barrier>()
+            .register_type::<DeployInteraction>()
+            .register_type::<PackUpInteraction>();
+
+        if is_server(app) {
+            app.add_systems(
+                Update,
+                (
+                    (prepare_deploy_interaction, prepare_pack_up_interaction)
+                        .in_set(GenerateInteractionList),
+                    deploy_interaction,
+                    pack_up_interaction,
+                    damage_barriers.in_set(DamageStage::Apply),
+                ),
+            );
+        }
+    }
+}
+
+const DEPLOY_TIME: Duration = Duration::from_millis(1500);
+const PACK_UP_TIME: Duration = Duration::from_secs(3);
+
+/// An item that unfolds into a barrier when used on a floor.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+struct Deployable {
+    /// Scene of the furniture it turns into
+    barrier: String,
+}
+
+/// Furniture that blocks movement until it is packed up or destroyed.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+struct Barrier {
+    /// Scene of the item it packs up into
+    item: String,
+    /// Kinetic energy in joules it can take before breaking
+    health: f32,
+    /// Inflatables are deflated instead of folded
+    inflatable: bool,
+}
+
+/// Checks that nothing stands where a barrier would be deployed, ignoring loose items.
+fn tile_is_clear(
+    center: Vec3,
+    rapier: &RapierContext,
+    parents: &Query<&Parent>,
+    items: &Query<(), With<Item>>,
+) -> bool {
+    let shape = Collider::cuboid(0.45, 0.8, 0.45);
+    let filter = QueryFilter::new()
+        .groups(CollisionGroups::new(
+            physics::RAYCASTING_GROUP,
+            physics::DEFAULT_GROUP | physics::LIMB_GROUP,
+        ))
+        .predicate(&|entity| {
+            !std::iter::once(entity)
+                .chain(parents.iter_ancestors(entity))
+                .any(|e| items.contains(e))
+        });
+    let mut clear = true;
+    rapier.intersections_with_shape(
+        center + Vec3::Y * 0.9,
+        Quat::IDENTITY,
+        &shape,
+        filter,
+        |_| {
+            clear = false;
+            false
+        },
+    );
+    clear
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct DeployInteraction {
+    item: Entity,
+    floor: Entity,
+}
+
+impl FromWorld for DeployInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            item: Entity::PLACEHOLDER,
+            floor: Entity::PLACEHOLDER,
+        }
+    }
+}
+
+fn prepare_deploy_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    deployables: Query<&Item, With<Deployable>>,
+    floors: Query<&TileEntity>,
+    tilemaps: Query<&TileMap>,
+) {
+    for event in interaction_list.events.iter() {
+        let Some(item) = event.item_in_hand else {
+            continue;
+        };
+        let Ok(name) = deployables.get(item).map(|i| &i.name) else {
+            continue;
+        };
+        let Ok(floor) = floors.get(event.target) else {
+            continue;
+        };
+        if floor.layer() != TileLayer::Turf {
+            continue;
+        }
+        let free = tilemaps.get(floor.tilemap()).map_or(false, |map| {
+            map.validate_placement(floor.position(), TileLayer::Furniture, None, |_| false)
+                .is_ok()
+        });
+        if !free {
+            continue;
+        }
+
+        event.add_interaction(InteractionOption {
+            text: format!("Deploy {}", name),
+            interaction: Box::new(DeployInteraction {
+                item,
+                floor: event.target,
+            }),
+            specificity: InteractionSpecificity::Specific,
+        });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn deploy_interaction(
+    mut query: Query<(&DeployInteraction, &mut ActiveInteraction)>,
+    deployables: Query<&Deployable>,
+    floors: Query<(&TileEntity, &GlobalTransform)>,
+    tilemaps: Query<&TileMap>,
+    rapier: Res<RapierContext>,
+    parents: Query<&Parent>,
+    items: Query<(), With<Item>>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (interaction, mut active) in query.iter_mut() {
+        active.set_initial_duration(DEPLOY_TIME);
+
+        let Ok(deployable) = deployables.get(interaction.item) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        let Ok((floor, floor_transform)) = floors.get(interaction.floor) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+
+        if active.start_time() + DEPLOY_TIME.as_secs_f32() > time.elapsed_seconds() {
+            continue;
+        }
+
+        let placement = tilemaps.get(floor.tilemap()).map_or(false, |map| {
+            map.validate_placement(floor.position(), TileLayer::Furniture, None, |_| false)
+                .is_ok()
+        });
+        if !placement || !tile_is_clear(floor_transform.translation(), &rapier, &parents, &items) {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        }
+
+        commands.spawn_tile_entity(
+            floor.tilemap(),
+            floor.position(),
+            TileLayer::Furniture,
+            Direction::default(),
+            deployable.barrier.clone(),
+        );
+        commands.despawn_cascade(interaction.item, ContentsPolicy::Destroy);
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct PackUpInteraction {
+    barrier: Entity,
+}
+
+impl FromWorld for PackUpInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            barrier: Entity::PLACEHOLDER,
+        }
+    }
+}
+
+fn prepare_pack_up_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    barriers: Query<&Barrier>,
+) {
+    for event in interaction_list.events.iter() {
+        let Ok(barrier) = barriers.get(event.target) else {
+            continue;
+        };
+
+        event.add_interaction(InteractionOption {
+            text: if barrier.inflatable {
+                "Deflate".into()
+            } else {
+                "Fold up".into()
+            },
+            interaction: Box::new(PackUpInteraction {
+                barrier: event.target,
+            }),
+            specificity: InteractionSpecificity::Specific,
+        });
+    }
+}
+
+fn pack_up_interaction(
+    mut query: Query<(&PackUpInteraction, &mut ActiveInteraction)>,
+    barriers: Query<(&Barrier, &GlobalTransform)>,
+    server: Res<AssetServer>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (interaction, mut active) in query.iter_mut() {
+        active.set_initial_duration(PACK_UP_TIME);
+
+        let Ok((barrier, transform)) = barriers.get(interaction.barrier) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+
+        if active.start_time() + PACK_UP_TIME.as_secs_f32() > time.elapsed_seconds() {
+            continue;
+        }
+
+        commands.spawn(NetworkSceneBundle {
+            scene: server.load(barrier.item.as_str()).into(),
+            transform: Transform::from_translation(transform.translation() + Vec3::Y * 0.2),
+            ..Default::default()
+        });
+        commands.despawn_cascade(interaction.barrier, ContentsPolicy::Drop);
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+/// Wears barriers down by the energy of impacts and removes them once it's used up
+fn damage_barriers(
+    queue: Res<DamageQueue>,
+    parents: Query<&Parent>,
+    mut barriers: Query<(Entity, &mut Barrier)>,
+    mut commands: Commands,
+) {
+    for event in queue.iter() {
+        let Damage::Kinetic(kinetic) = event.damage else {
+            continue;
+        };
+        let Some(entity) = std::iter::once(event.target)
+            .chain(parents.iter_ancestors(event.target))
+            .find(|&e| barriers.contains(e))
+        else {
+            continue;
+        };
+        let Ok((entity, mut barrier)) = barriers.get_mut(entity) else {
+            continue;
+        };
+
+        barrier.health -= 0.5 * kinetic.mass * kinetic.velocity * kinetic.velocity;
+        if barrier.health <= 0.0 {
+            debug!(barrier = ?entity, "Barrier destroyed");
+            commands.despawn_cascade(entity, ContentsPolicy::Drop);
+        }
+    }
+}
@@ -4,7 +4,7 @@ use bevy::{
     ecs::{
         entity::{EntityMapper, MapEntities},
         reflect::ReflectMapEntities,
-        system::{EntityCommands, SystemParam},
+        system::SystemParam,
     },
     prelude::*,
     reflect::TypeUuid,
@@ -26,18 +26,32 @@ use serde::{Deserialize, Serialize};
 use utils::task::*;
 
 use crate::{
+    body::{
+        appearance::{Appearance, CharacterAppearance},
+        health::{
+            species::{LimbDefinition, SpeciesAssets, SpeciesDefinition, SpeciesVitals},
+            transplant::Genome,
+        },
+    },
+    controls::{Action, Actions},
     interaction::{
         ActiveInteraction, GenerateInteractionList, InteractionListEvents, InteractionListRequest,
-        InteractionOption, InteractionSpecificity, InteractionStatus,
+        InteractionOption, InteractionSpecificity, InteractionStatus, TargetSource,
     },
     items::{
-        containers::{Container, MoveItem},
+        containers::{Container, ItemReservations, MoveItem},
         Item, StoredItem, StoredItemClient,
     },
-    ui::has_window,
+    round::RoundRng,
+    ui::{
+        has_window,
+        toasts::{PlayerToasts, Toast, ToastLevel},
+    },
 };
 
-mod ghost;
+pub mod appearance;
+pub mod facing;
+pub mod ghost;
 pub mod health;
 
 pub struct BodyPlugin;
@@ -86,7 +100,12 @@ impl Plugin for BodyPlugin {
             );
         }
 
-        app.add_plugins((health::HealthPlugin, ghost::GhostPlugin));
+        app.add_plugins((
+            health::HealthPlugin,
+            ghost::GhostPlugin,
+            appearance::AppearancePlugin,
+            facing::FacingPlugin,
+        ));
 
         app.insert_resource(BodyAssets {
             scenes: app
@@ -106,6 +125,12 @@ pub struct Body {
     limbs_to_remove: Vec<Entity>,
 }
 
+impl Body {
+    pub fn limbs(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.limbs.iter().copied()
+    }
+}
+
 impl MapEntities for Body {
     fn map_entities(&mut self, entity_mapper: &mut EntityMapper) {
         self.limbs = self
@@ -135,16 +160,25 @@ impl fmt::Display for LimbSide {
 #[reflect(Component)]
 pub struct Limb {
     attachment_position: Vec3,
+    /// Area of the body armor has to cover to protect this limb, e.g. "torso" or "arms"
+    zone: String,
 }
 
 impl FromWorld for Limb {
     fn from_world(_: &mut World) -> Self {
         Self {
             attachment_position: Vec3::ZERO,
+            zone: "".into(),
         }
     }
 }
 
+impl Limb {
+    pub fn zone(&self) -> &str {
+        &self.zone
+    }
+}
+
 #[derive(Event)]
 struct LimbEvent {
     limb_entity: Entity,
@@ -159,16 +193,17 @@ enum LimbEventKind {
 
 fn process_new_limbs(
     mut bodies: Query<&mut Body, Changed<Body>>,
-    mut limbs: Query<(&Limb, &mut Transform)>,
+    mut limbs: Query<(&Limb, Option<&LimbAttachment>, &mut Transform)>,
     mut writer: EventWriter<LimbEvent>,
     mut commands: Commands,
 ) {
     for mut body in bodies.iter_mut() {
         body.added_limbs.retain(|&limb_entity| {
-            let Ok((limb, mut transform)) = limbs.get_mut(limb_entity) else {
+            let Ok((limb, attachment, mut transform)) = limbs.get_mut(limb_entity) else {
                 return true;
             };
-            transform.translation = limb.attachment_position;
+            transform.translation =
+                attachment.map_or(limb.attachment_position, |attachment| attachment.0);
             commands
                 .entity(limb_entity)
                 .freeze(Some(ColliderGroup::AttachedLimbs));
@@ -413,7 +448,10 @@ fn hand_ui(
                     } else if label.clicked_by(egui::PointerButton::Secondary) {
                         // Request interaction list on right-click
                         if let Some(target) = held_item_id {
-                            sender.send_to_server(&InteractionListRequest { target });
+                            sender.send_to_server(&InteractionListRequest {
+                                target,
+                                source: TargetSource::Ui,
+                            });
                         }
                     }
                 }
@@ -422,12 +460,12 @@ fn hand_ui(
 }
 
 fn client_hands_keybind(
-    keyboard_input: Res<Input<KeyCode>>,
+    actions: Actions,
     mut bodies: Query<(&Body, &mut HandsClient), With<ClientControlled>>,
     hands: Query<&NetworkIdentity, With<Hand>>,
     mut sender: MessageSender,
 ) {
-    if !keyboard_input.just_pressed(KeyCode::X) {
+    if !actions.just_pressed(Action::SwapHands) {
         return;
     }
 
@@ -491,6 +529,7 @@ struct BodyAssets {
 /// Task to create the body of a given creature archetype
 pub struct SpawnCreature {
     pub archetype: String,
+    pub appearance: CharacterAppearance,
 }
 
 impl Task for SpawnCreature {
@@ -501,101 +540,76 @@ pub struct SpawnCreatureResult {
     pub root: Entity,
 }
 
-fn spawn_limb<'w, 's, 'a: 'b, 'b: 'c, 'c>(
-    builder: &'b mut ChildBuilder<'w, 's, 'a>,
+/// Overrides the attachment position from the scene of a limb
+#[derive(Component)]
+struct LimbAttachment(Vec3);
+
+/// Spawns limbs with everything attached to them, collecting all spawned entities
+fn spawn_limbs(
+    builder: &mut ChildBuilder,
     server: &AssetServer,
-    name: &str,
-) -> EntityCommands<'w, 's, 'c> {
-    builder.spawn(NetworkSceneBundle {
-        scene: server.load(format!("creatures/{}.scn.ron", name)).into(),
-        ..Default::default()
-    })
+    definitions: &[LimbDefinition],
+    limbs: &mut HashSet<Entity>,
+) {
+    for definition in definitions {
+        let mut limb = builder.spawn(NetworkSceneBundle {
+            scene: server.load(definition.scene.as_str()).into(),
+            ..Default::default()
+        });
+        if let Some(attachment) = definition.attachment {
+            limb.insert(LimbAttachment(attachment));
+        }
+        limb.with_children(|builder| {
+            spawn_limbs(builder, server, &definition.children, limbs);
+        });
+        limbs.insert(limb.id());
+    }
 }
 
 fn create_creature(
     mut tasks: ResMut<Tasks<SpawnCreature>>,
     server: Res<AssetServer>,
+    species: Res<SpeciesAssets>,
+    species_definitions: Res<Assets<SpeciesDefinition>>,
+    mut rng: Option<ResMut<RoundRng>>,
     mut commands: Commands,
 ) {
     tasks.process(|data| {
-        let mut creature = commands.spawn(NetworkSceneBundle {
-            scene: server.load("creatures/player.scn.ron").into(),
+        let definition = species.get(&data.archetype, &species_definitions);
+        if definition.is_none() {
+            warn!(
+                species = data.archetype.as_str(),
+                "Missing species definition"
+            );
+        }
+
+        let body_scene = definition.map_or("creatures/player.scn.ron", |d| d.body.as_str());
+        let vitals = definition.map_or_else(SpeciesVitals::default, |d| d.vitals.clone());
+        let mut creature = commands.spawn((
+            NetworkSceneBundle {
+                scene: server.load(body_scene).into(),
+                ..Default::default()
+            },
+            vitals,
+            Appearance::from(data.appearance),
+            Genome {
+                species: data.archetype.clone(),
+                dna: rng.as_mut().map(|rng| rng.next_u64()).unwrap_or_default(),
+            },
+        ));
+
+        let mut limbs = HashSet::default();
+        if let Some(definition) = definition {
+            creature.with_children(|builder| {
+                spawn_limbs(builder, server.as_ref(), &definition.limbs, &mut limbs);
+            });
+        }
+        let added_limbs = limbs.iter().copied().collect();
+        creature.insert(Body {
+            limbs,
+            added_limbs,
             ..Default::default()
         });
-        // TODO: Replace with species configuration in assets
-        match data.archetype.as_str() {
-            "human" => {
-                let mut limbs = HashSet::default();
-                creature.with_children(|builder| {
-                    let torso = spawn_limb(builder, server.as_ref(), "human_torso")
-                        .with_children(|builder| {
-                            // Head
-                            let head = spawn_limb(builder, server.as_ref(), "human_head")
-                                .with_children(|builder| {
-                                    let brain =
-                                        spawn_limb(builder, server.as_ref(), "organic_brain").id();
-                                    limbs.insert(brain);
-                                })
-                                .id();
-                            limbs.insert(head);
-
-                            // Arms
-                            let arm_left = spawn_limb(builder, server.as_ref(), "human_arm_left")
-                                .with_children(|builder| {
-                                    let hand_left =
-                                        spawn_limb(builder, server.as_ref(), "human_hand_left")
-                                            .id();
-                                    limbs.insert(hand_left);
-                                })
-                                .id();
-                            limbs.insert(arm_left);
-                            let arm_right = spawn_limb(builder, server.as_ref(), "human_arm_right")
-                                .with_children(|builder| {
-                                    let hand_right =
-                                        spawn_limb(builder, server.as_ref(), "human_hand_right")
-                                            .id();
-                                    limbs.insert(hand_right);
-                                })
-                                .id();
-                            limbs.insert(arm_right);
-
-                            // Legs
-                            let leg_left = spawn_limb(builder, server.as_ref(), "human_leg_left")
-                                .with_children(|builder| {
-                                    let foot_left =
-                                        spawn_limb(builder, server.as_ref(), "human_foot_left")
-                                            .id();
-                                    limbs.insert(foot_left);
-                                })
-                                .id();
-                            limbs.insert(leg_left);
-                            let leg_right = spawn_limb(builder, server.as_ref(), "human_leg_right")
-                                .with_children(|builder| {
-                                    let foot_right =
-                                        spawn_limb(builder, server.as_ref(), "human_foot_right")
-                                            .id();
-                                    limbs.insert(foot_right);
-                                })
-                                .id();
-                            limbs.insert(leg_right);
-
-                            let heart = spawn_limb(builder, server.as_ref(), "organic_heart").id();
-                            limbs.insert(heart);
-                            let lung = spawn_limb(builder, server.as_ref(), "organic_lung").id();
-                            limbs.insert(lung);
-                        })
-                        .id();
-                    limbs.insert(torso);
-                });
-                let added_limbs = limbs.iter().copied().collect();
-                creature.insert(Body {
-                    limbs,
-                    added_limbs,
-                    ..Default::default()
-                });
-            }
-            _ => todo!(),
-        }
 
         bevy::log::info!("Created creature");
         SpawnCreatureResult {
@@ -630,12 +644,17 @@ fn prepare_pickup_interaction(
     items: Query<&Item>,
     bodies: Query<(&Body, &Hands)>,
     hand_query: Query<(&Hand, &Container)>,
+    reservations: Res<ItemReservations>,
 ) {
     for event in interaction_lists.events.iter() {
         let Ok(_) = items.get(event.target) else {
             continue;
         };
 
+        if reservations.is_reserved_by_other(event.target, event.source) {
+            continue;
+        }
+
         let Ok((body, hands)) = bodies.get(event.source) else {
             continue;
         };
@@ -667,6 +686,8 @@ fn pickup_interaction(
     hands: Query<&Hands>,
     hand_query: Query<(Entity, &Hand, &Container)>,
     mut item_moves: ResMut<Tasks<MoveItem>>,
+    mut reservations: ResMut<ItemReservations>,
+    mut toasts: PlayerToasts,
 ) {
     for (source, mut interaction, mut active) in query.iter_mut() {
         if interaction.move_task.is_some() {
@@ -693,6 +714,19 @@ fn pickup_interaction(
             continue;
         }
 
+        if !reservations.reserve(active.target, source, &active) {
+            debug!(item = ?active.target, "Someone else grabbed the item first");
+            toasts.send(
+                source,
+                Toast::new(
+                    ToastLevel::Warning,
+                    "Someone else is already picking that up.",
+                ),
+            );
+            active.status = InteractionStatus::Canceled;
+            continue;
+        }
+
         // Creating a task to move the target item
         let id = item_moves.create(MoveItem {
             item: active.target,
@@ -703,7 +737,7 @@ fn pickup_interaction(
     }
 
     // Check for completed container moves
-    for (_, interaction, mut active) in query.iter_mut() {
+    for (source, interaction, mut active) in query.iter_mut() {
         let Some(task) = interaction.move_task else {
             continue;
         };
@@ -711,6 +745,12 @@ fn pickup_interaction(
             active.status = if result.was_success() {
                 InteractionStatus::Completed
             } else {
+                let text = if result.was_taken() {
+                    "Someone else grabbed it first."
+                } else {
+                    "You couldn't pick that up."
+                };
+                toasts.send(source, Toast::new(ToastLevel::Warning, text));
                 InteractionStatus::Canceled
             };
         }
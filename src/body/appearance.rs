@@ -0,0 +1,166 @@
+//! How a creature looks, as chosen during character creation.
+
+use bevy::{ecs::query::Has, prelude::*, reflect::TypeUuid};
+use networking::{
+    component::AppExt,
+    is_server,
+    variable::{NetworkVar, ServerVar},
+    Networked,
+};
+use serde::{Deserialize, Serialize};
+
+pub struct AppearancePlugin;
+
+impl Plugin for AppearancePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Skin>()
+            .register_type::<Hair>()
+            .add_networked_component::<Appearance, AppearanceClient>();
+
+        if !is_server(app) {
+            app.add_systems(
+                Update,
+                (update_appearance_materials, tint_body_parts).chain(),
+            );
+        }
+    }
+}
+
+/// Appearance choices stored in a character profile
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CharacterAppearance {
+    pub skin_color: [u8; 3],
+    pub hair_color: [u8; 3],
+}
+
+impl Default for CharacterAppearance {
+    fn default() -> Self {
+        Self {
+            skin_color: [224, 172, 140],
+            hair_color: [70, 45, 30],
+        }
+    }
+}
+
+/// Appearance of a creature, placed on its root entity
+#[derive(Component, Networked)]
+#[networked(client = "AppearanceClient")]
+pub struct Appearance {
+    skin_color: NetworkVar<[u8; 3]>,
+    hair_color: NetworkVar<[u8; 3]>,
+}
+
+impl From<CharacterAppearance> for Appearance {
+    fn from(appearance: CharacterAppearance) -> Self {
+        Self {
+            skin_color: appearance.skin_color.into(),
+            hair_color: appearance.hair_color.into(),
+        }
+    }
+}
+
+#[derive(Component, Default, TypeUuid, Networked)]
+#[uuid = "4c8d2a6e-91f3-4b57-a0e2-7d3b5f18c96a"]
+#[networked(server = "Appearance")]
+pub struct AppearanceClient {
+    skin_color: ServerVar<[u8; 3]>,
+    hair_color: ServerVar<[u8; 3]>,
+}
+
+/// Part of a creature tinted with its skin color
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
+pub struct Skin;
+
+/// Part of a creature tinted with its hair color
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
+pub struct Hair;
+
+/// Materials shared by all parts of one creature
+#[derive(Component, Default)]
+struct AppearanceMaterials {
+    skin: Option<Handle<StandardMaterial>>,
+    hair: Option<Handle<StandardMaterial>>,
+}
+
+/// Marks parts which already use the material of their creature.
+/// Detached limbs keep it, so they keep the color of who they belonged to.
+#[derive(Component)]
+struct Tinted;
+
+fn to_color([r, g, b]: [u8; 3]) -> Color {
+    Color::rgb_u8(r, g, b)
+}
+
+fn update_appearance_materials(
+    creatures: Query<
+        (Entity, &AppearanceClient, Option<&AppearanceMaterials>),
+        Changed<AppearanceClient>,
+    >,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut commands: Commands,
+) {
+    for (entity, appearance, appearance_materials) in creatures.iter() {
+        let Some(appearance_materials) = appearance_materials else {
+            commands
+                .entity(entity)
+                .insert(AppearanceMaterials::default());
+            continue;
+        };
+
+        let pairs = [
+            (&appearance_materials.skin, *appearance.skin_color),
+            (&appearance_materials.hair, *appearance.hair_color),
+        ];
+        for (handle, color) in pairs {
+            if let Some(material) = handle.as_ref().and_then(|h| materials.get_mut(h)) {
+                material.base_color = to_color(color);
+            }
+        }
+    }
+}
+
+fn tint_body_parts(
+    parts: Query<
+        (Entity, &Handle<StandardMaterial>, Has<Hair>),
+        (Or<(With<Skin>, With<Hair>)>, Without<Tinted>),
+    >,
+    parents: Query<&Parent>,
+    mut creatures: Query<(&AppearanceClient, &mut AppearanceMaterials)>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut commands: Commands,
+) {
+    for (entity, base_handle, is_hair) in parts.iter() {
+        let Some((appearance, mut appearance_materials)) = parents
+            .iter_ancestors(entity)
+            .find(|&e| creatures.contains(e))
+            .and_then(|e| creatures.get_mut(e).ok())
+        else {
+            continue;
+        };
+
+        let (slot, color) = if is_hair {
+            (&mut appearance_materials.hair, *appearance.hair_color)
+        } else {
+            (&mut appearance_materials.skin, *appearance.skin_color)
+        };
+        let handle = match slot {
+            Some(handle) => handle.clone(),
+            None => {
+                // Base the creature material on the one from the model, once it's loaded
+                let Some(base) = materials.get(base_handle) else {
+                    continue;
+                };
+                let handle = materials.add(StandardMaterial {
+                    base_color: to_color(color),
+                    ..base.clone()
+                });
+                *slot = Some(handle.clone());
+                handle
+            }
+        };
+
+        commands.entity(entity).insert((handle, Tinted));
+    }
+}
@@ -0,0 +1,200 @@
+//! Where characters are looking, so everyone can tell who is looking at what.
+//!
+//! The controlling client sends its look direction at a limited rate, towards the cursor
+//! or where it aims in combat. The server turns the head towards it as far as a neck allows,
+//! and the head's transform is replicated and interpolated like any other.
+//! Standing characters turn their whole body once they look further than the head can turn.
+
+use std::f32::consts::PI;
+
+use bevy::{math::Vec3Swizzles, prelude::*, window::PrimaryWindow};
+use networking::{
+    is_server,
+    messaging::{AppExt, MessageEvent, MessageSender},
+    spawning::{ClientControlled, ClientControls},
+    transform::ClientMovementClient,
+    Players,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camera::MainCamera,
+    combat::{ClientCombatModeStatus, CombatModeClient},
+    movement::MovementSystem,
+};
+
+use super::{Body, Limb};
+
+pub struct FacingPlugin;
+
+impl Plugin for FacingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_network_message::<FacingMessage>();
+
+        if is_server(app) {
+            app.add_systems(Update, (receive_facing, turn_heads).chain());
+        } else {
+            app.init_resource::<ClientFacing>()
+                .add_systems(Update, client_update_facing.before(MovementSystem::Update));
+        }
+    }
+}
+
+/// Furthest a head turns away from where the body faces, in radians
+const HEAD_TURN_LIMIT: f32 = 1.2;
+/// Seconds between look direction updates sent to the server
+const FACING_SEND_INTERVAL: f32 = 0.1;
+/// Smallest change in radians worth sending
+const FACING_MIN_CHANGE: f32 = 0.05;
+
+/// Where a character is looking, as a yaw in radians around the up axis
+#[derive(Component, Default)]
+pub struct Facing {
+    pub yaw: f32,
+}
+
+/// Yaw that turns the forward axis towards a direction on the ground
+fn yaw_of(direction: Vec2) -> f32 {
+    direction.x.atan2(direction.y)
+}
+
+/// Yaw of where a body with this rotation faces, bodies face their local Z axis
+fn body_yaw(rotation: Quat) -> f32 {
+    yaw_of((rotation * Vec3::Z).xz())
+}
+
+/// Wraps an angle into -PI..PI
+fn wrap_angle(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(2.0 * PI) - PI
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+struct FacingMessage {
+    yaw: f32,
+}
+
+fn receive_facing(
+    mut messages: EventReader<MessageEvent<FacingMessage>>,
+    players: Res<Players>,
+    controls: Res<ClientControls>,
+    mut facings: Query<&mut Facing>,
+    bodies: Query<(), With<Body>>,
+    mut commands: Commands,
+) {
+    for event in messages.iter() {
+        let yaw = event.message.yaw;
+        if !yaw.is_finite() {
+            continue;
+        }
+        let Some(entity) = players
+            .get(event.connection)
+            .and_then(|player| controls.controlled_entity(player.id))
+        else {
+            continue;
+        };
+        if !bodies.contains(entity) {
+            continue;
+        }
+
+        let yaw = wrap_angle(yaw);
+        match facings.get_mut(entity) {
+            Ok(mut facing) => facing.yaw = yaw,
+            Err(_) => {
+                commands.entity(entity).insert(Facing { yaw });
+            }
+        }
+    }
+}
+
+/// Turns heads towards where their body is looking, within the turn limit
+fn turn_heads(
+    facings: Query<(&Facing, &Body, &GlobalTransform)>,
+    mut heads: Query<(&Limb, &mut Transform)>,
+) {
+    for (facing, body, body_transform) in facings.iter() {
+        let (_, body_rotation, _) = body_transform.to_scale_rotation_translation();
+        let turn = wrap_angle(facing.yaw - body_yaw(body_rotation))
+            .clamp(-HEAD_TURN_LIMIT, HEAD_TURN_LIMIT);
+        let rotation = Quat::from_rotation_y(turn);
+
+        for limb in body.limbs() {
+            let Ok((limb, mut transform)) = heads.get_mut(limb) else {
+                continue;
+            };
+            if limb.zone() != "head" || transform.rotation.abs_diff_eq(rotation, 0.001) {
+                continue;
+            }
+            transform.rotation = rotation;
+        }
+    }
+}
+
+/// Look direction of the controlled character on this client
+#[derive(Resource, Default)]
+pub(crate) struct ClientFacing {
+    yaw: Option<f32>,
+    last_sent: Option<(f32, f32)>,
+}
+
+impl ClientFacing {
+    /// Direction to turn the body towards while standing, if the head can't turn that far
+    pub(crate) fn idle_turn(&self, body_rotation: Quat) -> Option<Vec2> {
+        let yaw = self.yaw?;
+        (wrap_angle(yaw - body_yaw(body_rotation)).abs() > HEAD_TURN_LIMIT)
+            .then(|| Vec2::new(yaw.sin(), yaw.cos()))
+    }
+}
+
+/// Looks towards the cursor, or the aim in combat, and tells the server now and then
+fn client_update_facing(
+    controlled: Query<
+        (&GlobalTransform, Option<&CombatModeClient>),
+        (With<ClientControlled>, With<ClientMovementClient>),
+    >,
+    combat_mode: ClientCombatModeStatus,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut facing: ResMut<ClientFacing>,
+    time: Res<Time>,
+    mut sender: MessageSender,
+) {
+    let Ok((transform, combat)) = controlled.get_single() else {
+        facing.yaw = None;
+        return;
+    };
+    let position = transform.translation();
+
+    let target = match combat {
+        Some(combat) if combat_mode.is_enabled() => Some(combat.aim.target_position),
+        _ => {
+            let cursor = windows.get_single().ok().and_then(|w| w.cursor_position());
+            cursor
+                .zip(cameras.iter().next())
+                .and_then(|(cursor, (camera, camera_transform))| {
+                    camera.viewport_to_world(camera_transform, cursor)
+                })
+                .and_then(|ray| {
+                    let distance = ray.intersect_plane(position, Vec3::Y)?;
+                    Some(ray.get_point(distance))
+                })
+        }
+    };
+    let Some(direction) = target
+        .map(|target| (target - position).xz())
+        .filter(|direction| direction.length_squared() > 0.01)
+    else {
+        return;
+    };
+
+    let yaw = yaw_of(direction);
+    facing.yaw = Some(yaw);
+
+    let now = time.elapsed_seconds();
+    let due = facing.last_sent.map_or(true, |(at, sent_yaw)| {
+        at + FACING_SEND_INTERVAL <= now && wrap_angle(yaw - sent_yaw).abs() >= FACING_MIN_CHANGE
+    });
+    if due {
+        facing.last_sent = Some((now, yaw));
+        sender.send_to_server(&FacingMessage { yaw });
+    }
+}
@@ -1,14 +1,40 @@
-use bevy::{prelude::*, utils::HashMap};
+//! Ghosts are what players control after their character died or was abandoned.
+//!
+//! Ghosts fly through walls and see further than the living. They can follow other players,
+//! jump anywhere on the map they can see, and join the round again once their respawn timer ran out.
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, Uuid},
+    window::PrimaryWindow,
+};
+use bevy_egui::{egui, EguiContexts};
 use networking::{
+    identity::{NetworkIdentities, NetworkIdentity},
     is_server,
-    messaging::{MessageReceivers, MessageSender},
+    messaging::{AppExt, MessageEvent, MessageReceivers, MessageSender},
     scene::NetworkSceneBundle,
-    spawning::ClientControls,
+    spawning::{ClientControlled, ClientControls},
     visibility::{NetworkObserver, NetworkObserverBundle},
     Players,
 };
+use serde::{Deserialize, Serialize};
 
-use crate::movement::ForcePositionMessage;
+use crate::{
+    camera::MainCamera,
+    communication::{
+        event_log::EventLog,
+        examine::{DescribeExamined, ExamineEvents},
+    },
+    config::ServerConfig,
+    controls::{Action, Actions},
+    job::SelectedJobs,
+    movement::ForcePositionMessage,
+    round::{RequestJoin, RoundState},
+    spectator::ClientSpectator,
+    ui::has_window,
+    GameState,
+};
 
 use super::{
     health::{BrainState, BrainStateEvent},
@@ -19,30 +45,189 @@ pub struct GhostPlugin;
 
 impl Plugin for GhostPlugin {
     fn build(&self, app: &mut App) {
+        app.add_network_message::<AbandonCharacterMessage>()
+            .add_network_message::<RespawnTimerMessage>();
+
         if is_server(app) {
-            app.init_resource::<Ghosts>().add_systems(
-                Update,
-                (create_ghost, return_to_body).run_if(on_event::<BrainStateEvent>()),
-            );
+            app.init_resource::<Ghosts>()
+                .init_resource::<RespawnTimers>()
+                .add_systems(
+                    Update,
+                    (
+                        (create_ghost, return_to_body).run_if(on_event::<BrainStateEvent>()),
+                        handle_abandon_message,
+                        despawn_unused_ghosts,
+                        describe_catatonic.in_set(DescribeExamined),
+                    ),
+                )
+                .add_systems(OnEnter(RoundState::Restarting), clear_ghosts);
+        } else {
+            app.init_resource::<ClientRespawn>()
+                .init_resource::<GhostFollowing>()
+                .add_systems(
+                    Update,
+                    (
+                        client_receive_respawn_timer,
+                        (client_abandon_ui, client_respawn_ui, client_ghost_jump)
+                            .run_if(has_window)
+                            .run_if(in_state(GameState::Game)),
+                        client_ghost_follow,
+                    )
+                        .chain(),
+                );
         }
     }
 }
 
+/// Grid cells around a ghost it observes, more than the living as walls don't block its view
+const GHOST_OBSERVER_RANGE: u32 = 2;
+
+/// A creature a player controls after leaving their body
+#[derive(Component)]
+pub(crate) struct Ghost {
+    player: Uuid,
+}
+
+impl Ghost {
+    pub(crate) fn player(&self) -> Uuid {
+        self.player
+    }
+}
+
+/// Marks a body whose player abandoned it. Nobody will return to it.
+#[derive(Component)]
+pub(crate) struct Catatonic;
+
 #[derive(Resource, Default)]
 struct Ghosts {
     brain_to_ghost: HashMap<Entity, Entity>,
 }
 
+/// When ghosts may join the round again and how often players respawned this round
+#[derive(Resource, Default)]
+pub(crate) struct RespawnTimers {
+    /// Seconds since startup
+    available_at: HashMap<Uuid, f32>,
+    respawns: HashMap<Uuid, u32>,
+}
+
+impl RespawnTimers {
+    pub(crate) fn can_respawn(&self, player: Uuid, now: f32) -> bool {
+        self.available_at
+            .get(&player)
+            .map_or(false, |&at| at <= now)
+    }
+
+    /// Respawns the player has left this round, if they are limited
+    pub(crate) fn respawns_left(&self, player: Uuid, config: &ServerConfig) -> Option<u32> {
+        let used = self.respawns.get(&player).copied().unwrap_or_default();
+        config
+            .respawn
+            .max_respawns
+            .map(|max| max.saturating_sub(used))
+    }
+
+    pub(crate) fn record_respawn(&mut self, player: Uuid) {
+        *self.respawns.entry(player).or_default() += 1;
+    }
+}
+
+/// Client message to give up on the current character and become a ghost
+#[derive(Serialize, Deserialize)]
+pub struct AbandonCharacterMessage;
+
+/// Tells a ghost when it may respawn, or that it no longer is a ghost
+#[derive(Serialize, Deserialize)]
+struct RespawnTimerMessage {
+    /// Seconds until respawning is possible
+    seconds: Option<f32>,
+    /// The job was freed and a new one must be picked
+    pick_job: bool,
+    /// Respawns left this round, if they are limited
+    respawns_left: Option<u32>,
+}
+
+fn spawn_ghost(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    player: Uuid,
+    position: Vec3,
+) -> Entity {
+    commands
+        .spawn((
+            NetworkSceneBundle {
+                scene: asset_server.load("creatures/ghost.scn.ron").into(),
+                transform: Transform::from_translation(position),
+                ..Default::default()
+            },
+            NetworkObserverBundle {
+                observer: NetworkObserver {
+                    range: GHOST_OBSERVER_RANGE,
+                    player_id: player,
+                },
+                cells: Default::default(),
+            },
+            networking::transform::ClientMovement,
+            Ghost { player },
+        ))
+        .id()
+}
+
+/// Moves the player to their ghost and starts the respawn timer
+#[allow(clippy::too_many_arguments)]
+fn become_ghost(
+    player: Uuid,
+    ghost: Entity,
+    position: Vec3,
+    pick_job: bool,
+    controls: &mut ClientControls,
+    timers: &mut RespawnTimers,
+    config: &ServerConfig,
+    players: &Players,
+    now: f32,
+    sender: &mut MessageSender,
+) {
+    controls.give_control(player, ghost);
+
+    let delay = config.respawn.delay().as_secs_f32();
+    timers.available_at.insert(player, now + delay);
+
+    let Some(connection) = players.get_connection(&player) else {
+        return;
+    };
+    // Set new position
+    // Holy shit server-movement when
+    sender.send_with_priority(
+        &ForcePositionMessage {
+            position,
+            rotation: Quat::IDENTITY,
+        },
+        MessageReceivers::Single(connection),
+        10,
+    );
+    sender.send(
+        &RespawnTimerMessage {
+            seconds: Some(delay),
+            pick_job,
+            respawns_left: timers.respawns_left(player, config),
+        },
+        MessageReceivers::Single(connection),
+    );
+}
+
 #[allow(clippy::too_many_arguments)]
 fn create_ghost(
     mut brain_events: EventReader<BrainStateEvent>,
     mut ghosts: ResMut<Ghosts>,
     mut controls: ResMut<ClientControls>,
+    mut timers: ResMut<RespawnTimers>,
     parents: Query<&Parent>,
     bodies: Query<(), With<Body>>,
     asset_server: Res<AssetServer>,
     players: Res<Players>,
+    config: Res<ServerConfig>,
     global_transforms: Query<&GlobalTransform>,
+    time: Res<Time>,
     mut commands: Commands,
     mut sender: MessageSender,
 ) {
@@ -68,45 +253,23 @@ fn create_ghost(
         };
 
         // Spawn ghost if it doesnt exist
-        if !ghosts.brain_to_ghost.contains_key(&event.brain) {
-            let ghost = commands
-                .spawn((
-                    NetworkSceneBundle {
-                        scene: asset_server.load("creatures/ghost.scn.ron").into(),
-                        transform: Transform::from_translation(position),
-                        ..Default::default()
-                    },
-                    NetworkObserverBundle {
-                        observer: NetworkObserver {
-                            range: 1,
-                            player_id: player,
-                        },
-                        cells: Default::default(),
-                    },
-                    networking::transform::ClientMovement,
-                ))
-                .id();
-            ghosts.brain_to_ghost.insert(event.brain, ghost);
-        }
+        let ghost = *ghosts
+            .brain_to_ghost
+            .entry(event.brain)
+            .or_insert_with(|| spawn_ghost(&mut commands, &asset_server, player, position));
 
-        // Set player to control ghost
-        controls.give_control(
+        become_ghost(
             player,
-            ghosts.brain_to_ghost.get(&event.brain).copied().unwrap(),
+            ghost,
+            position,
+            false,
+            &mut controls,
+            &mut timers,
+            &config,
+            &players,
+            time.elapsed_seconds(),
+            &mut sender,
         );
-
-        // Set new position
-        // Holy shit server-movement when
-        if let Some(connection) = players.get_connection(&player) {
-            sender.send_with_priority(
-                &ForcePositionMessage {
-                    position,
-                    rotation: Quat::IDENTITY,
-                },
-                MessageReceivers::Single(connection),
-                10,
-            );
-        }
     }
 }
 
@@ -116,7 +279,6 @@ fn return_to_body(
     mut controls: ResMut<ClientControls>,
     parents: Query<&Parent>,
     bodies: Query<(), With<Body>>,
-    mut commands: Commands,
 ) {
     for event in brain_events.iter() {
         if event.new_state == BrainState::Dead {
@@ -138,7 +300,337 @@ fn return_to_body(
             continue;
         };
 
+        // The ghost is cleaned up once nobody controls it
         controls.give_control(player, body_entity);
-        commands.entity(ghost_entity).despawn_recursive();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_abandon_message(
+    mut messages: EventReader<MessageEvent<AbandonCharacterMessage>>,
+    mut controls: ResMut<ClientControls>,
+    mut timers: ResMut<RespawnTimers>,
+    mut selected_jobs: ResMut<SelectedJobs>,
+    bodies: Query<&GlobalTransform, (With<Body>, Without<Ghost>)>,
+    asset_server: Res<AssetServer>,
+    players: Res<Players>,
+    config: Res<ServerConfig>,
+    time: Res<Time>,
+    mut commands: Commands,
+    mut sender: MessageSender,
+) {
+    for event in messages.iter() {
+        let Some(player) = players.get(event.connection) else {
+            continue;
+        };
+        let Some(body) = controls.controlled_entity(player.id) else {
+            continue;
+        };
+        let Ok(position) = bodies.get(body).map(|t| t.translation()) else {
+            continue;
+        };
+
+        info!(player = player.username, "Player abandoned their character");
+        commands.entity(body).insert(Catatonic);
+        let free_job = config.respawn.free_job_slot;
+        if free_job {
+            selected_jobs.set(event.connection, None);
+        }
+
+        let ghost = spawn_ghost(&mut commands, &asset_server, player.id, position);
+        become_ghost(
+            player.id,
+            ghost,
+            position,
+            free_job,
+            &mut controls,
+            &mut timers,
+            &config,
+            &players,
+            time.elapsed_seconds(),
+            &mut sender,
+        );
+    }
+}
+
+/// Removes ghosts whose player went back to a body or respawned
+fn despawn_unused_ghosts(
+    ghost_query: Query<(Entity, &Ghost)>,
+    controls: Res<ClientControls>,
+    mut ghosts: ResMut<Ghosts>,
+    mut timers: ResMut<RespawnTimers>,
+    players: Res<Players>,
+    mut commands: Commands,
+    mut sender: MessageSender,
+) {
+    for (entity, ghost) in ghost_query.iter() {
+        if controls.does_control(ghost.player, entity) {
+            continue;
+        }
+
+        commands.entity(entity).despawn_recursive();
+        ghosts.brain_to_ghost.retain(|_, &mut g| g != entity);
+        timers.available_at.remove(&ghost.player);
+        if let Some(connection) = players.get_connection(&ghost.player) {
+            sender.send(
+                &RespawnTimerMessage {
+                    seconds: None,
+                    pick_job: false,
+                    respawns_left: None,
+                },
+                MessageReceivers::Single(connection),
+            );
+        }
+    }
+}
+
+fn clear_ghosts(
+    mut ghosts: ResMut<Ghosts>,
+    mut timers: ResMut<RespawnTimers>,
+    mut sender: MessageSender,
+) {
+    ghosts.brain_to_ghost.clear();
+    *timers = Default::default();
+    sender.send(
+        &RespawnTimerMessage {
+            seconds: None,
+            pick_job: false,
+            respawns_left: None,
+        },
+        MessageReceivers::AllPlayers,
+    );
+}
+
+/// Respawn state of the local player, if they are a ghost
+#[derive(Resource, Default)]
+pub(crate) struct ClientRespawn {
+    /// Seconds since startup when respawning is possible
+    available_at: Option<f32>,
+    pick_job: bool,
+    respawns_left: Option<u32>,
+}
+
+impl ClientRespawn {
+    /// If the local player is a ghost
+    pub(crate) fn is_ghost(&self) -> bool {
+        self.available_at.is_some()
+    }
+
+    /// If the player is a ghost that needs to pick a new job
+    pub(crate) fn pick_job(&self) -> bool {
+        self.available_at.is_some() && self.pick_job
+    }
+}
+
+fn client_receive_respawn_timer(
+    mut messages: EventReader<MessageEvent<RespawnTimerMessage>>,
+    mut respawn: ResMut<ClientRespawn>,
+    mut log: ResMut<EventLog>,
+    time: Res<Time>,
+) {
+    for event in messages.iter() {
+        match event.message.seconds {
+            Some(seconds) if respawn.available_at.is_none() => log.record(&format!(
+                "You died. You can respawn in {:.0} seconds.",
+                seconds
+            )),
+            None if respawn.available_at.is_some() => log.record("You are back in a body."),
+            _ => {}
+        }
+        respawn.available_at = event
+            .message
+            .seconds
+            .map(|seconds| time.elapsed_seconds() + seconds);
+        respawn.pick_job = event.message.pick_job;
+        respawn.respawns_left = event.message.respawns_left;
+    }
+}
+
+fn client_abandon_ui(
+    mut contexts: EguiContexts,
+    respawn: Res<ClientRespawn>,
+    controlled: Query<(), With<ClientControlled>>,
+    mut confirming: Local<bool>,
+    mut sender: MessageSender,
+) {
+    if controlled.is_empty() || respawn.available_at.is_some() {
+        *confirming = false;
+        return;
+    }
+
+    egui::Window::new("Character")
+        .default_open(false)
+        .show(contexts.ctx_mut(), |ui| {
+            if ui.button("Abandon character").clicked() {
+                *confirming = true;
+            }
+        });
+
+    if !*confirming {
+        return;
+    }
+    egui::Window::new("Abandon character?")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label("You will become a ghost and can't return to this character.");
+            ui.label("You can join again as a new character after a while.");
+            ui.horizontal(|ui| {
+                if ui.button("Abandon").clicked() {
+                    sender.send_to_server(&AbandonCharacterMessage);
+                    *confirming = false;
+                }
+                if ui.button("Cancel").clicked() {
+                    *confirming = false;
+                }
+            });
+        });
+}
+
+fn client_respawn_ui(
+    mut contexts: EguiContexts,
+    respawn: Res<ClientRespawn>,
+    spectator: Res<ClientSpectator>,
+    mut following: ResMut<GhostFollowing>,
+    time: Res<Time>,
+    mut sender: MessageSender,
+) {
+    let Some(available_at) = respawn.available_at else {
+        return;
+    };
+
+    egui::Window::new("Ghost")
+        .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 30.0))
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            let targets = spectator.targets();
+            if targets.is_empty() {
+                ui.label("Nobody to follow");
+            } else {
+                let selected = following
+                    .0
+                    .and_then(|identity| targets.iter().find(|(i, _)| *i == identity))
+                    .map_or("Nobody", |(_, name)| name.as_str());
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_label("Follow")
+                        .selected_text(selected)
+                        .show_ui(ui, |ui| {
+                            for (identity, name) in targets.iter() {
+                                let current = following.0 == Some(*identity);
+                                if ui.selectable_label(current, name).clicked() {
+                                    following.0 = Some(*identity);
+                                }
+                            }
+                        });
+                    if following.0.is_some() && ui.small_button("Stop").clicked() {
+                        following.0 = None;
+                    }
+                });
+            }
+            ui.separator();
+
+            let remaining = available_at - time.elapsed_seconds();
+            if remaining > 0.0 {
+                ui.label(format!("You can respawn in {} seconds", remaining.ceil()));
+                return;
+            }
+            match respawn.respawns_left {
+                Some(0) => {
+                    ui.label("You have no respawns left this round");
+                    return;
+                }
+                Some(left) => {
+                    ui.small(format!("{} respawns left this round", left));
+                }
+                None => {}
+            }
+            if respawn.pick_job {
+                ui.small("Pick a job before respawning");
+            }
+            if ui.button("Respawn").clicked() {
+                sender.send_to_server(&RequestJoin);
+            }
+        });
+}
+
+/// Player the local ghost follows around
+#[derive(Resource, Default)]
+struct GhostFollowing(Option<NetworkIdentity>);
+
+/// Keeps the ghost on the creature it follows, until the player moves on their own
+fn client_ghost_follow(
+    respawn: Res<ClientRespawn>,
+    mut following: ResMut<GhostFollowing>,
+    identities: Res<NetworkIdentities>,
+    actions: Actions,
+    targets: Query<&GlobalTransform, Without<ClientControlled>>,
+    mut ghost: Query<&mut Transform, With<ClientControlled>>,
+) {
+    let Some(identity) = following.0 else {
+        return;
+    };
+    if !respawn.is_ghost() || actions.movement() != Vec2::ZERO {
+        following.0 = None;
+        return;
+    }
+
+    let Some(target) = identities
+        .get_entity(identity)
+        .and_then(|entity| targets.get(entity).ok())
+    else {
+        // Out of sight or gone, stay where we are
+        return;
+    };
+    let Ok(mut transform) = ghost.get_single_mut() else {
+        return;
+    };
+    let position = target.translation();
+    transform.translation.x = position.x;
+    transform.translation.z = position.z;
+}
+
+/// Moves the ghost to the point on the map the player clicks
+fn client_ghost_jump(
+    respawn: Res<ClientRespawn>,
+    actions: Actions,
+    mut contexts: EguiContexts,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut ghost: Query<&mut Transform, With<ClientControlled>>,
+    mut following: ResMut<GhostFollowing>,
+) {
+    if !respawn.is_ghost() || !actions.just_pressed(Action::GhostJump) {
+        return;
+    }
+    if contexts.ctx_mut().is_pointer_over_area() {
+        return;
+    }
+
+    let Ok(mut transform) = ghost.get_single_mut() else {
+        return;
+    };
+    let Some(cursor_position) = windows.get_single().ok().and_then(|w| w.cursor_position()) else {
+        return;
+    };
+    let Some((camera, camera_transform)) = cameras.iter().next() else {
+        return;
+    };
+    let Some(ray) = camera.viewport_to_world(camera_transform, cursor_position) else {
+        return;
+    };
+    let Some(toi) = ray.intersect_plane(transform.translation, Vec3::Y) else {
+        return;
+    };
+
+    transform.translation = ray.origin + ray.direction * toi;
+    following.0 = None;
+}
+
+fn describe_catatonic(examines: Res<ExamineEvents>, catatonic: Query<(), With<Catatonic>>) {
+    for examine in examines.events.iter() {
+        if catatonic.contains(examine.target) {
+            examine.add_line("They are staring blankly into space.");
+        }
     }
 }
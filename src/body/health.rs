@@ -1,12 +1,28 @@
 use bevy::{ecs::query::Has, prelude::*};
-use networking::is_server;
+use networking::{
+    is_server,
+    time::{NetworkTick, TickTime},
+};
 
-use crate::combat::damage::*;
+use crate::{
+    combat::damage::*,
+    communication::examine::{DescribeExamined, ExamineEvents},
+};
 
 use super::Body;
 
+use self::species::SpeciesVitals;
+
+mod blur;
+mod chemicals;
+mod hud;
 mod items;
+pub mod pain;
+pub mod prosthetic;
 mod scanner;
+pub mod species;
+mod surgery;
+pub mod transplant;
 mod ui;
 
 pub struct HealthPlugin;
@@ -24,23 +40,42 @@ impl Plugin for HealthPlugin {
                 .add_systems(
                     Update,
                     (
-                        (heart_beat, adjust_heart_rate).chain(),
+                        receive_damage.in_set(DamageStage::Apply),
+                        describe_injuries.in_set(DescribeExamined),
+                    ),
+                )
+                .add_systems(
+                    NetworkTick,
+                    (
+                        (
+                            heart_beat,
+                            adjust_heart_rate,
+                            track_cardiac_arrest,
+                            lung_gas_exchange,
+                        )
+                            .chain(),
                         breathing,
-                        lung_gas_exchange,
-                        receive_damage,
                         brain_live,
+                        knit_fractures,
                     ),
                 );
         }
         app.add_plugins((
+            chemicals::ChemicalEffectsPlugin,
+            hud::HealthHudPlugin,
             scanner::HealthScannerPlugin,
             items::HealthItemsPlugin,
+            pain::PainPlugin,
+            prosthetic::ProstheticPlugin,
+            species::SpeciesPlugin,
+            surgery::SurgeryPlugin,
+            transplant::TransplantPlugin,
             ui::HealthUiPlugin,
         ));
     }
 }
 
-/// How many liters of oxygen can fit in a liter of blood
+/// How many liters of oxygen can fit in a liter of human blood
 const MAX_BLOOD_OXYGEN: f32 = 0.05;
 
 #[derive(Component, Reflect)]
@@ -52,22 +87,33 @@ struct OrganicBody {
     blood_capacity: f32,
     /// Amount of oxygen in blood in liters
     oxygen_in_blood: f32,
+    /// How many liters of oxygen fit in a liter of blood
+    blood_gas_capacity: f32,
+    /// Body temperature in °C
+    temperature: f32,
 }
 
 impl Default for OrganicBody {
     fn default() -> Self {
-        let blood_capacity = 5.0;
+        let vitals = SpeciesVitals::default();
         Self {
-            blood: blood_capacity,
-            blood_capacity,
-            oxygen_in_blood: blood_capacity * MAX_BLOOD_OXYGEN,
+            blood: vitals.blood_capacity,
+            blood_capacity: vitals.blood_capacity,
+            oxygen_in_blood: vitals.blood_capacity * vitals.blood_gas_capacity,
+            blood_gas_capacity: vitals.blood_gas_capacity,
+            temperature: vitals.normal_temperature(),
         }
     }
 }
 
 impl OrganicBody {
     fn oxygen_capacity(&self) -> f32 {
-        self.blood * MAX_BLOOD_OXYGEN
+        self.blood * self.blood_gas_capacity
+    }
+
+    /// Oxygen capacity with a full supply of blood
+    fn max_oxygen_capacity(&self) -> f32 {
+        self.blood_capacity * self.blood_gas_capacity
     }
 
     fn add_oxygen(&mut self, amount: f32) -> f32 {
@@ -143,7 +189,10 @@ struct OrganicHeart {
     pump_rate: f32,
     /// Beats per minute
     heart_rate: u32,
-    last_beat: f32,
+    /// Seconds since the last beat
+    since_beat: f32,
+    /// When the heart stopped beating, if it is in cardiac arrest
+    stopped_since: Option<f32>,
 }
 
 impl Default for OrganicHeart {
@@ -151,7 +200,8 @@ impl Default for OrganicHeart {
         Self {
             pump_rate: 0.070,
             heart_rate: 70,
-            last_beat: 0.0,
+            since_beat: 0.0,
+            stopped_since: None,
         }
     }
 }
@@ -174,7 +224,8 @@ struct OrganicLung {
     exchange_rate: f32,
     /// Breaths per minute
     breath_rate: u32,
-    last_breath: f32,
+    /// Seconds since the last breath
+    since_breath: f32,
 }
 
 impl Default for OrganicLung {
@@ -185,7 +236,7 @@ impl Default for OrganicLung {
             oxygen_present: 6.0 * 0.21,
             exchange_rate: 4.28,
             breath_rate: 12,
-            last_breath: 0.0,
+            since_breath: 0.0,
         }
     }
 }
@@ -200,7 +251,8 @@ struct OrganicBrain {
     low_blood: bool,
     unconcious: bool,
 
-    last_think: f32,
+    /// Seconds since the brain last used oxygen
+    since_think: f32,
 
     last_oxygen_ratios: [f32; BRAIN_OXYGEN_LEN],
     oxygen_history_index: usize,
@@ -211,7 +263,7 @@ impl Default for OrganicBrain {
         Self {
             low_blood: Default::default(),
             unconcious: Default::default(),
-            last_think: Default::default(),
+            since_think: Default::default(),
             last_oxygen_ratios: [1.0; BRAIN_OXYGEN_LEN],
             oxygen_history_index: Default::default(),
         }
@@ -244,6 +296,17 @@ pub enum BrainState {
     Dead,
 }
 
+/// Finds the vitals of the creature an organ is part of
+fn organ_vitals<'a>(
+    organ: Entity,
+    parents: &Query<&Parent>,
+    vitals: &'a Query<&SpeciesVitals>,
+) -> Option<&'a SpeciesVitals> {
+    parents
+        .iter_ancestors(organ)
+        .find_map(|entity| vitals.get(entity).ok())
+}
+
 fn adjust_heart_rate(
     mut hearts: Query<(Entity, &mut OrganicHeart, Option<&OrganicBodyPart>)>,
     bodies: Query<&Body>,
@@ -300,15 +363,17 @@ fn heart_beat(
     mut event: EventWriter<HeartBeat>,
     lacerations: Query<(&OrganicLaceration, &Parent)>,
     parents: Query<&Parent>,
-    time: Res<Time>,
+    vitals: Query<&SpeciesVitals>,
+    tick: Res<TickTime>,
 ) {
     for (heart_entity, mut heart) in hearts.iter_mut() {
         // Is it time for the heart to beat again?
-        if heart.last_beat + (60.0 / heart.heart_rate as f32) > time.elapsed_seconds() {
+        heart.since_beat += tick.delta_seconds();
+        if heart.since_beat < 60.0 / heart.heart_rate as f32 {
             continue;
         }
 
-        heart.last_beat = time.elapsed_seconds();
+        heart.since_beat = 0.0;
 
         // The heart consumes oxygen to beat
         let mut pump_strength = 1.0;
@@ -319,8 +384,9 @@ fn heart_beat(
                 / (INTENSE_HEART_BPM - RESTING_HEART_BPM) as f32)
                 .clamp(0.0, 1.0);
             // TODO: Linear interpolation is not really accurate
+            let gas_use = organ_vitals(heart_entity, &parents, &vitals).map_or(1.0, |v| v.gas_use);
             let desired_oxygen =
-                (1.0 - t) * RESTING_HEART_CONSUMPTION + t * INTENSE_HEART_CONSUMPTION;
+                gas_use * ((1.0 - t) * RESTING_HEART_CONSUMPTION + t * INTENSE_HEART_CONSUMPTION);
             let consumed = heart_part.consume_oxygen(desired_oxygen);
             let ratio = consumed / desired_oxygen;
             bevy::log::debug!(
@@ -390,6 +456,9 @@ fn heart_beat(
         );
 
         for (laceration, parent) in lacerations.iter() {
+            if laceration.bandaged {
+                continue;
+            }
             let Ok((body_part_parent, _)) = body_parts.get(parent.get()) else {
                 continue;
             };
@@ -406,31 +475,51 @@ fn heart_beat(
     }
 }
 
+fn track_cardiac_arrest(mut hearts: Query<&mut OrganicHeart>, time: Res<Time>) {
+    // Defibrillators compare this with the frame clock
+    for mut heart in hearts.iter_mut() {
+        match (heart.heart_rate, heart.stopped_since) {
+            (0, None) => heart.stopped_since = Some(time.elapsed_seconds()),
+            (rate, Some(_)) if rate > 0 => heart.stopped_since = None,
+            _ => {}
+        }
+    }
+}
+
 const LUNG_CONSUMPTION: f32 = 0.0004;
 
-fn breathing(mut lungs: Query<(&mut OrganicLung, Option<&mut OrganicBodyPart>)>, time: Res<Time>) {
-    for (mut lung, part) in lungs.iter_mut() {
+fn breathing(
+    mut lungs: Query<(Entity, &mut OrganicLung, Option<&mut OrganicBodyPart>)>,
+    parents: Query<&Parent>,
+    vitals: Query<&SpeciesVitals>,
+    tick: Res<TickTime>,
+) {
+    for (lung_entity, mut lung, part) in lungs.iter_mut() {
         // Is it time for the next breath
-        if lung.last_breath + (60.0 / lung.breath_rate as f32) > time.elapsed_seconds() {
+        lung.since_breath += tick.delta_seconds();
+        if lung.since_breath < 60.0 / lung.breath_rate as f32 {
             continue;
         }
 
-        lung.last_breath = time.elapsed_seconds();
+        lung.since_breath = 0.0;
+        let species = organ_vitals(lung_entity, &parents, &vitals);
+        let gas_use = species.map_or(1.0, |v| v.gas_use);
+        let breath_gas = species.map(|v| v.breath_gas).unwrap_or_default();
 
         // Lung consumes oxygen to work
         let mut breath_strength = 1.0;
         if let Some(mut part) = part {
-            let consumed = part.consume_oxygen(LUNG_CONSUMPTION);
-            let ratio = consumed / LUNG_CONSUMPTION;
+            let to_consume = LUNG_CONSUMPTION * gas_use;
+            let consumed = part.consume_oxygen(to_consume);
+            let ratio = consumed / to_consume;
             breath_strength = ratio;
             if breath_strength < 0.05 {
                 continue;
             }
         };
 
-        // TODO: replace with gas content in air
-        // We breathe a full lung of air (21% is oxygen)
-        lung.oxygen_present = lung.capacity * breath_strength * 0.21;
+        // We breathe a full lung of air, of which only the species' breath gas is useful
+        lung.oxygen_present = lung.capacity * breath_strength * breath_gas.fraction_in_air();
     }
 }
 
@@ -464,7 +553,9 @@ const BRAIN_UPDATE_INTERVAL: f32 = 0.2;
 fn brain_live(
     mut brains: Query<(Entity, &mut OrganicBrain, Option<&mut OrganicBodyPart>)>,
     mut state_events: EventWriter<BrainStateEvent>,
-    time: Res<Time>,
+    parents: Query<&Parent>,
+    vitals: Query<&SpeciesVitals>,
+    tick: Res<TickTime>,
 ) {
     for (brain_entity, mut brain, part) in brains.iter_mut() {
         // Braindead... lol
@@ -472,15 +563,16 @@ fn brain_live(
             continue;
         }
         // Not time to think yet
-        if brain.last_think + BRAIN_UPDATE_INTERVAL > time.elapsed_seconds() {
+        brain.since_think += tick.delta_seconds();
+        if brain.since_think < BRAIN_UPDATE_INTERVAL {
             continue;
         }
-        let pondering_time = time.elapsed_seconds() - brain.last_think;
-        brain.last_think = time.elapsed_seconds();
+        let pondering_time = std::mem::take(&mut brain.since_think);
 
         // Brain consumes oxygen to work
         if let Some(mut part) = part {
-            let to_consume = BRAIN_CONSUMPTION * pondering_time;
+            let gas_use = organ_vitals(brain_entity, &parents, &vitals).map_or(1.0, |v| v.gas_use);
+            let to_consume = BRAIN_CONSUMPTION * gas_use * pondering_time;
             let consumed = part.consume_oxygen(to_consume);
             let ratio = consumed / to_consume;
 
@@ -531,8 +623,31 @@ struct OrganicLaceration {
     //    /// How much blood can exit the wound in liters per second
     // blood_leak_rate: f32,
     size: LacerationSize,
+    /// Bandaged wounds stop bleeding, but stay open until closed
+    bandaged: bool,
+}
+
+impl OrganicLaceration {
+    fn new(size: LacerationSize) -> Self {
+        Self {
+            size,
+            bandaged: false,
+        }
+    }
+}
+
+/// A broken bone in a limb
+#[derive(Component)]
+struct OrganicFracture {
+    /// When a splint was applied. Splinted bones knit back together over time.
+    splinted_since: Option<f32>,
 }
 
+/// Energy in joules of a blunt impact that breaks bones
+const FRACTURE_ENERGY: f32 = 150.0;
+/// Seconds a splinted fracture takes to heal
+const FRACTURE_KNIT_TIME: f32 = 240.0;
+
 #[allow(dead_code)]
 enum LacerationSize {
     Small,
@@ -560,24 +675,111 @@ impl std::fmt::Display for LacerationSize {
     }
 }
 
+/// Brute damage from this amount on opens a wound
+const BRUTE_LACERATION_DAMAGE: f32 = 0.15;
+
 fn receive_damage(
-    attacks: Query<(Entity, &AffectedEntity, &KineticDamage), Added<Attack>>,
-    body_parts: Query<&OrganicBodyPart>,
+    queue: Res<DamageQueue>,
+    mut body_parts: Query<(&mut OrganicBodyPart, Option<&Children>)>,
+    fractures: Query<(), With<OrganicFracture>>,
     mut commands: Commands,
 ) {
-    for (attack_entity, affected_entity, _kinetic) in attacks.iter() {
-        let Ok(_) = body_parts.get(affected_entity.0) else {
+    for event in queue.iter() {
+        let target = event.target;
+        let Ok((mut part, children)) = body_parts.get_mut(target) else {
             continue;
         };
 
         bevy::log::debug!("Received wound");
-        // TODO: Clothing/armor, hitting organs, arteries
-        commands.entity(attack_entity).despawn();
+        // TODO: Hitting organs, arteries
+
+        let kinetic = match event.damage {
+            Damage::Tissue(tissue) => {
+                part.damage(tissue.amount);
+                if tissue.kind == DamageType::Brute && tissue.amount >= BRUTE_LACERATION_DAMAGE {
+                    commands
+                        .spawn(OrganicLaceration::new(LacerationSize::Small))
+                        .set_parent(target);
+                }
+                continue;
+            }
+            Damage::Kinetic(kinetic) => kinetic,
+            // Only harms electronics
+            Damage::Emp(_) => continue,
+        };
+        // Armor may have stopped the hit entirely
+        if kinetic.mass <= 0.0 {
+            continue;
+        }
+
         commands
-            .spawn(OrganicLaceration {
-                // TODO: Consider kinetic profile
-                size: LacerationSize::Medium,
-            })
-            .set_parent(affected_entity.0);
+            // TODO: Consider kinetic profile
+            .spawn(OrganicLaceration::new(LacerationSize::Medium))
+            .set_parent(target);
+
+        let energy = 0.5 * kinetic.mass * kinetic.velocity * kinetic.velocity;
+        let already_broken =
+            children.map_or(false, |c| c.iter().any(|&child| fractures.contains(child)));
+        if matches!(kinetic.shape, KineticShape::Blunt)
+            && energy >= FRACTURE_ENERGY
+            && !already_broken
+        {
+            commands
+                .spawn(OrganicFracture {
+                    splinted_since: None,
+                })
+                .set_parent(target);
+        }
+    }
+}
+
+fn knit_fractures(
+    fractures: Query<(Entity, &OrganicFracture)>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    // Splints are timestamped with the frame clock when applied
+    let now = time.elapsed_seconds();
+    for (entity, fracture) in fractures.iter() {
+        if fracture
+            .splinted_since
+            .map_or(false, |since| since + FRACTURE_KNIT_TIME <= now)
+        {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Describes how hurt a creature looks
+fn describe_injuries(
+    examines: Res<ExamineEvents>,
+    bodies: Query<&Body>,
+    parts: Query<(&OrganicBodyPart, Option<&Children>)>,
+    lacerations: Query<&OrganicLaceration>,
+) {
+    for examine in examines.events.iter() {
+        let Ok(body) = bodies.get(examine.target) else {
+            continue;
+        };
+
+        let mut worst = 1.0f32;
+        let mut bleeding = false;
+        for (part, children) in parts.iter_many(body.limbs()) {
+            worst = worst.min(part.integrity);
+            bleeding |= children.map_or(false, |children| {
+                lacerations
+                    .iter_many(children.iter())
+                    .any(|laceration| !laceration.bandaged)
+            });
+        }
+
+        if worst < 0.3 {
+            examine.add_line("They look badly hurt.");
+        } else if worst < 0.8 {
+            examine.add_line("They look slightly hurt.");
+        }
+        if bleeding {
+            examine.add_line("They are bleeding.");
+        }
     }
 }
@@ -0,0 +1,60 @@
+use bevy::prelude::*;
+use chemistry::{ChemicalEffect, Metabolized, Metabolizer, ReagentContainer};
+use networking::is_server;
+
+use crate::body::Body;
+
+use super::{OrganicBody, OrganicBodyPart};
+
+pub(super) struct ChemicalEffectsPlugin;
+
+impl Plugin for ChemicalEffectsPlugin {
+    fn build(&self, app: &mut App) {
+        if is_server(app) {
+            app.add_systems(Update, (add_bloodstreams, apply_chemical_effects));
+        }
+    }
+}
+
+/// Units of reagents the bloodstream can hold
+const BLOODSTREAM_CAPACITY: f32 = 60.0;
+
+fn add_bloodstreams(bodies: Query<Entity, Added<OrganicBody>>, mut commands: Commands) {
+    for body in bodies.iter() {
+        commands
+            .entity(body)
+            .insert((ReagentContainer::new(BLOODSTREAM_CAPACITY), Metabolizer));
+    }
+}
+
+fn apply_chemical_effects(
+    mut events: EventReader<Metabolized>,
+    mut bodies: Query<(&Body, &mut OrganicBody)>,
+    mut parts: Query<&mut OrganicBodyPart>,
+) {
+    for event in events.iter() {
+        let Ok((body, mut organic_body)) = bodies.get_mut(event.entity) else {
+            continue;
+        };
+
+        match event.effect {
+            ChemicalEffect::Heal(strength) => {
+                let mut iter = parts.iter_many_mut(&body.limbs);
+                while let Some(mut part) = iter.fetch_next() {
+                    part.integrity = (part.integrity + strength * event.units).min(1.0);
+                }
+            }
+            ChemicalEffect::Toxin(strength) => {
+                let mut iter = parts.iter_many_mut(&body.limbs);
+                while let Some(mut part) = iter.fetch_next() {
+                    part.damage(strength * event.units);
+                }
+            }
+            ChemicalEffect::Oxygenate(strength) => {
+                organic_body.add_oxygen(strength * event.units);
+            }
+            // Handled by the transplant plugin
+            ChemicalEffect::Immunosuppress(_) => {}
+        }
+    }
+}
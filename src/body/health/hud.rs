@@ -0,0 +1,383 @@
+use bevy::{prelude::*, reflect::TypeUuid};
+use bevy_egui::{egui, EguiContexts};
+use networking::{
+    component::AppExt,
+    is_server,
+    spawning::{ClientControlled, ClientControls},
+    variable::{NetworkVar, ServerVar},
+    ConnectionId, Networked, Players,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    body::{Body, Limb},
+    items::Item,
+    ui::{
+        has_window,
+        palette::{Cue, Palette},
+    },
+};
+
+use super::{
+    pain::{Pain, PainStage},
+    prosthetic::RoboticBodyPart,
+    OrganicBody, OrganicBodyPart, OrganicBrain, OrganicHeart, OrganicLaceration, OrganicLung,
+};
+
+pub(super) struct HealthHudPlugin;
+
+impl Plugin for HealthHudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_networked_component::<HealthSummary, HealthSummaryClient>();
+        if is_server(app) {
+            app.add_systems(
+                Update,
+                (
+                    add_health_summaries,
+                    update_health_owners,
+                    update_health_summaries,
+                ),
+            );
+        } else {
+            app.add_systems(
+                Update,
+                (health_hud_ui, critical_vignette).run_if(has_window),
+            );
+        }
+    }
+}
+
+const HUD_UPDATE_INTERVAL: f32 = 0.25;
+
+/// What everyone can tell about a creature at a glance
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default, Debug)]
+pub enum VitalState {
+    #[default]
+    Standing,
+    Unconscious,
+    Dead,
+}
+
+/// Health of a body as seen by clients.
+/// Only the controlling player receives the detailed vitals.
+#[derive(Component, Networked)]
+#[networked(client = "HealthSummaryClient", owner = "owner")]
+struct HealthSummary {
+    last_update: f32,
+    owner: NetworkVar<Option<ConnectionId>>,
+    state: NetworkVar<VitalState>,
+    #[networked(owner_only)]
+    limbs: NetworkVar<Vec<LimbStatus>>,
+    /// Percent of the blood capacity
+    #[networked(owner_only)]
+    blood: NetworkVar<u8>,
+    /// Percent of blood oxygen saturation
+    #[networked(owner_only)]
+    oxygen: NetworkVar<u8>,
+    /// Beats per minute
+    #[networked(owner_only)]
+    pulse: NetworkVar<u32>,
+    #[networked(owner_only)]
+    critical: NetworkVar<bool>,
+}
+
+#[derive(Component, Default, TypeUuid, Networked)]
+#[uuid = "b3f0d6a2-6c41-4e8f-9a57-2d1e8c4b7f90"]
+#[networked(server = "HealthSummary")]
+pub struct HealthSummaryClient {
+    state: ServerVar<VitalState>,
+    limbs: ServerVar<Vec<LimbStatus>>,
+    blood: ServerVar<u8>,
+    oxygen: ServerVar<u8>,
+    pulse: ServerVar<u32>,
+    critical: ServerVar<bool>,
+}
+
+impl HealthSummaryClient {
+    pub fn state(&self) -> VitalState {
+        self.state.get().copied().unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+struct LimbStatus {
+    name: String,
+    /// Where the limb attaches to the body, used to lay out the paperdoll
+    position: (f32, f32),
+    /// Percent from 0 (destroyed) to 100 (intact)
+    integrity: u8,
+    bleeding: bool,
+    /// Internal organs are listed instead of drawn
+    organ: bool,
+}
+
+fn percent(ratio: f32) -> u8 {
+    (ratio.clamp(0.0, 1.0) * 100.0).round() as u8
+}
+
+fn add_health_summaries(bodies: Query<Entity, Added<OrganicBody>>, mut commands: Commands) {
+    for body in bodies.iter() {
+        commands.entity(body).insert(HealthSummary {
+            last_update: 0.0,
+            owner: None.into(),
+            state: Default::default(),
+            limbs: Default::default(),
+            blood: 100.into(),
+            oxygen: 100.into(),
+            pulse: Default::default(),
+            critical: Default::default(),
+        });
+    }
+}
+
+/// Keeps track of who receives the detailed vitals of a body
+fn update_health_owners(
+    mut summaries: Query<(Entity, &mut HealthSummary)>,
+    controls: Res<ClientControls>,
+    players: Res<Players>,
+) {
+    for (body, mut summary) in summaries.iter_mut() {
+        let owner = controls
+            .controlling_player(body)
+            .and_then(|id| players.get_connection(&id));
+        if *summary.owner != owner {
+            *summary.owner = owner;
+        }
+    }
+}
+
+fn update_health_summaries(
+    mut summaries: Query<(&mut HealthSummary, &Body, &OrganicBody, Option<&Pain>)>,
+    limbs: Query<(
+        Option<&Item>,
+        &Limb,
+        Option<&OrganicBodyPart>,
+        Option<&RoboticBodyPart>,
+        Option<&Children>,
+    )>,
+    organs: Query<(), Or<(With<OrganicHeart>, With<OrganicLung>, With<OrganicBrain>)>>,
+    hearts: Query<&OrganicHeart>,
+    brains: Query<(&OrganicBrain, Option<&OrganicBodyPart>)>,
+    lacerations: Query<(), With<OrganicLaceration>>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    for (mut summary, body, organic_body, pain) in summaries.iter_mut() {
+        if summary.last_update + HUD_UPDATE_INTERVAL > now {
+            continue;
+        }
+        summary.last_update = now;
+
+        let mut statuses = Vec::with_capacity(body.limbs.len());
+        for &limb in body.limbs.iter() {
+            let Ok((item, limb_data, organic, robotic, children)) = limbs.get(limb) else {
+                continue;
+            };
+            let integrity = match (organic, robotic) {
+                (Some(part), _) => part.integrity,
+                (None, Some(part)) => part.structure.min(part.wiring),
+                (None, None) => 1.0,
+            };
+            let bleeding = children.map_or(false, |c| {
+                c.iter().any(|&child| lacerations.contains(child))
+            });
+            statuses.push(LimbStatus {
+                name: item.map_or("Limb", |i| i.name.as_str()).to_owned(),
+                position: (
+                    limb_data.attachment_position.x,
+                    limb_data.attachment_position.y,
+                ),
+                integrity: percent(integrity),
+                bleeding,
+                organ: organs.contains(limb),
+            });
+        }
+        if *summary.limbs != statuses {
+            *summary.limbs = statuses;
+        }
+
+        let blood = percent(organic_body.blood / organic_body.blood_capacity.max(f32::EPSILON));
+        if *summary.blood != blood {
+            *summary.blood = blood;
+        }
+        let oxygen = percent(
+            organic_body.oxygen_in_blood / organic_body.oxygen_capacity().max(f32::EPSILON),
+        );
+        if *summary.oxygen != oxygen {
+            *summary.oxygen = oxygen;
+        }
+
+        let pulse = hearts
+            .iter_many(&body.limbs)
+            .next()
+            .map_or(0, |heart| heart.heart_rate);
+        if *summary.pulse != pulse {
+            *summary.pulse = pulse;
+        }
+
+        let state = match brains.iter_many(&body.limbs).next() {
+            None => VitalState::Dead,
+            Some((_, Some(part))) if part.unusable() => VitalState::Dead,
+            Some((brain, _)) if brain.unconcious => VitalState::Unconscious,
+            Some(_) => VitalState::Standing,
+        };
+        if *summary.state != state {
+            *summary.state = state;
+        }
+
+        let unconscious = state != VitalState::Standing;
+        let critical =
+            unconscious || pain.map_or(false, |p| p.stage() == PainStage::SoftCrit) || pulse == 0;
+        if *summary.critical != critical {
+            *summary.critical = critical;
+        }
+    }
+}
+
+/// Limbs below this integrity are crossed out when symbols are enabled
+const DAMAGED_INTEGRITY: u8 = 50;
+
+/// Pixels per meter of limb attachment position on the paperdoll
+const PAPERDOLL_SCALE: f32 = 90.0;
+const PAPERDOLL_SIZE: egui::Vec2 = egui::vec2(110.0, 170.0);
+
+fn health_hud_ui(
+    mut contexts: EguiContexts,
+    huds: Query<&HealthSummaryClient, With<ClientControlled>>,
+    palette: Res<Palette>,
+) {
+    let Ok(hud) = huds.get_single() else {
+        return;
+    };
+    let integrity_color = |integrity: u8| palette.scale(integrity as f32 / 100.0);
+    let Some(limbs) = hud.limbs.get() else {
+        return;
+    };
+
+    egui::Window::new("health")
+        .title_bar(false)
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-10.0, -10.0))
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            let (rect, _) = ui.allocate_exact_size(PAPERDOLL_SIZE, egui::Sense::hover());
+            let painter = ui.painter_at(rect);
+            // Body origin sits at the hips, limbs are mirrored to face the viewer
+            let origin = rect.center_bottom() - egui::vec2(0.0, PAPERDOLL_SIZE.y * 0.45);
+            for limb in limbs.iter().filter(|l| !l.organ) {
+                let center =
+                    origin + egui::vec2(limb.position.0, -limb.position.1) * PAPERDOLL_SCALE;
+                let limb_rect = egui::Rect::from_center_size(center, egui::vec2(14.0, 14.0));
+                painter.rect_filled(limb_rect, 3.0, integrity_color(limb.integrity));
+                if palette.symbols && limb.integrity < DAMAGED_INTEGRITY {
+                    let stroke = egui::Stroke::new(2.0, egui::Color32::BLACK);
+                    painter.line_segment([limb_rect.left_top(), limb_rect.right_bottom()], stroke);
+                    painter.line_segment([limb_rect.right_top(), limb_rect.left_bottom()], stroke);
+                }
+                if limb.bleeding {
+                    painter.rect_stroke(
+                        limb_rect.expand(2.0),
+                        3.0,
+                        egui::Stroke::new(2.0, egui::Color32::DARK_RED),
+                    );
+                }
+                let hovered = ui
+                    .ctx()
+                    .pointer_hover_pos()
+                    .map_or(false, |p| limb_rect.contains(p));
+                if hovered {
+                    egui::show_tooltip_text(
+                        ui.ctx(),
+                        egui::Id::new("paperdoll limb"),
+                        format!("{}: {}%", limb.name, limb.integrity),
+                    );
+                }
+            }
+
+            for limb in limbs.iter().filter(|l| l.organ) {
+                ui.colored_label(
+                    integrity_color(limb.integrity),
+                    format!("{}: {}%", limb.name, limb.integrity),
+                );
+            }
+            ui.separator();
+
+            let blood = hud.blood.get().copied().unwrap_or(100);
+            let oxygen = hud.oxygen.get().copied().unwrap_or(100);
+            ui.add(
+                egui::ProgressBar::new(blood as f32 / 100.0)
+                    .text(format!("Blood {}%", blood))
+                    .desired_width(PAPERDOLL_SIZE.x),
+            );
+            ui.add(
+                egui::ProgressBar::new(oxygen as f32 / 100.0)
+                    .text(format!("Oxygen {}%", oxygen))
+                    .desired_width(PAPERDOLL_SIZE.x),
+            );
+            match hud.state() {
+                VitalState::Standing => {}
+                VitalState::Unconscious => {
+                    ui.label(palette.text(Cue::Warning, "Unconscious"));
+                }
+                VitalState::Dead => {
+                    ui.label(palette.text(Cue::Bad, "Dead"));
+                }
+            }
+            let pulse = hud.pulse.get().copied().unwrap_or_default();
+            if pulse == 0 {
+                ui.label(palette.text(Cue::Bad, "No pulse"));
+            } else {
+                ui.label(format!("Pulse {} bpm", pulse));
+            }
+        });
+}
+
+/// Darkens the screen edges with a red pulse while in critical condition
+fn critical_vignette(
+    mut contexts: EguiContexts,
+    huds: Query<&HealthSummaryClient, With<ClientControlled>>,
+    time: Res<Time>,
+) {
+    let critical = huds
+        .get_single()
+        .ok()
+        .and_then(|hud| hud.critical.get().copied())
+        .unwrap_or_default();
+    if !critical {
+        return;
+    }
+
+    let ctx = contexts.ctx_mut();
+    let screen = ctx.screen_rect();
+    let inner = screen.shrink(screen.width().min(screen.height()) * 0.25);
+    let strength = 0.55 + 0.15 * (time.elapsed_seconds() * 3.0).sin();
+    let edge = egui::Color32::from_rgba_unmultiplied(90, 0, 0, (strength * 255.0) as u8);
+
+    // A frame of quads fading from the screen edge to transparent in the middle
+    let mut mesh = egui::Mesh::default();
+    for corner in [
+        screen.left_top(),
+        screen.right_top(),
+        screen.right_bottom(),
+        screen.left_bottom(),
+    ] {
+        mesh.colored_vertex(corner, edge);
+    }
+    for corner in [
+        inner.left_top(),
+        inner.right_top(),
+        inner.right_bottom(),
+        inner.left_bottom(),
+    ] {
+        mesh.colored_vertex(corner, egui::Color32::TRANSPARENT);
+    }
+    for side in 0..4u32 {
+        let next = (side + 1) % 4;
+        mesh.add_triangle(side, next, side + 4);
+        mesh.add_triangle(next, next + 4, side + 4);
+    }
+    ctx.layer_painter(egui::LayerId::new(
+        egui::Order::Background,
+        egui::Id::new("critical vignette"),
+    ))
+    .add(egui::Shape::mesh(mesh));
+}
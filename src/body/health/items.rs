@@ -5,13 +5,17 @@ use networking::is_server;
 
 use crate::{
     body::Body,
+    despawn::{ContentsPolicy, DespawnCommandsExt},
     interaction::{
-        ActiveInteraction, GenerateInteractionList, InteractionListEvents, InteractionOption,
-        InteractionSpecificity, InteractionStatus,
+        ActiveInteraction, DoAfter, GenerateInteractionList, InteractionListEvents,
+        InteractionOption, InteractionSpecificity, InteractionStatus,
     },
+    items::stacks::Stack,
 };
 
-use super::{OrganicBody, OrganicBodyPart, OrganicBrain, OrganicHeart, OrganicLaceration};
+use super::{
+    OrganicBody, OrganicBodyPart, OrganicBrain, OrganicFracture, OrganicHeart, OrganicLaceration,
+};
 
 pub struct HealthItemsPlugin;
 
@@ -19,6 +23,7 @@ impl Plugin for HealthItemsPlugin {
     fn build(&self, app: &mut App) {
         app.register_type::<HealingItem>()
             .register_type::<HealOrganicLaceration>()
+            .register_type::<Splint>()
             .register_type::<BloodTransfusion>()
             .register_type::<Defibrillator>();
 
@@ -44,9 +49,18 @@ impl Plugin for HealthItemsPlugin {
 #[reflect(Component)]
 pub struct HealingItem;
 
+/// Treats open wounds
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
+struct HealOrganicLaceration {
+    /// Sutures close the wound, bandages only stop the bleeding
+    closes: bool,
+}
+
+/// Holds a broken bone in place so it can heal
 #[derive(Component, Default, Reflect)]
 #[reflect(Component)]
-struct HealOrganicLaceration {}
+struct Splint;
 
 #[derive(Component, Reflect)]
 #[reflect(Component)]
@@ -65,40 +79,60 @@ impl FromWorld for ApplyMedicineInteraction {
     }
 }
 
+const TREATMENT_DURATION: Duration = Duration::from_millis(1000);
+
 fn apply_medicine_interaction(
-    mut query: Query<(&mut ApplyMedicineInteraction, &mut ActiveInteraction)>,
-    medicines: Query<AnyOf<(&HealOrganicLaceration,)>>,
-    wounds: Query<AnyOf<(&OrganicLaceration,)>>,
+    mut query: Query<(&ApplyMedicineInteraction, &mut ActiveInteraction)>,
+    medicines: Query<AnyOf<(&HealOrganicLaceration, &Splint)>>,
+    mut wounds: Query<AnyOf<(&mut OrganicLaceration, &mut OrganicFracture)>>,
+    mut stacks: Query<&mut Stack>,
     time: Res<Time>,
     mut commands: Commands,
 ) {
+    let now = time.elapsed_seconds();
     for (interaction, mut active) in query.iter_mut() {
-        active.set_initial_duration(Duration::from_millis(1000));
-
-        if active.start_time() + 1.0 > time.elapsed_seconds() {
+        if !active.do_after(DoAfter::new(TREATMENT_DURATION).stationary(), now) {
             continue;
         }
 
-        let Ok((heal_organic_laceration,)) = medicines.get(interaction.medicine) else {
+        let Ok((heal_laceration, splint)) = medicines.get(interaction.medicine) else {
             active.status = InteractionStatus::Canceled;
             continue;
         };
 
-        let Ok((organic_laceration,)) = wounds.get(interaction.wound) else {
+        let Ok((laceration, fracture)) = wounds.get_mut(interaction.wound) else {
             active.status = InteractionStatus::Canceled;
             continue;
         };
 
-        let mut heal = false;
-        if organic_laceration.is_some() && heal_organic_laceration.is_some() {
-            // TODO: Treating the wound shouldn't instantly make it disappear
-            heal = true;
+        let closes = heal_laceration.map_or(false, |h| h.closes);
+        let treated = match (laceration, fracture) {
+            (Some(_), _) if closes => {
+                commands.entity(interaction.wound).despawn_recursive();
+                true
+            }
+            (Some(mut laceration), _) if heal_laceration.is_some() && !laceration.bandaged => {
+                laceration.bandaged = true;
+                true
+            }
+            (_, Some(mut fracture)) if splint.is_some() && fracture.splinted_since.is_none() => {
+                fracture.splinted_since = Some(now);
+                true
+            }
+            _ => false,
+        };
+        if !treated {
+            active.status = InteractionStatus::Canceled;
+            continue;
         }
 
-        if heal {
-            commands.entity(interaction.wound).despawn_recursive();
+        let used_up = match stacks.get_mut(interaction.medicine) {
+            Ok(mut stack) => stack.take(1) && stack.count() == 0,
+            Err(_) => true,
+        };
+        if used_up {
+            commands.despawn_cascade(interaction.medicine, ContentsPolicy::Destroy);
         }
-
         active.status = InteractionStatus::Completed;
     }
 }
@@ -149,6 +183,8 @@ fn prepare_transfusion_interaction(
 }
 
 const TRANSFUSION_DURATION: Duration = Duration::from_millis(3000);
+/// Transfusions keep going while the patient is close enough for the line to reach
+const TRANSFUSION_RANGE: f32 = 2.0;
 
 fn transfusion_interaction(
     mut query: Query<(&mut TransfuseInteraction, &mut ActiveInteraction)>,
@@ -162,16 +198,15 @@ fn transfusion_interaction(
             continue;
         };
 
-        active.set_initial_duration(TRANSFUSION_DURATION);
-
-        if active.start_time() + TRANSFUSION_DURATION.as_secs_f32() > time.elapsed_seconds() {
+        let transfusion = DoAfter::new(TRANSFUSION_DURATION).within_range(TRANSFUSION_RANGE);
+        if !active.do_after(transfusion, time.elapsed_seconds()) {
             continue;
         }
 
         let capacity = body.blood_capacity;
         body.set_blood(capacity);
 
-        commands.entity(interaction.item).despawn_recursive();
+        commands.despawn_cascade(interaction.item, ContentsPolicy::Destroy);
         active.status = InteractionStatus::Completed;
     }
 }
@@ -228,6 +263,8 @@ fn prepare_defibrillate_interaction(
 }
 
 const DEFIBRILLATE_DURATION: Duration = Duration::from_millis(5000);
+/// Seconds after cardiac arrest in which the heart can still be restarted
+const DEFIBRILLATION_WINDOW: f32 = 120.0;
 
 fn defibrillate_interaction(
     mut query: Query<(&mut DefibrillateInteraction, &mut ActiveInteraction)>,
@@ -238,24 +275,28 @@ fn defibrillate_interaction(
     )>,
     time: Res<Time>,
 ) {
+    let now = time.elapsed_seconds();
     for (_interaction, mut active) in query.iter_mut() {
         let Ok(body) = bodies.get(active.target) else {
             active.status = InteractionStatus::Canceled;
             continue;
         };
 
-        active.set_initial_duration(DEFIBRILLATE_DURATION);
-
-        if active.start_time() + DEFIBRILLATE_DURATION.as_secs_f32() > time.elapsed_seconds() {
+        if !active.do_after(DoAfter::new(DEFIBRILLATE_DURATION).stationary(), now) {
             continue;
         }
 
         for &limb in body.limbs.iter() {
             if let Ok(((heart, brain), mut body_part)) = organs.get_mut(limb) {
                 if let Some(mut heart) = heart {
-                    // Restart heart
+                    // Restart heart, unless it stopped too long ago
                     if heart.heart_rate < 20 {
-                        heart.heart_rate = 80;
+                        let too_late = heart
+                            .stopped_since
+                            .map_or(false, |since| now - since > DEFIBRILLATION_WINDOW);
+                        if !too_late {
+                            heart.heart_rate = 80;
+                        }
                     } else {
                         // RIP lol
                         heart.heart_rate = 0;
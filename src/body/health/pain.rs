@@ -0,0 +1,292 @@
+use bevy::{prelude::*, reflect::TypeUuid};
+use networking::{
+    component::AppExt,
+    is_server,
+    spawning::ClientControlled,
+    time::{NetworkTick, TickTime},
+    variable::{NetworkVar, ServerVar},
+    Networked,
+};
+use serde::{Deserialize, Serialize};
+use utils::task::Tasks;
+
+use crate::{
+    body::{Body, Hand},
+    camera::MainCamera,
+    character_sheet::CharacterSheets,
+    items::containers::{Container, MoveItem},
+    round::RoundRng,
+};
+
+use super::{
+    blur::{VisionBlur, VisionBlurPlugin},
+    OrganicBodyPart, OrganicBrain, OrganicFracture, OrganicLaceration,
+};
+
+pub struct PainPlugin;
+
+impl Plugin for PainPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_networked_component::<Pain, PainClient>();
+
+        if is_server(app) {
+            app.add_systems(
+                Update,
+                (add_pain, drop_items_in_pain, show_pain_status).chain(),
+            )
+            .add_systems(NetworkTick, update_pain);
+        } else {
+            app.add_plugins(VisionBlurPlugin)
+                .add_systems(Update, blur_vision_in_pain);
+        }
+    }
+}
+
+/// How much pain a character is in, from mild to soft-crit.
+/// Full unconsciousness is still decided by the brain.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum PainStage {
+    #[default]
+    None,
+    Hurting,
+    Severe,
+    SoftCrit,
+}
+
+impl PainStage {
+    fn from_level(level: f32) -> Self {
+        if level >= 0.9 {
+            Self::SoftCrit
+        } else if level >= 0.6 {
+            Self::Severe
+        } else if level >= 0.3 {
+            Self::Hurting
+        } else {
+            Self::None
+        }
+    }
+
+    /// How fast the character can move compared to normal
+    pub fn movement_factor(self) -> f32 {
+        match self {
+            PainStage::None => 1.0,
+            PainStage::Hurting => 0.8,
+            PainStage::Severe => 0.6,
+            PainStage::SoftCrit => 0.25,
+        }
+    }
+
+    fn blur(self) -> f32 {
+        match self {
+            PainStage::None => 0.0,
+            PainStage::Hurting => 0.3,
+            PainStage::Severe => 0.6,
+            PainStage::SoftCrit => 1.0,
+        }
+    }
+
+    fn status_name(self) -> Option<&'static str> {
+        match self {
+            PainStage::None => None,
+            PainStage::Hurting => Some("In pain"),
+            PainStage::Severe => Some("Severe pain"),
+            PainStage::SoftCrit => Some("Soft critical"),
+        }
+    }
+}
+
+#[derive(Component, Networked)]
+#[networked(client = "PainClient")]
+pub struct Pain {
+    /// Current pain from 0 upwards, only the stage is networked
+    level: f32,
+    stage: NetworkVar<PainStage>,
+    /// When held items were last checked for dropping
+    last_drop_check: f32,
+    /// The stage currently shown on the character sheet
+    shown_stage: PainStage,
+}
+
+impl Pain {
+    pub fn stage(&self) -> PainStage {
+        *self.stage
+    }
+}
+
+#[derive(Component, Default, TypeUuid, Networked)]
+#[uuid = "f6b161f9-22b3-4144-b5d7-a3de2867c455"]
+#[networked(server = "Pain")]
+pub struct PainClient {
+    stage: ServerVar<PainStage>,
+}
+
+impl PainClient {
+    pub fn stage(&self) -> PainStage {
+        self.stage.get().copied().unwrap_or_default()
+    }
+}
+
+/// How quickly pain rises towards what the body is suffering, per second
+const PAIN_RISE_RATE: f32 = 0.5;
+/// How quickly pain fades once the cause is gone, per second
+const PAIN_FADE_RATE: f32 = 0.1;
+/// Pain caused by each open wound
+const LACERATION_PAIN: f32 = 0.15;
+const FRACTURE_PAIN: f32 = 0.3;
+/// A splint keeps the broken bone still, which hurts a lot less
+const SPLINTED_FRACTURE_PAIN: f32 = 0.1;
+/// Brain oxygen below this ratio starts causing pain
+const LOW_OXYGEN_PAIN_START: f32 = 0.8;
+const DROP_CHECK_INTERVAL: f32 = 4.0;
+/// Chance to drop the held item on each check while in severe pain
+const SEVERE_DROP_CHANCE: f32 = 0.15;
+
+fn add_pain(bodies: Query<Entity, (With<Body>, Without<Pain>)>, mut commands: Commands) {
+    for entity in bodies.iter() {
+        commands.entity(entity).insert(Pain {
+            level: 0.0,
+            stage: Default::default(),
+            last_drop_check: 0.0,
+            shown_stage: PainStage::None,
+        });
+    }
+}
+
+fn update_pain(
+    mut bodies: Query<(&Body, &mut Pain)>,
+    children: Query<&Children>,
+    parts: Query<&OrganicBodyPart>,
+    lacerations: Query<(), With<OrganicLaceration>>,
+    fractures: Query<&OrganicFracture>,
+    brains: Query<&OrganicBrain>,
+    tick: Res<TickTime>,
+) {
+    for (body, mut pain) in bodies.iter_mut() {
+        let mut target = 0.0;
+        for entity in body
+            .limbs
+            .iter()
+            .flat_map(|&limb| std::iter::once(limb).chain(children.iter_descendants(limb)))
+        {
+            if let Ok(part) = parts.get(entity) {
+                target += (1.0 - part.integrity) * 0.5;
+            }
+            if lacerations.contains(entity) {
+                target += LACERATION_PAIN;
+            }
+            if let Ok(fracture) = fractures.get(entity) {
+                target += match fracture.splinted_since {
+                    Some(_) => SPLINTED_FRACTURE_PAIN,
+                    None => FRACTURE_PAIN,
+                };
+            }
+            if let Ok(brain) = brains.get(entity) {
+                let missing = LOW_OXYGEN_PAIN_START - brain.oxygen_ratio();
+                target += (missing / LOW_OXYGEN_PAIN_START).max(0.0);
+            }
+        }
+
+        let rate = if target > pain.level {
+            PAIN_RISE_RATE
+        } else {
+            PAIN_FADE_RATE
+        };
+        let step = rate * tick.delta_seconds();
+        pain.level += (target - pain.level).clamp(-step, step);
+
+        let stage = PainStage::from_level(pain.level);
+        if *pain.stage != stage {
+            *pain.stage = stage;
+        }
+    }
+}
+
+/// Characters in severe pain sometimes drop what they're holding, and drop everything in soft-crit
+fn drop_items_in_pain(
+    mut bodies: Query<(&Body, &mut Pain)>,
+    hands: Query<&Container, With<Hand>>,
+    mut item_moves: ResMut<Tasks<MoveItem>>,
+    mut rng: Option<ResMut<RoundRng>>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    for (body, mut pain) in bodies.iter_mut() {
+        if *pain.stage < PainStage::Severe {
+            continue;
+        }
+        if pain.last_drop_check + DROP_CHECK_INTERVAL > now {
+            continue;
+        }
+        pain.last_drop_check = now;
+
+        for hand in hands.iter_many(&body.limbs) {
+            let drop = match *pain.stage {
+                PainStage::SoftCrit => true,
+                _ => rng
+                    .as_mut()
+                    .map(|rng| rng.next_f32() < SEVERE_DROP_CHANCE)
+                    .unwrap_or_default(),
+            };
+            if !drop {
+                continue;
+            }
+            for (_, &item) in hand.iter() {
+                item_moves.create_ignore(MoveItem {
+                    item,
+                    container: None,
+                    position: None,
+                });
+            }
+        }
+    }
+}
+
+fn show_pain_status(
+    mut bodies: Query<(Entity, &mut Pain)>,
+    mut sheets: CharacterSheets,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    for (entity, mut pain) in bodies.iter_mut() {
+        let stage = pain.stage();
+        if pain.shown_stage == stage {
+            continue;
+        }
+        let Some(mut sheet) = sheets.get_mut(entity) else {
+            continue;
+        };
+        if let Some(name) = pain.shown_stage.status_name() {
+            sheet.remove_status(name, now);
+        }
+        if let Some(name) = stage.status_name() {
+            sheet.set_status(name, None, now);
+        }
+        pain.shown_stage = stage;
+    }
+}
+
+fn blur_vision_in_pain(
+    controlled: Query<&PainClient, With<ClientControlled>>,
+    mut cameras: Query<(Entity, Option<&mut VisionBlur>), With<MainCamera>>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    let target = controlled
+        .get_single()
+        .map(|pain| pain.stage().blur())
+        .unwrap_or_default();
+    let Ok((camera, blur)) = cameras.get_single_mut() else {
+        return;
+    };
+    let Some(mut blur) = blur else {
+        commands.entity(camera).insert(VisionBlur::default());
+        return;
+    };
+
+    // Fade between stages instead of snapping
+    let step = time.delta_seconds();
+    let intensity = blur.intensity + (target - blur.intensity).clamp(-step, step);
+    if intensity != blur.intensity {
+        blur.intensity = intensity;
+    }
+}
@@ -0,0 +1,359 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use networking::is_server;
+use utils::task::{TaskId, Tasks};
+
+use crate::{
+    body::{Body, Limb},
+    combat::damage::{Damage, DamageQueue, DamageStage},
+    communication::examine::{DescribeExamined, ExamineEvents},
+    interaction::{
+        ActiveInteraction, GenerateInteractionList, InteractionListEvents, InteractionOption,
+        InteractionSpecificity, InteractionStatus,
+    },
+    items::{containers::MoveItem, tools::Tools, Item, StoredItem},
+};
+
+pub struct ProstheticPlugin;
+
+impl Plugin for ProstheticPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<RoboticBodyPart>()
+            .register_type::<RepairKind>()
+            .register_type::<RepairTool>();
+        if is_server(app) {
+            app.register_type::<RepairInteraction>()
+                .register_type::<AttachLimbInteraction>()
+                .add_systems(
+                    Update,
+                    (
+                        robotic_damage.in_set(DamageStage::Apply),
+                        (prepare_repair_interaction, prepare_attach_limb_interaction)
+                            .in_set(GenerateInteractionList),
+                        repair_interaction,
+                        attach_limb_interaction,
+                        describe_prosthetics.in_set(DescribeExamined),
+                    ),
+                );
+        }
+    }
+}
+
+const REPAIR_TIME: Duration = Duration::from_secs(3);
+const ATTACH_TIME: Duration = Duration::from_secs(5);
+/// How much of a stat one repair restores
+const REPAIR_AMOUNT: f32 = 0.5;
+/// Structure lost per kinetic hit
+// TODO: Consider kinetic profile
+const KINETIC_STRUCTURE_DAMAGE: f32 = 0.25;
+
+/// A mechanical body part.
+/// Unlike organic parts it doesn't need oxygen, but is vulnerable to EMPs.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct RoboticBodyPart {
+    /// Condition of the frame, damaged by hits and repaired by welding.
+    /// 1 is intact, 0 makes the limb fall off
+    pub structure: f32,
+    /// Condition of the electronics, damaged by EMPs and repaired with cable
+    pub wiring: f32,
+}
+
+impl Default for RoboticBodyPart {
+    fn default() -> Self {
+        Self {
+            structure: 1.0,
+            wiring: 1.0,
+        }
+    }
+}
+
+impl RoboticBodyPart {
+    fn condition(&self, kind: RepairKind) -> f32 {
+        match kind {
+            RepairKind::Welding => self.structure,
+            RepairKind::Cabling => self.wiring,
+        }
+    }
+
+    fn repair(&mut self, kind: RepairKind, amount: f32) {
+        let value = match kind {
+            RepairKind::Welding => &mut self.structure,
+            RepairKind::Cabling => &mut self.wiring,
+        };
+        *value = (*value + amount).min(1.0);
+    }
+}
+
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum RepairKind {
+    /// Fixes the structure of robotic parts
+    #[default]
+    Welding,
+    /// Fixes the wiring of robotic parts
+    Cabling,
+}
+
+impl RepairKind {
+    fn verb(self) -> &'static str {
+        match self {
+            RepairKind::Welding => "Weld",
+            RepairKind::Cabling => "Rewire",
+        }
+    }
+}
+
+/// An item used to repair robotic body parts.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct RepairTool {
+    kind: RepairKind,
+}
+
+fn robotic_damage(
+    queue: Res<DamageQueue>,
+    mut parts: Query<&mut RoboticBodyPart>,
+    mut bodies: Query<&mut Body>,
+    parents: Query<&Parent>,
+) {
+    for event in queue.iter() {
+        let part_entity = event.target;
+        let Ok(mut part) = parts.get_mut(part_entity) else {
+            continue;
+        };
+
+        match event.damage {
+            Damage::Kinetic(kinetic) if kinetic.mass > 0.0 => {
+                part.structure = (part.structure - KINETIC_STRUCTURE_DAMAGE).max(0.0);
+            }
+            Damage::Kinetic(_) => {}
+            Damage::Tissue(tissue) => {
+                part.structure = (part.structure - tissue.amount).max(0.0);
+            }
+            Damage::Emp(emp) => {
+                part.wiring = (part.wiring - emp.strength).max(0.0);
+            }
+        }
+
+        if part.structure > 0.0 {
+            continue;
+        }
+        // A wrecked limb comes loose
+        if let Some(body_entity) = parents
+            .iter_ancestors(part_entity)
+            .find(|&e| bodies.contains(e))
+        {
+            bodies
+                .get_mut(body_entity)
+                .unwrap()
+                .limbs_to_remove
+                .push(part_entity);
+        }
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct RepairInteraction {
+    part: Entity,
+    kind: RepairKind,
+    /// Item the repair is done with
+    tool: Entity,
+}
+
+impl FromWorld for RepairInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            part: Entity::PLACEHOLDER,
+            kind: RepairKind::Welding,
+            tool: Entity::PLACEHOLDER,
+        }
+    }
+}
+
+fn prepare_repair_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    repair_tools: Query<&RepairTool>,
+    tools: Tools,
+    bodies: Query<&Body>,
+    parts: Query<(Entity, &Item, &RoboticBodyPart)>,
+) {
+    for event in interaction_list.events.iter() {
+        let Some((item, tool)) = event
+            .item_in_hand
+            .and_then(|item| repair_tools.get(item).ok().map(|tool| (item, tool)))
+        else {
+            continue;
+        };
+        if !tools.has_fuel(item) {
+            continue;
+        }
+
+        // Parts can be repaired while attached or lying around
+        let candidates: Vec<Entity> = match bodies.get(event.target) {
+            Ok(body) => body.limbs.iter().copied().collect(),
+            Err(_) => vec![event.target],
+        };
+        for (part, item, robotic) in parts.iter_many(&candidates) {
+            if robotic.condition(tool.kind) >= 1.0 {
+                continue;
+            }
+            event.add_interaction(InteractionOption {
+                text: format!("{} {}", tool.kind.verb(), item.name),
+                interaction: Box::new(RepairInteraction {
+                    part,
+                    kind: tool.kind,
+                    tool: item,
+                }),
+                specificity: InteractionSpecificity::Specific,
+            });
+        }
+    }
+}
+
+fn repair_interaction(
+    mut query: Query<(&RepairInteraction, &mut ActiveInteraction)>,
+    mut parts: Query<&mut RoboticBodyPart>,
+    mut tools: Tools,
+    time: Res<Time>,
+) {
+    for (interaction, mut active) in query.iter_mut() {
+        let duration = tools.duration(interaction.tool, REPAIR_TIME);
+        active.set_initial_duration(duration);
+
+        let Ok(mut part) = parts.get_mut(interaction.part) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        if !tools.has_fuel(interaction.tool) {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        }
+
+        if active.start_time() + duration.as_secs_f32() > time.elapsed_seconds() {
+            continue;
+        }
+
+        tools.use_tool(interaction.tool);
+        part.repair(interaction.kind, REPAIR_AMOUNT);
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct AttachLimbInteraction {
+    limb: Entity,
+    #[reflect(ignore)]
+    move_task: Option<TaskId<MoveItem>>,
+}
+
+impl FromWorld for AttachLimbInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            limb: Entity::PLACEHOLDER,
+            move_task: None,
+        }
+    }
+}
+
+fn prepare_attach_limb_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    bodies: Query<(), With<Body>>,
+    limbs: Query<&Item, (With<Limb>, With<RoboticBodyPart>)>,
+) {
+    for event in interaction_list.events.iter() {
+        let Some(limb) = event.item_in_hand else {
+            continue;
+        };
+        let Ok(limb_item) = limbs.get(limb) else {
+            continue;
+        };
+        if !bodies.contains(event.target) {
+            continue;
+        }
+
+        event.add_interaction(InteractionOption {
+            text: format!("Attach {}", limb_item.name),
+            interaction: Box::new(AttachLimbInteraction {
+                limb,
+                move_task: None,
+            }),
+            specificity: InteractionSpecificity::Specific,
+        });
+    }
+}
+
+fn attach_limb_interaction(
+    mut query: Query<(&mut AttachLimbInteraction, &mut ActiveInteraction)>,
+    mut bodies: Query<&mut Body>,
+    limbs: Query<(), (With<Limb>, With<RoboticBodyPart>, With<StoredItem>)>,
+    mut item_moves: ResMut<Tasks<MoveItem>>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (mut interaction, mut active) in query.iter_mut() {
+        active.set_initial_duration(ATTACH_TIME);
+
+        if !bodies.contains(active.target) {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        }
+
+        let Some(task) = interaction.move_task else {
+            if !limbs.contains(interaction.limb) {
+                active.status = InteractionStatus::Canceled;
+                continue;
+            }
+            if active.start_time() + ATTACH_TIME.as_secs_f32() > time.elapsed_seconds() {
+                continue;
+            }
+            interaction.move_task = Some(item_moves.create(MoveItem {
+                item: interaction.limb,
+                container: None,
+                position: None,
+            }));
+            continue;
+        };
+
+        let Some(result) = item_moves.result(task) else {
+            continue;
+        };
+        if !result.was_success() {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        }
+
+        let mut body = bodies.get_mut(active.target).unwrap();
+        commands.entity(active.target).add_child(interaction.limb);
+        body.limbs.insert(interaction.limb);
+        body.added_limbs.push(interaction.limb);
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+/// Points out prosthetics and their damage
+fn describe_prosthetics(
+    examines: Res<ExamineEvents>,
+    prosthetics: Query<(&Item, &RoboticBodyPart)>,
+    bodies: Query<&Body>,
+) {
+    for examine in examines.events.iter() {
+        if let Ok((_, part)) = prosthetics.get(examine.target) {
+            examine.add_line("It's a prosthetic.");
+            if part.structure < 0.5 {
+                examine.add_line("It's badly dented.");
+            }
+            if part.wiring < 0.5 {
+                examine.add_line("Its wiring is sparking.");
+            }
+        } else if let Ok(body) = bodies.get(examine.target) {
+            for (prosthetic, _) in prosthetics.iter_many(body.limbs()) {
+                examine.add_line(format!("They have a {}.", prosthetic.name));
+            }
+        }
+    }
+}
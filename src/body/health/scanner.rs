@@ -17,10 +17,15 @@ use crate::{
         ActiveInteraction, GenerateInteractionList, InteractionListEvents, InteractionOption,
         InteractionSpecificity, InteractionStatus,
     },
+    items::Item,
     ui::has_window,
 };
 
-use super::{OrganicBody, OrganicBodyPart, OrganicBrain, OrganicHeart, MAX_BLOOD_OXYGEN};
+use super::{
+    prosthetic::RoboticBodyPart,
+    species::{BreathGas, SpeciesVitals},
+    OrganicBody, OrganicBodyPart, OrganicBrain, OrganicHeart,
+};
 
 pub struct HealthScannerPlugin;
 
@@ -74,14 +79,27 @@ struct Vitals {
     oxygen_capacity: f32,
     max_oxygen_capacity: f32,
     brain_integrity: Option<f32>,
+    temperature: f32,
+    /// Healthy temperature range of the species
+    normal_temperature: (f32, f32),
+    breath_gas: BreathGas,
+    prosthetics: Vec<ProstheticStatus>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct ProstheticStatus {
+    name: String,
+    structure: f32,
+    wiring: f32,
 }
 
 fn collect_vitals(
     mut scanners: Query<&mut HealthScanner>,
     identities: Res<NetworkIdentities>,
-    bodies: Query<(&Body, &OrganicBody)>,
+    bodies: Query<(&Body, &OrganicBody, Option<&SpeciesVitals>)>,
     hearts: Query<&OrganicHeart>,
     brains: Query<(&OrganicBrain, Option<&OrganicBodyPart>)>,
+    prosthetics: Query<(&Item, &RoboticBodyPart)>,
     time: Res<Time>,
 ) {
     for mut scanner in scanners.iter_mut() {
@@ -97,7 +115,7 @@ fn collect_vitals(
             *scanner.vitals = None;
             continue;
         };
-        let Ok((body, organic_body)) = bodies.get(target_entity) else {
+        let Ok((body, organic_body, species)) = bodies.get(target_entity) else {
             *scanner.vitals = None;
             continue;
         };
@@ -120,8 +138,21 @@ fn collect_vitals(
             oxygen_in_blood: organic_body.oxygen_in_blood,
             bpm,
             oxygen_capacity: organic_body.oxygen_capacity(),
-            max_oxygen_capacity: organic_body.blood_capacity * MAX_BLOOD_OXYGEN,
+            max_oxygen_capacity: organic_body.max_oxygen_capacity(),
             brain_integrity,
+            temperature: organic_body.temperature,
+            normal_temperature: species
+                .map(|s| s.body_temperature)
+                .unwrap_or_else(|| SpeciesVitals::default().body_temperature),
+            breath_gas: species.map(|s| s.breath_gas).unwrap_or_default(),
+            prosthetics: prosthetics
+                .iter_many(&body.limbs)
+                .map(|(item, part)| ProstheticStatus {
+                    name: item.name.clone(),
+                    structure: part.structure,
+                    wiring: part.wiring,
+                })
+                .collect(),
         };
         *scanner.vitals = Some(vitals);
     }
@@ -166,22 +197,37 @@ fn health_scanner_ui(
                             vitals.blood_capacity
                         ));
                         ui.label(format!(
-                            "Oxygen blood saturation: {:.0}% ({:.2}/{:.2}l)",
+                            "{} blood saturation: {:.0}% ({:.2}/{:.2}l)",
+                            vitals.breath_gas,
                             vitals.oxygen_in_blood / vitals.oxygen_capacity * 100.0,
                             vitals.oxygen_in_blood,
                             vitals.oxygen_capacity
                         ));
                         ui.label(format!(
-                            "Oxygen level: {:.0}% ({:.2}/{:.2}l)",
+                            "{} level: {:.0}% ({:.2}/{:.2}l)",
+                            vitals.breath_gas,
                             vitals.oxygen_in_blood / vitals.max_oxygen_capacity * 100.0,
                             vitals.oxygen_in_blood,
                             vitals.max_oxygen_capacity
                         ));
+                        let (min_temperature, max_temperature) = vitals.normal_temperature;
+                        ui.label(format!(
+                            "Body temperature: {:.1}°C (normal {:.1}-{:.1}°C)",
+                            vitals.temperature, min_temperature, max_temperature
+                        ));
                         if let Some(integrity) = vitals.brain_integrity {
                             ui.label(format!("Brain integrity: {:.0}%", integrity * 100.0,));
                         } else {
                             ui.label("Brain integrity: N/A");
                         }
+                        for prosthetic in vitals.prosthetics.iter() {
+                            ui.label(format!(
+                                "{} (prosthetic): structure {:.0}%, wiring {:.0}%",
+                                prosthetic.name,
+                                prosthetic.structure * 100.0,
+                                prosthetic.wiring * 100.0
+                            ));
+                        }
                     } else {
                         ui.label("No vitals available");
                     }
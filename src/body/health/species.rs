@@ -0,0 +1,170 @@
+use bevy::{
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+};
+use bevy_common_assets::ron::RonAssetPlugin;
+use networking::is_server;
+use serde::{Deserialize, Serialize};
+
+use super::{OrganicBody, MAX_BLOOD_OXYGEN};
+
+pub struct SpeciesPlugin;
+
+impl Plugin for SpeciesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonAssetPlugin::<SpeciesDefinition>::new(&["species.ron"]))
+            .add_systems(Startup, load_species);
+        if is_server(app) {
+            app.add_systems(Update, apply_species_vitals);
+        }
+    }
+}
+
+#[derive(Deserialize, TypeUuid, TypePath)]
+#[uuid = "4b7e2d91-3c58-4f0a-a6e2-91d0c7b35f18"]
+pub struct SpeciesDefinition {
+    pub id: String,
+    pub name: String,
+    /// If players can pick the species for their character
+    #[serde(default = "default_playable")]
+    pub playable: bool,
+    /// Scene of the creature the limbs are attached to
+    #[serde(default = "default_body")]
+    pub body: String,
+    /// Limbs attached directly to the body, with their own limbs and organs inside them
+    pub limbs: Vec<LimbDefinition>,
+    #[serde(default)]
+    pub vitals: SpeciesVitals,
+}
+
+fn default_playable() -> bool {
+    true
+}
+
+fn default_body() -> String {
+    "creatures/player.scn.ron".into()
+}
+
+/// A limb or organ, and everything attached to it
+#[derive(Deserialize)]
+pub struct LimbDefinition {
+    pub scene: String,
+    /// Position relative to the parent limb, replaces the one in the scene
+    #[serde(default)]
+    pub attachment: Option<Vec3>,
+    #[serde(default)]
+    pub children: Vec<LimbDefinition>,
+}
+
+/// Gases a species can breathe
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum BreathGas {
+    #[default]
+    Oxygen,
+    Nitrogen,
+    Plasma,
+}
+
+impl BreathGas {
+    /// Fraction of station air made up of this gas
+    // TODO: replace with gas content in air
+    pub(super) fn fraction_in_air(self) -> f32 {
+        match self {
+            BreathGas::Oxygen => 0.21,
+            BreathGas::Nitrogen => 0.78,
+            BreathGas::Plasma => 0.0,
+        }
+    }
+}
+
+impl std::fmt::Display for BreathGas {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BreathGas::Oxygen => write!(f, "Oxygen"),
+            BreathGas::Nitrogen => write!(f, "Nitrogen"),
+            BreathGas::Plasma => write!(f, "Plasma"),
+        }
+    }
+}
+
+/// Vital parameters of a creature, taken from its species.
+/// Missing values default to those of a human.
+#[derive(Component, Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct SpeciesVitals {
+    /// Amount of blood in a healthy body in liters
+    pub blood_capacity: f32,
+    /// How many liters of breath gas fit in a liter of blood
+    pub blood_gas_capacity: f32,
+    /// The gas lungs extract from the air.
+    /// Everywhere else in the health code "oxygen" refers to this gas.
+    pub breath_gas: BreathGas,
+    /// Multiplier for how much breath gas organs consume
+    pub gas_use: f32,
+    /// Inclusive range of healthy body temperature in °C
+    pub body_temperature: (f32, f32),
+}
+
+impl Default for SpeciesVitals {
+    fn default() -> Self {
+        Self {
+            blood_capacity: 5.0,
+            blood_gas_capacity: MAX_BLOOD_OXYGEN,
+            breath_gas: BreathGas::Oxygen,
+            gas_use: 1.0,
+            body_temperature: (36.1, 37.2),
+        }
+    }
+}
+
+impl SpeciesVitals {
+    pub fn normal_temperature(&self) -> f32 {
+        (self.body_temperature.0 + self.body_temperature.1) / 2.0
+    }
+}
+
+#[derive(Resource)]
+pub struct SpeciesAssets {
+    definitions: Vec<Handle<SpeciesDefinition>>,
+}
+
+impl SpeciesAssets {
+    pub fn get<'a>(
+        &self,
+        id: &str,
+        assets: &'a Assets<SpeciesDefinition>,
+    ) -> Option<&'a SpeciesDefinition> {
+        self.definitions
+            .iter()
+            .filter_map(|handle| assets.get(handle))
+            .find(|definition| definition.id == id)
+    }
+}
+
+fn load_species(mut commands: Commands, server: ResMut<AssetServer>) {
+    let assets = SpeciesAssets {
+        definitions: server
+            .load_folder("species")
+            .expect("assets/species is missing")
+            .into_iter()
+            .map(HandleUntyped::typed)
+            .collect(),
+    };
+    commands.insert_resource(assets);
+}
+
+/// Sets up the blood of a creature once both its body and species are known
+fn apply_species_vitals(
+    mut bodies: Query<
+        (&SpeciesVitals, &mut OrganicBody),
+        Or<(Added<SpeciesVitals>, Added<OrganicBody>)>,
+    >,
+) {
+    for (vitals, mut body) in bodies.iter_mut() {
+        body.blood_capacity = vitals.blood_capacity;
+        body.blood_gas_capacity = vitals.blood_gas_capacity;
+        body.temperature = vitals.normal_temperature();
+        body.blood = vitals.blood_capacity;
+        body.oxygen_in_blood = body.oxygen_capacity();
+    }
+}
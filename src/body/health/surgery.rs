@@ -0,0 +1,353 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use networking::is_server;
+
+use crate::{
+    body::{Body, Limb},
+    interaction::{
+        ActiveInteraction, GenerateInteractionList, InteractionListEvents, InteractionOption,
+        InteractionSpecificity, InteractionStatus,
+    },
+    items::{
+        tools::{ToolKind, Tools},
+        Item,
+    },
+};
+
+use super::{
+    transplant::OrganFilter, LacerationSize, OrganicBody, OrganicBodyPart, OrganicLaceration,
+};
+
+pub(super) struct SurgeryPlugin;
+
+impl Plugin for SurgeryPlugin {
+    fn build(&self, app: &mut App) {
+        if is_server(app) {
+            app.register_type::<IncisionInteraction>()
+                .register_type::<RetractInteraction>()
+                .register_type::<RepairOrganInteraction>()
+                .add_systems(
+                    Update,
+                    (
+                        (
+                            prepare_incision_interaction,
+                            prepare_retract_interaction,
+                            prepare_repair_organ_interaction,
+                        )
+                            .in_set(GenerateInteractionList),
+                        incision_interaction,
+                        retract_interaction,
+                        repair_organ_interaction,
+                    ),
+                );
+        }
+    }
+}
+
+const INCISION_TIME: Duration = Duration::from_secs(3);
+const RETRACT_TIME: Duration = Duration::from_secs(2);
+const ORGAN_REPAIR_TIME: Duration = Duration::from_secs(6);
+/// Integrity an organ regains from one repair
+const ORGAN_REPAIR_AMOUNT: f32 = 0.5;
+
+/// A surgical opening in a limb.
+/// It is part of the wound it leaves, so suturing the wound ends the surgery.
+#[derive(Component)]
+struct Incision {
+    retracted: bool,
+}
+
+/// Limbs of a body that can be operated on, with their names
+type SurgerySites<'w, 's> =
+    Query<'w, 's, (&'static Item, Option<&'static Children>), (With<Limb>, With<OrganicBodyPart>)>;
+
+/// Finds the incision in a limb, if it has one
+fn incision_in(limb: Option<&Children>, incisions: &Query<&Incision>) -> Option<Entity> {
+    limb?
+        .iter()
+        .copied()
+        .find(|&child| incisions.contains(child))
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct IncisionInteraction {
+    limb: Entity,
+}
+
+impl FromWorld for IncisionInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            limb: Entity::PLACEHOLDER,
+        }
+    }
+}
+
+fn prepare_incision_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    tools: Tools,
+    bodies: Query<&Body, With<OrganicBody>>,
+    sites: SurgerySites,
+    organs: Query<(), OrganFilter>,
+    incisions: Query<&Incision>,
+) {
+    for event in interaction_list.events.iter() {
+        let Some(item) = event.item_in_hand else {
+            continue;
+        };
+        if !tools.is(item, ToolKind::Scalpel) {
+            continue;
+        }
+        let Ok(body) = bodies.get(event.target) else {
+            continue;
+        };
+
+        for limb in body.limbs().filter(|&limb| !organs.contains(limb)) {
+            let Ok((limb_item, children)) = sites.get(limb) else {
+                continue;
+            };
+            if incision_in(children, &incisions).is_some() {
+                continue;
+            }
+            event.add_interaction(InteractionOption {
+                text: format!("Make incision in {}", limb_item.name),
+                interaction: Box::new(IncisionInteraction { limb }),
+                specificity: InteractionSpecificity::Specific,
+            });
+        }
+    }
+}
+
+fn incision_interaction(
+    mut query: Query<(Entity, &IncisionInteraction, &mut ActiveInteraction)>,
+    mut tools: Tools,
+    bodies: Query<&Body>,
+    sites: SurgerySites,
+    incisions: Query<&Incision>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (source, interaction, mut active) in query.iter_mut() {
+        let Some(tool) = tools.held(source, ToolKind::Scalpel) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        let duration = tools.duration(tool, INCISION_TIME);
+        active.set_initial_duration(duration);
+
+        let in_body = bodies
+            .get(active.target)
+            .map_or(false, |body| body.limbs().any(|l| l == interaction.limb));
+        let Ok((_, children)) = sites.get(interaction.limb) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        if !in_body || incision_in(children, &incisions).is_some() {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        }
+
+        if active.start_time() + duration.as_secs_f32() > time.elapsed_seconds() {
+            continue;
+        }
+
+        tools.use_tool(tool);
+        commands
+            .spawn((
+                OrganicLaceration::new(LacerationSize::Small),
+                Incision { retracted: false },
+            ))
+            .set_parent(interaction.limb);
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct RetractInteraction {
+    incision: Entity,
+}
+
+impl FromWorld for RetractInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            incision: Entity::PLACEHOLDER,
+        }
+    }
+}
+
+fn prepare_retract_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    tools: Tools,
+    bodies: Query<&Body, With<OrganicBody>>,
+    sites: SurgerySites,
+    incisions: Query<&Incision>,
+) {
+    for event in interaction_list.events.iter() {
+        let Some(item) = event.item_in_hand else {
+            continue;
+        };
+        if !tools.is(item, ToolKind::Retractor) {
+            continue;
+        }
+        let Ok(body) = bodies.get(event.target) else {
+            continue;
+        };
+
+        for limb in body.limbs() {
+            let Ok((limb_item, children)) = sites.get(limb) else {
+                continue;
+            };
+            let Some(incision) = incision_in(children, &incisions) else {
+                continue;
+            };
+            if incisions.get(incision).map_or(true, |i| i.retracted) {
+                continue;
+            }
+            event.add_interaction(InteractionOption {
+                text: format!("Retract incision in {}", limb_item.name),
+                interaction: Box::new(RetractInteraction { incision }),
+                specificity: InteractionSpecificity::Specific,
+            });
+        }
+    }
+}
+
+fn retract_interaction(
+    mut query: Query<(Entity, &RetractInteraction, &mut ActiveInteraction)>,
+    mut tools: Tools,
+    mut incisions: Query<&mut Incision>,
+    time: Res<Time>,
+) {
+    for (source, interaction, mut active) in query.iter_mut() {
+        let Some(tool) = tools.held(source, ToolKind::Retractor) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        let duration = tools.duration(tool, RETRACT_TIME);
+        active.set_initial_duration(duration);
+
+        let Ok(mut incision) = incisions.get_mut(interaction.incision) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+
+        if active.start_time() + duration.as_secs_f32() > time.elapsed_seconds() {
+            continue;
+        }
+
+        tools.use_tool(tool);
+        incision.retracted = true;
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+/// Is any limb of the body opened up far enough to reach the organs?
+fn has_open_site(body: &Body, sites: &SurgerySites, incisions: &Query<&Incision>) -> bool {
+    body.limbs().any(|limb| {
+        sites
+            .get(limb)
+            .ok()
+            .and_then(|(_, children)| incision_in(children, incisions))
+            .and_then(|incision| incisions.get(incision).ok())
+            .map_or(false, |incision| incision.retracted)
+    })
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct RepairOrganInteraction {
+    organ: Entity,
+}
+
+impl FromWorld for RepairOrganInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            organ: Entity::PLACEHOLDER,
+        }
+    }
+}
+
+fn prepare_repair_organ_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    tools: Tools,
+    bodies: Query<&Body, With<OrganicBody>>,
+    sites: SurgerySites,
+    incisions: Query<&Incision>,
+    organs: Query<(&Item, &OrganicBodyPart), OrganFilter>,
+) {
+    for event in interaction_list.events.iter() {
+        let Some(item) = event.item_in_hand else {
+            continue;
+        };
+        if !tools.is(item, ToolKind::Hemostat) {
+            continue;
+        }
+        let Ok(body) = bodies.get(event.target) else {
+            continue;
+        };
+        if !has_open_site(body, &sites, &incisions) {
+            continue;
+        }
+
+        for organ in body.limbs() {
+            let Ok((organ_item, part)) = organs.get(organ) else {
+                continue;
+            };
+            if part.integrity >= 1.0 {
+                continue;
+            }
+            event.add_interaction(InteractionOption {
+                text: format!("Repair {}", organ_item.name),
+                interaction: Box::new(RepairOrganInteraction { organ }),
+                specificity: InteractionSpecificity::Specific,
+            });
+        }
+    }
+}
+
+fn repair_organ_interaction(
+    mut query: Query<(Entity, &RepairOrganInteraction, &mut ActiveInteraction)>,
+    mut tools: Tools,
+    bodies: Query<&Body>,
+    sites: SurgerySites,
+    incisions: Query<&Incision>,
+    mut organs: Query<&mut OrganicBodyPart, OrganFilter>,
+    time: Res<Time>,
+) {
+    for (source, interaction, mut active) in query.iter_mut() {
+        let Some(tool) = tools.held(source, ToolKind::Hemostat) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        let duration = tools.duration(tool, ORGAN_REPAIR_TIME);
+        active.set_initial_duration(duration);
+
+        let Ok(body) = bodies.get(active.target) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        if !body.limbs().any(|limb| limb == interaction.organ)
+            || !has_open_site(body, &sites, &incisions)
+        {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        }
+        let Ok(mut organ) = organs.get_mut(interaction.organ) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+
+        if active.start_time() + duration.as_secs_f32() > time.elapsed_seconds() {
+            continue;
+        }
+
+        tools.use_tool(tool);
+        organ.integrity = (organ.integrity + ORGAN_REPAIR_AMOUNT).min(1.0);
+        active.status = InteractionStatus::Completed;
+    }
+}
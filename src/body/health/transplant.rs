@@ -0,0 +1,401 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, utils::HashMap};
+use chemistry::{ChemicalEffect, Metabolized};
+use networking::{
+    is_server,
+    time::{NetworkTick, TickTime},
+};
+use utils::task::{TaskId, Tasks};
+
+use crate::{
+    body::{Body, Cutting, Limb},
+    character_sheet::CharacterSheets,
+    interaction::{
+        ActiveInteraction, GenerateInteractionList, InteractionListEvents, InteractionOption,
+        InteractionSpecificity, InteractionStatus,
+    },
+    items::{containers::MoveItem, Item, StoredItem},
+};
+
+use super::{OrganicBody, OrganicBodyPart, OrganicBrain, OrganicHeart, OrganicLung};
+
+pub struct TransplantPlugin;
+
+impl Plugin for TransplantPlugin {
+    fn build(&self, app: &mut App) {
+        if is_server(app) {
+            app.register_type::<RemoveOrganInteraction>()
+                .register_type::<InsertOrganInteraction>()
+                .add_systems(
+                    Update,
+                    (
+                        stamp_organ_genomes,
+                        apply_immunosuppressants,
+                        (
+                            prepare_remove_organ_interaction,
+                            prepare_insert_organ_interaction,
+                        )
+                            .in_set(GenerateInteractionList),
+                        remove_organ_interaction,
+                        insert_organ_interaction,
+                    ),
+                )
+                .add_systems(NetworkTick, organ_rejection);
+        }
+    }
+}
+
+const SURGERY_TIME: Duration = Duration::from_secs(5);
+/// Integrity lost per second by an organ from another creature of the same species
+const DNA_REJECTION_RATE: f32 = 0.002;
+/// Integrity lost per second by an organ from another species
+const SPECIES_REJECTION_RATE: f32 = 0.02;
+const REJECTION_STATUS: &str = "Organ rejection";
+const IMMUNOSUPPRESSED_STATUS: &str = "Immunosuppressed";
+/// How far the end of an immunosuppression can drift before the shown status is updated
+const STATUS_REFRESH_SECONDS: f32 = 10.0;
+
+/// Where a creature or organ came from.
+/// Organs take on the genome of the first body they are part of.
+#[derive(Component, Clone, PartialEq, Eq, Debug)]
+pub struct Genome {
+    pub species: String,
+    pub dna: u64,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+enum Compatibility {
+    Compatible,
+    /// Same species, but a different donor
+    Mismatched,
+    Incompatible,
+}
+
+impl Genome {
+    fn compatibility(&self, recipient: &Genome) -> Compatibility {
+        if self.species != recipient.species {
+            Compatibility::Incompatible
+        } else if self.dna != recipient.dna {
+            Compatibility::Mismatched
+        } else {
+            Compatibility::Compatible
+        }
+    }
+}
+
+/// Organs that can be removed and transplanted
+pub(super) type OrganFilter = Or<(With<OrganicHeart>, With<OrganicLung>, With<OrganicBrain>)>;
+
+fn stamp_organ_genomes(
+    bodies: Query<(&Body, &Genome)>,
+    organs: Query<Entity, (With<OrganicBodyPart>, Without<Genome>)>,
+    mut commands: Commands,
+) {
+    for (body, genome) in bodies.iter() {
+        for organ in organs.iter_many(&body.limbs) {
+            commands.entity(organ).insert(genome.clone());
+        }
+    }
+}
+
+/// Keeps foreign organs from being rejected while active
+#[derive(Component)]
+struct Immunosuppressed {
+    until: f32,
+    /// End of the status shown on the character sheet
+    shown_until: f32,
+}
+
+/// Marks bodies currently rejecting an organ, so the status only changes when this does
+#[derive(Component)]
+struct RejectingOrgans;
+
+/// Where an organ sat in the body it was removed from, e.g. "head"
+#[derive(Component)]
+struct OrganSite(String);
+
+fn apply_immunosuppressants(
+    mut events: EventReader<Metabolized>,
+    mut bodies: Query<Option<&mut Immunosuppressed>, With<Genome>>,
+    mut sheets: CharacterSheets,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    let mut added: HashMap<Entity, f32> = HashMap::default();
+    for event in events.iter() {
+        if let ChemicalEffect::Immunosuppress(seconds) = event.effect {
+            *added.entry(event.entity).or_default() += seconds * event.units;
+        }
+    }
+
+    let now = time.elapsed_seconds();
+    for (entity, seconds) in added {
+        let Ok(suppressed) = bodies.get_mut(entity) else {
+            continue;
+        };
+        let until = match suppressed {
+            Some(mut suppressed) => {
+                suppressed.until = suppressed.until.max(now) + seconds;
+                if (suppressed.until - suppressed.shown_until).abs() < STATUS_REFRESH_SECONDS {
+                    continue;
+                }
+                suppressed.shown_until = suppressed.until;
+                suppressed.until
+            }
+            None => {
+                let until = now + seconds;
+                commands.entity(entity).insert(Immunosuppressed {
+                    until,
+                    shown_until: until,
+                });
+                until
+            }
+        };
+        if let Some(mut sheet) = sheets.get_mut(entity) {
+            sheet.set_status(
+                IMMUNOSUPPRESSED_STATUS,
+                Some(Duration::from_secs_f32(until - now)),
+                now,
+            );
+        }
+    }
+}
+
+fn organ_rejection(
+    bodies: Query<(
+        Entity,
+        &Body,
+        &Genome,
+        Option<&Immunosuppressed>,
+        Has<RejectingOrgans>,
+    )>,
+    mut organs: Query<(&Genome, &mut OrganicBodyPart)>,
+    mut sheets: CharacterSheets,
+    time: Res<Time>,
+    tick: Res<TickTime>,
+    mut commands: Commands,
+) {
+    let now = time.elapsed_seconds();
+    for (entity, body, genome, suppressed, was_rejecting) in bodies.iter() {
+        let suppressed = suppressed.map(|s| s.until > now).unwrap_or_default();
+
+        let mut rejecting = false;
+        let mut iter = organs.iter_many_mut(&body.limbs);
+        while let Some((organ_genome, mut part)) = iter.fetch_next() {
+            let rate = match organ_genome.compatibility(genome) {
+                Compatibility::Compatible => continue,
+                Compatibility::Mismatched => DNA_REJECTION_RATE,
+                Compatibility::Incompatible => SPECIES_REJECTION_RATE,
+            };
+            if suppressed || part.unusable() {
+                continue;
+            }
+            part.damage(rate * tick.delta_seconds());
+            rejecting = true;
+        }
+
+        if rejecting == was_rejecting {
+            continue;
+        }
+        if rejecting {
+            commands.entity(entity).insert(RejectingOrgans);
+        } else {
+            commands.entity(entity).remove::<RejectingOrgans>();
+        }
+        let Some(mut sheet) = sheets.get_mut(entity) else {
+            continue;
+        };
+        if rejecting {
+            sheet.set_status(REJECTION_STATUS, None, now);
+        } else {
+            sheet.remove_status(REJECTION_STATUS, now);
+        }
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct RemoveOrganInteraction {
+    organ: Entity,
+}
+
+impl FromWorld for RemoveOrganInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            organ: Entity::PLACEHOLDER,
+        }
+    }
+}
+
+fn prepare_remove_organ_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    cutting_items: Query<(), (With<Item>, With<Cutting>)>,
+    bodies: Query<&Body, With<OrganicBody>>,
+    organs: Query<(Entity, &Item), OrganFilter>,
+) {
+    for event in interaction_list.events.iter() {
+        let Some(item) = event.item_in_hand else {
+            continue;
+        };
+        if !cutting_items.contains(item) {
+            continue;
+        }
+        let Ok(body) = bodies.get(event.target) else {
+            continue;
+        };
+
+        for (organ, organ_item) in organs.iter_many(&body.limbs) {
+            event.add_interaction(InteractionOption {
+                text: format!("Remove {}", organ_item.name),
+                interaction: Box::new(RemoveOrganInteraction { organ }),
+                specificity: InteractionSpecificity::Specific,
+            });
+        }
+    }
+}
+
+fn remove_organ_interaction(
+    mut query: Query<(&RemoveOrganInteraction, &mut ActiveInteraction)>,
+    mut bodies: Query<&mut Body>,
+    parents: Query<&Parent>,
+    limbs: Query<&Limb>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (interaction, mut active) in query.iter_mut() {
+        active.set_initial_duration(SURGERY_TIME);
+
+        let Ok(mut body) = bodies.get_mut(active.target) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        if !body.limbs.contains(&interaction.organ) {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        }
+
+        if active.start_time() + SURGERY_TIME.as_secs_f32() > time.elapsed_seconds() {
+            continue;
+        }
+
+        let site = parents
+            .get(interaction.organ)
+            .and_then(|parent| limbs.get(parent.get()));
+        if let Ok(limb) = site {
+            commands
+                .entity(interaction.organ)
+                .insert(OrganSite(limb.zone().to_owned()));
+        }
+        body.limbs_to_remove.push(interaction.organ);
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct InsertOrganInteraction {
+    organ: Entity,
+    #[reflect(ignore)]
+    move_task: Option<TaskId<MoveItem>>,
+}
+
+impl FromWorld for InsertOrganInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            organ: Entity::PLACEHOLDER,
+            move_task: None,
+        }
+    }
+}
+
+fn prepare_insert_organ_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    bodies: Query<(), (With<Body>, With<OrganicBody>)>,
+    organs: Query<&Item, OrganFilter>,
+) {
+    for event in interaction_list.events.iter() {
+        let Some(organ) = event.item_in_hand else {
+            continue;
+        };
+        let Ok(organ_item) = organs.get(organ) else {
+            continue;
+        };
+        if !bodies.contains(event.target) {
+            continue;
+        }
+
+        event.add_interaction(InteractionOption {
+            text: format!("Insert {}", organ_item.name),
+            interaction: Box::new(InsertOrganInteraction {
+                organ,
+                move_task: None,
+            }),
+            specificity: InteractionSpecificity::Specific,
+        });
+    }
+}
+
+fn insert_organ_interaction(
+    mut query: Query<(&mut InsertOrganInteraction, &mut ActiveInteraction)>,
+    mut bodies: Query<&mut Body>,
+    organs: Query<(), (OrganFilter, With<StoredItem>)>,
+    sites: Query<&OrganSite>,
+    limbs: Query<&Limb>,
+    mut item_moves: ResMut<Tasks<MoveItem>>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (mut interaction, mut active) in query.iter_mut() {
+        active.set_initial_duration(SURGERY_TIME);
+
+        if !bodies.contains(active.target) {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        }
+
+        // Take the organ out of the surgeon's hand first
+        let Some(task) = interaction.move_task else {
+            if !organs.contains(interaction.organ) {
+                active.status = InteractionStatus::Canceled;
+                continue;
+            }
+            if active.start_time() + SURGERY_TIME.as_secs_f32() > time.elapsed_seconds() {
+                continue;
+            }
+            interaction.move_task = Some(item_moves.create(MoveItem {
+                item: interaction.organ,
+                container: None,
+                position: None,
+            }));
+            continue;
+        };
+
+        let Some(result) = item_moves.result(task) else {
+            continue;
+        };
+        if !result.was_success() {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        }
+
+        let mut body = bodies.get_mut(active.target).unwrap();
+        // Put the organ back into the same kind of limb it came from, if the body has one
+        let parent = sites
+            .get(interaction.organ)
+            .ok()
+            .and_then(|site| {
+                body.limbs
+                    .iter()
+                    .copied()
+                    .find(|&limb| limbs.get(limb).map_or(false, |limb| limb.zone() == site.0))
+            })
+            .unwrap_or(active.target);
+        commands.entity(parent).add_child(interaction.organ);
+        body.limbs.insert(interaction.organ);
+        body.added_limbs.push(interaction.organ);
+        active.status = InteractionStatus::Completed;
+    }
+}
@@ -25,7 +25,7 @@ use crate::{
 
 use super::{
     items::{ApplyMedicineInteraction, HealingItem},
-    OrganicLaceration,
+    OrganicFracture, OrganicLaceration,
 };
 
 pub struct HealthUiPlugin;
@@ -140,7 +140,7 @@ fn collect_vitals(
     bodies: Query<&Body>,
     limbs: Query<(&Children, &Item), With<Limb>>,
     identities: Res<NetworkIdentities>,
-    injuries: Query<(Entity, AnyOf<(&OrganicLaceration, ())>)>,
+    injuries: Query<(Entity, AnyOf<(&OrganicLaceration, &OrganicFracture)>)>,
     time: Res<Time>,
     mut commands: Commands,
 ) {
@@ -170,11 +170,25 @@ fn collect_vitals(
 
             let mut limb_injuries = Vec::default();
             let name = &item.name;
-            for (entity, (organic_laceration, _)) in injuries.iter_many(children) {
-                if let Some(injury) = organic_laceration {
+            for (entity, (laceration, fracture)) in injuries.iter_many(children) {
+                if let Some(laceration) = laceration {
+                    let name = match laceration.bandaged {
+                        true => format!("{} Laceration (bandaged)", laceration.size),
+                        false => format!("{} Laceration", laceration.size),
+                    };
                     limb_injuries.push(Injury {
                         server_entity: entity,
-                        name: format!("{} Laceration", injury.size),
+                        name,
+                    });
+                }
+                if let Some(fracture) = fracture {
+                    let name = match fracture.splinted_since {
+                        Some(_) => "Fracture (splinted)",
+                        None => "Fracture",
+                    };
+                    limb_injuries.push(Injury {
+                        server_entity: entity,
+                        name: name.into(),
                     });
                 }
             }
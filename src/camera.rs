@@ -1,6 +1,9 @@
 use bevy::{input::mouse::MouseWheel, prelude::*};
 
-use crate::movement::MovementSystem;
+use crate::{
+    controls::{Action, Actions},
+    movement::MovementSystem,
+};
 
 #[derive(Component)]
 pub struct MainCamera;
@@ -37,15 +40,15 @@ impl TopDownCamera {
 
 pub fn top_down_camera_input_system(
     mut camera_query: Query<&mut TopDownCamera>,
-    keyboard_input: Res<Input<KeyCode>>,
+    actions: Actions,
     mut mouse_wheel: EventReader<MouseWheel>,
 ) {
     let scroll_amount: f32 = mouse_wheel.iter().map(|e| e.y).sum();
     for mut camera in camera_query.iter_mut() {
         let mut rotation = None;
-        if keyboard_input.just_pressed(KeyCode::Q) {
+        if actions.just_pressed(Action::RotateCameraLeft) {
             rotation = Some(-1.0);
-        } else if keyboard_input.just_pressed(KeyCode::E) {
+        } else if actions.just_pressed(Action::RotateCameraRight) {
             rotation = Some(1.0);
         }
 
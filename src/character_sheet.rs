@@ -0,0 +1,325 @@
+use std::time::Duration;
+
+use bevy::{ecs::system::SystemParam, prelude::*, reflect::TypeUuid, utils::HashMap};
+use bevy_egui::{egui, EguiContexts};
+use networking::{
+    component::AppExt,
+    identity::{EntityCommandsExt, NetworkIdentities, NetworkIdentity},
+    is_server,
+    spawning::ClientControlled,
+    variable::{NetworkVar, ServerVar},
+    visibility::AlwaysVisible,
+    Networked,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{job::JobDefinition, ui::has_window, GameState};
+
+pub struct CharacterSheetPlugin;
+
+impl Plugin for CharacterSheetPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_networked_component::<CharacterSheet, CharacterSheetClient>();
+
+        if is_server(app) {
+            app.add_systems(Update, (expire_statuses, remove_orphaned_sheets));
+        } else {
+            app.add_systems(
+                Update,
+                (
+                    client_track_status_updates,
+                    client_character_sheet_ui
+                        .run_if(has_window)
+                        .run_if(in_state(GameState::Game)),
+                )
+                    .chain(),
+            );
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct StatusSummary {
+    pub name: String,
+    /// Seconds left when the status list was last changed. `None` if it lasts indefinitely.
+    pub remaining: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct AccentSummary {
+    pub name: String,
+    /// How strongly the accent affects speech, from 0 to 1
+    pub severity: f32,
+}
+
+/// A summary of a character, only networked to the player controlling it.
+///
+/// Other systems keep this up to date through [`CharacterSheets`].
+#[derive(Component, Networked)]
+#[networked(client = "CharacterSheetClient")]
+pub struct CharacterSheet {
+    /// The creature this sheet describes
+    #[networked(
+        with = "Self::network_owner(Res<'static, NetworkIdentities>) -> Option<NetworkIdentity>"
+    )]
+    owner: NetworkVar<Entity>,
+    job: NetworkVar<Option<String>>,
+    access: NetworkVar<Vec<String>>,
+    statuses: NetworkVar<Vec<StatusSummary>>,
+    accents: NetworkVar<Vec<AccentSummary>>,
+    /// When timed statuses end, in seconds since startup
+    status_ends: HashMap<String, f32>,
+}
+
+impl CharacterSheet {
+    fn network_owner(entity: &Entity, param: Res<NetworkIdentities>) -> Option<NetworkIdentity> {
+        param.get_identity(*entity)
+    }
+
+    pub fn set_job(&mut self, job: Option<String>) {
+        if *self.job != job {
+            *self.job = job;
+        }
+    }
+
+    pub fn set_access(&mut self, access: Vec<String>) {
+        if *self.access != access {
+            *self.access = access;
+        }
+    }
+
+    /// Adds or refreshes a status. A `duration` of `None` lasts until removed.
+    pub fn set_status(&mut self, name: impl Into<String>, duration: Option<Duration>, now: f32) {
+        let name = name.into();
+        match duration {
+            Some(duration) => {
+                self.status_ends
+                    .insert(name.clone(), now + duration.as_secs_f32());
+            }
+            None => {
+                self.status_ends.remove(&name);
+            }
+        }
+        if !self.statuses.iter().any(|s| s.name == name) {
+            self.statuses.push(StatusSummary {
+                name,
+                remaining: None,
+            });
+        }
+        self.refresh_remaining(now);
+    }
+
+    pub fn remove_status(&mut self, name: &str, now: f32) {
+        if !self.statuses.iter().any(|s| s.name == name) {
+            return;
+        }
+        self.status_ends.remove(name);
+        self.statuses.retain(|s| s.name != name);
+        self.refresh_remaining(now);
+    }
+
+    pub fn set_accent(&mut self, name: impl Into<String>, severity: f32) {
+        let name = name.into();
+        match self.accents.iter().position(|a| a.name == name) {
+            Some(i) if self.accents[i].severity == severity => {}
+            Some(i) => self.accents[i].severity = severity,
+            None => self.accents.push(AccentSummary { name, severity }),
+        }
+    }
+
+    /// Replaces all accents
+    pub fn set_accents(&mut self, accents: Vec<AccentSummary>) {
+        if *self.accents != accents {
+            *self.accents = accents;
+        }
+    }
+
+    pub fn remove_accent(&mut self, name: &str) {
+        if self.accents.iter().any(|a| a.name == name) {
+            self.accents.retain(|a| a.name != name);
+        }
+    }
+
+    /// Updates the remaining time of all statuses, as they are sent together
+    fn refresh_remaining(&mut self, now: f32) {
+        for status in self.statuses.iter_mut() {
+            status.remaining = self
+                .status_ends
+                .get(&status.name)
+                .map(|end| (end - now).max(0.0));
+        }
+    }
+}
+
+#[derive(Component, Default, TypeUuid, Networked)]
+#[uuid = "9b7e4d1a-3c52-4f0e-a6d8-2e51c7b9f834"]
+#[networked(server = "CharacterSheet")]
+pub struct CharacterSheetClient {
+    owner: ServerVar<Option<NetworkIdentity>>,
+    job: ServerVar<Option<String>>,
+    access: ServerVar<Vec<String>>,
+    #[networked(updated = "Self::on_statuses_update")]
+    statuses: ServerVar<Vec<StatusSummary>>,
+    accents: ServerVar<Vec<AccentSummary>>,
+    statuses_changed: bool,
+    /// When the current status list was received
+    statuses_received: f32,
+}
+
+impl CharacterSheetClient {
+    fn on_statuses_update(&mut self, _: &Vec<StatusSummary>) {
+        self.statuses_changed = true;
+    }
+}
+
+/// Points from a creature to its character sheet
+#[derive(Component)]
+pub struct HasCharacterSheet(Entity);
+
+/// Access to the character sheets of creatures.
+#[derive(SystemParam)]
+pub struct CharacterSheets<'w, 's> {
+    links: Query<'w, 's, &'static HasCharacterSheet>,
+    sheets: Query<'w, 's, &'static mut CharacterSheet>,
+}
+
+impl<'w, 's> CharacterSheets<'w, 's> {
+    pub fn get_mut(&mut self, creature: Entity) -> Option<Mut<CharacterSheet>> {
+        let link = self.links.get(creature).ok()?;
+        self.sheets.get_mut(link.0).ok()
+    }
+}
+
+/// Creates a character sheet for a player controlled creature.
+pub fn spawn_character_sheet(commands: &mut Commands, creature: Entity, job: &JobDefinition) {
+    let sheet = commands
+        .spawn((
+            CharacterSheet {
+                owner: creature.into(),
+                job: Some(job.name.clone()).into(),
+                access: job.access.clone().into(),
+                statuses: Default::default(),
+                accents: Default::default(),
+                status_ends: Default::default(),
+            },
+            AlwaysVisible::single(creature),
+        ))
+        .networked()
+        .id();
+    commands.entity(creature).insert(HasCharacterSheet(sheet));
+}
+
+fn expire_statuses(mut sheets: Query<&mut CharacterSheet>, time: Res<Time>) {
+    let now = time.elapsed_seconds();
+    for mut sheet in sheets.iter_mut() {
+        if !sheet.status_ends.values().any(|&end| end <= now) {
+            continue;
+        }
+
+        let sheet = &mut *sheet;
+        sheet.status_ends.retain(|_, end| *end > now);
+        let ends = &sheet.status_ends;
+        sheet
+            .statuses
+            .retain(|s| s.remaining.is_none() || ends.contains_key(&s.name));
+        sheet.refresh_remaining(now);
+    }
+}
+
+fn remove_orphaned_sheets(
+    sheets: Query<(Entity, &CharacterSheet)>,
+    creatures: Query<(), With<HasCharacterSheet>>,
+    mut commands: Commands,
+) {
+    for (entity, sheet) in sheets.iter() {
+        if !creatures.contains(*sheet.owner) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn client_track_status_updates(
+    mut sheets: Query<&mut CharacterSheetClient, Changed<CharacterSheetClient>>,
+    time: Res<Time>,
+) {
+    for mut sheet in sheets.iter_mut() {
+        if sheet.statuses_changed {
+            sheet.statuses_changed = false;
+            sheet.statuses_received = time.elapsed_seconds();
+        }
+    }
+}
+
+fn client_character_sheet_ui(
+    mut contexts: EguiContexts,
+    sheets: Query<&CharacterSheetClient>,
+    controlled: Query<Entity, With<ClientControlled>>,
+    identities: Res<NetworkIdentities>,
+    keyboard: Res<Input<KeyCode>>,
+    mut open: Local<bool>,
+    time: Res<Time>,
+) {
+    if keyboard.just_pressed(KeyCode::C) {
+        *open = !*open;
+    }
+    if !*open {
+        return;
+    }
+    // Sheets of creatures we controlled before can still be around
+    let Some(sheet) = controlled
+        .get_single()
+        .ok()
+        .and_then(|creature| identities.get_identity(creature))
+        .and_then(|identity| {
+            sheets
+                .iter()
+                .find(|sheet| sheet.owner.get().copied().flatten() == Some(identity))
+        })
+    else {
+        return;
+    };
+
+    egui::Window::new("Character")
+        .open(&mut *open)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.heading(sheet.job.as_deref().unwrap_or("No job"));
+
+            ui.separator();
+            ui.label(egui::RichText::new("Access").strong());
+            if sheet.access.is_empty() {
+                ui.label("None");
+            }
+            for area in sheet.access.iter() {
+                ui.label(area);
+            }
+
+            ui.separator();
+            ui.label(egui::RichText::new("Status effects").strong());
+            if sheet.statuses.is_empty() {
+                ui.label("None");
+            }
+            let elapsed = time.elapsed_seconds() - sheet.statuses_received;
+            for status in sheet.statuses.iter() {
+                match status.remaining {
+                    Some(remaining) => ui.label(format!(
+                        "{} ({:.0}s)",
+                        status.name,
+                        (remaining - elapsed).max(0.0).ceil()
+                    )),
+                    None => ui.label(&status.name),
+                };
+            }
+
+            ui.separator();
+            ui.label(egui::RichText::new("Accents").strong());
+            if sheet.accents.is_empty() {
+                ui.label("None");
+            }
+            for accent in sheet.accents.iter() {
+                ui.horizontal(|ui| {
+                    ui.label(&accent.name);
+                    ui.add(egui::ProgressBar::new(accent.severity.clamp(0.0, 1.0)));
+                });
+            }
+        });
+}
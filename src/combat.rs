@@ -13,14 +13,35 @@ use serde::{Deserialize, Serialize};
 use crate::{
     body::{Hand, Hands},
     camera::MainCamera,
+    controls::{Action, Actions},
     items::containers::Container,
-    ui::has_window,
+    ui::{
+        has_window,
+        toasts::{PlayerToasts, Toast, ToastLevel},
+    },
 };
 
-use self::ranged::RangedPlugin;
+use self::{
+    damage::DamagePlugin,
+    emp::EmpPlugin,
+    feedback::HitFeedbackPlugin,
+    grab::GrabPlugin,
+    melee::MeleePlugin,
+    nonlethal::{NonLethalPlugin, Subdued},
+    projectile::ProjectilePlugin,
+    ranged::RangedPlugin,
+    safety::{SafetyPlugin, WeaponSafety},
+};
 
 pub mod damage;
+pub mod emp;
+pub mod feedback;
+pub mod grab;
+mod melee;
+pub mod nonlethal;
+mod projectile;
 mod ranged;
+pub mod safety;
 pub struct CombatPlugin;
 
 impl Plugin for CombatPlugin {
@@ -44,7 +65,17 @@ impl Plugin for CombatPlugin {
                     .chain(),
             );
         }
-        app.add_plugins(RangedPlugin);
+        app.add_plugins((
+            DamagePlugin,
+            RangedPlugin,
+            ProjectilePlugin,
+            MeleePlugin,
+            EmpPlugin,
+            NonLethalPlugin,
+            GrabPlugin,
+            SafetyPlugin,
+            HitFeedbackPlugin,
+        ));
     }
 }
 
@@ -58,6 +89,10 @@ impl CombatMode {
     pub fn set(&mut self, enabled: bool) {
         *self.enabled = enabled;
     }
+
+    pub fn is_enabled(&self) -> bool {
+        *self.enabled
+    }
 }
 
 #[derive(Component, Networked, TypeUuid, Default)]
@@ -130,11 +165,11 @@ fn client_combat_mode_ui(mut contexts: EguiContexts, status: ClientCombatModeSta
 }
 
 fn client_toggle_combat_mode(
-    keys: Res<Input<KeyCode>>,
+    actions: Actions,
     status: ClientCombatModeStatus,
     mut sender: MessageSender,
 ) {
-    if !keys.just_pressed(KeyCode::Tab) {
+    if !actions.just_pressed(Action::ToggleCombat) {
         return;
     }
 
@@ -200,11 +235,11 @@ struct CombatInput {
 
 fn client_combat_input(
     combat_mode: ClientCombatModeStatus,
-    buttons: Res<Input<MouseButton>>,
+    actions: Actions,
     players: Query<&CombatModeClient, With<ClientControlled>>,
     mut sender: MessageSender,
 ) {
-    if !buttons.just_pressed(MouseButton::Left) {
+    if !actions.just_pressed(Action::Attack) {
         return;
     }
 
@@ -223,7 +258,6 @@ fn client_combat_input(
 
 #[derive(Event)]
 struct CombatInputEvent {
-    #[allow(dead_code)]
     actor: Entity,
     input: CombatInput,
     wielded_weapon: Option<Entity>,
@@ -231,12 +265,16 @@ struct CombatInputEvent {
     used_hand: Option<Entity>,
 }
 
+#[allow(clippy::too_many_arguments)]
 fn handle_attack_request(
     mut events: EventReader<MessageEvent<CombatInput>>,
     players: Res<Players>,
     controls: Res<ClientControls>,
     bodies: Query<&Hands>,
     hand_query: Query<(Entity, &Container), With<Hand>>,
+    subdued: Query<&Subdued>,
+    safeties: Query<&WeaponSafety>,
+    mut toasts: PlayerToasts,
     mut attack_event: EventWriter<CombatInputEvent>,
 ) {
     for event in events.iter() {
@@ -246,6 +284,13 @@ fn handle_attack_request(
         let Some(player_entity) = controls.controlled_entity(player) else {
             continue;
         };
+        if !subdued
+            .get(player_entity)
+            .map(|s| s.can_use_hands())
+            .unwrap_or(true)
+        {
+            continue;
+        }
 
         let hand = bodies
             .get(player_entity)
@@ -255,6 +300,17 @@ fn handle_attack_request(
             hand.and_then(|(_, container)| container.iter().next().map(|(_, item)| *item));
         let used_hand = hand.unzip().0;
 
+        let safety_on = wielded_weapon
+            .and_then(|weapon| safeties.get(weapon).ok())
+            .map_or(false, |safety| safety.on);
+        if safety_on {
+            toasts.send(
+                player_entity,
+                Toast::new(ToastLevel::Warning, "The safety is on."),
+            );
+            continue;
+        }
+
         attack_event.send(CombatInputEvent {
             actor: player_entity,
             input: event.message,
@@ -1,13 +1,152 @@
 use bevy::prelude::*;
+use networking::is_server;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    audio::PlaySound,
+    body::{Body, Limb},
+    communication::{
+        examine::{DescribeExamined, ExamineEvents},
+        SpeechName,
+    },
+    items::{clothes::Equipped, variants::VariantModifiers},
+};
+
+pub struct DamagePlugin;
+
+impl Plugin for DamagePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Armor>();
+        if is_server(app) {
+            app.add_event::<DamageEvent>()
+                .add_event::<DamageDealt>()
+                .add_event::<PracticeHit>()
+                .init_resource::<DamageQueue>()
+                .configure_sets(
+                    Update,
+                    (
+                        DamageStage::Cancel,
+                        DamageStage::Armor,
+                        DamageStage::Statuses,
+                        DamageStage::Apply,
+                    )
+                        .chain()
+                        .after(collect_damage)
+                        .before(finish_damage),
+                )
+                .add_systems(
+                    Update,
+                    (
+                        collect_damage,
+                        (practice_hits, cancel_invulnerable).in_set(DamageStage::Cancel),
+                        apply_armor.in_set(DamageStage::Armor),
+                        finish_damage,
+                        (hit_sounds, log_damage).after(finish_damage),
+                        describe_armor.in_set(DescribeExamined),
+                    ),
+                );
+        }
+    }
+}
+
+/// Stages damage goes through before and while it's applied, in order.
+/// Systems in a stage go through [`DamageQueue::iter_mut`] to change or cancel the damage.
+#[derive(SystemSet, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum DamageStage {
+    /// Damage that shouldn't happen at all, like from practice weapons or to invulnerable creatures
+    Cancel,
+    /// Reduction by worn and built-in armor
+    Armor,
+    /// Changes from the state of the target or attacker, like pain resistance or weakness
+    Statuses,
+    /// Body parts and objects taking the damage, nothing changes it anymore
+    Apply,
+}
+
+/// Damage to an entity, usually a body part. Send it as an event to have it go through the [`DamageStage`]s.
+#[derive(Event, Clone)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub source: Option<AttackSource>,
+    pub damage: Damage,
+    canceled: bool,
+}
+
+impl DamageEvent {
+    pub fn new(target: Entity, damage: Damage) -> Self {
+        Self {
+            target,
+            source: None,
+            damage,
+            canceled: false,
+        }
+    }
+
+    pub fn source(mut self, attacker: Entity, weapon: Option<Entity>) -> Self {
+        self.source = Some(AttackSource { attacker, weapon });
+        self
+    }
+
+    /// Stops the damage from being applied, later stages won't see it
+    pub fn cancel(&mut self) {
+        self.canceled = true;
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Damage {
+    Kinetic(KineticDamage),
+    Tissue(TissueDamage),
+    Emp(EmpDamage),
+}
+
+/// Damage events of this frame that weren't canceled
+#[derive(Resource, Default)]
+pub struct DamageQueue {
+    events: Vec<DamageEvent>,
+}
+
+impl DamageQueue {
+    pub fn iter(&self) -> impl Iterator<Item = &DamageEvent> {
+        self.events.iter().filter(|event| !event.canceled)
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut DamageEvent> {
+        self.events.iter_mut().filter(|event| !event.canceled)
+    }
+}
+
+/// Damage that went through all stages and was applied, for systems reacting to it
+#[derive(Event, Clone)]
+pub struct DamageDealt {
+    pub target: Entity,
+    pub source: Option<AttackSource>,
+    pub damage: Damage,
+}
+
+fn collect_damage(mut events: EventReader<DamageEvent>, mut queue: ResMut<DamageQueue>) {
+    queue.events.extend(events.iter().cloned());
+}
+
+fn finish_damage(mut queue: ResMut<DamageQueue>, mut dealt: EventWriter<DamageDealt>) {
+    for event in queue.events.drain(..).filter(|event| !event.canceled) {
+        dealt.send(DamageDealt {
+            target: event.target,
+            source: event.source,
+            damage: event.damage,
+        });
+    }
+}
 
 #[allow(dead_code)]
+#[derive(Clone, Copy, Deserialize)]
 pub enum KineticShape {
     Blunt,
     Sharp,
     Point,
 }
 
-#[derive(Component)]
+#[derive(Clone, Copy)]
 pub struct KineticDamage {
     /// Relative velocity on impact in m/s
     pub velocity: f32,
@@ -16,9 +155,237 @@ pub struct KineticDamage {
     pub shape: KineticShape,
 }
 
-/// Marker component for entities representing an attack / impact
-#[derive(Component)]
-pub struct Attack;
+/// Who made an attack and with what
+#[derive(Clone, Copy)]
+pub struct AttackSource {
+    pub attacker: Entity,
+    pub weapon: Option<Entity>,
+}
 
+/// Takes no damage, neither itself nor its body parts
 #[derive(Component)]
-pub struct AffectedEntity(pub Entity);
+pub struct Invulnerable;
+
+/// An attack with a practice weapon landed, it did no damage
+#[derive(Event)]
+pub struct PracticeHit {
+    pub attacker: Entity,
+    pub weapon: Entity,
+    pub target: Entity,
+}
+
+/// Electromagnetic pulse hitting an entity, only harmful to electronics
+#[derive(Clone, Copy)]
+pub struct EmpDamage {
+    /// From 0 (harmless) to 1 (destroys wiring outright)
+    pub strength: f32,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize, Reflect)]
+pub enum DamageType {
+    /// Cuts and bruises, bleeds when severe
+    #[default]
+    Brute,
+    Burn,
+}
+
+/// Damage taken directly from the integrity of the affected body part
+#[derive(Clone, Copy)]
+pub struct TissueDamage {
+    /// 1 destroys an undamaged body part
+    pub amount: f32,
+    pub kind: DamageType,
+}
+
+/// Protection against damage, worn as clothing or built into a limb
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Armor {
+    /// Limb zones protected while worn, e.g. "torso" or "arms".
+    /// Armor on a limb itself always protects that limb.
+    pub coverage: Vec<String>,
+    /// Share of brute and kinetic damage stopped, from 0 to 1
+    pub brute: f32,
+    /// Share of burn damage stopped, from 0 to 1
+    pub burn: f32,
+}
+
+impl Armor {
+    pub fn protection(&self, kind: DamageType) -> f32 {
+        match kind {
+            DamageType::Brute => self.brute,
+            DamageType::Burn => self.burn,
+        }
+        .clamp(0.0, 1.0)
+    }
+
+    pub fn covers(&self, zone: &str) -> bool {
+        self.coverage.iter().any(|covered| covered == zone)
+    }
+}
+
+fn apply_armor(
+    mut queue: ResMut<DamageQueue>,
+    limbs: Query<&Limb>,
+    armor: Query<(&Armor, Option<&Equipped>)>,
+    bodies: Query<(), With<Body>>,
+    parents: Query<&Parent>,
+    children: Query<&Children>,
+) {
+    for event in queue.iter_mut() {
+        let part = event.target;
+        let Ok(limb) = limbs.get(part) else {
+            continue;
+        };
+
+        // Worn armor covering the zone of the limb, and armor of the limb itself
+        let body = parents.iter_ancestors(part).find(|&e| bodies.contains(e));
+        let worn = body
+            .into_iter()
+            .flat_map(|body| children.iter_descendants(body))
+            .filter_map(|entity| armor.get(entity).ok())
+            .filter(|(armor, equipped)| equipped.is_some() && armor.covers(limb.zone()))
+            .map(|(armor, _)| armor);
+        let pieces: Vec<&Armor> = armor
+            .get(part)
+            .ok()
+            .map(|(armor, _)| armor)
+            .into_iter()
+            .chain(worn)
+            .collect();
+        if pieces.is_empty() {
+            continue;
+        }
+
+        // Layers each stop their share of what got through the previous ones
+        let remaining = |kind: DamageType| {
+            pieces
+                .iter()
+                .map(|armor| 1.0 - armor.protection(kind))
+                .product::<f32>()
+        };
+        match &mut event.damage {
+            Damage::Kinetic(kinetic) => kinetic.mass *= remaining(DamageType::Brute),
+            Damage::Tissue(tissue) => tissue.amount *= remaining(tissue.kind),
+            Damage::Emp(_) => {}
+        }
+    }
+}
+
+/// Cancels damage done with practice weapons, reporting it as a hit instead
+fn practice_hits(
+    mut queue: ResMut<DamageQueue>,
+    weapons: Query<&VariantModifiers>,
+    transforms: Query<&GlobalTransform>,
+    mut hits: EventWriter<PracticeHit>,
+    mut sounds: EventWriter<PlaySound>,
+) {
+    for event in queue.iter_mut() {
+        let Some((attacker, weapon)) = event.source.and_then(|source| {
+            source
+                .weapon
+                .filter(|&weapon| weapons.get(weapon).map_or(false, |m| m.practice))
+                .map(|weapon| (source.attacker, weapon))
+        }) else {
+            continue;
+        };
+
+        event.cancel();
+        hits.send(PracticeHit {
+            attacker,
+            weapon,
+            target: event.target,
+        });
+        if let Ok(transform) = transforms.get(event.target) {
+            sounds.send(PlaySound::new(
+                "sounds/combat/practice_hit.ogg",
+                transform.translation(),
+            ));
+        }
+    }
+}
+
+fn cancel_invulnerable(
+    mut queue: ResMut<DamageQueue>,
+    invulnerable: Query<(), With<Invulnerable>>,
+    parents: Query<&Parent>,
+) {
+    for event in queue.iter_mut() {
+        if std::iter::once(event.target)
+            .chain(parents.iter_ancestors(event.target))
+            .any(|e| invulnerable.contains(e))
+        {
+            event.cancel();
+        }
+    }
+}
+
+/// Plays a sound where kinetic damage lands
+fn hit_sounds(
+    mut dealt: EventReader<DamageDealt>,
+    transforms: Query<&GlobalTransform>,
+    mut sounds: EventWriter<PlaySound>,
+) {
+    for event in dealt.iter() {
+        let Damage::Kinetic(damage) = event.damage else {
+            continue;
+        };
+        let Ok(transform) = transforms.get(event.target) else {
+            continue;
+        };
+        let asset = match damage.shape {
+            KineticShape::Blunt => "sounds/combat/blunt.ogg",
+            KineticShape::Sharp => "sounds/combat/slash.ogg",
+            KineticShape::Point => "sounds/combat/bullet_hit.ogg",
+        };
+        sounds.send(PlaySound::new(asset, transform.translation()));
+    }
+}
+
+/// Logs who hurt whom, for staff looking into what happened
+fn log_damage(
+    mut dealt: EventReader<DamageDealt>,
+    names: Query<&SpeechName>,
+    parents: Query<&Parent>,
+) {
+    let name_of = |entity: Entity| {
+        std::iter::once(entity)
+            .chain(parents.iter_ancestors(entity))
+            .find_map(|e| names.get(e).ok())
+            .map_or_else(|| format!("{:?}", entity), |name| name.0.clone())
+    };
+    for event in dealt.iter() {
+        let Some(source) = event.source else {
+            continue;
+        };
+        let kind = match event.damage {
+            Damage::Kinetic(_) => "kinetic",
+            Damage::Tissue(_) => "tissue",
+            Damage::Emp(_) => "emp",
+        };
+        info!(
+            attacker = %name_of(source.attacker),
+            target = %name_of(event.target),
+            kind,
+            "Damage dealt"
+        );
+    }
+}
+
+fn describe_armor(examines: Res<ExamineEvents>, armor: Query<&Armor>) {
+    for examine in examines.events.iter() {
+        let Ok(armor) = armor.get(examine.target) else {
+            continue;
+        };
+        let mut line = format!(
+            "It stops {:.0}% of brute and {:.0}% of burn damage",
+            armor.protection(DamageType::Brute) * 100.0,
+            armor.protection(DamageType::Burn) * 100.0
+        );
+        if !armor.coverage.is_empty() {
+            line += &format!(" to the {}", armor.coverage.join(", "));
+        }
+        line.push('.');
+        examine.add_line(line);
+    }
+}
@@ -0,0 +1,205 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use networking::is_server;
+use power::{AreaPowerController, Disrupted, PowerConsumer};
+
+use crate::{
+    body::{health::prosthetic::RoboticBodyPart, Body},
+    combat::damage::*,
+    communication::RadioJammed,
+    despawn::{ContentsPolicy, DespawnCommandsExt},
+    interaction::{
+        ActiveInteraction, GenerateInteractionList, InteractionListEvents, InteractionOption,
+        InteractionSpecificity, InteractionStatus,
+    },
+    items::cells::{PowerCell, PoweredDevice},
+};
+
+pub struct EmpPlugin;
+
+impl Plugin for EmpPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<EmpGrenade>();
+        if is_server(app) {
+            app.register_type::<DetonateEmpInteraction>()
+                .add_event::<Emp>()
+                .add_systems(
+                    Update,
+                    (
+                        prepare_detonate_interaction.in_set(GenerateInteractionList),
+                        detonate_interaction,
+                        handle_emp,
+                    )
+                        .chain(),
+                );
+        }
+    }
+}
+
+/// How long a full strength EMP knocks out electronics in seconds
+const MAX_DISRUPTION_TIME: f32 = 30.0;
+const DETONATE_TIME: Duration = Duration::from_secs(1);
+
+/// An electromagnetic pulse going off
+#[derive(Event)]
+pub struct Emp {
+    pub position: Vec3,
+    pub radius: f32,
+    /// Strength at the center, from 0 to 1. Falls off linearly towards the edge.
+    pub strength: f32,
+}
+
+impl Emp {
+    pub(crate) fn strength_at(&self, position: Vec3) -> Option<f32> {
+        let distance = self.position.distance(position);
+        (distance <= self.radius).then(|| self.strength * (1.0 - distance / self.radius))
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_emp(
+    mut events: EventReader<Emp>,
+    robotic_parts: Query<(Entity, &GlobalTransform), With<RoboticBodyPart>>,
+    machines: Query<(Entity, &GlobalTransform, Option<&Disrupted>), With<PowerConsumer>>,
+    mut apcs: Query<(&mut AreaPowerController, &GlobalTransform)>,
+    mut cells: Query<(&mut PowerCell, &GlobalTransform)>,
+    mut devices: Query<(&mut PoweredDevice, &GlobalTransform)>,
+    creatures: Query<(Entity, &GlobalTransform), With<Body>>,
+    time: Res<Time>,
+    mut damage: EventWriter<DamageEvent>,
+    mut commands: Commands,
+) {
+    let now = time.elapsed_seconds();
+    for emp in events.iter() {
+        for (part, transform) in robotic_parts.iter() {
+            let Some(strength) = emp.strength_at(transform.translation()) else {
+                continue;
+            };
+            damage.send(DamageEvent::new(part, Damage::Emp(EmpDamage { strength })));
+        }
+
+        for (machine, transform, disrupted) in machines.iter() {
+            let Some(strength) = emp.strength_at(transform.translation()) else {
+                continue;
+            };
+            let until = now + strength * MAX_DISRUPTION_TIME;
+            if disrupted.map(|d| d.until >= until).unwrap_or_default() {
+                continue;
+            }
+            commands.entity(machine).insert(Disrupted { until });
+        }
+
+        for (mut apc, transform) in apcs.iter_mut() {
+            let Some(strength) = emp.strength_at(transform.translation()) else {
+                continue;
+            };
+            apc.charge *= 1.0 - strength;
+        }
+
+        for (mut cell, transform) in cells.iter_mut() {
+            let Some(strength) = emp.strength_at(transform.translation()) else {
+                continue;
+            };
+            cell.drain(strength);
+        }
+
+        for (mut device, transform) in devices.iter_mut() {
+            if emp.strength_at(transform.translation()).is_some() {
+                device.set_active(false);
+            }
+        }
+
+        for (creature, transform) in creatures.iter() {
+            let Some(strength) = emp.strength_at(transform.translation()) else {
+                continue;
+            };
+            commands.entity(creature).insert(RadioJammed {
+                until: now + strength * MAX_DISRUPTION_TIME,
+            });
+        }
+
+        info!(position = ?emp.position, radius = emp.radius, "EMP went off");
+    }
+}
+
+/// A handheld device releasing an EMP around its user.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+struct EmpGrenade {
+    radius: f32,
+    strength: f32,
+}
+
+impl Default for EmpGrenade {
+    fn default() -> Self {
+        Self {
+            radius: 5.0,
+            strength: 0.8,
+        }
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct DetonateEmpInteraction {
+    grenade: Entity,
+}
+
+impl FromWorld for DetonateEmpInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            grenade: Entity::PLACEHOLDER,
+        }
+    }
+}
+
+fn prepare_detonate_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    grenades: Query<(), With<EmpGrenade>>,
+) {
+    for event in interaction_list.events.iter() {
+        let Some(item) = event.item_in_hand else {
+            continue;
+        };
+        if !grenades.contains(item) {
+            continue;
+        }
+
+        event.add_interaction(InteractionOption {
+            text: "Detonate EMP".into(),
+            interaction: Box::new(DetonateEmpInteraction { grenade: item }),
+            specificity: InteractionSpecificity::Generic,
+        });
+    }
+}
+
+fn detonate_interaction(
+    mut query: Query<(&DetonateEmpInteraction, &mut ActiveInteraction)>,
+    grenades: Query<(&EmpGrenade, &GlobalTransform)>,
+    mut emps: EventWriter<Emp>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (interaction, mut active) in query.iter_mut() {
+        active.set_initial_duration(DETONATE_TIME);
+
+        let Ok((grenade, transform)) = grenades.get(interaction.grenade) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+
+        if active.start_time() + DETONATE_TIME.as_secs_f32() > time.elapsed_seconds() {
+            continue;
+        }
+
+        emps.send(Emp {
+            position: transform.translation(),
+            radius: grenade.radius,
+            strength: grenade.strength,
+        });
+        commands.despawn_cascade(interaction.grenade, ContentsPolicy::Destroy);
+        active.status = InteractionStatus::Completed;
+    }
+}
@@ -0,0 +1,275 @@
+//! Optional feedback for attackers on what their hits did.
+//!
+//! The server only tells attackers the body zone and a rough level of effect,
+//! never exact damage or the health left. Clients show hits as markers or numbers,
+//! or not at all, which is the default so fights stay immersive.
+
+use std::fs::{read_to_string, write};
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_egui::{egui, EguiContexts};
+use networking::{
+    is_server,
+    messaging::{AppExt, MessageEvent, MessageReceivers, MessageSender},
+    spawning::ClientControls,
+    Players,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{body::Limb, camera::MainCamera, ui::has_window, GameState};
+
+use super::damage::{Damage, DamageDealt};
+
+pub struct HitFeedbackPlugin;
+
+impl Plugin for HitFeedbackPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_network_message::<HitMessage>();
+
+        if is_server(app) {
+            app.add_systems(Update, send_hits);
+        } else {
+            app.insert_resource(load_settings()).add_systems(
+                Update,
+                (
+                    client_show_hits
+                        .run_if(has_window)
+                        .run_if(in_state(GameState::Game)),
+                    save_settings.run_if(resource_changed::<HitFeedbackSettings>()),
+                ),
+            );
+        }
+    }
+}
+
+/// Tissue damage below this counts as stopped
+const BLOCKED_TISSUE_DAMAGE: f32 = 0.01;
+/// Upper bounds of tissue damage for light and moderate hits
+const TISSUE_THRESHOLDS: (f32, f32) = (0.1, 0.3);
+/// Upper bounds of impact energy in joules for light and moderate hits
+const KINETIC_THRESHOLDS: (f32, f32) = (50.0, 150.0);
+/// Upper bounds of EMP strength for light and moderate hits
+const EMP_THRESHOLDS: (f32, f32) = (0.3, 0.6);
+
+const HIT_SECONDS: f32 = 0.6;
+const HIT_MARKER_SIZE: f32 = 0.15;
+/// Meters numbers rise over their lifetime
+const NUMBER_RISE: f32 = 0.4;
+
+/// How much a hit did, in steps too coarse to work out anyone's health
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum HitEffect {
+    /// Armor or something else stopped it
+    Blocked,
+    Light,
+    Moderate,
+    Heavy,
+}
+
+impl HitEffect {
+    fn of(damage: &Damage) -> Self {
+        let level = |value: f32, (light, moderate): (f32, f32)| {
+            if value < light {
+                HitEffect::Light
+            } else if value < moderate {
+                HitEffect::Moderate
+            } else {
+                HitEffect::Heavy
+            }
+        };
+        match damage {
+            Damage::Tissue(tissue) if tissue.amount < BLOCKED_TISSUE_DAMAGE => HitEffect::Blocked,
+            Damage::Tissue(tissue) => level(tissue.amount, TISSUE_THRESHOLDS),
+            Damage::Kinetic(kinetic) if kinetic.mass <= 0.0 => HitEffect::Blocked,
+            Damage::Kinetic(kinetic) => level(
+                0.5 * kinetic.mass * kinetic.velocity * kinetic.velocity,
+                KINETIC_THRESHOLDS,
+            ),
+            Damage::Emp(emp) => level(emp.strength, EMP_THRESHOLDS),
+        }
+    }
+
+    /// Rough share of a body part the hit took, shown as a floating number
+    fn number(self) -> &'static str {
+        match self {
+            HitEffect::Blocked => "0",
+            HitEffect::Light => "<10",
+            HitEffect::Moderate => "10-30",
+            HitEffect::Heavy => "30+",
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            HitEffect::Blocked => Color::GRAY,
+            HitEffect::Light => Color::YELLOW,
+            HitEffect::Moderate => Color::ORANGE,
+            HitEffect::Heavy => Color::RED,
+        }
+    }
+}
+
+/// Tells an attacker one of their attacks hit something
+#[derive(Serialize, Deserialize, Clone)]
+struct HitMessage {
+    position: Vec3,
+    /// Body zone that was hit, like "head" or "arms"
+    zone: Option<String>,
+    effect: HitEffect,
+}
+
+/// Sends attackers one hit per target and frame, with the strongest effect
+fn send_hits(
+    mut dealt: EventReader<DamageDealt>,
+    transforms: Query<&GlobalTransform>,
+    limbs: Query<&Limb>,
+    controls: Res<ClientControls>,
+    players: Res<Players>,
+    mut sender: MessageSender,
+) {
+    let mut hits: HashMap<(Entity, Entity), HitEffect> = HashMap::default();
+    for event in dealt.iter() {
+        let Some(source) = event.source else {
+            continue;
+        };
+        let effect = HitEffect::of(&event.damage);
+        hits.entry((source.attacker, event.target))
+            .and_modify(|current| *current = (*current).max(effect))
+            .or_insert(effect);
+    }
+
+    for ((attacker, target), effect) in hits {
+        let Some(connection) = controls
+            .controlling_player(attacker)
+            .and_then(|player| players.get_connection(&player))
+        else {
+            continue;
+        };
+        let Ok(transform) = transforms.get(target) else {
+            continue;
+        };
+        sender.send(
+            &HitMessage {
+                position: transform.translation(),
+                zone: limbs.get(target).ok().map(|limb| limb.zone().to_owned()),
+                effect,
+            },
+            MessageReceivers::Single(connection),
+        );
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum HitFeedback {
+    #[default]
+    Off,
+    /// A cross where the hit landed, sized and colored by its effect
+    Markers,
+    /// The hit zone and a rough damage number floating up from the hit
+    Numbers,
+}
+
+impl HitFeedback {
+    pub const ALL: [HitFeedback; 3] =
+        [HitFeedback::Off, HitFeedback::Markers, HitFeedback::Numbers];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HitFeedback::Off => "Off",
+            HitFeedback::Markers => "Markers",
+            HitFeedback::Numbers => "Numbers",
+        }
+    }
+}
+
+/// Client setting for how hits are shown
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub struct HitFeedbackSettings {
+    #[serde(default)]
+    pub feedback: HitFeedback,
+}
+
+const SETTINGS_FILE: &str = "combat.toml";
+
+fn load_settings() -> HitFeedbackSettings {
+    let Ok(text) = read_to_string(SETTINGS_FILE) else {
+        return HitFeedbackSettings::default();
+    };
+    toml::from_str(&text).unwrap_or_else(|err| {
+        error!("Error loading combat settings: {}", err);
+        HitFeedbackSettings::default()
+    })
+}
+
+fn save_settings(settings: Res<HitFeedbackSettings>) {
+    if settings.is_added() {
+        return;
+    }
+    let result = toml::to_string(&*settings)
+        .map_err(|err| err.to_string())
+        .and_then(|text| write(SETTINGS_FILE, text).map_err(|err| err.to_string()));
+    if let Err(err) = result {
+        error!("Error saving combat settings: {}", err);
+    }
+}
+
+fn client_show_hits(
+    mut messages: EventReader<MessageEvent<HitMessage>>,
+    settings: Res<HitFeedbackSettings>,
+    mut current: Local<Vec<(f32, HitMessage)>>,
+    time: Res<Time>,
+    cameras: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut contexts: EguiContexts,
+    mut gizmos: Gizmos,
+) {
+    if settings.feedback == HitFeedback::Off {
+        messages.clear();
+        current.clear();
+        return;
+    }
+
+    let now = time.elapsed_seconds();
+    current.extend(messages.iter().map(|event| (now, event.message.clone())));
+    current.retain(|(time, _)| now - time < HIT_SECONDS);
+
+    match settings.feedback {
+        HitFeedback::Off => {}
+        HitFeedback::Markers => {
+            for (_, hit) in current.iter() {
+                let size = HIT_MARKER_SIZE * (1.0 + hit.effect as u8 as f32 * 0.5);
+                let a = Vec3::new(size, 0.0, size);
+                let b = Vec3::new(size, 0.0, -size);
+                gizmos.line(hit.position - a, hit.position + a, hit.effect.color());
+                gizmos.line(hit.position - b, hit.position + b, hit.effect.color());
+            }
+        }
+        HitFeedback::Numbers => {
+            let Some((camera, camera_transform)) = cameras.iter().next() else {
+                return;
+            };
+            let painter = contexts.ctx_mut().layer_painter(egui::LayerId::new(
+                egui::Order::Background,
+                egui::Id::new("hit_numbers"),
+            ));
+            for (at, hit) in current.iter() {
+                let age = (now - at) / HIT_SECONDS;
+                let position = hit.position + Vec3::Y * NUMBER_RISE * age;
+                let Some(screen) = camera.world_to_viewport(camera_transform, position) else {
+                    continue;
+                };
+                let text = match &hit.zone {
+                    Some(zone) => format!("{} {}", hit.effect.number(), zone),
+                    None => hit.effect.number().to_owned(),
+                };
+                let [r, g, b, _] = hit.effect.color().as_rgba_f32();
+                painter.text(
+                    egui::pos2(screen.x, screen.y),
+                    egui::Align2::CENTER_CENTER,
+                    text,
+                    egui::FontId::proportional(16.0),
+                    egui::Rgba::from_rgba_unmultiplied(r, g, b, 1.0 - age).into(),
+                );
+            }
+        }
+    }
+}
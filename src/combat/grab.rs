@@ -0,0 +1,463 @@
+//! Grabbing and pulling creatures and loose items.
+//!
+//! A passive grab pulls the target along, but a conscious creature can still walk away.
+//! An aggressive grab takes movement control away from the target until it's released.
+
+use std::time::Duration;
+
+use bevy::{ecs::query::Has, math::Vec3Swizzles, prelude::*, reflect::TypeUuid};
+use bevy_egui::{egui, EguiContexts};
+use bevy_rapier3d::prelude::Velocity;
+use networking::{
+    component::AppExt,
+    is_server,
+    spawning::ClientControlled,
+    transform::ClientMovement,
+    variable::{NetworkVar, ServerVar},
+    Networked,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    body::{ghost::Ghost, health::BrainStateEvent, Body},
+    interaction::{
+        ActiveInteraction, GenerateInteractionList, InteractionListEvents, InteractionOption,
+        InteractionSpecificity, InteractionStatus,
+    },
+    items::{Item, StoredItem},
+    ui::has_window,
+};
+
+use super::{damage::DamageDealt, nonlethal::Subdued};
+
+pub struct GrabPlugin;
+
+impl Plugin for GrabPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_networked_component::<Grabbing, GrabbingClient>()
+            .add_networked_component::<Grabbed, GrabbedClient>();
+        if is_server(app) {
+            app.register_type::<GrabInteraction>()
+                .register_type::<TightenGrabInteraction>()
+                .register_type::<ReleaseGrabInteraction>()
+                .register_type::<BreakFreeInteraction>()
+                .add_systems(
+                    Update,
+                    (
+                        prepare_grab_interactions.in_set(GenerateInteractionList),
+                        grab_interaction,
+                        tighten_grab_interaction,
+                        release_grab_interaction,
+                        break_free_interaction,
+                        (
+                            release_on_damage,
+                            release_on_brain_state.run_if(on_event::<BrainStateEvent>()),
+                            pull_grabbed,
+                        )
+                            .chain(),
+                    ),
+                );
+        } else {
+            app.add_systems(Update, client_grab_ui.run_if(has_window));
+        }
+    }
+}
+
+const TIGHTEN_GRAB_TIME: Duration = Duration::from_millis(1500);
+const BREAK_PASSIVE_GRAB_TIME: Duration = Duration::from_secs(1);
+const BREAK_AGGRESSIVE_GRAB_TIME: Duration = Duration::from_secs(4);
+/// Targets closer than this aren't pulled
+const PULL_DISTANCE: f32 = 1.0;
+/// Grabs are let go once the target is this far away
+const BREAK_DISTANCE: f32 = 2.5;
+const MAX_PULL_SPEED: f32 = 5.0;
+/// How fast a pulled target catches up, per meter it's behind
+const PULL_STIFFNESS: f32 = 8.0;
+/// Walking speed of someone pulling something
+pub const PULLING_MOVEMENT_FACTOR: f32 = 0.75;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum GrabLevel {
+    #[default]
+    Passive,
+    Aggressive,
+}
+
+/// Placed on a creature holding on to something
+#[derive(Component, Networked)]
+#[networked(client = "GrabbingClient")]
+pub struct Grabbing {
+    target: Entity,
+    level: NetworkVar<GrabLevel>,
+}
+
+#[derive(Component, Default, TypeUuid, Networked)]
+#[uuid = "c3a7e1f4-2b86-4d09-9e5a-71f0d8b2c64e"]
+#[networked(server = "Grabbing")]
+pub struct GrabbingClient {
+    level: ServerVar<GrabLevel>,
+}
+
+/// Placed on whatever is being held
+#[derive(Component, Networked)]
+#[networked(client = "GrabbedClient")]
+pub struct Grabbed {
+    by: Entity,
+    level: NetworkVar<GrabLevel>,
+    /// Movement control was taken from the target and must be given back on release
+    restore_movement: bool,
+}
+
+#[derive(Component, Default, TypeUuid, Networked)]
+#[uuid = "5e2d9b47-a1c8-4f63-8d7e-0b94c3f1a285"]
+#[networked(server = "Grabbed")]
+pub struct GrabbedClient {
+    level: ServerVar<GrabLevel>,
+}
+
+/// Lets go of a grab.
+/// Movement isn't given back if something else is already taking care of it.
+fn release(commands: &mut Commands, grabber: Entity, target: Entity, restore_movement: bool) {
+    if let Some(mut grabber) = commands.get_entity(grabber) {
+        grabber.remove::<Grabbing>();
+    }
+    if let Some(mut target) = commands.get_entity(target) {
+        target.remove::<Grabbed>();
+        if restore_movement {
+            target.insert(ClientMovement);
+        }
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct GrabInteraction;
+
+impl FromWorld for GrabInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct TightenGrabInteraction;
+
+impl FromWorld for TightenGrabInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct ReleaseGrabInteraction;
+
+impl FromWorld for ReleaseGrabInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct BreakFreeInteraction;
+
+impl FromWorld for BreakFreeInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self
+    }
+}
+
+fn prepare_grab_interactions(
+    interaction_list: Res<InteractionListEvents>,
+    grabbing: Query<&Grabbing>,
+    grabbed: Query<&Grabbed>,
+    creatures: Query<(), (With<Body>, Without<Ghost>)>,
+    loose_items: Query<(), (With<Item>, Without<StoredItem>)>,
+    subdued: Query<&Subdued>,
+) {
+    for event in interaction_list.events.iter() {
+        if event.source == event.target {
+            if grabbed.contains(event.source) {
+                event.add_interaction(InteractionOption {
+                    text: "Break free".into(),
+                    interaction: Box::new(BreakFreeInteraction),
+                    specificity: InteractionSpecificity::Specific,
+                });
+            }
+            continue;
+        }
+
+        if let Some(grab) = grabbing
+            .get(event.source)
+            .ok()
+            .filter(|g| g.target == event.target)
+        {
+            if *grab.level == GrabLevel::Passive && creatures.contains(event.target) {
+                event.add_interaction(InteractionOption {
+                    text: "Grab tighter".into(),
+                    interaction: Box::new(TightenGrabInteraction),
+                    specificity: InteractionSpecificity::Common,
+                });
+            }
+            event.add_interaction(InteractionOption {
+                text: "Let go".into(),
+                interaction: Box::new(ReleaseGrabInteraction),
+                specificity: InteractionSpecificity::Common,
+            });
+            continue;
+        }
+
+        // Grabbing needs a free hand that can be used
+        if event.used_hand.is_none() || event.item_in_hand.is_some() {
+            continue;
+        }
+        if subdued
+            .get(event.source)
+            .map_or(false, |s| !s.can_use_hands())
+        {
+            continue;
+        }
+        let text = if creatures.contains(event.target) {
+            "Grab"
+        } else if loose_items.contains(event.target) {
+            "Pull"
+        } else {
+            continue;
+        };
+        event.add_interaction(InteractionOption {
+            text: text.into(),
+            interaction: Box::new(GrabInteraction),
+            specificity: InteractionSpecificity::Generic,
+        });
+    }
+}
+
+fn grab_interaction(
+    mut query: Query<(Entity, &mut ActiveInteraction, Option<&Grabbing>), With<GrabInteraction>>,
+    grabbed: Query<&Grabbed>,
+    mut commands: Commands,
+) {
+    for (source, mut active, previous) in query.iter_mut() {
+        let target = active.target;
+        if grabbed.contains(target) {
+            // Someone else already has it
+            active.status = InteractionStatus::Canceled;
+            continue;
+        }
+
+        if let Some(previous) = previous {
+            let restore = grabbed
+                .get(previous.target)
+                .map_or(false, |g| g.restore_movement);
+            release(&mut commands, source, previous.target, restore);
+        }
+        commands.entity(source).insert(Grabbing {
+            target,
+            level: GrabLevel::Passive.into(),
+        });
+        commands.entity(target).insert(Grabbed {
+            by: source,
+            level: GrabLevel::Passive.into(),
+            restore_movement: false,
+        });
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+fn tighten_grab_interaction(
+    mut query: Query<(Entity, &mut ActiveInteraction, &mut Grabbing), With<TightenGrabInteraction>>,
+    mut targets: Query<(&mut Grabbed, Has<ClientMovement>)>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (source, mut active, mut grabbing) in query.iter_mut() {
+        active.set_initial_duration(TIGHTEN_GRAB_TIME);
+
+        let valid = grabbing.target == active.target && *grabbing.level == GrabLevel::Passive;
+        let Some((mut grabbed, has_movement)) = targets
+            .get_mut(active.target)
+            .ok()
+            .filter(|(g, _)| valid && g.by == source)
+        else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+
+        if active.start_time() + TIGHTEN_GRAB_TIME.as_secs_f32() > time.elapsed_seconds() {
+            continue;
+        }
+
+        *grabbing.level = GrabLevel::Aggressive;
+        *grabbed.level = GrabLevel::Aggressive;
+        if has_movement {
+            commands.entity(active.target).remove::<ClientMovement>();
+            grabbed.restore_movement = true;
+        }
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+fn release_grab_interaction(
+    mut query: Query<(Entity, &mut ActiveInteraction, &Grabbing), With<ReleaseGrabInteraction>>,
+    grabbed: Query<&Grabbed>,
+    mut commands: Commands,
+) {
+    for (source, mut active, grabbing) in query.iter_mut() {
+        let restore = grabbed
+            .get(grabbing.target)
+            .map_or(false, |g| g.restore_movement);
+        release(&mut commands, source, grabbing.target, restore);
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+fn break_free_interaction(
+    mut query: Query<(Entity, &mut ActiveInteraction, &Grabbed), With<BreakFreeInteraction>>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (source, mut active, grabbed) in query.iter_mut() {
+        let duration = match *grabbed.level {
+            GrabLevel::Passive => BREAK_PASSIVE_GRAB_TIME,
+            GrabLevel::Aggressive => BREAK_AGGRESSIVE_GRAB_TIME,
+        };
+        active.set_initial_duration(duration);
+
+        if active.start_time() + duration.as_secs_f32() > time.elapsed_seconds() {
+            continue;
+        }
+
+        release(&mut commands, grabbed.by, source, grabbed.restore_movement);
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+/// Getting hurt makes a creature let go
+fn release_on_damage(
+    mut dealt: EventReader<DamageDealt>,
+    grabbers: Query<&Grabbing>,
+    grabbed: Query<&Grabbed>,
+    parents: Query<&Parent>,
+    mut commands: Commands,
+) {
+    for event in dealt.iter() {
+        let Some((grabber, grabbing)) = std::iter::once(event.target)
+            .chain(parents.iter_ancestors(event.target))
+            .find_map(|e| grabbers.get(e).ok().map(|g| (e, g)))
+        else {
+            continue;
+        };
+        let restore = grabbed
+            .get(grabbing.target)
+            .map_or(false, |g| g.restore_movement);
+        release(&mut commands, grabber, grabbing.target, restore);
+    }
+}
+
+/// Fainting or waking up ends grabs. Movement of the target is then handled by its new state.
+fn release_on_brain_state(
+    mut events: EventReader<BrainStateEvent>,
+    grabbers: Query<&Grabbing>,
+    grabbed: Query<&Grabbed>,
+    parents: Query<&Parent>,
+    mut commands: Commands,
+) {
+    for event in events.iter() {
+        for entity in parents.iter_ancestors(event.brain) {
+            if let Ok(grabbing) = grabbers.get(entity) {
+                let restore = grabbed
+                    .get(grabbing.target)
+                    .map_or(false, |g| g.restore_movement);
+                release(&mut commands, entity, grabbing.target, restore);
+            }
+            if let Ok(target) = grabbed.get(entity) {
+                release(&mut commands, target.by, entity, false);
+            }
+        }
+    }
+}
+
+/// Drags grabbed things behind their grabber and lets go of ones that got away
+#[allow(clippy::type_complexity)]
+fn pull_grabbed(
+    grabbers: Query<(Entity, &Grabbing, &GlobalTransform, Option<&Subdued>)>,
+    mut targets: Query<(
+        Entity,
+        &Grabbed,
+        &GlobalTransform,
+        Option<&mut Velocity>,
+        Has<StoredItem>,
+    )>,
+    mut commands: Commands,
+) {
+    for (grabber, grabbing, transform, subdued) in grabbers.iter() {
+        let Ok((_, grabbed, target_transform, velocity, stored)) = targets.get_mut(grabbing.target)
+        else {
+            commands.entity(grabber).remove::<Grabbing>();
+            continue;
+        };
+
+        let offset = transform.translation().xz() - target_transform.translation().xz();
+        let distance = offset.length();
+        let can_hold = subdued.map_or(true, |s| s.can_use_hands());
+        if stored || !can_hold || distance > BREAK_DISTANCE {
+            release(
+                &mut commands,
+                grabber,
+                grabbing.target,
+                grabbed.restore_movement,
+            );
+            continue;
+        }
+
+        let Some(mut velocity) = velocity else {
+            continue;
+        };
+        if distance > PULL_DISTANCE {
+            let speed = ((distance - PULL_DISTANCE) * PULL_STIFFNESS).min(MAX_PULL_SPEED);
+            let pull = offset / distance * speed;
+            velocity.linvel.x = pull.x;
+            velocity.linvel.z = pull.y;
+        } else if *grabbed.level == GrabLevel::Aggressive {
+            // Held tight, so don't let it drift away
+            velocity.linvel.x = 0.0;
+            velocity.linvel.z = 0.0;
+        }
+    }
+
+    // The grabber was despawned
+    for (target, grabbed, ..) in targets.iter() {
+        if !grabbers.contains(grabbed.by) {
+            release(&mut commands, grabbed.by, target, grabbed.restore_movement);
+        }
+    }
+}
+
+fn client_grab_ui(
+    mut contexts: EguiContexts,
+    grabbing: Query<&GrabbingClient, With<ClientControlled>>,
+    grabbed: Query<&GrabbedClient, With<ClientControlled>>,
+) {
+    let text = match (grabbing.get_single(), grabbed.get_single()) {
+        (_, Ok(grabbed)) => match grabbed.level.get() {
+            Some(GrabLevel::Aggressive) => "Held tight",
+            _ => "Being pulled",
+        },
+        (Ok(grabbing), _) => match grabbing.level.get() {
+            Some(GrabLevel::Aggressive) => "Holding tight",
+            _ => "Pulling",
+        },
+        _ => return,
+    };
+    egui::Area::new("grab_indicator")
+        .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0.0, -10.0))
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(egui::RichText::new(text).strong());
+        });
+}
@@ -0,0 +1,159 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::{CollisionGroups, QueryFilter, RapierContext};
+use networking::{
+    is_server,
+    messaging::{AppExt, MessageEvent, MessageReceivers, MessageSender},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::{damage::*, RANGED_AIM_HEIGHT},
+    items::variants::VariantModifiers,
+    GameState,
+};
+
+use super::CombatInputEvent;
+
+pub struct MeleePlugin;
+
+impl Plugin for MeleePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<MeleeWeapon>()
+            .register_type::<DamageType>()
+            .add_network_message::<MeleeSwingMessage>();
+
+        if is_server(app) {
+            app.add_systems(Update, swing_melee_weapon);
+        } else {
+            app.add_systems(
+                Update,
+                client_handle_swing_effects.run_if(in_state(GameState::Game)),
+            );
+        }
+    }
+}
+
+/// A weapon that hits the body part in front of its wielder
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+struct MeleeWeapon {
+    /// Integrity taken from the body part that is hit
+    damage: f32,
+    damage_type: DamageType,
+    time_between_swings: Duration,
+    /// How far the weapon reaches from the center of its wielder
+    reach: f32,
+    #[reflect(ignore)]
+    next_swing_time: f32,
+}
+
+impl Default for MeleeWeapon {
+    fn default() -> Self {
+        Self {
+            damage: 0.1,
+            damage_type: DamageType::Brute,
+            time_between_swings: Duration::from_secs(1),
+            reach: 1.5,
+            next_swing_time: 0.0,
+        }
+    }
+}
+
+fn swing_melee_weapon(
+    mut input: EventReader<CombatInputEvent>,
+    mut weapons: Query<(&mut MeleeWeapon, Option<&VariantModifiers>)>,
+    parents: Query<&Parent>,
+    rapier: Res<RapierContext>,
+    time: Res<Time>,
+    mut damage: EventWriter<DamageEvent>,
+    mut sender: MessageSender,
+) {
+    let now = time.elapsed_seconds();
+    for event in input.iter() {
+        if !event.input.primary_attack {
+            continue;
+        }
+        let Some(weapon_entity) = event.wielded_weapon else {
+            continue;
+        };
+        let Ok((mut weapon, modifiers)) = weapons.get_mut(weapon_entity) else {
+            continue;
+        };
+        // Swings during the cooldown are dropped, whatever the client thinks
+        if weapon.next_swing_time > now {
+            continue;
+        }
+        weapon.next_swing_time = now + weapon.time_between_swings.as_secs_f32();
+
+        let origin = event.input.aim.origin + Vec3::new(0.0, RANGED_AIM_HEIGHT, 0.0);
+        let mut direction = event.input.aim.target_position - origin;
+        direction.y = 0.;
+        let direction = direction.normalize_or_zero();
+
+        let filter = QueryFilter::new()
+            .groups(CollisionGroups::new(
+                physics::RAYCASTING_GROUP,
+                physics::DEFAULT_GROUP | physics::LIMB_GROUP,
+            ))
+            .predicate(&|entity| {
+                // Don't hit yourself
+                entity != event.actor && !parents.iter_ancestors(entity).any(|e| e == event.actor)
+            });
+        let hit = rapier.cast_ray(origin, direction, weapon.reach, false, filter);
+
+        if let Some((hit_entity, _)) = hit {
+            damage.send(
+                DamageEvent::new(
+                    hit_entity,
+                    Damage::Tissue(TissueDamage {
+                        amount: weapon.damage
+                            * modifiers.map(|m| m.damage_multiplier).unwrap_or(1.0),
+                        kind: weapon.damage_type,
+                    }),
+                )
+                .source(event.actor, Some(weapon_entity)),
+            );
+        }
+
+        let end = origin + direction * hit.map_or(weapon.reach, |(_, toi)| toi);
+        sender.send(
+            &MeleeSwingMessage {
+                origin,
+                end,
+                hit: hit.is_some(),
+            },
+            MessageReceivers::AllPlayers,
+        );
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+struct MeleeSwingMessage {
+    origin: Vec3,
+    /// Where the swing hit something or ran out of reach
+    end: Vec3,
+    hit: bool,
+}
+
+const SWING_VISIBLE_SECONDS: f32 = 0.2;
+
+fn client_handle_swing_effects(
+    mut messages: EventReader<MessageEvent<MeleeSwingMessage>>,
+    mut current: Local<Vec<(f32, MeleeSwingMessage)>>,
+    time: Res<Time>,
+    mut gizmos: Gizmos,
+) {
+    let now = time.elapsed_seconds();
+    for event in messages.iter() {
+        current.push((now, event.message));
+    }
+
+    current.retain(|(time, _)| now - time < SWING_VISIBLE_SECONDS);
+
+    for (_, swing) in current.iter() {
+        let color = if swing.hit { Color::RED } else { Color::WHITE };
+        gizmos.line(swing.origin, swing.end, color);
+    }
+}
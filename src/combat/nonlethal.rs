@@ -0,0 +1,515 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, reflect::TypeUuid};
+use bevy_rapier3d::prelude::{CollisionGroups, QueryFilter, RapierContext};
+use networking::{
+    component::AppExt,
+    is_server,
+    variable::{NetworkVar, ServerVar},
+    Networked,
+};
+use utils::task::Tasks;
+
+use crate::{
+    body::{Body, Hand, Hands},
+    character_sheet::CharacterSheets,
+    combat::{CombatMode, RANGED_AIM_HEIGHT},
+    despawn::{ContentsPolicy, DespawnCommandsExt},
+    interaction::{
+        ActiveInteraction, DoAfter, GenerateInteractionList, InteractionListEvents,
+        InteractionOption, InteractionSpecificity, InteractionStatus,
+    },
+    items::{
+        cells::{DeviceCells, PoweredDevice},
+        containers::{Container, MoveItem},
+        Item,
+    },
+    round::RoundRng,
+};
+
+use super::CombatInputEvent;
+
+pub struct NonLethalPlugin;
+
+impl Plugin for NonLethalPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<StunBaton>()
+            .register_type::<Restraints>()
+            .add_networked_component::<Subdued, SubduedClient>();
+        if is_server(app) {
+            app.register_type::<DisarmInteraction>()
+                .register_type::<RestrainInteraction>()
+                .register_type::<RemoveRestraintsInteraction>()
+                .add_systems(
+                    Update,
+                    (
+                        add_subdued,
+                        baton_attack,
+                        expire_stun,
+                        (
+                            prepare_disarm_interaction,
+                            prepare_restrain_interaction,
+                            prepare_remove_restraints_interaction,
+                        )
+                            .in_set(GenerateInteractionList),
+                        disarm_interaction,
+                        restrain_interaction,
+                        remove_restraints_interaction,
+                    ),
+                );
+        }
+    }
+}
+
+const STUNNED_STATUS: &str = "Stunned";
+const RESTRAINED_STATUS: &str = "Restrained";
+const DISARM_TIME: Duration = Duration::from_millis(500);
+/// Chance to knock away the held item of someone who can still fight back
+const DISARM_CHANCE: f32 = 0.5;
+const REMOVE_RESTRAINTS_TIME: Duration = Duration::from_secs(3);
+/// How long it takes to wriggle out of restraints without help
+const ESCAPE_RESTRAINTS_TIME: Duration = Duration::from_secs(30);
+
+/// Non-lethal conditions keeping a creature from acting
+#[derive(Component, Networked)]
+#[networked(client = "SubduedClient")]
+pub struct Subdued {
+    stunned_until: f32,
+    stunned: NetworkVar<bool>,
+    restrained: NetworkVar<bool>,
+}
+
+impl Subdued {
+    pub fn is_stunned(&self) -> bool {
+        *self.stunned
+    }
+
+    /// Stuns the creature until the given time, unless it already is for longer
+    pub fn stun(&mut self, until: f32) {
+        self.stunned_until = self.stunned_until.max(until);
+        if !self.is_stunned() {
+            *self.stunned = true;
+        }
+    }
+
+    pub fn is_restrained(&self) -> bool {
+        *self.restrained
+    }
+
+    /// Whether the creature can use its hands
+    pub fn can_use_hands(&self) -> bool {
+        !self.is_stunned() && !self.is_restrained()
+    }
+}
+
+#[derive(Component, Default, TypeUuid, Networked)]
+#[uuid = "77f7dba0-53ee-41d9-9308-0c91f6e461d4"]
+#[networked(server = "Subdued")]
+pub struct SubduedClient {
+    stunned: ServerVar<bool>,
+    restrained: ServerVar<bool>,
+}
+
+impl SubduedClient {
+    pub fn is_stunned(&self) -> bool {
+        self.stunned.get().copied().unwrap_or_default()
+    }
+}
+
+/// A melee weapon stunning whoever it hits, using charge from its cell.
+/// Only works while switched on.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+struct StunBaton {
+    stun_time: Duration,
+    /// Energy used per hit in joules
+    energy_per_hit: f32,
+    time_between_hits: Duration,
+    #[reflect(ignore)]
+    next_hit_time: f32,
+}
+
+impl Default for StunBaton {
+    fn default() -> Self {
+        Self {
+            stun_time: Duration::from_secs(5),
+            energy_per_hit: 1000.0,
+            time_between_hits: Duration::from_secs(1),
+            next_hit_time: 0.0,
+        }
+    }
+}
+
+/// An item that can tie up someone's hands. Used up when applied.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+struct Restraints {
+    apply_time: Duration,
+}
+
+impl Default for Restraints {
+    fn default() -> Self {
+        Self {
+            apply_time: Duration::from_secs(5),
+        }
+    }
+}
+
+fn add_subdued(bodies: Query<Entity, (With<Body>, Without<Subdued>)>, mut commands: Commands) {
+    for entity in bodies.iter() {
+        commands.entity(entity).insert(Subdued {
+            stunned_until: 0.0,
+            stunned: false.into(),
+            restrained: false.into(),
+        });
+    }
+}
+
+/// Empties the hands of a creature onto the floor
+fn drop_held_items(
+    body: &Body,
+    hands: &Query<&Container, With<Hand>>,
+    item_moves: &mut Tasks<MoveItem>,
+) {
+    for hand in hands.iter_many(body.limbs()) {
+        for (_, &item) in hand.iter() {
+            item_moves.create_ignore(MoveItem {
+                item,
+                container: None,
+                position: None,
+            });
+        }
+    }
+}
+
+/// How far a baton reaches from the center of its wielder
+const BATON_REACH: f32 = 1.5;
+
+#[allow(clippy::too_many_arguments)]
+fn baton_attack(
+    mut input: EventReader<CombatInputEvent>,
+    mut batons: Query<(&mut StunBaton, &PoweredDevice)>,
+    mut cells: DeviceCells,
+    mut targets: Query<(&Body, &mut Subdued)>,
+    parents: Query<&Parent>,
+    hands: Query<&Container, With<Hand>>,
+    mut item_moves: ResMut<Tasks<MoveItem>>,
+    mut sheets: CharacterSheets,
+    rapier: Res<RapierContext>,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    for event in input.iter() {
+        if !event.input.primary_attack {
+            continue;
+        }
+        let Some(weapon) = event.wielded_weapon else {
+            continue;
+        };
+        let Ok((mut baton, device)) = batons.get_mut(weapon) else {
+            continue;
+        };
+        if baton.next_hit_time > now {
+            continue;
+        }
+        baton.next_hit_time = now + baton.time_between_hits.as_secs_f32();
+
+        let origin = event.input.aim.origin + Vec3::new(0.0, RANGED_AIM_HEIGHT, 0.0);
+        let mut direction = event.input.aim.target_position - origin;
+        direction.y = 0.;
+        let direction = direction.normalize_or_zero();
+
+        let filter = QueryFilter::new()
+            .groups(CollisionGroups::new(
+                physics::RAYCASTING_GROUP,
+                physics::DEFAULT_GROUP | physics::LIMB_GROUP,
+            ))
+            .predicate(&|entity| {
+                // Don't hit yourself
+                entity != event.actor && !parents.iter_ancestors(entity).any(|e| e == event.actor)
+            });
+        let Some((hit_entity, _)) = rapier.cast_ray(origin, direction, BATON_REACH, false, filter)
+        else {
+            continue;
+        };
+        let Some(target) = std::iter::once(hit_entity)
+            .chain(parents.iter_ancestors(hit_entity))
+            .find(|&e| targets.contains(e))
+        else {
+            continue;
+        };
+
+        // A baton that's off or out of charge just bonks
+        if !device.is_active() || !cells.consume(weapon, baton.energy_per_hit) {
+            continue;
+        }
+
+        let (body, mut subdued) = targets.get_mut(target).unwrap();
+        subdued.stun(now + baton.stun_time.as_secs_f32());
+        drop_held_items(body, &hands, &mut item_moves);
+        if let Some(mut sheet) = sheets.get_mut(target) {
+            sheet.set_status(STUNNED_STATUS, Some(baton.stun_time), now);
+        }
+    }
+}
+
+fn expire_stun(
+    mut creatures: Query<(Entity, &mut Subdued)>,
+    mut sheets: CharacterSheets,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    for (entity, mut subdued) in creatures.iter_mut() {
+        if !subdued.is_stunned() || subdued.stunned_until > now {
+            continue;
+        }
+        *subdued.stunned = false;
+        if let Some(mut sheet) = sheets.get_mut(entity) {
+            sheet.remove_status(STUNNED_STATUS, now);
+        }
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct DisarmInteraction;
+
+impl FromWorld for DisarmInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self
+    }
+}
+
+fn prepare_disarm_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    combat_modes: Query<&CombatMode>,
+    hands: Query<(), With<Hands>>,
+) {
+    for event in interaction_list.events.iter() {
+        // Disarming needs a free hand and the intent to fight
+        if event.used_hand.is_none() || event.item_in_hand.is_some() {
+            continue;
+        }
+        if !combat_modes
+            .get(event.source)
+            .map(|mode| mode.is_enabled())
+            .unwrap_or(false)
+        {
+            continue;
+        }
+        if event.source == event.target || !hands.contains(event.target) {
+            continue;
+        }
+
+        event.add_interaction(InteractionOption {
+            text: "Disarm".into(),
+            interaction: Box::new(DisarmInteraction),
+            specificity: InteractionSpecificity::Common,
+        });
+    }
+}
+
+fn disarm_interaction(
+    mut query: Query<&mut ActiveInteraction, With<DisarmInteraction>>,
+    targets: Query<(&Hands, Option<&Subdued>)>,
+    hands: Query<&Container, With<Hand>>,
+    mut item_moves: ResMut<Tasks<MoveItem>>,
+    mut rng: Option<ResMut<RoundRng>>,
+    time: Res<Time>,
+) {
+    for mut active in query.iter_mut() {
+        active.set_initial_duration(DISARM_TIME);
+
+        let Ok((target_hands, subdued)) = targets.get(active.target) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+
+        if active.start_time() + DISARM_TIME.as_secs_f32() > time.elapsed_seconds() {
+            continue;
+        }
+
+        let defenseless = subdued.map(|s| !s.can_use_hands()).unwrap_or(false);
+        let success = defenseless
+            || rng
+                .as_mut()
+                .map(|rng| rng.next_f32() < DISARM_CHANCE)
+                .unwrap_or_default();
+        if success {
+            if let Ok(hand) = hands.get(target_hands.active_hand()) {
+                for (_, &item) in hand.iter() {
+                    item_moves.create_ignore(MoveItem {
+                        item,
+                        container: None,
+                        position: None,
+                    });
+                }
+            }
+        }
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct RestrainInteraction {
+    restraints: Entity,
+}
+
+impl FromWorld for RestrainInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            restraints: Entity::PLACEHOLDER,
+        }
+    }
+}
+
+fn prepare_restrain_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    restraints: Query<&Item, With<Restraints>>,
+    targets: Query<&Subdued>,
+) {
+    for event in interaction_list.events.iter() {
+        let Some(item) = event.item_in_hand else {
+            continue;
+        };
+        let Ok(restraints_item) = restraints.get(item) else {
+            continue;
+        };
+        if event.source == event.target {
+            continue;
+        }
+        let Ok(subdued) = targets.get(event.target) else {
+            continue;
+        };
+        if subdued.is_restrained() {
+            continue;
+        }
+
+        event.add_interaction(InteractionOption {
+            text: format!("Restrain with {}", restraints_item.name),
+            interaction: Box::new(RestrainInteraction { restraints: item }),
+            specificity: InteractionSpecificity::Specific,
+        });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn restrain_interaction(
+    mut query: Query<(&RestrainInteraction, &mut ActiveInteraction)>,
+    restraints: Query<&Restraints>,
+    mut targets: Query<(&Body, &mut Subdued)>,
+    hands: Query<&Container, With<Hand>>,
+    mut item_moves: ResMut<Tasks<MoveItem>>,
+    mut sheets: CharacterSheets,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    let now = time.elapsed_seconds();
+    for (interaction, mut active) in query.iter_mut() {
+        let Ok(restraints) = restraints.get(interaction.restraints) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        let Ok((body, mut subdued)) = targets.get_mut(active.target) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        if subdued.is_restrained() {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        }
+
+        if !active.do_after(DoAfter::new(restraints.apply_time).stationary(), now) {
+            continue;
+        }
+
+        *subdued.restrained = true;
+        drop_held_items(body, &hands, &mut item_moves);
+        commands.despawn_cascade(interaction.restraints, ContentsPolicy::Destroy);
+        if let Some(mut sheet) = sheets.get_mut(active.target) {
+            sheet.set_status(RESTRAINED_STATUS, None, now);
+        }
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct RemoveRestraintsInteraction;
+
+impl FromWorld for RemoveRestraintsInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self
+    }
+}
+
+impl RemoveRestraintsInteraction {
+    fn duration(user: Entity, target: Entity) -> Duration {
+        if user == target {
+            ESCAPE_RESTRAINTS_TIME
+        } else {
+            REMOVE_RESTRAINTS_TIME
+        }
+    }
+}
+
+fn prepare_remove_restraints_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    targets: Query<&Subdued>,
+) {
+    for event in interaction_list.events.iter() {
+        let Ok(subdued) = targets.get(event.target) else {
+            continue;
+        };
+        if !subdued.is_restrained() {
+            continue;
+        }
+
+        let text = if event.source == event.target {
+            "Wriggle out of restraints"
+        } else if event.used_hand.is_some() {
+            "Remove restraints"
+        } else {
+            continue;
+        };
+        event.add_interaction(InteractionOption {
+            text: text.into(),
+            interaction: Box::new(RemoveRestraintsInteraction),
+            specificity: InteractionSpecificity::Specific,
+        });
+    }
+}
+
+fn remove_restraints_interaction(
+    mut query: Query<(Entity, &mut ActiveInteraction), With<RemoveRestraintsInteraction>>,
+    mut targets: Query<&mut Subdued>,
+    mut sheets: CharacterSheets,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    for (user, mut active) in query.iter_mut() {
+        let duration = RemoveRestraintsInteraction::duration(user, active.target);
+        let Ok(mut subdued) = targets.get_mut(active.target) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        if !subdued.is_restrained() {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        }
+
+        if !active.do_after(DoAfter::new(duration).stationary(), now) {
+            continue;
+        }
+
+        *subdued.restrained = false;
+        if let Some(mut sheet) = sheets.get_mut(active.target) {
+            sheet.remove_status(RESTRAINED_STATUS, now);
+        }
+        active.status = InteractionStatus::Completed;
+    }
+}
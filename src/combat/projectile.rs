@@ -0,0 +1,278 @@
+//! Bullets and other projectiles that take time to reach their target.
+//!
+//! Projectiles only exist as entities on the server. Clients get one message when a projectile
+//! is fired and one when it hits something, and move their tracer in between on their own.
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use bevy_rapier3d::prelude::{CollisionGroups, QueryFilter, RapierContext};
+use networking::{
+    is_server,
+    messaging::{AppExt, MessageEvent, MessageReceivers, MessageSender},
+    spawning::ClientControls,
+    ConnectionId, Players,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{body::Body, combat::damage::*, GameState};
+
+pub struct ProjectilePlugin;
+
+impl Plugin for ProjectilePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_network_message::<ProjectileFiredMessage>()
+            .add_network_message::<ProjectileImpactMessage>();
+
+        if is_server(app) {
+            app.add_systems(Update, (announce_projectiles, move_projectiles).chain());
+        } else {
+            app.init_resource::<ClientProjectiles>().add_systems(
+                Update,
+                client_projectile_effects.run_if(in_state(GameState::Game)),
+            );
+        }
+    }
+}
+
+/// Players further away than this from where a projectile is fired don't see it
+const PROJECTILE_AUDIENCE_RANGE: f32 = 30.0;
+/// Length of the streak drawn behind a projectile
+const TRACER_LENGTH: f32 = 0.6;
+const IMPACT_VISIBLE_SECONDS: f32 = 0.3;
+
+/// A projectile in flight, moving along its velocity every frame until it hits something
+#[derive(Component)]
+pub(crate) struct Projectile {
+    /// Who fired it, it never hits them
+    pub(crate) shooter: Entity,
+    /// What it was fired from
+    pub(crate) weapon: Option<Entity>,
+    /// Direction and speed in the world, in m/s
+    pub(crate) velocity: Vec3,
+    /// Distance left before it drops
+    pub(crate) range: f32,
+    /// Damage done to whatever it hits
+    pub(crate) damage: KineticDamage,
+    /// Set once clients were told about it
+    id: Option<u32>,
+    receivers: HashSet<ConnectionId>,
+}
+
+impl Projectile {
+    pub(crate) fn new(
+        shooter: Entity,
+        weapon: Option<Entity>,
+        velocity: Vec3,
+        range: f32,
+        damage: KineticDamage,
+    ) -> Self {
+        Self {
+            shooter,
+            weapon,
+            velocity,
+            range,
+            damage,
+            id: None,
+            receivers: Default::default(),
+        }
+    }
+}
+
+/// A projectile was fired, sent instead of replicating the projectile entity
+#[derive(Serialize, Deserialize, Clone, Copy)]
+struct ProjectileFiredMessage {
+    id: u32,
+    origin: Vec3,
+    velocity: Vec3,
+    range: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+enum ImpactKind {
+    Creature,
+    Surface,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+struct ProjectileImpactMessage {
+    id: u32,
+    position: Vec3,
+    kind: ImpactKind,
+}
+
+/// Tells players near new projectiles about them
+fn announce_projectiles(
+    mut projectiles: Query<(&mut Projectile, &Transform), Added<Projectile>>,
+    players: Res<Players>,
+    controls: Res<ClientControls>,
+    transforms: Query<&GlobalTransform>,
+    mut next_id: Local<u32>,
+    mut sender: MessageSender,
+) {
+    for (mut projectile, transform) in projectiles.iter_mut() {
+        let origin = transform.translation;
+        let receivers: HashSet<ConnectionId> = players
+            .players()
+            .iter()
+            .filter(|(_, player)| {
+                controls
+                    .controlled_entity(player.id)
+                    .and_then(|entity| transforms.get(entity).ok())
+                    .map_or(false, |t| {
+                        t.translation().distance(origin) <= PROJECTILE_AUDIENCE_RANGE
+                    })
+            })
+            .map(|(connection, _)| *connection)
+            .collect();
+
+        *next_id = next_id.wrapping_add(1);
+        projectile.id = Some(*next_id);
+        if !receivers.is_empty() {
+            sender.send(
+                &ProjectileFiredMessage {
+                    id: *next_id,
+                    origin,
+                    velocity: projectile.velocity,
+                    range: projectile.range,
+                },
+                MessageReceivers::Set(receivers.clone()),
+            );
+        }
+        projectile.receivers = receivers;
+    }
+}
+
+/// Moves projectiles, casting a ray over the distance they cover each frame
+#[allow(clippy::too_many_arguments)]
+fn move_projectiles(
+    mut projectiles: Query<(Entity, &mut Projectile, &mut Transform)>,
+    parents: Query<&Parent>,
+    bodies: Query<(), With<Body>>,
+    rapier: Res<RapierContext>,
+    time: Res<Time>,
+    mut damage: EventWriter<DamageEvent>,
+    mut commands: Commands,
+    mut sender: MessageSender,
+) {
+    for (entity, mut projectile, mut transform) in projectiles.iter_mut() {
+        let speed = projectile.velocity.length();
+        let step = (speed * time.delta_seconds()).min(projectile.range);
+        if speed <= 0.0 || step <= 0.0 {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        let direction = projectile.velocity / speed;
+
+        let shooter = projectile.shooter;
+        let filter = QueryFilter::new()
+            .groups(CollisionGroups::new(
+                physics::RAYCASTING_GROUP,
+                physics::DEFAULT_GROUP | physics::LIMB_GROUP,
+            ))
+            .predicate(&|hit| hit != shooter && !parents.iter_ancestors(hit).any(|e| e == shooter));
+        let hit = rapier.cast_ray(transform.translation, direction, step, false, filter);
+
+        let Some((hit_entity, toi)) = hit else {
+            transform.translation += direction * step;
+            projectile.range -= step;
+            if projectile.range <= 0.0 {
+                commands.entity(entity).despawn_recursive();
+            }
+            continue;
+        };
+
+        // Limbs are hit directly, so the damage lands on the part that was in the way
+        damage.send(
+            DamageEvent::new(hit_entity, Damage::Kinetic(projectile.damage))
+                .source(shooter, projectile.weapon),
+        );
+
+        let hit_creature = bodies.contains(hit_entity)
+            || parents
+                .iter_ancestors(hit_entity)
+                .any(|e| bodies.contains(e));
+        if let Some(id) = projectile.id {
+            if !projectile.receivers.is_empty() {
+                sender.send(
+                    &ProjectileImpactMessage {
+                        id,
+                        position: transform.translation + direction * toi,
+                        kind: if hit_creature {
+                            ImpactKind::Creature
+                        } else {
+                            ImpactKind::Surface
+                        },
+                    },
+                    MessageReceivers::Set(std::mem::take(&mut projectile.receivers)),
+                );
+            }
+        }
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+struct ClientProjectile {
+    position: Vec3,
+    velocity: Vec3,
+    range: f32,
+}
+
+#[derive(Resource, Default)]
+struct ClientProjectiles {
+    flying: HashMap<u32, ClientProjectile>,
+    /// Impacts being shown, with the time they happened
+    impacts: Vec<(f32, ProjectileImpactMessage)>,
+}
+
+fn client_projectile_effects(
+    mut fired: EventReader<MessageEvent<ProjectileFiredMessage>>,
+    mut impacts: EventReader<MessageEvent<ProjectileImpactMessage>>,
+    mut projectiles: ResMut<ClientProjectiles>,
+    time: Res<Time>,
+    mut gizmos: Gizmos,
+) {
+    let now = time.elapsed_seconds();
+    for event in fired.iter() {
+        let message = event.message;
+        projectiles.flying.insert(
+            message.id,
+            ClientProjectile {
+                position: message.origin,
+                velocity: message.velocity,
+                range: message.range,
+            },
+        );
+    }
+    for event in impacts.iter() {
+        projectiles.flying.remove(&event.message.id);
+        projectiles.impacts.push((now, event.message));
+    }
+
+    let delta = time.delta_seconds();
+    projectiles.flying.retain(|_, projectile| {
+        let speed = projectile.velocity.length();
+        let step = (speed * delta).min(projectile.range);
+        let direction = projectile.velocity.normalize_or_zero();
+        projectile.position += direction * step;
+        projectile.range -= step;
+        gizmos.line(
+            projectile.position - direction * TRACER_LENGTH.min(speed * delta.max(0.016)),
+            projectile.position,
+            Color::YELLOW,
+        );
+        projectile.range > 0.0
+    });
+
+    projectiles
+        .impacts
+        .retain(|(time, _)| now - time < IMPACT_VISIBLE_SECONDS);
+    for (_, impact) in projectiles.impacts.iter() {
+        let color = match impact.kind {
+            ImpactKind::Creature => Color::RED,
+            ImpactKind::Surface => Color::ORANGE,
+        };
+        gizmos.sphere(impact.position, Quat::IDENTITY, 0.05, color);
+    }
+}
@@ -1,48 +1,90 @@
 use std::time::Duration;
 
-use bevy::{prelude::*, reflect::TypeUuid};
-use bevy_rapier3d::prelude::{CollisionGroups, QueryFilter, RapierContext};
+use bevy::{ecs::system::SystemParam, prelude::*, reflect::TypeUuid};
+use bevy_egui::{egui, EguiContexts};
 use networking::{
     component::AppExt,
     is_server,
-    messaging::{AppExt as MessageAppExt, MessageEvent, MessageReceivers, MessageSender},
     variable::{NetworkVar, ServerVar},
     Networked,
 };
-use serde::{Deserialize, Serialize};
+use utils::task::{TaskId, Tasks};
 
 use crate::{
+    body::ClientHeldItem,
     combat::{damage::*, RANGED_AIM_HEIGHT},
+    despawn::{ContentsPolicy, DespawnCommandsExt},
+    interaction::{
+        ActiveInteraction, GenerateInteractionList, InteractionListEvents, InteractionOption,
+        InteractionSpecificity, InteractionStatus,
+    },
+    items::{
+        containers::{Container, MoveItem},
+        stacks::Stack,
+        variants::VariantModifiers,
+        Item,
+    },
+    ui::has_window,
     GameState,
 };
 
-use super::CombatInputEvent;
+use super::{projectile::Projectile, CombatInputEvent};
 
 pub struct RangedPlugin;
 
 impl Plugin for RangedPlugin {
     fn build(&self, app: &mut App) {
         app.register_type::<Gun>()
+            .register_type::<Bullet>()
+            .register_type::<Magazine>()
+            .register_type::<Ammo>()
             .add_networked_component::<Gun, GunClient>()
-            .add_network_message::<GunShotMessage>();
+            .add_networked_component::<Magazine, MagazineClient>();
 
         if is_server(app) {
-            app.add_systems(Update, shoot_gun);
+            app.register_type::<InsertMagazineInteraction>()
+                .register_type::<RemoveMagazineInteraction>()
+                .register_type::<LoadRoundsInteraction>()
+                .add_systems(
+                    Update,
+                    (
+                        (
+                            prepare_insert_magazine_interaction,
+                            prepare_remove_magazine_interaction,
+                            prepare_load_rounds_interaction,
+                        )
+                            .in_set(GenerateInteractionList),
+                        insert_magazine_interaction,
+                        remove_magazine_interaction,
+                        load_rounds_interaction,
+                        shoot_gun,
+                        update_magazine_rounds,
+                    ),
+                );
         } else {
             app.add_systems(
                 Update,
-                client_handle_gun_shot_effects.run_if(in_state(GameState::Game)),
+                client_ammo_ui
+                    .run_if(in_state(GameState::Game))
+                    .run_if(has_window),
             );
         }
     }
 }
 
-/// A ranged weapon that shoots projectiles
+const SWAP_MAGAZINE_TIME: Duration = Duration::from_millis(1200);
+const LOAD_ROUNDS_TIME: Duration = Duration::from_millis(1500);
+
+/// A ranged weapon firing rounds from the magazine stored in its container
 #[derive(Component, Reflect, Networked)]
 #[reflect(Component)]
 #[networked(client = "GunClient")]
 struct Gun {
     time_between_shots: Duration,
+    /// Magazines of a different caliber don't fit
+    caliber: String,
+    /// How far bullets fly before dropping, in meters
+    range: f32,
 
     #[reflect(ignore)]
     next_shot_time: NetworkVar<f32>,
@@ -52,18 +94,140 @@ impl Default for Gun {
     fn default() -> Self {
         Self {
             time_between_shots: Duration::from_secs_f32(0.1),
+            caliber: "9mm".into(),
+            range: 20.0,
             next_shot_time: NetworkVar::from_default(0.0),
         }
     }
 }
 
+#[derive(Component, Networked, TypeUuid)]
+#[networked(server = "Gun")]
+#[uuid = "aab5eca9-9ca6-4837-8496-2c4d066009d9"]
+struct GunClient {
+    next_shot_time: ServerVar<f32>,
+}
+
+impl Default for GunClient {
+    fn default() -> Self {
+        Self {
+            next_shot_time: ServerVar::from_default(0.0),
+        }
+    }
+}
+
+/// What a single round does when fired
+#[derive(Reflect, Clone, Copy, PartialEq)]
+struct Bullet {
+    /// Mass in kg
+    mass: f32,
+    /// Velocity used for damage on impact, in m/s
+    velocity: f32,
+    /// How fast the projectile travels in the game, in m/s.
+    /// Much slower than `velocity` so bullets can be seen and take time to arrive.
+    speed: f32,
+}
+
+impl Default for Bullet {
+    fn default() -> Self {
+        Self {
+            mass: 0.115,
+            velocity: 400.0,
+            speed: 40.0,
+        }
+    }
+}
+
+/// Holds rounds for a gun, loaded from ammo stacks
+#[derive(Component, Reflect, Networked)]
+#[reflect(Component)]
+#[networked(client = "MagazineClient")]
+struct Magazine {
+    caliber: String,
+    capacity: u32,
+    rounds: u32,
+    /// The kind of round currently loaded
+    bullet: Bullet,
+    #[reflect(ignore)]
+    synced_rounds: NetworkVar<u32>,
+}
+
+impl Default for Magazine {
+    fn default() -> Self {
+        Self {
+            caliber: "9mm".into(),
+            capacity: 10,
+            rounds: 0,
+            bullet: Default::default(),
+            synced_rounds: 0.into(),
+        }
+    }
+}
+
+impl Magazine {
+    fn space(&self) -> u32 {
+        self.capacity.saturating_sub(self.rounds)
+    }
+}
+
+#[derive(Component, Default, TypeUuid, Networked)]
+#[uuid = "7d2f4a61-93c8-4e0b-b5a2-6c1e8f3d9a47"]
+#[networked(server = "Magazine")]
+struct MagazineClient {
+    synced_rounds: ServerVar<u32>,
+}
+
+impl MagazineClient {
+    fn rounds(&self) -> u32 {
+        self.synced_rounds.get().copied().unwrap_or_default()
+    }
+}
+
+/// A stack of loose rounds that can be loaded into magazines
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+struct Ammo {
+    caliber: String,
+    bullet: Bullet,
+}
+
+fn update_magazine_rounds(mut magazines: Query<&mut Magazine, Changed<Magazine>>) {
+    for mut magazine in magazines.iter_mut() {
+        if *magazine.synced_rounds != magazine.rounds {
+            let rounds = magazine.rounds;
+            *magazine.synced_rounds = rounds;
+        }
+    }
+}
+
+/// Finds the magazines inside guns
+#[derive(SystemParam)]
+struct GunMagazines<'w, 's> {
+    containers: Query<'w, 's, &'static Container>,
+    magazines: Query<'w, 's, &'static mut Magazine>,
+}
+
+impl<'w, 's> GunMagazines<'w, 's> {
+    fn magazine_entity(&self, gun: Entity) -> Option<Entity> {
+        let container = self.containers.get(gun).ok()?;
+        container
+            .iter()
+            .map(|(_, &item)| item)
+            .find(|&item| self.magazines.contains(item))
+    }
+
+    fn magazine_mut(&mut self, gun: Entity) -> Option<Mut<Magazine>> {
+        let magazine = self.magazine_entity(gun)?;
+        self.magazines.get_mut(magazine).ok()
+    }
+}
+
 fn shoot_gun(
     mut input: EventReader<CombatInputEvent>,
-    mut guns: Query<&mut Gun>,
+    mut guns: Query<(&mut Gun, Option<&VariantModifiers>)>,
+    mut magazines: GunMagazines,
     time: Res<Time>,
-    rapier: Res<RapierContext>,
     mut commands: Commands,
-    mut sender: MessageSender,
 ) {
     for event in input.iter() {
         if !event.input.primary_attack {
@@ -74,7 +238,7 @@ fn shoot_gun(
             continue;
         };
 
-        let Ok(mut gun) = guns.get_mut(wielded_weapon) else {
+        let Ok((mut gun, modifiers)) = guns.get_mut(wielded_weapon) else {
             continue;
         };
 
@@ -83,88 +247,315 @@ fn shoot_gun(
             continue;
         }
 
-        // Shoot
-        let target_position = event.input.aim.target_position;
+        let Some(mut magazine) = magazines.magazine_mut(wielded_weapon) else {
+            continue;
+        };
+        if magazine.rounds == 0 {
+            continue;
+        }
+        magazine.rounds -= 1;
+        let bullet = magazine.bullet;
+
         // Hack: to shoot further up and not on ground level
-        let mut origin = event.input.aim.origin + Vec3::new(0.0, RANGED_AIM_HEIGHT, 0.0);
-        let mut direction = (target_position - origin).normalize_or_zero();
+        let origin = event.input.aim.origin + Vec3::new(0.0, RANGED_AIM_HEIGHT, 0.0);
+        let mut direction = event.input.aim.target_position - origin;
         // Don't aim up or down for now
         direction.y = 0.;
-        // Prevent player from hitting themselves
-        origin += direction * 0.5;
-
-        let filter = QueryFilter::new().groups(CollisionGroups::new(
-            physics::RAYCASTING_GROUP,
-            physics::DEFAULT_GROUP | physics::LIMB_GROUP,
-        ));
-        if let Some((hit_entity, toi)) = rapier.cast_ray(origin, direction, 20.0, false, filter) {
-            let position = origin + direction * toi;
+        let direction = direction.normalize_or_zero();
 
-            commands.spawn((
-                Attack,
-                AffectedEntity(hit_entity),
-                // TODO: Grab from weapon and ammo used
+        commands.spawn((
+            TransformBundle::from_transform(Transform::from_translation(origin)),
+            Projectile::new(
+                event.actor,
+                Some(wielded_weapon),
+                direction * bullet.speed,
+                gun.range,
                 KineticDamage {
-                    mass: 0.115,
-                    velocity: 400.0,
+                    mass: bullet.mass * modifiers.map(|m| m.damage_multiplier).unwrap_or(1.0),
+                    velocity: bullet.velocity,
                     shape: KineticShape::Point,
                 },
-            ));
-            // TODO: Attacks are not yet automatically deleted
-
-            // TODO: Maybe handle with entity?
-            // TODO: Don't send to all players, only in range
-            sender.send(
-                &GunShotMessage {
-                    origin,
-                    hit: position,
-                },
-                MessageReceivers::AllPlayers,
-            );
-        }
+            ),
+        ));
 
         *gun.next_shot_time = elapsed + gun.time_between_shots.as_secs_f32();
     }
 }
 
-#[derive(Component, Networked, TypeUuid)]
-#[networked(server = "Gun")]
-#[uuid = "aab5eca9-9ca6-4837-8496-2c4d066009d9"]
-struct GunClient {
-    next_shot_time: ServerVar<f32>,
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct InsertMagazineInteraction {
+    magazine: Entity,
+    #[reflect(ignore)]
+    move_task: Option<TaskId<MoveItem>>,
 }
 
-impl Default for GunClient {
-    fn default() -> Self {
+impl FromWorld for InsertMagazineInteraction {
+    fn from_world(_: &mut World) -> Self {
         Self {
-            next_shot_time: ServerVar::from_default(0.0),
+            magazine: Entity::PLACEHOLDER,
+            move_task: None,
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy)]
-struct GunShotMessage {
-    origin: Vec3,
-    hit: Vec3,
+fn prepare_insert_magazine_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    magazines: Query<(&Item, &Magazine)>,
+    guns: Query<(&Gun, &Container)>,
+) {
+    for event in interaction_list.events.iter() {
+        let Some(magazine) = event.item_in_hand else {
+            continue;
+        };
+        let Ok((magazine_item, magazine_data)) = magazines.get(magazine) else {
+            continue;
+        };
+        let Ok((gun, container)) = guns.get(event.target) else {
+            continue;
+        };
+        if !container.is_empty() || gun.caliber != magazine_data.caliber {
+            continue;
+        }
+
+        event.add_interaction(InteractionOption {
+            text: format!("Insert {}", magazine_item.name),
+            interaction: Box::new(InsertMagazineInteraction {
+                magazine,
+                move_task: None,
+            }),
+            specificity: InteractionSpecificity::Specific,
+        });
+    }
+}
+
+fn insert_magazine_interaction(
+    mut query: Query<(&mut InsertMagazineInteraction, &mut ActiveInteraction)>,
+    mut item_moves: ResMut<Tasks<MoveItem>>,
+    time: Res<Time>,
+) {
+    for (mut interaction, mut active) in query.iter_mut() {
+        active.set_initial_duration(SWAP_MAGAZINE_TIME);
+
+        let Some(task) = interaction.move_task else {
+            if active.start_time() + SWAP_MAGAZINE_TIME.as_secs_f32() > time.elapsed_seconds() {
+                continue;
+            }
+            interaction.move_task = Some(item_moves.create(MoveItem {
+                item: interaction.magazine,
+                container: Some(active.target),
+                position: Some(UVec2::ZERO),
+            }));
+            continue;
+        };
+
+        let Some(result) = item_moves.result(task) else {
+            continue;
+        };
+        active.status = if result.was_success() {
+            InteractionStatus::Completed
+        } else {
+            InteractionStatus::Canceled
+        };
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct RemoveMagazineInteraction {
+    hand: Entity,
+    #[reflect(ignore)]
+    move_task: Option<TaskId<MoveItem>>,
 }
 
-const BULLET_TRACER_VISIBLE_SECONDS: f32 = 0.5;
+impl FromWorld for RemoveMagazineInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            hand: Entity::PLACEHOLDER,
+            move_task: None,
+        }
+    }
+}
 
-fn client_handle_gun_shot_effects(
-    mut messages: EventReader<MessageEvent<GunShotMessage>>,
-    mut current: Local<Vec<(f32, GunShotMessage)>>,
+fn prepare_remove_magazine_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    guns: Query<(), With<Gun>>,
+    magazines: GunMagazines,
+    items: Query<&Item>,
+) {
+    for event in interaction_list.events.iter() {
+        if event.item_in_hand.is_some() || !guns.contains(event.target) {
+            continue;
+        }
+        let Some(magazine) = magazines.magazine_entity(event.target) else {
+            continue;
+        };
+        let Ok(magazine_item) = items.get(magazine) else {
+            continue;
+        };
+
+        event.add_interaction(InteractionOption {
+            text: format!("Remove {}", magazine_item.name),
+            interaction: Box::new(RemoveMagazineInteraction {
+                hand: event.used_hand,
+                move_task: None,
+            }),
+            specificity: InteractionSpecificity::Specific,
+        });
+    }
+}
+
+fn remove_magazine_interaction(
+    mut query: Query<(&mut RemoveMagazineInteraction, &mut ActiveInteraction)>,
+    magazines: GunMagazines,
+    mut item_moves: ResMut<Tasks<MoveItem>>,
     time: Res<Time>,
-    mut gizmos: Gizmos,
 ) {
-    let now = time.elapsed_seconds();
-    for event in messages.iter() {
-        let message = event.message;
-        current.push((now, message));
+    for (mut interaction, mut active) in query.iter_mut() {
+        active.set_initial_duration(SWAP_MAGAZINE_TIME);
+
+        let Some(task) = interaction.move_task else {
+            if active.start_time() + SWAP_MAGAZINE_TIME.as_secs_f32() > time.elapsed_seconds() {
+                continue;
+            }
+            let Some(magazine) = magazines.magazine_entity(active.target) else {
+                active.status = InteractionStatus::Canceled;
+                continue;
+            };
+            interaction.move_task = Some(item_moves.create(MoveItem {
+                item: magazine,
+                container: Some(interaction.hand),
+                position: Some(UVec2::ZERO),
+            }));
+            continue;
+        };
+
+        let Some(result) = item_moves.result(task) else {
+            continue;
+        };
+        active.status = if result.was_success() {
+            InteractionStatus::Completed
+        } else {
+            InteractionStatus::Canceled
+        };
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct LoadRoundsInteraction {
+    ammo: Entity,
+}
+
+impl FromWorld for LoadRoundsInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            ammo: Entity::PLACEHOLDER,
+        }
+    }
+}
+
+fn prepare_load_rounds_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    ammo: Query<&Ammo, With<Stack>>,
+    magazines: Query<&Magazine>,
+) {
+    for event in interaction_list.events.iter() {
+        let Some(item) = event.item_in_hand else {
+            continue;
+        };
+        let Ok(ammo_data) = ammo.get(item) else {
+            continue;
+        };
+        let Ok(magazine) = magazines.get(event.target) else {
+            continue;
+        };
+        if magazine.caliber != ammo_data.caliber || magazine.space() == 0 {
+            continue;
+        }
+
+        event.add_interaction(InteractionOption {
+            text: "Load rounds".into(),
+            interaction: Box::new(LoadRoundsInteraction { ammo: item }),
+            specificity: InteractionSpecificity::Specific,
+        });
     }
+}
 
-    current.retain(|(time, _)| now - time < BULLET_TRACER_VISIBLE_SECONDS);
+fn load_rounds_interaction(
+    mut query: Query<(&LoadRoundsInteraction, &mut ActiveInteraction)>,
+    mut magazines: Query<&mut Magazine>,
+    mut ammo: Query<(&Ammo, &mut Stack)>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (interaction, mut active) in query.iter_mut() {
+        active.set_initial_duration(LOAD_ROUNDS_TIME);
+        if active.start_time() + LOAD_ROUNDS_TIME.as_secs_f32() > time.elapsed_seconds() {
+            continue;
+        }
+
+        let (Ok(mut magazine), Ok((ammo_data, mut stack))) = (
+            magazines.get_mut(active.target),
+            ammo.get_mut(interaction.ammo),
+        ) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
 
-    for (_, message) in current.iter() {
-        gizmos.line(message.origin, message.hit, Color::RED);
+        // Different rounds can't be mixed in one magazine
+        if magazine.rounds > 0 && magazine.bullet != ammo_data.bullet {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        }
+
+        let amount = magazine.space().min(stack.count());
+        if amount == 0 || !stack.take(amount) {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        }
+        magazine.rounds += amount;
+        magazine.bullet = ammo_data.bullet;
+        if stack.count() == 0 {
+            commands.despawn_cascade(interaction.ammo, ContentsPolicy::Destroy);
+        }
+        active.status = InteractionStatus::Completed;
     }
 }
+
+/// Shows the rounds left in the held gun or magazine
+fn client_ammo_ui(
+    mut contexts: EguiContexts,
+    held_item: ClientHeldItem,
+    magazines: Query<&MagazineClient>,
+    guns: Query<(), With<GunClient>>,
+    children: Query<&Children>,
+) {
+    let Some(item) = held_item.get() else {
+        return;
+    };
+    let rounds = match magazines.get(item) {
+        Ok(magazine) => Some(magazine.rounds()),
+        Err(_) if guns.contains(item) => children
+            .iter_descendants(item)
+            .find_map(|child| magazines.get(child).ok())
+            .map(|magazine| magazine.rounds()),
+        Err(_) => return,
+    };
+
+    egui::Window::new("Ammo")
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::Vec2::new(0.0, -120.0))
+        .resizable(false)
+        .title_bar(false)
+        .show(contexts.ctx_mut(), |ui| match rounds {
+            Some(rounds) => {
+                ui.label(format!("{} rounds", rounds));
+            }
+            None => {
+                ui.label("No magazine");
+            }
+        });
+}
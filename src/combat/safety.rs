@@ -0,0 +1,96 @@
+//! Weapon safeties, which stop a weapon from being used to attack while on.
+
+use bevy::prelude::*;
+use networking::is_server;
+
+use crate::{
+    communication::examine::{DescribeExamined, ExamineEvents},
+    interaction::{
+        ActiveInteraction, GenerateInteractionList, InteractionListEvents, InteractionOption,
+        InteractionSpecificity, InteractionStatus,
+    },
+};
+
+pub struct SafetyPlugin;
+
+impl Plugin for SafetyPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<WeaponSafety>();
+
+        if is_server(app) {
+            app.register_type::<ToggleSafetyInteraction>().add_systems(
+                Update,
+                (
+                    prepare_toggle_safety_interaction.in_set(GenerateInteractionList),
+                    toggle_safety_interaction,
+                    describe_safety.in_set(DescribeExamined),
+                ),
+            );
+        }
+    }
+}
+
+/// A weapon with a safety can't attack while it's on
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct WeaponSafety {
+    pub on: bool,
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct ToggleSafetyInteraction;
+
+impl FromWorld for ToggleSafetyInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self
+    }
+}
+
+fn prepare_toggle_safety_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    safeties: Query<&WeaponSafety>,
+) {
+    for event in interaction_list.events.iter() {
+        let Ok(safety) = safeties.get(event.target) else {
+            continue;
+        };
+
+        let text = if safety.on {
+            "Turn safety off"
+        } else {
+            "Turn safety on"
+        };
+        event.add_interaction(InteractionOption {
+            text: text.into(),
+            interaction: Box::new(ToggleSafetyInteraction),
+            specificity: InteractionSpecificity::Specific,
+        });
+    }
+}
+
+fn toggle_safety_interaction(
+    mut query: Query<&mut ActiveInteraction, With<ToggleSafetyInteraction>>,
+    mut safeties: Query<&mut WeaponSafety>,
+) {
+    for mut active in query.iter_mut() {
+        if let Ok(mut safety) = safeties.get_mut(active.target) {
+            safety.on = !safety.on;
+        }
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+fn describe_safety(examines: Res<ExamineEvents>, safeties: Query<&WeaponSafety>) {
+    for examine in examines.events.iter() {
+        let Ok(safety) = safeties.get(examine.target) else {
+            continue;
+        };
+        examine.add_line(if safety.on {
+            "The safety is on."
+        } else {
+            "The safety is off."
+        });
+    }
+}
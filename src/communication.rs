@@ -1,53 +1,89 @@
 use std::ops::Range;
 
-use bevy::{prelude::*, utils::HashMap};
+use bevy::{
+    prelude::*,
+    utils::{HashMap, Uuid},
+};
 use bevy_egui::{egui, EguiContexts};
 use networking::{
     identity::{NetworkIdentities, NetworkIdentity},
     is_server,
     messaging::{AppExt, MessageEvent, MessageReceivers, MessageSender},
-    spawning::ClientControls,
+    spawning::{ClientControlled, ClientControls},
     Players,
 };
 use serde::{Deserialize, Serialize};
 
-use crate::{camera::MainCamera, ui::has_window, GameState};
+use crate::{
+    admin::{ClientStaffRole, StaffRole},
+    camera::MainCamera,
+    debug::DebugState,
+    items::clothes::EquippedClient,
+    round::RoundRng,
+    ui::has_window,
+    GameState,
+};
+
+use self::{
+    accents::Accents,
+    examine::ExamineMessage,
+    radio::{HeadsetClient, Radios, TuneHeadsetMessage},
+};
+
+pub mod accents;
+pub mod announcements;
+pub mod event_log;
+pub mod examine;
+pub mod radio;
 
 pub struct CommunicationPlugin;
 
 impl Plugin for CommunicationPlugin {
     fn build(&self, app: &mut App) {
-        app.add_network_message::<SpeakMessage>()
-            .add_network_message::<SpeechMessage>();
+        app.add_plugins((
+            announcements::AnnouncementPlugin,
+            examine::ExaminePlugin,
+            radio::RadioPlugin,
+        ))
+        .add_network_message::<SpeakMessage>()
+        .add_network_message::<SpeechMessage>()
+        // Leaves room for the length prefix and chat kind
+        .set_message_limit::<SpeakMessage>(MAX_MESSAGE_LENGTH + 32);
 
         if is_server(app) {
-            app.add_systems(Update, handle_speech);
+            app.init_resource::<ChatRouting>()
+                .add_event::<ChatEvent>()
+                .add_systems(Update, (handle_speech, accents::sync_accents_to_sheet));
         } else {
-            app.init_resource::<ClientChat>().add_systems(
-                Update,
-                (
-                    (client_chat_box, client_speech_bubbles)
-                        .run_if(has_window)
-                        .run_if(in_state(GameState::Game)),
-                    client_handle_chat,
-                ),
-            );
+            app.add_plugins(event_log::EventLogPlugin)
+                .init_resource::<ClientChat>()
+                .add_systems(
+                    Update,
+                    (
+                        (client_chat_box, client_speech_bubbles)
+                            .run_if(has_window)
+                            .run_if(in_state(GameState::Game)),
+                        client_handle_chat,
+                    ),
+                );
         }
     }
 }
 
-#[derive(Serialize, Deserialize)]
-struct RadioChannel(pub u32);
+/// A radio frequency, in tenths of a unit
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct RadioChannel(pub u32);
 
-#[derive(Serialize, Deserialize)]
-enum ChatKind {
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub(crate) enum ChatKind {
+    #[default]
     Local,
     Ooc,
     Radio(RadioChannel),
 }
 
 /// A chat message in serializable form.
-#[derive(Serialize, Deserialize, Default)]
+#[derive(Serialize, Deserialize, Default, Clone)]
 struct ChatMessage {
     text: String,
     sections: Vec<ChatSection>,
@@ -55,11 +91,15 @@ struct ChatMessage {
     spoken_range: Option<Range<usize>>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 struct ChatSection {
     /// Part of the chat message this format applies to
     range: Range<usize>,
     format: ChatFormat,
+    /// Entity this section refers to. Rendered as a clickable link.
+    /// Links are only created by the server, text sent by players is never parsed for them.
+    #[serde(default)]
+    link: Option<NetworkIdentity>,
 }
 
 #[derive(Serialize, Deserialize, Default, Clone, Copy)]
@@ -86,6 +126,22 @@ impl From<ChatFormat> for egui::TextFormat {
     }
 }
 
+impl ChatFormat {
+    fn rich_text(self, text: &str) -> egui::RichText {
+        let mut rich = egui::RichText::new(text);
+        if self.italics {
+            rich = rich.italics();
+        }
+        if self.underline {
+            rich = rich.underline();
+        }
+        if self.bold {
+            rich = rich.strong();
+        }
+        rich
+    }
+}
+
 impl ChatMessage {
     fn section(&mut self, text: &str, format: ChatFormat) {
         let start = self.text.len();
@@ -93,9 +149,16 @@ impl ChatMessage {
         self.sections.push(ChatSection {
             range: start..self.text.len(),
             format,
+            link: None,
         });
     }
 
+    /// Append a section that links to an entity
+    fn entity_link(&mut self, text: &str, identity: Option<NetworkIdentity>, format: ChatFormat) {
+        self.section(text, format);
+        self.sections.last_mut().unwrap().link = identity;
+    }
+
     /// Append text without creating a new section
     fn append(&mut self, text: &str) {
         self.text += text;
@@ -109,6 +172,7 @@ impl ChatMessage {
                 self.sections.push(ChatSection {
                     range: 0..self.text.len(),
                     format: Default::default(),
+                    link: None,
                 });
             }
         }
@@ -120,19 +184,30 @@ impl ChatMessage {
         self.spoken_range = Some(start..self.text.len());
     }
 
-    fn append_to(&self, layout: &mut egui::text::LayoutJob) {
-        Self::add_newline(layout);
-
-        let base_index = layout.text.len();
-        layout.text += self.text.as_str();
-
-        for section in &self.sections {
-            layout.sections.push(egui::text::LayoutSection {
-                leading_space: 0.0,
-                byte_range: (base_index + section.range.start)..(base_index + section.range.end),
-                format: section.format.into(),
-            });
-        }
+    /// Shows the message and returns the entity of a link that was clicked
+    fn show(&self, ui: &mut egui::Ui) -> Option<NetworkIdentity> {
+        let mut clicked = None;
+        ui.horizontal_wrapped(|ui| {
+            ui.spacing_mut().item_spacing.x = 0.0;
+            for section in &self.sections {
+                // Ignore malformed sections instead of panicking
+                let Some(text) = self.text.get(section.range.clone()) else {
+                    continue;
+                };
+                let text = section.format.rich_text(text);
+                match section.link {
+                    Some(identity) => {
+                        if ui.link(text).clicked() {
+                            clicked = Some(identity);
+                        }
+                    }
+                    None => {
+                        ui.label(text);
+                    }
+                }
+            }
+        });
+        clicked
     }
 
     fn append_spoken_part(&self, layout: &mut egui::text::LayoutJob) -> Option<()> {
@@ -190,19 +265,116 @@ struct SpeechMessage {
     speaker: Option<NetworkIdentity>,
 }
 
+/// Sent on the server for every chat message that went out, for anything that watches chat
+#[derive(Event, Clone)]
+pub(crate) struct ChatEvent {
+    pub(crate) player: Uuid,
+    pub(crate) username: String,
+    /// Name of the speaking character, OOC has none
+    pub(crate) speaker: Option<String>,
+    pub(crate) kind: ChatKind,
+    /// The text as it was typed, before accents
+    pub(crate) text: String,
+}
+
+/// Keeps a creature's radio from sending or receiving until the given time
+#[derive(Component)]
+pub struct RadioJammed {
+    /// Seconds since startup
+    pub until: f32,
+}
+
+/// Server rules for who receives chat messages
+#[derive(Resource)]
+pub struct ChatRouting {
+    /// How far away local speech can be heard
+    pub local_range: f32,
+    pub ooc_enabled: bool,
+    pub radio_enabled: bool,
+}
+
+impl Default for ChatRouting {
+    fn default() -> Self {
+        Self {
+            local_range: 7.0,
+            ooc_enabled: true,
+            radio_enabled: true,
+        }
+    }
+}
+
+/// Maximum length of a chat message in bytes
+const MAX_MESSAGE_LENGTH: usize = 512;
+
+#[allow(clippy::too_many_arguments)]
 fn handle_speech(
     mut messages: EventReader<MessageEvent<SpeakMessage>>,
     players: Res<Players>,
     controlled: Res<ClientControls>,
     identities: Res<NetworkIdentities>,
     names: Query<AnyOf<(&SpeechName, &Name)>>,
+    accents: Query<&Accents>,
+    transforms: Query<&GlobalTransform>,
+    routing: Res<ChatRouting>,
+    jammed: Query<&RadioJammed>,
+    radios: Radios,
+    time: Res<Time>,
+    mut rng: Option<ResMut<RoundRng>>,
     mut sender: MessageSender,
+    mut chat_events: EventWriter<ChatEvent>,
 ) {
+    let is_jammed = |entity: Entity| {
+        jammed
+            .get(entity)
+            .map(|j| j.until > time.elapsed_seconds())
+            .unwrap_or_default()
+    };
+
     for event in messages.iter() {
         let Some(player) = players.get(event.connection) else {
             continue;
         };
 
+        let text = event.message.text.trim();
+        if text.is_empty() || text.len() > MAX_MESSAGE_LENGTH {
+            continue;
+        }
+
+        // OOC is spoken by the player, not their character
+        if event.message.kind == ChatKind::Ooc {
+            if !routing.ooc_enabled {
+                continue;
+            }
+
+            let mut message = ChatMessage::default();
+            message.section(
+                &format!("OOC {}", player.username),
+                ChatFormat {
+                    bold: true,
+                    ..Default::default()
+                },
+            );
+            message.section(": ", Default::default());
+            message.append(text);
+
+            info!(player = player.id.to_string().as_str(), text, "OOC message");
+            sender.send(
+                &SpeechMessage {
+                    message,
+                    speaker: None,
+                },
+                MessageReceivers::AllPlayers,
+            );
+            chat_events.send(ChatEvent {
+                player: player.id,
+                username: player.username.clone(),
+                speaker: None,
+                kind: ChatKind::Ooc,
+                text: text.to_owned(),
+            });
+            continue;
+        }
+
         let Some(player_entity) = controlled.controlled_entity(player.id) else {
             continue;
         };
@@ -214,20 +386,75 @@ fn handle_speech(
             _ => "Unknown".to_owned(),
         };
 
-        let text = event.message.text.as_str();
-
-        // TODO: Use chat kind (ex. OOC)
+        let spoken = match (accents.get(player_entity), rng.as_deref_mut()) {
+            (Ok(accents), Some(rng)) => accents.apply(text, &mut **rng),
+            _ => text.to_owned(),
+        };
 
         let mut message = ChatMessage::default();
-        message.section(
+        let receivers = match event.message.kind {
+            ChatKind::Radio(channel) => {
+                if !routing.radio_enabled || is_jammed(player_entity) {
+                    continue;
+                }
+                let can_send = radios
+                    .worn(player_entity)
+                    .map(|headset| headset.can_use(channel.0))
+                    .unwrap_or(false);
+                if !can_send {
+                    continue;
+                }
+                message.section(
+                    &format!("[{}] ", radio::channel_name(channel.0)),
+                    ChatFormat {
+                        italics: true,
+                        ..Default::default()
+                    },
+                );
+                players
+                    .players()
+                    .iter()
+                    .filter(|(_, p)| {
+                        controlled
+                            .controlled_entity(p.id)
+                            .filter(|&e| !is_jammed(e))
+                            .and_then(|e| radios.worn(e))
+                            .map(|headset| headset.can_use(channel.0))
+                            .unwrap_or(false)
+                    })
+                    .map(|(c, _)| *c)
+                    .collect()
+            }
+            _ => {
+                let Ok(origin) = transforms.get(player_entity) else {
+                    continue;
+                };
+                let origin = origin.translation();
+                players
+                    .players()
+                    .iter()
+                    .filter(|(_, p)| {
+                        controlled
+                            .controlled_entity(p.id)
+                            .and_then(|e| transforms.get(e).ok())
+                            .map(|t| t.translation().distance(origin) <= routing.local_range)
+                            .unwrap_or(false)
+                    })
+                    .map(|(c, _)| *c)
+                    .collect()
+            }
+        };
+
+        message.entity_link(
             &name,
+            identities.get_identity(player_entity),
             ChatFormat {
                 bold: true,
                 ..Default::default()
             },
         );
         message.section(" says, \"", Default::default());
-        message.append_speech(text);
+        message.append_speech(&spoken);
         message.append("\"");
 
         info!(
@@ -240,16 +467,23 @@ fn handle_speech(
                 message,
                 speaker: identities.get_identity(player_entity),
             },
-            // TODO: Respect local chat, only send to nearby & hearing players
-            MessageReceivers::AllPlayers,
+            MessageReceivers::Set(receivers),
         );
+        chat_events.send(ChatEvent {
+            player: player.id,
+            username: player.username.clone(),
+            speaker: Some(name),
+            kind: event.message.kind,
+            text: text.to_owned(),
+        });
     }
 }
 
 #[derive(Resource, Default)]
 struct ClientChat {
     input_chat: String,
-    history: egui::text::LayoutJob,
+    input_kind: ChatKind,
+    history: Vec<ChatMessage>,
     bubbles: HashMap<NetworkIdentity, SpeechBubble>,
     bubble_id: usize,
 }
@@ -260,22 +494,73 @@ struct SpeechBubble {
     when: f32,
 }
 
+#[allow(clippy::too_many_arguments)]
 fn client_chat_box(
     mut contexts: EguiContexts,
     mut data: ResMut<ClientChat>,
     mut keyboard: ResMut<Input<KeyCode>>,
+    mut debug: ResMut<DebugState>,
+    staff: Res<ClientStaffRole>,
+    identities: Res<NetworkIdentities>,
+    controlled: Query<Entity, With<ClientControlled>>,
+    children: Query<&Children>,
+    headsets: Query<(&HeadsetClient, &EquippedClient)>,
     mut sender: MessageSender,
 ) {
+    let headset = controlled.get_single().ok().and_then(|creature| {
+        headsets
+            .iter_many(children.iter_descendants(creature))
+            .find(|(_, equipped)| equipped.slot() == Some(radio::HEADSET_SLOT))
+            .map(|(headset, _)| headset)
+    });
+
+    let mut clicked = None;
     egui::Window::new("Chat")
         .anchor(egui::Align2::RIGHT_BOTTOM, egui::Vec2::ZERO)
         .default_size(egui::vec2(200.0, 800.0))
         .resizable(true)
         .show(contexts.ctx_mut(), |ui| {
-            egui::ScrollArea::vertical().show(ui, |ui| {
-                // This is probably expensive?
-                ui.label(data.history.clone());
+            egui::ScrollArea::vertical()
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for message in data.history.iter() {
+                        if let Some(identity) = message.show(ui) {
+                            clicked = Some(identity);
+                        }
+                    }
+                });
+
+            ui.horizontal_wrapped(|ui| {
+                ui.selectable_value(&mut data.input_kind, ChatKind::Local, "Local");
+                for frequency in headset.iter().flat_map(|h| h.channels()) {
+                    ui.selectable_value(
+                        &mut data.input_kind,
+                        ChatKind::Radio(RadioChannel(frequency)),
+                        radio::channel_name(frequency),
+                    );
+                }
+                ui.selectable_value(&mut data.input_kind, ChatKind::Ooc, "OOC");
             });
 
+            if let Some(current) = headset.and_then(|h| h.frequency()) {
+                let mut frequency = current;
+                ui.horizontal(|ui| {
+                    ui.label("Frequency");
+                    ui.add(
+                        egui::DragValue::new(&mut frequency)
+                            .clamp_range(radio::PUBLIC_FREQUENCIES)
+                            .custom_formatter(|value, _| radio::channel_name(value as u32)),
+                    );
+                });
+                if frequency != current {
+                    // Keep talking on the tuned frequency
+                    if data.input_kind == ChatKind::Radio(RadioChannel(current)) {
+                        data.input_kind = ChatKind::Radio(RadioChannel(frequency));
+                    }
+                    sender.send_to_server(&TuneHeadsetMessage { frequency });
+                }
+            }
+
             let response = egui::TextEdit::singleline(&mut data.input_chat)
                 .hint_text("Talk")
                 .id_source("chat_input")
@@ -294,12 +579,21 @@ fn client_chat_box(
                 if !data.input_chat.trim().is_empty() {
                     sender.send_to_server(&SpeakMessage {
                         text: std::mem::take(&mut data.input_chat),
-                        kind: ChatKind::Local,
+                        kind: data.input_kind,
                     });
                 }
                 data.input_chat.clear();
             }
         });
+
+    let Some(identity) = clicked else {
+        return;
+    };
+    // Admins get the inspector, everyone else examines
+    match identities.get_entity(identity) {
+        Some(entity) if staff.0 >= Some(StaffRole::Admin) => debug.inspected_entity = Some(entity),
+        _ => sender.send_to_server(&ExamineMessage { target: identity }),
+    }
 }
 
 fn client_handle_chat(
@@ -309,17 +603,14 @@ fn client_handle_chat(
 ) {
     for event in messages.iter() {
         let data = &mut *data;
-        event.message.message.append_to(&mut data.history);
+        let message = event.message.message.clone();
 
         // Check if we should add a speech bubble
-        let Some(speaker) = event.message.speaker else {
+        let (Some(speaker), Some(_)) = (event.message.speaker, &message.spoken_range) else {
+            data.history.push(message);
             continue;
         };
 
-        if event.message.message.spoken_range.is_none() {
-            continue;
-        }
-
         let bubble = data
             .bubbles
             .entry(speaker)
@@ -333,7 +624,8 @@ fn client_handle_chat(
                     when: time.elapsed_seconds(),
                 }
             });
-        event.message.message.append_spoken_part(&mut bubble.text);
+        message.append_spoken_part(&mut bubble.text);
+        data.history.push(message);
     }
 }
 
@@ -0,0 +1,120 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use utils::rng::SeededRng;
+
+use crate::character_sheet::{AccentSummary, CharacterSheets};
+
+/// A speech impediment that changes what a creature says.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Accent {
+    /// Repeats the start of words
+    Stutter,
+    /// Drags out vowels and slurs 's' sounds
+    Slur,
+    /// Replaces 's' with 'th'
+    Lisp,
+}
+
+impl Accent {
+    pub const ALL: [Accent; 3] = [Accent::Stutter, Accent::Slur, Accent::Lisp];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Accent::Stutter => "Stutter",
+            Accent::Slur => "Slurred speech",
+            Accent::Lisp => "Lisp",
+        }
+    }
+
+    fn apply(self, text: &str, severity: f32, rng: &mut SeededRng) -> String {
+        let mut output = String::with_capacity(text.len());
+        match self {
+            Accent::Stutter => {
+                let mut word_start = true;
+                for c in text.chars() {
+                    if word_start && c.is_alphabetic() && rng.next_f32() < severity * 0.5 {
+                        output.push(c);
+                        output.push('-');
+                        output.push(c.to_ascii_lowercase());
+                    } else {
+                        output.push(c);
+                    }
+                    word_start = c.is_whitespace();
+                }
+            }
+            Accent::Slur => {
+                for c in text.chars() {
+                    match c {
+                        's' if rng.next_f32() < severity => output.push_str("sh"),
+                        'S' if rng.next_f32() < severity => output.push_str("Sh"),
+                        'a' | 'e' | 'i' | 'o' | 'u' if rng.next_f32() < severity * 0.3 => {
+                            output.push(c);
+                            output.push(c);
+                        }
+                        c => output.push(c),
+                    }
+                }
+            }
+            Accent::Lisp => {
+                for c in text.chars() {
+                    match c {
+                        's' | 'z' if rng.next_f32() < severity => output.push_str("th"),
+                        'S' | 'Z' if rng.next_f32() < severity => output.push_str("Th"),
+                        c => output.push(c),
+                    }
+                }
+            }
+        }
+        output
+    }
+}
+
+/// Accents of a speaking creature with their severity (0 to 1).
+#[derive(Component, Default)]
+pub struct Accents {
+    pub active: Vec<(Accent, f32)>,
+}
+
+impl Accents {
+    pub fn set(&mut self, accent: Accent, severity: f32) {
+        match self.active.iter_mut().find(|(a, _)| *a == accent) {
+            Some((_, s)) => *s = severity,
+            None => self.active.push((accent, severity)),
+        }
+    }
+
+    pub fn remove(&mut self, accent: Accent) {
+        self.active.retain(|(a, _)| *a != accent);
+    }
+
+    /// Runs spoken text through all active accents
+    pub fn apply(&self, text: &str, rng: &mut SeededRng) -> String {
+        self.active
+            .iter()
+            .fold(text.to_owned(), |text, (accent, severity)| {
+                accent.apply(&text, *severity, rng)
+            })
+    }
+}
+
+/// Shows changed accents on the speaker's character sheet
+pub(super) fn sync_accents_to_sheet(
+    accents: Query<(Entity, &Accents), Changed<Accents>>,
+    mut sheets: CharacterSheets,
+) {
+    for (entity, accents) in accents.iter() {
+        let Some(mut sheet) = sheets.get_mut(entity) else {
+            continue;
+        };
+        sheet.set_accents(
+            accents
+                .active
+                .iter()
+                .map(|(accent, severity)| AccentSummary {
+                    name: accent.name().to_owned(),
+                    severity: *severity,
+                })
+                .collect(),
+        );
+    }
+}
@@ -0,0 +1,280 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use networking::{
+    is_server,
+    messaging::{AppExt, MessageEvent, MessageReceivers, MessageSender},
+    spawning::ClientControls,
+    Players,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    access::AccessCheck,
+    ui::{
+        has_window,
+        palette::{Cue, Palette},
+    },
+    GameState,
+};
+
+use super::{ChatFormat, ChatMessage, ClientChat};
+
+pub struct AnnouncementPlugin;
+
+impl Plugin for AnnouncementPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Announcement>()
+            .add_network_message::<AnnouncementMessage>();
+
+        if is_server(app) {
+            app.init_resource::<AnnouncementQueue>()
+                .add_systems(Update, (queue_announcements, play_announcements).chain());
+        } else {
+            app.init_resource::<ClientBanner>().add_systems(
+                Update,
+                (
+                    client_receive_announcements,
+                    client_banner
+                        .run_if(has_window)
+                        .run_if(in_state(GameState::Game)),
+                )
+                    .chain(),
+            );
+        }
+    }
+}
+
+/// Decides which announcement plays first and how it sounds
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum AnnouncementPriority {
+    Low,
+    Normal,
+    High,
+    /// Station-wide emergencies
+    Critical,
+}
+
+impl AnnouncementPriority {
+    pub const ALL: [Self; 4] = [Self::Low, Self::Normal, Self::High, Self::Critical];
+
+    /// Sound played when an announcement starts
+    #[cfg(feature = "client")]
+    fn cue(self) -> &'static str {
+        match self {
+            Self::Low => "sounds/announcements/chime.ogg",
+            Self::Normal => "sounds/announcements/announce.ogg",
+            Self::High => "sounds/announcements/alert.ogg",
+            Self::Critical => "sounds/announcements/siren.ogg",
+        }
+    }
+
+    fn banner_duration(self) -> Duration {
+        match self {
+            Self::Low => Duration::from_secs(4),
+            Self::Normal => Duration::from_secs(6),
+            Self::High | Self::Critical => Duration::from_secs(10),
+        }
+    }
+}
+
+/// Send this event on the server to make a station announcement.
+/// Announcements are queued, so only one plays at a time.
+#[derive(Event, Clone)]
+pub struct Announcement {
+    pub title: String,
+    pub text: String,
+    pub priority: AnnouncementPriority,
+    /// Only creatures carrying this access hear the announcement
+    pub department: Option<String>,
+    /// How long the banner is shown. Defaults depend on the priority.
+    pub duration: Option<Duration>,
+}
+
+impl Announcement {
+    pub fn new(
+        title: impl Into<String>,
+        text: impl Into<String>,
+        priority: AnnouncementPriority,
+    ) -> Self {
+        Self {
+            title: title.into(),
+            text: text.into(),
+            priority,
+            department: None,
+            duration: None,
+        }
+    }
+
+    pub fn department(mut self, access: impl Into<String>) -> Self {
+        self.department = Some(access.into());
+        self
+    }
+}
+
+#[derive(Resource, Default)]
+struct AnnouncementQueue {
+    pending: Vec<(u64, Announcement)>,
+    next_sequence: u64,
+    /// Seconds since startup when the current announcement is over
+    busy_until: f32,
+}
+
+impl AnnouncementQueue {
+    /// Takes the announcement with the highest priority, oldest first
+    fn pop(&mut self) -> Option<Announcement> {
+        let index = self
+            .pending
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, (sequence, a))| (a.priority, std::cmp::Reverse(*sequence)))
+            .map(|(i, _)| i)?;
+        Some(self.pending.remove(index).1)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct AnnouncementMessage {
+    title: String,
+    text: String,
+    priority: AnnouncementPriority,
+    department: Option<String>,
+    duration: Duration,
+}
+
+fn queue_announcements(
+    mut announcements: EventReader<Announcement>,
+    mut queue: ResMut<AnnouncementQueue>,
+) {
+    for announcement in announcements.iter() {
+        let sequence = queue.next_sequence;
+        queue.next_sequence += 1;
+        queue.pending.push((sequence, announcement.clone()));
+    }
+}
+
+fn play_announcements(
+    mut queue: ResMut<AnnouncementQueue>,
+    players: Res<Players>,
+    controls: Res<ClientControls>,
+    access: AccessCheck,
+    time: Res<Time>,
+    mut sender: MessageSender,
+) {
+    let now = time.elapsed_seconds();
+    if queue.busy_until > now {
+        return;
+    }
+    let Some(announcement) = queue.pop() else {
+        return;
+    };
+
+    let duration = announcement
+        .duration
+        .unwrap_or_else(|| announcement.priority.banner_duration());
+    queue.busy_until = now + duration.as_secs_f32();
+
+    let receivers = match &announcement.department {
+        Some(department) => MessageReceivers::Set(
+            players
+                .players()
+                .iter()
+                .filter(|(_, p)| {
+                    controls
+                        .controlled_entity(p.id)
+                        .map(|creature| access.access(creature).contains(department))
+                        .unwrap_or(false)
+                })
+                .map(|(c, _)| *c)
+                .collect(),
+        ),
+        None => MessageReceivers::AllPlayers,
+    };
+
+    info!(title = announcement.title.as_str(), department = ?announcement.department, "Announcement");
+    sender.send(
+        &AnnouncementMessage {
+            title: announcement.title,
+            text: announcement.text,
+            priority: announcement.priority,
+            department: announcement.department,
+            duration,
+        },
+        receivers,
+    );
+}
+
+#[derive(Resource, Default)]
+struct ClientBanner {
+    current: Option<AnnouncementMessage>,
+    /// Seconds since startup when the banner disappears
+    until: f32,
+}
+
+fn client_receive_announcements(
+    mut messages: EventReader<MessageEvent<AnnouncementMessage>>,
+    mut banner: ResMut<ClientBanner>,
+    mut chat: ResMut<ClientChat>,
+    time: Res<Time>,
+    #[cfg(feature = "client")] server: Res<AssetServer>,
+    #[cfg(feature = "client")] mut commands: Commands,
+) {
+    for event in messages.iter() {
+        let announcement = &event.message;
+
+        let mut entry = ChatMessage::default();
+        let heading = match &announcement.department {
+            Some(department) => format!("[{}] {}: ", department, announcement.title),
+            None => format!("{}: ", announcement.title),
+        };
+        entry.section(
+            &heading,
+            ChatFormat {
+                bold: true,
+                ..Default::default()
+            },
+        );
+        entry.append(&announcement.text);
+        chat.history.push(entry);
+
+        #[cfg(feature = "client")]
+        commands.spawn(AudioBundle {
+            source: server.load(announcement.priority.cue()),
+            settings: PlaybackSettings::DESPAWN,
+        });
+
+        banner.until = time.elapsed_seconds() + announcement.duration.as_secs_f32();
+        banner.current = Some(announcement.clone());
+    }
+}
+
+fn client_banner(
+    mut contexts: EguiContexts,
+    mut banner: ResMut<ClientBanner>,
+    palette: Res<Palette>,
+    time: Res<Time>,
+) {
+    if banner.until < time.elapsed_seconds() {
+        banner.current = None;
+    }
+    let Some(announcement) = banner.current.as_ref() else {
+        return;
+    };
+
+    let cue = match announcement.priority {
+        AnnouncementPriority::Low | AnnouncementPriority::Normal => Cue::Info,
+        AnnouncementPriority::High => Cue::Warning,
+        AnnouncementPriority::Critical => Cue::Bad,
+    };
+    egui::Window::new("announcement_banner")
+        .title_bar(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 10.0))
+        .show(contexts.ctx_mut(), |ui| {
+            ui.vertical_centered(|ui| {
+                ui.heading(palette.text(cue, &announcement.title));
+                ui.label(&announcement.text);
+            });
+        });
+}
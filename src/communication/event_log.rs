@@ -0,0 +1,124 @@
+//! Plain text log of everything shown to the player.
+//!
+//! Chat, examine results and game events end up in one scrollable window,
+//! which works with screen readers and can be copied into bug reports.
+
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    controls::{Action, Actions},
+    ui::has_window,
+    GameState,
+};
+
+use super::ClientChat;
+
+pub struct EventLogPlugin;
+
+impl Plugin for EventLogPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EventLog>().add_systems(
+            Update,
+            (
+                log_chat_history,
+                event_log_ui
+                    .run_if(has_window)
+                    .run_if(in_state(GameState::Game)),
+            )
+                .chain(),
+        );
+    }
+}
+
+/// File the log is mirrored to when enabled
+const EVENT_LOG_FILE: &str = "event-log.txt";
+
+#[derive(Resource, Default)]
+pub struct EventLog {
+    /// All entries, one per line
+    text: String,
+    /// Also append new entries to [`EVENT_LOG_FILE`]
+    mirror_to_file: bool,
+    open: bool,
+}
+
+impl EventLog {
+    pub fn record(&mut self, entry: &str) {
+        let line = format!("[{}] {}\n", timestamp(), entry);
+        self.text += &line;
+
+        if self.mirror_to_file {
+            let result = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(EVENT_LOG_FILE)
+                .and_then(|mut file| file.write_all(line.as_bytes()));
+            if let Err(err) = result {
+                error!("Error writing event log: {}", err);
+                self.mirror_to_file = false;
+            }
+        }
+    }
+}
+
+/// Time of day in UTC, so entries can be matched with server logs
+fn timestamp() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600 % 24,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Copies new chat messages into the log, including examine results and announcements
+fn log_chat_history(chat: Res<ClientChat>, mut log: ResMut<EventLog>, mut logged: Local<usize>) {
+    for message in chat.history.iter().skip(*logged) {
+        log.record(&message.text);
+    }
+    *logged = chat.history.len();
+}
+
+fn event_log_ui(mut contexts: EguiContexts, mut log: ResMut<EventLog>, actions: Actions) {
+    let ctx = contexts.ctx_mut();
+    if actions.just_pressed(Action::ToggleEventLog) && !ctx.wants_keyboard_input() {
+        log.open = !log.open;
+    }
+    if !log.open {
+        return;
+    }
+
+    let log = &mut *log;
+    egui::Window::new("Event log")
+        .open(&mut log.open)
+        .default_size(egui::vec2(400.0, 300.0))
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Copy all").clicked() {
+                    ui.output_mut(|output| output.copied_text = log.text.clone());
+                }
+                ui.checkbox(&mut log.mirror_to_file, "Save to file")
+                    .on_hover_text(EVENT_LOG_FILE);
+            });
+            egui::ScrollArea::vertical()
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    // Read-only text can still be selected and read by screen readers
+                    ui.add(
+                        egui::TextEdit::multiline(&mut log.text.as_str())
+                            .desired_width(f32::INFINITY),
+                    );
+                });
+        });
+}
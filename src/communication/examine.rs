@@ -0,0 +1,244 @@
+//! Examining entities, from clicking a link in chat or the "Examine" interaction.
+//!
+//! Other plugins add lines to a description by handling [`ExamineEvents`]
+//! in the [`DescribeExamined`] set.
+
+use std::sync::Mutex;
+
+use bevy::prelude::*;
+use networking::{
+    identity::{NetworkIdentities, NetworkIdentity},
+    is_server,
+    messaging::{AppExt, MessageEvent, MessageReceivers, MessageSender},
+    spawning::ClientControls,
+    visibility::NetworkVisibilities,
+    ConnectionId, Players,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    interaction::{
+        ActiveInteraction, GenerateInteractionList, InteractionListEvents, InteractionOption,
+        InteractionSpecificity, InteractionStatus,
+    },
+    items::{stacks::Stack, Item},
+};
+
+use super::{ChatFormat, ChatMessage, SpeechMessage, SpeechName};
+
+pub struct ExaminePlugin;
+
+impl Plugin for ExaminePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_network_message::<ExamineMessage>();
+
+        if is_server(app) {
+            app.register_type::<ExamineInteraction>()
+                .init_resource::<ExamineEvents>()
+                .configure_sets(
+                    Update,
+                    DescribeExamined
+                        .run_if(|examines: Res<ExamineEvents>| !examines.events.is_empty())
+                        .after(begin_examine)
+                        .before(send_descriptions),
+                )
+                .add_systems(
+                    Update,
+                    (
+                        prepare_examine_interaction.in_set(GenerateInteractionList),
+                        (
+                            examine_interaction,
+                            begin_examine,
+                            describe_basics.in_set(DescribeExamined),
+                            send_descriptions,
+                        )
+                            .chain(),
+                    ),
+                );
+        }
+    }
+}
+
+/// Client message to examine an entity
+#[derive(Serialize, Deserialize)]
+pub(super) struct ExamineMessage {
+    pub(super) target: NetworkIdentity,
+}
+
+/// The set in which systems add lines to descriptions of examined entities.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, SystemSet)]
+pub struct DescribeExamined;
+
+/// A player examining an entity
+pub struct ExamineEvent {
+    connection: ConnectionId,
+    /// The creature doing the examining, if the player controls one
+    pub examiner: Option<Entity>,
+    pub target: Entity,
+    // Behind a mutex to allow concurrent execution of describing systems
+    lines: Mutex<Vec<String>>,
+}
+
+impl ExamineEvent {
+    /// Adds a sentence to the description
+    pub fn add_line(&self, line: impl Into<String>) {
+        self.lines.lock().unwrap().push(line.into());
+    }
+}
+
+/// Entities examined this frame, being described by systems in [`DescribeExamined`]
+#[derive(Resource, Default)]
+pub struct ExamineEvents {
+    pub events: Vec<ExamineEvent>,
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct ExamineInteraction;
+
+impl FromWorld for ExamineInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self
+    }
+}
+
+fn prepare_examine_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    identities: Res<NetworkIdentities>,
+) {
+    for event in interaction_list.events.iter() {
+        if identities.get_identity(event.target).is_none() {
+            continue;
+        }
+
+        event.add_interaction(InteractionOption {
+            text: "Examine".into(),
+            interaction: Box::new(ExamineInteraction),
+            specificity: InteractionSpecificity::Generic,
+        });
+    }
+}
+
+fn examine_interaction(
+    mut query: Query<(Entity, &mut ActiveInteraction), With<ExamineInteraction>>,
+    controls: Res<ClientControls>,
+    players: Res<Players>,
+    mut examines: ResMut<ExamineEvents>,
+) {
+    for (source, mut active) in query.iter_mut() {
+        active.status = InteractionStatus::Completed;
+        let Some(connection) = controls
+            .controlling_player(source)
+            .and_then(|player| players.get_connection(&player))
+        else {
+            continue;
+        };
+        examines.events.push(ExamineEvent {
+            connection,
+            examiner: None,
+            target: active.target,
+            lines: Default::default(),
+        });
+    }
+}
+
+fn begin_examine(
+    mut messages: EventReader<MessageEvent<ExamineMessage>>,
+    mut examines: ResMut<ExamineEvents>,
+    identities: Res<NetworkIdentities>,
+    mut visibilities: ResMut<NetworkVisibilities>,
+    controls: Res<ClientControls>,
+    players: Res<Players>,
+) {
+    for event in messages.iter() {
+        let target = event.message.target;
+
+        // Only allow examining things the player can see
+        let visible = visibilities
+            .get_mut(target)
+            .map(|v| v.has_observer(&event.connection))
+            .unwrap_or(false);
+        if !visible {
+            continue;
+        }
+
+        let Some(entity) = identities.get_entity(target) else {
+            continue;
+        };
+        examines.events.push(ExamineEvent {
+            connection: event.connection,
+            examiner: None,
+            target: entity,
+            lines: Default::default(),
+        });
+    }
+
+    for examine in examines.events.iter_mut() {
+        examine.examiner = players
+            .get(examine.connection)
+            .and_then(|player| controls.controlled_entity(player.id));
+    }
+}
+
+/// Describes what is common to all items
+fn describe_basics(examines: Res<ExamineEvents>, items: Query<(&Item, Option<&Stack>)>) {
+    for examine in examines.events.iter() {
+        let Ok((item, stack)) = items.get(examine.target) else {
+            continue;
+        };
+        if !item.description.is_empty() {
+            examine.add_line(item.description.as_str());
+        }
+        if let Some(stack) = stack {
+            examine.add_line(format!("There are {} in the stack.", stack.count()));
+        }
+    }
+}
+
+fn send_descriptions(
+    mut examines: ResMut<ExamineEvents>,
+    identities: Res<NetworkIdentities>,
+    names: Query<AnyOf<(&SpeechName, &Item, &Name)>>,
+    mut sender: MessageSender,
+) {
+    for examine in examines.events.drain(..) {
+        let name = match names.get(examine.target) {
+            Ok((Some(speech_name), _, _)) => speech_name.0.clone(),
+            Ok((_, Some(item), _)) => item.name.clone(),
+            Ok((_, _, Some(name))) => name.as_str().to_owned(),
+            _ => continue,
+        };
+
+        let mut message = ChatMessage::default();
+        message.section(
+            "That's ",
+            ChatFormat {
+                italics: true,
+                ..Default::default()
+            },
+        );
+        message.entity_link(
+            &name,
+            identities.get_identity(examine.target),
+            ChatFormat {
+                italics: true,
+                bold: true,
+                ..Default::default()
+            },
+        );
+        message.append(".");
+        for line in examine.lines.into_inner().unwrap() {
+            message.append(" ");
+            message.append(&line);
+        }
+
+        sender.send(
+            &SpeechMessage {
+                message,
+                speaker: None,
+            },
+            MessageReceivers::Single(examine.connection),
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use std::ops::RangeInclusive;
+
+use bevy::{ecs::system::SystemParam, prelude::*, reflect::TypeUuid};
+use networking::{
+    component::AppExt as ComponentAppExt,
+    is_server,
+    messaging::{AppExt, MessageEvent},
+    spawning::ClientControls,
+    variable::{NetworkVar, ServerVar},
+    Networked, Players,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::items::clothes::Equipped;
+
+pub struct RadioPlugin;
+
+impl Plugin for RadioPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Headset>()
+            .add_networked_component::<Headset, HeadsetClient>()
+            .add_network_message::<TuneHeadsetMessage>();
+        if is_server(app) {
+            app.add_systems(Update, (setup_headsets, handle_tune_message));
+        }
+    }
+}
+
+/// Clothing slot headsets are worn in
+pub const HEADSET_SLOT: &str = "ears";
+/// Frequency new headsets are tuned to
+const COMMON_FREQUENCY: u32 = 1459;
+/// Frequencies anyone can tune to, in tenths of a unit
+pub const PUBLIC_FREQUENCIES: RangeInclusive<u32> = 1441..=1489;
+/// Channels only headsets with the department's key can use: (key, name, frequency)
+const DEPARTMENT_CHANNELS: &[(&str, &str, u32)] = &[
+    ("command", "Command", 1353),
+    ("security", "Security", 1359),
+    ("medical", "Medical", 1355),
+    ("engineering", "Engineering", 1357),
+    ("cargo", "Supply", 1347),
+];
+
+/// Display name of a frequency
+pub fn channel_name(frequency: u32) -> String {
+    DEPARTMENT_CHANNELS
+        .iter()
+        .find(|(_, _, f)| *f == frequency)
+        .map(|(_, name, _)| (*name).to_owned())
+        .unwrap_or_else(|| format!("{}.{}", frequency / 10, frequency % 10))
+}
+
+/// A radio worn on the ears. Sends and receives on its tuned frequency
+/// and on the channels of departments it has keys for.
+#[derive(Component, Reflect, Networked)]
+#[reflect(Component)]
+#[networked(client = "HeadsetClient")]
+pub struct Headset {
+    /// Departments this headset has encryption keys for
+    keys: Vec<String>,
+    #[reflect(ignore)]
+    frequency: NetworkVar<u32>,
+    /// Frequencies of the keyed departments, kept for clients
+    #[reflect(ignore)]
+    department_frequencies: NetworkVar<Vec<u32>>,
+}
+
+impl Default for Headset {
+    fn default() -> Self {
+        Self {
+            keys: Vec::new(),
+            frequency: COMMON_FREQUENCY.into(),
+            department_frequencies: Default::default(),
+        }
+    }
+}
+
+impl Headset {
+    /// If the headset can send and receive on a frequency
+    pub fn can_use(&self, frequency: u32) -> bool {
+        *self.frequency == frequency || self.department_frequencies.contains(&frequency)
+    }
+}
+
+#[derive(Component, Default, TypeUuid, Networked)]
+#[uuid = "c4b9e2a1-5d3f-4f6a-8e07-9a1b2c3d4e5f"]
+#[networked(server = "Headset")]
+pub struct HeadsetClient {
+    frequency: ServerVar<u32>,
+    department_frequencies: ServerVar<Vec<u32>>,
+}
+
+impl HeadsetClient {
+    pub fn frequency(&self) -> Option<u32> {
+        self.frequency.get().copied()
+    }
+
+    /// Every frequency the headset can talk on, starting with the tuned one
+    pub fn channels(&self) -> impl Iterator<Item = u32> + '_ {
+        self.frequency().into_iter().chain(
+            self.department_frequencies
+                .get()
+                .into_iter()
+                .flatten()
+                .copied(),
+        )
+    }
+}
+
+fn setup_headsets(mut headsets: Query<&mut Headset, Added<Headset>>) {
+    for mut headset in headsets.iter_mut() {
+        let frequencies = DEPARTMENT_CHANNELS
+            .iter()
+            .filter(|(key, _, _)| headset.keys.iter().any(|k| k == key))
+            .map(|(_, _, frequency)| *frequency)
+            .collect();
+        *headset.department_frequencies = frequencies;
+    }
+}
+
+/// Finds the headsets creatures are wearing
+#[derive(SystemParam)]
+pub struct Radios<'w, 's> {
+    children: Query<'w, 's, &'static Children>,
+    headsets: Query<'w, 's, (&'static Headset, &'static Equipped)>,
+}
+
+impl<'w, 's> Radios<'w, 's> {
+    pub fn worn(&self, creature: Entity) -> Option<&Headset> {
+        self.headsets
+            .iter_many(self.children.iter_descendants(creature))
+            .find(|(_, equipped)| equipped.slot() == HEADSET_SLOT)
+            .map(|(headset, _)| headset)
+    }
+}
+
+/// Client message to tune the worn headset to a public frequency
+#[derive(Serialize, Deserialize)]
+pub struct TuneHeadsetMessage {
+    pub frequency: u32,
+}
+
+fn handle_tune_message(
+    mut messages: EventReader<MessageEvent<TuneHeadsetMessage>>,
+    players: Res<Players>,
+    controls: Res<ClientControls>,
+    children: Query<&Children>,
+    mut headsets: Query<(&mut Headset, &Equipped)>,
+) {
+    for event in messages.iter() {
+        let frequency = event.message.frequency;
+        if !PUBLIC_FREQUENCIES.contains(&frequency) {
+            continue;
+        }
+        let Some(creature) = players
+            .get(event.connection)
+            .and_then(|player| controls.controlled_entity(player.id))
+        else {
+            continue;
+        };
+
+        let mut iter = headsets.iter_many_mut(children.iter_descendants(creature));
+        while let Some((mut headset, equipped)) = iter.fetch_next() {
+            if equipped.slot() == HEADSET_SLOT {
+                *headset.frequency = frequency;
+                break;
+            }
+        }
+    }
+}
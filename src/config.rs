@@ -1,18 +1,222 @@
-use std::{fs::read_to_string, time::Duration};
+use std::{collections::HashMap, fs::read_to_string, path::PathBuf, time::Duration};
 
 use async_compat::Compat;
 use bevy::{
     prelude::{error, Res, Resource},
     tasks::IoTaskPool,
 };
+use networking::guard::GuardConfig;
 use serde::{Deserialize, Serialize};
 use tokio::time::{interval, MissedTickBehavior};
 
-use crate::{ArgCommands, Args};
+use crate::{admin::StaffRole, webhooks::WebhookEventKind, ArgCommands, Args};
 
 #[derive(Default, Deserialize, Resource)]
 pub struct ServerConfig {
     pub registration: Option<ServerRegistration>,
+    /// Fixed seed for round randomness. A random seed is used if not set.
+    pub round_seed: Option<u64>,
+    #[serde(default)]
+    pub staff: StaffConfig,
+    /// Ticks per second the server runs at when it isn't overloaded. Defaults to 60.
+    pub tick_rate: Option<u32>,
+    /// Lowest tick rate the server may drop to when overloaded
+    pub min_tps: Option<u32>,
+    /// Grid cells a player can start seeing each tick after moving into a new area. Defaults to 4.
+    /// Higher values fill in new areas faster, at the cost of larger bandwidth spikes.
+    pub new_cells_per_tick: Option<usize>,
+    #[serde(default)]
+    pub item_cleanup: ItemCleanupConfig,
+    /// Where player characters are saved. Defaults to `data/characters`.
+    pub character_directory: Option<PathBuf>,
+    /// Where bans are saved. Defaults to `data/bans.toml`.
+    pub ban_file: Option<PathBuf>,
+    /// Players that can be in the round at once. Everyone else joins as a spectator.
+    pub max_players: Option<usize>,
+    /// Map loaded when the server starts. Defaults to `BoxStation`.
+    pub map: Option<String>,
+    /// Carry tiles changed during a round over to the next round on the same map
+    #[serde(default)]
+    pub map_wear: bool,
+    #[serde(default)]
+    pub round: RoundConfig,
+    #[serde(default)]
+    pub respawn: RespawnConfig,
+    #[serde(default)]
+    pub webhooks: WebhookConfig,
+    /// Rate limits and penalties for clients sending invalid messages
+    #[serde(default)]
+    pub network_guard: GuardConfig,
+    #[serde(default)]
+    pub backups: BackupConfig,
+}
+
+/// Timing of the round lifecycle
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct RoundConfig {
+    /// Seconds between everyone being ready and the round starting
+    pub start_countdown_seconds: f32,
+    /// Seconds between the round ending and the server restarting
+    pub end_delay_seconds: f32,
+    /// Minutes after which the round ends on its own
+    pub max_duration_minutes: Option<f32>,
+}
+
+impl Default for RoundConfig {
+    fn default() -> Self {
+        Self {
+            start_countdown_seconds: 30.0,
+            end_delay_seconds: 30.0,
+            max_duration_minutes: None,
+        }
+    }
+}
+
+impl RoundConfig {
+    pub fn start_countdown(&self) -> Duration {
+        Duration::from_secs_f32(self.start_countdown_seconds.max(0.0))
+    }
+
+    pub fn end_delay(&self) -> Duration {
+        Duration::from_secs_f32(self.end_delay_seconds.max(0.0))
+    }
+
+    pub fn max_duration(&self) -> Option<Duration> {
+        self.max_duration_minutes
+            .map(|minutes| Duration::from_secs_f32(minutes.max(0.0) * 60.0))
+    }
+}
+
+/// Penalties for players abandoning their character
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct RespawnConfig {
+    /// Minutes a ghost waits before joining as a new character
+    pub delay_minutes: f32,
+    /// Clear the job of abandoned characters so it can be taken again
+    pub free_job_slot: bool,
+    /// Times a player may respawn in one round, unlimited if not set
+    pub max_respawns: Option<u32>,
+}
+
+impl Default for RespawnConfig {
+    fn default() -> Self {
+        Self {
+            delay_minutes: 5.0,
+            free_job_slot: true,
+            max_respawns: None,
+        }
+    }
+}
+
+impl RespawnConfig {
+    pub fn delay(&self) -> Duration {
+        Duration::from_secs_f32(self.delay_minutes.max(0.0) * 60.0)
+    }
+}
+
+/// Outbound webhooks for community services like chat bots and dashboards
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct WebhookConfig {
+    pub endpoints: Vec<WebhookEndpoint>,
+    /// Times a delivery is tried before it is written to the dead-letter file
+    pub max_attempts: u32,
+    /// Seconds before the first retry, doubled for every retry after it
+    pub retry_delay_seconds: f32,
+    /// Where undeliverable events are appended to
+    pub dead_letter_file: PathBuf,
+    /// Numbers of online players that are announced when reached
+    pub player_milestones: Vec<usize>,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            endpoints: Vec::new(),
+            max_attempts: 5,
+            retry_delay_seconds: 2.0,
+            dead_letter_file: "webhook-dead-letters.log".into(),
+            player_milestones: Vec::new(),
+        }
+    }
+}
+
+impl WebhookConfig {
+    pub fn retry_delay(&self) -> Duration {
+        Duration::from_secs_f32(self.retry_delay_seconds.max(0.0))
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct WebhookEndpoint {
+    pub url: String,
+    /// Events posted to this endpoint, all events if empty
+    #[serde(default)]
+    pub(crate) events: Vec<WebhookEventKind>,
+}
+
+/// Backups of persistent data like bans and characters
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct BackupConfig {
+    /// Minutes between backups, only backed up by command if not set
+    pub interval_minutes: Option<f32>,
+    /// Backups kept, older ones are deleted
+    pub keep: usize,
+    pub directory: PathBuf,
+    /// Where exported backups are copied to, they are never deleted
+    pub export_directory: PathBuf,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            interval_minutes: Some(30.0),
+            keep: 5,
+            directory: "data/backups".into(),
+            export_directory: "data/exports".into(),
+        }
+    }
+}
+
+impl BackupConfig {
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval_minutes
+            .map(|minutes| Duration::from_secs_f32(minutes.max(1.0) * 60.0))
+    }
+}
+
+/// When loose items are removed from the world
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ItemCleanupConfig {
+    /// Minutes before trash outside of containers is removed
+    pub trash_minutes: Option<f32>,
+    /// Minutes before any other item outside of containers is removed
+    pub loose_minutes: Option<f32>,
+}
+
+impl Default for ItemCleanupConfig {
+    fn default() -> Self {
+        Self {
+            trash_minutes: Some(10.0),
+            loose_minutes: None,
+        }
+    }
+}
+
+/// Player ids of server staff
+#[derive(Default, Deserialize)]
+pub struct StaffConfig {
+    #[serde(default)]
+    pub admins: Vec<String>,
+    #[serde(default)]
+    pub mentors: Vec<String>,
+    /// Staff role needed for console commands, by command name
+    #[serde(default)]
+    pub(crate) command_roles: HashMap<String, StaffRole>,
 }
 
 #[derive(Deserialize, Clone)]
@@ -1,27 +1,44 @@
 use std::time::Duration;
 
 use bevy::prelude::*;
-use maps::MapCommandsExt;
-use networking::is_server;
+use maps::{MapCommandsExt, TileEntity};
+use networking::{is_server, scene::NetworkSceneBundle};
 
-use crate::interaction::{
-    ActiveInteraction, GenerateInteractionList, InteractionListEvents, InteractionOption,
-    InteractionSpecificity, InteractionStatus,
+mod recipes;
+
+use crate::{
+    despawn::{ContentsPolicy, DespawnCommandsExt},
+    interaction::{
+        ActiveInteraction, GenerateInteractionList, InteractionListEvents, InteractionOption,
+        InteractionSpecificity, InteractionStatus,
+    },
+    items::tools::{ToolKind, Tools},
 };
 
 pub struct ConstructionPlugin;
 
 impl Plugin for ConstructionPlugin {
     fn build(&self, app: &mut App) {
-        app.register_type::<Wrench>()
-            .register_type::<WrenchDeconstructable>()
-            .register_type::<WrenchDeconstructInteraction>();
+        app.register_type::<WrenchDeconstructable>()
+            .register_type::<WrenchDeconstructInteraction>()
+            .register_type::<WrenchRotatable>()
+            .register_type::<WrenchRotateInteraction>()
+            .register_type::<CrowbarRemovable>()
+            .register_type::<CrowbarRemoveInteraction>()
+            .add_plugins(recipes::RecipePlugin);
         if is_server(app) {
             app.add_systems(
                 Update,
                 (
-                    prepare_deconstruct_wrench_interaction.in_set(GenerateInteractionList),
+                    (
+                        prepare_deconstruct_wrench_interaction,
+                        prepare_rotate_wrench_interaction,
+                        prepare_crowbar_remove_interaction,
+                    )
+                        .in_set(GenerateInteractionList),
                     execute_deconstruct_wrench_interaction,
+                    execute_rotate_wrench_interaction,
+                    execute_crowbar_remove_interaction,
                 ),
             );
         }
@@ -29,11 +46,8 @@ impl Plugin for ConstructionPlugin {
 }
 
 const DECONSTRUCT_TIME: Duration = Duration::from_secs(2);
-
-/// Marks an object as a wrench tool.
-#[derive(Component, Reflect, Default)]
-#[reflect(Component)]
-struct Wrench;
+const ROTATE_TIME: Duration = Duration::from_millis(500);
+const PRY_TIME: Duration = Duration::from_secs(1);
 
 #[derive(Component, Reflect, Default)]
 #[reflect(Component)]
@@ -57,7 +71,7 @@ impl Default for WrenchDeconstructInteraction {
 
 fn prepare_deconstruct_wrench_interaction(
     list: Res<InteractionListEvents>,
-    wrenches: Query<(), With<Wrench>>,
+    tools: Tools,
     deconstructables: Query<(), With<WrenchDeconstructable>>,
 ) {
     for event in list.events.iter() {
@@ -65,7 +79,7 @@ fn prepare_deconstruct_wrench_interaction(
             continue;
         };
 
-        if !wrenches.contains(item_in_hand) {
+        if !tools.is(item_in_hand, ToolKind::Wrench) {
             continue;
         }
 
@@ -84,24 +98,208 @@ fn prepare_deconstruct_wrench_interaction(
 }
 
 fn execute_deconstruct_wrench_interaction(
-    mut query: Query<(&WrenchDeconstructInteraction, &mut ActiveInteraction)>,
+    mut query: Query<(
+        Entity,
+        &WrenchDeconstructInteraction,
+        &mut ActiveInteraction,
+    )>,
+    mut tools: Tools,
     deconstructables: Query<(), With<WrenchDeconstructable>>,
     time: Res<Time>,
     mut commands: Commands,
 ) {
-    for (interaction, mut active) in query.iter_mut() {
-        active.set_initial_duration(DECONSTRUCT_TIME);
+    for (source, interaction, mut active) in query.iter_mut() {
+        let Some(tool) = tools.held(source, ToolKind::Wrench) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        let duration = tools.duration(tool, DECONSTRUCT_TIME);
+        active.set_initial_duration(duration);
 
         if !deconstructables.contains(active.target) {
             active.status = InteractionStatus::Canceled;
             continue;
         }
 
-        if active.start_time() + DECONSTRUCT_TIME.as_secs_f32() > time.elapsed_seconds() {
+        if active.start_time() + duration.as_secs_f32() > time.elapsed_seconds() {
             continue;
         }
 
-        commands.despawn_tile_entity(interaction.target);
+        tools.use_tool(tool);
+
+        commands.despawn_cascade(interaction.target, ContentsPolicy::Drop);
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+/// Allows rotating a tile object in place using a wrench.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+struct WrenchRotatable;
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct WrenchRotateInteraction {
+    target: Entity,
+}
+
+// Dummy default for Reflect
+impl Default for WrenchRotateInteraction {
+    fn default() -> Self {
+        Self {
+            target: Entity::from_raw(0),
+        }
+    }
+}
+
+fn prepare_rotate_wrench_interaction(
+    list: Res<InteractionListEvents>,
+    tools: Tools,
+    rotatables: Query<(), (With<WrenchRotatable>, With<TileEntity>)>,
+) {
+    for event in list.events.iter() {
+        let Some(item_in_hand) = event.item_in_hand else {
+            continue;
+        };
+
+        if !tools.is(item_in_hand, ToolKind::Wrench) {
+            continue;
+        }
+
+        if !rotatables.contains(event.target) {
+            continue;
+        }
+
+        event.add_interaction(InteractionOption {
+            text: "Rotate".into(),
+            interaction: Box::new(WrenchRotateInteraction {
+                target: event.target,
+            }),
+            specificity: InteractionSpecificity::Specific,
+        });
+    }
+}
+
+fn execute_rotate_wrench_interaction(
+    mut query: Query<(Entity, &WrenchRotateInteraction, &mut ActiveInteraction)>,
+    mut tools: Tools,
+    rotatables: Query<&TileEntity, With<WrenchRotatable>>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (source, interaction, mut active) in query.iter_mut() {
+        let Some(tool) = tools.held(source, ToolKind::Wrench) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        let duration = tools.duration(tool, ROTATE_TIME);
+        active.set_initial_duration(duration);
+
+        let Ok(tile) = rotatables.get(interaction.target) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+
+        if active.start_time() + duration.as_secs_f32() > time.elapsed_seconds() {
+            continue;
+        }
+
+        tools.use_tool(tool);
+
+        commands.rotate_tile_entity(interaction.target, tile.direction().rotate_clockwise());
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+/// A tile object that can be pried off with a crowbar (like floor tiles).
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+struct CrowbarRemovable {
+    /// Scene path of the item left behind after removal
+    item: String,
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct CrowbarRemoveInteraction {
+    target: Entity,
+}
+
+// Dummy default for Reflect
+impl Default for CrowbarRemoveInteraction {
+    fn default() -> Self {
+        Self {
+            target: Entity::from_raw(0),
+        }
+    }
+}
+
+fn prepare_crowbar_remove_interaction(
+    list: Res<InteractionListEvents>,
+    tools: Tools,
+    removables: Query<(), (With<CrowbarRemovable>, With<TileEntity>)>,
+) {
+    for event in list.events.iter() {
+        let Some(item_in_hand) = event.item_in_hand else {
+            continue;
+        };
+
+        if !tools.is(item_in_hand, ToolKind::Crowbar) {
+            continue;
+        }
+
+        if !removables.contains(event.target) {
+            continue;
+        }
+
+        event.add_interaction(InteractionOption {
+            text: "Pry off".into(),
+            interaction: Box::new(CrowbarRemoveInteraction {
+                target: event.target,
+            }),
+            specificity: InteractionSpecificity::Specific,
+        });
+    }
+}
+
+fn execute_crowbar_remove_interaction(
+    mut query: Query<(Entity, &CrowbarRemoveInteraction, &mut ActiveInteraction)>,
+    mut tools: Tools,
+    removables: Query<(&CrowbarRemovable, &GlobalTransform)>,
+    time: Res<Time>,
+    server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    for (source, interaction, mut active) in query.iter_mut() {
+        let Some(tool) = tools.held(source, ToolKind::Crowbar) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        let duration = tools.duration(tool, PRY_TIME);
+        active.set_initial_duration(duration);
+
+        let Ok((removable, transform)) = removables.get(interaction.target) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+
+        if active.start_time() + duration.as_secs_f32() > time.elapsed_seconds() {
+            continue;
+        }
+
+        tools.use_tool(tool);
+
+        if !removable.item.is_empty() {
+            commands.spawn(NetworkSceneBundle {
+                scene: server.load(removable.item.as_str()).into(),
+                transform: Transform::from_translation(transform.translation() + Vec3::Y * 0.2),
+                ..Default::default()
+            });
+        }
+
+        commands.despawn_cascade(interaction.target, ContentsPolicy::Drop);
         active.status = InteractionStatus::Completed;
     }
 }
@@ -0,0 +1,540 @@
+use std::time::Duration;
+
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+};
+use bevy_common_assets::ron::RonAssetPlugin;
+use maps::{Direction, MapCommandsExt, MountSurface, TileEntity, TileLayer, TileMap, DIRECTIONS};
+use networking::{
+    is_server,
+    scene::{NetworkScene, NetworkSceneBundle},
+};
+use serde::Deserialize;
+
+use crate::{
+    despawn::{ContentsPolicy, DespawnCommandsExt},
+    interaction::{
+        ActiveInteraction, GenerateInteractionList, InteractionListEvents, InteractionOption,
+        InteractionSpecificity, InteractionStatus,
+    },
+    items::{
+        stacks::{PendingStack, Stack},
+        tools::{ToolKind, Tools},
+    },
+};
+
+pub struct RecipePlugin;
+
+impl Plugin for RecipePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<ConstructionMaterial>();
+
+        if is_server(app) {
+            app.add_plugins(RonAssetPlugin::<ConstructionRecipe>::new(&["recipe.ron"]))
+                .register_type::<BuildInteraction>()
+                .register_type::<DismantleInteraction>()
+                .add_systems(Startup, load_recipes)
+                .add_systems(
+                    Update,
+                    (
+                        (prepare_build_interaction, prepare_dismantle_interaction)
+                            .in_set(GenerateInteractionList),
+                        build_interaction,
+                        dismantle_interaction,
+                    ),
+                );
+        }
+    }
+}
+
+/// A sequence of tile entities built on top of each other, like girder, wall and reinforced wall.
+/// Building advances to the next stage, dismantling goes back to the previous one.
+#[derive(Deserialize, TypeUuid, TypePath)]
+#[uuid = "8e4b7c1a-2f6d-4a93-b0e5-6c3d9f1a7b28"]
+pub struct ConstructionRecipe {
+    pub id: String,
+    /// Turf put back when a stage on the turf layer is dismantled into one that isn't
+    #[serde(default)]
+    pub floor: Option<String>,
+    pub stages: Vec<ConstructionStage>,
+}
+
+#[derive(Deserialize)]
+pub struct ConstructionStage {
+    pub name: String,
+    /// Tile entity scene of the stage
+    pub scene: String,
+    pub layer: TileLayer,
+    /// Held material used up to build the stage
+    #[serde(default)]
+    pub material: Option<MaterialCost>,
+    /// Held tool needed to build the stage, only used if no material is needed
+    #[serde(default)]
+    pub tool: Option<ToolKind>,
+    /// Seconds it takes to build or dismantle the stage
+    pub seconds: f32,
+    /// Tool needed to dismantle the stage. It can't be dismantled if `None`.
+    #[serde(default)]
+    pub dismantle_tool: Option<ToolKind>,
+}
+
+impl ConstructionStage {
+    fn duration(&self) -> Duration {
+        Duration::from_secs_f32(self.seconds.max(0.0))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct MaterialCost {
+    /// Identifier of the [`ConstructionMaterial`]
+    pub material: String,
+    pub amount: u32,
+    /// Item scene given back when dismantling
+    pub item: String,
+}
+
+/// Material that construction recipes use up. The amount is taken from its [`Stack`].
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct ConstructionMaterial {
+    pub material: String,
+}
+
+#[derive(Resource)]
+struct RecipeAssets {
+    recipes: Vec<Handle<ConstructionRecipe>>,
+}
+
+fn load_recipes(mut commands: Commands, server: ResMut<AssetServer>) {
+    let assets = RecipeAssets {
+        recipes: server
+            .load_folder("construction")
+            .expect("assets/construction is missing")
+            .into_iter()
+            .map(HandleUntyped::typed)
+            .collect(),
+    };
+    commands.insert_resource(assets);
+}
+
+/// Looks up recipes and the stage a tile entity is in
+#[derive(SystemParam)]
+struct Recipes<'w, 's> {
+    assets: Res<'w, RecipeAssets>,
+    recipes: Res<'w, Assets<ConstructionRecipe>>,
+    server: Res<'w, AssetServer>,
+    scenes: Query<'w, 's, &'static NetworkScene>,
+}
+
+impl<'w, 's> Recipes<'w, 's> {
+    fn iter(&self) -> impl Iterator<Item = &ConstructionRecipe> {
+        self.assets
+            .recipes
+            .iter()
+            .filter_map(|h| self.recipes.get(h))
+    }
+
+    fn get(&self, id: &str) -> Option<&ConstructionRecipe> {
+        self.iter().find(|r| r.id == id)
+    }
+
+    /// Finds the recipe stages the entity is spawned from
+    fn stages_of(&self, entity: Entity) -> Vec<(&ConstructionRecipe, usize)> {
+        let Some(path) = self
+            .scenes
+            .get(entity)
+            .ok()
+            .and_then(|scene| self.server.get_handle_path(scene.handle()))
+        else {
+            return Vec::new();
+        };
+        let path = path.path().to_string_lossy().replace('\\', "/");
+        self.iter()
+            .flat_map(|recipe| {
+                recipe
+                    .stages
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, stage)| stage.scene == path)
+                    .map(move |(index, _)| (recipe, index))
+            })
+            .collect()
+    }
+}
+
+/// Checks if the held item can be used to build a stage
+fn can_build(
+    stage: &ConstructionStage,
+    item: Entity,
+    material: Option<(&ConstructionMaterial, Option<&Stack>)>,
+    tools: &Tools,
+) -> bool {
+    match (&stage.material, stage.tool) {
+        (Some(cost), _) => material.map_or(false, |(m, stack)| {
+            m.material == cost.material && stack.map_or(1, Stack::count) >= cost.amount
+        }),
+        (None, Some(tool)) => tools.is(item, tool),
+        (None, None) => true,
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct BuildInteraction {
+    /// The turf to start on, or the entity of the previous stage
+    target: Entity,
+    item: Entity,
+    recipe: String,
+    /// Stage that is being built
+    stage: usize,
+    /// Wall a new wall mount is hung on
+    #[reflect(ignore)]
+    direction: Direction,
+}
+
+impl FromWorld for BuildInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            target: Entity::PLACEHOLDER,
+            item: Entity::PLACEHOLDER,
+            recipe: String::new(),
+            stage: 0,
+            direction: Direction::default(),
+        }
+    }
+}
+
+/// Slot a new construction takes on its tile. Only wall mounts use the direction.
+fn placement_slot(layer: TileLayer, direction: Direction) -> Option<Direction> {
+    (layer == TileLayer::HighMount).then_some(direction)
+}
+
+/// Directions a new construction can be started in on a floor
+fn start_directions(
+    map: &TileMap,
+    position: UVec2,
+    layer: TileLayer,
+    mount_surfaces: &Query<(), With<MountSurface>>,
+) -> Vec<Direction> {
+    let directions: &[Direction] = match layer {
+        TileLayer::HighMount => &DIRECTIONS,
+        _ => &[Direction::North],
+    };
+    directions
+        .iter()
+        .copied()
+        .filter(|&direction| {
+            map.validate_placement(position, layer, placement_slot(layer, direction), |turf| {
+                mount_surfaces.contains(turf)
+            })
+            .is_ok()
+        })
+        .collect()
+}
+
+fn prepare_build_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    recipes: Recipes,
+    materials: Query<(&ConstructionMaterial, Option<&Stack>)>,
+    tools: Tools,
+    tile_entities: Query<&TileEntity>,
+    tilemaps: Query<&TileMap>,
+    mount_surfaces: Query<(), With<MountSurface>>,
+) {
+    for event in interaction_list.events.iter() {
+        let Some(item) = event.item_in_hand else {
+            continue;
+        };
+        let Ok(tile) = tile_entities.get(event.target) else {
+            continue;
+        };
+
+        let mut options: Vec<(&ConstructionRecipe, usize, Direction)> = recipes
+            .stages_of(event.target)
+            .into_iter()
+            .filter(|(recipe, index)| *index + 1 < recipe.stages.len())
+            .map(|(recipe, index)| (recipe, index + 1, tile.direction()))
+            .collect();
+
+        // New constructions are started on floors
+        if tile.layer() == TileLayer::Turf && options.is_empty() {
+            let Ok(map) = tilemaps.get(tile.tilemap()) else {
+                continue;
+            };
+            for recipe in recipes.iter() {
+                let Some(stage) = recipe.stages.first() else {
+                    continue;
+                };
+                if stage.layer == TileLayer::Turf {
+                    continue;
+                }
+                for direction in
+                    start_directions(map, tile.position(), stage.layer, &mount_surfaces)
+                {
+                    options.push((recipe, 0, direction));
+                }
+            }
+        }
+
+        for (recipe, stage_index, direction) in options {
+            let stage = &recipe.stages[stage_index];
+            if !can_build(stage, item, materials.get(item).ok(), &tools) {
+                continue;
+            }
+
+            let text = match placement_slot(stage.layer, direction) {
+                Some(direction) if stage_index == 0 => {
+                    format!("Build {} ({:?} wall)", stage.name, direction)
+                }
+                _ => format!("Build {}", stage.name),
+            };
+            event.add_interaction(InteractionOption {
+                text,
+                interaction: Box::new(BuildInteraction {
+                    target: event.target,
+                    item,
+                    recipe: recipe.id.clone(),
+                    stage: stage_index,
+                    direction,
+                }),
+                specificity: InteractionSpecificity::Specific,
+            });
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn build_interaction(
+    mut query: Query<(&BuildInteraction, &mut ActiveInteraction)>,
+    recipes: Recipes,
+    mut materials: Query<(&ConstructionMaterial, Option<&mut Stack>)>,
+    mut tools: Tools,
+    tile_entities: Query<&TileEntity>,
+    tilemaps: Query<&TileMap>,
+    mount_surfaces: Query<(), With<MountSurface>>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (interaction, mut active) in query.iter_mut() {
+        let Some(recipe) = recipes.get(&interaction.recipe) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        let Some(stage) = recipe.stages.get(interaction.stage) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        let duration = tools.duration(interaction.item, stage.duration());
+        active.set_initial_duration(duration);
+
+        let Ok(tile) = tile_entities.get(interaction.target) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        let material = materials.get(interaction.item).ok();
+        if !can_build(stage, interaction.item, material, &tools) {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        }
+
+        if active.start_time() + duration.as_secs_f32() > time.elapsed_seconds() {
+            continue;
+        }
+
+        let position = tile.position();
+        let direction = if interaction.stage == 0 {
+            let free = tilemaps.get(tile.tilemap()).map_or(false, |map| {
+                map.validate_placement(
+                    position,
+                    stage.layer,
+                    placement_slot(stage.layer, interaction.direction),
+                    |turf| mount_surfaces.contains(turf),
+                )
+                .is_ok()
+            });
+            if !free {
+                active.status = InteractionStatus::Canceled;
+                continue;
+            }
+            interaction.direction
+        } else {
+            commands.despawn_cascade(interaction.target, ContentsPolicy::Drop);
+            // Building over the floor replaces it
+            let previous = &recipe.stages[interaction.stage - 1];
+            if stage.layer == TileLayer::Turf && previous.layer != TileLayer::Turf {
+                let turf = tilemaps
+                    .get(tile.tilemap())
+                    .ok()
+                    .and_then(|map| map.tile(position))
+                    .and_then(|t| t.turf);
+                if let Some(turf) = turf {
+                    commands.despawn_cascade(turf, ContentsPolicy::Drop);
+                }
+            }
+            tile.direction()
+        };
+
+        commands.spawn_tile_entity(
+            tile.tilemap(),
+            position,
+            stage.layer,
+            direction,
+            stage.scene.clone(),
+        );
+
+        if let (Some(cost), Ok((_, stack))) = (&stage.material, materials.get_mut(interaction.item))
+        {
+            let used_up = match stack {
+                Some(mut stack) => stack.take(cost.amount) && stack.count() == 0,
+                None => true,
+            };
+            if used_up {
+                commands.despawn_cascade(interaction.item, ContentsPolicy::Destroy);
+            }
+        } else if stage.tool.is_some() {
+            tools.use_tool(interaction.item);
+        }
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct DismantleInteraction {
+    target: Entity,
+    /// Tool used to dismantle
+    item: Entity,
+    recipe: String,
+    stage: usize,
+}
+
+impl FromWorld for DismantleInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            target: Entity::PLACEHOLDER,
+            item: Entity::PLACEHOLDER,
+            recipe: String::new(),
+            stage: 0,
+        }
+    }
+}
+
+fn prepare_dismantle_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    recipes: Recipes,
+    tools: Tools,
+) {
+    for event in interaction_list.events.iter() {
+        let Some(item) = event.item_in_hand else {
+            continue;
+        };
+
+        for (recipe, stage_index) in recipes.stages_of(event.target) {
+            let stage = &recipe.stages[stage_index];
+            let Some(tool) = stage.dismantle_tool else {
+                continue;
+            };
+            if !tools.is(item, tool) {
+                continue;
+            }
+
+            event.add_interaction(InteractionOption {
+                text: format!("Dismantle {}", stage.name),
+                interaction: Box::new(DismantleInteraction {
+                    target: event.target,
+                    item,
+                    recipe: recipe.id.clone(),
+                    stage: stage_index,
+                }),
+                specificity: InteractionSpecificity::Specific,
+            });
+            // A tile entity can't be dismantled two ways
+            break;
+        }
+    }
+}
+
+fn dismantle_interaction(
+    mut query: Query<(&DismantleInteraction, &mut ActiveInteraction)>,
+    recipes: Recipes,
+    mut tools: Tools,
+    tile_entities: Query<(&TileEntity, &GlobalTransform)>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    for (interaction, mut active) in query.iter_mut() {
+        let Some(recipe) = recipes.get(&interaction.recipe) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        let Some(stage) = recipe.stages.get(interaction.stage) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        let duration = tools.duration(interaction.item, stage.duration());
+        active.set_initial_duration(duration);
+
+        let Ok((tile, transform)) = tile_entities.get(interaction.target) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        let usable = stage
+            .dismantle_tool
+            .map_or(false, |tool| tools.is(interaction.item, tool));
+        if !usable {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        }
+
+        if active.start_time() + duration.as_secs_f32() > time.elapsed_seconds() {
+            continue;
+        }
+
+        tools.use_tool(interaction.item);
+
+        commands.despawn_cascade(interaction.target, ContentsPolicy::Drop);
+        if let Some(previous) = interaction
+            .stage
+            .checked_sub(1)
+            .and_then(|i| recipe.stages.get(i))
+        {
+            if stage.layer == TileLayer::Turf && previous.layer != TileLayer::Turf {
+                if let Some(floor) = &recipe.floor {
+                    commands.spawn_tile_entity(
+                        tile.tilemap(),
+                        tile.position(),
+                        TileLayer::Turf,
+                        Default::default(),
+                        floor.clone(),
+                    );
+                }
+            }
+            commands.spawn_tile_entity(
+                tile.tilemap(),
+                tile.position(),
+                previous.layer,
+                tile.direction(),
+                previous.scene.clone(),
+            );
+        }
+
+        if let Some(cost) = &stage.material {
+            commands.spawn((
+                NetworkSceneBundle {
+                    scene: recipes.server.load(cost.item.as_str()).into(),
+                    transform: Transform::from_translation(transform.translation() + Vec3::Y * 0.2),
+                    ..Default::default()
+                },
+                PendingStack {
+                    count: cost.amount,
+                    container: None,
+                },
+            ));
+        }
+        active.status = InteractionStatus::Completed;
+    }
+}
@@ -0,0 +1,275 @@
+//! Game actions and the inputs bound to them.
+//!
+//! Gameplay code asks for [`Action`]s instead of specific keys,
+//! so the same code works with keyboard, mouse and gamepad.
+
+use std::{collections::HashMap, fs::read_to_string};
+
+use bevy::{ecs::system::SystemParam, prelude::*, window::PrimaryWindow};
+use serde::Deserialize;
+
+use crate::ui::has_window;
+
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        let bindings = match load_bindings() {
+            Ok(bindings) => bindings,
+            Err(err) => {
+                error!("Error loading control bindings: {}", err);
+                ActionBindings::default()
+            }
+        };
+
+        app.insert_resource(bindings)
+            .add_systems(Update, move_gamepad_cursor.run_if(has_window));
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Deserialize)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    /// Run the default interaction on the target
+    Interact,
+    /// Show all interactions with the target
+    InteractionMenu,
+    SwapHands,
+    ToggleCombat,
+    Attack,
+    RotateCameraLeft,
+    RotateCameraRight,
+    ToggleEventLog,
+    /// Move to the cursor while a ghost
+    GhostJump,
+    MenuUp,
+    MenuDown,
+    MenuConfirm,
+    MenuBack,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButtonType),
+}
+
+#[derive(Resource)]
+pub struct ActionBindings {
+    actions: HashMap<Action, Vec<Binding>>,
+    /// Stick input below this is ignored
+    stick_deadzone: f32,
+    /// Pixels per second the right stick moves the cursor
+    cursor_speed: f32,
+}
+
+impl Default for ActionBindings {
+    fn default() -> Self {
+        use Binding::*;
+        let actions = [
+            (Action::MoveForward, vec![Key(KeyCode::W)]),
+            (Action::MoveBackward, vec![Key(KeyCode::S)]),
+            (Action::MoveLeft, vec![Key(KeyCode::A)]),
+            (Action::MoveRight, vec![Key(KeyCode::D)]),
+            (
+                Action::Interact,
+                vec![Mouse(MouseButton::Left), Gamepad(GamepadButtonType::South)],
+            ),
+            (
+                Action::InteractionMenu,
+                vec![Mouse(MouseButton::Right), Gamepad(GamepadButtonType::West)],
+            ),
+            (
+                Action::SwapHands,
+                vec![Key(KeyCode::X), Gamepad(GamepadButtonType::North)],
+            ),
+            (
+                Action::ToggleCombat,
+                vec![Key(KeyCode::Tab), Gamepad(GamepadButtonType::LeftTrigger2)],
+            ),
+            (
+                Action::Attack,
+                vec![
+                    Mouse(MouseButton::Left),
+                    Gamepad(GamepadButtonType::RightTrigger2),
+                ],
+            ),
+            (
+                Action::RotateCameraLeft,
+                vec![Key(KeyCode::Q), Gamepad(GamepadButtonType::LeftTrigger)],
+            ),
+            (
+                Action::RotateCameraRight,
+                vec![Key(KeyCode::E), Gamepad(GamepadButtonType::RightTrigger)],
+            ),
+            (Action::ToggleEventLog, vec![Key(KeyCode::L)]),
+            (Action::GhostJump, vec![Mouse(MouseButton::Middle)]),
+            (
+                Action::MenuUp,
+                vec![Key(KeyCode::Up), Gamepad(GamepadButtonType::DPadUp)],
+            ),
+            (
+                Action::MenuDown,
+                vec![Key(KeyCode::Down), Gamepad(GamepadButtonType::DPadDown)],
+            ),
+            (
+                Action::MenuConfirm,
+                vec![Key(KeyCode::Return), Gamepad(GamepadButtonType::South)],
+            ),
+            (Action::MenuBack, vec![Gamepad(GamepadButtonType::East)]),
+        ];
+
+        Self {
+            actions: actions.into_iter().collect(),
+            stick_deadzone: 0.15,
+            cursor_speed: 800.0,
+        }
+    }
+}
+
+impl ActionBindings {
+    fn bindings(&self, action: Action) -> &[Binding] {
+        self.actions.get(&action).map_or(&[], |b| b.as_slice())
+    }
+}
+
+/// Overrides for the default bindings, read from [`CONTROLS_FILE`]
+#[derive(Deserialize)]
+struct ControlsConfig {
+    #[serde(default)]
+    bindings: HashMap<Action, Vec<Binding>>,
+    stick_deadzone: Option<f32>,
+    cursor_speed: Option<f32>,
+}
+
+const CONTROLS_FILE: &str = "controls.toml";
+
+fn load_bindings() -> Result<ActionBindings, toml::de::Error> {
+    let mut bindings = ActionBindings::default();
+    let Ok(text) = read_to_string(CONTROLS_FILE) else {
+        return Ok(bindings);
+    };
+
+    let config: ControlsConfig = toml::from_str(&text)?;
+    bindings.actions.extend(config.bindings);
+    if let Some(deadzone) = config.stick_deadzone {
+        bindings.stick_deadzone = deadzone.clamp(0.0, 0.9);
+    }
+    if let Some(speed) = config.cursor_speed {
+        bindings.cursor_speed = speed.max(0.0);
+    }
+    Ok(bindings)
+}
+
+/// Reads the state of actions from all input devices.
+#[derive(SystemParam)]
+pub struct Actions<'w> {
+    bindings: Res<'w, ActionBindings>,
+    keys: Res<'w, Input<KeyCode>>,
+    mouse: Res<'w, Input<MouseButton>>,
+    gamepad_buttons: Res<'w, Input<GamepadButton>>,
+    gamepad_axes: Res<'w, Axis<GamepadAxis>>,
+    gamepads: Res<'w, Gamepads>,
+}
+
+impl<'w> Actions<'w> {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.any_binding(action, |binding| match binding {
+            Binding::Key(key) => self.keys.pressed(key),
+            Binding::Mouse(button) => self.mouse.pressed(button),
+            Binding::Gamepad(button) => self.gamepads.iter().any(|gamepad| {
+                self.gamepad_buttons
+                    .pressed(GamepadButton::new(gamepad, button))
+            }),
+        })
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.any_binding(action, |binding| match binding {
+            Binding::Key(key) => self.keys.just_pressed(key),
+            Binding::Mouse(button) => self.mouse.just_pressed(button),
+            Binding::Gamepad(button) => self.gamepads.iter().any(|gamepad| {
+                self.gamepad_buttons
+                    .just_pressed(GamepadButton::new(gamepad, button))
+            }),
+        })
+    }
+
+    fn any_binding(&self, action: Action, mut f: impl FnMut(Binding) -> bool) -> bool {
+        self.bindings
+            .bindings(action)
+            .iter()
+            .any(|&binding| f(binding))
+    }
+
+    /// Direction the player wants to move in, with x to the right and y forward.
+    /// The left stick allows moving slower than full speed.
+    pub fn movement(&self) -> Vec2 {
+        let digital = Vec2::new(
+            self.axis(Action::MoveRight, Action::MoveLeft),
+            self.axis(Action::MoveForward, Action::MoveBackward),
+        );
+        let stick = self.stick(GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY);
+        (digital + stick).clamp_length_max(1.0)
+    }
+
+    /// Right stick input, used to move the cursor
+    pub fn aim(&self) -> Vec2 {
+        self.stick(GamepadAxisType::RightStickX, GamepadAxisType::RightStickY)
+    }
+
+    fn axis(&self, plus: Action, minus: Action) -> f32 {
+        let mut axis = 0.0;
+        if self.pressed(plus) {
+            axis += 1.0;
+        }
+        if self.pressed(minus) {
+            axis -= 1.0;
+        }
+        axis
+    }
+
+    fn stick(&self, x: GamepadAxisType, y: GamepadAxisType) -> Vec2 {
+        let Some(gamepad) = self.gamepads.iter().next() else {
+            return Vec2::ZERO;
+        };
+        let value = Vec2::new(
+            self.gamepad_axes
+                .get(GamepadAxis::new(gamepad, x))
+                .unwrap_or_default(),
+            self.gamepad_axes
+                .get(GamepadAxis::new(gamepad, y))
+                .unwrap_or_default(),
+        );
+        if value.length() < self.bindings.stick_deadzone {
+            Vec2::ZERO
+        } else {
+            value
+        }
+    }
+}
+
+/// Moves the mouse cursor with the right stick, so cursor targeting works on a gamepad
+fn move_gamepad_cursor(
+    actions: Actions,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    time: Res<Time>,
+) {
+    let aim = actions.aim();
+    if aim == Vec2::ZERO {
+        return;
+    }
+    let Ok(mut window) = windows.get_single_mut() else {
+        return;
+    };
+
+    let size = Vec2::new(window.width(), window.height());
+    let current = window.cursor_position().unwrap_or(size / 2.0);
+    // Window coordinates go down, the stick goes up
+    let offset = Vec2::new(aim.x, -aim.y) * actions.bindings.cursor_speed * time.delta_seconds();
+    window.set_cursor_position(Some((current + offset).clamp(Vec2::ZERO, size)));
+}
@@ -1,15 +1,35 @@
-use bevy::prelude::*;
-use bevy_egui::{egui, EguiContexts};
-use bevy_inspector_egui::quick::WorldInspectorPlugin;
+use bevy::{prelude::*, utils::get_short_name, window::PrimaryWindow};
+use bevy_egui::{egui, EguiContext, EguiContexts};
+use bevy_inspector_egui::{bevy_inspector, quick::WorldInspectorPlugin};
 use bevy_rapier3d::render::DebugRenderContext;
+use networking::{
+    component::LastComponentUpdate,
+    identity::NetworkIdentity,
+    messaging::MessageTypes,
+    scene::NetworkScene,
+    stats::{NetworkStats, STATS_INTERVAL_SECONDS},
+    transform::NetworkedTransform,
+};
 
-use crate::{ui::has_window, GameState};
+use crate::{camera::MainCamera, ui::has_window, GameState};
 
 pub(crate) struct DebugPlugin;
 
 #[derive(Resource, Default)]
-struct DebugState {
-    inspector_enabled: bool,
+pub(crate) struct DebugState {
+    pub(crate) inspector_enabled: bool,
+    /// Entity shown in its own inspector window
+    pub(crate) inspected_entity: Option<Entity>,
+    /// Overlay of the network visibility grid, needs admin rights on the server
+    pub(crate) network_visibility: bool,
+    /// Window with the chat of every channel, needs a staff role on the server
+    pub(crate) chat_monitor: bool,
+    /// Window with the server's bandwidth usage per feature, needs admin rights on the server
+    pub(crate) bandwidth_report: bool,
+    /// Overlay with traffic per channel and message type, and the connection quality
+    pub(crate) network_stats: bool,
+    /// Labels on networked entities with their identity, scene and last update
+    pub(crate) network_identities: bool,
 }
 
 impl Plugin for DebugPlugin {
@@ -22,7 +42,14 @@ impl Plugin for DebugPlugin {
             ))
             .add_systems(
                 Update,
-                (debug_menu, debug_watermark)
+                (
+                    debug_menu,
+                    debug_watermark,
+                    entity_inspector,
+                    network_stats_overlay.run_if(|state: Res<DebugState>| state.network_stats),
+                    network_identity_overlay
+                        .run_if(|state: Res<DebugState>| state.network_identities),
+                )
                     .run_if(has_window)
                     .run_if(in_state(GameState::Game)),
             );
@@ -37,6 +64,11 @@ fn debug_menu(
     egui::Window::new("Debug Menu").show(contexts.ctx_mut(), |ui| {
         ui.checkbox(&mut state.inspector_enabled, "World inspector");
         ui.checkbox(&mut rapier_debug.enabled, "Show physics objects");
+        ui.checkbox(&mut state.network_visibility, "Show network visibility");
+        ui.checkbox(&mut state.chat_monitor, "Chat monitor");
+        ui.checkbox(&mut state.bandwidth_report, "Bandwidth usage");
+        ui.checkbox(&mut state.network_stats, "Network statistics");
+        ui.checkbox(&mut state.network_identities, "Show network identities");
     });
 }
 
@@ -52,3 +84,186 @@ fn debug_watermark(mut contexts: EguiContexts) {
             );
         });
 }
+
+fn entity_inspector(world: &mut World) {
+    let Some(entity) = world.resource::<DebugState>().inspected_entity else {
+        return;
+    };
+    if world.get_entity(entity).is_none() {
+        world.resource_mut::<DebugState>().inspected_entity = None;
+        return;
+    }
+
+    let Ok(context) = world
+        .query_filtered::<&mut EguiContext, With<PrimaryWindow>>()
+        .get_single(world)
+    else {
+        return;
+    };
+    let mut context = context.clone();
+
+    let mut open = true;
+    egui::Window::new("Entity Inspector")
+        .open(&mut open)
+        .show(context.get_mut(), |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                bevy_inspector::ui_for_entity(world, entity, ui);
+            });
+        });
+    if !open {
+        world.resource_mut::<DebugState>().inspected_entity = None;
+    }
+}
+
+/// Message types shown in the network statistics, ordered by bytes
+const SHOWN_MESSAGE_TYPES: usize = 10;
+
+fn network_stats_overlay(
+    mut contexts: EguiContexts,
+    mut state: ResMut<DebugState>,
+    stats: Res<NetworkStats>,
+    types: Res<MessageTypes>,
+) {
+    let kib_per_second = |bytes: u64| bytes as f32 / 1024.0 / STATS_INTERVAL_SECONDS;
+
+    egui::Window::new("Network statistics")
+        .open(&mut state.network_stats)
+        .show(contexts.ctx_mut(), |ui| {
+            match stats.server() {
+                Some(quality) => {
+                    ui.label(format!(
+                        "RTT {:.0} ms, packet loss {:.1}%",
+                        quality.rtt,
+                        quality.packet_loss * 100.0
+                    ));
+                }
+                None => {
+                    ui.label("Not connected");
+                }
+            }
+
+            ui.heading("Channels");
+            egui::Grid::new("network channels")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("");
+                    ui.label("Up KiB/s");
+                    ui.label("Down KiB/s");
+                    ui.label("Up msg/s");
+                    ui.label("Down msg/s");
+                    ui.end_row();
+
+                    for (name, traffic) in stats.channels() {
+                        ui.label(name);
+                        ui.label(format!("{:.2}", kib_per_second(traffic.bytes_sent)));
+                        ui.label(format!("{:.2}", kib_per_second(traffic.bytes_received)));
+                        ui.label(traffic.messages_sent.to_string());
+                        ui.label(traffic.messages_received.to_string());
+                        ui.end_row();
+                    }
+                });
+
+            ui.heading("Message types");
+            let mut message_types: Vec<_> = stats.message_types().collect();
+            message_types.sort_by_key(|(_, traffic)| {
+                std::cmp::Reverse(traffic.bytes_sent + traffic.bytes_received)
+            });
+            egui::Grid::new("network message types")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("");
+                    ui.label("Up KiB/s");
+                    ui.label("Down KiB/s");
+                    ui.label("Count");
+                    ui.end_row();
+
+                    for (id, traffic) in message_types.into_iter().take(SHOWN_MESSAGE_TYPES) {
+                        let name = types
+                            .name(id)
+                            .map_or_else(|| format!("#{}", id), get_short_name);
+                        ui.label(name);
+                        ui.label(format!("{:.2}", kib_per_second(traffic.bytes_sent)));
+                        ui.label(format!("{:.2}", kib_per_second(traffic.bytes_received)));
+                        ui.label((traffic.messages_sent + traffic.messages_received).to_string());
+                        ui.end_row();
+                    }
+                });
+        });
+}
+
+/// Meters around the camera in which networked entities get labels
+const IDENTITY_LABEL_RANGE: f32 = 12.0;
+
+/// Labels networked entities so desync reports can name the exact entity.
+/// Clicking a label copies a report about the entity.
+#[allow(clippy::type_complexity)]
+fn network_identity_overlay(
+    mut contexts: EguiContexts,
+    entities: Query<(
+        Entity,
+        &NetworkIdentity,
+        &GlobalTransform,
+        Option<&Name>,
+        Option<&NetworkScene>,
+        Option<&NetworkedTransform>,
+        Option<&LastComponentUpdate>,
+    )>,
+    cameras: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    asset_server: Res<AssetServer>,
+) {
+    let Some((camera, camera_transform)) = cameras.iter().next() else {
+        return;
+    };
+    let ctx = contexts.ctx_mut();
+
+    for (entity, identity, transform, name, scene, networked_transform, last_update) in
+        entities.iter()
+    {
+        let position = transform.translation();
+        if position.distance(camera_transform.translation()) > IDENTITY_LABEL_RANGE {
+            continue;
+        }
+        let Some(screen) = camera.world_to_viewport(camera_transform, position + Vec3::Y * 0.5)
+        else {
+            continue;
+        };
+
+        let scene = scene
+            .and_then(|scene| asset_server.get_handle_path(scene.handle()))
+            .map(|path| path.path().to_string_lossy().replace('\\', "/"));
+        let transform_tick = networked_transform.and_then(|t| t.last_snapshot_tick());
+        let component_tick = last_update.map(|update| update.tick);
+        let tick = |tick: Option<u32>| tick.map_or_else(|| "-".to_owned(), |t| t.to_string());
+
+        let label = format!(
+            "{:?}\n{}\ntransform {} / components {}",
+            identity,
+            scene.as_deref().unwrap_or("no scene"),
+            tick(transform_tick),
+            tick(component_tick)
+        );
+        egui::Area::new(("network identity", entity))
+            .fixed_pos(egui::pos2(screen.x, screen.y))
+            .order(egui::Order::Background)
+            .show(ctx, |ui| {
+                let response = ui
+                    .add(
+                        egui::Label::new(egui::RichText::new(label).small().monospace())
+                            .sense(egui::Sense::click()),
+                    )
+                    .on_hover_text("Click to copy a report");
+                if response.clicked() {
+                    let report = format!(
+                        "Entity {:?} ({})\nScene: {}\nPosition: {:.2}\nLast transform update: tick {}\nLast component update: tick {}",
+                        identity,
+                        name.map_or("unnamed", |name| name.as_str()),
+                        scene.as_deref().unwrap_or("none"),
+                        position,
+                        tick(transform_tick),
+                        tick(component_tick)
+                    );
+                    ui.output_mut(|output| output.copied_text = report);
+                }
+            });
+    }
+}
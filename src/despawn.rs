@@ -0,0 +1,306 @@
+use bevy::{
+    ecs::system::{Command, SystemState},
+    prelude::*,
+};
+use networking::spawning::release_network_identity;
+
+use crate::items::containers::{self, Container};
+
+/// What happens to items stored in containers of a despawned entity
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ContentsPolicy {
+    /// The items are despawned as well
+    Destroy,
+    /// The items are left in the world where they are
+    Drop,
+}
+
+/// Despawns an entity with its children after removing all references to it.
+///
+/// The entity is taken out of any container and tilemap it is part of.
+/// Items in its containers are handled according to the [`ContentsPolicy`],
+/// even if the container attaches them to another entity.
+/// Network identities of the entity and its descendants are released right away.
+pub struct DespawnCommand {
+    pub entity: Entity,
+    pub contents: ContentsPolicy,
+}
+
+impl Command for DespawnCommand {
+    fn apply(self, world: &mut World) {
+        if world.get_entity(self.entity).is_none() {
+            return;
+        }
+
+        let stored_items: Vec<Entity> = hierarchy(world, self.entity)
+            .into_iter()
+            .filter_map(|entity| world.get::<Container>(entity))
+            .flat_map(|container| container.iter().map(|(_, &item)| item))
+            .collect();
+        for item in stored_items {
+            match self.contents {
+                ContentsPolicy::Destroy => DespawnCommand {
+                    entity: item,
+                    contents: ContentsPolicy::Destroy,
+                }
+                .apply(world),
+                ContentsPolicy::Drop => containers::drop_item(world, item),
+            }
+        }
+
+        containers::unlink_item(world, self.entity);
+        for entity in hierarchy(world, self.entity) {
+            maps::unlink_tile_entity(world, entity);
+            release_network_identity(world, entity);
+        }
+
+        world.entity_mut(self.entity).despawn_recursive();
+    }
+}
+
+/// The entity and all of its descendants
+fn hierarchy(world: &mut World, root: Entity) -> Vec<Entity> {
+    let mut state: SystemState<Query<&Children>> = SystemState::new(world);
+    let children = state.get(world);
+    std::iter::once(root)
+        .chain(children.iter_descendants(root))
+        .collect()
+}
+
+pub trait DespawnCommandsExt {
+    /// Queues a [`DespawnCommand`] for the entity
+    fn despawn_cascade(&mut self, entity: Entity, contents: ContentsPolicy);
+}
+
+impl<'w, 's> DespawnCommandsExt for Commands<'w, 's> {
+    fn despawn_cascade(&mut self, entity: Entity, contents: ContentsPolicy) {
+        self.add(DespawnCommand { entity, contents });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::Command, prelude::*};
+    use maps::{testing, TileMap};
+    use networking::{
+        identity::{NetworkCommand, NetworkIdentities, NetworkIdentity},
+        NetworkManager, NetworkRole,
+    };
+
+    use super::{ContentsPolicy, DespawnCommand};
+    use crate::{
+        body::Body,
+        items::{containers::Container, StoredItem},
+    };
+
+    fn despawn(world: &mut World, entity: Entity, contents: ContentsPolicy) {
+        DespawnCommand { entity, contents }.apply(world);
+    }
+
+    fn spawn_container(world: &mut World, attach_to: Option<Entity>) -> Entity {
+        let mut container = Container::from_world(world);
+        container.attach_to = attach_to;
+        world.spawn((container, GlobalTransform::default())).id()
+    }
+
+    /// Stores an item the same way moving it into the container does
+    fn store(world: &mut World, container: Entity, item: Entity) {
+        let mut data = world.get_mut::<Container>(container).unwrap();
+        let slot = UVec2::new(data.iter().count() as u32, 0);
+        data.insert_item_unchecked(item, slot);
+        let parent = data.attach_to.unwrap_or(container);
+        world
+            .entity_mut(item)
+            .insert(StoredItem::new(container, slot, false))
+            .set_parent(parent);
+    }
+
+    fn server_world() -> World {
+        let mut world = World::new();
+        world.insert_resource(NetworkManager {
+            role: NetworkRole::Server,
+        });
+        world.init_resource::<NetworkIdentities>();
+        world
+    }
+
+    fn networked(world: &mut World, entity: Entity) -> NetworkIdentity {
+        NetworkCommand { entity }.apply(world);
+        *world.get::<NetworkIdentity>(entity).unwrap()
+    }
+
+    #[test]
+    fn children_are_despawned() {
+        let mut world = World::new();
+        let child = world.spawn_empty().id();
+        let parent = world.spawn_empty().add_child(child).id();
+
+        despawn(&mut world, parent, ContentsPolicy::Drop);
+
+        assert!(world.get_entity(parent).is_none());
+        assert!(world.get_entity(child).is_none());
+    }
+
+    #[test]
+    fn missing_entity_is_ignored() {
+        let mut world = World::new();
+        let entity = world.spawn_empty().id();
+        world.despawn(entity);
+
+        despawn(&mut world, entity, ContentsPolicy::Destroy);
+    }
+
+    #[test]
+    fn stored_item_leaves_container() {
+        let mut world = World::new();
+        let container = spawn_container(&mut world, None);
+        let item = world.spawn_empty().id();
+        store(&mut world, container, item);
+
+        despawn(&mut world, item, ContentsPolicy::Destroy);
+
+        assert!(world.get_entity(item).is_none());
+        assert!(world.get::<Container>(container).unwrap().is_empty());
+        assert!(world.get::<Children>(container).is_none());
+    }
+
+    #[test]
+    fn dropped_contents_stay_where_they_were() {
+        let mut world = World::new();
+        let container = spawn_container(&mut world, None);
+        let position = Vec3::new(1.0, 2.0, 3.0);
+        let item = world
+            .spawn((
+                Transform::default(),
+                GlobalTransform::from_translation(position),
+            ))
+            .id();
+        store(&mut world, container, item);
+
+        despawn(&mut world, container, ContentsPolicy::Drop);
+
+        assert!(world.get_entity(container).is_none());
+        let item = world.entity(item);
+        assert!(!item.contains::<StoredItem>());
+        assert!(!item.contains::<Parent>());
+        assert_eq!(item.get::<Transform>().unwrap().translation, position);
+    }
+
+    #[test]
+    fn destroyed_contents_are_found_outside_the_hierarchy() {
+        let mut world = World::new();
+        let holder = world.spawn_empty().id();
+        let container = spawn_container(&mut world, Some(holder));
+        let item = world.spawn_empty().id();
+        store(&mut world, container, item);
+
+        despawn(&mut world, container, ContentsPolicy::Destroy);
+
+        assert!(world.get_entity(item).is_none());
+        assert!(world.get_entity(holder).is_some());
+    }
+
+    #[test]
+    fn nested_containers_are_destroyed() {
+        let mut world = World::new();
+        let locker = spawn_container(&mut world, None);
+        let bag = spawn_container(&mut world, None);
+        let item = world.spawn_empty().id();
+        store(&mut world, locker, bag);
+        store(&mut world, bag, item);
+
+        despawn(&mut world, locker, ContentsPolicy::Destroy);
+
+        assert!(world.get_entity(bag).is_none());
+        assert!(world.get_entity(item).is_none());
+    }
+
+    #[test]
+    fn dropped_containers_keep_their_contents() {
+        let mut world = World::new();
+        let locker = spawn_container(&mut world, None);
+        let bag = spawn_container(&mut world, None);
+        let item = world.spawn_empty().id();
+        store(&mut world, locker, bag);
+        store(&mut world, bag, item);
+
+        despawn(&mut world, locker, ContentsPolicy::Drop);
+
+        assert!(!world.entity(bag).contains::<StoredItem>());
+        assert_eq!(world.get::<Parent>(item).unwrap().get(), bag);
+        assert!(world.entity(item).contains::<StoredItem>());
+    }
+
+    #[test]
+    fn tile_entity_leaves_tilemap() {
+        let mut world = World::new();
+        let tilemap = testing::spawn_tilemap(&mut world, UVec2::ONE);
+        let position = UVec2::new(2, 3);
+        let furniture = testing::spawn_furniture(&mut world, tilemap, position);
+
+        despawn(&mut world, furniture, ContentsPolicy::Drop);
+
+        assert!(world.get_entity(furniture).is_none());
+        let map = world.get::<TileMap>(tilemap).unwrap();
+        assert_eq!(map.tile(position).unwrap().furniture, None);
+    }
+
+    #[test]
+    fn tile_entity_contents_are_dropped() {
+        let mut world = World::new();
+        let tilemap = testing::spawn_tilemap(&mut world, UVec2::ONE);
+        let locker = testing::spawn_furniture(&mut world, tilemap, UVec2::ZERO);
+        let container = Container::from_world(&mut world);
+        world
+            .entity_mut(locker)
+            .insert((container, GlobalTransform::default()));
+        let item = world.spawn(GlobalTransform::default()).id();
+        store(&mut world, locker, item);
+
+        despawn(&mut world, locker, ContentsPolicy::Drop);
+
+        assert!(world.get_entity(item).is_some());
+        assert!(!world.entity(item).contains::<Parent>());
+    }
+
+    #[test]
+    fn network_identities_are_released() {
+        let mut world = server_world();
+        let child = world.spawn_empty().id();
+        let parent = world.spawn_empty().add_child(child).id();
+        let parent_identity = networked(&mut world, parent);
+        let child_identity = networked(&mut world, child);
+
+        despawn(&mut world, parent, ContentsPolicy::Destroy);
+
+        let identities = world.resource::<NetworkIdentities>();
+        assert_eq!(identities.get_entity(parent_identity), None);
+        assert_eq!(identities.get_entity(child_identity), None);
+    }
+
+    #[test]
+    fn creature_drops_held_items() {
+        let mut world = server_world();
+        let creature = world
+            .spawn((Body::default(), GlobalTransform::default()))
+            .id();
+        let hand = spawn_container(&mut world, None);
+        world.entity_mut(creature).add_child(hand);
+        let item = world
+            .spawn((Transform::default(), GlobalTransform::default()))
+            .id();
+        store(&mut world, hand, item);
+        let creature_identity = networked(&mut world, creature);
+        let item_identity = networked(&mut world, item);
+
+        despawn(&mut world, creature, ContentsPolicy::Drop);
+
+        assert!(world.get_entity(creature).is_none());
+        assert!(world.get_entity(hand).is_none());
+        assert!(!world.entity(item).contains::<StoredItem>());
+        assert!(!world.entity(item).contains::<Parent>());
+        let identities = world.resource::<NetworkIdentities>();
+        assert_eq!(identities.get_entity(creature_identity), None);
+        assert_eq!(identities.get_entity(item_identity), Some(item));
+    }
+}
@@ -0,0 +1,464 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, reflect::TypeUuid, utils::HashMap};
+use bevy_rapier3d::prelude::{Collider, CollisionGroups, QueryFilter, RapierContext};
+use maps::io::TileStateAppExt;
+use networking::{
+    component::AppExt,
+    is_server,
+    variable::{NetworkVar, ServerVar},
+    Networked,
+};
+use physics::PhysicsEntityCommands;
+use power::Powered;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    access::AccessCheck,
+    audio::PlaySound,
+    body::Body,
+    character_sheet::CharacterSheets,
+    combat::{
+        damage::{Damage, DamageEvent, KineticDamage, KineticShape},
+        nonlethal::Subdued,
+    },
+    interaction::{
+        ActiveInteraction, GenerateInteractionList, InteractionListEvents, InteractionOption,
+        InteractionSpecificity, InteractionStatus,
+    },
+    items::tools::{ToolKind, Tools},
+};
+
+pub struct DoorPlugin;
+
+impl Plugin for DoorPlugin {
+    fn build(&self, app: &mut App) {
+        app.save_tile_state::<Door>()
+            .register_type::<DoorPanel>()
+            .register_type::<DoorInteraction>()
+            .register_type::<DoorWireInteraction>()
+            .add_networked_component::<Door, DoorClient>();
+        if is_server(app) {
+            app.add_systems(
+                Update,
+                (
+                    (prepare_door_interaction, prepare_door_wire_interaction)
+                        .in_set(GenerateInteractionList),
+                    execute_door_interaction,
+                    execute_door_wire_interaction,
+                    (
+                        update_doors,
+                        update_door_collision,
+                        door_sounds
+                            .after(execute_door_interaction)
+                            .after(execute_door_wire_interaction),
+                    )
+                        .chain(),
+                ),
+            );
+        } else {
+            app.add_systems(Update, animate_door_panels);
+        }
+    }
+}
+
+const USE_DOOR_TIME: Duration = Duration::from_millis(300);
+/// Seconds it takes a door to open or close
+const MOVE_TIME: f32 = 0.8;
+/// How far the door panel sinks into the floor when fully open
+const PANEL_TRAVEL: f32 = 1.9;
+const CUT_WIRE_TIME: Duration = Duration::from_secs(3);
+/// Seconds between crushes while a door without safety is blocked
+const CRUSH_INTERVAL: f32 = 2.0;
+/// How long a crushed creature stays pinned, longer than the interval so it can't slip away
+const PIN_TIME: Duration = Duration::from_millis(2500);
+const PINNED_STATUS: &str = "Pinned";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum DoorState {
+    #[default]
+    Closed,
+    Opening,
+    Open,
+    Closing,
+}
+
+/// A door that can be opened and closed.
+/// Add an [`crate::access::AccessRequirement`] to restrict who can use it.
+#[derive(Component, Reflect, Networked)]
+#[reflect(Component)]
+#[networked(client = "DoorClient")]
+pub struct Door {
+    #[reflect(ignore)]
+    state: NetworkVar<DoorState>,
+    /// Seconds after which an open door closes by itself. Stays open if `None`.
+    auto_close: Option<f32>,
+    /// When the door last changed state, in seconds since startup
+    last_change: f32,
+    /// Reopens instead of closing on creatures in the doorway.
+    /// Cutting the safety wire makes the door crush them instead.
+    safety: bool,
+    /// When a blocked door tries to close again, in seconds since startup
+    next_close_attempt: f32,
+}
+
+impl Default for Door {
+    fn default() -> Self {
+        Self {
+            state: Default::default(),
+            auto_close: Some(5.0),
+            last_change: 0.0,
+            safety: true,
+            next_close_attempt: 0.0,
+        }
+    }
+}
+
+impl Door {
+    pub fn state(&self) -> DoorState {
+        *self.state
+    }
+
+    pub fn is_open(&self) -> bool {
+        matches!(*self.state, DoorState::Open | DoorState::Opening)
+    }
+
+    fn set_state(&mut self, state: DoorState, now: f32) {
+        if *self.state != state {
+            *self.state = state;
+            self.last_change = now;
+        }
+    }
+}
+
+#[derive(Component, Default, TypeUuid, Networked)]
+#[uuid = "2d8f5c0e-4a7b-4e91-b3c6-7f1e2a9d0b54"]
+#[networked(server = "Door")]
+pub struct DoorClient {
+    state: ServerVar<DoorState>,
+    /// How far the panel is open, from 0 (closed) to 1 (open)
+    progress: f32,
+}
+
+impl DoorClient {
+    pub fn state(&self) -> DoorState {
+        self.state.get().copied().unwrap_or_default()
+    }
+}
+
+/// The moving part of a door, slid out of the way when the door opens.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+struct DoorPanel;
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct DoorInteraction {
+    door: Entity,
+}
+
+// Dummy default for Reflect
+impl Default for DoorInteraction {
+    fn default() -> Self {
+        Self {
+            door: Entity::from_raw(0),
+        }
+    }
+}
+
+fn prepare_door_interaction(list: Res<InteractionListEvents>, doors: Query<&Door>) {
+    for event in list.events.iter() {
+        let Ok(door) = doors.get(event.target) else {
+            continue;
+        };
+
+        let text = if door.is_open() { "Close" } else { "Open" };
+        event.add_interaction(InteractionOption {
+            text: text.into(),
+            interaction: Box::new(DoorInteraction { door: event.target }),
+            specificity: InteractionSpecificity::Specific,
+        });
+    }
+}
+
+fn execute_door_interaction(
+    mut query: Query<(Entity, &DoorInteraction, &mut ActiveInteraction)>,
+    mut doors: Query<(&mut Door, Option<&Powered>)>,
+    access: AccessCheck,
+    time: Res<Time>,
+) {
+    for (user, interaction, mut active) in query.iter_mut() {
+        active.set_initial_duration(USE_DOOR_TIME);
+
+        let Ok((mut door, powered)) = doors.get_mut(interaction.door) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+
+        if active.start_time() + USE_DOOR_TIME.as_secs_f32() > time.elapsed_seconds() {
+            continue;
+        }
+
+        // Unpowered doors can't be moved
+        if !powered.map(|p| p.is_powered()).unwrap_or(true) {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        }
+
+        if !access.is_allowed(user, interaction.door) {
+            debug!(door = ?interaction.door, "Door access denied");
+            active.status = InteractionStatus::Canceled;
+            continue;
+        }
+
+        let now = time.elapsed_seconds();
+        let new_state = if door.is_open() {
+            DoorState::Closing
+        } else {
+            DoorState::Opening
+        };
+        door.set_state(new_state, now);
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct DoorWireInteraction {
+    door: Entity,
+}
+
+impl FromWorld for DoorWireInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            door: Entity::PLACEHOLDER,
+        }
+    }
+}
+
+fn prepare_door_wire_interaction(
+    list: Res<InteractionListEvents>,
+    doors: Query<&Door>,
+    tools: Tools,
+) {
+    for event in list.events.iter() {
+        let Ok(door) = doors.get(event.target) else {
+            continue;
+        };
+        if !event
+            .item_in_hand
+            .map_or(false, |i| tools.is(i, ToolKind::Wirecutters))
+        {
+            continue;
+        }
+
+        let text = if door.safety {
+            "Cut safety wire"
+        } else {
+            "Mend safety wire"
+        };
+        event.add_interaction(InteractionOption {
+            text: text.into(),
+            interaction: Box::new(DoorWireInteraction { door: event.target }),
+            specificity: InteractionSpecificity::Specific,
+        });
+    }
+}
+
+fn execute_door_wire_interaction(
+    mut query: Query<(Entity, &DoorWireInteraction, &mut ActiveInteraction)>,
+    mut doors: Query<&mut Door>,
+    tools: Tools,
+    time: Res<Time>,
+) {
+    for (source, interaction, mut active) in query.iter_mut() {
+        let Some(wirecutters) = tools.held(source, ToolKind::Wirecutters) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        let duration = tools.duration(wirecutters, CUT_WIRE_TIME);
+        active.set_initial_duration(duration);
+
+        let Ok(mut door) = doors.get_mut(interaction.door) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+
+        if active.start_time() + duration.as_secs_f32() > time.elapsed_seconds() {
+            continue;
+        }
+
+        door.safety = !door.safety;
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+/// Finishes door movement and closes doors that were left open
+#[allow(clippy::too_many_arguments)]
+fn update_doors(
+    mut doors: Query<(Entity, &mut Door, &GlobalTransform, Option<&Powered>)>,
+    creatures: Query<(), With<Body>>,
+    mut subdued: Query<&mut Subdued>,
+    parents: Query<&Parent>,
+    mut sheets: CharacterSheets,
+    rapier: Res<RapierContext>,
+    time: Res<Time>,
+    mut damage: EventWriter<DamageEvent>,
+) {
+    let now = time.elapsed_seconds();
+    for (entity, mut door, transform, powered) in doors.iter_mut() {
+        let elapsed = now - door.last_change;
+        match door.state() {
+            DoorState::Opening if elapsed >= MOVE_TIME => {
+                door.set_state(DoorState::Open, now);
+            }
+            DoorState::Closing if elapsed >= MOVE_TIME && now >= door.next_close_attempt => {
+                let blocking = doorway_obstructions(
+                    entity,
+                    transform.translation(),
+                    &rapier,
+                    &parents,
+                    &creatures,
+                );
+                if blocking.is_empty() {
+                    door.set_state(DoorState::Closed, now);
+                    continue;
+                }
+
+                if door.safety {
+                    door.set_state(DoorState::Opening, now);
+                    continue;
+                }
+
+                // Without safety the door slams into whoever is in the way and keeps pushing
+                door.next_close_attempt = now + CRUSH_INTERVAL;
+                for (creature, parts) in blocking {
+                    if let Some(&part) = parts.first() {
+                        damage.send(DamageEvent::new(
+                            part,
+                            Damage::Kinetic(KineticDamage {
+                                mass: 200.0,
+                                velocity: 1.5,
+                                shape: KineticShape::Blunt,
+                            }),
+                        ));
+                    }
+                    if let Ok(mut subdued) = subdued.get_mut(creature) {
+                        subdued.stun(now + PIN_TIME.as_secs_f32());
+                    }
+                    if let Some(mut sheet) = sheets.get_mut(creature) {
+                        sheet.set_status(PINNED_STATUS, Some(PIN_TIME), now);
+                    }
+                }
+            }
+            DoorState::Open => {
+                let Some(auto_close) = door.auto_close else {
+                    continue;
+                };
+                if elapsed >= auto_close && powered.map(|p| p.is_powered()).unwrap_or(true) {
+                    door.set_state(DoorState::Closing, now);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Plays a sound when doors start moving
+fn door_sounds(
+    doors: Query<(&Door, &GlobalTransform), Changed<Door>>,
+    time: Res<Time>,
+    mut sounds: EventWriter<PlaySound>,
+) {
+    let now = time.elapsed_seconds();
+    for (door, transform) in doors.iter() {
+        if door.last_change != now {
+            continue;
+        }
+        let asset = match door.state() {
+            DoorState::Opening => "sounds/doors/open.ogg",
+            DoorState::Closing => "sounds/doors/close.ogg",
+            _ => continue,
+        };
+        sounds.send(PlaySound::new(asset, transform.translation()));
+    }
+}
+
+/// Finds creatures standing in a doorway, along with their body parts inside it
+fn doorway_obstructions(
+    door: Entity,
+    position: Vec3,
+    rapier: &RapierContext,
+    parents: &Query<&Parent>,
+    creatures: &Query<(), With<Body>>,
+) -> HashMap<Entity, Vec<Entity>> {
+    let mut blocking: HashMap<Entity, Vec<Entity>> = HashMap::default();
+    let shape = Collider::cuboid(0.45, 0.9, 0.45);
+    let filter = QueryFilter::new()
+        .groups(CollisionGroups::new(
+            physics::RAYCASTING_GROUP,
+            physics::DEFAULT_GROUP | physics::LIMB_GROUP,
+        ))
+        .predicate(&|entity| entity != door && !parents.iter_ancestors(entity).any(|e| e == door));
+    rapier.intersections_with_shape(
+        position + Vec3::Y * 0.9,
+        Quat::IDENTITY,
+        &shape,
+        filter,
+        |entity| {
+            let creature = std::iter::once(entity)
+                .chain(parents.iter_ancestors(entity))
+                .find(|&e| creatures.contains(e));
+            if let Some(creature) = creature {
+                let parts = blocking.entry(creature).or_default();
+                if entity != creature {
+                    parts.push(entity);
+                }
+            }
+            true
+        },
+    );
+    blocking
+}
+
+/// Lets creatures pass through doors once they are fully open
+fn update_door_collision(doors: Query<(Entity, &Door), Changed<Door>>, mut commands: Commands) {
+    for (entity, door) in doors.iter() {
+        match door.state() {
+            DoorState::Open => {
+                commands.entity(entity).disable_physics();
+            }
+            // Stays passable while closing, so the door can check what's in the way
+            DoorState::Closed => {
+                commands.entity(entity).enable_physics();
+            }
+            DoorState::Opening | DoorState::Closing => {}
+        }
+    }
+}
+
+fn animate_door_panels(
+    mut doors: Query<(&mut DoorClient, &Children)>,
+    mut panels: Query<&mut Transform, With<DoorPanel>>,
+    time: Res<Time>,
+) {
+    for (mut door, children) in doors.iter_mut() {
+        let target = match door.state() {
+            DoorState::Opening | DoorState::Open => 1.0,
+            DoorState::Closing | DoorState::Closed => 0.0,
+        };
+        if door.progress == target {
+            continue;
+        }
+
+        let step = time.delta_seconds() / MOVE_TIME;
+        door.progress += (target - door.progress).clamp(-step, step);
+
+        let mut iter = panels.iter_many_mut(children);
+        while let Some(mut transform) = iter.fetch_next() {
+            transform.translation.y = -door.progress * PANEL_TRAVEL;
+        }
+    }
+}
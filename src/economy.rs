@@ -0,0 +1,827 @@
+use std::{fmt::Display, time::Duration};
+
+use bevy::{
+    prelude::*,
+    time::common_conditions::on_timer,
+    utils::{HashMap, Uuid},
+};
+use bevy_egui::{egui, EguiContexts};
+use networking::{
+    is_server,
+    messaging::{AppExt, MessageEvent, MessageReceivers, MessageSender},
+    scene::NetworkSceneBundle,
+    spawning::ClientControls,
+    ConnectionId, Players, ServerEvent,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    access::{AccessCheck, IdCard},
+    interaction::{
+        ActiveInteraction, GenerateInteractionList, InteractionListEvents, InteractionOption,
+        InteractionSpecificity, InteractionStatus,
+    },
+    round::RoundState,
+    ui::{
+        has_window,
+        palette::{Cue, Palette},
+    },
+};
+
+pub struct EconomyPlugin;
+
+impl Plugin for EconomyPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<VendingMachine>()
+            .register_type::<VendingProduct>()
+            .register_type::<Vec<VendingProduct>>()
+            .register_type::<BankTerminal>()
+            .add_network_message::<BankAccountMessage>()
+            .add_network_message::<TransferRequest>()
+            .add_network_message::<CloseBankAccount>();
+        if is_server(app) {
+            app.init_resource::<BankAccounts>()
+                .init_resource::<BankSessions>()
+                .register_type::<BuyInteraction>()
+                .register_type::<OpenBankTerminalInteraction>()
+                .add_systems(OnEnter(RoundState::Restarting), close_accounts)
+                .add_systems(
+                    Update,
+                    (
+                        link_id_cards,
+                        pay_salaries
+                            .run_if(on_timer(SALARY_INTERVAL))
+                            .run_if(in_state(RoundState::InProgress)),
+                        (prepare_buy_interaction, prepare_bank_terminal_interaction)
+                            .in_set(GenerateInteractionList),
+                        buy_interaction,
+                        open_bank_terminal_interaction,
+                        (end_bank_sessions, handle_transfer_request).chain(),
+                    ),
+                );
+        } else {
+            app.init_resource::<ClientBankAccount>().add_systems(
+                Update,
+                (
+                    client_receive_account,
+                    client_close_account,
+                    client_bank_ui.run_if(has_window),
+                )
+                    .chain(),
+            );
+        }
+    }
+}
+
+const SALARY_INTERVAL: Duration = Duration::from_secs(600);
+const BUY_TIME: Duration = Duration::from_secs(1);
+/// Transactions shown on a bank terminal
+const SHOWN_TRANSACTIONS: usize = 20;
+const MAX_REASON_LENGTH: usize = 64;
+/// Furthest a player can be from the bank terminal they have open
+const TERMINAL_REACH: f32 = 3.0;
+
+/// Number of a bank account
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct AccountId(pub u32);
+
+impl Display for AccountId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:06}", self.0)
+    }
+}
+
+pub struct Account {
+    pub holder: String,
+    pub balance: u32,
+    /// Credits paid every salary period
+    pub salary: u32,
+}
+
+/// A completed movement of credits.
+/// `None` stands for the station itself, which pays salaries and receives purchases.
+pub struct Transaction {
+    pub from: Option<AccountId>,
+    pub to: Option<AccountId>,
+    pub amount: u32,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionError {
+    InvalidAmount,
+    UnknownAccount,
+    SameAccount,
+    InsufficientFunds,
+}
+
+impl Display for TransactionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransactionError::InvalidAmount => write!(f, "Invalid amount"),
+            TransactionError::UnknownAccount => write!(f, "Unknown account"),
+            TransactionError::SameAccount => write!(f, "Can't transfer to the same account"),
+            TransactionError::InsufficientFunds => write!(f, "Insufficient funds"),
+        }
+    }
+}
+
+/// Bank accounts of all characters, and a log of every transaction between them
+#[derive(Resource)]
+pub struct BankAccounts {
+    accounts: HashMap<AccountId, Account>,
+    log: Vec<Transaction>,
+    next_id: u32,
+}
+
+impl Default for BankAccounts {
+    fn default() -> Self {
+        Self {
+            accounts: Default::default(),
+            log: Default::default(),
+            next_id: 100_001,
+        }
+    }
+}
+
+impl BankAccounts {
+    pub fn open(&mut self, holder: String, balance: u32, salary: u32) -> AccountId {
+        let id = AccountId(self.next_id);
+        self.next_id += 1;
+        self.accounts.insert(
+            id,
+            Account {
+                holder,
+                balance,
+                salary,
+            },
+        );
+        id
+    }
+
+    pub fn get(&self, id: AccountId) -> Option<&Account> {
+        self.accounts.get(&id)
+    }
+
+    /// Moves credits between accounts after checking the transaction is valid
+    pub fn transfer(
+        &mut self,
+        from: Option<AccountId>,
+        to: Option<AccountId>,
+        amount: u32,
+        reason: impl Into<String>,
+    ) -> Result<(), TransactionError> {
+        if amount == 0 {
+            return Err(TransactionError::InvalidAmount);
+        }
+        if from.is_some() && from == to {
+            return Err(TransactionError::SameAccount);
+        }
+        if let Some(to) = to {
+            let receiver = self
+                .accounts
+                .get(&to)
+                .ok_or(TransactionError::UnknownAccount)?;
+            if receiver.balance.checked_add(amount).is_none() {
+                return Err(TransactionError::InvalidAmount);
+            }
+        }
+        if let Some(from) = from {
+            let sender = self
+                .accounts
+                .get_mut(&from)
+                .ok_or(TransactionError::UnknownAccount)?;
+            if sender.balance < amount {
+                return Err(TransactionError::InsufficientFunds);
+            }
+            sender.balance -= amount;
+        }
+        if let Some(to) = to {
+            self.accounts.get_mut(&to).unwrap().balance += amount;
+        }
+
+        let reason = reason.into();
+        info!(?from, ?to, amount, reason = reason.as_str(), "Transaction");
+        self.log.push(Transaction {
+            from,
+            to,
+            amount,
+            reason,
+        });
+        Ok(())
+    }
+
+    /// Transactions involving an account, newest first
+    pub fn history(&self, id: AccountId) -> impl Iterator<Item = &Transaction> + '_ {
+        self.log
+            .iter()
+            .rev()
+            .filter(move |t| t.from == Some(id) || t.to == Some(id))
+    }
+}
+
+/// The bank account of a character, written onto their ID cards
+#[derive(Component)]
+pub struct AccountHolder(pub AccountId);
+
+fn link_id_cards(
+    holders: Query<(Entity, &AccountHolder), Added<AccountHolder>>,
+    child_query: Query<&Children>,
+    mut cards: Query<&mut IdCard>,
+) {
+    for (creature, holder) in holders.iter() {
+        let mut iter = cards.iter_many_mut(child_query.iter_descendants(creature));
+        while let Some(mut card) = iter.fetch_next() {
+            if card.account.is_none() {
+                card.account = Some(holder.0);
+            }
+        }
+    }
+}
+
+/// Accounts only last for one round
+fn close_accounts(mut accounts: ResMut<BankAccounts>, mut sessions: ResMut<BankSessions>) {
+    *accounts = Default::default();
+    sessions.0.clear();
+}
+
+fn pay_salaries(mut accounts: ResMut<BankAccounts>) {
+    let payroll: Vec<_> = accounts
+        .accounts
+        .iter()
+        .filter(|(_, account)| account.salary > 0)
+        .map(|(&id, account)| (id, account.salary))
+        .collect();
+    for (id, salary) in payroll {
+        if let Err(err) = accounts.transfer(None, Some(id), salary, "Salary") {
+            warn!(account = %id, error = %err, "Failed to pay salary");
+        }
+    }
+}
+
+/// The account of the first linked ID card a creature carries
+fn carried_account(access: &AccessCheck, creature: Entity) -> Option<AccountId> {
+    access.cards(creature).find_map(|card| card.account)
+}
+
+#[derive(Reflect, Default, Clone)]
+struct VendingProduct {
+    name: String,
+    /// Scene of the item handed out
+    scene: String,
+    price: u32,
+}
+
+/// A machine selling items, paid for with the account of an ID card.
+/// Also used for cargo consoles, restricted by an [`crate::access::AccessRequirement`].
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+struct VendingMachine {
+    products: Vec<VendingProduct>,
+    /// Where bought items appear, relative to the machine
+    output_offset: Vec3,
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct BuyInteraction {
+    machine: Entity,
+    product: usize,
+}
+
+impl FromWorld for BuyInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            machine: Entity::PLACEHOLDER,
+            product: 0,
+        }
+    }
+}
+
+fn prepare_buy_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    machines: Query<&VendingMachine>,
+    access: AccessCheck,
+) {
+    for event in interaction_list.events.iter() {
+        let Ok(machine) = machines.get(event.target) else {
+            continue;
+        };
+        if !access.is_allowed(event.source, event.target) {
+            continue;
+        }
+        for (index, product) in machine.products.iter().enumerate() {
+            event.add_interaction(InteractionOption {
+                text: format!("Buy {} ({} cr)", product.name, product.price),
+                interaction: Box::new(BuyInteraction {
+                    machine: event.target,
+                    product: index,
+                }),
+                specificity: InteractionSpecificity::Specific,
+            });
+        }
+    }
+}
+
+fn buy_interaction(
+    mut query: Query<(Entity, &BuyInteraction, &mut ActiveInteraction)>,
+    machines: Query<(&VendingMachine, &GlobalTransform)>,
+    access: AccessCheck,
+    mut accounts: ResMut<BankAccounts>,
+    server: Res<AssetServer>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    let now = time.elapsed_seconds();
+    for (user, interaction, mut active) in query.iter_mut() {
+        active.set_initial_duration(BUY_TIME);
+
+        let Some((product, transform)) =
+            machines
+                .get(interaction.machine)
+                .ok()
+                .and_then(|(machine, transform)| {
+                    machine
+                        .products
+                        .get(interaction.product)
+                        .map(|p| (p, transform.transform_point(machine.output_offset)))
+                })
+        else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+
+        if active.start_time() + BUY_TIME.as_secs_f32() > now {
+            continue;
+        }
+
+        active.status = InteractionStatus::Canceled;
+        let Some(account) = carried_account(&access, user) else {
+            continue;
+        };
+        let reason = format!("Purchase of {}", product.name);
+        if let Err(err) = accounts.transfer(Some(account), None, product.price, reason) {
+            debug!(account = %account, error = %err, "Purchase declined");
+            continue;
+        }
+
+        commands.spawn(NetworkSceneBundle {
+            scene: server.load(product.scene.as_str()).into(),
+            transform: Transform::from_translation(transform),
+            ..Default::default()
+        });
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+/// A terminal to check an account and transfer credits, using the account of an ID card
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+struct BankTerminal;
+
+/// An account a player has open on a bank terminal
+struct BankSession {
+    account: AccountId,
+    terminal: Entity,
+    /// The creature that opened the terminal
+    user: Entity,
+    player: Uuid,
+}
+
+impl BankSession {
+    /// Whether the creature is still standing at the terminal
+    fn in_reach(&self, transforms: &Query<&GlobalTransform>) -> bool {
+        let (Ok(user), Ok(terminal)) = (transforms.get(self.user), transforms.get(self.terminal))
+        else {
+            return false;
+        };
+        user.translation().distance(terminal.translation()) <= TERMINAL_REACH
+    }
+}
+
+/// Which account each player has open on a bank terminal
+#[derive(Resource, Default)]
+struct BankSessions(HashMap<ConnectionId, BankSession>);
+
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct OpenBankTerminalInteraction;
+
+fn prepare_bank_terminal_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    terminals: Query<(), With<BankTerminal>>,
+) {
+    for event in interaction_list.events.iter() {
+        if !terminals.contains(event.target) {
+            continue;
+        }
+        event.add_interaction(InteractionOption {
+            text: "Access bank account".into(),
+            interaction: Box::new(OpenBankTerminalInteraction),
+            specificity: InteractionSpecificity::Specific,
+        });
+    }
+}
+
+/// State of an account, sent to the player that has it open
+#[derive(Serialize, Deserialize, Clone)]
+struct BankAccountMessage {
+    account: AccountId,
+    holder: String,
+    balance: u32,
+    history: Vec<TransactionSummary>,
+    /// Why the last transfer request failed
+    error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct TransactionSummary {
+    /// Negative for outgoing credits
+    amount: i64,
+    /// Holder of the other account, or the station
+    other: String,
+    reason: String,
+}
+
+impl BankAccountMessage {
+    fn new(accounts: &BankAccounts, id: AccountId, error: Option<String>) -> Option<Self> {
+        let account = accounts.get(id)?;
+        let name_of = |other: Option<AccountId>| {
+            other
+                .and_then(|o| accounts.get(o))
+                .map(|a| a.holder.clone())
+                .unwrap_or_else(|| "Station".into())
+        };
+        let history = accounts
+            .history(id)
+            .take(SHOWN_TRANSACTIONS)
+            .map(|t| {
+                let outgoing = t.from == Some(id);
+                TransactionSummary {
+                    amount: if outgoing {
+                        -(t.amount as i64)
+                    } else {
+                        t.amount as i64
+                    },
+                    other: name_of(if outgoing { t.to } else { t.from }),
+                    reason: t.reason.clone(),
+                }
+            })
+            .collect();
+        Some(Self {
+            account: id,
+            holder: account.holder.clone(),
+            balance: account.balance,
+            history,
+            error,
+        })
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn open_bank_terminal_interaction(
+    mut query: Query<(Entity, &mut ActiveInteraction), With<OpenBankTerminalInteraction>>,
+    access: AccessCheck,
+    accounts: Res<BankAccounts>,
+    mut sessions: ResMut<BankSessions>,
+    controls: Res<ClientControls>,
+    players: Res<Players>,
+    mut sender: MessageSender,
+) {
+    for (user, mut active) in query.iter_mut() {
+        active.status = InteractionStatus::Completed;
+
+        let Some(player) = controls.controlling_player(user) else {
+            continue;
+        };
+        let Some(connection) = players.get_connection(&player) else {
+            continue;
+        };
+        let Some(message) = carried_account(&access, user)
+            .and_then(|account| BankAccountMessage::new(&accounts, account, None))
+        else {
+            continue;
+        };
+
+        sessions.0.insert(
+            connection,
+            BankSession {
+                account: message.account,
+                terminal: active.target,
+                user,
+                player,
+            },
+        );
+        sender.send(&message, MessageReceivers::Single(connection));
+    }
+}
+
+/// Client request to send credits from the account open on a bank terminal
+#[derive(Serialize, Deserialize)]
+struct TransferRequest {
+    to: AccountId,
+    amount: u32,
+    reason: String,
+}
+
+/// Closes the bank terminal UI. Sent by the client when closing the window,
+/// and by the server when the session ends on its side.
+#[derive(Serialize, Deserialize)]
+struct CloseBankAccount;
+
+/// Ends bank sessions when the player closes the terminal, walks away from it or leaves
+fn end_bank_sessions(
+    mut closed: EventReader<MessageEvent<CloseBankAccount>>,
+    mut server_events: EventReader<ServerEvent>,
+    mut sessions: ResMut<BankSessions>,
+    transforms: Query<&GlobalTransform>,
+    mut sender: MessageSender,
+) {
+    for event in closed.iter() {
+        sessions.0.remove(&event.connection);
+    }
+    for event in server_events.iter() {
+        if let ServerEvent::PlayerDisconnected(connection) = event {
+            sessions.0.remove(connection);
+        }
+    }
+    sessions.0.retain(|&connection, session| {
+        if session.in_reach(&transforms) {
+            return true;
+        }
+        sender.send(&CloseBankAccount, MessageReceivers::Single(connection));
+        false
+    });
+}
+
+fn handle_transfer_request(
+    mut messages: EventReader<MessageEvent<TransferRequest>>,
+    sessions: Res<BankSessions>,
+    access: AccessCheck,
+    mut accounts: ResMut<BankAccounts>,
+    controls: Res<ClientControls>,
+    transforms: Query<&GlobalTransform>,
+    mut sender: MessageSender,
+) {
+    for event in messages.iter() {
+        let Some(session) = sessions.0.get(&event.connection) else {
+            continue;
+        };
+        let from = session.account;
+        // The player has to still be at the terminal, with the card on them
+        if !controls.does_control(session.player, session.user) || !session.in_reach(&transforms) {
+            continue;
+        }
+        if !access
+            .cards(session.user)
+            .any(|card| card.account == Some(from))
+        {
+            continue;
+        }
+
+        let request = &event.message;
+        let mut reason: String = request
+            .reason
+            .trim()
+            .chars()
+            .take(MAX_REASON_LENGTH)
+            .collect();
+        if reason.is_empty() {
+            reason = "Transfer".into();
+        }
+        let error = accounts
+            .transfer(Some(from), Some(request.to), request.amount, reason)
+            .err()
+            .map(|err| err.to_string());
+
+        if let Some(message) = BankAccountMessage::new(&accounts, from, error) {
+            sender.send(&message, MessageReceivers::Single(event.connection));
+        }
+    }
+}
+
+/// The account open on a bank terminal, shown until closed
+#[derive(Resource, Default)]
+struct ClientBankAccount(Option<BankAccountMessage>);
+
+fn client_receive_account(
+    mut messages: EventReader<MessageEvent<BankAccountMessage>>,
+    mut account: ResMut<ClientBankAccount>,
+) {
+    for event in messages.iter() {
+        account.0 = Some(event.message.clone());
+    }
+}
+
+fn client_close_account(
+    mut messages: EventReader<MessageEvent<CloseBankAccount>>,
+    mut account: ResMut<ClientBankAccount>,
+) {
+    if messages.iter().last().is_some() {
+        account.0 = None;
+    }
+}
+
+#[derive(Default)]
+struct TransferForm {
+    to: String,
+    amount: String,
+    reason: String,
+}
+
+fn client_bank_ui(
+    mut contexts: EguiContexts,
+    mut account: ResMut<ClientBankAccount>,
+    mut form: Local<TransferForm>,
+    palette: Res<Palette>,
+    mut sender: MessageSender,
+) {
+    let Some(state) = account.0.as_ref() else {
+        return;
+    };
+
+    let mut open = true;
+    egui::Window::new("Bank Account")
+        .open(&mut open)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(format!("Account {} - {}", state.account, state.holder));
+            ui.label(egui::RichText::new(format!("{} cr", state.balance)).strong());
+
+            ui.separator();
+            egui::Grid::new("transfer").show(ui, |ui| {
+                ui.label("To account");
+                ui.text_edit_singleline(&mut form.to);
+                ui.end_row();
+                ui.label("Amount");
+                ui.text_edit_singleline(&mut form.amount);
+                ui.end_row();
+                ui.label("Reason");
+                ui.text_edit_singleline(&mut form.reason);
+                ui.end_row();
+            });
+            let to = form.to.trim().parse().ok().map(AccountId);
+            let amount = form.amount.trim().parse().ok();
+            if let (Some(to), Some(amount)) = (to, amount) {
+                if ui.button("Transfer").clicked() {
+                    sender.send_to_server(&TransferRequest {
+                        to,
+                        amount,
+                        reason: form.reason.clone(),
+                    });
+                    *form = TransferForm::default();
+                }
+            }
+            if let Some(error) = state.error.as_ref() {
+                ui.label(palette.text(Cue::Bad, error));
+            }
+
+            ui.separator();
+            for transaction in state.history.iter() {
+                let cue = if transaction.amount < 0 {
+                    Cue::Bad
+                } else {
+                    Cue::Good
+                };
+                ui.horizontal(|ui| {
+                    ui.label(palette.text(cue, &format!("{:+} cr", transaction.amount)));
+                    ui.label(format!("{}: {}", transaction.other, transaction.reason));
+                });
+            }
+        });
+    if !open {
+        account.0 = None;
+        sender.send_to_server(&CloseBankAccount);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::event::Events, prelude::*, utils::Uuid};
+    use networking::{
+        messaging::{AppExt, MessageEvent},
+        spawning::ClientControls,
+        testing::TestServer,
+    };
+
+    use super::{
+        handle_transfer_request, AccountId, BankAccountMessage, BankAccounts, BankSession,
+        BankSessions, TransferRequest,
+    };
+    use crate::{access::IdCard, body::Hand, items::StoredItem};
+
+    struct Setup {
+        server: TestServer,
+        user: Entity,
+        from: AccountId,
+        to: AccountId,
+    }
+
+    /// A player standing at a bank terminal with an open session, holding their ID card
+    fn setup() -> Setup {
+        let mut server = TestServer::default();
+        let connection = server.connection();
+        let app = &mut server.app;
+        app.add_network_message::<BankAccountMessage>()
+            .add_network_message::<TransferRequest>()
+            .init_resource::<BankAccounts>()
+            .init_resource::<BankSessions>()
+            .init_resource::<ClientControls>()
+            .add_systems(Update, handle_transfer_request);
+
+        let mut accounts = app.world.resource_mut::<BankAccounts>();
+        let from = accounts.open("Sender".into(), 100, 0);
+        let to = accounts.open("Receiver".into(), 0, 0);
+
+        let world = &mut app.world;
+        let terminal = world.spawn(GlobalTransform::default()).id();
+        let user = world.spawn(GlobalTransform::from_translation(Vec3::X)).id();
+        let hand = Hand::from_world(world);
+        let hand = world.spawn(hand).set_parent(user).id();
+        world
+            .spawn((
+                IdCard {
+                    access: Vec::new(),
+                    account: Some(from),
+                },
+                StoredItem::new(hand, UVec2::ZERO, false),
+            ))
+            .set_parent(hand);
+
+        let player = Uuid::from_u128(1);
+        world
+            .resource_mut::<ClientControls>()
+            .give_control(player, user);
+        world.resource_mut::<BankSessions>().0.insert(
+            connection,
+            BankSession {
+                account: from,
+                terminal,
+                user,
+                player,
+            },
+        );
+
+        Setup {
+            server,
+            user,
+            from,
+            to,
+        }
+    }
+
+    fn request_transfer(setup: &mut Setup, amount: u32) {
+        let connection = setup.server.connection();
+        let to = setup.to;
+        setup
+            .server
+            .app
+            .world
+            .resource_mut::<Events<MessageEvent<TransferRequest>>>()
+            .send(MessageEvent {
+                message: TransferRequest {
+                    to,
+                    amount,
+                    reason: String::new(),
+                },
+                connection,
+            });
+        setup.server.update();
+    }
+
+    fn balance(setup: &Setup, account: AccountId) -> u32 {
+        setup
+            .server
+            .app
+            .world
+            .resource::<BankAccounts>()
+            .get(account)
+            .unwrap()
+            .balance
+    }
+
+    #[test]
+    fn transfers_at_terminal() {
+        let mut setup = setup();
+        request_transfer(&mut setup, 40);
+
+        assert_eq!(balance(&setup, setup.from), 60);
+        assert_eq!(balance(&setup, setup.to), 40);
+    }
+
+    #[test]
+    fn rejects_transfer_after_walking_away() {
+        let mut setup = setup();
+        *setup
+            .server
+            .app
+            .world
+            .get_mut::<GlobalTransform>(setup.user)
+            .unwrap() = GlobalTransform::from_translation(Vec3::new(20.0, 0.0, 0.0));
+        request_transfer(&mut setup, 40);
+
+        assert_eq!(balance(&setup, setup.from), 100);
+        assert_eq!(balance(&setup, setup.to), 0);
+    }
+}
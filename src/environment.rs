@@ -0,0 +1,164 @@
+//! Map environment settings, like ambient light color, fog and the background.
+//!
+//! The server takes the [`MapEnvironment`] of the loaded map and syncs it to clients,
+//! which apply it to the main camera. Leaving a server resets everything to the defaults.
+
+use bevy::{
+    asset::LoadState,
+    core_pipeline::Skybox,
+    pbr::{FogFalloff, FogSettings},
+    prelude::*,
+    reflect::TypeUuid,
+    render::render_resource::{TextureViewDescriptor, TextureViewDimension},
+};
+use maps::{
+    io::{MapBackground, MapEnvironment},
+    TileMap,
+};
+use networking::{
+    is_server,
+    resource::AppExt,
+    variable::{NetworkVar, ServerVar},
+    ClientEvent, Networked,
+};
+
+use crate::camera::MainCamera;
+
+pub struct EnvironmentPlugin;
+
+impl Plugin for EnvironmentPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_networked_resource::<MapEnvironmentData, MapEnvironmentClient>();
+        if is_server(app) {
+            app.init_resource::<MapEnvironmentData>()
+                .add_systems(Update, update_map_environment);
+        } else {
+            app.init_resource::<PendingSkybox>()
+                .add_systems(Update, (apply_map_environment, show_skybox).chain());
+        }
+    }
+}
+
+#[derive(Networked, Resource, Default)]
+#[networked(client = "MapEnvironmentClient")]
+struct MapEnvironmentData {
+    environment: NetworkVar<MapEnvironment>,
+}
+
+#[derive(Default, TypeUuid, Networked, Resource)]
+#[uuid = "5ac5b263-b80e-4125-bc07-6b7e28aad1a1"]
+#[networked(server = "MapEnvironmentData")]
+struct MapEnvironmentClient {
+    environment: ServerVar<MapEnvironment>,
+}
+
+fn update_map_environment(
+    maps: Query<Option<&MapEnvironment>, Added<TileMap>>,
+    mut data: ResMut<MapEnvironmentData>,
+) {
+    for environment in maps.iter() {
+        // Converted BYOND maps have no environment
+        let environment = environment.cloned().unwrap_or_default();
+        if *data.environment != environment {
+            *data.environment = environment;
+        }
+    }
+}
+
+/// A skybox image that is shown once it is loaded
+#[derive(Resource, Default)]
+struct PendingSkybox(Option<Handle<Image>>);
+
+#[allow(clippy::too_many_arguments)]
+fn apply_map_environment(
+    environment: Option<Res<MapEnvironmentClient>>,
+    mut client_events: EventReader<ClientEvent>,
+    mut ambient: ResMut<AmbientLight>,
+    mut clear_color: ResMut<ClearColor>,
+    mut default_clear_color: Local<Option<Color>>,
+    mut pending_skybox: ResMut<PendingSkybox>,
+    cameras: Query<Entity, With<MainCamera>>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    let left = client_events
+        .iter()
+        .any(|e| matches!(e, ClientEvent::Disconnected(_)));
+    let environment = match environment {
+        _ if left => {
+            commands.remove_resource::<MapEnvironmentClient>();
+            MapEnvironment::default()
+        }
+        Some(environment) if environment.is_changed() => environment.environment.clone(),
+        _ => return,
+    };
+    let default_clear_color = *default_clear_color.get_or_insert(clear_color.0);
+
+    ambient.color = environment.ambient_color;
+    clear_color.0 = match environment.background {
+        Some(MapBackground::Color(color)) => color,
+        _ => default_clear_color,
+    };
+    pending_skybox.0 = match &environment.background {
+        Some(MapBackground::Skybox(path)) => Some(asset_server.load(path.as_str())),
+        _ => None,
+    };
+
+    for camera in cameras.iter() {
+        let mut camera = commands.entity(camera);
+        camera.remove::<Skybox>();
+        match &environment.fog {
+            Some(fog) => camera.insert(FogSettings {
+                color: fog.color,
+                falloff: FogFalloff::Linear {
+                    start: fog.start,
+                    end: fog.end,
+                },
+                ..Default::default()
+            }),
+            None => camera.remove::<FogSettings>(),
+        };
+    }
+}
+
+fn show_skybox(
+    mut pending: ResMut<PendingSkybox>,
+    mut images: ResMut<Assets<Image>>,
+    asset_server: Res<AssetServer>,
+    cameras: Query<Entity, With<MainCamera>>,
+    mut commands: Commands,
+) {
+    let Some(handle) = pending.0.clone() else {
+        return;
+    };
+    if asset_server.get_load_state(&handle) == LoadState::Failed {
+        warn!("Failed to load the skybox of the map");
+        pending.0 = None;
+        return;
+    }
+    let Some(image) = images.get_mut(&handle) else {
+        return;
+    };
+
+    // Skybox files have their faces stacked vertically, the skybox needs them as a cubemap
+    if image.texture_descriptor.array_layer_count() == 1 {
+        let size = image.texture_descriptor.size;
+        if size.height != 6 * size.width {
+            warn!(
+                "Skybox of the map is {}x{}, but needs six square faces stacked vertically",
+                size.width, size.height
+            );
+            pending.0 = None;
+            return;
+        }
+        image.reinterpret_stacked_2d_as_array(size.height / size.width);
+        image.texture_view_descriptor = Some(TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::Cube),
+            ..Default::default()
+        });
+    }
+    for camera in cameras.iter() {
+        commands.entity(camera).insert(Skybox(handle.clone()));
+    }
+    pending.0 = None;
+}
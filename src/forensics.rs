@@ -0,0 +1,530 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use networking::{
+    is_server,
+    messaging::{AppExt, MessageEvent, MessageReceivers, MessageSender},
+    spawning::ClientControls,
+    Players,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    access::AccessCheck,
+    body::{health::prosthetic::RoboticBodyPart, Body, Hand, Hands, Limb},
+    combat::damage::{Damage, DamageDealt, DamageType},
+    communication::{
+        announcements::{Announcement, AnnouncementPriority},
+        SpeechName,
+    },
+    doors::Door,
+    interaction::{
+        ActiveInteraction, GenerateInteractionList, InteractionListEvents, InteractionOption,
+        InteractionSpecificity, InteractionStatus,
+    },
+    items::{clothes::Equipped, containers::Container, Item},
+    round::{RoundRng, RoundState},
+    ui::has_window,
+};
+
+pub struct ForensicsPlugin;
+
+impl Plugin for ForensicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Fibers>()
+            .register_type::<ForensicScanner>()
+            .register_type::<SecurityRecordsConsole>()
+            .add_network_message::<SecurityRecordsMessage>();
+        if is_server(app) {
+            app.init_resource::<CaseRecords>()
+                .register_type::<ScanTracesInteraction>()
+                .register_type::<UploadRecordsInteraction>()
+                .register_type::<OpenRecordsInteraction>()
+                .add_systems(OnEnter(RoundState::Restarting), clear_case_records)
+                .add_systems(
+                    Update,
+                    (
+                        add_fingerprints,
+                        leave_traces,
+                        bloody_weapons,
+                        (prepare_scan_interaction, prepare_console_interactions)
+                            .in_set(GenerateInteractionList),
+                        scan_interaction,
+                        upload_interaction,
+                        open_records_interaction,
+                    ),
+                );
+        } else {
+            app.init_resource::<ClientSecurityRecords>().add_systems(
+                Update,
+                (client_receive_records, client_records_ui.run_if(has_window)).chain(),
+            );
+        }
+    }
+}
+
+const SCAN_TIME: Duration = Duration::from_secs(2);
+const UPLOAD_TIME: Duration = Duration::from_secs(1);
+/// Older traces get smudged over by newer ones
+const MAX_TRACES: usize = 8;
+/// Clothing slot that covers the hands
+const GLOVES_SLOT: &str = "hands";
+
+/// Something left behind by touching an object
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub enum Trace {
+    Fingerprint(String),
+    Fiber(String),
+    /// Blood of the creature with this fingerprint
+    Blood(String),
+}
+
+/// The unique fingerprint of a creature
+#[derive(Component)]
+pub struct Fingerprint(pub String);
+
+/// Clothing leaving fibers instead of fingerprints when worn on the hands
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Fibers {
+    pub description: String,
+}
+
+/// Traces left on an item or door by whoever touched it
+#[derive(Component, Default)]
+pub struct ForensicTraces {
+    traces: Vec<Trace>,
+}
+
+impl ForensicTraces {
+    fn add(&mut self, trace: Trace) {
+        self.traces.retain(|t| t != &trace);
+        self.traces.push(trace);
+        if self.traces.len() > MAX_TRACES {
+            self.traces.remove(0);
+        }
+    }
+}
+
+fn add_fingerprints(
+    bodies: Query<Entity, (With<Body>, Without<Fingerprint>)>,
+    rng: Option<ResMut<RoundRng>>,
+    mut commands: Commands,
+) {
+    let Some(mut rng) = rng else {
+        return;
+    };
+    for entity in bodies.iter() {
+        let print = format!("{:012x}", rng.next_u64() >> 16);
+        commands.entity(entity).insert(Fingerprint(print));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn leave_traces(
+    interactions: Query<(Entity, &ActiveInteraction), Added<ActiveInteraction>>,
+    fingerprints: Query<&Fingerprint>,
+    child_query: Query<&Children>,
+    gloves: Query<(&Fibers, &Equipped)>,
+    bodies: Query<&Hands>,
+    hand_query: Query<&Container, With<Hand>>,
+    touchable: Query<(), Or<(With<Item>, With<Door>)>>,
+    mut traced: Query<&mut ForensicTraces>,
+    mut commands: Commands,
+) {
+    for (user, active) in interactions.iter() {
+        let worn_gloves = gloves
+            .iter_many(child_query.iter_descendants(user))
+            .find(|(_, equipped)| equipped.slot() == GLOVES_SLOT);
+        let trace = match (worn_gloves, fingerprints.get(user)) {
+            (Some((fibers, _)), _) => Trace::Fiber(fibers.description.clone()),
+            (None, Ok(print)) => Trace::Fingerprint(print.0.clone()),
+            (None, Err(_)) => continue,
+        };
+
+        let held_item = bodies
+            .get(user)
+            .ok()
+            .and_then(|hands| hand_query.get(hands.active_hand()).ok())
+            .and_then(|hand| hand.iter().next().map(|(_, &item)| item));
+        let touched = std::iter::once(active.target)
+            .filter(|&target| target != user && touchable.contains(target))
+            .chain(held_item);
+        for entity in touched {
+            if let Ok(mut traces) = traced.get_mut(entity) {
+                traces.add(trace.clone());
+            } else {
+                commands.entity(entity).insert(ForensicTraces {
+                    traces: vec![trace.clone()],
+                });
+            }
+        }
+    }
+}
+
+/// Weapons that cut someone get their blood on them
+fn bloody_weapons(
+    mut dealt: EventReader<DamageDealt>,
+    flesh: Query<(), (With<Limb>, Without<RoboticBodyPart>)>,
+    fingerprints: Query<&Fingerprint>,
+    parents: Query<&Parent>,
+    mut traced: Query<&mut ForensicTraces>,
+    mut commands: Commands,
+) {
+    for event in dealt.iter() {
+        let Some(weapon) = event.source.and_then(|source| source.weapon) else {
+            continue;
+        };
+        let Damage::Tissue(tissue) = event.damage else {
+            continue;
+        };
+        if tissue.kind != DamageType::Brute || tissue.amount <= 0.0 || !flesh.contains(event.target)
+        {
+            continue;
+        }
+        let Some(print) = parents
+            .iter_ancestors(event.target)
+            .find_map(|e| fingerprints.get(e).ok())
+        else {
+            continue;
+        };
+
+        let trace = Trace::Blood(print.0.clone());
+        if let Ok(mut traces) = traced.get_mut(weapon) {
+            traces.add(trace);
+        } else {
+            commands.entity(weapon).insert(ForensicTraces {
+                traces: vec![trace],
+            });
+        }
+    }
+}
+
+/// Traces collected from one object
+#[derive(Clone)]
+struct CaseRecord {
+    object: String,
+    traces: Vec<Trace>,
+}
+
+/// A handheld scanner collecting traces into case records
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+struct ForensicScanner {
+    /// Records not yet uploaded to a records console
+    #[reflect(ignore)]
+    records: Vec<CaseRecord>,
+}
+
+/// Gives access to the case records of the station
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+struct SecurityRecordsConsole;
+
+/// All case records uploaded to the station
+#[derive(Resource, Default)]
+struct CaseRecords {
+    cases: Vec<CaseRecord>,
+}
+
+fn clear_case_records(mut records: ResMut<CaseRecords>) {
+    records.cases.clear();
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct ScanTracesInteraction {
+    scanner: Entity,
+}
+
+impl FromWorld for ScanTracesInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            scanner: Entity::PLACEHOLDER,
+        }
+    }
+}
+
+fn prepare_scan_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    scanners: Query<(), With<ForensicScanner>>,
+    traced: Query<&ForensicTraces>,
+) {
+    for event in interaction_list.events.iter() {
+        let Some(scanner) = event.item_in_hand else {
+            continue;
+        };
+        if !scanners.contains(scanner) {
+            continue;
+        }
+        if traced
+            .get(event.target)
+            .map(|t| t.traces.is_empty())
+            .unwrap_or(true)
+        {
+            continue;
+        }
+
+        event.add_interaction(InteractionOption {
+            text: "Scan for traces".into(),
+            interaction: Box::new(ScanTracesInteraction { scanner }),
+            specificity: InteractionSpecificity::Specific,
+        });
+    }
+}
+
+fn scan_interaction(
+    mut query: Query<(&ScanTracesInteraction, &mut ActiveInteraction)>,
+    mut scanners: Query<&mut ForensicScanner>,
+    traced: Query<(&ForensicTraces, Option<&Item>, Option<&Name>)>,
+    time: Res<Time>,
+) {
+    for (interaction, mut active) in query.iter_mut() {
+        active.set_initial_duration(SCAN_TIME);
+
+        let (Ok(mut scanner), Ok((traces, item, name))) = (
+            scanners.get_mut(interaction.scanner),
+            traced.get(active.target),
+        ) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+
+        if active.start_time() + SCAN_TIME.as_secs_f32() > time.elapsed_seconds() {
+            continue;
+        }
+
+        let object = item
+            .map(|i| i.name.clone())
+            .or_else(|| name.map(|n| n.to_string()))
+            .unwrap_or_else(|| "Unknown object".into());
+        scanner.records.push(CaseRecord {
+            object,
+            traces: traces.traces.clone(),
+        });
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct UploadRecordsInteraction {
+    scanner: Entity,
+}
+
+impl FromWorld for UploadRecordsInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            scanner: Entity::PLACEHOLDER,
+        }
+    }
+}
+
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct OpenRecordsInteraction;
+
+fn prepare_console_interactions(
+    interaction_list: Res<InteractionListEvents>,
+    consoles: Query<(), With<SecurityRecordsConsole>>,
+    scanners: Query<&ForensicScanner>,
+    access: AccessCheck,
+) {
+    for event in interaction_list.events.iter() {
+        if !consoles.contains(event.target) || !access.is_allowed(event.source, event.target) {
+            continue;
+        }
+
+        event.add_interaction(InteractionOption {
+            text: "Open security records".into(),
+            interaction: Box::new(OpenRecordsInteraction),
+            specificity: InteractionSpecificity::Specific,
+        });
+
+        let Some(scanner) = event.item_in_hand else {
+            continue;
+        };
+        if scanners
+            .get(scanner)
+            .map(|s| s.records.is_empty())
+            .unwrap_or(true)
+        {
+            continue;
+        }
+        event.add_interaction(InteractionOption {
+            text: "Upload case records".into(),
+            interaction: Box::new(UploadRecordsInteraction { scanner }),
+            specificity: InteractionSpecificity::Specific,
+        });
+    }
+}
+
+fn upload_interaction(
+    mut query: Query<(&UploadRecordsInteraction, &mut ActiveInteraction)>,
+    mut scanners: Query<&mut ForensicScanner>,
+    mut records: ResMut<CaseRecords>,
+    mut announcements: EventWriter<Announcement>,
+    time: Res<Time>,
+) {
+    for (interaction, mut active) in query.iter_mut() {
+        active.set_initial_duration(UPLOAD_TIME);
+
+        let Ok(mut scanner) = scanners.get_mut(interaction.scanner) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+
+        if active.start_time() + UPLOAD_TIME.as_secs_f32() > time.elapsed_seconds() {
+            continue;
+        }
+
+        if !scanner.records.is_empty() {
+            announcements.send(
+                Announcement::new(
+                    "Security Records",
+                    format!("{} new case records uploaded.", scanner.records.len()),
+                    AnnouncementPriority::Low,
+                )
+                .department("security"),
+            );
+        }
+        records.cases.append(&mut scanner.records);
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+/// Case records as shown on a records console, with traces matched to known characters
+#[derive(Serialize, Deserialize)]
+struct SecurityRecordsMessage {
+    cases: Vec<CaseSummary>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct CaseSummary {
+    object: String,
+    traces: Vec<TraceSummary>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct TraceSummary {
+    trace: Trace,
+    /// Names of characters the trace belongs to
+    matches: Vec<String>,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn open_records_interaction(
+    mut query: Query<(Entity, &mut ActiveInteraction), With<OpenRecordsInteraction>>,
+    records: Res<CaseRecords>,
+    fingerprints: Query<(&Fingerprint, &SpeechName)>,
+    gloves: Query<(&Fibers, &Parent), With<Equipped>>,
+    parents: Query<&Parent>,
+    names: Query<&SpeechName>,
+    controls: Res<ClientControls>,
+    players: Res<Players>,
+    mut sender: MessageSender,
+) {
+    for (user, mut active) in query.iter_mut() {
+        active.status = InteractionStatus::Completed;
+
+        let Some(connection) = controls
+            .controlling_player(user)
+            .and_then(|player| players.get_connection(&player))
+        else {
+            continue;
+        };
+
+        let matches = |trace: &Trace| -> Vec<String> {
+            match trace {
+                Trace::Fingerprint(print) | Trace::Blood(print) => fingerprints
+                    .iter()
+                    .filter(|(p, _)| &p.0 == print)
+                    .map(|(_, name)| name.0.clone())
+                    .collect(),
+                // Fibers can only be matched to whoever is wearing that kind of clothing right now
+                Trace::Fiber(description) => gloves
+                    .iter()
+                    .filter(|(fibers, _)| &fibers.description == description)
+                    .filter_map(|(_, parent)| {
+                        parents
+                            .iter_ancestors(parent.get())
+                            .find_map(|e| names.get(e).ok())
+                    })
+                    .map(|name| name.0.clone())
+                    .collect(),
+            }
+        };
+
+        let cases = records
+            .cases
+            .iter()
+            .map(|case| CaseSummary {
+                object: case.object.clone(),
+                traces: case
+                    .traces
+                    .iter()
+                    .map(|trace| TraceSummary {
+                        trace: trace.clone(),
+                        matches: matches(trace),
+                    })
+                    .collect(),
+            })
+            .collect();
+        sender.send(
+            &SecurityRecordsMessage { cases },
+            MessageReceivers::Single(connection),
+        );
+    }
+}
+
+/// Records last opened on a console, shown until closed
+#[derive(Resource, Default)]
+struct ClientSecurityRecords(Option<Vec<CaseSummary>>);
+
+fn client_receive_records(
+    mut messages: EventReader<MessageEvent<SecurityRecordsMessage>>,
+    mut records: ResMut<ClientSecurityRecords>,
+) {
+    for event in messages.iter() {
+        records.0 = Some(event.message.cases.clone());
+    }
+}
+
+fn client_records_ui(mut contexts: EguiContexts, mut records: ResMut<ClientSecurityRecords>) {
+    let Some(cases) = records.0.as_ref() else {
+        return;
+    };
+
+    let mut open = true;
+    egui::Window::new("Security Records")
+        .open(&mut open)
+        .show(contexts.ctx_mut(), |ui| {
+            if cases.is_empty() {
+                ui.label("No case records");
+            }
+            for (index, case) in cases.iter().enumerate() {
+                ui.collapsing(format!("#{} {}", index + 1, case.object), |ui| {
+                    for summary in case.traces.iter() {
+                        let (kind, value) = match &summary.trace {
+                            Trace::Fingerprint(print) => ("Fingerprint", print),
+                            Trace::Fiber(description) => ("Fibers", description),
+                            Trace::Blood(print) => ("Blood", print),
+                        };
+                        let matched = if summary.matches.is_empty() {
+                            "no match".to_owned()
+                        } else {
+                            summary.matches.join(", ")
+                        };
+                        ui.label(format!("{}: {} ({})", kind, value, matched));
+                    }
+                });
+            }
+        });
+    if !open {
+        records.0 = None;
+    }
+}
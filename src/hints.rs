@@ -0,0 +1,154 @@
+//! Tips shown to players the first time they run into a mechanic.
+//!
+//! The server decides when a tip applies and remembers which ones were shown in the character profile,
+//! so each tip only appears once per player. Tips are shown as toasts, which let players turn them off.
+
+use bevy::prelude::*;
+use networking::{
+    is_server,
+    messaging::{AppExt, MessageEvent, MessageSender},
+    spawning::ClientControls,
+    Players,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::{nonlethal::Subdued, CombatMode},
+    persistence::CharacterProfiles,
+    ui::toasts::{PlayerToasts, Toast, ToastActionEvent, ToastLevel, Toasts},
+};
+
+pub struct HintPlugin;
+
+impl Plugin for HintPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_network_message::<DisableHintsMessage>();
+
+        if is_server(app) {
+            app.add_systems(Update, (handle_disable_hints, show_hints).chain());
+        } else {
+            app.add_systems(Update, client_disable_hints);
+        }
+    }
+}
+
+/// Seconds a tip stays on screen if the player doesn't dismiss it
+const HINT_VISIBLE_SECONDS: f32 = 30.0;
+/// Id of the toast button that turns tips off
+const DISABLE_HINTS_ACTION: &str = "disable_hints";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Hint {
+    CombatMode,
+    Stunned,
+    Restrained,
+}
+
+impl Hint {
+    const ALL: [Hint; 3] = [Hint::CombatMode, Hint::Stunned, Hint::Restrained];
+
+    /// Saved in the character profile, so it must not change once released
+    fn id(self) -> &'static str {
+        match self {
+            Hint::CombatMode => "combat_mode",
+            Hint::Stunned => "stunned",
+            Hint::Restrained => "restrained",
+        }
+    }
+
+    fn text(self) -> &'static str {
+        match self {
+            Hint::CombatMode => {
+                "You are in combat mode. Clicking attacks instead of picking up or using things. \
+                Press Tab to leave it."
+            }
+            Hint::Stunned => "You are stunned and can't use your hands until it wears off.",
+            Hint::Restrained => {
+                "You are restrained. You can't use your hands until someone removes your restraints."
+            }
+        }
+    }
+}
+
+/// Client request to stop receiving tips
+#[derive(Serialize, Deserialize)]
+struct DisableHintsMessage;
+
+fn handle_disable_hints(
+    mut messages: EventReader<MessageEvent<DisableHintsMessage>>,
+    players: Res<Players>,
+    mut profiles: ResMut<CharacterProfiles>,
+) {
+    for event in messages.iter() {
+        let Some(player) = players.get(event.connection) else {
+            continue;
+        };
+        profiles.update(player.id, |profile| profile.hints_disabled = true);
+    }
+}
+
+/// Sends tips for the situations controlled creatures are in, if the player hasn't seen them yet
+fn show_hints(
+    players: Res<Players>,
+    controls: Res<ClientControls>,
+    creatures: Query<(Option<&CombatMode>, Option<&Subdued>)>,
+    mut profiles: ResMut<CharacterProfiles>,
+    mut toasts: PlayerToasts,
+) {
+    for player in players.players().values() {
+        let Some(creature) = controls.controlled_entity(player.id) else {
+            continue;
+        };
+        let Ok((combat_mode, subdued)) = creatures.get(creature) else {
+            continue;
+        };
+        let Some(profile) = profiles.get(player.id) else {
+            continue;
+        };
+        if profile.hints_disabled {
+            continue;
+        }
+
+        let applies = |hint: Hint| match hint {
+            Hint::CombatMode => combat_mode.map_or(false, |c| c.is_enabled()),
+            Hint::Stunned => subdued.map_or(false, |s| s.is_stunned()),
+            Hint::Restrained => subdued.map_or(false, |s| s.is_restrained()),
+        };
+        let new_hints: Vec<_> = Hint::ALL
+            .into_iter()
+            .filter(|hint| applies(*hint) && !profile.seen_hints.iter().any(|id| id == hint.id()))
+            .collect();
+        if new_hints.is_empty() {
+            continue;
+        }
+
+        profiles.update(player.id, |profile| {
+            profile
+                .seen_hints
+                .extend(new_hints.iter().map(|hint| hint.id().to_owned()));
+        });
+        for hint in new_hints {
+            toasts.send(
+                creature,
+                Toast::new(ToastLevel::Unlock, hint.text())
+                    .title("Tip")
+                    .seconds(HINT_VISIBLE_SECONDS)
+                    .action("Don't show tips", DISABLE_HINTS_ACTION),
+            );
+        }
+    }
+}
+
+fn client_disable_hints(
+    mut actions: EventReader<ToastActionEvent>,
+    mut toasts: ResMut<Toasts>,
+    mut sender: MessageSender,
+) {
+    if actions
+        .iter()
+        .any(|action| action.id == DISABLE_HINTS_ACTION)
+    {
+        sender.send_to_server(&DisableHintsMessage);
+        toasts.remove_with_action(DISABLE_HINTS_ACTION);
+    }
+}
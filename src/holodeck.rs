@@ -0,0 +1,324 @@
+use std::time::Duration;
+
+use bevy::{
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+};
+use bevy_common_assets::ron::RonAssetPlugin;
+use maps::{journal::TileJournal, Direction, MapCommandsExt, TileEntity, TileLayer, TileMap};
+use networking::{is_server, scene::NetworkSceneBundle};
+use serde::Deserialize;
+
+use crate::{
+    despawn::{ContentsPolicy, DespawnCommandsExt},
+    interaction::{
+        ActiveInteraction, GenerateInteractionList, InteractionListEvents, InteractionOption,
+        InteractionSpecificity, InteractionStatus,
+    },
+};
+
+pub struct HolodeckPlugin;
+
+impl Plugin for HolodeckPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<HolodeckConsole>();
+
+        if is_server(app) {
+            app.add_plugins(RonAssetPlugin::<HolodeckProgram>::new(&["holo.ron"]))
+                .register_type::<LoadProgramInteraction>()
+                .add_systems(Startup, load_programs)
+                .add_systems(
+                    Update,
+                    (
+                        (setup_consoles, flag_simulated_tiles).chain(),
+                        prepare_load_program_interaction.in_set(GenerateInteractionList),
+                        load_program_interaction,
+                    ),
+                );
+        }
+    }
+}
+
+const LOAD_PROGRAM_TIME: Duration = Duration::from_secs(2);
+
+/// A layout the holodeck can be filled with.
+#[derive(Deserialize, TypeUuid, TypePath)]
+#[uuid = "5b1f0c3e-7a2d-4e8f-9c61-3d4a8b2e6f07"]
+pub struct HolodeckProgram {
+    pub id: String,
+    pub name: String,
+    /// Turf placed on every tile of the holodeck
+    pub floor: String,
+    #[serde(default)]
+    pub furniture: Vec<ProgramFurniture>,
+    #[serde(default)]
+    pub items: Vec<ProgramItem>,
+}
+
+#[derive(Deserialize)]
+pub struct ProgramFurniture {
+    /// Tile position relative to the corner of the holodeck
+    pub position: UVec2,
+    pub scene: String,
+    #[serde(default)]
+    pub direction: Direction,
+}
+
+#[derive(Deserialize)]
+pub struct ProgramItem {
+    /// Position relative to the corner of the holodeck
+    pub position: Vec3,
+    pub scene: String,
+}
+
+#[derive(Resource)]
+struct HolodeckAssets {
+    programs: Vec<Handle<HolodeckProgram>>,
+}
+
+fn load_programs(mut commands: Commands, server: ResMut<AssetServer>) {
+    let assets = HolodeckAssets {
+        programs: server
+            .load_folder("holodeck")
+            .expect("assets/holodeck is missing")
+            .into_iter()
+            .map(HandleUntyped::typed)
+            .collect(),
+    };
+    commands.insert_resource(assets);
+}
+
+/// Controls a rectangle of tiles whose turf and furniture are replaced by holodeck programs.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+struct HolodeckConsole {
+    /// Corner of the holodeck, relative to the tile of the console
+    area_offset: IVec2,
+    area_size: UVec2,
+    #[reflect(ignore)]
+    area: Option<HolodeckArea>,
+    /// Id of the running program
+    #[reflect(ignore)]
+    program: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+struct HolodeckArea {
+    tilemap: Entity,
+    min: UVec2,
+    max: UVec2,
+}
+
+impl HolodeckArea {
+    fn contains(&self, position: UVec2) -> bool {
+        position.cmpge(self.min).all() && position.cmple(self.max).all()
+    }
+
+    fn positions(&self) -> impl Iterator<Item = UVec2> {
+        let (min, max) = (self.min, self.max);
+        (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| UVec2::new(x, y)))
+    }
+}
+
+/// Marks entities created by a holodeck program.
+/// They are removed when the holodeck switches to another program.
+#[derive(Component)]
+pub struct Simulated {
+    pub holodeck: Entity,
+}
+
+/// Resolves the area of new consoles and keeps it out of the map journal
+fn setup_consoles(
+    mut consoles: Query<(&mut HolodeckConsole, &TileEntity), Added<HolodeckConsole>>,
+    tilemaps: Query<&TileMap>,
+    mut journal: ResMut<TileJournal>,
+) {
+    for (mut console, tile) in consoles.iter_mut() {
+        let Ok(map) = tilemaps.get(tile.tilemap()) else {
+            continue;
+        };
+        let min = tile.position().as_ivec2() + console.area_offset;
+        let max = min + console.area_size.as_ivec2() - IVec2::ONE;
+        if min.cmplt(IVec2::ZERO).any()
+            || max.cmplt(min).any()
+            || max.cmpge(map.size().as_ivec2()).any()
+        {
+            warn!(position = ?tile.position(), "Holodeck area is outside of the tilemap");
+            continue;
+        }
+
+        let (min, max) = (min.as_uvec2(), max.as_uvec2());
+        journal.ignore_area(min, max);
+        console.area = Some(HolodeckArea {
+            tilemap: tile.tilemap(),
+            min,
+            max,
+        });
+    }
+}
+
+fn flag_simulated_tiles(
+    new_tiles: Query<(Entity, &TileEntity), Added<TileEntity>>,
+    consoles: Query<(Entity, &HolodeckConsole)>,
+    mut commands: Commands,
+) {
+    for (entity, tile) in new_tiles.iter() {
+        if !matches!(tile.layer(), TileLayer::Turf | TileLayer::Furniture) {
+            continue;
+        }
+        let holodeck = consoles.iter().find(|(_, console)| {
+            console.area.map_or(false, |area| {
+                area.tilemap == tile.tilemap() && area.contains(tile.position())
+            })
+        });
+        if let Some((holodeck, _)) = holodeck {
+            commands.entity(entity).insert(Simulated { holodeck });
+        }
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct LoadProgramInteraction {
+    console: Entity,
+    program: String,
+}
+
+impl FromWorld for LoadProgramInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            console: Entity::PLACEHOLDER,
+            program: String::new(),
+        }
+    }
+}
+
+fn prepare_load_program_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    consoles: Query<&HolodeckConsole>,
+    assets: Res<HolodeckAssets>,
+    programs: Res<Assets<HolodeckProgram>>,
+) {
+    for event in interaction_list.events.iter() {
+        let Ok(console) = consoles.get(event.target) else {
+            continue;
+        };
+        if console.area.is_none() {
+            continue;
+        }
+        for program in assets.programs.iter().filter_map(|h| programs.get(h)) {
+            if console.program.as_ref() == Some(&program.id) {
+                continue;
+            }
+            event.add_interaction(InteractionOption {
+                text: format!("Load program: {}", program.name),
+                interaction: Box::new(LoadProgramInteraction {
+                    console: event.target,
+                    program: program.id.clone(),
+                }),
+                specificity: InteractionSpecificity::Specific,
+            });
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn load_program_interaction(
+    mut query: Query<(&LoadProgramInteraction, &mut ActiveInteraction)>,
+    mut consoles: Query<&mut HolodeckConsole>,
+    tilemaps: Query<(&TileMap, &GlobalTransform)>,
+    simulated: Query<(Entity, &Simulated), Without<TileEntity>>,
+    programs: Res<Assets<HolodeckProgram>>,
+    server: Res<AssetServer>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    let now = time.elapsed_seconds();
+    for (interaction, mut active) in query.iter_mut() {
+        active.set_initial_duration(LOAD_PROGRAM_TIME);
+
+        let Some(program) = programs
+            .iter()
+            .map(|(_, p)| p)
+            .find(|p| p.id == interaction.program)
+        else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        let Ok(mut console) = consoles.get_mut(interaction.console) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        let Some(area) = console.area else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        let Ok((map, map_transform)) = tilemaps.get(area.tilemap) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+
+        if active.start_time() + LOAD_PROGRAM_TIME.as_secs_f32() > now {
+            continue;
+        }
+
+        // Clear the previous program
+        for (entity, simulated) in simulated.iter() {
+            if simulated.holodeck == interaction.console {
+                commands.despawn_cascade(entity, ContentsPolicy::Destroy);
+            }
+        }
+        for position in area.positions() {
+            let Some(tile) = map.tile(position) else {
+                continue;
+            };
+            for entity in [tile.turf, tile.furniture].into_iter().flatten() {
+                commands.despawn_cascade(entity, ContentsPolicy::Destroy);
+            }
+        }
+
+        // Build the new one
+        for position in area.positions() {
+            commands.spawn_tile_entity(
+                area.tilemap,
+                position,
+                TileLayer::Turf,
+                Direction::default(),
+                program.floor.clone(),
+            );
+        }
+        for furniture in program.furniture.iter() {
+            let position = area.min + furniture.position;
+            if !area.contains(position) {
+                warn!(program = program.id, position = ?furniture.position, "Holodeck furniture outside of area");
+                continue;
+            }
+            commands.spawn_tile_entity(
+                area.tilemap,
+                position,
+                TileLayer::Furniture,
+                furniture.direction,
+                furniture.scene.clone(),
+            );
+        }
+        let corner = Vec3::new(area.min.x as f32, 0.0, area.min.y as f32);
+        for item in program.items.iter() {
+            commands.spawn((
+                NetworkSceneBundle {
+                    scene: server.load(item.scene.as_str()).into(),
+                    transform: Transform::from_translation(
+                        map_transform.transform_point(corner + item.position),
+                    ),
+                    ..Default::default()
+                },
+                Simulated {
+                    holodeck: interaction.console,
+                },
+            ));
+        }
+
+        console.program = Some(program.id.clone());
+        active.status = InteractionStatus::Completed;
+    }
+}
@@ -21,11 +21,17 @@ use utils::task::{Task, Tasks};
 use crate::{
     body::{Hand, Hands},
     camera::MainCamera,
-    combat::ClientCombatModeStatus,
+    combat::{nonlethal::Subdued, ClientCombatModeStatus},
+    controls::{Action, Actions},
     items::containers::Container,
     ui::has_window,
 };
 
+pub use self::targeting::TargetSource;
+use self::targeting::TargetValidation;
+
+mod targeting;
+
 pub struct InteractionPlugin;
 
 impl Plugin for InteractionPlugin {
@@ -40,6 +46,7 @@ impl Plugin for InteractionPlugin {
         if is_server(app) {
             app.init_resource::<SentInteractionLists>()
                 .init_resource::<InteractionListEvents>()
+                .add_event::<DoAfterCanceled>()
                 .init_resource::<Tasks<ExecuteInteraction>>()
                 .configure_sets(
                     Update,
@@ -65,12 +72,16 @@ impl Plugin for InteractionPlugin {
                         clear_completed_interactions,
                     )
                         .chain(),
-                );
+                )
+                // Before interactions run, so they see the cancellation right away
+                .add_systems(PreUpdate, check_do_afters);
         } else {
             app.init_resource::<ClientInteractionUi>().add_systems(
                 Update,
                 (
-                    client_request_interaction_list.in_set(InteractionSystem::Input),
+                    client_request_interaction_list
+                        .in_set(InteractionSystem::Input)
+                        .before(client_interaction_selection_ui),
                     (
                         client_receive_interactions,
                         client_interaction_selection_ui.run_if(has_window),
@@ -93,6 +104,7 @@ pub enum InteractionSystem {
 struct InteractionListOrder {
     connection: ConnectionId,
     target: NetworkIdentity,
+    source: TargetSource,
     send_to_client: bool,
 }
 
@@ -142,6 +154,7 @@ struct SentInteractionLists {
 #[derive(Serialize, Deserialize)]
 pub struct InteractionListRequest {
     pub target: NetworkIdentity,
+    pub source: TargetSource,
 }
 
 #[derive(Serialize, Deserialize, Clone, Copy)]
@@ -152,6 +165,7 @@ struct InteractionExecuteRequest {
 #[derive(Serialize, Deserialize, Clone, Copy)]
 pub struct InteractionExecuteDefaultRequest {
     pub target: NetworkIdentity,
+    pub source: TargetSource,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -165,6 +179,7 @@ struct InteractionListClient {
     interactions: Vec<InteractionOptionClient>,
 }
 
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub enum InteractionStatus {
     Running,
     Canceled,
@@ -193,6 +208,7 @@ pub struct ActiveInteraction {
     pub target: Entity,
     pub status: InteractionStatus,
     reflect_component: ReflectComponent,
+    do_after: Option<DoAfterState>,
 }
 
 impl ActiveInteraction {
@@ -206,8 +222,78 @@ impl ActiveInteraction {
             *self.estimate_duration = Some(duration.as_secs_f32());
         }
     }
+
+    /// Waits out the timed part of an interaction, showing its progress to the player.
+    /// Returns true once the duration has passed and the interaction wasn't canceled.
+    ///
+    /// The requirements of the first call are kept for the whole interaction.
+    /// If they stop being met, the interaction is canceled and a [`DoAfterCanceled`] event is sent.
+    pub fn do_after(&mut self, do_after: DoAfter, now: f32) -> bool {
+        if self.do_after.is_none() {
+            self.do_after = Some(DoAfterState {
+                settings: do_after,
+                positions: None,
+            });
+        }
+        self.set_initial_duration(do_after.duration);
+        self.status == InteractionStatus::Running
+            && self.started + do_after.duration.as_secs_f32() <= now
+    }
+}
+
+/// A timed action as part of an interaction, see [`ActiveInteraction::do_after`].
+#[derive(Clone, Copy)]
+pub struct DoAfter {
+    pub duration: Duration,
+    /// Cancel if the source and target get further apart than this
+    pub range: Option<f32>,
+    /// Cancel if the source or target moves
+    pub stationary: bool,
+}
+
+impl DoAfter {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            range: None,
+            stationary: false,
+        }
+    }
+
+    pub fn within_range(mut self, range: f32) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    pub fn stationary(mut self) -> Self {
+        self.stationary = true;
+        self
+    }
+}
+
+struct DoAfterState {
+    settings: DoAfter,
+    /// Where the source and target were when the do-after started
+    positions: Option<(Vec3, Vec3)>,
 }
 
+/// Sent when an interaction is canceled because the requirements of its do-after weren't met
+#[derive(Event)]
+pub struct DoAfterCanceled {
+    pub entity: Entity,
+    pub target: Entity,
+    pub reason: DoAfterCancelReason,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DoAfterCancelReason {
+    Moved,
+    OutOfRange,
+}
+
+/// How far something can shift before it counts as moving, so physics jitter doesn't cancel
+const DO_AFTER_MOVE_TOLERANCE: f32 = 0.1;
+
 // TODO: Restrict networking to owning player
 #[derive(Component, Networked, TypeUuid, Default)]
 #[uuid = "6af71909-2f7e-4020-846e-2496ed1faec5"]
@@ -230,17 +316,24 @@ impl Task for ExecuteInteraction {
     type Result = ();
 }
 
+#[allow(clippy::too_many_arguments)]
 fn begin_interaction_list(
     mut orders: EventReader<InteractionListOrder>,
     mut interaction_lists: ResMut<InteractionListEvents>,
+    mut sent: ResMut<SentInteractionLists>,
+    targeting: TargetValidation,
     identities: Res<NetworkIdentities>,
     players: Res<Players>,
     controls: Res<ClientControls>,
     bodies: Query<&Hands>,
     hand_query: Query<(Entity, &Container), With<Hand>>,
+    subdued: Query<&Subdued>,
 ) {
     for event in orders.iter() {
         let connection = event.connection;
+        // A new list replaces the old one, even if it can't be built
+        sent.map.remove(&connection);
+
         let Some(target) = identities.get_entity(event.target) else {
             warn!(connection=?connection, "Interaction list attempted for non-existent identity {:?}", event.target);
             continue;
@@ -254,10 +347,21 @@ fn begin_interaction_list(
             continue;
         };
 
+        if !targeting.is_plausible(player_entity, target, event.source) {
+            debug!(connection=?connection, target=?target, source=?event.source, "Rejected implausible interaction target");
+            continue;
+        }
+
+        let subdued = subdued.get(player_entity).ok();
+        if subdued.map_or(false, |s| s.is_stunned()) {
+            continue;
+        }
+
         // Fetch the used hand and item once here, as it's used in many interactions
         let hand = bodies
             .get(player_entity)
             .ok()
+            .filter(|_| subdued.map_or(true, |s| s.can_use_hands()))
             .and_then(|hands| hand_query.get(hands.active_hand()).ok());
         let item_in_hand =
             hand.and_then(|(_, container)| container.iter().next().map(|(_, item)| *item));
@@ -321,6 +425,7 @@ fn handle_interaction_list_request(
         orders.send(InteractionListOrder {
             connection: event.connection,
             target: event.message.target,
+            source: event.message.source,
             send_to_client: true,
         });
         debug!(connection=?event.connection, target=?event.message.target, "Interaction list requested");
@@ -335,6 +440,7 @@ fn handle_default_interaction_request(
         orders.send(InteractionListOrder {
             connection: event.connection,
             target: event.message.target,
+            source: event.message.source,
             send_to_client: false,
         });
         debug!(connection=?event.connection, target=?event.message.target, "Default interaction requested");
@@ -444,11 +550,57 @@ fn run_interactions(world: &mut World) {
                 target: task.target,
                 status: InteractionStatus::Running,
                 reflect_component: reflect_component.clone(),
+                do_after: None,
             });
         });
     });
 }
 
+fn check_do_afters(
+    mut interactions: Query<(Entity, &mut ActiveInteraction)>,
+    transforms: Query<&GlobalTransform>,
+    mut canceled: EventWriter<DoAfterCanceled>,
+) {
+    for (entity, mut active) in interactions.iter_mut() {
+        if active.status != InteractionStatus::Running {
+            continue;
+        }
+        let Some(state) = active.do_after.as_ref() else {
+            continue;
+        };
+        let (Ok(source), Ok(target)) = (transforms.get(entity), transforms.get(active.target))
+        else {
+            continue;
+        };
+        let current = (source.translation(), target.translation());
+        let Some(start) = state.positions else {
+            active.do_after.as_mut().unwrap().positions = Some(current);
+            continue;
+        };
+
+        let settings = state.settings;
+        let moved = start.0.distance(current.0) > DO_AFTER_MOVE_TOLERANCE
+            || start.1.distance(current.1) > DO_AFTER_MOVE_TOLERANCE;
+        let reason = if settings.stationary && moved {
+            DoAfterCancelReason::Moved
+        } else if settings
+            .range
+            .map_or(false, |range| current.0.distance(current.1) > range)
+        {
+            DoAfterCancelReason::OutOfRange
+        } else {
+            continue;
+        };
+
+        active.status = InteractionStatus::Canceled;
+        canceled.send(DoAfterCanceled {
+            entity,
+            target: active.target,
+            reason,
+        });
+    }
+}
+
 fn clear_completed_interactions(
     world: &mut World,
     query: &mut QueryState<(Entity, &ActiveInteraction), Changed<ActiveInteraction>>,
@@ -478,7 +630,8 @@ fn clear_completed_interactions(
 
 #[allow(clippy::too_many_arguments)]
 fn client_request_interaction_list(
-    buttons: Res<Input<MouseButton>>,
+    actions: Actions,
+    ui_state: Res<ClientInteractionUi>,
     mut contexts: EguiContexts,
     rapier_context: Res<RapierContext>,
     windows: Query<(Entity, &Window), With<PrimaryWindow>>,
@@ -488,12 +641,17 @@ fn client_request_interaction_list(
     combat_status: ClientCombatModeStatus,
     mut sender: MessageSender,
 ) {
-    let execute_default = buttons.just_pressed(MouseButton::Left);
-    let request_list = buttons.just_pressed(MouseButton::Right);
+    let execute_default = actions.just_pressed(Action::Interact);
+    let request_list = actions.just_pressed(Action::InteractionMenu);
     if !execute_default && !request_list {
         return;
     }
 
+    // The same button may select an entry of the open list instead
+    if ui_state.current.is_some() && actions.just_pressed(Action::MenuConfirm) {
+        return;
+    }
+
     // We prevent interaction with the world while fighting
     // so we can reuse the same mouse buttons for attacking
     if combat_status.is_enabled() {
@@ -540,16 +698,22 @@ fn client_request_interaction_list(
         return;
     };
 
+    let source = TargetSource::Cursor {
+        origin: ray.origin,
+        direction: ray.direction,
+    };
     if execute_default {
-        sender.send_to_server(&InteractionExecuteDefaultRequest { target });
+        sender.send_to_server(&InteractionExecuteDefaultRequest { target, source });
     } else {
-        sender.send_to_server(&InteractionListRequest { target });
+        sender.send_to_server(&InteractionListRequest { target, source });
     }
 }
 
 #[derive(Resource, Default)]
 struct ClientInteractionUi {
     current: Option<InteractionListClient>,
+    /// Entry highlighted for keyboard and gamepad navigation
+    selected: usize,
 }
 
 fn client_receive_interactions(
@@ -566,18 +730,43 @@ fn client_receive_interactions(
     } else {
         state.current = Some(event.message.clone());
     }
+    state.selected = 0;
 }
 
 fn client_interaction_selection_ui(
     mut contexts: EguiContexts,
     mut state: ResMut<ClientInteractionUi>,
     windows: Query<&Window, With<PrimaryWindow>>,
+    actions: Actions,
     mut sender: MessageSender,
 ) {
-    let Some(list) = &state.current else {
+    let Some(count) = state.current.as_ref().map(|l| l.interactions.len()) else {
         return;
     };
 
+    if actions.just_pressed(Action::MenuBack) {
+        state.current = None;
+        return;
+    }
+    // Don't count as a change, which would move the window to the cursor
+    let selected = &mut state.bypass_change_detection().selected;
+    if actions.just_pressed(Action::MenuDown) {
+        *selected = (*selected + 1) % count;
+    }
+    if actions.just_pressed(Action::MenuUp) {
+        *selected = (*selected + count - 1) % count;
+    }
+    if actions.just_pressed(Action::MenuConfirm) {
+        sender.send_to_server(&InteractionExecuteRequest {
+            index: state.selected,
+        });
+        state.current = None;
+        return;
+    }
+
+    let Some(list) = &state.current else {
+        return;
+    };
     let mut clear = false;
 
     let mut ui_window = egui::Window::new("Interact")
@@ -594,7 +783,10 @@ fn client_interaction_selection_ui(
 
     ui_window.show(contexts.ctx_mut(), |ui| {
         for (index, interaction) in list.interactions.iter().enumerate() {
-            if ui.button(&interaction.text).clicked() {
+            if ui
+                .selectable_label(index == state.selected, &interaction.text)
+                .clicked()
+            {
                 sender.send_to_server(&InteractionExecuteRequest { index });
                 clear = true;
             }
@@ -0,0 +1,133 @@
+//! Checks that targets picked by clients are plausible,
+//! so a modified client can't interact with things it isn't pointing at.
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_rapier3d::prelude::{QueryFilter, RapierContext};
+use networking::identity::NetworkIdentities;
+use serde::{Deserialize, Serialize};
+
+/// Furthest the camera can be from the controlled creature
+const MAX_CAMERA_DISTANCE: f32 = 20.0;
+/// How far a target can be from the cursor ray.
+/// Covers objects that moved while the request was in flight.
+const CURSOR_TOLERANCE: f32 = 1.0;
+/// How much closer than the target the server ray can hit something, before the target counts as hidden
+const OCCLUSION_TOLERANCE: f32 = 0.5;
+/// Furthest a target picked in a UI can be from the creature
+const UI_REACH: f32 = 3.0;
+const MAX_RAY_DISTANCE: f32 = 100.0;
+
+/// How the client picked the target of an interaction
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub enum TargetSource {
+    /// Clicked in the world, with the ray from the camera through the cursor
+    Cursor { origin: Vec3, direction: Vec3 },
+    /// Picked in a UI, like the item held in a hand.
+    /// The target still has to be in reach and not behind a wall.
+    Ui,
+}
+
+/// Compares targets reported by clients against the authoritative state of the server
+#[derive(SystemParam)]
+pub(crate) struct TargetValidation<'w, 's> {
+    rapier: Res<'w, RapierContext>,
+    identities: Res<'w, NetworkIdentities>,
+    transforms: Query<'w, 's, &'static GlobalTransform>,
+    parents: Query<'w, 's, &'static Parent>,
+}
+
+impl<'w, 's> TargetValidation<'w, 's> {
+    /// Could `actor` have picked `target` the way the client says it did?
+    pub fn is_plausible(&self, actor: Entity, target: Entity, source: TargetSource) -> bool {
+        let (Ok(actor_transform), Ok(target_transform)) =
+            (self.transforms.get(actor), self.transforms.get(target))
+        else {
+            return false;
+        };
+        let actor_position = actor_transform.translation();
+        let target_position = target_transform.translation();
+
+        let (origin, direction) = match source {
+            TargetSource::Ui => {
+                return actor_position.distance(target_position) <= UI_REACH
+                    && self.in_line_of_sight(actor, actor_position, target, target_position);
+            }
+            TargetSource::Cursor { origin, direction } => (origin, direction),
+        };
+
+        if !origin.is_finite()
+            || !direction.is_normalized()
+            || origin.distance(actor_position) > MAX_CAMERA_DISTANCE
+        {
+            warn!(
+                ?actor,
+                ?origin,
+                ?direction,
+                "Received impossible cursor ray"
+            );
+            return false;
+        }
+
+        let hit = self.rapier.cast_ray(
+            origin,
+            direction,
+            MAX_RAY_DISTANCE,
+            true,
+            QueryFilter::default().exclude_rigid_body(actor),
+        );
+        if let Some((entity, _)) = hit {
+            if self.network_entity(entity) == Some(target) {
+                return true;
+            }
+        }
+
+        // The client may have seen an older state, accept targets close to the ray
+        let along_ray = (target_position - origin).dot(direction);
+        if along_ray < 0.0 {
+            return false;
+        }
+        let closest = origin + direction * along_ray;
+        if closest.distance(target_position) > CURSOR_TOLERANCE {
+            return false;
+        }
+        // ...unless something is blocking the view
+        hit.map_or(true, |(_, toi)| toi + OCCLUSION_TOLERANCE >= along_ray)
+    }
+
+    /// Is nothing but the target, or what it is stored in, between the actor and the target?
+    fn in_line_of_sight(&self, actor: Entity, from: Vec3, target: Entity, to: Vec3) -> bool {
+        // Anything the actor carries is always in sight
+        if self.parents.iter_ancestors(target).any(|e| e == actor) {
+            return true;
+        }
+
+        let offset = to - from;
+        let distance = offset.length();
+        if distance <= f32::EPSILON {
+            return true;
+        }
+        let hit = self.rapier.cast_ray(
+            from,
+            offset / distance,
+            distance,
+            true,
+            QueryFilter::default().exclude_rigid_body(actor),
+        );
+        let Some((entity, toi)) = hit else {
+            return true;
+        };
+        if toi + OCCLUSION_TOLERANCE >= distance {
+            return true;
+        }
+        self.network_entity(entity).map_or(false, |hit| {
+            hit == target || self.parents.iter_ancestors(target).any(|e| e == hit)
+        })
+    }
+
+    /// The networked entity a collider belongs to
+    fn network_entity(&self, entity: Entity) -> Option<Entity> {
+        std::iter::once(entity)
+            .chain(self.parents.iter_ancestors(entity))
+            .find(|&e| self.identities.get_identity(e).is_some())
+    }
+}
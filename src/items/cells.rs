@@ -0,0 +1,580 @@
+use std::time::Duration;
+
+use bevy::{ecs::system::SystemParam, prelude::*, reflect::TypeUuid};
+use bevy_egui::{egui, EguiContexts};
+use networking::{
+    component::AppExt,
+    is_server,
+    time::{NetworkTick, TickTime},
+    variable::{NetworkVar, ServerVar},
+    Networked,
+};
+use power::{AreaPowerController, Powered};
+use utils::task::{TaskId, Tasks};
+
+use crate::{
+    body::ClientHeldItem,
+    interaction::{
+        ActiveInteraction, GenerateInteractionList, InteractionListEvents, InteractionOption,
+        InteractionSpecificity, InteractionStatus,
+    },
+    ui::has_window,
+    GameState,
+};
+
+use super::{
+    containers::{Container, MoveItem},
+    Item,
+};
+
+pub struct CellPlugin;
+
+impl Plugin for CellPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<PowerCell>()
+            .register_type::<PoweredDevice>()
+            .register_type::<CellCharger>()
+            .add_networked_component::<PowerCell, PowerCellClient>()
+            .add_networked_component::<PoweredDevice, PoweredDeviceClient>();
+        if is_server(app) {
+            app.register_type::<InsertCellInteraction>()
+                .register_type::<RemoveCellInteraction>()
+                .register_type::<ToggleDeviceInteraction>()
+                .add_systems(
+                    Update,
+                    (
+                        (
+                            prepare_insert_cell_interaction,
+                            prepare_remove_cell_interaction,
+                            prepare_toggle_device_interaction,
+                        )
+                            .in_set(GenerateInteractionList),
+                        insert_cell_interaction,
+                        remove_cell_interaction,
+                        toggle_device_interaction,
+                        update_cell_level,
+                    ),
+                )
+                .add_systems(NetworkTick, (drain_active_devices, charge_cells));
+        } else {
+            app.add_systems(
+                Update,
+                (
+                    client_update_device_lights,
+                    client_charge_ui
+                        .run_if(in_state(GameState::Game))
+                        .run_if(has_window),
+                ),
+            );
+        }
+    }
+}
+
+const SWAP_CELL_TIME: Duration = Duration::from_millis(800);
+
+/// A rechargeable battery, fitting into devices and chargers.
+#[derive(Component, Reflect, Networked)]
+#[reflect(Component)]
+#[networked(client = "PowerCellClient")]
+pub struct PowerCell {
+    /// Maximum charge in joules
+    capacity: f32,
+    /// Current charge in joules
+    charge: f32,
+    /// Charge in percent, kept for clients
+    #[reflect(ignore)]
+    level: NetworkVar<u8>,
+}
+
+impl Default for PowerCell {
+    fn default() -> Self {
+        Self {
+            capacity: 10_000.0,
+            charge: 10_000.0,
+            level: 100.into(),
+        }
+    }
+}
+
+impl PowerCell {
+    pub fn charge(&self) -> f32 {
+        self.charge
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.charge >= self.capacity
+    }
+
+    /// Takes energy out of the cell. Returns false without draining if there isn't enough.
+    pub fn consume(&mut self, energy: f32) -> bool {
+        if self.charge < energy {
+            return false;
+        }
+        self.charge -= energy;
+        true
+    }
+
+    /// Drains the cell, e.g. from an EMP. `fraction` goes from 0 to 1.
+    pub fn drain(&mut self, fraction: f32) {
+        self.charge *= 1.0 - fraction.clamp(0.0, 1.0);
+    }
+
+    fn add_charge(&mut self, energy: f32) {
+        self.charge = (self.charge + energy).min(self.capacity);
+    }
+}
+
+#[derive(Component, Default, TypeUuid, Networked)]
+#[uuid = "c3b85e12-7a4f-4f6d-9d1e-58a0b2e7f4c9"]
+#[networked(server = "PowerCell")]
+pub struct PowerCellClient {
+    level: ServerVar<u8>,
+}
+
+impl PowerCellClient {
+    /// Charge in percent
+    pub fn level(&self) -> u8 {
+        self.level.get().copied().unwrap_or_default()
+    }
+}
+
+/// An item running off a power cell stored in its container.
+#[derive(Component, Reflect, Networked)]
+#[reflect(Component)]
+#[networked(client = "PoweredDeviceClient")]
+pub struct PoweredDevice {
+    /// Power draw in watts while switched on
+    draw: f32,
+    /// If the device can be switched on and off by hand
+    switchable: bool,
+    #[reflect(ignore)]
+    active: NetworkVar<bool>,
+}
+
+impl Default for PoweredDevice {
+    fn default() -> Self {
+        Self {
+            draw: 0.0,
+            switchable: false,
+            active: false.into(),
+        }
+    }
+}
+
+impl PoweredDevice {
+    pub fn is_active(&self) -> bool {
+        *self.active
+    }
+
+    pub fn set_active(&mut self, active: bool) {
+        if *self.active != active {
+            *self.active = active;
+        }
+    }
+}
+
+#[derive(Component, Default, TypeUuid, Networked)]
+#[uuid = "5e0d7a93-2c61-4b8f-a4e7-13f9c6d2b085"]
+#[networked(server = "PoweredDevice")]
+pub struct PoweredDeviceClient {
+    active: ServerVar<bool>,
+}
+
+impl PoweredDeviceClient {
+    pub fn is_active(&self) -> bool {
+        self.active.get().copied().unwrap_or_default()
+    }
+}
+
+/// A wall mounted machine recharging the cell put into it from station power.
+/// The energy is taken from the APC supplying the charger.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct CellCharger {
+    /// Charging power in watts
+    rate: f32,
+}
+
+impl Default for CellCharger {
+    fn default() -> Self {
+        Self { rate: 500.0 }
+    }
+}
+
+/// Finds the cells inside devices
+#[derive(SystemParam)]
+pub struct DeviceCells<'w, 's> {
+    containers: Query<'w, 's, &'static Container>,
+    cells: Query<'w, 's, &'static mut PowerCell>,
+}
+
+impl<'w, 's> DeviceCells<'w, 's> {
+    pub fn cell_entity(&self, device: Entity) -> Option<Entity> {
+        let container = self.containers.get(device).ok()?;
+        container
+            .iter()
+            .map(|(_, &item)| item)
+            .find(|&item| self.cells.contains(item))
+    }
+
+    pub fn cell_mut(&mut self, device: Entity) -> Option<Mut<PowerCell>> {
+        let cell = self.cell_entity(device)?;
+        self.cells.get_mut(cell).ok()
+    }
+
+    /// Uses energy from the cell of a device, e.g. for a single stun
+    pub fn consume(&mut self, device: Entity, energy: f32) -> bool {
+        self.cell_mut(device)
+            .map(|mut cell| cell.consume(energy))
+            .unwrap_or(false)
+    }
+}
+
+fn drain_active_devices(
+    mut devices: Query<(Entity, &mut PoweredDevice)>,
+    mut cells: DeviceCells,
+    tick: Res<TickTime>,
+) {
+    for (entity, mut device) in devices.iter_mut() {
+        if !device.is_active() || device.draw <= 0.0 {
+            continue;
+        }
+        // Devices without enough charge switch off
+        if !cells.consume(entity, device.draw * tick.delta_seconds()) {
+            device.set_active(false);
+        }
+    }
+}
+
+fn charge_cells(
+    chargers: Query<(Entity, &CellCharger, Option<&Powered>)>,
+    mut apcs: Query<&mut AreaPowerController>,
+    mut cells: DeviceCells,
+    tick: Res<TickTime>,
+) {
+    for (entity, charger, powered) in chargers.iter() {
+        if !powered.map(|p| p.is_powered()).unwrap_or(true) {
+            continue;
+        }
+        let Some(mut cell) = cells.cell_mut(entity) else {
+            continue;
+        };
+        if cell.is_full() {
+            continue;
+        }
+        let mut energy = (charger.rate * tick.delta_seconds()).min(cell.capacity - cell.charge);
+        // Like other consumers, chargers outside of every APC's area run for free
+        if let Some(mut apc) = powered
+            .and_then(|p| p.supplier())
+            .and_then(|supplier| apcs.get_mut(supplier).ok())
+        {
+            energy = energy.min(apc.charge);
+            apc.charge -= energy;
+        }
+        cell.add_charge(energy);
+    }
+}
+
+fn update_cell_level(mut cells: Query<&mut PowerCell, Changed<PowerCell>>) {
+    for mut cell in cells.iter_mut() {
+        let level = (cell.charge / cell.capacity * 100.0).round() as u8;
+        if *cell.level != level {
+            *cell.level = level;
+        }
+    }
+}
+
+/// Things a cell can be put into
+type CellHolderFilter = Or<(With<PoweredDevice>, With<CellCharger>)>;
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct InsertCellInteraction {
+    cell: Entity,
+    #[reflect(ignore)]
+    move_task: Option<TaskId<MoveItem>>,
+}
+
+impl FromWorld for InsertCellInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            cell: Entity::PLACEHOLDER,
+            move_task: None,
+        }
+    }
+}
+
+fn prepare_insert_cell_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    cells: Query<&Item, With<PowerCell>>,
+    holders: Query<&Container, CellHolderFilter>,
+) {
+    for event in interaction_list.events.iter() {
+        let Some(cell) = event.item_in_hand else {
+            continue;
+        };
+        let Ok(cell_item) = cells.get(cell) else {
+            continue;
+        };
+        let Ok(container) = holders.get(event.target) else {
+            continue;
+        };
+        if !container.is_empty() {
+            continue;
+        }
+
+        event.add_interaction(InteractionOption {
+            text: format!("Insert {}", cell_item.name),
+            interaction: Box::new(InsertCellInteraction {
+                cell,
+                move_task: None,
+            }),
+            specificity: InteractionSpecificity::Specific,
+        });
+    }
+}
+
+fn insert_cell_interaction(
+    mut query: Query<(&mut InsertCellInteraction, &mut ActiveInteraction)>,
+    mut item_moves: ResMut<Tasks<MoveItem>>,
+    time: Res<Time>,
+) {
+    for (mut interaction, mut active) in query.iter_mut() {
+        active.set_initial_duration(SWAP_CELL_TIME);
+
+        let Some(task) = interaction.move_task else {
+            if active.start_time() + SWAP_CELL_TIME.as_secs_f32() > time.elapsed_seconds() {
+                continue;
+            }
+            interaction.move_task = Some(item_moves.create(MoveItem {
+                item: interaction.cell,
+                container: Some(active.target),
+                position: Some(UVec2::ZERO),
+            }));
+            continue;
+        };
+
+        let Some(result) = item_moves.result(task) else {
+            continue;
+        };
+        active.status = if result.was_success() {
+            InteractionStatus::Completed
+        } else {
+            InteractionStatus::Canceled
+        };
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct RemoveCellInteraction {
+    hand: Entity,
+    #[reflect(ignore)]
+    move_task: Option<TaskId<MoveItem>>,
+}
+
+impl FromWorld for RemoveCellInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            hand: Entity::PLACEHOLDER,
+            move_task: None,
+        }
+    }
+}
+
+fn prepare_remove_cell_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    holders: Query<(), CellHolderFilter>,
+    cells: DeviceCells,
+    items: Query<&Item>,
+) {
+    for event in interaction_list.events.iter() {
+        if event.item_in_hand.is_some() || !holders.contains(event.target) {
+            continue;
+        }
+        let Some(cell) = cells.cell_entity(event.target) else {
+            continue;
+        };
+        let Ok(cell_item) = items.get(cell) else {
+            continue;
+        };
+
+        event.add_interaction(InteractionOption {
+            text: format!("Remove {}", cell_item.name),
+            interaction: Box::new(RemoveCellInteraction {
+                hand: event.used_hand,
+                move_task: None,
+            }),
+            specificity: InteractionSpecificity::Specific,
+        });
+    }
+}
+
+fn remove_cell_interaction(
+    mut query: Query<(&mut RemoveCellInteraction, &mut ActiveInteraction)>,
+    mut devices: Query<&mut PoweredDevice>,
+    cells: DeviceCells,
+    mut item_moves: ResMut<Tasks<MoveItem>>,
+    time: Res<Time>,
+) {
+    for (mut interaction, mut active) in query.iter_mut() {
+        active.set_initial_duration(SWAP_CELL_TIME);
+
+        let Some(task) = interaction.move_task else {
+            if active.start_time() + SWAP_CELL_TIME.as_secs_f32() > time.elapsed_seconds() {
+                continue;
+            }
+            let Some(cell) = cells.cell_entity(active.target) else {
+                active.status = InteractionStatus::Canceled;
+                continue;
+            };
+            interaction.move_task = Some(item_moves.create(MoveItem {
+                item: cell,
+                container: Some(interaction.hand),
+                position: Some(UVec2::ZERO),
+            }));
+            continue;
+        };
+
+        let Some(result) = item_moves.result(task) else {
+            continue;
+        };
+        if !result.was_success() {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        }
+        if let Ok(mut device) = devices.get_mut(active.target) {
+            device.set_active(false);
+        }
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct ToggleDeviceInteraction {
+    device: Entity,
+}
+
+impl FromWorld for ToggleDeviceInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            device: Entity::PLACEHOLDER,
+        }
+    }
+}
+
+fn prepare_toggle_device_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    devices: Query<&PoweredDevice>,
+) {
+    for event in interaction_list.events.iter() {
+        let Some(item) = event.item_in_hand else {
+            continue;
+        };
+        let Ok(device) = devices.get(item) else {
+            continue;
+        };
+        if !device.switchable {
+            continue;
+        }
+
+        let text = if device.is_active() {
+            "Switch off"
+        } else {
+            "Switch on"
+        };
+        event.add_interaction(InteractionOption {
+            text: text.into(),
+            interaction: Box::new(ToggleDeviceInteraction { device: item }),
+            specificity: InteractionSpecificity::Generic,
+        });
+    }
+}
+
+fn toggle_device_interaction(
+    mut query: Query<(&ToggleDeviceInteraction, &mut ActiveInteraction)>,
+    mut devices: Query<&mut PoweredDevice>,
+    mut cells: DeviceCells,
+) {
+    for (interaction, mut active) in query.iter_mut() {
+        let Ok(mut device) = devices.get_mut(interaction.device) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+
+        if device.is_active() {
+            device.set_active(false);
+        } else {
+            // Can't switch on with an empty cell
+            let has_charge = cells
+                .cell_mut(interaction.device)
+                .map(|cell| cell.charge() > 0.0)
+                .unwrap_or(false);
+            if !has_charge {
+                active.status = InteractionStatus::Canceled;
+                continue;
+            }
+            device.set_active(true);
+        }
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+/// Shows lights of devices only while they are switched on
+fn client_update_device_lights(
+    devices: Query<(Entity, &PoweredDeviceClient), Changed<PoweredDeviceClient>>,
+    children: Query<&Children>,
+    mut lights: Query<&mut Visibility, With<PointLight>>,
+) {
+    for (entity, device) in devices.iter() {
+        let visibility = if device.is_active() {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        let mut iter = lights.iter_many_mut(children.iter_descendants(entity));
+        while let Some(mut light) = iter.fetch_next() {
+            *light = visibility;
+        }
+    }
+}
+
+/// Shows the charge of the held cell or device
+fn client_charge_ui(
+    mut contexts: EguiContexts,
+    held_item: ClientHeldItem,
+    cells: Query<&PowerCellClient>,
+    devices: Query<&PoweredDeviceClient>,
+    children: Query<&Children>,
+) {
+    let Some(item) = held_item.get() else {
+        return;
+    };
+    let level = match cells.get(item) {
+        Ok(cell) => Some(cell.level()),
+        Err(_) if devices.contains(item) => children
+            .iter_descendants(item)
+            .find_map(|child| cells.get(child).ok())
+            .map(|cell| cell.level()),
+        Err(_) => return,
+    };
+
+    egui::Window::new("Charge")
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::Vec2::new(0.0, -120.0))
+        .resizable(false)
+        .title_bar(false)
+        .show(contexts.ctx_mut(), |ui| match level {
+            Some(level) => {
+                ui.add(
+                    egui::ProgressBar::new(level as f32 / 100.0).text(format!("Charge {}%", level)),
+                );
+            }
+            None => {
+                ui.label("No cell");
+            }
+        });
+}
@@ -0,0 +1,269 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use networking::{is_server, ConnectionId};
+
+use crate::{
+    body::Body,
+    config::ServerConfig,
+    despawn::{ContentsPolicy, DespawnCommandsExt},
+};
+
+use super::{containers::Container, Item, StoredItem};
+
+pub(crate) struct ItemCleanupPlugin;
+
+impl Plugin for ItemCleanupPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Trash>()
+            .register_type::<Unique>()
+            .add_event::<ItemCleanupRequest>()
+            .add_event::<ItemCleanupFinished>();
+
+        if is_server(app) {
+            app.init_resource::<ItemCleanupPolicy>()
+                .init_resource::<ItemCleanupStats>()
+                .add_systems(Update, (track_loose_items, sweep_loose_items).chain());
+        }
+    }
+}
+
+/// Marks an item as worthless, so it can be cleaned up sooner
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Trash;
+
+/// Marks an item that must never be cleaned up
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct Unique;
+
+/// Since when an item has been outside of a container, in seconds since startup
+#[derive(Component)]
+struct LooseItem {
+    since: f32,
+}
+
+/// How long items can lie around outside of containers
+#[derive(Resource)]
+pub struct ItemCleanupPolicy {
+    /// `None` keeps trash forever
+    pub trash_lifetime: Option<Duration>,
+    /// `None` keeps other items forever
+    pub loose_lifetime: Option<Duration>,
+    pub sweep_timer: Timer,
+}
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+impl FromWorld for ItemCleanupPolicy {
+    fn from_world(world: &mut World) -> Self {
+        let config = world
+            .get_resource::<ServerConfig>()
+            .map(|c| &c.item_cleanup);
+        let minutes = |m: Option<f32>| m.map(|m| Duration::from_secs_f32(m * 60.0));
+        Self {
+            trash_lifetime: minutes(config.and_then(|c| c.trash_minutes)),
+            loose_lifetime: minutes(config.and_then(|c| c.loose_minutes)),
+            sweep_timer: Timer::new(SWEEP_INTERVAL, TimerMode::Repeating),
+        }
+    }
+}
+
+impl ItemCleanupPolicy {
+    fn lifetime(&self, trash: bool) -> Option<Duration> {
+        if trash {
+            self.trash_lifetime
+        } else {
+            self.loose_lifetime
+        }
+    }
+}
+
+/// Numbers about cleaned up items since the server started
+#[derive(Resource, Default, Clone, Copy)]
+pub struct ItemCleanupStats {
+    pub sweeps: u32,
+    pub total_reclaimed: u64,
+    pub last_reclaimed: u32,
+    /// How many items were outside of containers during the last sweep
+    pub loose_items: u32,
+}
+
+/// Run a cleanup sweep right away
+#[derive(Event)]
+pub(crate) struct ItemCleanupRequest {
+    pub(crate) requested_by: Option<ConnectionId>,
+}
+
+#[derive(Event)]
+pub(crate) struct ItemCleanupFinished {
+    pub(crate) requested_by: Option<ConnectionId>,
+    pub(crate) stats: ItemCleanupStats,
+}
+
+/// Limbs and organs are items, but aren't loose while they are attached to a body
+fn is_body_part(entity: Entity, parents: &Query<&Parent>, bodies: &Query<(), With<Body>>) -> bool {
+    parents
+        .iter_ancestors(entity)
+        .any(|ancestor| bodies.contains(ancestor))
+}
+
+fn track_loose_items(
+    new_loose: Query<Entity, (With<Item>, Without<StoredItem>, Without<LooseItem>)>,
+    stored: Query<Entity, (With<LooseItem>, With<StoredItem>)>,
+    parents: Query<&Parent>,
+    bodies: Query<(), With<Body>>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds();
+    for entity in new_loose.iter() {
+        if is_body_part(entity, &parents, &bodies) {
+            continue;
+        }
+        commands.entity(entity).insert(LooseItem { since: now });
+    }
+    for entity in stored.iter() {
+        commands.entity(entity).remove::<LooseItem>();
+    }
+}
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn sweep_loose_items(
+    mut policy: ResMut<ItemCleanupPolicy>,
+    mut stats: ResMut<ItemCleanupStats>,
+    mut requests: EventReader<ItemCleanupRequest>,
+    mut finished: EventWriter<ItemCleanupFinished>,
+    items: Query<
+        (Entity, &LooseItem, Has<Trash>, Option<&Container>),
+        (Without<StoredItem>, Without<Unique>),
+    >,
+    parents: Query<&Parent>,
+    bodies: Query<(), With<Body>>,
+    mut commands: Commands,
+    time: Res<Time>,
+) {
+    let requested_by: Vec<_> = requests.iter().map(|r| r.requested_by).collect();
+    let scheduled = policy.sweep_timer.tick(time.delta()).just_finished();
+    if !scheduled && requested_by.is_empty() {
+        return;
+    }
+
+    let now = time.elapsed_seconds();
+    let mut reclaimed = 0;
+    let mut loose = 0;
+    for (entity, item, trash, container) in items.iter() {
+        // Attached to a body since it was dropped
+        if is_body_part(entity, &parents, &bodies) {
+            commands.entity(entity).remove::<LooseItem>();
+            continue;
+        }
+        loose += 1;
+        // Don't throw away anything stored inside
+        if container.map(|c| !c.is_empty()).unwrap_or_default() {
+            continue;
+        }
+        let Some(lifetime) = policy.lifetime(trash) else {
+            continue;
+        };
+        if now - item.since < lifetime.as_secs_f32() {
+            continue;
+        }
+
+        commands.despawn_cascade(entity, ContentsPolicy::Destroy);
+        reclaimed += 1;
+    }
+
+    stats.sweeps += 1;
+    stats.last_reclaimed = reclaimed;
+    stats.total_reclaimed += reclaimed as u64;
+    stats.loose_items = loose - reclaimed;
+    if reclaimed > 0 {
+        info!(
+            reclaimed,
+            remaining = stats.loose_items,
+            total = stats.total_reclaimed,
+            "Cleaned up loose items"
+        );
+    }
+
+    for requested_by in requested_by {
+        finished.send(ItemCleanupFinished {
+            requested_by,
+            stats: *stats,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::prelude::*;
+
+    use super::{
+        sweep_loose_items, track_loose_items, ItemCleanupFinished, ItemCleanupPolicy,
+        ItemCleanupRequest, ItemCleanupStats, SWEEP_INTERVAL,
+    };
+    use crate::{body::Body, items::Item};
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .insert_resource(ItemCleanupPolicy {
+                trash_lifetime: Some(Duration::ZERO),
+                loose_lifetime: Some(Duration::ZERO),
+                sweep_timer: Timer::new(SWEEP_INTERVAL, TimerMode::Repeating),
+            })
+            .init_resource::<ItemCleanupStats>()
+            .add_event::<ItemCleanupRequest>()
+            .add_event::<ItemCleanupFinished>()
+            .add_systems(
+                Update,
+                (track_loose_items, apply_deferred, sweep_loose_items).chain(),
+            );
+        app
+    }
+
+    fn sweep(app: &mut App) {
+        app.world
+            .send_event(ItemCleanupRequest { requested_by: None });
+        app.update();
+    }
+
+    #[test]
+    fn loose_items_are_removed() {
+        let mut app = app();
+        let item = app.world.spawn(Item::default()).id();
+
+        sweep(&mut app);
+
+        assert!(app.world.get_entity(item).is_none());
+    }
+
+    #[test]
+    fn creature_survives_sweep() {
+        let mut app = app();
+        let organ = app.world.spawn(Item::default()).id();
+        let limb = app.world.spawn(Item::default()).add_child(organ).id();
+        let creature = app.world.spawn(Body::default()).add_child(limb).id();
+
+        sweep(&mut app);
+
+        assert!(app.world.get_entity(creature).is_some());
+        assert!(app.world.get_entity(limb).is_some());
+        assert!(app.world.get_entity(organ).is_some());
+    }
+
+    #[test]
+    fn severed_limb_is_removed() {
+        let mut app = app();
+        let limb = app.world.spawn(Item::default()).id();
+        app.world.spawn(Body::default());
+
+        sweep(&mut app);
+
+        assert!(app.world.get_entity(limb).is_none());
+    }
+}
@@ -1,19 +1,27 @@
 #![allow(clippy::too_many_arguments)]
 
-use bevy::{prelude::*, utils::HashMap};
+use std::time::Duration;
+
+use bevy::{prelude::*, reflect::TypeUuid, utils::HashMap};
 use bevy_egui::{egui, EguiContexts};
 use networking::{
+    component::AppExt as ComponentAppExt,
     identity::{NetworkIdentities, NetworkIdentity},
     is_server,
     messaging::{AppExt, MessageEvent, MessageSender},
     spawning::{ClientControlled, ClientControls},
-    Players,
+    variable::{NetworkVar, ServerVar},
+    Networked, Players,
 };
 use serde::{Deserialize, Serialize};
 use utils::task::{Task, TaskId, TaskStatus, Tasks};
 
 use crate::{
     body::{ClientHeldItem, Hands},
+    interaction::{
+        ActiveInteraction, GenerateInteractionList, InteractionListEvents, InteractionOption,
+        InteractionSpecificity, InteractionStatus,
+    },
     ui::has_window,
     GameState,
 };
@@ -29,20 +37,29 @@ impl Plugin for ClothingPlugin {
     fn build(&self, app: &mut App) {
         app.register_type::<Clothing>()
             .register_type::<ClothingHolder>()
+            .add_networked_component::<Equipped, EquippedClient>()
             .add_network_message::<EquipClothingMessage>()
             .add_network_message::<UnequipClothingMessage>();
 
         if is_server(app) {
-            app.init_resource::<Tasks<EquipClothing>>().add_systems(
-                Update,
-                (
-                    handle_equip_clothing_message
-                        .run_if(on_event::<MessageEvent<EquipClothingMessage>>()),
-                    handle_unequip_clothing_message
-                        .run_if(on_event::<MessageEvent<UnequipClothingMessage>>()),
-                    process_equip_clothing.in_set(EquipClothingSystem),
-                ),
-            );
+            app.init_resource::<Tasks<EquipClothing>>()
+                .register_type::<EquipInteraction>()
+                .register_type::<UnequipInteraction>()
+                .add_systems(
+                    Update,
+                    (
+                        handle_equip_clothing_message
+                            .run_if(on_event::<MessageEvent<EquipClothingMessage>>()),
+                        handle_unequip_clothing_message
+                            .run_if(on_event::<MessageEvent<UnequipClothingMessage>>()),
+                        process_equip_clothing.in_set(EquipClothingSystem),
+                        update_equipped_state,
+                        (prepare_equip_interaction, prepare_unequip_interaction)
+                            .in_set(GenerateInteractionList),
+                        equip_interaction,
+                        unequip_interaction,
+                    ),
+                );
         } else {
             app.add_systems(
                 Update,
@@ -86,6 +103,65 @@ impl FromWorld for ClothingHolder {
     }
 }
 
+/// Marks a piece of clothing that is currently worn.
+#[derive(Component, Networked)]
+#[networked(client = "EquippedClient")]
+pub struct Equipped {
+    /// Clothing type of the slot it's worn in
+    slot: NetworkVar<String>,
+}
+
+impl Equipped {
+    pub fn slot(&self) -> &str {
+        &self.slot
+    }
+}
+
+#[derive(Component, Default, TypeUuid, Networked)]
+#[uuid = "6a1f3e8c-9b27-4d05-8e4a-c2f7d1b09e36"]
+#[networked(server = "Equipped")]
+pub struct EquippedClient {
+    slot: ServerVar<String>,
+}
+
+impl EquippedClient {
+    pub fn slot(&self) -> Option<&str> {
+        self.slot.get().map(|s| s.as_str())
+    }
+}
+
+/// Keeps [`Equipped`] in sync with which clothing is stored in a clothing holder
+fn update_equipped_state(
+    stored: Query<(Entity, &StoredItem, Option<&Equipped>), (With<Clothing>, Changed<StoredItem>)>,
+    dropped: Query<Entity, (With<Equipped>, Without<StoredItem>)>,
+    holders: Query<&ClothingHolder>,
+    mut commands: Commands,
+) {
+    for (entity, stored_item, equipped) in stored.iter() {
+        match holders.get(stored_item.container()) {
+            Ok(holder) => {
+                if equipped
+                    .map(|e| e.slot() != holder.clothing_type)
+                    .unwrap_or(true)
+                {
+                    commands.entity(entity).insert(Equipped {
+                        slot: holder.clothing_type.clone().into(),
+                    });
+                }
+            }
+            Err(_) => {
+                if equipped.is_some() {
+                    commands.entity(entity).remove::<Equipped>();
+                }
+            }
+        }
+    }
+
+    for entity in dropped.iter() {
+        commands.entity(entity).remove::<Equipped>();
+    }
+}
+
 pub struct EquipClothing {
     pub creature: Entity,
     pub clothing: Entity,
@@ -179,6 +255,15 @@ struct UnequipClothingMessage {
     clothing: NetworkIdentity,
 }
 
+/// Where slots are shown in the paperdoll, by clothing type.
+/// Slot types appearing twice are filled by multiple holders of that type, e.g. both hands.
+const PAPERDOLL_LAYOUT: [[Option<&str>; 3]; 4] = [
+    [None, Some("head"), None],
+    [Some("id"), Some("torso"), Some("back")],
+    [Some("hands"), Some("belt"), Some("hands")],
+    [Some("feet"), None, Some("feet")],
+];
+
 fn client_clothing_ui(
     mut contexts: EguiContexts,
     bodies: Query<Entity, With<ClientControlled>>,
@@ -191,54 +276,82 @@ fn client_clothing_ui(
     let Ok(body_entity) = bodies.get_single() else {
         return;
     };
-    let holders = child_query
+    let mut holders: Vec<_> = child_query
         .iter_descendants(body_entity)
-        .filter_map(|e| clothing_holders.get(e).ok());
+        .filter_map(|e| clothing_holders.get(e).ok())
+        .collect();
     let held_item = held_item.get();
     let held_clothing = held_item.and_then(|item| clothing.get(item).ok());
 
-    egui::Window::new("Clothing")
+    let mut slot_ui = |ui: &mut egui::Ui,
+                       (holder_id, holder, holder_children): (
+        &NetworkIdentity,
+        &ClothingHolder,
+        Option<&Children>,
+    )| {
+        ui.vertical(|ui| {
+            // Check if clothing is equipped on the slot
+            let clothing_in_slot =
+                holder_children.and_then(|children| clothing.iter_many(children).next());
+
+            ui.label(egui::RichText::new(holder.clothing_type.as_str()).small());
+            match clothing_in_slot {
+                Some((_, item, &clothing_id)) => {
+                    // Button to unequip worn clothing
+                    let button =
+                        ui.add_enabled(held_item.is_none(), egui::Button::new(item.name.as_str()));
+                    if button.on_hover_text("Unequip").clicked() {
+                        sender.send_to_server(&UnequipClothingMessage {
+                            clothing: clothing_id,
+                        });
+                    }
+                }
+                None => {
+                    // Button to equip held clothing
+                    let fits = held_clothing
+                        .filter(|(clothing, ..)| clothing.clothing_type == holder.clothing_type);
+                    let button = ui.add_enabled(fits.is_some(), egui::Button::new("empty"));
+                    if let Some((_, _, &clothing_id)) = fits {
+                        if button.on_hover_text("Equip").clicked() {
+                            sender.send_to_server(&EquipClothingMessage {
+                                body_part: *holder_id,
+                                clothing: clothing_id,
+                            });
+                        }
+                    }
+                }
+            }
+        });
+    };
+
+    egui::Window::new("Equipment")
         .anchor(egui::Align2::LEFT_BOTTOM, egui::Vec2::ZERO)
         .resizable(false)
         .show(contexts.ctx_mut(), |ui| {
-            for (holder_id, holder, holder_children) in holders {
-                ui.horizontal(|ui| {
-                    // Check if clothing is equipped on the slot
-                    let clothing_in_slot =
-                        holder_children.and_then(|children| clothing.iter_many(children).next());
-
-                    // Label slot
-                    ui.label(format!(
-                        "{} - {}",
-                        holder.clothing_type,
-                        if let Some((_, item, _)) = clothing_in_slot {
-                            &item.name
-                        } else {
-                            "empty"
-                        }
-                    ));
-
-                    if let Some((_, _, &clothing_id)) = clothing_in_slot {
-                        // Button to unequip worn clothing
-                        if held_item.is_none() && ui.button("Unequip").clicked() {
-                            sender.send_to_server(&UnequipClothingMessage {
-                                clothing: clothing_id,
+            egui::Grid::new("paperdoll")
+                .min_col_width(80.0)
+                .show(ui, |ui| {
+                    for row in PAPERDOLL_LAYOUT {
+                        for cell in row {
+                            let index = cell.and_then(|slot_type| {
+                                holders
+                                    .iter()
+                                    .position(|(_, h, _)| h.clothing_type == slot_type)
                             });
-                        }
-                    } else {
-                        // Button to equip held clothing
-                        if let Some((clothing, _, &clothing_id)) = held_clothing {
-                            if clothing.clothing_type == holder.clothing_type
-                                && ui.button("Equip").clicked()
-                            {
-                                sender.send_to_server(&EquipClothingMessage {
-                                    body_part: *holder_id,
-                                    clothing: clothing_id,
-                                });
+                            match index {
+                                Some(index) => slot_ui(ui, holders.remove(index)),
+                                None => {
+                                    ui.label("");
+                                }
                             }
                         }
+                        ui.end_row();
                     }
                 });
+
+            // Slots the layout doesn't know about
+            for holder in holders.drain(..) {
+                slot_ui(ui, holder);
             }
         });
 }
@@ -345,3 +458,204 @@ fn handle_unequip_clothing_message(
         });
     }
 }
+
+/// Time to put clothing on or off yourself
+const DRESS_SELF_TIME: Duration = Duration::from_secs(1);
+/// Time to put clothing on or off someone else
+const DRESS_OTHER_TIME: Duration = Duration::from_secs(4);
+
+fn dress_time(active: &ActiveInteraction, user: Entity) -> Duration {
+    if active.target == user {
+        DRESS_SELF_TIME
+    } else {
+        DRESS_OTHER_TIME
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct EquipInteraction {
+    clothing: Entity,
+    slot: Entity,
+    #[reflect(ignore)]
+    task: Option<TaskId<EquipClothing>>,
+}
+
+impl FromWorld for EquipInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            clothing: Entity::PLACEHOLDER,
+            slot: Entity::PLACEHOLDER,
+            task: None,
+        }
+    }
+}
+
+fn prepare_equip_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    clothes: Query<(&Clothing, &Item), Without<Equipped>>,
+    holders: Query<(Entity, &ClothingHolder, &Container)>,
+    creatures: Query<(), With<Hands>>,
+    child_query: Query<&Children>,
+) {
+    for event in interaction_list.events.iter() {
+        let Some(item) = event.item_in_hand else {
+            continue;
+        };
+        let Ok((clothing, clothing_item)) = clothes.get(item) else {
+            continue;
+        };
+        if !creatures.contains(event.target) {
+            continue;
+        }
+
+        // Offer the first free slot that fits
+        let slot = holders
+            .iter_many(child_query.iter_descendants(event.target))
+            .find(|(_, holder, container)| {
+                holder.clothing_type == clothing.clothing_type && container.is_empty()
+            })
+            .map(|(entity, ..)| entity);
+        let Some(slot) = slot else {
+            continue;
+        };
+
+        let text = if event.target == event.source {
+            format!("Wear {}", clothing_item.name)
+        } else {
+            format!("Dress with {}", clothing_item.name)
+        };
+        event.add_interaction(InteractionOption {
+            text,
+            interaction: Box::new(EquipInteraction {
+                clothing: item,
+                slot,
+                task: None,
+            }),
+            specificity: InteractionSpecificity::Common,
+        });
+    }
+}
+
+fn equip_interaction(
+    mut query: Query<(Entity, &mut EquipInteraction, &mut ActiveInteraction)>,
+    mut equips: ResMut<Tasks<EquipClothing>>,
+    time: Res<Time>,
+) {
+    for (user, mut interaction, mut active) in query.iter_mut() {
+        let duration = dress_time(&active, user);
+        active.set_initial_duration(duration);
+
+        let Some(task) = interaction.task else {
+            if active.start_time() + duration.as_secs_f32() > time.elapsed_seconds() {
+                continue;
+            }
+            interaction.task = Some(equips.create(EquipClothing {
+                creature: active.target,
+                clothing: interaction.clothing,
+                slot: Some(interaction.slot),
+            }));
+            continue;
+        };
+
+        let Some(result) = equips.result(task) else {
+            continue;
+        };
+        active.status = match result {
+            Ok(()) => InteractionStatus::Completed,
+            Err(()) => InteractionStatus::Canceled,
+        };
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct UnequipInteraction {
+    clothing: Entity,
+    #[reflect(ignore)]
+    task: Option<TaskId<MoveItem>>,
+}
+
+impl FromWorld for UnequipInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            clothing: Entity::PLACEHOLDER,
+            task: None,
+        }
+    }
+}
+
+fn prepare_unequip_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    clothes: Query<(Entity, &Item), With<Equipped>>,
+    creatures: Query<(), With<Hands>>,
+    child_query: Query<&Children>,
+) {
+    for event in interaction_list.events.iter() {
+        // Clothing is taken off into an empty hand
+        if event.item_in_hand.is_some() || !creatures.contains(event.target) {
+            continue;
+        }
+
+        for (clothing, item) in clothes.iter_many(child_query.iter_descendants(event.target)) {
+            event.add_interaction(InteractionOption {
+                text: format!("Take off {}", item.name),
+                interaction: Box::new(UnequipInteraction {
+                    clothing,
+                    task: None,
+                }),
+                specificity: InteractionSpecificity::Common,
+            });
+        }
+    }
+}
+
+fn unequip_interaction(
+    mut query: Query<(Entity, &mut UnequipInteraction, &mut ActiveInteraction)>,
+    hands: Query<&Hands>,
+    containers: Query<&Container>,
+    clothes: Query<(), With<Equipped>>,
+    mut item_moves: ResMut<Tasks<MoveItem>>,
+    time: Res<Time>,
+) {
+    for (user, mut interaction, mut active) in query.iter_mut() {
+        let duration = dress_time(&active, user);
+        active.set_initial_duration(duration);
+
+        let Some(task) = interaction.task else {
+            if !clothes.contains(interaction.clothing) {
+                active.status = InteractionStatus::Canceled;
+                continue;
+            }
+            if active.start_time() + duration.as_secs_f32() > time.elapsed_seconds() {
+                continue;
+            }
+
+            let Some(hand) = hands.get(user).ok().map(|h| h.active_hand()) else {
+                active.status = InteractionStatus::Canceled;
+                continue;
+            };
+            if !containers.get(hand).map(|c| c.is_empty()).unwrap_or(false) {
+                active.status = InteractionStatus::Canceled;
+                continue;
+            }
+            interaction.task = Some(item_moves.create(MoveItem {
+                item: interaction.clothing,
+                container: Some(hand),
+                position: Some(UVec2::ZERO),
+            }));
+            continue;
+        };
+
+        let Some(result) = item_moves.result(task) else {
+            continue;
+        };
+        active.status = if result.was_success() {
+            InteractionStatus::Completed
+        } else {
+            InteractionStatus::Canceled
+        };
+    }
+}
@@ -1,6 +1,7 @@
 use bevy::{
+    ecs::system::{CommandQueue, SystemParam},
     prelude::*,
-    utils::{HashMap, HashSet},
+    utils::{hashbrown::hash_map::Entry, HashMap, HashSet},
 };
 use networking::{
     identity::NetworkIdentity,
@@ -12,6 +13,8 @@ use networking::{
 use physics::PhysicsEntityCommands;
 use utils::task::{Task, Tasks};
 
+use crate::interaction::ActiveInteraction;
+
 use super::{Item, StoredItem};
 
 mod ui;
@@ -24,21 +27,31 @@ impl Plugin for ContainerPlugin {
             .register_type::<DisplayContainer>();
         if is_server(app) {
             app.init_resource::<Tasks<MoveItem>>()
+                .init_resource::<Tasks<MoveItems>>()
                 .init_resource::<ContainerItems>()
+                .init_resource::<ItemReservations>()
                 .add_systems(
                     PreUpdate,
                     item_in_container_visibility
                         .in_set(NetworkSet::ServerVisibility)
                         .after(VisibilitySystem::GridVisibility),
                 )
-                .add_systems(Update, (cleanup_deleted_entities, do_item_move).chain());
+                .add_systems(
+                    Update,
+                    (
+                        cleanup_deleted_entities,
+                        release_item_reservations,
+                        do_item_move,
+                    )
+                        .chain(),
+                );
         }
 
         app.add_plugins(ui::ContainerUiPlugin);
     }
 }
 
-#[derive(Component, Reflect)]
+#[derive(Component, Reflect, Clone)]
 #[reflect(Component)]
 pub struct Container {
     size: UVec2,
@@ -149,113 +162,276 @@ impl Task for MoveItem {
 
 pub struct MoveItemResult {
     success: bool,
+    taken: bool,
 }
 
 impl MoveItemResult {
+    fn failed() -> Self {
+        Self {
+            success: false,
+            taken: false,
+        }
+    }
+
     pub fn was_success(&self) -> bool {
         self.success
     }
+
+    /// The move failed because someone else grabbed the item first
+    pub fn was_taken(&self) -> bool {
+        self.taken
+    }
 }
 
-fn do_item_move(
-    mut tasks: ResMut<Tasks<MoveItem>>,
-    mut containers: Query<&mut Container>,
-    mut items: Query<(Entity, &Item, Option<&mut StoredItem>)>,
-    mut container_items: ResMut<ContainerItems>,
-    global_transforms: Query<&GlobalTransform>,
-    only_items: Query<&Item>,
-    mut commands: Commands,
+/// Items claimed by an interaction that is going to move them.
+/// A reservation lasts as long as the interaction, others can't reserve the item meanwhile.
+#[derive(Resource, Default)]
+pub struct ItemReservations {
+    /// Entity doing the interaction and when it started, by item
+    items: HashMap<Entity, (Entity, f32)>,
+}
+
+impl ItemReservations {
+    /// Reserves an item for the interaction of `by`.
+    /// Returns false if the item is already reserved by someone else.
+    pub fn reserve(&mut self, item: Entity, by: Entity, interaction: &ActiveInteraction) -> bool {
+        let reservation = (by, interaction.start_time());
+        match self.items.entry(item) {
+            Entry::Occupied(entry) => *entry.get() == reservation,
+            Entry::Vacant(entry) => {
+                entry.insert(reservation);
+                true
+            }
+        }
+    }
+
+    /// If the item is reserved by anyone other than `by`
+    pub fn is_reserved_by_other(&self, item: Entity, by: Entity) -> bool {
+        self.items
+            .get(&item)
+            .map_or(false, |&(holder, _)| holder != by)
+    }
+}
+
+/// Releases reservations of interactions that ended
+fn release_item_reservations(
+    mut reservations: ResMut<ItemReservations>,
+    interactions: Query<&ActiveInteraction>,
 ) {
-    tasks.process(|data| {
-        let Ok((item_entity, item, mut stored)) = items.get_mut(data.item) else {
-            warn!(task = ?data, "Failed to move item because it does not have an item component");
-            return MoveItemResult { success: false };
-        };
+    reservations.items.retain(|_, (by, started)| {
+        interactions
+            .get(*by)
+            .map_or(false, |interaction| interaction.start_time() == *started)
+    });
+}
+
+/// Moves a set of items at once. Either all of them are moved or none are.
+///
+/// All items are taken out of their containers before any is put into its new place,
+/// so items can trade places, e.g. swapping a held item with a pocket item.
+#[derive(Debug)]
+pub struct MoveItems {
+    pub moves: Vec<MoveItem>,
+}
+
+impl Task for MoveItems {
+    type Result = MoveItemResult;
+}
+
+#[derive(SystemParam)]
+struct ItemMover<'w, 's> {
+    containers: Query<'w, 's, &'static mut Container>,
+    items: Query<'w, 's, Option<&'static mut StoredItem>, With<Item>>,
+    only_items: Query<'w, 's, &'static Item>,
+    container_items: ResMut<'w, ContainerItems>,
+    global_transforms: Query<'w, 's, &'static GlobalTransform>,
+    commands: Commands<'w, 's>,
+}
 
-        if data.container == Some(data.item) {
-            error!(task = ?data, "Tried to store a container inside itself");
-            return MoveItemResult { success: false };
+impl<'w, 's> ItemMover<'w, 's> {
+    /// Checks that all moves can happen together, returning where each item ends up.
+    /// Works on copies of the affected containers, so nothing changes if any move fails.
+    fn plan(&self, moves: &[MoveItem]) -> Option<Vec<Option<(Entity, UVec2)>>> {
+        let mut scratch: HashMap<Entity, Container> = HashMap::default();
+        let mut seen = HashSet::new();
+
+        // Take every item out first
+        for data in moves {
+            let Ok(stored) = self.items.get(data.item) else {
+                warn!(task = ?data, "Failed to move item because it does not have an item component");
+                return None;
+            };
+            if data.container == Some(data.item) {
+                error!(task = ?data, "Tried to store a container inside itself");
+                return None;
+            }
+            if !seen.insert(data.item) {
+                error!(task = ?data, "Tried to move the same item twice at once");
+                return None;
+            }
+            if let Some(stored) = stored {
+                let container =
+                    scratch_container(&mut scratch, &self.containers, stored.container())?;
+                container.remove_item(data.item);
+            }
         }
 
-        // Remove from old container if it exists
-        if let Some(stored) = stored.as_mut() {
-            let mut container = containers.get_mut(*stored.container).unwrap();
+        let mut destinations = Vec::with_capacity(moves.len());
+        for data in moves {
+            let Some(container_entity) = data.container else {
+                destinations.push(None);
+                continue;
+            };
+            let Some(container) =
+                scratch_container(&mut scratch, &self.containers, container_entity)
+            else {
+                warn!(task = ?data, "Failed to move item because target is not a container");
+                return None;
+            };
+
+            let item = self.only_items.get(data.item).ok()?;
+            let position = data.position.unwrap_or_else(|| {
+                container
+                    .find_space(&self.only_items, item)
+                    .unwrap_or_default()
+            });
+            if !container.can_fit(&self.only_items, item, position) {
+                warn!(task = ?data, "Failed to move item because it does not fit in the container");
+                return None;
+            }
+            container.insert_item_unchecked(data.item, position);
+            destinations.push(Some((container_entity, position)));
+        }
+
+        Some(destinations)
+    }
 
-            // Remove from container
+    /// Removes an item from the container it is stored in
+    fn take_out(&mut self, item_entity: Entity) {
+        let Ok(Some(stored)) = self.items.get(item_entity) else {
+            return;
+        };
+        let container_entity = stored.container();
+        if let Ok(mut container) = self.containers.get_mut(container_entity) {
             container.remove_item(item_entity);
-            if let Some(items) = container_items
-                .containers_to_items
-                .get_mut(&*stored.container)
-            {
-                items.remove(&item_entity);
-            }
         }
+        if let Some(items) = self
+            .container_items
+            .containers_to_items
+            .get_mut(&container_entity)
+        {
+            items.remove(&item_entity);
+        }
+    }
+
+    /// Puts a taken out item into its planned place, or back into the world
+    fn put(&mut self, item_entity: Entity, destination: Option<(Entity, UVec2)>) {
+        let Ok(mut stored) = self.items.get_mut(item_entity) else {
+            return;
+        };
 
-        let Some(container_entity) = data.container else {
+        let Some((container_entity, position)) = destination else {
             // If we're putting it back into the world
             if stored.is_some() {
-                let mut entity_commands = commands.entity(item_entity);
+                let mut entity_commands = self.commands.entity(item_entity);
                 entity_commands
                     .remove::<StoredItem>()
                     .remove_parent()
                     .enable_physics();
 
-                if let Ok(transform) = global_transforms.get(item_entity) {
+                if let Ok(transform) = self.global_transforms.get(item_entity) {
                     entity_commands.insert(Transform::from(*transform));
                 }
 
-                container_items.items_to_container.remove(&item_entity);
+                self.container_items.items_to_container.remove(&item_entity);
             }
-            return MoveItemResult { success: true };
+            return;
         };
 
-        let Ok(mut container) = containers.get_mut(container_entity) else {
-            warn!(task = ?data, "Failed to move item because target is not a container");
-            return MoveItemResult { success: false };
-        };
-
-        let position = data
-            .position
-            .unwrap_or_else(|| container.find_space(&only_items, item).unwrap_or_default());
-        if !container.can_fit(&only_items, item, position) {
-            warn!(task = ?data, "Failed to move item because it does not fit in the container");
-            return MoveItemResult { success: false };
-        }
-
-        container.insert_item_unchecked(data.item, position);
+        let mut container = self.containers.get_mut(container_entity).unwrap();
+        container.insert_item_unchecked(item_entity, position);
         if let Some(stored) = stored.as_mut() {
             *stored.container = container_entity;
             *stored.slot = position;
             *stored.visible = container.items_visible;
         } else {
-            commands.entity(data.item).insert(StoredItem {
-                container: container_entity.into(),
-                slot: position.into(),
-                visible: container.items_visible.into(),
-            });
+            self.commands.entity(item_entity).insert(StoredItem::new(
+                container_entity,
+                position,
+                container.items_visible,
+            ));
         }
 
-        container_items
+        self.container_items
             .items_to_container
             .insert(item_entity, container_entity);
-        container_items
+        self.container_items
             .containers_to_items
             .entry(container_entity)
             .or_default()
             .insert(item_entity);
 
         // TODO: Do all containers nest their items? Probably...
-        commands
+        self.commands
             .entity(container.attach_to.unwrap_or(container_entity))
-            .add_child(data.item);
+            .add_child(item_entity);
         // Freeze the item as a child
-        commands
+        self.commands
             .entity(item_entity)
             .insert(Transform::default())
             .disable_physics();
+    }
 
-        MoveItemResult { success: true }
-    });
+    /// Moves all items if every move is valid
+    fn apply(&mut self, moves: &[MoveItem], moved: &mut HashSet<Entity>) -> MoveItemResult {
+        if let Some(data) = moves.iter().find(|data| moved.contains(&data.item)) {
+            debug!(task = ?data, "Failed to move item because it was already moved this frame");
+            return MoveItemResult {
+                success: false,
+                taken: true,
+            };
+        }
+
+        let Some(destinations) = self.plan(moves) else {
+            return MoveItemResult::failed();
+        };
+
+        for data in moves {
+            self.take_out(data.item);
+        }
+        for (data, destination) in moves.iter().zip(destinations) {
+            self.put(data.item, destination);
+            moved.insert(data.item);
+        }
+
+        MoveItemResult {
+            success: true,
+            taken: false,
+        }
+    }
+}
+
+/// Gets the copy of a container used for planning, copying it on first use
+fn scratch_container<'a>(
+    scratch: &'a mut HashMap<Entity, Container>,
+    containers: &Query<&mut Container>,
+    entity: Entity,
+) -> Option<&'a mut Container> {
+    match scratch.entry(entity) {
+        Entry::Occupied(entry) => Some(entry.into_mut()),
+        Entry::Vacant(entry) => Some(entry.insert(containers.get(entity).ok()?.clone())),
+    }
+}
+
+fn do_item_move(
+    mut tasks: ResMut<Tasks<MoveItem>>,
+    mut transactions: ResMut<Tasks<MoveItems>>,
+    mut mover: ItemMover,
+) {
+    // The first move of an item wins, later ones in the same frame would steal it
+    let mut moved = HashSet::new();
+    tasks.process(|data| mover.apply(std::slice::from_ref(&data), &mut moved));
+    transactions.process(|data| mover.apply(&data.moves, &mut moved));
 }
 
 fn cleanup_deleted_entities(
@@ -287,8 +463,48 @@ fn cleanup_deleted_entities(
         container_items
             .containers_to_items
             .remove(&container_entity);
-        // TODO: Do we need to do anything to the items? I assume the container was removed using `despawn_recursive`
+        // The contents were already dropped or destroyed by the despawn command
+    }
+}
+
+/// Removes an item from the container it is stored in right away,
+/// so the container never refers to a despawned item.
+pub(crate) fn unlink_item(world: &mut World, item: Entity) {
+    let Some(container_entity) = world.get::<StoredItem>(item).map(|s| s.container()) else {
+        return;
+    };
+    if let Some(mut container) = world.get_mut::<Container>(container_entity) {
+        container.remove_item(item);
+    }
+    if let Some(mut container_items) = world.get_resource_mut::<ContainerItems>() {
+        container_items.items_to_container.remove(&item);
+        if let Some(items) = container_items
+            .containers_to_items
+            .get_mut(&container_entity)
+        {
+            items.remove(&item);
+        }
+    }
+}
+
+/// Takes an item out of its container and leaves it in the world where it currently is
+pub(crate) fn drop_item(world: &mut World, item: Entity) {
+    unlink_item(world, item);
+    let transform = world
+        .get::<GlobalTransform>(item)
+        .map(|t| Transform::from(*t));
+
+    let mut queue = CommandQueue::default();
+    let mut commands = Commands::new(&mut queue, world);
+    let mut entity_commands = commands.entity(item);
+    entity_commands
+        .remove::<StoredItem>()
+        .remove_parent()
+        .enable_physics();
+    if let Some(transform) = transform {
+        entity_commands.insert(transform);
     }
+    queue.apply(world);
 }
 
 /// Influences the network visibility of items that are stored in a container.
@@ -17,11 +17,15 @@ use crate::{
         ActiveInteraction, GenerateInteractionList, InteractionListEvents, InteractionOption,
         InteractionSpecificity, InteractionStatus,
     },
-    items::{Item, StoredItemClient},
-    ui::{has_window, CloseUiMessage, NetworkUi},
+    items::{stacks::StackClient, Item, StoredItem, StoredItemClient},
+    ui::{
+        has_window,
+        palette::{Cue, Palette},
+        CloseUiMessage, NetworkUi,
+    },
 };
 
-use super::{Container, MoveItem};
+use super::{Container, MoveItem, MoveItems};
 
 pub struct ContainerUiPlugin;
 
@@ -80,10 +84,17 @@ const SLOT_SIZE: egui::Vec2 = egui::vec2(36.0, 36.0);
 fn container_ui(
     mut contexts: EguiContexts,
     uis: Query<(Entity, &NetworkIdentity, &ContainerUiClient)>,
-    mut items: Query<(Entity, &NetworkIdentity, &Item, &mut StoredItemClient)>,
+    mut items: Query<(
+        Entity,
+        &NetworkIdentity,
+        &Item,
+        &mut StoredItemClient,
+        Option<&StackClient>,
+    )>,
     containers: Query<(&Container, &Children)>,
     identities: Res<NetworkIdentities>,
     mut dragged: ResMut<DraggedItem>,
+    palette: Res<Palette>,
     mut sender: MessageSender,
     mut commands: Commands,
 ) {
@@ -97,7 +108,13 @@ fn container_ui(
 
         let stored: HashMap<_, _> = items
             .iter_many(children)
-            .map(|(entity, _, item, stored)| (*stored.slot, (entity, item.name.clone(), item.size)))
+            .map(|(entity, _, item, stored, stack)| {
+                let name = match stack {
+                    Some(stack) => format!("{} ({})", item.name, stack.count()),
+                    None => item.name.clone(),
+                };
+                (*stored.slot, (entity, name, item.size))
+            })
             .collect();
 
         let mut keep_open = true;
@@ -172,23 +189,31 @@ fn container_ui(
                             ),
                         )
                         .translate(grid_rect.left_top().to_vec2());
+                        let cue = if out_of_bounds { Cue::Bad } else { Cue::Good };
                         ui.painter().rect(
                             item_rect,
                             0.,
-                            if out_of_bounds {
-                                egui::Color32::RED
-                            } else {
-                                egui::Color32::GREEN
-                            }
-                            .gamma_multiply(0.25),
+                            palette.color(cue).gamma_multiply(0.25),
                             egui::Stroke::NONE,
                         );
+                        if palette.symbols && out_of_bounds {
+                            let stroke = egui::Stroke::new(2.0, palette.color(cue));
+                            ui.painter().line_segment(
+                                [item_rect.left_top(), item_rect.right_bottom()],
+                                stroke,
+                            );
+                            ui.painter().line_segment(
+                                [item_rect.right_top(), item_rect.left_bottom()],
+                                stroke,
+                            );
+                        }
                     }
 
                     if !out_of_bounds {
                         // Drop if pointer released
                         if ui.input(|i| i.pointer.any_released()) {
-                            if let Ok((item_entity, &identity, _, mut item)) = items.get_mut(entity)
+                            if let Ok((item_entity, &identity, _, mut item, _)) =
+                                items.get_mut(entity)
                             {
                                 // Tell server to move it
                                 sender.send_to_server(&MoveItemMessage {
@@ -303,6 +328,9 @@ fn handle_move_message(
     mut messages: EventReader<MessageEvent<MoveItemMessage>>,
     identities: Res<NetworkIdentities>,
     mut item_moves: ResMut<Tasks<MoveItem>>,
+    mut transactions: ResMut<Tasks<MoveItems>>,
+    containers: Query<&Container>,
+    stored_items: Query<&StoredItem>,
 ) {
     for event in messages.iter() {
         let message = &event.message;
@@ -310,6 +338,33 @@ fn handle_move_message(
             continue;
         };
         let container_entity = message.to_container.and_then(|i| identities.get_entity(i));
+
+        // Dropping an item onto another one swaps them
+        let occupant = container_entity
+            .and_then(|entity| containers.get(entity).ok())
+            .and_then(|container| {
+                container
+                    .iter()
+                    .find(|(&position, &other)| position == message.to_slot && other != item_entity)
+                    .map(|(_, &other)| other)
+            });
+        if let (Some(occupant), Ok(stored)) = (occupant, stored_items.get(item_entity)) {
+            transactions.create_ignore(MoveItems {
+                moves: vec![
+                    MoveItem {
+                        item: item_entity,
+                        container: container_entity,
+                        position: Some(message.to_slot),
+                    },
+                    MoveItem {
+                        item: occupant,
+                        container: Some(stored.container()),
+                        position: Some(stored.slot()),
+                    },
+                ],
+            });
+            continue;
+        }
         item_moves.create_ignore(MoveItem {
             item: item_entity,
             container: container_entity,
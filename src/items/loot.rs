@@ -0,0 +1,159 @@
+use bevy::{
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+};
+use bevy_common_assets::ron::RonAssetPlugin;
+use maps::TileMap;
+use networking::{is_server, scene::NetworkSceneBundle};
+use serde::Deserialize;
+
+use crate::round::{RoundRng, RoundState};
+
+use super::variants::{variant_bundle, ItemVariant};
+
+pub struct LootPlugin;
+
+impl Plugin for LootPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<LootSpawner>();
+
+        if is_server(app) {
+            app.add_plugins(RonAssetPlugin::<LootTable>::new(&["loot.ron"]))
+                .add_systems(Startup, load_loot_tables)
+                .add_systems(Update, spawn_map_loot_markers)
+                .add_systems(OnEnter(RoundState::InProgress), roll_loot_spawners);
+        }
+    }
+}
+
+/// A weighted list of items that can be spawned by a [`LootSpawner`].
+#[derive(Deserialize, TypeUuid, TypePath)]
+#[uuid = "2c8a2f5e-9d6f-4b1e-8c44-7a0b6c9e3f12"]
+pub struct LootTable {
+    pub id: String,
+    pub entries: Vec<LootEntry>,
+}
+
+#[derive(Deserialize)]
+pub struct LootEntry {
+    /// Path of an item scene or variant. `None` spawns nothing.
+    pub item: Option<String>,
+    pub weight: f32,
+    /// Inclusive range of how many are spawned
+    #[serde(default = "LootEntry::default_quantity")]
+    pub quantity: (u32, u32),
+}
+
+impl LootEntry {
+    fn default_quantity() -> (u32, u32) {
+        (1, 1)
+    }
+}
+
+#[derive(Resource)]
+struct LootAssets {
+    tables: Vec<Handle<LootTable>>,
+}
+
+fn load_loot_tables(mut commands: Commands, server: ResMut<AssetServer>) {
+    let assets = LootAssets {
+        tables: server
+            .load_folder("loot")
+            .expect("assets/loot is missing")
+            .into_iter()
+            .map(HandleUntyped::typed)
+            .collect(),
+    };
+    commands.insert_resource(assets);
+}
+
+/// A marker that spawns items from a loot table when the round starts.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct LootSpawner {
+    /// Identifier of the loot table
+    pub table: String,
+    /// How many times the table is rolled
+    pub rolls: u32,
+}
+
+/// Creates spawner entities for the loot points defined in a map
+fn spawn_map_loot_markers(maps: Query<&TileMap, Added<TileMap>>, mut commands: Commands) {
+    for map in maps.iter() {
+        for point in map.loot_spawns.iter() {
+            commands.spawn((
+                LootSpawner {
+                    table: point.table.clone(),
+                    rolls: point.rolls,
+                },
+                TransformBundle::from_transform(Transform::from_xyz(
+                    point.position.x as f32,
+                    0.5,
+                    point.position.y as f32,
+                )),
+            ));
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn roll_loot_spawners(
+    spawners: Query<(Entity, &LootSpawner, &GlobalTransform)>,
+    assets: Res<LootAssets>,
+    tables: Res<Assets<LootTable>>,
+    variants: Res<Assets<ItemVariant>>,
+    mut rng: ResMut<RoundRng>,
+    server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    let mut spawned = 0;
+    for (entity, spawner, transform) in spawners.iter() {
+        commands.entity(entity).despawn_recursive();
+
+        // TODO: Fix O(n) lookup
+        let Some(table) = assets
+            .tables
+            .iter()
+            .filter_map(|h| tables.get(h))
+            .find(|t| t.id == spawner.table)
+        else {
+            warn!(table = ?spawner.table, "Missing loot table");
+            continue;
+        };
+
+        for _ in 0..spawner.rolls {
+            let Some(entry) = rng.choose_weighted(&table.entries, |e| e.weight) else {
+                continue;
+            };
+            let Some(item) = entry.item.as_ref() else {
+                continue;
+            };
+
+            let (min, max) = entry.quantity;
+            for _ in 0..rng.range(min..=max) {
+                // Scatter items a bit so they don't stack inside each other
+                let offset = Vec3::new(rng.next_f32() - 0.5, 0.0, rng.next_f32() - 0.5) * 0.5;
+                let transform = Transform::from_translation(transform.translation() + offset);
+
+                if item.ends_with(".variant.ron") {
+                    let Some((mut scene, variant)) =
+                        variant_bundle(item.as_str().into(), &variants, &server)
+                    else {
+                        warn!(item = ?item, "Loot variant is not loaded");
+                        continue;
+                    };
+                    scene.transform = transform;
+                    commands.spawn((scene, variant));
+                } else {
+                    commands.spawn(NetworkSceneBundle {
+                        scene: server.load(item.as_str()).into(),
+                        transform,
+                        ..Default::default()
+                    });
+                }
+                spawned += 1;
+            }
+        }
+    }
+    info!(count = spawned, "Spawned round start loot");
+}
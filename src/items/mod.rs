@@ -3,14 +3,26 @@ use networking::{
     component::AppExt,
     identity::{NetworkIdentities, NetworkIdentity},
     is_server,
+    scene::{insert_pbr_bundles, AppExt as SceneAppExt},
     variable::{NetworkVar, ServerVar},
     NetworkManager, Networked,
 };
 
-use self::{clothes::ClothingPlugin, containers::ContainerPlugin};
+use self::{
+    cells::CellPlugin, cleanup::ItemCleanupPlugin, clothes::ClothingPlugin,
+    containers::ContainerPlugin, loot::LootPlugin, reagents::ReagentItemsPlugin,
+    stacks::StackPlugin, tools::ToolPlugin, variants::ItemVariantPlugin,
+};
 
+pub mod cells;
+pub mod cleanup;
 pub mod clothes;
 pub mod containers;
+pub mod loot;
+pub mod reagents;
+pub mod stacks;
+pub mod tools;
+pub mod variants;
 
 pub struct ItemPlugin;
 
@@ -18,18 +30,23 @@ impl Plugin for ItemPlugin {
     fn build(&self, app: &mut App) {
         app.register_type::<Item>()
             .add_networked_component::<StoredItem, StoredItemClient>()
+            .add_scene_preset("item", item_preset)
             .add_systems(Startup, load_item_assets);
 
         if !is_server(app) {
-            app.add_systems(
-                Update,
-                (
-                    client_initialize_spawned_items,
-                    client_update_item_visibility,
-                ),
-            );
+            app.add_systems(Update, client_update_item_visibility);
         }
-        app.add_plugins((ContainerPlugin, ClothingPlugin));
+        app.add_plugins((
+            ContainerPlugin,
+            ClothingPlugin,
+            ItemVariantPlugin,
+            LootPlugin,
+            ItemCleanupPlugin,
+            CellPlugin,
+            StackPlugin,
+            ToolPlugin,
+            ReagentItemsPlugin,
+        ));
     }
 }
 
@@ -37,6 +54,8 @@ impl Plugin for ItemPlugin {
 #[reflect(Component)]
 pub struct Item {
     pub name: String,
+    /// Shown when examining the item
+    pub description: String,
     pub size: UVec2,
 }
 
@@ -44,6 +63,7 @@ impl Default for Item {
     fn default() -> Self {
         Self {
             name: "Default item name".to_string(),
+            description: String::new(),
             size: UVec2::ONE,
         }
     }
@@ -61,6 +81,14 @@ pub struct StoredItem {
 }
 
 impl StoredItem {
+    pub(crate) fn new(container: Entity, slot: UVec2, visible: bool) -> Self {
+        Self {
+            container: container.into(),
+            slot: slot.into(),
+            visible: visible.into(),
+        }
+    }
+
     fn network_container(entity: &Entity, param: Res<NetworkIdentities>) -> NetworkIdentity {
         param
             .get_identity(*entity)
@@ -70,6 +98,10 @@ impl StoredItem {
     pub fn container(&self) -> Entity {
         *self.container
     }
+
+    pub fn slot(&self) -> UVec2 {
+        *self.slot
+    }
 }
 
 #[derive(Component, Default, Networked, TypeUuid)]
@@ -119,36 +151,17 @@ fn load_item_assets(
     commands.insert_resource(assets);
 }
 
-// TODO: Remove once scenes support composition
-/// Adds some bundles to spawned tile scenes, so we don't need to specify them every time
-fn client_initialize_spawned_items(
-    new: Query<Entity, Added<Item>>,
-    children_query: Query<&Children>,
-    existing_meshes: Query<(&Handle<Mesh>, Option<&Transform>), Without<Handle<StandardMaterial>>>,
-    assets: Res<ItemAssets>,
-    mut commands: Commands,
-) {
-    let Some(assets) = assets.client.as_ref() else {
+/// Adds rendering components to spawned items on the client
+fn item_preset(world: &mut World, root: Entity) {
+    let Some(material) = world
+        .resource::<ItemAssets>()
+        .client
+        .as_ref()
+        .map(|c| c.default_material.clone())
+    else {
         return;
     };
-
-    let mut process_entity = |entity| {
-        if let Ok((mesh, transform)) = existing_meshes.get(entity) {
-            commands.entity(entity).insert(PbrBundle {
-                mesh: mesh.clone(),
-                material: assets.default_material.clone(),
-                transform: transform.cloned().unwrap_or_default(),
-                ..Default::default()
-            });
-        }
-    };
-
-    for root in new.iter() {
-        process_entity(root);
-        for child in children_query.iter_descendants(root) {
-            process_entity(child);
-        }
-    }
+    insert_pbr_bundles(world, root, material);
 }
 
 fn client_update_item_visibility(
@@ -0,0 +1,199 @@
+use std::time::Duration;
+
+use bevy::{ecs::query::Has, prelude::*};
+use chemistry::{Metabolizer, ReagentContainer};
+use networking::is_server;
+
+use crate::interaction::{
+    ActiveInteraction, GenerateInteractionList, InteractionListEvents, InteractionOption,
+    InteractionSpecificity, InteractionStatus,
+};
+
+pub struct ReagentItemsPlugin;
+
+impl Plugin for ReagentItemsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Syringe>().register_type::<Pourable>();
+
+        if is_server(app) {
+            app.register_type::<TransferReagentsInteraction>()
+                .add_systems(
+                    Update,
+                    (
+                        (prepare_syringe_interaction, prepare_pour_interaction)
+                            .in_set(GenerateInteractionList),
+                        transfer_reagents_interaction,
+                    ),
+                );
+        }
+    }
+}
+
+const INJECT_TIME: Duration = Duration::from_secs(2);
+const DRAW_TIME: Duration = Duration::from_secs(1);
+const POUR_TIME: Duration = Duration::from_secs(1);
+const FEED_TIME: Duration = Duration::from_secs(3);
+
+/// Draws reagents from containers and injects them into bodies or other containers.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Syringe {
+    /// Units moved per use
+    pub transfer_amount: f32,
+}
+
+impl Default for Syringe {
+    fn default() -> Self {
+        Self {
+            transfer_amount: 5.0,
+        }
+    }
+}
+
+/// An open container, like a beaker, that can be poured out or drunk from.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Pourable {
+    /// Units moved per use
+    pub transfer_amount: f32,
+}
+
+impl Default for Pourable {
+    fn default() -> Self {
+        Self {
+            transfer_amount: 10.0,
+        }
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct TransferReagentsInteraction {
+    from: Entity,
+    to: Entity,
+    amount: f32,
+    duration: Duration,
+}
+
+impl FromWorld for TransferReagentsInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            from: Entity::PLACEHOLDER,
+            to: Entity::PLACEHOLDER,
+            amount: 0.0,
+            duration: Duration::ZERO,
+        }
+    }
+}
+
+fn prepare_syringe_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    syringes: Query<(&Syringe, &ReagentContainer)>,
+    containers: Query<(&ReagentContainer, Has<Metabolizer>)>,
+) {
+    for event in interaction_list.events.iter() {
+        let Some(item) = event.item_in_hand else {
+            continue;
+        };
+        let Ok((syringe, contents)) = syringes.get(item) else {
+            continue;
+        };
+        let Ok((target, is_body)) = containers.get(event.target) else {
+            continue;
+        };
+        if item == event.target {
+            continue;
+        }
+
+        if !contents.is_empty() && target.free_space() > 0.0 {
+            event.add_interaction(InteractionOption {
+                text: "Inject".into(),
+                interaction: Box::new(TransferReagentsInteraction {
+                    from: item,
+                    to: event.target,
+                    amount: syringe.transfer_amount,
+                    duration: INJECT_TIME,
+                }),
+                specificity: InteractionSpecificity::Specific,
+            });
+        }
+
+        // Drawing blood isn't supported yet
+        if !is_body && !target.is_empty() && contents.free_space() > 0.0 {
+            event.add_interaction(InteractionOption {
+                text: "Draw".into(),
+                interaction: Box::new(TransferReagentsInteraction {
+                    from: event.target,
+                    to: item,
+                    amount: syringe.transfer_amount,
+                    duration: DRAW_TIME,
+                }),
+                specificity: InteractionSpecificity::Specific,
+            });
+        }
+    }
+}
+
+fn prepare_pour_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    pourables: Query<(&Pourable, &ReagentContainer)>,
+    containers: Query<(&ReagentContainer, Has<Metabolizer>)>,
+) {
+    for event in interaction_list.events.iter() {
+        let Some(item) = event.item_in_hand else {
+            continue;
+        };
+        let Ok((pourable, contents)) = pourables.get(item) else {
+            continue;
+        };
+        let Ok((target, is_body)) = containers.get(event.target) else {
+            continue;
+        };
+        if item == event.target || contents.is_empty() || target.free_space() <= 0.0 {
+            continue;
+        }
+
+        let (text, duration) = if is_body {
+            ("Feed", FEED_TIME)
+        } else {
+            ("Pour", POUR_TIME)
+        };
+        event.add_interaction(InteractionOption {
+            text: text.into(),
+            interaction: Box::new(TransferReagentsInteraction {
+                from: item,
+                to: event.target,
+                amount: pourable.transfer_amount,
+                duration,
+            }),
+            specificity: InteractionSpecificity::Specific,
+        });
+    }
+}
+
+fn transfer_reagents_interaction(
+    mut query: Query<(&TransferReagentsInteraction, &mut ActiveInteraction)>,
+    mut containers: Query<&mut ReagentContainer>,
+    time: Res<Time>,
+) {
+    for (interaction, mut active) in query.iter_mut() {
+        active.set_initial_duration(interaction.duration);
+
+        if active.start_time() + interaction.duration.as_secs_f32() > time.elapsed_seconds() {
+            continue;
+        }
+
+        let Ok([mut from, mut to]) = containers.get_many_mut([interaction.from, interaction.to])
+        else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+
+        if from.transfer_to(&mut to, interaction.amount) > 0.0 {
+            active.status = InteractionStatus::Completed;
+        } else {
+            active.status = InteractionStatus::Canceled;
+        }
+    }
+}
@@ -0,0 +1,302 @@
+use bevy::{prelude::*, reflect::TypeUuid};
+use networking::{
+    component::AppExt,
+    is_server,
+    scene::{NetworkScene, NetworkSceneBundle},
+    variable::{NetworkVar, ServerVar},
+    Networked,
+};
+use utils::task::Tasks;
+
+use crate::{
+    body::Hands,
+    despawn::{ContentsPolicy, DespawnCommandsExt},
+    interaction::{
+        ActiveInteraction, GenerateInteractionList, InteractionListEvents, InteractionOption,
+        InteractionSpecificity, InteractionStatus,
+    },
+};
+
+use super::containers::{Container, MoveItem};
+
+pub struct StackPlugin;
+
+impl Plugin for StackPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Stack>()
+            .add_networked_component::<Stack, StackClient>();
+        if is_server(app) {
+            app.register_type::<MergeStackInteraction>()
+                .register_type::<SplitStackInteraction>()
+                .add_systems(
+                    Update,
+                    (
+                        (prepare_merge_interaction, prepare_split_interaction)
+                            .in_set(GenerateInteractionList),
+                        merge_interaction,
+                        split_interaction,
+                        apply_pending_stacks,
+                        update_stack_count,
+                    ),
+                );
+        }
+    }
+}
+
+/// Many of the same item in one, like sheets of metal.
+/// Stacks spawned from the same scene can be merged.
+#[derive(Component, Reflect, Networked)]
+#[reflect(Component)]
+#[networked(client = "StackClient")]
+pub struct Stack {
+    count: u32,
+    max: u32,
+    /// Count kept for clients
+    #[reflect(ignore)]
+    synced_count: NetworkVar<u32>,
+}
+
+impl Default for Stack {
+    fn default() -> Self {
+        Self {
+            count: 1,
+            max: 50,
+            synced_count: 1.into(),
+        }
+    }
+}
+
+impl Stack {
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// How many more fit onto the stack
+    pub fn space(&self) -> u32 {
+        self.max.saturating_sub(self.count)
+    }
+
+    /// Removes an amount from the stack. Returns false without removing anything if there isn't enough.
+    pub fn take(&mut self, amount: u32) -> bool {
+        if self.count < amount {
+            return false;
+        }
+        self.count -= amount;
+        true
+    }
+}
+
+#[derive(Component, Default, TypeUuid, Networked)]
+#[uuid = "5d2c8f47-1e9b-4a63-b7d0-93f4a6e1c28b"]
+#[networked(server = "Stack")]
+pub struct StackClient {
+    synced_count: ServerVar<u32>,
+}
+
+impl StackClient {
+    pub fn count(&self) -> u32 {
+        self.synced_count.get().copied().unwrap_or(1)
+    }
+}
+
+/// Sets up a stack spawned from a scene once the scene is ready
+#[derive(Component)]
+pub struct PendingStack {
+    pub count: u32,
+    /// Container to put the stack into, usually a hand
+    pub container: Option<Entity>,
+}
+
+fn apply_pending_stacks(
+    mut pending: Query<(Entity, &PendingStack, &mut Stack)>,
+    mut item_moves: ResMut<Tasks<MoveItem>>,
+    mut commands: Commands,
+) {
+    for (entity, pending, mut stack) in pending.iter_mut() {
+        stack.count = pending.count;
+        if let Some(container) = pending.container {
+            item_moves.create_ignore(MoveItem {
+                item: entity,
+                container: Some(container),
+                position: Some(UVec2::ZERO),
+            });
+        }
+        commands.entity(entity).remove::<PendingStack>();
+    }
+}
+
+fn update_stack_count(mut stacks: Query<&mut Stack, Changed<Stack>>) {
+    for mut stack in stacks.iter_mut() {
+        if *stack.synced_count != stack.count {
+            *stack.synced_count = stack.count;
+        }
+    }
+}
+
+/// Whether two stacks were spawned from the same scene
+fn same_kind(scenes: &Query<&NetworkScene>, a: Entity, b: Entity) -> bool {
+    match (scenes.get(a), scenes.get(b)) {
+        (Ok(a), Ok(b)) => a.handle() == b.handle(),
+        _ => false,
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct MergeStackInteraction {
+    held: Entity,
+}
+
+impl FromWorld for MergeStackInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            held: Entity::PLACEHOLDER,
+        }
+    }
+}
+
+fn prepare_merge_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    stacks: Query<&Stack>,
+    scenes: Query<&NetworkScene>,
+) {
+    for event in interaction_list.events.iter() {
+        let Some(held) = event.item_in_hand else {
+            continue;
+        };
+        if held == event.target || !same_kind(&scenes, held, event.target) {
+            continue;
+        }
+        let (Ok(held_stack), Ok(_)) = (stacks.get(held), stacks.get(event.target)) else {
+            continue;
+        };
+        if held_stack.space() == 0 {
+            continue;
+        }
+
+        event.add_interaction(InteractionOption {
+            text: "Add to stack".into(),
+            interaction: Box::new(MergeStackInteraction { held }),
+            specificity: InteractionSpecificity::Specific,
+        });
+    }
+}
+
+fn merge_interaction(
+    mut query: Query<(&MergeStackInteraction, &mut ActiveInteraction)>,
+    mut stacks: Query<&mut Stack>,
+    mut commands: Commands,
+) {
+    for (interaction, mut active) in query.iter_mut() {
+        let Ok([mut held, mut target]) = stacks.get_many_mut([interaction.held, active.target])
+        else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+
+        let amount = held.space().min(target.count);
+        target.count -= amount;
+        held.count += amount;
+        if target.count == 0 {
+            commands.despawn_cascade(active.target, ContentsPolicy::Destroy);
+        }
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct SplitStackInteraction {
+    amount: u32,
+}
+
+impl FromWorld for SplitStackInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self { amount: 1 }
+    }
+}
+
+/// Finds the active hand of a creature if it is empty
+fn empty_hand(
+    source: Entity,
+    hands: &Query<&Hands>,
+    containers: &Query<&Container>,
+) -> Option<Entity> {
+    let hand = hands.get(source).ok()?.active_hand();
+    containers
+        .get(hand)
+        .ok()
+        .filter(|c| c.is_empty())
+        .map(|_| hand)
+}
+
+fn prepare_split_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    stacks: Query<&Stack>,
+    hands: Query<&Hands>,
+    containers: Query<&Container>,
+) {
+    for event in interaction_list.events.iter() {
+        if event.item_in_hand.is_some() {
+            continue;
+        }
+        let Ok(stack) = stacks.get(event.target) else {
+            continue;
+        };
+        if stack.count < 2 || empty_hand(event.source, &hands, &containers).is_none() {
+            continue;
+        }
+
+        let half = stack.count / 2;
+        let mut options = vec![("Take one", 1)];
+        if half > 1 {
+            options.push(("Take half", half));
+        }
+        for (text, amount) in options {
+            event.add_interaction(InteractionOption {
+                text: text.into(),
+                interaction: Box::new(SplitStackInteraction { amount }),
+                specificity: InteractionSpecificity::Specific,
+            });
+        }
+    }
+}
+
+fn split_interaction(
+    mut query: Query<(Entity, &SplitStackInteraction, &mut ActiveInteraction)>,
+    mut stacks: Query<(&mut Stack, &NetworkScene, &GlobalTransform)>,
+    hands: Query<&Hands>,
+    containers: Query<&Container>,
+    mut commands: Commands,
+) {
+    for (source, interaction, mut active) in query.iter_mut() {
+        let Some(hand) = empty_hand(source, &hands, &containers) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        let Ok((mut stack, scene, transform)) = stacks.get_mut(active.target) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        // Keep at least one in the original stack
+        if interaction.amount >= stack.count || !stack.take(interaction.amount) {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        }
+
+        commands.spawn((
+            NetworkSceneBundle {
+                scene: scene.handle().clone().into(),
+                transform: Transform::from_translation(transform.translation()),
+                ..Default::default()
+            },
+            PendingStack {
+                count: interaction.amount,
+                container: Some(hand),
+            },
+        ));
+        active.status = InteractionStatus::Completed;
+    }
+}
@@ -0,0 +1,240 @@
+use std::time::Duration;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use networking::is_server;
+use serde::Deserialize;
+
+use crate::{
+    body::Hands,
+    communication::examine::{DescribeExamined, ExamineEvents},
+    interaction::{
+        ActiveInteraction, GenerateInteractionList, InteractionListEvents, InteractionOption,
+        InteractionSpecificity, InteractionStatus,
+    },
+};
+
+use super::containers::Container;
+
+pub struct ToolPlugin;
+
+impl Plugin for ToolPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Tool>()
+            .register_type::<ToolKind>()
+            .register_type::<Fuel>()
+            .register_type::<FuelTank>();
+        if is_server(app) {
+            app.register_type::<RefuelInteraction>().add_systems(
+                Update,
+                (
+                    prepare_refuel_interaction.in_set(GenerateInteractionList),
+                    refuel_interaction,
+                    describe_fuel.in_set(DescribeExamined),
+                ),
+            );
+        }
+    }
+}
+
+const REFUEL_TIME: Duration = Duration::from_secs(1);
+
+#[derive(Reflect, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum ToolKind {
+    #[default]
+    Wrench,
+    Crowbar,
+    Screwdriver,
+    Wirecutters,
+    Welder,
+    Scalpel,
+    Retractor,
+    Hemostat,
+}
+
+/// Fuel or charge a tool uses up, like the fuel of a welder
+#[derive(Reflect, Clone, Default)]
+pub struct Fuel {
+    amount: f32,
+    capacity: f32,
+    per_use: f32,
+}
+
+/// An item used to work on things
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Tool {
+    kind: ToolKind,
+    /// How fast work is done compared to a standard tool
+    speed: f32,
+    fuel: Option<Fuel>,
+}
+
+impl Default for Tool {
+    fn default() -> Self {
+        Self {
+            kind: ToolKind::Wrench,
+            speed: 1.0,
+            fuel: None,
+        }
+    }
+}
+
+impl Tool {
+    pub fn kind(&self) -> ToolKind {
+        self.kind
+    }
+
+    /// Remaining fuel and capacity, if the tool uses fuel
+    pub fn fuel(&self) -> Option<(f32, f32)> {
+        self.fuel.as_ref().map(|f| (f.amount, f.capacity))
+    }
+
+    fn has_fuel(&self) -> bool {
+        self.fuel.as_ref().map_or(true, |f| f.amount >= f.per_use)
+    }
+
+    fn consume_fuel(&mut self) -> bool {
+        let Some(fuel) = &mut self.fuel else {
+            return true;
+        };
+        if fuel.amount < fuel.per_use {
+            return false;
+        }
+        fuel.amount -= fuel.per_use;
+        true
+    }
+}
+
+/// Finds the tools creatures are holding and uses them up
+#[derive(SystemParam)]
+pub struct Tools<'w, 's> {
+    tools: Query<'w, 's, &'static mut Tool>,
+    hands: Query<'w, 's, &'static Hands>,
+    containers: Query<'w, 's, &'static Container>,
+}
+
+impl<'w, 's> Tools<'w, 's> {
+    /// If the item is a tool of the given kind that can be used right now
+    pub fn is(&self, item: Entity, kind: ToolKind) -> bool {
+        self.tools
+            .get(item)
+            .map_or(false, |tool| tool.kind == kind && tool.has_fuel())
+    }
+
+    /// Finds a usable tool of the given kind in the active hand of a creature
+    pub fn held(&self, creature: Entity, kind: ToolKind) -> Option<Entity> {
+        let hand = self.hands.get(creature).ok()?.active_hand();
+        self.containers
+            .get(hand)
+            .ok()?
+            .iter()
+            .map(|(_, &item)| item)
+            .find(|&item| self.is(item, kind))
+    }
+
+    /// If the item has enough fuel for one use. Items without fuel always do.
+    pub fn has_fuel(&self, item: Entity) -> bool {
+        self.tools.get(item).map_or(true, |tool| tool.has_fuel())
+    }
+
+    /// How long a task that normally takes `base` takes with the item
+    pub fn duration(&self, item: Entity, base: Duration) -> Duration {
+        self.tools
+            .get(item)
+            .map_or(base, |tool| base.div_f32(tool.speed.max(0.1)))
+    }
+
+    /// Uses up the fuel for one use. Returns false without using anything if there isn't enough.
+    pub fn use_tool(&mut self, item: Entity) -> bool {
+        self.tools
+            .get_mut(item)
+            .map_or(true, |mut tool| tool.consume_fuel())
+    }
+}
+
+/// Refills the fuel of tools, like a welding fuel tank
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+pub struct FuelTank {
+    kind: ToolKind,
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct RefuelInteraction {
+    tool: Entity,
+}
+
+impl FromWorld for RefuelInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            tool: Entity::PLACEHOLDER,
+        }
+    }
+}
+
+fn prepare_refuel_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    tanks: Query<&FuelTank>,
+    tools: Query<&Tool>,
+) {
+    for event in interaction_list.events.iter() {
+        let Some(item) = event.item_in_hand else {
+            continue;
+        };
+        let (Ok(tank), Ok(tool)) = (tanks.get(event.target), tools.get(item)) else {
+            continue;
+        };
+        let Some((amount, capacity)) = tool.fuel() else {
+            continue;
+        };
+        if tool.kind != tank.kind || amount >= capacity {
+            continue;
+        }
+
+        event.add_interaction(InteractionOption {
+            text: "Refuel".into(),
+            interaction: Box::new(RefuelInteraction { tool: item }),
+            specificity: InteractionSpecificity::Specific,
+        });
+    }
+}
+
+fn refuel_interaction(
+    mut query: Query<(&RefuelInteraction, &mut ActiveInteraction)>,
+    tanks: Query<&FuelTank>,
+    mut tools: Query<&mut Tool>,
+    time: Res<Time>,
+) {
+    for (interaction, mut active) in query.iter_mut() {
+        active.set_initial_duration(REFUEL_TIME);
+
+        let (Ok(tank), Ok(mut tool)) = (tanks.get(active.target), tools.get_mut(interaction.tool))
+        else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        if tool.kind != tank.kind {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        }
+
+        if active.start_time() + REFUEL_TIME.as_secs_f32() > time.elapsed_seconds() {
+            continue;
+        }
+
+        if let Some(fuel) = &mut tool.fuel {
+            fuel.amount = fuel.capacity;
+        }
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+fn describe_fuel(examines: Res<ExamineEvents>, tools: Query<&Tool>) {
+    for examine in examines.events.iter() {
+        if let Some((amount, capacity)) = tools.get(examine.target).ok().and_then(Tool::fuel) {
+            examine.add_line(format!("It has {:.0}/{:.0} fuel.", amount, capacity));
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use bevy::{
+    asset::{AssetPathId, HandleId},
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    utils::HashMap,
+};
+use bevy_common_assets::ron::RonAssetPlugin;
+use networking::{
+    component::AppExt,
+    is_server,
+    scene::NetworkSceneBundle,
+    variable::{NetworkVar, ServerVar},
+    Networked,
+};
+use serde::Deserialize;
+
+use super::{Item, ItemAssets};
+
+pub struct ItemVariantPlugin;
+
+impl Plugin for ItemVariantPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonAssetPlugin::<ItemVariant>::new(&["variant.ron"]))
+            .add_networked_component::<PrefabVariant, PrefabVariantClient>()
+            .add_systems(Startup, load_variant_assets);
+
+        if is_server(app) {
+            app.add_systems(Update, apply_variants_server);
+        } else {
+            app.init_resource::<VariantMaterials>()
+                .add_systems(Update, apply_variants_client);
+        }
+    }
+}
+
+/// An overlay that modifies a base item scene when spawned.
+#[derive(Deserialize, TypeUuid, TypePath)]
+#[uuid = "5b0e8f5c-3f5b-4c7e-9a52-1c0f1f8a2d47"]
+pub struct ItemVariant {
+    pub id: String,
+    /// Path of the item scene this variant is based on
+    pub base: String,
+    /// Appended to the item name
+    #[serde(default)]
+    pub name_suffix: Option<String>,
+    /// Tint applied to the item material (RGBA)
+    #[serde(default)]
+    pub color: Option<[f32; 4]>,
+    /// Overrides the item size in containers
+    #[serde(default)]
+    pub size: Option<UVec2>,
+    #[serde(default)]
+    pub modifiers: VariantModifiers,
+}
+
+/// Stat modifiers of an item variant. Read by systems that use the item.
+#[derive(Component, Deserialize, Clone, Copy)]
+pub struct VariantModifiers {
+    #[serde(default = "one")]
+    pub damage_multiplier: f32,
+    /// Attacks with the item do no damage, for training
+    #[serde(default)]
+    pub practice: bool,
+}
+
+impl Default for VariantModifiers {
+    fn default() -> Self {
+        Self {
+            damage_multiplier: 1.0,
+            practice: false,
+        }
+    }
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+/// Stores strong references to all item variants.
+#[derive(Resource)]
+pub struct ItemVariantAssets {
+    pub variants: Vec<Handle<ItemVariant>>,
+}
+
+impl ItemVariantAssets {
+    pub fn contains(&self, id: AssetPathId) -> bool {
+        // TODO: Fix O(n) lookup
+        self.variants
+            .iter()
+            .any(|h| h.id() == HandleId::AssetPathId(id))
+    }
+}
+
+fn load_variant_assets(mut commands: Commands, server: ResMut<AssetServer>) {
+    let assets = ItemVariantAssets {
+        variants: server
+            .load_folder("variants")
+            .expect("assets/variants is missing")
+            .into_iter()
+            .map(HandleUntyped::typed)
+            .collect(),
+    };
+    commands.insert_resource(assets);
+}
+
+/// Marks a spawned item as a variant of its base scene.
+/// The variant asset is networked, so clients can apply the same visuals.
+#[derive(Component, Networked)]
+#[networked(client = "PrefabVariantClient")]
+pub struct PrefabVariant {
+    variant: NetworkVar<AssetPathId>,
+}
+
+#[derive(Component, Default, TypeUuid, Networked)]
+#[uuid = "e8b3c0f2-6f0d-4a1b-b7a4-52d3f3d1a8e9"]
+#[networked(server = "PrefabVariant")]
+pub struct PrefabVariantClient {
+    variant: ServerVar<AssetPathId>,
+}
+
+/// Added once the variant has been applied to the spawned scene.
+#[derive(Component)]
+struct VariantApplied;
+
+/// Creates the components needed to spawn an item variant.
+/// Returns `None` if the variant isn't loaded yet.
+pub fn variant_bundle(
+    variant: AssetPathId,
+    variants: &Assets<ItemVariant>,
+    server: &AssetServer,
+) -> Option<(NetworkSceneBundle, PrefabVariant)> {
+    let definition = variants.get(&variants.get_handle(variant))?;
+    Some((
+        NetworkSceneBundle {
+            scene: server.load(definition.base.as_str()).into(),
+            ..Default::default()
+        },
+        PrefabVariant {
+            variant: variant.into(),
+        },
+    ))
+}
+
+fn apply_to_item(variant: &ItemVariant, item: &mut Item) {
+    if let Some(suffix) = &variant.name_suffix {
+        item.name = format!("{} {}", item.name, suffix);
+    }
+    if let Some(size) = variant.size {
+        item.size = size;
+    }
+}
+
+fn apply_variants_server(
+    mut query: Query<(Entity, &PrefabVariant, &mut Item), Without<VariantApplied>>,
+    variants: Res<Assets<ItemVariant>>,
+    mut commands: Commands,
+) {
+    for (entity, prefab, mut item) in query.iter_mut() {
+        let Some(variant) = variants.get(&variants.get_handle(*prefab.variant)) else {
+            continue;
+        };
+        apply_to_item(variant, &mut item);
+        commands
+            .entity(entity)
+            .insert((variant.modifiers, VariantApplied));
+    }
+}
+
+/// Tinted materials shared by all items of a variant
+#[derive(Resource, Default)]
+struct VariantMaterials {
+    materials: HashMap<AssetPathId, Handle<StandardMaterial>>,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn apply_variants_client(
+    mut query: Query<(Entity, &PrefabVariantClient, &mut Item), Without<VariantApplied>>,
+    children: Query<&Children>,
+    mut material_handles: Query<&mut Handle<StandardMaterial>>,
+    variants: Res<Assets<ItemVariant>>,
+    item_assets: Res<ItemAssets>,
+    mut variant_materials: ResMut<VariantMaterials>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut commands: Commands,
+) {
+    for (entity, prefab, mut item) in query.iter_mut() {
+        let variant_id = *prefab.variant;
+        let Some(variant) = variants.get(&variants.get_handle(variant_id)) else {
+            continue;
+        };
+        apply_to_item(variant, &mut item);
+
+        if let (Some([r, g, b, a]), Some(client)) = (variant.color, item_assets.client.as_ref()) {
+            let material = variant_materials
+                .materials
+                .entry(variant_id)
+                .or_insert_with(|| {
+                    let mut material = materials
+                        .get(&client.default_material)
+                        .cloned()
+                        .unwrap_or_default();
+                    material.base_color = Color::rgba(r, g, b, a);
+                    materials.add(material)
+                })
+                .clone();
+
+            for target in std::iter::once(entity).chain(children.iter_descendants(entity)) {
+                if let Ok(mut handle) = material_handles.get_mut(target) {
+                    *handle = material.clone();
+                }
+            }
+        }
+
+        commands
+            .entity(entity)
+            .insert((variant.modifiers, VariantApplied));
+    }
+}
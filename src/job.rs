@@ -14,6 +14,8 @@ use networking::{
 };
 use serde::{Deserialize, Serialize};
 
+use crate::body::ghost::Ghost;
+
 pub struct JobPlugin;
 
 impl Plugin for JobPlugin {
@@ -35,6 +37,15 @@ pub struct JobDefinition {
     pub name: String,
     pub description: String,
     pub clothing: Vec<String>,
+    /// Areas the job has access to
+    #[serde(default)]
+    pub access: Vec<String>,
+    /// Credits in the bank account at round start
+    #[serde(default)]
+    pub starting_credits: u32,
+    /// Credits paid every salary period
+    #[serde(default)]
+    pub salary: u32,
 }
 
 #[derive(Resource)]
@@ -72,6 +83,17 @@ impl SelectedJobs {
             .filter_map(|(c, def)| def.map(|j| (c, j)))
     }
 
+    pub fn set(&mut self, connection: ConnectionId, job: Option<AssetPathId>) {
+        match job {
+            Some(job) => {
+                self.selected.insert(connection, job);
+            }
+            None => {
+                self.selected.remove(&connection);
+            }
+        }
+    }
+
     pub fn get<'a>(
         &'a self,
         connection: ConnectionId,
@@ -92,6 +114,7 @@ fn handle_job_selection(
     mut messages: EventReader<MessageEvent<SelectJobMessage>>,
     players: Res<Players>,
     controlled: Res<ClientControls>,
+    ghosts: Query<(), With<Ghost>>,
     mut resource: ResMut<SelectedJobs>,
 ) {
     for event in messages.iter() {
@@ -99,18 +122,16 @@ fn handle_job_selection(
             Some(p) => p,
             None => continue,
         };
-        // Only allow job selection if not already a character in the game
-        if controlled.controlled_entity(player.id).is_some() {
-            return;
-        }
-        match event.message.job {
-            Some(job) => {
-                resource.selected.insert(event.connection, job);
-            }
-            None => {
-                resource.selected.remove(&event.connection);
+        // Only allow job selection if not already a character in the game,
+        // or a ghost whose job was freed when abandoning their character
+        if let Some(entity) = controlled.controlled_entity(player.id) {
+            let job_freed =
+                ghosts.contains(entity) && !resource.selected.contains_key(&event.connection);
+            if !job_freed {
+                return;
             }
         }
+        resource.set(event.connection, event.message.job);
     }
 }
 
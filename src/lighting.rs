@@ -0,0 +1,462 @@
+//! Light fixtures lighting up the station.
+//!
+//! Fixtures are tile entities with a [`LightFixture`]. Clients give them a light, which shines while the
+//! fixture is powered and not broken. Everything else only gets a faint ambient light, so areas
+//! without working fixtures are dark.
+
+use std::time::Duration;
+
+use bevy::{prelude::*, reflect::TypeUuid};
+use networking::{
+    component::AppExt,
+    is_server,
+    spawning::ClientControlled,
+    variable::{NetworkVar, ServerVar},
+    Networked,
+};
+
+use crate::{
+    audio::PlaySound,
+    body::ghost::ClientRespawn,
+    combat::{
+        damage::{Damage, DamageEvent, DamageQueue, DamageStage, KineticDamage, KineticShape},
+        emp::Emp,
+    },
+    communication::examine::{DescribeExamined, ExamineEvents},
+    despawn::{ContentsPolicy, DespawnCommandsExt},
+    interaction::{
+        ActiveInteraction, DoAfter, GenerateInteractionList, InteractionListEvents,
+        InteractionOption, InteractionSpecificity, InteractionStatus,
+    },
+    items::stacks::Stack,
+};
+
+pub struct LightingPlugin;
+
+impl Plugin for LightingPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<LightFixture>()
+            .register_type::<LightKind>()
+            .register_type::<ReplacementLight>()
+            .add_networked_component::<FixtureState, FixtureStateClient>();
+
+        if is_server(app) {
+            app.register_type::<SmashLightInteraction>()
+                .register_type::<ReplaceLightInteraction>()
+                .add_systems(
+                    Update,
+                    (
+                        add_fixture_state,
+                        break_lights.in_set(DamageStage::Apply),
+                        burst_lights.run_if(on_event::<Emp>()),
+                        (
+                            prepare_smash_light_interaction,
+                            prepare_replace_light_interaction,
+                        )
+                            .in_set(GenerateInteractionList),
+                        smash_light_interaction,
+                        replace_light_interaction,
+                        describe_fixtures.in_set(DescribeExamined),
+                    ),
+                );
+        } else {
+            app.insert_resource(AmbientLight {
+                brightness: DARK_AMBIENT_BRIGHTNESS,
+                ..Default::default()
+            })
+            .add_systems(
+                Update,
+                (
+                    client_add_fixture_lights,
+                    client_update_fixture_lights,
+                    client_update_ambient_light,
+                ),
+            );
+        }
+    }
+}
+
+/// Ambient light for players in a body, just enough to make out shapes in the dark
+const DARK_AMBIENT_BRIGHTNESS: f32 = 0.005;
+/// Ambient light for ghosts and spectators, who see everything
+const FULLBRIGHT_AMBIENT_BRIGHTNESS: f32 = 0.3;
+/// EMPs at least this strong burst the lights they reach
+const LIGHT_BURST_STRENGTH: f32 = 0.6;
+const SMASH_TIME: Duration = Duration::from_millis(800);
+const REPLACE_TIME: Duration = Duration::from_secs(2);
+const INTERACTION_RANGE: f32 = 2.5;
+
+#[derive(Reflect, Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LightKind {
+    /// Shines in all directions, like a light tube
+    #[default]
+    Point,
+    /// Shines down and away from the wall, like a small bulb
+    Spot,
+}
+
+/// A light mounted on the station, shining while powered and not broken
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct LightFixture {
+    pub kind: LightKind,
+    pub color: Color,
+    /// Luminous power in lumens
+    pub intensity: f32,
+    /// Distance the light reaches, in meters
+    pub range: f32,
+}
+
+impl Default for LightFixture {
+    fn default() -> Self {
+        Self {
+            kind: LightKind::Point,
+            color: Color::WHITE,
+            intensity: 800.0,
+            range: 8.0,
+        }
+    }
+}
+
+/// If the bulb of a fixture is broken
+#[derive(Component, Networked)]
+#[networked(client = "FixtureStateClient")]
+pub struct FixtureState {
+    broken: NetworkVar<bool>,
+}
+
+impl FixtureState {
+    pub fn is_broken(&self) -> bool {
+        *self.broken
+    }
+
+    pub fn set_broken(&mut self, broken: bool) {
+        if *self.broken != broken {
+            *self.broken = broken;
+        }
+    }
+}
+
+#[derive(Component, Default, TypeUuid, Networked)]
+#[uuid = "cfee2529-8fee-4801-867d-22ed4a65dcb3"]
+#[networked(server = "FixtureState")]
+pub struct FixtureStateClient {
+    broken: ServerVar<bool>,
+}
+
+impl FixtureStateClient {
+    pub fn is_broken(&self) -> bool {
+        self.broken.get().copied().unwrap_or_default()
+    }
+}
+
+/// A spare tube or bulb, used up to fix a broken fixture
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
+struct ReplacementLight;
+
+fn add_fixture_state(
+    fixtures: Query<Entity, (With<LightFixture>, Without<FixtureState>)>,
+    mut commands: Commands,
+) {
+    for entity in fixtures.iter() {
+        commands.entity(entity).insert(FixtureState {
+            broken: false.into(),
+        });
+    }
+}
+
+fn break_light(
+    state: &mut FixtureState,
+    transform: &GlobalTransform,
+    sounds: &mut EventWriter<PlaySound>,
+) {
+    if state.is_broken() {
+        return;
+    }
+    state.set_broken(true);
+    sounds.send(PlaySound::new(
+        "sounds/effects/glass_break.ogg",
+        transform.translation(),
+    ));
+}
+
+/// Any damage that gets through to a fixture breaks its bulb
+fn break_lights(
+    queue: Res<DamageQueue>,
+    mut fixtures: Query<(&mut FixtureState, &GlobalTransform)>,
+    parents: Query<&Parent>,
+    mut sounds: EventWriter<PlaySound>,
+) {
+    for event in queue.iter() {
+        let Some(fixture) = std::iter::once(event.target)
+            .chain(parents.iter_ancestors(event.target))
+            .find(|&e| fixtures.contains(e))
+        else {
+            continue;
+        };
+        let (mut state, transform) = fixtures.get_mut(fixture).unwrap();
+        break_light(&mut state, transform, &mut sounds);
+    }
+}
+
+/// Strong EMPs overload the fixtures around them
+fn burst_lights(
+    mut emps: EventReader<Emp>,
+    mut fixtures: Query<(&mut FixtureState, &GlobalTransform)>,
+    mut sounds: EventWriter<PlaySound>,
+) {
+    for emp in emps.iter() {
+        for (mut state, transform) in fixtures.iter_mut() {
+            let strong = emp
+                .strength_at(transform.translation())
+                .map_or(false, |strength| strength >= LIGHT_BURST_STRENGTH);
+            if strong {
+                break_light(&mut state, transform, &mut sounds);
+            }
+        }
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct SmashLightInteraction {
+    item: Entity,
+}
+
+impl FromWorld for SmashLightInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            item: Entity::PLACEHOLDER,
+        }
+    }
+}
+
+fn prepare_smash_light_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    fixtures: Query<&FixtureState>,
+) {
+    for event in interaction_list.events.iter() {
+        let Some(item) = event.item_in_hand else {
+            continue;
+        };
+        if fixtures.get(event.target).map_or(true, |s| s.is_broken()) {
+            continue;
+        }
+
+        event.add_interaction(InteractionOption {
+            text: "Smash light".into(),
+            interaction: Box::new(SmashLightInteraction { item }),
+            specificity: InteractionSpecificity::Generic,
+        });
+    }
+}
+
+/// Hits the fixture with the held item, which breaks it unless something in the damage pipeline stops it
+fn smash_light_interaction(
+    mut query: Query<(Entity, &SmashLightInteraction, &mut ActiveInteraction)>,
+    time: Res<Time>,
+    mut damage: EventWriter<DamageEvent>,
+) {
+    let now = time.elapsed_seconds();
+    for (user, interaction, mut active) in query.iter_mut() {
+        let do_after = DoAfter::new(SMASH_TIME).within_range(INTERACTION_RANGE);
+        if !active.do_after(do_after, now) {
+            continue;
+        }
+
+        damage.send(
+            DamageEvent::new(
+                active.target,
+                Damage::Kinetic(KineticDamage {
+                    velocity: 4.0,
+                    mass: 1.0,
+                    shape: KineticShape::Blunt,
+                }),
+            )
+            .source(user, Some(interaction.item)),
+        );
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+#[component(storage = "SparseSet")]
+struct ReplaceLightInteraction {
+    replacement: Entity,
+}
+
+impl FromWorld for ReplaceLightInteraction {
+    fn from_world(_: &mut World) -> Self {
+        Self {
+            replacement: Entity::PLACEHOLDER,
+        }
+    }
+}
+
+fn prepare_replace_light_interaction(
+    interaction_list: Res<InteractionListEvents>,
+    fixtures: Query<&FixtureState>,
+    replacements: Query<(), With<ReplacementLight>>,
+) {
+    for event in interaction_list.events.iter() {
+        let Some(replacement) = event
+            .item_in_hand
+            .filter(|&item| replacements.contains(item))
+        else {
+            continue;
+        };
+        if !fixtures.get(event.target).map_or(false, |s| s.is_broken()) {
+            continue;
+        }
+
+        event.add_interaction(InteractionOption {
+            text: "Replace light".into(),
+            interaction: Box::new(ReplaceLightInteraction { replacement }),
+            specificity: InteractionSpecificity::Specific,
+        });
+    }
+}
+
+fn replace_light_interaction(
+    mut query: Query<(&ReplaceLightInteraction, &mut ActiveInteraction)>,
+    mut fixtures: Query<&mut FixtureState>,
+    mut stacks: Query<&mut Stack>,
+    time: Res<Time>,
+    mut commands: Commands,
+) {
+    let now = time.elapsed_seconds();
+    for (interaction, mut active) in query.iter_mut() {
+        let do_after = DoAfter::new(REPLACE_TIME)
+            .within_range(INTERACTION_RANGE)
+            .stationary();
+        if !active.do_after(do_after, now) {
+            continue;
+        }
+
+        let Ok(mut state) = fixtures.get_mut(active.target) else {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        };
+        if !state.is_broken() {
+            active.status = InteractionStatus::Canceled;
+            continue;
+        }
+
+        state.set_broken(false);
+        let used_up = match stacks.get_mut(interaction.replacement) {
+            Ok(mut stack) => stack.take(1) && stack.count() == 0,
+            Err(_) => true,
+        };
+        if used_up {
+            commands.despawn_cascade(interaction.replacement, ContentsPolicy::Destroy);
+        }
+        active.status = InteractionStatus::Completed;
+    }
+}
+
+fn describe_fixtures(examines: Res<ExamineEvents>, fixtures: Query<&FixtureState>) {
+    for examine in examines.events.iter() {
+        if fixtures
+            .get(examine.target)
+            .map_or(false, |s| s.is_broken())
+        {
+            examine.add_line("The bulb is broken.");
+        }
+    }
+}
+
+/// The light a client spawned for a fixture
+#[derive(Component)]
+struct FixtureLight;
+
+fn fixture_intensity(fixture: &LightFixture, state: Option<&FixtureStateClient>) -> f32 {
+    if state.map_or(false, |s| s.is_broken()) {
+        0.0
+    } else {
+        fixture.intensity
+    }
+}
+
+fn client_add_fixture_lights(
+    fixtures: Query<(Entity, &LightFixture, Option<&FixtureStateClient>), Added<LightFixture>>,
+    mut commands: Commands,
+) {
+    for (entity, fixture, state) in fixtures.iter() {
+        let intensity = fixture_intensity(fixture, state);
+        let light = match fixture.kind {
+            LightKind::Point => commands
+                .spawn(PointLightBundle {
+                    point_light: PointLight {
+                        color: fixture.color,
+                        intensity,
+                        range: fixture.range,
+                        shadows_enabled: true,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .id(),
+            LightKind::Spot => commands
+                .spawn(SpotLightBundle {
+                    spot_light: SpotLight {
+                        color: fixture.color,
+                        intensity,
+                        range: fixture.range,
+                        shadows_enabled: true,
+                        outer_angle: 1.2,
+                        inner_angle: 0.6,
+                        ..Default::default()
+                    },
+                    // Down and away from the wall
+                    transform: Transform::from_rotation(Quat::from_rotation_x(-1.2)),
+                    ..Default::default()
+                })
+                .id(),
+        };
+        commands
+            .entity(light)
+            .insert(FixtureLight)
+            .set_parent(entity);
+    }
+}
+
+/// Dims the lights of broken fixtures. Power turns them on and off.
+fn client_update_fixture_lights(
+    fixtures: Query<(&LightFixture, Ref<FixtureStateClient>, &Children)>,
+    mut points: Query<&mut PointLight, With<FixtureLight>>,
+    mut spots: Query<&mut SpotLight, With<FixtureLight>>,
+) {
+    for (fixture, state, children) in fixtures.iter() {
+        if !state.is_changed() {
+            continue;
+        }
+        let intensity = fixture_intensity(fixture, Some(&state));
+        for &child in children.iter() {
+            if let Ok(mut light) = points.get_mut(child) {
+                light.intensity = intensity;
+            }
+            if let Ok(mut light) = spots.get_mut(child) {
+                light.intensity = intensity;
+            }
+        }
+    }
+}
+
+/// Keeps it dark for players in a body, ghosts and spectators see everything
+fn client_update_ambient_light(
+    respawn: Res<ClientRespawn>,
+    controlled: Query<(), With<ClientControlled>>,
+    mut ambient: ResMut<AmbientLight>,
+) {
+    let brightness = if respawn.is_ghost() || controlled.is_empty() {
+        FULLBRIGHT_AMBIENT_BRIGHTNESS
+    } else {
+        DARK_AMBIENT_BRIGHTNESS
+    };
+    if ambient.brightness != brightness {
+        ambient.brightness = brightness;
+    }
+}
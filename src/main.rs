@@ -1,41 +1,73 @@
 #![allow(clippy::type_complexity)]
 
+mod access;
 mod admin;
+mod ai;
+mod audio;
+mod barriers;
 mod body;
 mod camera;
+mod character_sheet;
 mod combat;
 mod communication;
 mod components;
 mod config;
 mod construction;
+mod controls;
 mod debug;
+mod despawn;
+mod doors;
+mod economy;
+mod environment;
+mod forensics;
+mod hints;
+mod holodeck;
 mod interaction;
 mod items;
 mod job;
+mod lighting;
 mod movement;
+mod navigation;
+mod persistence;
 mod round;
 mod scene;
+#[cfg(feature = "client")]
+mod singleplayer;
+mod spectator;
+mod storage;
+mod tutorial;
 mod ui;
+mod wear;
+mod webhooks;
 
 use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 use admin::AdminPlugin;
-use bevy::app::ScheduleRunnerPlugin;
-use bevy::asset::AssetPlugin;
+use bevy::app::{AppExit, ScheduleRunnerPlugin};
+use bevy::asset::{AssetPlugin, LoadState};
 use bevy::log::LogPlugin;
 use bevy::prelude::*;
 use bevy::scene::ScenePlugin;
 use bevy::tasks::{AsyncComputeTaskPool, Task};
+use bevy::utils::Uuid;
 use bevy_rapier3d::plugin::{NoUserData, RapierPhysicsPlugin};
 use bevy_rapier3d::prelude::Collider;
 use byond::tgm::TgmLoader;
 use clap::{Parser, Subcommand};
 use config::ServerConfig;
 use futures_lite::future;
-use maps::TileMapData;
+use maps::{
+    io::{MapFile, MapPrefabSlots, TileStateAppExt, MAP_FILE_EXTENSION},
+    TileMapData,
+};
 use networking::identity::EntityCommandsExt as NetworkingEntityCommandsExt;
-use networking::{NetworkRole, NetworkingPlugin, ServerAuthentication};
+use networking::{
+    protocol::ProtocolDescription, time::TickRateBounds, visibility::ObserverCellRate, NetworkRole,
+    NetworkingPlugin, ServerAuthentication, UserIdentity,
+};
+use round::RoundRng;
 
 #[cfg(feature = "client")]
 use {
@@ -45,13 +77,26 @@ use {
     networking::{ClientEvent, ConnectToken, TargetServer, UserData},
 };
 
-/// How many ticks the server runs per second
-const SERVER_TPS: u32 = 60;
+/// How many ticks the server runs per second, unless set in the server config
+const DEFAULT_SERVER_TPS: u32 = 60;
 
 #[derive(Parser, Resource)]
 struct Args {
     #[clap(subcommand)]
     command: Option<ArgCommands>,
+    /// write all network messages and synced types to a file and exit.
+    /// uses markdown for `.md` files and JSON otherwise
+    #[clap(long, global = true)]
+    dump_protocol: Option<PathBuf>,
+}
+
+impl Args {
+    fn is_singleplayer(&self) -> bool {
+        #[cfg(feature = "client")]
+        return matches!(self.command, Some(ArgCommands::Singleplayer { .. }));
+        #[cfg(not(feature = "client"))]
+        false
+    }
 }
 
 #[derive(Subcommand)]
@@ -64,6 +109,9 @@ enum ArgCommands {
         /// set this when hosting behind NAT (ex. a home router)
         #[clap(long)]
         public_address: Option<IpAddr>,
+        /// run a local tutorial server, started by the `tutorial` command
+        #[clap(long, hide = true)]
+        tutorial: bool,
     },
     #[cfg(feature = "client")]
     /// join a game
@@ -74,14 +122,91 @@ enum ArgCommands {
         /// base64 encoded connection token
         token: String,
     },
+    #[cfg(feature = "client")]
+    /// learn the basics on a local tutorial map
+    Tutorial,
+    #[cfg(feature = "client")]
+    /// play alone on a server running in the same process
+    Singleplayer { name: String },
+    /// print a connection token for this server, signed with the private key of its registration
+    IssueToken {
+        /// address clients reach the server at
+        server_address: SocketAddr,
+        username: String,
+        /// id of the player, a random one is used if not set
+        #[clap(long)]
+        id: Option<Uuid>,
+    },
 }
 
 fn main() {
     let args = Args::parse();
+    if let Some(ArgCommands::IssueToken {
+        server_address,
+        username,
+        id,
+    }) = &args.command
+    {
+        if let Err(err) = issue_token(*server_address, username, *id) {
+            eprintln!("Failed to issue token: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
     let role = match args.command {
         Some(ArgCommands::Host { .. }) => NetworkRole::Server,
         _ => NetworkRole::Client,
     };
+
+    #[cfg(feature = "client")]
+    let local_server = match args.command {
+        Some(ArgCommands::Singleplayer { .. }) => Some(singleplayer::start_server()),
+        _ => None,
+    };
+
+    let Some(mut app) = create_app(args, role) else {
+        return;
+    };
+    #[cfg(feature = "client")]
+    if let Some(address) = local_server {
+        app.insert_resource(address);
+    }
+    app.run();
+}
+
+/// Prints a base64 encoded token for joining with `join-token`
+fn issue_token(server_address: SocketAddr, username: &str, id: Option<Uuid>) -> Result<(), String> {
+    let config = config::load_server_config().map_err(|err| err.to_string())?;
+    let Some(registration) = config.registration else {
+        return Err(
+            "the server config has no registration, clients can join without a token".into(),
+        );
+    };
+    if username.is_empty() || username.len() > UserIdentity::MAX_USERNAME_BYTES {
+        return Err(format!(
+            "usernames need 1 to {} bytes",
+            UserIdentity::MAX_USERNAME_BYTES
+        ));
+    }
+
+    let identity = UserIdentity {
+        id: id.unwrap_or_else(Uuid::new_v4),
+        username: username.to_owned(),
+    };
+    let token = networking::generate_connect_token(
+        &registration.private_key,
+        vec![server_address],
+        &identity,
+    )
+    .map_err(|err| err.to_string())?;
+    let mut data = Vec::new();
+    token.write(&mut data).map_err(|err| err.to_string())?;
+    println!("{}", base64::encode(data));
+    Ok(())
+}
+
+/// Builds the app for one side of the game, returns `None` if the server configuration is invalid
+fn create_app(args: Args, role: NetworkRole) -> Option<App> {
     let networking_plugin = NetworkingPlugin { role };
 
     let mut app = App::new();
@@ -89,21 +214,45 @@ fn main() {
 
     match role {
         NetworkRole::Server => {
-            match config::load_server_config() {
-                Ok(config) => app.insert_resource(config),
+            let tps = match config::load_server_config() {
+                Ok(mut config) => {
+                    if let Some(ArgCommands::Host { tutorial: true, .. }) = args.command {
+                        tutorial::configure_server(&mut config);
+                        app.insert_resource(tutorial::TutorialMode);
+                    }
+                    #[cfg(feature = "client")]
+                    if args.is_singleplayer() {
+                        singleplayer::configure_server(&mut config);
+                    }
+                    let tps = config.tick_rate.unwrap_or(DEFAULT_SERVER_TPS).max(1);
+                    app.insert_resource(TickRateBounds {
+                        min_tps: config.min_tps.unwrap_or(tps / 2).clamp(1, tps) as f64,
+                        max_tps: tps as f64,
+                    })
+                    .insert_resource(config.network_guard.clone())
+                    .insert_resource(ObserverCellRate {
+                        new_cells_per_tick: config
+                            .new_cells_per_tick
+                            .unwrap_or(ObserverCellRate::default().new_cells_per_tick),
+                    })
+                    .insert_resource(config);
+                    tps
+                }
                 Err(err) => {
                     error!("Error loading server configuration: {}", err);
-                    return;
+                    return None;
                 }
             };
 
-            let runner =
-                ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(1f64 / SERVER_TPS as f64));
+            let runner = ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(1f64 / tps as f64));
+            // A singleplayer server logs through the client it runs next to
+            if !args.is_singleplayer() {
+                app.add_plugins(LogPlugin::default());
+            }
             app.add_plugins((
                 MinimalPlugins.set(runner),
                 TransformPlugin,
                 AssetPlugin::default(),
-                LogPlugin::default(),
                 ScenePlugin,
                 HierarchyPlugin,
                 networking_plugin,
@@ -123,9 +272,13 @@ fn main() {
             .register_type::<Vec<Entity>>()
             .add_asset_loader(TgmLoader)
             .add_systems(Startup, (setup_server, config::server_startup))
-            .add_systems(Update, (convert_tgm_map, create_tilemap_from_converted));
+            .add_systems(Update, (convert_map, create_tilemap_from_converted));
         }
         NetworkRole::Client => {
+            #[cfg(feature = "client")]
+            if let Some(ArgCommands::Tutorial) = args.command {
+                app.insert_resource(tutorial::TutorialMode);
+            }
             #[cfg(feature = "client")]
             app.add_plugins((
                 DefaultPlugins.set(WindowPlugin {
@@ -137,6 +290,7 @@ fn main() {
                 }),
                 networking_plugin,
                 camera::CameraPlugin,
+                controls::ControlsPlugin,
                 EguiPlugin,
                 debug::DebugPlugin,
             ))
@@ -158,6 +312,7 @@ fn main() {
         scene::ScenePlugin,
         movement::MovementPlugin,
         maps::MapPlugin,
+        power::PowerPlugin,
         AdminPlugin,
         items::ItemPlugin,
         body::BodyPlugin,
@@ -168,10 +323,36 @@ fn main() {
         combat::CombatPlugin,
         communication::CommunicationPlugin,
     ))
-    .add_plugins((ui::UiPlugin,))
+    .add_plugins((
+        chemistry::ChemistryPlugin,
+        ui::UiPlugin,
+        character_sheet::CharacterSheetPlugin,
+        doors::DoorPlugin,
+        access::AccessPlugin,
+        persistence::PersistencePlugin,
+        forensics::ForensicsPlugin,
+        economy::EconomyPlugin,
+        holodeck::HolodeckPlugin,
+        barriers::BarrierPlugin,
+        tutorial::TutorialPlugin,
+        ai::AiPlugin,
+        navigation::NavigationPlugin,
+        wear::WearPlugin,
+        spectator::SpectatorPlugin,
+    ))
+    .add_plugins((
+        hints::HintPlugin,
+        audio::SoundPlugin,
+        lighting::LightingPlugin,
+        webhooks::WebhookPlugin,
+        storage::StoragePlugin,
+        environment::EnvironmentPlugin,
+    ))
+    // The power crate doesn't know about maps
+    .save_tile_state::<power::AreaPowerController>()
     .insert_resource(args)
-    .add_systems(Startup, setup_shared)
-    .run();
+    .add_systems(Startup, (setup_shared, dump_protocol));
+    Some(app)
 }
 
 #[derive(Clone, Eq, PartialEq, Debug, Default, Hash, States)]
@@ -210,10 +391,39 @@ impl Default for Player {
     }
 }
 
+#[derive(Clone)]
+pub enum MapHandle {
+    Tgm(Handle<byond::tgm::TileMap>),
+    Native(Handle<MapFile>),
+}
+
 #[derive(Clone, Resource)]
 pub struct Map {
-    pub handle: Handle<byond::tgm::TileMap>,
+    pub handle: MapHandle,
     pub spawned: bool,
+    /// Prefabs picked for the slots of a native map, picked once the map file is loaded
+    pub prefabs: Option<Vec<Option<Handle<MapFile>>>>,
+}
+
+impl Map {
+    /// Loads a map by name, preferring the native format over TGM.
+    pub fn load(server: &AssetServer, name: &str) -> Self {
+        let native_path = format!("maps/{}.{}", name, MAP_FILE_EXTENSION);
+        let native_exists = server
+            .asset_io()
+            .get_metadata(Path::new(&native_path))
+            .is_ok();
+        let handle = if native_exists {
+            MapHandle::Native(server.load(native_path))
+        } else {
+            MapHandle::Tgm(server.load(format!("maps/{}.dmm", name)))
+        };
+        Self {
+            handle,
+            spawned: false,
+            prefabs: None,
+        }
+    }
 }
 
 fn setup_shared(mut commands: Commands) {
@@ -230,6 +440,7 @@ fn setup_server(args: Res<Args>, server_config: Res<ServerConfig>, mut commands:
         &ArgCommands::Host {
             bind_address,
             public_address,
+            ..
         } => {
             let authentication = match &server_config.registration {
                 Some(registration) => {
@@ -247,12 +458,46 @@ fn setup_server(args: Res<Args>, server_config: Res<ServerConfig>, mut commands:
                 networking::create_server(bind_address, public_address, authentication);
             commands.insert_resource(server);
             commands.insert_resource(transport);
+            commands.insert_resource(networking::PlayerSlots {
+                max_players: server_config.max_players,
+            });
         }
+        // The server and its local transport are inserted when the server is created
         #[cfg(feature = "client")]
+        ArgCommands::Singleplayer { .. } => {
+            commands.insert_resource(networking::PlayerSlots {
+                max_players: server_config.max_players,
+            });
+        }
         _ => panic!("Missing commandline argument"),
     };
 }
 
+fn dump_protocol(
+    args: Res<Args>,
+    protocol: Res<ProtocolDescription>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let Some(path) = args.dump_protocol.as_ref() else {
+        return;
+    };
+    let text = if path.extension().map_or(false, |e| e == "md") {
+        Ok(protocol.to_markdown())
+    } else {
+        protocol.to_json()
+    };
+    let result = text
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+        .and_then(|text| std::fs::write(path, text));
+    match result {
+        Ok(()) => info!(path = %path.display(), "Wrote protocol description"),
+        Err(err) => {
+            error!(path = %path.display(), error = %err, "Failed to write protocol description")
+        }
+    }
+    exit.send(AppExit);
+}
+
 #[cfg(feature = "client")]
 fn setup_client(
     mut commands: Commands,
@@ -260,12 +505,6 @@ fn setup_client(
     mut client_events: EventWriter<ClientEvent>,
     mut state: ResMut<NextState<GameState>>,
 ) {
-    // TODO: Replace with on-station lights
-    commands.insert_resource(AmbientLight {
-        brightness: 0.1,
-        ..Default::default()
-    });
-
     let temporary_camera_target = commands.spawn(GlobalTransform::default()).id();
 
     commands.spawn((
@@ -294,12 +533,32 @@ fn setup_client(
         let token_data = base64::decode(token).expect("invalid token: not valid base64");
         let mut reader = std::io::Cursor::new(token_data);
         let token = ConnectToken::read(&mut reader).expect("invalid token: not a connection token");
+        // The username is part of the token
         client_events.send(ClientEvent::Join(TargetServer::Token(Box::new(token))));
+    }
 
+    // Play on the server started next to the client
+    if let Some(ArgCommands::Singleplayer { name }) = &args.command {
+        state.set(GameState::MainMenu);
+        client_events.send(ClientEvent::Join(TargetServer::Local));
         commands.insert_resource(UserData {
-            username: "Change me".to_owned(),
+            username: name.clone(),
         });
     }
+
+    // Start a local server to play the tutorial on
+    if let Some(ArgCommands::Tutorial) = &args.command {
+        match tutorial::start_server() {
+            Ok(address) => {
+                state.set(GameState::MainMenu);
+                client_events.send(ClientEvent::Join(TargetServer::Raw(address)));
+                commands.insert_resource(UserData {
+                    username: tutorial::USERNAME.to_owned(),
+                });
+            }
+            Err(err) => error!("Failed to start tutorial server: {}", err),
+        }
+    }
 }
 
 #[cfg(feature = "client")]
@@ -344,19 +603,73 @@ fn set_camera_target(
 #[derive(Component)]
 struct ConvertByondMap(Task<TileMapData>);
 
-fn convert_tgm_map(
+fn convert_map(
     mut commands: Commands,
     map_resource: Option<ResMut<Map>>,
     tilemaps: Res<Assets<byond::tgm::TileMap>>,
+    map_files: Res<Assets<MapFile>>,
+    asset_server: Res<AssetServer>,
+    mut rng: Option<ResMut<RoundRng>>,
 ) {
-    if let Some(res) = map_resource {
-        if let Some(map) = tilemaps.get(&res.handle) {
-            let map_copy = byond::tgm::TileMap::clone(map);
-            let thread_pool = AsyncComputeTaskPool::get();
-            let task =
-                thread_pool.spawn(async move { byond::tgm::conversion::to_map_data(&map_copy) });
-            let new_entity = commands.spawn(ConvertByondMap(task)).id();
-            info!("Scheduled tgm map conversion (entity={:?})", new_entity);
+    let Some(mut res) = map_resource else {
+        return;
+    };
+    let res = &mut *res;
+    match &res.handle {
+        MapHandle::Tgm(handle) => {
+            if let Some(map) = tilemaps.get(handle) {
+                let map_copy = byond::tgm::TileMap::clone(map);
+                let thread_pool = AsyncComputeTaskPool::get();
+                let task = thread_pool
+                    .spawn(async move { byond::tgm::conversion::to_map_data(&map_copy) });
+                let new_entity = commands.spawn(ConvertByondMap(task)).id();
+                info!("Scheduled tgm map conversion (entity={:?})", new_entity);
+                commands.remove_resource::<Map>();
+            }
+        }
+        MapHandle::Native(handle) => {
+            let Some(map) = map_files.get(handle) else {
+                return;
+            };
+            let prefabs = res.prefabs.get_or_insert_with(|| {
+                map.prefab_slots
+                    .iter()
+                    .map(|slot| {
+                        let last = slot.prefabs.len().saturating_sub(1) as u32;
+                        let index = rng.as_mut().map_or(0, |rng| rng.range(0..=last));
+                        let path = slot.prefabs.get(index as usize)?;
+                        Some(asset_server.load(path.as_str()))
+                    })
+                    .collect()
+            });
+            let loading = prefabs.iter().flatten().any(|prefab| {
+                !map_files.contains(prefab)
+                    && asset_server.get_load_state(prefab) != LoadState::Failed
+            });
+            if loading {
+                return;
+            }
+
+            let mut data = map.to_map_data();
+            for (slot, prefab) in map.prefab_slots.iter().zip(prefabs.iter()) {
+                let Some(prefab) = prefab else {
+                    continue;
+                };
+                match map_files.get(prefab) {
+                    Some(prefab_file) => prefab_file.place_prefab(&mut data, slot),
+                    None => warn!(position = ?slot.position, "Failed to load prefab for map slot"),
+                }
+            }
+            let entity = commands
+                .spawn((
+                    data,
+                    map.environment.clone(),
+                    MapPrefabSlots(map.prefab_slots.clone()),
+                    SpatialBundle::default(),
+                ))
+                .networked()
+                .id();
+            info!("Loaded native map (entity={:?})", entity);
             commands.remove_resource::<Map>();
         }
     }
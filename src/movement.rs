@@ -1,15 +1,25 @@
 use std::time::Duration;
 
 use crate::{
+    audio::PlaySound,
     body::{
-        health::{BrainState, BrainStateEvent},
+        facing::ClientFacing,
+        health::{pain::PainClient, BrainState, BrainStateEvent},
         Body,
     },
     camera::{MainCamera, TopDownCamera},
-    combat::{ClientCombatModeStatus, CombatModeClient},
+    combat::{
+        grab::{GrabbingClient, PULLING_MOVEMENT_FACTOR},
+        nonlethal::{Subdued, SubduedClient},
+        ClientCombatModeStatus, CombatModeClient,
+    },
+    controls::Actions,
     Player,
 };
-use bevy::{ecs::query::Has, math::Vec3Swizzles, prelude::*, time::common_conditions::on_timer};
+use bevy::{
+    ecs::query::Has, math::Vec3Swizzles, prelude::*, time::common_conditions::on_timer,
+    utils::HashMap,
+};
 use bevy_rapier3d::prelude::{ExternalForce, ReadMassProperties, Velocity};
 use networking::{
     messaging::{AppExt, MessageEvent, MessageReceivers, MessageSender},
@@ -21,7 +31,7 @@ use serde::{Deserialize, Serialize};
 
 pub fn movement_system(
     time: Res<Time>,
-    keyboard_input: Res<Input<KeyCode>>,
+    actions: Actions,
     mut query: Query<
         (
             Entity,
@@ -30,23 +40,28 @@ pub fn movement_system(
             Option<&mut ExternalForce>,
             &ReadMassProperties,
             Has<ClientMovementClient>,
+            Option<&PainClient>,
+            Option<&SubduedClient>,
+            Has<GrabbingClient>,
         ),
         With<ClientControlled>,
     >,
     camera_query: Query<&TopDownCamera, With<MainCamera>>,
     mut commands: Commands,
 ) {
-    for (entity, mut player, velocity, forces, mass_properties, can_move) in query.iter_mut() {
+    for (entity, mut player, velocity, forces, mass_properties, can_move, pain, subdued, pulling) in
+        query.iter_mut()
+    {
         // Reset force if we can't move
-        if !can_move {
+        let stunned = subdued.map_or(false, |s| s.is_stunned());
+        if !can_move || stunned {
             if let Some(mut forces) = forces {
                 forces.force = Vec3::ZERO;
             }
             continue;
         }
 
-        let axis_x = movement_axis(&keyboard_input, KeyCode::W, KeyCode::S);
-        let axis_z = movement_axis(&keyboard_input, KeyCode::D, KeyCode::A);
+        let input = actions.movement();
 
         let current_angle = match camera_query.get_single() {
             Ok(c) => c.current_angle(),
@@ -55,17 +70,21 @@ pub fn movement_system(
 
         // Figure out where we want to go by key input and camera angle
         let target_direction = Quat::from_euler(bevy::math::EulerRot::XYZ, 0.0, current_angle, 0.0)
-            .mul_vec3(Vec3::new(axis_x, 0.0, axis_z))
+            .mul_vec3(Vec3::new(input.y, 0.0, input.x))
             .xz();
         player.target_direction = target_direction;
 
         // What is our ideal speed
-        let mut ideal_speed: Vec2 = target_direction * player.max_velocity;
-
-        // Prevent diagonal movement being twice as fast
-        if target_direction.length_squared() > f32::EPSILON {
-            ideal_speed /= target_direction.length();
-        }
+        let pull_factor = if pulling {
+            PULLING_MOVEMENT_FACTOR
+        } else {
+            1.0
+        };
+        let max_velocity = player.max_velocity
+            * pain.map_or(1.0, |pain| pain.stage().movement_factor())
+            * pull_factor;
+        // Input is at most length 1, so diagonal movement isn't faster
+        let ideal_speed: Vec2 = target_direction * max_velocity;
 
         // Move target velocity towards ideal speed, by acceleration
         let difference: Vec2 = ideal_speed - player.target_velocity;
@@ -109,6 +128,7 @@ fn character_rotation_system(
         (With<ClientControlled>, With<ClientMovementClient>),
     >,
     combat_mode: ClientCombatModeStatus,
+    facing: Res<ClientFacing>,
 ) {
     let is_combat = combat_mode.is_enabled();
     for (player, mut transform, combat) in query.iter_mut() {
@@ -120,10 +140,14 @@ fn character_rotation_system(
             }
             _ => {
                 let direction = player.target_direction;
-                if direction.length() < 0.01 {
+                if direction.length() >= 0.01 {
+                    direction.normalize()
+                } else if let Some(direction) = facing.idle_turn(transform.rotation) {
+                    // Standing still, turn around once the head can't look any further
+                    direction
+                } else {
                     continue;
                 }
-                direction.normalize()
             }
         };
 
@@ -153,17 +177,6 @@ fn character_rotation_system(
     }
 }
 
-fn movement_axis(input: &Res<Input<KeyCode>>, plus: KeyCode, minus: KeyCode) -> f32 {
-    let mut axis = 0.0;
-    if input.pressed(plus) {
-        axis += 1.0;
-    }
-    if input.pressed(minus) {
-        axis -= 1.0;
-    }
-    axis
-}
-
 fn send_movement_update(
     // Require client control and already having a position from the server
     query: Query<
@@ -188,10 +201,18 @@ fn send_movement_update(
 }
 
 fn handle_movement_message(
-    mut query: Query<&mut Transform, With<ClientMovement>>,
+    mut query: Query<
+        (
+            &mut Transform,
+            Option<&ClientAuthoritativeTransform>,
+            Option<&Subdued>,
+        ),
+        With<ClientMovement>,
+    >,
     controls: Res<ClientControls>,
     players: Res<Players>,
     mut messages: EventReader<MessageEvent<MovementMessage>>,
+    mut sender: MessageSender,
     mut commands: Commands,
 ) {
     for event in messages.iter() {
@@ -201,7 +222,24 @@ fn handle_movement_message(
         };
 
         if let Some(controlled) = controls.controlled_entity(player.id) {
-            if let Ok(mut transform) = query.get_mut(controlled) {
+            if let Ok((mut transform, authoritative, subdued)) = query.get_mut(controlled) {
+                // Stunned creatures can't walk, no matter what their client says
+                if subdued.map_or(false, |s| s.is_stunned()) {
+                    let drift = authoritative.map_or(0.0, |authoritative| {
+                        authoritative.position.distance(event.message.position)
+                    });
+                    if drift > MAX_STUNNED_DRIFT {
+                        sender.send(
+                            &ForcePositionMessage {
+                                position: transform.translation,
+                                rotation: transform.rotation,
+                            },
+                            MessageReceivers::Single(event.connection),
+                        );
+                    }
+                    continue;
+                }
+
                 transform.translation = event.message.position;
                 transform.rotation = event.message.rotation;
                 // Reset velocity to prevent server physics from going crazy
@@ -281,6 +319,9 @@ fn force_position_on_rejoin(
     }
 }
 
+/// How far a stunned creature's client may slide before it is put back
+const MAX_STUNNED_DRIFT: f32 = 0.5;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct MovementMessage {
     position: Vec3,
@@ -299,8 +340,8 @@ pub struct ForcePositionMessage {
 struct ForcePositionReceived;
 
 #[derive(Component)]
-struct ClientAuthoritativeTransform {
-    position: Vec3,
+pub(crate) struct ClientAuthoritativeTransform {
+    pub(crate) position: Vec3,
     rotation: Quat,
 }
 
@@ -367,6 +408,33 @@ fn prevent_movement_when_unconcious(
     }
 }
 
+/// Meters walked between footstep sounds
+const STEP_DISTANCE: f32 = 1.4;
+const FOOTSTEP_SOUND: &str = "sounds/footsteps/floor.ogg";
+
+/// Plays footsteps for walking creatures
+fn footstep_sounds(
+    walkers: Query<(Entity, &GlobalTransform), With<Player>>,
+    mut walked: Local<HashMap<Entity, (Vec3, f32)>>,
+    mut sounds: EventWriter<PlaySound>,
+) {
+    walked.retain(|entity, _| walkers.contains(*entity));
+    for (entity, transform) in walkers.iter() {
+        let position = transform.translation();
+        let (last, distance) = walked.entry(entity).or_insert((position, 0.0));
+        *distance += last.xz().distance(position.xz());
+        *last = position;
+        if *distance >= STEP_DISTANCE {
+            *distance = 0.0;
+            sounds.send(
+                PlaySound::new(FOOTSTEP_SOUND, position)
+                    .volume(0.5)
+                    .range(8.0),
+            );
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, SystemSet)]
 pub enum MovementSystem {
     Update,
@@ -405,6 +473,7 @@ impl Plugin for MovementPlugin {
                     handle_movement_message,
                     force_position_on_rejoin,
                     prevent_movement_when_unconcious.run_if(on_event::<BrainStateEvent>()),
+                    footstep_sounds,
                 ),
             )
             .add_systems(
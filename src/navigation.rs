@@ -0,0 +1,203 @@
+//! Which tiles creatures can walk on, and paths between them.
+//!
+//! [`TilemapNav`] caches passability per tile. It's only updated for tiles
+//! flagged as changed in the [`TileMap`], doors that open or close, and
+//! tile entities whose colliders finished loading.
+
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use bevy_rapier3d::prelude::{Collider, ColliderDisabled};
+use maps::{tile_neighbours, TileEntity, TileMap};
+use networking::is_server;
+
+use crate::doors::Door;
+
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut App) {
+        if is_server(app) {
+            app.init_resource::<TilemapNav>()
+                .add_systems(Update, update_navigation);
+        }
+    }
+}
+
+/// Most tiles looked at when searching for a path, so unreachable goals stay cheap
+const MAX_SEARCHED_TILES: usize = 512;
+/// Colliders up to this half height are floors, anything taller blocks the tile
+const FLOOR_HALF_HEIGHT: f32 = 0.1;
+
+/// Tile position of a point in the world
+pub fn tile_at(position: Vec3) -> Option<UVec2> {
+    let tile = Vec2::new(position.x, position.z).round();
+    (tile.x >= 0.0 && tile.y >= 0.0).then(|| tile.as_uvec2())
+}
+
+/// Center of a tile at floor height
+pub fn tile_center(tile: UVec2) -> Vec3 {
+    Vec3::new(tile.x as f32, 0.0, tile.y as f32)
+}
+
+/// Passability of the tiles of the station map
+#[derive(Resource, Default)]
+pub struct TilemapNav {
+    walkable: HashSet<UVec2>,
+}
+
+impl TilemapNav {
+    /// If a tile has a floor and nothing solid on it.
+    /// Open doors are walkable, closed ones are not.
+    pub fn is_walkable(&self, tile: UVec2) -> bool {
+        self.walkable.contains(&tile)
+    }
+
+    /// Closest walkable tile within `max_distance` tiles, checking `tile` itself first.
+    pub fn nearest_walkable(&self, tile: UVec2, max_distance: u32) -> Option<UVec2> {
+        let center = tile.as_ivec2();
+        let max_distance = max_distance as i32;
+        (0..=max_distance).find_map(|distance| {
+            (-distance..=distance)
+                .flat_map(|x| (-distance..=distance).map(move |y| IVec2::new(x, y)))
+                .filter(|offset| offset.x.abs().max(offset.y.abs()) == distance)
+                .map(|offset| center + offset)
+                .filter(|position| position.min_element() >= 0)
+                .map(IVec2::as_uvec2)
+                .find(|&position| self.is_walkable(position))
+        })
+    }
+
+    /// Finds the shortest path between two tiles, excluding the start tile.
+    pub fn find_path(&self, from: UVec2, to: UVec2) -> Option<Vec<UVec2>> {
+        if from == to {
+            return Some(Vec::new());
+        }
+        if !self.is_walkable(to) {
+            return None;
+        }
+
+        let heuristic = |tile: UVec2| {
+            let d = tile.as_ivec2() - to.as_ivec2();
+            d.x.unsigned_abs() + d.y.unsigned_abs()
+        };
+
+        let mut open = BinaryHeap::new();
+        let mut came_from: HashMap<UVec2, UVec2> = HashMap::default();
+        let mut cost: HashMap<UVec2, u32> = HashMap::default();
+        open.push(Reverse((heuristic(from), from.x, from.y)));
+        cost.insert(from, 0);
+
+        while let Some(Reverse((_, x, y))) = open.pop() {
+            let current = UVec2::new(x, y);
+            if current == to {
+                let mut path = vec![current];
+                let mut tile = current;
+                while let Some(&previous) = came_from.get(&tile) {
+                    if previous == from {
+                        break;
+                    }
+                    path.push(previous);
+                    tile = previous;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if cost.len() > MAX_SEARCHED_TILES {
+                return None;
+            }
+
+            let next_cost = cost[&current] + 1;
+            for (_, neighbour) in tile_neighbours(current) {
+                if !self.is_walkable(neighbour)
+                    || cost.get(&neighbour).map_or(false, |&c| c <= next_cost)
+                {
+                    continue;
+                }
+                cost.insert(neighbour, next_cost);
+                came_from.insert(neighbour, current);
+                open.push(Reverse((
+                    next_cost + heuristic(neighbour),
+                    neighbour.x,
+                    neighbour.y,
+                )));
+            }
+        }
+        None
+    }
+}
+
+fn update_navigation(
+    mut tilemaps: Query<&mut TileMap>,
+    mut nav: ResMut<TilemapNav>,
+    changed_doors: Query<&TileEntity, Changed<Door>>,
+    new_colliders: Query<Entity, Added<Collider>>,
+    tile_entities: Query<&TileEntity>,
+    parents: Query<&Parent>,
+    blockers: TileBlockers,
+) {
+    let Ok(mut map) = tilemaps.get_single_mut() else {
+        return;
+    };
+    if map.is_added() {
+        nav.walkable.clear();
+    }
+
+    let mut dirty: HashSet<UVec2> = changed_doors.iter().map(TileEntity::position).collect();
+    // Scenes of tile entities load after they were placed in the map
+    for entity in new_colliders.iter() {
+        let tile = std::iter::once(entity)
+            .chain(parents.iter_ancestors(entity))
+            .find_map(|e| tile_entities.get(e).ok());
+        if let Some(tile) = tile {
+            dirty.insert(tile.position());
+        }
+    }
+    if map.has_changed_tiles() {
+        // Reading the flags isn't a change other systems care about
+        dirty.extend(map.bypass_change_detection().take_changed_tiles());
+    }
+
+    for tile in dirty {
+        let walkable = map.tile(tile).map_or(false, |reference| {
+            reference.turf.is_some()
+                && [reference.turf, reference.furniture]
+                    .into_iter()
+                    .flatten()
+                    .all(|entity| !blockers.blocks(entity))
+        });
+        if walkable {
+            nav.walkable.insert(tile);
+        } else {
+            nav.walkable.remove(&tile);
+        }
+    }
+}
+
+#[derive(SystemParam)]
+struct TileBlockers<'w, 's> {
+    doors: Query<'w, 's, &'static Door>,
+    children: Query<'w, 's, &'static Children>,
+    colliders: Query<'w, 's, &'static Collider, Without<ColliderDisabled>>,
+}
+
+impl<'w, 's> TileBlockers<'w, 's> {
+    /// If a tile entity keeps creatures from walking onto its tile
+    fn blocks(&self, entity: Entity) -> bool {
+        if let Ok(door) = self.doors.get(entity) {
+            return !door.is_open();
+        }
+        std::iter::once(entity)
+            .chain(self.children.iter_descendants(entity))
+            .filter_map(|e| self.colliders.get(e).ok())
+            .any(|collider| {
+                collider
+                    .as_cuboid()
+                    .map_or(true, |cuboid| cuboid.half_extents().y > FLOOR_HALF_HEIGHT)
+            })
+    }
+}
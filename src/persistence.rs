@@ -0,0 +1,342 @@
+//! Player characters saved between rounds, keyed by player id.
+
+use std::path::PathBuf;
+
+use bevy::{
+    asset::HandleId,
+    prelude::*,
+    utils::{HashMap, HashSet, Uuid},
+};
+use networking::{
+    is_server,
+    messaging::{AppExt, MessageEvent, MessageReceivers, MessageSender},
+    spawning::ClientControls,
+    Players, ServerEvent,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    body::{
+        appearance::CharacterAppearance,
+        health::species::{SpeciesAssets, SpeciesDefinition},
+    },
+    communication::accents::Accent,
+    config::ServerConfig,
+    job::{JobDefinition, SelectJobMessage, SelectedJobs},
+    storage::Storage,
+};
+
+pub struct PersistencePlugin;
+
+impl Plugin for PersistencePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_network_message::<CharacterProfileMessage>()
+            .add_network_message::<UpdateCharacterMessage>();
+        if is_server(app) {
+            app.init_resource::<CharacterProfiles>().add_systems(
+                Update,
+                (
+                    (load_profile_on_connect, evict_disconnected_profiles),
+                    (
+                        handle_character_update,
+                        save_job_preference.run_if(on_event::<MessageEvent<SelectJobMessage>>()),
+                    ),
+                    send_changed_profiles,
+                )
+                    .chain(),
+            );
+        } else {
+            app.init_resource::<ClientCharacterProfile>()
+                .add_systems(Update, client_receive_profile);
+        }
+    }
+}
+
+const DEFAULT_CHARACTER_DIRECTORY: &str = "data/characters";
+const MAX_NAME_LENGTH: usize = 42;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CharacterProfile {
+    /// Empty if the player hasn't picked one yet
+    pub name: String,
+    pub species: String,
+    #[serde(default)]
+    pub accents: Vec<AccentPreference>,
+    #[serde(default)]
+    pub appearance: CharacterAppearance,
+    /// Id of the job the player last selected
+    pub job: Option<String>,
+    /// Ids of the gameplay tips the player was already shown
+    #[serde(default)]
+    pub seen_hints: Vec<String>,
+    #[serde(default)]
+    pub hints_disabled: bool,
+}
+
+impl Default for CharacterProfile {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            species: "human".into(),
+            accents: Vec::new(),
+            appearance: Default::default(),
+            job: None,
+            seen_hints: Vec::new(),
+            hints_disabled: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct AccentPreference {
+    pub accent: Accent,
+    /// From 0 to 1
+    pub severity: f32,
+}
+
+/// Characters of connected players, loaded from and saved to disk
+#[derive(Resource)]
+pub struct CharacterProfiles {
+    directory: PathBuf,
+    profiles: HashMap<Uuid, CharacterProfile>,
+    /// Players whose profile changed since it was last sent to them
+    changed: HashSet<Uuid>,
+}
+
+/// Where characters are saved to
+pub(crate) fn character_directory(config: Option<&ServerConfig>) -> PathBuf {
+    config
+        .and_then(|config| config.character_directory.clone())
+        .unwrap_or_else(|| DEFAULT_CHARACTER_DIRECTORY.into())
+}
+
+impl FromWorld for CharacterProfiles {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            directory: character_directory(world.get_resource::<ServerConfig>()),
+            profiles: Default::default(),
+            changed: Default::default(),
+        }
+    }
+}
+
+impl CharacterProfiles {
+    pub fn get(&self, player: Uuid) -> Option<&CharacterProfile> {
+        self.profiles.get(&player)
+    }
+
+    fn path(&self, player: Uuid) -> PathBuf {
+        self.directory.join(format!("{}.toml", player))
+    }
+
+    /// Reads a player's character from disk, or creates a new one
+    fn load(&mut self, player: Uuid, storage: &Storage) -> &CharacterProfile {
+        let path = self.path(player);
+        let profile = storage.load_checked(&path).unwrap_or_default();
+        self.profiles.entry(player).or_insert(profile)
+    }
+
+    pub(crate) fn update(&mut self, player: Uuid, change: impl FnOnce(&mut CharacterProfile)) {
+        let profile = self.profiles.entry(player).or_default();
+        change(profile);
+        let profile = profile.clone();
+        self.changed.insert(player);
+
+        let path = self.path(player);
+        let result = std::fs::create_dir_all(&self.directory).and_then(|_| {
+            let text = toml::to_string(&profile)
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+            std::fs::write(&path, text)
+        });
+        if let Err(err) = result {
+            error!(path = ?path, error = %err, "Failed to save character profile");
+        }
+    }
+}
+
+/// Sends a player their character and any state that is derived from it
+#[derive(Serialize, Deserialize)]
+pub struct CharacterProfileMessage {
+    pub profile: CharacterProfile,
+}
+
+/// Client request to change their character
+#[derive(Serialize, Deserialize)]
+pub struct UpdateCharacterMessage {
+    pub name: String,
+    pub species: String,
+    pub accents: Vec<AccentPreference>,
+    pub appearance: CharacterAppearance,
+    pub hints_disabled: bool,
+}
+
+fn load_profile_on_connect(
+    mut events: EventReader<ServerEvent>,
+    players: Res<Players>,
+    mut profiles: ResMut<CharacterProfiles>,
+    storage: Res<Storage>,
+    mut selected_jobs: ResMut<SelectedJobs>,
+    jobs: Res<Assets<JobDefinition>>,
+    mut sender: MessageSender,
+) {
+    for event in events.iter() {
+        let ServerEvent::PlayerConnected(connection) = *event else {
+            continue;
+        };
+        let Some(player) = players.get(connection) else {
+            continue;
+        };
+
+        let profile = profiles.load(player.id, &storage).clone();
+
+        // Restore the job the player had picked last time
+        let job = profile.job.as_deref().and_then(|job_id| {
+            jobs.iter()
+                .find(|(_, job)| job.id == job_id)
+                .and_then(|(handle, _)| match handle {
+                    HandleId::AssetPathId(id) => Some(id),
+                    HandleId::Id(..) => None,
+                })
+        });
+        if job.is_some() {
+            selected_jobs.set(connection, job);
+        }
+
+        sender.send(
+            &CharacterProfileMessage { profile },
+            MessageReceivers::Single(connection),
+        );
+    }
+}
+
+fn handle_character_update(
+    mut messages: EventReader<MessageEvent<UpdateCharacterMessage>>,
+    players: Res<Players>,
+    controlled: Res<ClientControls>,
+    species: Option<Res<SpeciesAssets>>,
+    species_definitions: Res<Assets<SpeciesDefinition>>,
+    mut profiles: ResMut<CharacterProfiles>,
+) {
+    for event in messages.iter() {
+        let Some(player) = players.get(event.connection) else {
+            continue;
+        };
+        // The character can't change while it's in the round
+        if controlled.controlled_entity(player.id).is_some() {
+            continue;
+        }
+
+        let message = &event.message;
+        let name = message.name.trim();
+        if name.chars().count() > MAX_NAME_LENGTH {
+            continue;
+        }
+        let playable = species
+            .as_ref()
+            .and_then(|s| s.get(&message.species, &species_definitions))
+            .map_or(false, |definition| definition.playable);
+        if !playable {
+            continue;
+        }
+
+        profiles.update(player.id, |profile| {
+            profile.name = name.to_owned();
+            profile.species = message.species.clone();
+            profile.accents.clear();
+            for preference in message.accents.iter() {
+                // Only the first severity picked for an accent counts
+                if profile
+                    .accents
+                    .iter()
+                    .any(|a| a.accent == preference.accent)
+                {
+                    continue;
+                }
+                profile.accents.push(AccentPreference {
+                    accent: preference.accent,
+                    severity: preference.severity.clamp(0.0, 1.0),
+                });
+            }
+            profile.appearance = message.appearance;
+            profile.hints_disabled = message.hints_disabled;
+        });
+    }
+}
+
+/// Forgets the characters of players that left, they are loaded again when they reconnect
+fn evict_disconnected_profiles(
+    mut events: EventReader<ServerEvent>,
+    players: Res<Players>,
+    mut profiles: ResMut<CharacterProfiles>,
+) {
+    let disconnected = events
+        .iter()
+        .any(|event| matches!(event, ServerEvent::PlayerDisconnected(_)));
+    if !disconnected {
+        return;
+    }
+    let profiles = profiles.as_mut();
+    profiles
+        .profiles
+        .retain(|&id, _| players.get_connection(&id).is_some());
+    profiles
+        .changed
+        .retain(|&id| players.get_connection(&id).is_some());
+}
+
+/// Keeps the character editor of players in sync with their saved character
+fn send_changed_profiles(
+    players: Res<Players>,
+    mut profiles: ResMut<CharacterProfiles>,
+    mut sender: MessageSender,
+) {
+    if profiles.changed.is_empty() {
+        return;
+    }
+    let profiles = profiles.as_mut();
+    for id in profiles.changed.drain() {
+        let (Some(connection), Some(profile)) =
+            (players.get_connection(&id), profiles.profiles.get(&id))
+        else {
+            continue;
+        };
+        sender.send(
+            &CharacterProfileMessage {
+                profile: profile.clone(),
+            },
+            MessageReceivers::Single(connection),
+        );
+    }
+}
+
+fn save_job_preference(
+    mut messages: EventReader<MessageEvent<SelectJobMessage>>,
+    players: Res<Players>,
+    jobs: Res<Assets<JobDefinition>>,
+    mut profiles: ResMut<CharacterProfiles>,
+) {
+    for event in messages.iter() {
+        let Some(player) = players.get(event.connection) else {
+            continue;
+        };
+        let job = event
+            .message
+            .job
+            .and_then(|id| jobs.get(&jobs.get_handle(id)))
+            .map(|job| job.id.clone());
+        profiles.update(player.id, |profile| profile.job = job);
+    }
+}
+
+/// The character of this client, as last sent by the server
+#[derive(Resource, Default)]
+pub struct ClientCharacterProfile(pub Option<CharacterProfile>);
+
+fn client_receive_profile(
+    mut messages: EventReader<MessageEvent<CharacterProfileMessage>>,
+    mut profile: ResMut<ClientCharacterProfile>,
+) {
+    for event in messages.iter() {
+        profile.0 = Some(event.message.profile.clone());
+    }
+}
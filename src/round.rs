@@ -1,13 +1,14 @@
 use bevy::{
     prelude::*,
     reflect::TypeUuid,
-    utils::{HashMap, Uuid},
+    utils::{HashMap, HashSet, Uuid},
 };
-use maps::TileMap;
+use maps::{journal::TileJournal, TileMap};
 use networking::{
+    identity::NetworkIdentity,
     is_client, is_server,
     messaging::{AppExt, MessageEvent, MessageReceivers, MessageSender},
-    resource::AppExt as ResAppExt,
+    resource::{AppExt as ResAppExt, ReplicationPolicy},
     scene::NetworkSceneBundle,
     spawning::ClientControls,
     time::ServerNetworkTime,
@@ -16,40 +17,76 @@ use networking::{
     Networked, Players,
 };
 use serde::{Deserialize, Serialize};
-use utils::task::*;
+use utils::{rng::SeededRng, task::*};
 
 use crate::{
-    body::SpawnCreature,
+    access::AssignAccess,
+    body::{
+        ghost::{Ghost, RespawnTimers},
+        SpawnCreature,
+    },
+    communication::{
+        accents::Accents,
+        announcements::{Announcement, AnnouncementPriority},
+    },
+    config::ServerConfig,
+    economy::{AccountHolder, BankAccounts},
     items::clothes::{EquipClothing, EquipClothingSystem},
     job::{JobDefinition, SelectedJobs},
     movement::ForcePositionMessage,
+    persistence::CharacterProfiles,
+    ui::toasts::{Toast, ToastLevel, Toasts},
 };
 
 pub struct RoundPlugin;
 
 impl Plugin for RoundPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.add_network_message::<StartRoundRequest>()
+        app.add_network_message::<SetReadyMessage>()
             .add_network_message::<RequestJoin>()
-            .add_networked_resource::<RoundData, RoundDataClient>();
+            .add_network_message::<JoinDeniedMessage>()
+            // The countdown changes every second, it shouldn't resend the rest
+            .add_networked_resource_with_policy::<RoundData, RoundDataClient>(
+                ReplicationPolicy::reliable().delta(),
+            );
         if is_server(app) {
             app.add_state::<RoundState>()
                 .insert_resource(RoundData {
-                    state: RoundState::Loading.into(),
+                    state: RoundState::Restarting.into(),
                     start: None.into(),
+                    countdown: None.into(),
+                    ready_players: 0.into(),
                 })
                 .init_resource::<SpawnsInProgress>()
-                .add_systems(OnEnter(RoundState::Loading), load_map)
+                .init_resource::<ReadyPlayers>()
+                .init_resource::<RoundCountdown>()
+                .add_systems(
+                    OnEnter(RoundState::Restarting),
+                    (reset_world, apply_deferred, load_map).chain(),
+                )
+                .add_systems(OnEnter(RoundState::Lobby), clear_countdown)
+                .add_systems(OnEnter(RoundState::Starting), start_countdown)
+                .add_systems(
+                    OnEnter(RoundState::InProgress),
+                    (
+                        spawn_players_roundstart,
+                        start_round_timer,
+                        announce_round_start,
+                    ),
+                )
                 .add_systems(
-                    OnEnter(RoundState::Running),
-                    (spawn_players_roundstart, start_round_timer),
+                    OnEnter(RoundState::Ending),
+                    (start_end_delay, announce_round_end),
                 )
                 .add_systems(
                     Update,
                     (
-                        set_ready.run_if(in_state(RoundState::Loading)),
-                        handle_start_round_request.run_if(in_state(RoundState::Ready)),
-                        spawn_player_latejoin.run_if(in_state(RoundState::Running)),
+                        finish_loading.run_if(in_state(RoundState::Restarting)),
+                        handle_ready_message,
+                        start_when_ready.run_if(in_state(RoundState::Lobby)),
+                        cancel_start.run_if(in_state(RoundState::Starting)),
+                        tick_countdown,
+                        spawn_player_latejoin,
                         update_round_data.run_if(state_changed::<RoundState>()),
                         (
                             handle_player_body_spawned.after(EquipClothingSystem),
@@ -59,6 +96,8 @@ impl Plugin for RoundPlugin {
                             .chain(),
                     ),
                 );
+        } else {
+            app.add_systems(Update, client_join_denied);
         }
 
         let player_scene = app
@@ -77,11 +116,17 @@ impl Plugin for RoundPlugin {
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, States)]
 pub enum RoundState {
+    /// The world is cleared and the map for the next round is loading.
+    /// The server starts in this state.
     #[default]
-    Loading,
-    Ready,
-    Running,
-    Ended,
+    Restarting,
+    /// Players pick their jobs and ready up
+    Lobby,
+    /// Counting down to the round start
+    Starting,
+    InProgress,
+    /// The round is over and the server restarts after a delay
+    Ending,
 }
 
 #[derive(Networked, Resource)]
@@ -90,6 +135,10 @@ struct RoundData {
     state: NetworkVar<RoundState>,
     /// The server tick the round was started.
     start: NetworkVar<Option<u32>>,
+    /// Seconds until the state changes on its own
+    countdown: NetworkVar<Option<u32>>,
+    /// Connected players that are ready for the round
+    ready_players: NetworkVar<u32>,
 }
 
 #[derive(Default, TypeUuid, Networked, Resource)]
@@ -98,6 +147,8 @@ struct RoundData {
 pub struct RoundDataClient {
     state: ServerVar<RoundState>,
     start: ServerVar<Option<u32>>,
+    countdown: ServerVar<Option<u32>>,
+    ready_players: ServerVar<u32>,
 }
 
 impl RoundDataClient {
@@ -108,33 +159,217 @@ impl RoundDataClient {
     pub fn start(&self) -> Option<u32> {
         *self.start
     }
+
+    pub fn countdown(&self) -> Option<u32> {
+        *self.countdown
+    }
+
+    pub fn ready_players(&self) -> u32 {
+        *self.ready_players
+    }
 }
 
+/// Sent by a client in the lobby to join or leave the next round
 #[derive(Serialize, Deserialize)]
-pub struct StartRoundRequest;
-
-fn load_map(mut commands: Commands, server: Res<AssetServer>) {
-    // TODO: Make map selection configurable
-    let handle = server.load("maps/BoxStation.dmm");
-    commands.insert_resource(crate::Map {
-        handle,
-        spawned: false,
+pub struct SetReadyMessage {
+    pub ready: bool,
+}
+
+/// Players that will be spawned when the round starts
+#[derive(Resource, Default)]
+struct ReadyPlayers(HashSet<Uuid>);
+
+impl ReadyPlayers {
+    fn connected(&self, players: &Players) -> u32 {
+        players
+            .players()
+            .values()
+            .filter(|p| !p.spectator && self.0.contains(&p.id))
+            .count() as u32
+    }
+}
+
+/// Time until the current round state advances to the next one
+#[derive(Resource, Default)]
+struct RoundCountdown(Option<Timer>);
+
+/// Source of randomness for everything that happens during a round.
+/// Seeded at round start, so a round can be reproduced by setting `round_seed` in the server config.
+#[derive(Resource, Deref, DerefMut)]
+pub struct RoundRng(SeededRng);
+
+/// Removes everything left over from the previous round
+fn reset_world(
+    networked: Query<Entity, (With<NetworkIdentity>, Without<Parent>)>,
+    mut controls: ResMut<ClientControls>,
+    mut ready: ResMut<ReadyPlayers>,
+    mut spawns: ResMut<SpawnsInProgress>,
+    mut countdown: ResMut<RoundCountdown>,
+    mut round_data: ResMut<RoundData>,
+    mut commands: Commands,
+) {
+    let count = networked.iter().count();
+    if count > 0 {
+        info!(entities = count, "Clearing world for round restart");
+    }
+    for entity in networked.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    controls.release_all();
+    ready.0.clear();
+    *spawns = Default::default();
+    countdown.0 = None;
+    *round_data.start = None;
+}
+
+fn load_map(
+    mut commands: Commands,
+    server: Res<AssetServer>,
+    config: Res<ServerConfig>,
+    mut journal: ResMut<TileJournal>,
+) {
+    let seed = config.round_seed.unwrap_or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default()
     });
+    info!(seed, "Seeding round randomness");
+    commands.insert_resource(RoundRng(SeededRng::new(seed)));
+
+    // Restarts keep the map that was last loaded
+    let map = journal
+        .base_map
+        .clone()
+        .or_else(|| config.map.clone())
+        .unwrap_or_else(|| "BoxStation".into());
+    commands.insert_resource(crate::Map::load(&server, &map));
+    journal.reset(map);
 }
 
 // TODO: Make it wait for all potential maps
-fn set_ready(query: Query<(), Added<TileMap>>, mut state: ResMut<NextState<RoundState>>) {
+fn finish_loading(query: Query<(), Added<TileMap>>, mut state: ResMut<NextState<RoundState>>) {
     if !query.is_empty() {
-        state.set(RoundState::Ready);
+        state.set(RoundState::Lobby);
+    }
+}
+
+fn handle_ready_message(
+    mut messages: EventReader<MessageEvent<SetReadyMessage>>,
+    state: Res<State<RoundState>>,
+    players: Res<Players>,
+    selected_jobs: Res<SelectedJobs>,
+    job_data: Res<Assets<JobDefinition>>,
+    mut ready: ResMut<ReadyPlayers>,
+    mut round_data: ResMut<RoundData>,
+) {
+    for event in messages.iter() {
+        if !matches!(state.get(), RoundState::Lobby | RoundState::Starting) {
+            continue;
+        }
+        let Some(player) = players.get(event.connection) else {
+            continue;
+        };
+        if player.spectator {
+            continue;
+        }
+
+        if event.message.ready {
+            // Can't play without a job
+            if selected_jobs.get(event.connection, &job_data).is_none() {
+                continue;
+            }
+            ready.0.insert(player.id);
+        } else {
+            ready.0.remove(&player.id);
+        }
+    }
+
+    let count = ready.connected(&players);
+    if *round_data.ready_players != count {
+        *round_data.ready_players = count;
+    }
+}
+
+/// Starts the countdown once every connected player is ready
+fn start_when_ready(
+    ready: Res<ReadyPlayers>,
+    players: Res<Players>,
+    mut state: ResMut<NextState<RoundState>>,
+) {
+    let count = ready.connected(&players);
+    if count > 0 && count as usize == players.playing() {
+        state.set(RoundState::Starting);
     }
 }
 
-fn handle_start_round_request(
-    mut query: EventReader<MessageEvent<StartRoundRequest>>,
+/// Goes back to the lobby if everyone stopped being ready
+fn cancel_start(
+    ready: Res<ReadyPlayers>,
+    players: Res<Players>,
     mut state: ResMut<NextState<RoundState>>,
 ) {
-    if query.iter().next().is_some() {
-        state.set(RoundState::Running);
+    if ready.connected(&players) == 0 {
+        state.set(RoundState::Lobby);
+    }
+}
+
+fn clear_countdown(mut countdown: ResMut<RoundCountdown>) {
+    countdown.0 = None;
+}
+
+fn start_countdown(config: Res<ServerConfig>, mut countdown: ResMut<RoundCountdown>) {
+    countdown.0 = Some(Timer::new(config.round.start_countdown(), TimerMode::Once));
+}
+
+fn start_end_delay(config: Res<ServerConfig>, mut countdown: ResMut<RoundCountdown>) {
+    countdown.0 = Some(Timer::new(config.round.end_delay(), TimerMode::Once));
+}
+
+fn announce_round_start(mut announcements: EventWriter<Announcement>) {
+    announcements.send(Announcement::new(
+        "Shift Start",
+        "Welcome aboard. All crew report to your departments.",
+        AnnouncementPriority::Normal,
+    ));
+}
+
+fn announce_round_end(mut announcements: EventWriter<Announcement>, config: Res<ServerConfig>) {
+    announcements.send(Announcement::new(
+        "Shift Over",
+        format!(
+            "The shift has ended. The station restarts in {} seconds.",
+            config.round.end_delay().as_secs()
+        ),
+        AnnouncementPriority::High,
+    ));
+}
+
+/// Advances the round once the countdown of the current state runs out
+fn tick_countdown(
+    state: Res<State<RoundState>>,
+    mut next_state: ResMut<NextState<RoundState>>,
+    mut countdown: ResMut<RoundCountdown>,
+    mut round_data: ResMut<RoundData>,
+    time: Res<Time>,
+) {
+    let remaining = countdown.0.as_mut().map(|timer| {
+        timer.tick(time.delta());
+        timer.remaining().as_secs_f32().ceil() as u32
+    });
+    if *round_data.countdown != remaining {
+        *round_data.countdown = remaining;
+    }
+
+    if !countdown.0.as_ref().map_or(false, Timer::just_finished) {
+        return;
+    }
+    countdown.0 = None;
+    match state.get() {
+        RoundState::Starting => next_state.set(RoundState::InProgress),
+        RoundState::InProgress => next_state.set(RoundState::Ending),
+        RoundState::Ending => next_state.set(RoundState::Restarting),
+        _ => {}
     }
 }
 
@@ -144,8 +379,17 @@ fn update_round_data(state: Res<State<RoundState>>, mut round_data: ResMut<Round
     }
 }
 
-fn start_round_timer(mut round_data: ResMut<RoundData>, server_time: Res<ServerNetworkTime>) {
+fn start_round_timer(
+    mut round_data: ResMut<RoundData>,
+    server_time: Res<ServerNetworkTime>,
+    config: Res<ServerConfig>,
+    mut countdown: ResMut<RoundCountdown>,
+) {
     *round_data.start = Some(server_time.current_tick());
+    countdown.0 = config
+        .round
+        .max_duration()
+        .map(|duration| Timer::new(duration, TimerMode::Once));
 }
 
 #[derive(Resource)]
@@ -165,9 +409,11 @@ struct SpawnsInProgress {
 }
 
 fn spawn_players_roundstart(
+    ready: Res<ReadyPlayers>,
     selected_jobs: Res<SelectedJobs>,
     job_data: Res<Assets<JobDefinition>>,
     players: Res<Players>,
+    profiles: Res<CharacterProfiles>,
     mut spawns: ResMut<SpawnsInProgress>,
     mut spawning: ResMut<Tasks<SpawnCreature>>,
 ) {
@@ -176,44 +422,116 @@ fn spawn_players_roundstart(
             Some(p) => p,
             None => continue,
         };
+        if player.spectator || !ready.0.contains(&player.id) {
+            continue;
+        }
 
-        let spawn_id = spawning.create(SpawnCreature {
-            archetype: "human".into(),
-        });
+        let spawn_id = spawning.create(player_creature(&profiles, player.id));
 
         spawns.spawn_tasks.insert(spawn_id, player.id);
     }
 }
 
+/// The creature a player set up for their character
+fn player_creature(profiles: &CharacterProfiles, player: Uuid) -> SpawnCreature {
+    match profiles.get(player) {
+        Some(profile) => SpawnCreature {
+            archetype: profile.species.clone(),
+            appearance: profile.appearance,
+        },
+        None => SpawnCreature {
+            archetype: "human".into(),
+            appearance: Default::default(),
+        },
+    }
+}
+
+/// Client request to join the round, or to respawn as a ghost
 #[derive(Serialize, Deserialize)]
 pub struct RequestJoin;
 
+/// Tells a client why it couldn't join the round
+#[derive(Serialize, Deserialize)]
+struct JoinDeniedMessage {
+    reason: String,
+}
+
 #[allow(clippy::too_many_arguments)]
 fn spawn_player_latejoin(
     mut messages: EventReader<MessageEvent<RequestJoin>>,
+    state: Res<State<RoundState>>,
     selected_jobs: Res<SelectedJobs>,
     job_data: Res<Assets<JobDefinition>>,
     players: Res<Players>,
+    profiles: Res<CharacterProfiles>,
+    config: Res<ServerConfig>,
+    controls: Res<ClientControls>,
+    ghosts: Query<(), With<Ghost>>,
+    mut respawn_timers: ResMut<RespawnTimers>,
+    time: Res<Time>,
     mut spawns: ResMut<SpawnsInProgress>,
     mut spawning: ResMut<Tasks<SpawnCreature>>,
+    mut sender: MessageSender,
 ) {
     for event in messages.iter() {
         let Some(player) = players.get(event.connection) else {
             continue;
         };
+        if spawns.spawn_tasks.values().any(|&id| id == player.id) {
+            continue;
+        }
 
-        if selected_jobs.get(event.connection, &job_data).is_none() {
+        let respawning = controls.controlled_entity(player.id);
+        let denied = if player.spectator {
+            Some("Spectators have to wait for a player slot.")
+        } else if *state.get() != RoundState::InProgress {
+            Some("The round is not in progress.")
+        } else if respawning.map_or(false, |entity| !ghosts.contains(entity)) {
+            Some("You are already in the round.")
+        } else if respawning.is_some()
+            && !respawn_timers.can_respawn(player.id, time.elapsed_seconds())
+        {
+            Some("You can't respawn yet.")
+        } else if respawning.is_some()
+            && respawn_timers.respawns_left(player.id, &config) == Some(0)
+        {
+            Some("You have no respawns left this round.")
+        } else if selected_jobs.get(event.connection, &job_data).is_none() {
+            Some("Pick a job first.")
+        } else {
+            None
+        };
+        if let Some(reason) = denied {
+            sender.send(
+                &JoinDeniedMessage {
+                    reason: reason.into(),
+                },
+                MessageReceivers::Single(event.connection),
+            );
             continue;
         }
 
-        let spawn_id = spawning.create(SpawnCreature {
-            archetype: "human".into(),
-        });
+        if respawning.is_some() {
+            respawn_timers.record_respawn(player.id);
+            info!(player = player.username, "Player respawned");
+        }
+        let spawn_id = spawning.create(player_creature(&profiles, player.id));
 
         spawns.spawn_tasks.insert(spawn_id, player.id);
     }
 }
 
+fn client_join_denied(
+    mut messages: EventReader<MessageEvent<JoinDeniedMessage>>,
+    mut toasts: ResMut<Toasts>,
+) {
+    for event in messages.iter() {
+        toasts.push(
+            Toast::new(ToastLevel::Warning, event.message.reason.clone()).title("Can't join"),
+        );
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 fn handle_player_body_spawned(
     players: Res<Players>,
@@ -243,13 +561,17 @@ fn handle_player_body_spawned(
             .clothing
             .iter()
             .map(|clothing| {
+                // ID cards among the clothing get the access of the job
                 let clothing_entity = commands
-                    .spawn(NetworkSceneBundle {
-                        scene: asset_server
-                            .load(format!("items/{}.scn.ron", clothing))
-                            .into(),
-                        ..Default::default()
-                    })
+                    .spawn((
+                        NetworkSceneBundle {
+                            scene: asset_server
+                                .load(format!("items/{}.scn.ron", clothing))
+                                .into(),
+                            ..Default::default()
+                        },
+                        AssignAccess(job.access.clone()),
+                    ))
                     .id();
                 clothing_equip.create(EquipClothing {
                     creature: result.root,
@@ -274,6 +596,8 @@ fn finalise_player_spawn(
     job_data: Res<Assets<JobDefinition>>,
     mut spawns: ResMut<SpawnsInProgress>,
     mut clothing: ResMut<Tasks<EquipClothing>>,
+    profiles: Res<CharacterProfiles>,
+    mut accounts: ResMut<BankAccounts>,
     mut controls: ResMut<ClientControls>,
     mut commands: Commands,
     mut sender: MessageSender,
@@ -303,13 +627,24 @@ fn finalise_player_spawn(
                 return false;
             };
 
-            let Some(name) = players
+            let Some(username) = players
                 .players()
                 .get(&connection)
                 .map(|p| p.username.clone())
             else {
                 return false;
             };
+            let profile = profiles.get(*player_id);
+            // Players that haven't named their character play under their username
+            let name = profile
+                .map(|p| p.name.clone())
+                .filter(|name| !name.is_empty())
+                .unwrap_or(username);
+            let accents = Accents {
+                active: profile
+                    .map(|p| p.accents.iter().map(|a| (a.accent, a.severity)).collect())
+                    .unwrap_or_default(),
+            };
 
             let Some(job) = selected_jobs.get(connection, &job_data) else {
                 return false;
@@ -321,6 +656,7 @@ fn finalise_player_spawn(
             };
 
             let spawn_position = crate::job::get_spawn_position(main_map, job);
+            let account = accounts.open(name.clone(), job.starting_credits, job.salary);
 
             // Add some player specific components
             commands.entity(*player_entity).insert((
@@ -333,8 +669,11 @@ fn finalise_player_spawn(
                 },
                 Transform::from_translation(spawn_position),
                 crate::communication::SpeechName(name),
+                accents,
+                AccountHolder(account),
                 networking::transform::ClientMovement,
             ));
+            crate::character_sheet::spawn_character_sheet(&mut commands, *player_entity, job);
 
             controls.give_control(*player_id, *player_entity);
 
@@ -0,0 +1,39 @@
+//! Singleplayer, where the server runs on a thread next to the client.
+//!
+//! The client joins it through a local transport instead of UDP, so nothing has to be hosted.
+
+use std::thread;
+
+use networking::{local::LocalServerAddress, NetworkRole};
+
+use crate::{config::ServerConfig, create_app, ArgCommands, Args};
+
+/// Starts a server on another thread and returns the address to join it with.
+/// The server stops with the process.
+pub fn start_server() -> LocalServerAddress {
+    let (server, transport, address) = networking::local::create_local_server();
+    thread::Builder::new()
+        .name("singleplayer server".into())
+        .spawn(move || {
+            let args = Args {
+                command: Some(ArgCommands::Singleplayer {
+                    name: String::new(),
+                }),
+                dump_protocol: None,
+            };
+            // The app is not `Send`, so it is created on the thread it runs on
+            let Some(mut app) = create_app(args, NetworkRole::Server) else {
+                return;
+            };
+            app.insert_resource(server).insert_resource(transport).run();
+        })
+        .expect("Failed to start singleplayer server thread");
+    address
+}
+
+/// Overrides the server configuration for a singleplayer session
+pub fn configure_server(config: &mut ServerConfig) {
+    // Nobody else can join, there is nothing to announce
+    config.registration = None;
+    config.webhooks.endpoints.clear();
+}
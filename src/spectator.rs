@@ -0,0 +1,342 @@
+//! Spectators watch the round without a body, following players around.
+//!
+//! Clients join as spectators when they choose to observe or when every player slot is taken.
+//! The networking crate promotes waiting spectators once a slot frees up.
+//! Ghosts get the same list of players to follow.
+
+use bevy::{
+    prelude::*,
+    utils::{HashSet, Uuid},
+};
+use bevy_egui::{egui, EguiContexts};
+use networking::{
+    identity::{EntityCommandsExt, NetworkIdentities, NetworkIdentity},
+    is_server,
+    messaging::{AppExt, MessageEvent, MessageReceivers, MessageSender},
+    spawning::ClientControls,
+    visibility::{NetworkObserver, NetworkObserverBundle},
+    ClientRole, Players, RequestPlayerSlot,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    body::ghost::Ghost,
+    camera::{MainCamera, TopDownCamera},
+    communication::SpeechName,
+    ui::has_window,
+    GameState,
+};
+
+pub struct SpectatorPlugin;
+
+impl Plugin for SpectatorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_network_message::<FollowMessage>()
+            .add_network_message::<SpectatorTargetsMessage>();
+
+        if is_server(app) {
+            app.add_systems(
+                Update,
+                (
+                    spawn_spectator_observers,
+                    handle_follow_messages,
+                    follow_targets,
+                    send_spectator_targets,
+                )
+                    .chain(),
+            );
+        } else {
+            app.init_resource::<ClientSpectator>().add_systems(
+                Update,
+                (
+                    client_receive_targets,
+                    client_follow_camera,
+                    client_spectator_ui.run_if(has_window),
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Game)),
+            );
+        }
+    }
+}
+
+/// Seconds between updates of the players spectators can follow
+const TARGETS_INTERVAL: f32 = 1.0;
+
+/// The point a spectator observes the world from
+#[derive(Component)]
+struct SpectatorObserver {
+    player: Uuid,
+    following: Option<Entity>,
+}
+
+/// Sent by spectators to pick who to follow
+#[derive(Serialize, Deserialize)]
+struct FollowMessage {
+    target: NetworkIdentity,
+}
+
+/// Players a spectator can follow and who it currently follows
+#[derive(Serialize, Deserialize, Clone, Default)]
+struct SpectatorTargetsMessage {
+    targets: Vec<(NetworkIdentity, String)>,
+    following: Option<NetworkIdentity>,
+}
+
+/// Gives every spectator an observer and removes the ones of promoted or disconnected spectators.
+/// Observers are also despawned with the rest of the world on round restart.
+fn spawn_spectator_observers(
+    observers: Query<(Entity, &SpectatorObserver)>,
+    players: Res<Players>,
+    mut commands: Commands,
+) {
+    let mut observed = HashSet::new();
+    for (entity, observer) in observers.iter() {
+        let spectating = players
+            .get_connection(&observer.player)
+            .map_or(false, |connection| players.is_spectator(connection));
+        if spectating {
+            observed.insert(observer.player);
+        } else {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    for player in players.players().values() {
+        if !player.spectator || observed.contains(&player.id) {
+            continue;
+        }
+        commands
+            .spawn((
+                TransformBundle::default(),
+                NetworkObserverBundle {
+                    observer: NetworkObserver {
+                        range: 1,
+                        player_id: player.id,
+                    },
+                    cells: Default::default(),
+                },
+                SpectatorObserver {
+                    player: player.id,
+                    following: None,
+                },
+            ))
+            .networked();
+    }
+}
+
+/// Player controlled creatures with a name are worth following
+fn is_target(entity: Entity, controls: &ClientControls, names: &Query<&SpeechName>) -> bool {
+    controls.controlling_player(entity).is_some() && names.contains(entity)
+}
+
+fn handle_follow_messages(
+    mut messages: EventReader<MessageEvent<FollowMessage>>,
+    mut observers: Query<&mut SpectatorObserver>,
+    players: Res<Players>,
+    identities: Res<NetworkIdentities>,
+    controls: Res<ClientControls>,
+    names: Query<&SpeechName>,
+) {
+    for event in messages.iter() {
+        let Some(player) = players.get(event.connection) else {
+            continue;
+        };
+        let Some(target) = identities
+            .get_entity(event.message.target)
+            .filter(|&entity| is_target(entity, &controls, &names))
+        else {
+            continue;
+        };
+        if let Some(mut observer) = observers.iter_mut().find(|o| o.player == player.id) {
+            observer.following = Some(target);
+        }
+    }
+}
+
+/// Keeps observers on the creature they follow, picking another one if it's gone
+fn follow_targets(
+    mut observers: Query<(&mut SpectatorObserver, &mut Transform)>,
+    targets: Query<(Entity, &GlobalTransform), With<SpeechName>>,
+    controls: Res<ClientControls>,
+    names: Query<&SpeechName>,
+) {
+    for (mut observer, mut transform) in observers.iter_mut() {
+        let current = observer
+            .following
+            .filter(|&entity| is_target(entity, &controls, &names));
+        observer.following = current.or_else(|| {
+            targets
+                .iter()
+                .map(|(entity, _)| entity)
+                .find(|&entity| is_target(entity, &controls, &names))
+        });
+
+        let Some(position) = observer
+            .following
+            .and_then(|entity| targets.get(entity).ok())
+            .map(|(_, target)| target.translation())
+        else {
+            continue;
+        };
+        if transform.translation != position {
+            transform.translation = position;
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn send_spectator_targets(
+    observers: Query<&SpectatorObserver>,
+    ghosts: Query<&Ghost>,
+    targets: Query<(Entity, &SpeechName)>,
+    players: Res<Players>,
+    identities: Res<NetworkIdentities>,
+    controls: Res<ClientControls>,
+    time: Res<Time>,
+    mut last_update: Local<f32>,
+    mut sender: MessageSender,
+) {
+    let now = time.elapsed_seconds();
+    if (observers.is_empty() && ghosts.is_empty()) || *last_update + TARGETS_INTERVAL > now {
+        return;
+    }
+    *last_update = now;
+
+    let mut list: Vec<_> = targets
+        .iter()
+        .filter(|(entity, _)| controls.controlling_player(*entity).is_some())
+        .filter_map(|(entity, name)| Some((identities.get_identity(entity)?, name.0.clone())))
+        .collect();
+    list.sort_by(|a, b| a.1.cmp(&b.1));
+
+    for observer in observers.iter() {
+        let Some(connection) = players.get_connection(&observer.player) else {
+            continue;
+        };
+        sender.send(
+            &SpectatorTargetsMessage {
+                targets: list.clone(),
+                following: observer
+                    .following
+                    .and_then(|entity| identities.get_identity(entity)),
+            },
+            MessageReceivers::Single(connection),
+        );
+    }
+
+    // Ghosts follow on their own, the server doesn't track who
+    for ghost in ghosts.iter() {
+        let Some(connection) = players.get_connection(&ghost.player()) else {
+            continue;
+        };
+        sender.send(
+            &SpectatorTargetsMessage {
+                targets: list.clone(),
+                following: None,
+            },
+            MessageReceivers::Single(connection),
+        );
+    }
+}
+
+/// Players the local spectator or ghost can follow
+#[derive(Resource, Default)]
+pub(crate) struct ClientSpectator(SpectatorTargetsMessage);
+
+impl ClientSpectator {
+    pub(crate) fn targets(&self) -> &[(NetworkIdentity, String)] {
+        &self.0.targets
+    }
+}
+
+fn client_receive_targets(
+    mut messages: EventReader<MessageEvent<SpectatorTargetsMessage>>,
+    mut spectator: ResMut<ClientSpectator>,
+    role: Res<ClientRole>,
+) {
+    if let Some(event) = messages.iter().last() {
+        spectator.0 = event.message.clone();
+    }
+    if role.is_changed() && !role.spectator {
+        spectator.0 = Default::default();
+    }
+}
+
+/// Points the camera at the creature the spectator follows
+fn client_follow_camera(
+    spectator: Res<ClientSpectator>,
+    role: Res<ClientRole>,
+    identities: Res<NetworkIdentities>,
+    mut camera: Query<&mut TopDownCamera, With<MainCamera>>,
+) {
+    if !role.spectator {
+        return;
+    }
+    let Some(target) = spectator
+        .0
+        .following
+        .and_then(|identity| identities.get_entity(identity))
+    else {
+        return;
+    };
+    if let Ok(mut camera) = camera.get_single_mut() {
+        if camera.target != target {
+            camera.target = target;
+        }
+    }
+}
+
+fn client_spectator_ui(
+    mut contexts: EguiContexts,
+    spectator: Res<ClientSpectator>,
+    role: Res<ClientRole>,
+    mut sender: MessageSender,
+) {
+    if !role.spectator {
+        return;
+    }
+
+    egui::Window::new("Spectating")
+        .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 30.0))
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            match role.queue_position {
+                Some(position) => {
+                    ui.label(format!(
+                        "Waiting for a player slot, {} ahead of you",
+                        position
+                    ));
+                    if ui.button("Stop waiting").clicked() {
+                        sender.send_to_server(&RequestPlayerSlot { waiting: false });
+                    }
+                }
+                None => {
+                    if ui.button("Wait for a player slot").clicked() {
+                        sender.send_to_server(&RequestPlayerSlot { waiting: true });
+                    }
+                }
+            }
+
+            let targets = &spectator.0.targets;
+            if targets.is_empty() {
+                ui.label("Nobody to follow");
+                return;
+            }
+            let selected = spectator
+                .0
+                .following
+                .and_then(|identity| targets.iter().find(|(i, _)| *i == identity))
+                .map_or("Nobody", |(_, name)| name.as_str());
+            egui::ComboBox::from_label("Following")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    for (identity, name) in targets.iter() {
+                        let current = spectator.0.following == Some(*identity);
+                        if ui.selectable_label(current, name).clicked() && !current {
+                            sender.send_to_server(&FollowMessage { target: *identity });
+                        }
+                    }
+                });
+        });
+}
@@ -0,0 +1,253 @@
+//! Backups of persistent data, like bans and characters.
+//!
+//! Persistent data is written to disk as soon as it changes. Backups copy all of it at an interval
+//! into a directory named after the time it was made, keeping only the newest few.
+//! Files that fail to parse when loaded are replaced with their newest copy from a backup that parses.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use maps::wear::WEAR_FILE_EXTENSION;
+use networking::{bans::unix_now, is_server};
+use serde::de::DeserializeOwned;
+
+use crate::{
+    admin::ban_file,
+    config::{BackupConfig, ServerConfig},
+    persistence::character_directory,
+    wear::WEAR_DIRECTORY,
+};
+
+pub struct StoragePlugin;
+
+impl Plugin for StoragePlugin {
+    fn build(&self, app: &mut App) {
+        if is_server(app) {
+            app.init_resource::<Storage>().add_systems(Update, autosave);
+        }
+    }
+}
+
+/// Suffix of backups that are still being written
+const PARTIAL_SUFFIX: &str = ".partial";
+
+#[derive(Resource)]
+pub(crate) struct Storage {
+    config: BackupConfig,
+    /// Persistent files and directories, with the name they have in a backup
+    sources: Vec<(&'static str, PathBuf)>,
+    timer: Option<Timer>,
+}
+
+impl FromWorld for Storage {
+    fn from_world(world: &mut World) -> Self {
+        let config = world.get_resource::<ServerConfig>();
+        let backups = config.map(|c| c.backups.clone()).unwrap_or_default();
+        Self {
+            timer: backups
+                .interval()
+                .map(|interval| Timer::new(interval, TimerMode::Repeating)),
+            config: backups,
+            sources: vec![
+                ("bans.toml", ban_file(config)),
+                ("characters", character_directory(config)),
+                ("map-wear", WEAR_DIRECTORY.into()),
+            ],
+        }
+    }
+}
+
+impl Storage {
+    /// Copies all persistent data into a new backup and deletes the oldest ones past the limit.
+    /// Returns the name of the backup.
+    pub(crate) fn backup(&self) -> io::Result<String> {
+        let name = unix_now().to_string();
+        let target = self.config.directory.join(&name);
+        // A backup was already made this second
+        if target.exists() {
+            return Ok(name);
+        }
+
+        // Half written backups are never mistaken for complete ones
+        let partial = self
+            .config
+            .directory
+            .join(format!("{}{}", name, PARTIAL_SUFFIX));
+        fs::create_dir_all(&partial)?;
+        for (backup_name, source) in self.sources.iter() {
+            if source.exists() {
+                copy_recursive(source, &partial.join(backup_name))?;
+            }
+        }
+        fs::rename(&partial, &target)?;
+
+        self.rotate()?;
+        Ok(name)
+    }
+
+    /// Names of complete backups, newest first
+    pub(crate) fn backups(&self) -> Vec<String> {
+        let Ok(entries) = fs::read_dir(&self.config.directory) else {
+            return Vec::new();
+        };
+        let mut backups: Vec<(u64, String)> = entries
+            .flatten()
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                Some((name.parse().ok()?, name))
+            })
+            .collect();
+        backups.sort_unstable_by(|a, b| b.0.cmp(&a.0));
+        backups.into_iter().map(|(_, name)| name).collect()
+    }
+
+    fn rotate(&self) -> io::Result<()> {
+        for name in self.backups().iter().skip(self.config.keep.max(1)) {
+            fs::remove_dir_all(self.config.directory.join(name))?;
+        }
+        // Leftovers of backups that were interrupted
+        for entry in fs::read_dir(&self.config.directory)?.flatten() {
+            if entry
+                .file_name()
+                .to_string_lossy()
+                .ends_with(PARTIAL_SUFFIX)
+            {
+                fs::remove_dir_all(entry.path())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies a backup to the export directory, where it isn't rotated away.
+    /// Exports the newest backup if no name is given.
+    pub(crate) fn export(&self, name: Option<&str>) -> io::Result<PathBuf> {
+        let backups = self.backups();
+        let name = match name {
+            Some(name) => backups.iter().find(|b| *b == name),
+            None => backups.first(),
+        }
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such backup"))?;
+
+        let target = self
+            .config
+            .export_directory
+            .join(format!("backup-{}", name));
+        copy_recursive(&self.config.directory.join(name), &target)?;
+        Ok(target)
+    }
+
+    /// Where a persistent file is stored in a backup
+    fn backup_path(&self, backup: &str, path: &Path) -> Option<PathBuf> {
+        self.sources.iter().find_map(|(name, source)| {
+            let relative = path.strip_prefix(source).ok()?;
+            let copy = self.config.directory.join(backup).join(name);
+            // Single files are backed up under their own name
+            Some(if relative.as_os_str().is_empty() {
+                copy
+            } else {
+                copy.join(relative)
+            })
+        })
+    }
+
+    /// Parses a persistent file, returns `None` if it doesn't exist.
+    /// If it fails to parse, it is moved aside and replaced with the newest backup that parses.
+    pub(crate) fn load_checked<T: DeserializeOwned>(&self, path: &Path) -> Option<T> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return None,
+            Err(err) => {
+                error!(path = ?path, error = %err, "Failed to read persistent file");
+                return None;
+            }
+        };
+        let format = Format::of(path);
+        let err = match format.parse(&text) {
+            Ok(value) => return Some(value),
+            Err(err) => err,
+        };
+        error!(path = ?path, error = %err, "Persistent file is corrupt, looking for a backup");
+
+        for backup in self.backups() {
+            let Some(backup_path) = self.backup_path(&backup, path) else {
+                break;
+            };
+            let Some(value) = fs::read_to_string(&backup_path)
+                .ok()
+                .and_then(|text| format.parse(&text).ok())
+            else {
+                continue;
+            };
+
+            let corrupt = PathBuf::from(format!("{}.corrupt", path.display()));
+            let result = fs::rename(path, &corrupt).and_then(|_| fs::copy(&backup_path, path));
+            match result {
+                Ok(_) => {
+                    warn!(path = ?path, %backup, corrupt = ?corrupt, "Restored persistent file from backup")
+                }
+                Err(err) => {
+                    error!(path = ?path, %backup, error = %err, "Failed to restore persistent file")
+                }
+            }
+            return Some(value);
+        }
+        error!(path = ?path, "No backup of the persistent file could be loaded");
+        None
+    }
+}
+
+/// Text format of a persistent file
+#[derive(Clone, Copy)]
+enum Format {
+    Toml,
+    Ron,
+}
+
+impl Format {
+    fn of(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("ron") | Some(WEAR_FILE_EXTENSION) => Self::Ron,
+            _ => Self::Toml,
+        }
+    }
+
+    fn parse<T: DeserializeOwned>(self, text: &str) -> Result<T, String> {
+        match self {
+            Self::Toml => toml::from_str(text).map_err(|e| e.to_string()),
+            Self::Ron => ron::from_str(text).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+fn copy_recursive(from: &Path, to: &Path) -> io::Result<()> {
+    if from.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else {
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(from, to)?;
+    }
+    Ok(())
+}
+
+fn autosave(mut storage: ResMut<Storage>, time: Res<Time>) {
+    let Some(timer) = storage.timer.as_mut() else {
+        return;
+    };
+    if !timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    match storage.backup() {
+        Ok(name) => info!(backup = %name, "Backed up persistent data"),
+        Err(err) => error!(error = %err, "Failed to back up persistent data"),
+    }
+}
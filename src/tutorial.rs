@@ -0,0 +1,296 @@
+use bevy::{
+    app::AppExit,
+    prelude::*,
+    utils::{HashMap, Uuid},
+};
+use bevy_egui::{egui, EguiContexts};
+use networking::{
+    is_server,
+    messaging::{AppExt, MessageEvent, MessageReceivers, MessageSender},
+    spawning::{ClientControlled, ClientControls},
+    Players,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    body::{Body, Hand, Hands},
+    communication::event_log::EventLog,
+    config::ServerConfig,
+    doors::Door,
+    interaction::ActiveInteraction,
+    items::StoredItem,
+    ui::has_window,
+    GameState,
+};
+
+pub struct TutorialPlugin;
+
+impl Plugin for TutorialPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_network_message::<TutorialStepMessage>();
+
+        if is_server(app) {
+            app.init_resource::<TutorialProgress>().add_systems(
+                Update,
+                (track_tutorial_progress, stop_when_empty)
+                    .run_if(resource_exists::<TutorialMode>()),
+            );
+        } else {
+            app.init_resource::<ClientTutorial>().add_systems(
+                Update,
+                (
+                    client_receive_step,
+                    tutorial_ui
+                        .run_if(has_window)
+                        .run_if(in_state(GameState::Game)),
+                )
+                    .chain()
+                    .run_if(resource_exists::<TutorialMode>()),
+            );
+        }
+    }
+}
+
+/// Present when playing the tutorial, on both the local server and the client
+#[derive(Resource)]
+pub struct TutorialMode;
+
+/// Name of the player on the tutorial server
+#[cfg(feature = "client")]
+pub const USERNAME: &str = "Trainee";
+
+/// Seconds the tutorial server waits for the player before giving up
+const JOIN_TIMEOUT: f32 = 120.0;
+/// Meters walked to finish the movement step
+const WALK_DISTANCE: f32 = 2.5;
+
+/// Starts a tutorial server in a separate process and returns the address to join.
+/// The server stops by itself once the player leaves.
+#[cfg(feature = "client")]
+pub fn start_server() -> std::io::Result<std::net::SocketAddr> {
+    // Let the OS pick a free port
+    let address = std::net::UdpSocket::bind((std::net::Ipv4Addr::LOCALHOST, 0))?.local_addr()?;
+    std::process::Command::new(std::env::current_exe()?)
+        .arg("host")
+        .arg(address.to_string())
+        .arg("--tutorial")
+        .spawn()?;
+    Ok(address)
+}
+
+/// Overrides the server configuration for a local tutorial session
+pub fn configure_server(config: &mut ServerConfig) {
+    config.registration = None;
+    config.map = Some("Tutorial".into());
+    config.round.start_countdown_seconds = 3.0;
+    config.round.max_duration_minutes = None;
+    config.respawn.delay_minutes = 0.0;
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum TutorialStep {
+    Join,
+    Walk,
+    PickUp,
+    SwitchHands,
+    Store,
+    Interact,
+    Done,
+}
+
+impl TutorialStep {
+    const ALL: [TutorialStep; 7] = [
+        TutorialStep::Join,
+        TutorialStep::Walk,
+        TutorialStep::PickUp,
+        TutorialStep::SwitchHands,
+        TutorialStep::Store,
+        TutorialStep::Interact,
+        TutorialStep::Done,
+    ];
+
+    fn prompt(self) -> &'static str {
+        match self {
+            TutorialStep::Join => "Pick a job in the Jobs window and press Ready",
+            TutorialStep::Walk => "Walk around using W, A, S and D",
+            TutorialStep::PickUp => "Left-click the crowbar on the floor to pick it up",
+            TutorialStep::SwitchHands => "Press X to switch to your other hand",
+            TutorialStep::Store => {
+                "Right-click the backpack on the floor while holding an item and choose Insert"
+            }
+            TutorialStep::Interact => {
+                "Right-click the door to see what you can do with it, then open it"
+            }
+            TutorialStep::Done => "You are ready to join a real station!",
+        }
+    }
+
+    fn next(self) -> Self {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|step| *step > self)
+            .unwrap_or(TutorialStep::Done)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct TutorialStepMessage {
+    step: TutorialStep,
+}
+
+struct PlayerProgress {
+    step: TutorialStep,
+    /// Where the player was when the walking step started
+    walk_start: Option<Vec3>,
+    /// The active hand when the switching step started
+    first_hand: Option<Entity>,
+}
+
+#[derive(Resource, Default)]
+struct TutorialProgress(HashMap<Uuid, PlayerProgress>);
+
+#[allow(clippy::too_many_arguments)]
+fn track_tutorial_progress(
+    mut progress: ResMut<TutorialProgress>,
+    controls: Res<ClientControls>,
+    players: Res<Players>,
+    bodies: Query<(&GlobalTransform, &Hands, &Body)>,
+    hands: Query<(), With<Hand>>,
+    parents: Query<&Parent>,
+    stored: Query<&StoredItem>,
+    moved_items: Query<&StoredItem, Changed<StoredItem>>,
+    started_interactions: Query<(Entity, &ActiveInteraction), Added<ActiveInteraction>>,
+    doors: Query<(), With<Door>>,
+    mut sender: MessageSender,
+) {
+    for (&connection, player) in players.players().iter() {
+        let Some(body) = controls.controlled_entity(player.id) else {
+            continue;
+        };
+        let Ok((transform, body_hands, body_data)) = bodies.get(body) else {
+            continue;
+        };
+
+        let mut changed = false;
+        let progress = progress.0.entry(player.id).or_insert_with(|| {
+            changed = true;
+            PlayerProgress {
+                step: TutorialStep::Walk,
+                walk_start: None,
+                first_hand: None,
+            }
+        });
+
+        let complete = match progress.step {
+            TutorialStep::Join | TutorialStep::Done => false,
+            TutorialStep::Walk => {
+                let start = *progress.walk_start.get_or_insert(transform.translation());
+                start.distance(transform.translation()) >= WALK_DISTANCE
+            }
+            TutorialStep::PickUp => stored.iter().any(|item| {
+                hands.contains(item.container())
+                    && body_data.limbs().any(|limb| limb == item.container())
+            }),
+            TutorialStep::SwitchHands => {
+                let active = body_hands.active_hand();
+                *progress.first_hand.get_or_insert(active) != active
+            }
+            TutorialStep::Store => moved_items
+                .iter()
+                .any(|item| !hands.contains(item.container())),
+            TutorialStep::Interact => started_interactions.iter().any(|(entity, active)| {
+                entity == body
+                    && std::iter::once(active.target)
+                        .chain(parents.iter_ancestors(active.target))
+                        .any(|e| doors.contains(e))
+            }),
+        };
+        if complete {
+            progress.step = progress.step.next();
+            changed = true;
+        }
+
+        if changed {
+            sender.send(
+                &TutorialStepMessage {
+                    step: progress.step,
+                },
+                MessageReceivers::Single(connection),
+            );
+        }
+    }
+}
+
+/// Shuts the tutorial server down after the player leaves
+fn stop_when_empty(
+    players: Res<Players>,
+    time: Res<Time>,
+    mut joined: Local<bool>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if !players.players().is_empty() {
+        *joined = true;
+        return;
+    }
+    if *joined || time.elapsed_seconds() > JOIN_TIMEOUT {
+        info!("Tutorial player left, stopping server");
+        exit.send(AppExit);
+    }
+}
+
+#[derive(Resource)]
+struct ClientTutorial {
+    step: TutorialStep,
+}
+
+impl Default for ClientTutorial {
+    fn default() -> Self {
+        Self {
+            step: TutorialStep::Join,
+        }
+    }
+}
+
+fn client_receive_step(
+    mut messages: EventReader<MessageEvent<TutorialStepMessage>>,
+    mut tutorial: ResMut<ClientTutorial>,
+    mut log: ResMut<EventLog>,
+) {
+    for event in messages.iter() {
+        tutorial.step = event.message.step;
+        log.record(&format!("Tutorial: {}", tutorial.step.prompt()));
+    }
+}
+
+fn tutorial_ui(
+    mut contexts: EguiContexts,
+    tutorial: Res<ClientTutorial>,
+    controlled: Query<(), With<ClientControlled>>,
+) {
+    // Back in the lobby after dying or a restart
+    let current = if controlled.is_empty() {
+        TutorialStep::Join
+    } else {
+        tutorial.step
+    };
+
+    egui::Window::new("Tutorial")
+        .anchor(egui::Align2::LEFT_TOP, egui::vec2(10.0, 10.0))
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            for step in TutorialStep::ALL {
+                if step == TutorialStep::Done && current != TutorialStep::Done {
+                    continue;
+                }
+                let text = egui::RichText::new(step.prompt());
+                if step < current {
+                    ui.label(text.weak().strikethrough());
+                } else if step == current {
+                    ui.label(text.strong());
+                } else {
+                    ui.label(text.weak());
+                }
+            }
+        });
+}
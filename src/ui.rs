@@ -11,30 +11,39 @@ use networking::{
 use serde::{Deserialize, Serialize};
 
 use self::{
-    lobby::LobbyPlugin, main_menu::MainMenuPlugin, pause_menu::PauseMenuPlugin,
-    splash::SplashPlugin,
+    lobby::LobbyPlugin, main_menu::MainMenuPlugin, palette::PalettePlugin,
+    pause_menu::PauseMenuPlugin, splash::SplashPlugin, toasts::ToastPlugin,
 };
 
 mod lobby;
 mod main_menu;
+pub mod palette;
 mod pause_menu;
 mod splash;
+pub mod toasts;
 
 pub struct UiPlugin;
 
 impl Plugin for UiPlugin {
     fn build(&self, app: &mut App) {
-        app.add_network_message::<CloseUiMessage>();
+        app.add_network_message::<CloseUiMessage>()
+            .add_plugins(ToastPlugin);
         if is_server(app) {
             app.add_systems(Update, (handle_close_ui, close_unused_uis));
         } else {
-            app.add_plugins((SplashPlugin, MainMenuPlugin, PauseMenuPlugin, LobbyPlugin))
-                .add_systems(
-                    PreUpdate,
-                    (absorb_egui_inputs,)
-                        .after(bevy_egui::systems::process_input_system)
-                        .before(bevy_egui::EguiSet::BeginFrame),
-                );
+            app.add_plugins((
+                SplashPlugin,
+                MainMenuPlugin,
+                PauseMenuPlugin,
+                LobbyPlugin,
+                PalettePlugin,
+            ))
+            .add_systems(
+                PreUpdate,
+                (absorb_egui_inputs,)
+                    .after(bevy_egui::systems::process_input_system)
+                    .before(bevy_egui::EguiSet::BeginFrame),
+            );
         }
     }
 }
@@ -1,12 +1,17 @@
 use crate::{
+    body::{ghost::ClientRespawn, health::species::SpeciesDefinition},
+    communication::accents::Accent,
     job::{JobDefinition, SelectJobMessage},
-    round::{RequestJoin, RoundDataClient, RoundState, StartRoundRequest},
+    persistence::{
+        AccentPreference, CharacterProfile, ClientCharacterProfile, UpdateCharacterMessage,
+    },
+    round::{RequestJoin, RoundDataClient, RoundState, SetReadyMessage},
     GameState,
 };
 use bevy::{asset::HandleId, prelude::*};
 use bevy_egui::EguiContexts;
 use bevy_inspector_egui::egui;
-use networking::{messaging::MessageSender, spawning::ClientControlled};
+use networking::{messaging::MessageSender, spawning::ClientControlled, ClientRole};
 
 use super::has_window;
 
@@ -16,7 +21,7 @@ impl Plugin for LobbyPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
             Update,
-            (ui, job_ui)
+            (ui, job_ui, character_ui)
                 .run_if(in_state(GameState::Game))
                 .run_if(has_window),
         );
@@ -27,52 +32,115 @@ fn ui(
     mut contexts: EguiContexts,
     round_data: Option<Res<RoundDataClient>>,
     client_controlled: Query<(), With<ClientControlled>>,
+    role: Res<ClientRole>,
     mut sender: MessageSender,
+    mut ready: Local<bool>,
 ) {
-    // Only show lobby UI if not controlling any entity
+    // Spectators can't take part until they get a player slot
+    if role.spectator {
+        *ready = false;
+        return;
+    }
+
+    let Some(data) = round_data else {
+        if client_controlled.is_empty() {
+            egui::Window::new("Lobby")
+                .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+                .show(contexts.ctx_mut(), |ui| {
+                    ui.label("Loading...");
+                });
+        }
+        return;
+    };
+
+    // Readiness only applies to the upcoming round
+    if !matches!(data.state(), RoundState::Lobby | RoundState::Starting) {
+        *ready = false;
+    }
+
+    // Players in the round only get told when it ends
     if !client_controlled.is_empty() {
+        if *data.state() == RoundState::Ending {
+            egui::Window::new("Round over")
+                .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 30.0))
+                .show(contexts.ctx_mut(), |ui| {
+                    restart_label(ui, &data);
+                });
+        }
         return;
     }
 
     egui::Window::new("Lobby")
         .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
-        .show(contexts.ctx_mut(), |ui| {
-            if let Some(data) = round_data {
-                ui.label(format!("Round state: {:?}", data.state()));
-
-                match data.state() {
-                    RoundState::Ready => {
-                        if ui.button("Start round").clicked() {
-                            sender.send_to_server(&StartRoundRequest);
-                        }
+        .show(contexts.ctx_mut(), |ui| match data.state() {
+            RoundState::Restarting => {
+                ui.label("Preparing the next round...");
+            }
+            RoundState::Lobby | RoundState::Starting => {
+                match (data.state(), data.countdown()) {
+                    (RoundState::Starting, Some(seconds)) => {
+                        ui.label(format!("Round starts in {} seconds", seconds));
                     }
-                    RoundState::Running => {
-                        ui.label(format!("Round started tick: {}", data.start().unwrap()));
-                        if ui.button("Join").clicked() {
-                            sender.send_to_server(&RequestJoin);
-                        }
+                    _ => {
+                        ui.label("Waiting for everyone to be ready");
                     }
-                    _ => {}
                 }
-            } else {
-                ui.label("Loading...");
+                ui.label(format!("Players ready: {}", data.ready_players()));
+                if ui.checkbox(&mut *ready, "Ready").changed() {
+                    sender.send_to_server(&SetReadyMessage { ready: *ready });
+                }
+                ui.small("Pick a job before readying up");
+            }
+            RoundState::InProgress => {
+                if let Some(start) = data.start() {
+                    ui.label(format!("Round started tick: {}", start));
+                }
+                if ui.button("Join").clicked() {
+                    sender.send_to_server(&RequestJoin);
+                }
+            }
+            RoundState::Ending => {
+                ui.label("The round is over");
+                restart_label(ui, &data);
             }
         });
 }
 
+fn restart_label(ui: &mut egui::Ui, data: &RoundDataClient) {
+    if let Some(seconds) = data.countdown() {
+        ui.label(format!("Restarting in {} seconds", seconds));
+    }
+}
+
 fn job_ui(
     mut contexts: EguiContexts,
     client_controlled: Query<(), With<ClientControlled>>,
     jobs: Res<Assets<JobDefinition>>,
+    profile: Res<ClientCharacterProfile>,
+    respawn: Res<ClientRespawn>,
     mut sender: MessageSender,
     mut selected_job: Local<Option<HandleId>>,
     mut sorted_jobs: Local<Vec<Handle<JobDefinition>>>,
 ) {
-    // Only show lobby UI if not controlling any entity
-    if !client_controlled.is_empty() {
+    // Only show lobby UI if not controlling any entity, unless a ghost needs a new job
+    if !client_controlled.is_empty() && !respawn.pick_job() {
         return;
     }
 
+    // The server cleared the job when the character was abandoned
+    if respawn.is_changed() && respawn.pick_job() {
+        *selected_job = None;
+    }
+
+    // Show the job the server restored from the saved character
+    if profile.is_changed() {
+        if let Some(job_id) = profile.0.as_ref().and_then(|p| p.job.as_deref()) {
+            if let Some((handle, _)) = jobs.iter().find(|(_, job)| job.id == job_id) {
+                *selected_job = Some(handle);
+            }
+        }
+    }
+
     if jobs.len() != sorted_jobs.len() {
         let mut new_sorted: Vec<_> = jobs.iter().collect();
         new_sorted.sort_unstable_by_key(|x| &x.1.name);
@@ -103,3 +171,100 @@ fn job_ui(
         sender.send_to_server(&SelectJobMessage { job: asset_id });
     }
 }
+
+fn character_ui(
+    mut contexts: EguiContexts,
+    client_controlled: Query<(), With<ClientControlled>>,
+    profile: Res<ClientCharacterProfile>,
+    species: Res<Assets<SpeciesDefinition>>,
+    mut draft: Local<Option<CharacterProfile>>,
+    mut sender: MessageSender,
+) {
+    // Only show lobby UI if not controlling any entity
+    if !client_controlled.is_empty() {
+        return;
+    }
+
+    if profile.is_changed() {
+        draft.clone_from(&profile.0);
+    }
+    let Some(draft) = draft.as_mut() else {
+        return;
+    };
+
+    egui::Window::new("Character")
+        .anchor(egui::Align2::LEFT_CENTER, egui::vec2(30.0, 0.0))
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Name");
+                ui.text_edit_singleline(&mut draft.name);
+            });
+
+            egui::ComboBox::from_label("Species")
+                .selected_text(
+                    species
+                        .iter()
+                        .find(|(_, s)| s.id == draft.species)
+                        .map(|(_, s)| s.name.as_str())
+                        .unwrap_or(draft.species.as_str()),
+                )
+                .show_ui(ui, |ui| {
+                    for (_, definition) in species.iter().filter(|(_, s)| s.playable) {
+                        ui.selectable_value(
+                            &mut draft.species,
+                            definition.id.clone(),
+                            &definition.name,
+                        );
+                    }
+                });
+
+            ui.label(egui::RichText::new("Accents").strong());
+            for accent in Accent::ALL {
+                let index = draft.accents.iter().position(|a| a.accent == accent);
+                let mut enabled = index.is_some();
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut enabled, accent.name());
+                    if let Some(index) = index {
+                        ui.add(egui::Slider::new(
+                            &mut draft.accents[index].severity,
+                            0.1..=1.0,
+                        ));
+                    }
+                });
+                match (enabled, index) {
+                    (true, None) => draft.accents.push(AccentPreference {
+                        accent,
+                        severity: 0.5,
+                    }),
+                    (false, Some(index)) => {
+                        draft.accents.remove(index);
+                    }
+                    _ => {}
+                }
+            }
+
+            ui.label(egui::RichText::new("Appearance").strong());
+            ui.horizontal(|ui| {
+                ui.color_edit_button_srgb(&mut draft.appearance.skin_color);
+                ui.label("Skin");
+            });
+            ui.horizontal(|ui| {
+                ui.color_edit_button_srgb(&mut draft.appearance.hair_color);
+                ui.label("Hair");
+            });
+
+            let mut show_hints = !draft.hints_disabled;
+            ui.checkbox(&mut show_hints, "Show gameplay tips");
+            draft.hints_disabled = !show_hints;
+
+            if ui.button("Save").clicked() {
+                sender.send_to_server(&UpdateCharacterMessage {
+                    name: draft.name.clone(),
+                    species: draft.species.clone(),
+                    accents: draft.accents.clone(),
+                    appearance: draft.appearance,
+                    hints_disabled: draft.hints_disabled,
+                });
+            }
+        });
+}
@@ -3,7 +3,9 @@ use std::{net::SocketAddr, str::FromStr};
 use bevy::prelude::*;
 use bevy_egui::EguiContexts;
 use bevy_inspector_egui::egui::{self, TextEdit};
-use networking::{ClientEvent, TargetServer, UserData};
+use networking::{sync::InitialSync, ClientEvent, JoinOptions, TargetServer, UserData};
+#[cfg(feature = "client")]
+use {crate::singleplayer, networking::local::LocalServerAddress};
 
 use crate::GameState;
 
@@ -17,6 +19,9 @@ impl Plugin for MainMenuPlugin {
             Update,
             (
                 ui.run_if(in_state(GameState::MainMenu)).run_if(has_window),
+                joining_ui
+                    .run_if(in_state(GameState::Joining))
+                    .run_if(has_window),
                 react_to_client_change,
             ),
         );
@@ -34,13 +39,13 @@ fn ui(
     mut name: Local<String>,
     mut client_events: EventWriter<ClientEvent>,
     disconnect: Option<Res<DisconnectReason>>,
+    #[cfg(feature = "client")] local_server: Option<Res<LocalServerAddress>>,
     mut commands: Commands,
 ) {
     egui::Area::new("main buttons")
         .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
         .show(contexts.ctx_mut(), |ui| {
             ui.horizontal(|ui| {
-                // TODO: Actually use name
                 let name_field = TextEdit::singleline(&mut *name).hint_text("Name");
                 if name_field.show(ui).response.changed() {
                     commands.insert_resource(UserData {
@@ -51,13 +56,31 @@ fn ui(
                 let ip_field = TextEdit::singleline(&mut *ip).hint_text("Server IP");
                 ip_field.show(ui);
 
-                if ui.button("Join").clicked() {
-                    if let Ok(address) = SocketAddr::from_str(ip.as_ref()) {
-                        client_events.send(ClientEvent::Join(TargetServer::Raw(address)));
+                for (label, spectate) in [("Join", false), ("Observe", true)] {
+                    if ui.button(label).clicked() {
+                        if let Ok(address) = SocketAddr::from_str(ip.as_ref()) {
+                            commands.insert_resource(JoinOptions { spectate });
+                            client_events.send(ClientEvent::Join(TargetServer::Raw(address)));
+                        }
                     }
                 }
             });
 
+            #[cfg(feature = "client")]
+            if ui.button("Singleplayer").clicked() {
+                let address = local_server
+                    .as_deref()
+                    .cloned()
+                    .unwrap_or_else(singleplayer::start_server);
+                // Join once the address is available to the join flow
+                commands.add(move |world: &mut World| {
+                    world.insert_resource(address);
+                    world
+                        .resource_mut::<Events<ClientEvent>>()
+                        .send(ClientEvent::Join(TargetServer::Local));
+                });
+            }
+
             if !ip.is_empty() && SocketAddr::from_str(ip.as_ref()).is_err() {
                 ui.colored_label(egui::Color32::DARK_RED, "Invalid address");
             }
@@ -69,6 +92,24 @@ fn ui(
         });
 }
 
+fn joining_ui(mut contexts: EguiContexts, sync: Option<Res<InitialSync>>) {
+    egui::Area::new("joining")
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.set_width(300.0);
+            match sync.and_then(|s| s.progress()) {
+                Some(progress) => {
+                    ui.label("Receiving station...");
+                    ui.add(egui::ProgressBar::new(progress).show_percentage());
+                }
+                None => {
+                    ui.label("Connecting...");
+                    ui.spinner();
+                }
+            }
+        });
+}
+
 fn react_to_client_change(
     mut events: EventReader<ClientEvent>,
     mut game_state: ResMut<NextState<GameState>>,
@@ -0,0 +1,148 @@
+//! Colors for color-coded cues, with palettes for color vision deficiencies.
+//!
+//! UI code asks for the color of a [`Cue`] instead of hardcoding red and green.
+//! Players can also enable symbols, so cues don't rely on color alone.
+
+use std::fs::{read_to_string, write};
+
+use bevy::prelude::*;
+use bevy_egui::egui;
+use serde::{Deserialize, Serialize};
+
+pub struct PalettePlugin;
+
+impl Plugin for PalettePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(load_palette())
+            .add_systems(Update, save_palette.run_if(resource_changed::<Palette>()));
+    }
+}
+
+/// Meaning of a color-coded element
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Cue {
+    Good,
+    Bad,
+    Warning,
+    Info,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum PaletteKind {
+    #[default]
+    Default,
+    /// Avoids telling apart red and green (protanopia and deuteranopia)
+    RedGreen,
+    /// Avoids telling apart blue and yellow (tritanopia)
+    BlueYellow,
+}
+
+impl PaletteKind {
+    pub const ALL: [PaletteKind; 3] = [
+        PaletteKind::Default,
+        PaletteKind::RedGreen,
+        PaletteKind::BlueYellow,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PaletteKind::Default => "Default",
+            PaletteKind::RedGreen => "Red-green safe",
+            PaletteKind::BlueYellow => "Blue-yellow safe",
+        }
+    }
+}
+
+/// Client setting for how cues are shown
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub struct Palette {
+    #[serde(default)]
+    pub kind: PaletteKind,
+    /// Add symbols and patterns to color-coded elements
+    #[serde(default)]
+    pub symbols: bool,
+}
+
+impl Palette {
+    pub fn color(&self, cue: Cue) -> egui::Color32 {
+        use egui::Color32;
+        match (self.kind, cue) {
+            (PaletteKind::Default, Cue::Good) => Color32::GREEN,
+            (PaletteKind::Default, Cue::Bad) => Color32::RED,
+            (PaletteKind::Default, Cue::Warning) => Color32::YELLOW,
+            (PaletteKind::Default, Cue::Info) => Color32::LIGHT_BLUE,
+            // Based on the Okabe-Ito palette
+            (PaletteKind::RedGreen, Cue::Good) => Color32::from_rgb(0, 114, 178),
+            (PaletteKind::RedGreen, Cue::Bad) => Color32::from_rgb(213, 94, 0),
+            (PaletteKind::RedGreen, Cue::Warning) => Color32::from_rgb(240, 228, 66),
+            (PaletteKind::RedGreen, Cue::Info) => Color32::from_rgb(86, 180, 233),
+            (PaletteKind::BlueYellow, Cue::Good) => Color32::from_rgb(0, 158, 115),
+            (PaletteKind::BlueYellow, Cue::Bad) => Color32::from_rgb(220, 30, 30),
+            (PaletteKind::BlueYellow, Cue::Warning) => Color32::from_rgb(255, 120, 200),
+            (PaletteKind::BlueYellow, Cue::Info) => Color32::from_gray(210),
+        }
+    }
+
+    /// Color between bad (0.0), warning (0.5) and good (1.0)
+    pub fn scale(&self, t: f32) -> egui::Color32 {
+        let t = t.clamp(0.0, 1.0);
+        let (from, to, t) = if t > 0.5 {
+            (Cue::Warning, Cue::Good, t * 2.0 - 1.0)
+        } else {
+            (Cue::Bad, Cue::Warning, t * 2.0)
+        };
+        lerp_color(self.color(from), self.color(to), t)
+    }
+
+    /// Text in the color of a cue, prefixed by its symbol if enabled
+    pub fn text(&self, cue: Cue, text: &str) -> egui::RichText {
+        let text = if self.symbols {
+            format!("{} {}", symbol(cue), text)
+        } else {
+            text.to_owned()
+        };
+        egui::RichText::new(text).color(self.color(cue))
+    }
+}
+
+fn symbol(cue: Cue) -> &'static str {
+    match cue {
+        Cue::Good => "✔",
+        Cue::Bad => "❌",
+        Cue::Warning => "⚠",
+        Cue::Info => "ℹ",
+    }
+}
+
+fn lerp_color(a: egui::Color32, b: egui::Color32, t: f32) -> egui::Color32 {
+    let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    egui::Color32::from_rgb(
+        channel(a.r(), b.r()),
+        channel(a.g(), b.g()),
+        channel(a.b(), b.b()),
+    )
+}
+
+const PALETTE_FILE: &str = "accessibility.toml";
+
+fn load_palette() -> Palette {
+    let Ok(text) = read_to_string(PALETTE_FILE) else {
+        return Palette::default();
+    };
+    toml::from_str(&text).unwrap_or_else(|err| {
+        error!("Error loading accessibility settings: {}", err);
+        Palette::default()
+    })
+}
+
+fn save_palette(palette: Res<Palette>) {
+    if palette.is_added() {
+        return;
+    }
+    let result = toml::to_string(&*palette)
+        .map_err(|err| err.to_string())
+        .and_then(|text| write(PALETTE_FILE, text).map_err(|err| err.to_string()));
+    if let Err(err) = result {
+        error!("Error saving accessibility settings: {}", err);
+    }
+}
@@ -3,9 +3,16 @@ use bevy_egui::EguiContexts;
 use bevy_inspector_egui::egui;
 use networking::{ClientState, ClientTask};
 
-use crate::GameState;
+use crate::{
+    combat::feedback::{HitFeedback, HitFeedbackSettings},
+    GameState,
+};
 
-use super::has_window;
+use super::{
+    has_window,
+    palette::{Palette, PaletteKind},
+    toasts::{ToastPosition, ToastSettings},
+};
 
 pub struct PauseMenuPlugin;
 
@@ -24,6 +31,9 @@ fn ui(
     mut visible: Local<bool>,
     state: Res<State<ClientState>>,
     mut tasks: EventWriter<ClientTask>,
+    mut palette: ResMut<Palette>,
+    mut toast_settings: ResMut<ToastSettings>,
+    mut hit_settings: ResMut<HitFeedbackSettings>,
 ) {
     if !matches!(state.get(), ClientState::Connected) {
         *visible = false;
@@ -52,5 +62,47 @@ fn ui(
                     tasks.send(ClientTask::Leave);
                 }
             });
+
+            ui.separator();
+            // Only write back on change, the palette is saved whenever it changes
+            let mut settings = *palette;
+            egui::ComboBox::from_label("Colors")
+                .selected_text(settings.kind.name())
+                .show_ui(ui, |ui| {
+                    for kind in PaletteKind::ALL {
+                        ui.selectable_value(&mut settings.kind, kind, kind.name());
+                    }
+                });
+            ui.checkbox(&mut settings.symbols, "Show symbols");
+            if settings != *palette {
+                *palette = settings;
+            }
+
+            ui.separator();
+            let mut settings = *toast_settings;
+            egui::ComboBox::from_label("Notifications")
+                .selected_text(settings.position.name())
+                .show_ui(ui, |ui| {
+                    for position in ToastPosition::ALL {
+                        ui.selectable_value(&mut settings.position, position, position.name());
+                    }
+                });
+            ui.add(egui::Slider::new(&mut settings.seconds, 2.0..=20.0).text("Seconds shown"));
+            if settings != *toast_settings {
+                *toast_settings = settings;
+            }
+
+            ui.separator();
+            let mut settings = *hit_settings;
+            egui::ComboBox::from_label("Hit feedback")
+                .selected_text(settings.feedback.name())
+                .show_ui(ui, |ui| {
+                    for feedback in HitFeedback::ALL {
+                        ui.selectable_value(&mut settings.feedback, feedback, feedback.name());
+                    }
+                });
+            if settings != *hit_settings {
+                *hit_settings = settings;
+            }
         });
 }
@@ -0,0 +1,352 @@
+//! Short notifications that pop up in a corner of the screen and go away by themselves.
+//!
+//! Client systems push [`Toast`]s to the [`Toasts`] resource.
+//! Server systems send them to a creature's player with [`PlayerToasts`].
+
+use std::{
+    collections::VecDeque,
+    fs::{read_to_string, write},
+};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_egui::{egui, EguiContexts};
+use networking::{
+    is_server,
+    messaging::{AppExt, MessageEvent, MessageReceivers, MessageSender},
+    spawning::ClientControls,
+    ConnectionId, Players,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::GameState;
+
+use super::{
+    has_window,
+    palette::{Cue, Palette},
+};
+
+pub struct ToastPlugin;
+
+impl Plugin for ToastPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_network_message::<ToastMessage>();
+
+        if !is_server(app) {
+            app.init_resource::<Toasts>()
+                .insert_resource(load_settings())
+                .add_event::<ToastActionEvent>()
+                .add_systems(
+                    Update,
+                    (
+                        client_receive_toasts,
+                        toasts_ui
+                            .run_if(has_window)
+                            .run_if(in_state(GameState::Game)),
+                    )
+                        .chain(),
+                )
+                .add_systems(
+                    Update,
+                    save_settings.run_if(resource_changed::<ToastSettings>()),
+                );
+        }
+    }
+}
+
+/// Most toasts on screen at once, the rest wait their turn
+const MAX_VISIBLE_TOASTS: usize = 4;
+const TOAST_WIDTH: f32 = 280.0;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ToastLevel {
+    Info,
+    Success,
+    Warning,
+    Error,
+    /// Something new for the player, like a tip or an unlock
+    Unlock,
+}
+
+impl ToastLevel {
+    fn cue(self) -> Cue {
+        match self {
+            ToastLevel::Info | ToastLevel::Unlock => Cue::Info,
+            ToastLevel::Success => Cue::Good,
+            ToastLevel::Warning => Cue::Warning,
+            ToastLevel::Error => Cue::Bad,
+        }
+    }
+
+    fn icon(self) -> &'static str {
+        match self {
+            ToastLevel::Info => "ℹ",
+            ToastLevel::Success => "✔",
+            ToastLevel::Warning => "⚠",
+            ToastLevel::Error => "❌",
+            ToastLevel::Unlock => "★",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Toast {
+    pub level: ToastLevel,
+    pub title: Option<String>,
+    pub text: String,
+    /// Seconds it stays on screen, the player's setting if not set
+    pub seconds: Option<f32>,
+    /// Label and id of an extra button, clicking it sends a [`ToastActionEvent`]
+    pub action: Option<(String, String)>,
+}
+
+impl Toast {
+    pub fn new(level: ToastLevel, text: impl Into<String>) -> Self {
+        Self {
+            level,
+            title: None,
+            text: text.into(),
+            seconds: None,
+            action: None,
+        }
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn seconds(mut self, seconds: f32) -> Self {
+        self.seconds = Some(seconds);
+        self
+    }
+
+    pub fn action(mut self, label: impl Into<String>, id: impl Into<String>) -> Self {
+        self.action = Some((label.into(), id.into()));
+        self
+    }
+}
+
+/// Sent when the action button of a toast was clicked
+#[derive(Event)]
+pub struct ToastActionEvent {
+    pub id: String,
+}
+
+/// Shows a toast to the receiving player
+#[derive(Serialize, Deserialize)]
+struct ToastMessage {
+    toast: Toast,
+}
+
+/// Sends toasts to the players controlling creatures
+#[derive(SystemParam)]
+pub struct PlayerToasts<'w, 's> {
+    controls: Res<'w, ClientControls>,
+    players: Res<'w, Players>,
+    sender: MessageSender<'w, 's>,
+}
+
+impl<'w, 's> PlayerToasts<'w, 's> {
+    /// Does nothing if no player controls the creature
+    pub fn send(&mut self, creature: Entity, toast: Toast) {
+        let Some(connection) = self
+            .controls
+            .controlling_player(creature)
+            .and_then(|player| self.players.get_connection(&player))
+        else {
+            return;
+        };
+        self.send_to_connection(connection, toast);
+    }
+
+    /// Sends a toast to a player that may not control a creature, like one in the lobby
+    pub fn send_to_connection(&mut self, connection: ConnectionId, toast: Toast) {
+        self.sender.send(
+            &ToastMessage { toast },
+            MessageReceivers::Single(connection),
+        );
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ToastPosition {
+    #[default]
+    TopRight,
+    TopLeft,
+    BottomRight,
+    BottomLeft,
+}
+
+impl ToastPosition {
+    pub const ALL: [ToastPosition; 4] = [
+        ToastPosition::TopRight,
+        ToastPosition::TopLeft,
+        ToastPosition::BottomRight,
+        ToastPosition::BottomLeft,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ToastPosition::TopRight => "Top right",
+            ToastPosition::TopLeft => "Top left",
+            ToastPosition::BottomRight => "Bottom right",
+            ToastPosition::BottomLeft => "Bottom left",
+        }
+    }
+
+    fn anchor(self) -> (egui::Align2, egui::Vec2) {
+        match self {
+            ToastPosition::TopRight => (egui::Align2::RIGHT_TOP, egui::vec2(-10.0, 40.0)),
+            ToastPosition::TopLeft => (egui::Align2::LEFT_TOP, egui::vec2(10.0, 40.0)),
+            ToastPosition::BottomRight => (egui::Align2::RIGHT_BOTTOM, egui::vec2(-10.0, -10.0)),
+            ToastPosition::BottomLeft => (egui::Align2::LEFT_BOTTOM, egui::vec2(10.0, -10.0)),
+        }
+    }
+}
+
+/// Client setting for where toasts show up and for how long
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct ToastSettings {
+    #[serde(default)]
+    pub position: ToastPosition,
+    #[serde(default = "default_toast_seconds")]
+    pub seconds: f32,
+}
+
+fn default_toast_seconds() -> f32 {
+    6.0
+}
+
+impl Default for ToastSettings {
+    fn default() -> Self {
+        Self {
+            position: Default::default(),
+            seconds: default_toast_seconds(),
+        }
+    }
+}
+
+const SETTINGS_FILE: &str = "notifications.toml";
+
+fn load_settings() -> ToastSettings {
+    let Ok(text) = read_to_string(SETTINGS_FILE) else {
+        return ToastSettings::default();
+    };
+    toml::from_str(&text).unwrap_or_else(|err| {
+        error!("Error loading notification settings: {}", err);
+        ToastSettings::default()
+    })
+}
+
+fn save_settings(settings: Res<ToastSettings>) {
+    if settings.is_added() {
+        return;
+    }
+    let result = toml::to_string(&*settings)
+        .map_err(|err| err.to_string())
+        .and_then(|text| write(SETTINGS_FILE, text).map_err(|err| err.to_string()));
+    if let Err(err) = result {
+        error!("Error saving notification settings: {}", err);
+    }
+}
+
+struct ShownToast {
+    toast: Toast,
+    /// When it goes away, set once it's on screen
+    until: Option<f32>,
+}
+
+/// Toasts on screen and waiting to be shown on this client
+#[derive(Resource, Default)]
+pub struct Toasts {
+    queue: VecDeque<ShownToast>,
+}
+
+impl Toasts {
+    pub fn push(&mut self, toast: Toast) {
+        self.queue.push_back(ShownToast { toast, until: None });
+    }
+
+    /// Removes all toasts with the given action, for when it no longer applies
+    pub fn remove_with_action(&mut self, id: &str) {
+        self.queue.retain(|shown| {
+            shown
+                .toast
+                .action
+                .as_ref()
+                .map_or(true, |(_, action)| action != id)
+        });
+    }
+}
+
+fn client_receive_toasts(
+    mut messages: EventReader<MessageEvent<ToastMessage>>,
+    mut toasts: ResMut<Toasts>,
+) {
+    for event in messages.iter() {
+        toasts.push(event.message.toast.clone());
+    }
+}
+
+fn toasts_ui(
+    mut contexts: EguiContexts,
+    mut toasts: ResMut<Toasts>,
+    settings: Res<ToastSettings>,
+    palette: Res<Palette>,
+    time: Res<Time>,
+    mut actions: EventWriter<ToastActionEvent>,
+) {
+    let now = time.elapsed_seconds();
+    toasts
+        .queue
+        .retain(|shown| shown.until.map_or(true, |until| until > now));
+    if toasts.queue.is_empty() {
+        return;
+    }
+
+    let mut dismissed = None;
+    let (align, offset) = settings.position.anchor();
+    egui::Area::new("toasts")
+        .anchor(align, offset)
+        .show(contexts.ctx_mut(), |ui| {
+            for (index, shown) in toasts.queue.iter_mut().take(MAX_VISIBLE_TOASTS).enumerate() {
+                let toast = &shown.toast;
+                shown
+                    .until
+                    .get_or_insert(now + toast.seconds.unwrap_or(settings.seconds));
+
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_width(TOAST_WIDTH);
+                    ui.horizontal(|ui| {
+                        let cue = toast.level.cue();
+                        ui.label(
+                            egui::RichText::new(toast.level.icon())
+                                .color(palette.color(cue))
+                                .heading(),
+                        );
+                        ui.vertical(|ui| {
+                            if let Some(title) = &toast.title {
+                                ui.label(egui::RichText::new(title).strong());
+                            }
+                            ui.label(&toast.text);
+                            ui.horizontal(|ui| {
+                                if ui.small_button("Dismiss").clicked() {
+                                    dismissed = Some(index);
+                                }
+                                if let Some((label, id)) = &toast.action {
+                                    if ui.small_button(label).clicked() {
+                                        actions.send(ToastActionEvent { id: id.clone() });
+                                        dismissed = Some(index);
+                                    }
+                                }
+                            });
+                        });
+                    });
+                });
+            }
+        });
+
+    if let Some(index) = dismissed {
+        toasts.queue.remove(index);
+    }
+}
@@ -0,0 +1,97 @@
+//! Station wear: tiles changed during a round stay changed in the next round on the same map.
+
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use maps::{
+    journal::TileJournal,
+    wear::{MapWear, WEAR_FILE_EXTENSION},
+    TileMap, TileMapData,
+};
+use networking::is_server;
+
+use crate::{config::ServerConfig, round::RoundState, storage::Storage};
+
+pub struct WearPlugin;
+
+impl Plugin for WearPlugin {
+    fn build(&self, app: &mut App) {
+        if is_server(app) {
+            app.init_resource::<StationWear>()
+                // Map data is inserted during update, so this runs before it's spawned
+                .add_systems(PreUpdate, apply_map_wear.run_if(wear_enabled))
+                .add_systems(
+                    OnEnter(RoundState::Ending),
+                    save_map_wear.run_if(wear_enabled),
+                );
+        }
+    }
+}
+
+pub(crate) const WEAR_DIRECTORY: &str = "data/map-wear";
+
+/// Wear of the loaded map, including tiles worn in earlier rounds
+#[derive(Resource, Default)]
+struct StationWear(Option<MapWear>);
+
+fn wear_enabled(config: Res<ServerConfig>) -> bool {
+    config.map_wear
+}
+
+fn wear_path(map_name: &str) -> PathBuf {
+    PathBuf::from(format!(
+        "{}/{}.{}",
+        WEAR_DIRECTORY, map_name, WEAR_FILE_EXTENSION
+    ))
+}
+
+/// Replaces worn tiles in the map data before the tilemap is spawned
+fn apply_map_wear(
+    mut maps: Query<&mut TileMapData, Added<TileMapData>>,
+    journal: Res<TileJournal>,
+    storage: Res<Storage>,
+    mut wear: ResMut<StationWear>,
+) {
+    let Some(map_name) = journal.base_map.as_deref() else {
+        return;
+    };
+
+    for mut data in maps.iter_mut() {
+        let path = wear_path(map_name);
+        let loaded = match storage.load_checked::<MapWear>(&path) {
+            Some(loaded) if loaded.base_map == map_name && loaded.size == data.size => Some(loaded),
+            Some(loaded) => {
+                warn!(path = ?path, base_map = ?loaded.base_map, "Map wear doesn't fit the loaded map");
+                None
+            }
+            None => None,
+        };
+
+        if let Some(loaded) = loaded.as_ref() {
+            let applied = loaded.apply(&mut data);
+            info!(path = ?path, tiles = applied, "Applied map wear");
+        }
+        wear.0 = Some(loaded.unwrap_or_else(|| MapWear::new(map_name, data.size)));
+    }
+}
+
+/// Records the tiles changed this round once it's over
+fn save_map_wear(world: &mut World) {
+    let Some(mut wear) = world.resource_mut::<StationWear>().0.take() else {
+        return;
+    };
+    let Some(tilemap) = world
+        .query_filtered::<Entity, With<TileMap>>()
+        .iter(world)
+        .next()
+    else {
+        return;
+    };
+
+    let recorded = wear.record(world, tilemap);
+    let path = wear_path(&wear.base_map);
+    match wear.save(&path) {
+        Ok(()) => info!(path = ?path, recorded, total = wear.len(), "Saved map wear"),
+        Err(err) => error!(path = ?path, error = %err, "Failed to save map wear"),
+    }
+}
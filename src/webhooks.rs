@@ -0,0 +1,278 @@
+//! Outbound webhooks, so community services can follow what happens on the server.
+//!
+//! Every configured endpoint gets a JSON payload for the events it subscribed to.
+//! Failed deliveries are retried with exponential backoff. Deliveries that are given up on
+//! are appended to a dead-letter file instead of being lost.
+
+use std::{fs::OpenOptions, io::Write, panic, path::Path, sync::Arc, time::Duration};
+
+use async_compat::Compat;
+use bevy::{prelude::*, tasks::IoTaskPool};
+use futures_lite::future;
+use maps::journal::TileJournal;
+use networking::{bans::unix_now, is_server, Players, ServerEvent};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    admin::StaffRole,
+    config::{ServerConfig, WebhookConfig},
+    round::RoundState,
+};
+
+pub struct WebhookPlugin;
+
+impl Plugin for WebhookPlugin {
+    fn build(&self, app: &mut App) {
+        if !is_server(app) {
+            return;
+        }
+        let Some(config) = app.world.get_resource::<ServerConfig>() else {
+            return;
+        };
+        if config.webhooks.endpoints.is_empty() {
+            return;
+        }
+
+        let webhooks = Webhooks {
+            client: reqwest::Client::new(),
+            config: Arc::new(config.webhooks.clone()),
+        };
+        // Crashes are reported before the server goes down, there is no time to retry
+        let crash_webhooks = webhooks.clone();
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            default_hook(info);
+            crash_webhooks.send_now(&WebhookEvent::ServerError {
+                message: info.to_string(),
+            });
+        }));
+
+        app.insert_resource(webhooks).add_systems(
+            Update,
+            (
+                send_round_events.run_if(state_changed::<RoundState>()),
+                send_staff_logins,
+                send_player_milestones,
+            ),
+        );
+    }
+}
+
+/// Maximum time a single delivery may take
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEventKind {
+    RoundStarted,
+    RoundEnded,
+    StaffLogin,
+    PlayerMilestone,
+    ServerError,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum WebhookEvent {
+    RoundStarted {
+        map: Option<String>,
+    },
+    RoundEnded {
+        duration_minutes: u64,
+    },
+    StaffLogin {
+        username: String,
+        role: StaffRole,
+    },
+    /// The number of players online reached a configured milestone
+    PlayerMilestone {
+        players: usize,
+    },
+    /// The server crashed
+    ServerError {
+        message: String,
+    },
+}
+
+impl WebhookEvent {
+    fn kind(&self) -> WebhookEventKind {
+        match self {
+            Self::RoundStarted { .. } => WebhookEventKind::RoundStarted,
+            Self::RoundEnded { .. } => WebhookEventKind::RoundEnded,
+            Self::StaffLogin { .. } => WebhookEventKind::StaffLogin,
+            Self::PlayerMilestone { .. } => WebhookEventKind::PlayerMilestone,
+            Self::ServerError { .. } => WebhookEventKind::ServerError,
+        }
+    }
+
+    fn payload(&self) -> Value {
+        let mut payload = serde_json::to_value(self).expect("Unable to serialize webhook event");
+        payload["timestamp"] = unix_now().into();
+        payload
+    }
+}
+
+#[derive(Resource, Clone)]
+struct Webhooks {
+    client: reqwest::Client,
+    config: Arc<WebhookConfig>,
+}
+
+impl Webhooks {
+    fn urls(&self, kind: WebhookEventKind) -> impl Iterator<Item = &str> {
+        self.config
+            .endpoints
+            .iter()
+            .filter(move |endpoint| endpoint.events.is_empty() || endpoint.events.contains(&kind))
+            .map(|endpoint| endpoint.url.as_str())
+    }
+
+    /// Delivers the event in the background, retrying if it fails
+    fn send(&self, event: &WebhookEvent) {
+        let payload = event.payload();
+        for url in self.urls(event.kind()) {
+            let delivery = deliver(
+                self.client.clone(),
+                self.config.clone(),
+                url.to_owned(),
+                payload.clone(),
+            );
+            IoTaskPool::get().spawn(Compat::new(delivery)).detach();
+        }
+    }
+
+    /// Delivers the event before returning, trying only once
+    fn send_now(&self, event: &WebhookEvent) {
+        let payload = event.payload();
+        for url in self.urls(event.kind()) {
+            let result = future::block_on(Compat::new(post(&self.client, url, &payload)));
+            if let Err(err) = result {
+                write_dead_letter(&self.config.dead_letter_file, url, &payload, &err);
+            }
+        }
+    }
+}
+
+async fn post(client: &reqwest::Client, url: &str, payload: &Value) -> reqwest::Result<()> {
+    client
+        .post(url)
+        .json(payload)
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+async fn deliver(client: reqwest::Client, config: Arc<WebhookConfig>, url: String, payload: Value) {
+    let mut delay = config.retry_delay();
+    let mut attempt = 1;
+    loop {
+        let err = match post(&client, &url, &payload).await {
+            Ok(()) => return,
+            Err(err) => err,
+        };
+        if attempt >= config.max_attempts {
+            error!(url, attempts = attempt, error = %err, "Giving up on webhook delivery");
+            write_dead_letter(&config.dead_letter_file, &url, &payload, &err);
+            return;
+        }
+        warn!(url, attempt, error = %err, "Webhook delivery failed, retrying");
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+        attempt += 1;
+    }
+}
+
+#[derive(Serialize)]
+struct DeadLetter<'a> {
+    timestamp: u64,
+    url: &'a str,
+    error: String,
+    payload: &'a Value,
+}
+
+/// Appends an undeliverable event to the dead-letter file, one JSON object per line
+fn write_dead_letter(path: &Path, url: &str, payload: &Value, err: &reqwest::Error) {
+    let letter = DeadLetter {
+        timestamp: unix_now(),
+        url,
+        error: err.to_string(),
+        payload,
+    };
+    let line = serde_json::to_string(&letter).expect("Unable to serialize dead letter");
+    let result = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| writeln!(file, "{}", line));
+    if let Err(err) = result {
+        error!(path = ?path, error = %err, "Failed to write webhook dead letter");
+    }
+}
+
+fn send_round_events(
+    state: Res<State<RoundState>>,
+    journal: Res<TileJournal>,
+    webhooks: Res<Webhooks>,
+    mut started_at: Local<Option<u64>>,
+) {
+    match state.get() {
+        RoundState::InProgress => {
+            *started_at = Some(unix_now());
+            webhooks.send(&WebhookEvent::RoundStarted {
+                map: journal.base_map.clone(),
+            });
+        }
+        RoundState::Ending => {
+            let duration = started_at
+                .take()
+                .map_or(0, |start| unix_now().saturating_sub(start));
+            webhooks.send(&WebhookEvent::RoundEnded {
+                duration_minutes: duration / 60,
+            });
+        }
+        _ => {}
+    }
+}
+
+fn send_staff_logins(
+    mut server_events: EventReader<ServerEvent>,
+    players: Res<Players>,
+    config: Res<ServerConfig>,
+    webhooks: Res<Webhooks>,
+) {
+    for event in server_events.iter() {
+        let ServerEvent::PlayerConnected(connection) = event else {
+            continue;
+        };
+        let Some(player) = players.get(*connection) else {
+            continue;
+        };
+        if let Some(role) = config.staff_role(player.id) {
+            webhooks.send(&WebhookEvent::StaffLogin {
+                username: player.username.clone(),
+                role,
+            });
+        }
+    }
+}
+
+/// Announces when the number of online players goes up to a milestone
+fn send_player_milestones(
+    players: Res<Players>,
+    webhooks: Res<Webhooks>,
+    mut last_count: Local<usize>,
+) {
+    if !players.is_changed() {
+        return;
+    }
+    let count = players.players().len();
+    for &milestone in webhooks.config.player_milestones.iter() {
+        if *last_count < milestone && milestone <= count {
+            webhooks.send(&WebhookEvent::PlayerMilestone { players: milestone });
+        }
+    }
+    *last_count = count;
+}